@@ -23,6 +23,9 @@ pub struct ApiConfig {
     pub middleware: MiddlewareConfig,
     /// 指标配置
     pub metrics: MetricsConfig,
+    /// PostgreSQL线协议配置
+    #[serde(default)]
+    pub postgres: PostgresConfig,
 }
 
 impl Default for ApiConfig {
@@ -36,6 +39,7 @@ impl Default for ApiConfig {
             auth: AuthConfig::default(),
             middleware: MiddlewareConfig::default(),
             metrics: MetricsConfig::default(),
+            postgres: PostgresConfig::default(),
         }
     }
 }
@@ -326,6 +330,30 @@ impl Default for MetricsConfig {
     }
 }
 
+/// PostgreSQL线协议配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostgresConfig {
+    /// 是否启用PostgreSQL线协议服务
+    pub enabled: bool,
+    /// 监听端口
+    pub port: u16,
+    /// 对客户端报告的服务端版本（`server_version`与`version()`）
+    pub server_version: String,
+    /// 单条前端消息的最大字节数
+    pub max_message_size: usize,
+}
+
+impl Default for PostgresConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            port: crate::DEFAULT_POSTGRES_PORT,
+            server_version: "14.0".to_string(),
+            max_message_size: 64 * 1024 * 1024, // 64MB
+        }
+    }
+}
+
 impl ApiConfig {
     /// 从文件加载配置
     pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
@@ -355,6 +383,10 @@ impl ApiConfig {
             return Err("max_body_size must be greater than 0".to_string());
         }
         
+        if self.postgres.enabled && self.postgres.max_message_size == 0 {
+            return Err("postgres.max_message_size must be greater than 0".to_string());
+        }
+        
         Ok(())
     }
 }
@@ -369,6 +401,7 @@ mod tests {
         assert!(config.validate().is_ok());
         assert_eq!(config.rest.port, crate::DEFAULT_REST_PORT);
         assert_eq!(config.grpc.port, crate::DEFAULT_GRPC_PORT);
+        assert_eq!(config.postgres.port, crate::DEFAULT_POSTGRES_PORT);
     }
 
     #[test]
@@ -232,6 +232,7 @@ impl From<fdc_core::error::Error> for ApiError {
                 ApiError::conflict(format!("Resource already exists: {}", resource))
             }
             fdc_core::error::Error::Timeout { .. } => ApiError::Timeout,
+            fdc_core::error::Error::Cancelled { reason } => ApiError::query(format!("Query cancelled: {}", reason)),
             _ => ApiError::internal(err.to_string()),
         }
     }
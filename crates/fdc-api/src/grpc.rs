@@ -1,16 +1,46 @@
 //! gRPC API implementation
 
-use crate::{config::GrpcConfig, errors::ApiResult};
+use crate::{
+    config::GrpcConfig,
+    errors::{ApiError, ApiResult},
+    models::{QueryRequest, StreamFrame},
+};
+use fdc_query::QueryEngine;
+use futures::{Stream, StreamExt};
+use std::sync::Arc;
 
 /// gRPC服务器
 pub struct GrpcServer {
     config: GrpcConfig,
+    query_engine: Option<Arc<QueryEngine>>,
 }
 
 impl GrpcServer {
     /// 创建新的gRPC服务器
     pub fn new(config: GrpcConfig) -> Self {
-        Self { config }
+        Self { config, query_engine: None }
+    }
+    
+    /// 设置处理查询请求的查询引擎
+    pub fn with_query_engine(mut self, engine: Arc<QueryEngine>) -> Self {
+        self.query_engine = Some(engine);
+        self
+    }
+    
+    /// 服务端流式查询：返回的流可直接作为server-streaming RPC的响应体
+    ///
+    /// 查询错误以`Status`结束流；tonic按客户端的接收窗口拉取，慢客户端会让查询扫描随之暂停。
+    pub async fn stream_query(
+        &self,
+        request: QueryRequest,
+    ) -> Result<impl Stream<Item = Result<StreamFrame, tonic::Status>> + Send + 'static, tonic::Status> {
+        let engine = self.query_engine.clone()
+            .ok_or_else(|| tonic::Status::unavailable("Query engine is not available"))?;
+        let frames = crate::streaming::query_frames(engine, request).await.map_err(to_status)?;
+        Ok(frames.map(|frame| match frame {
+            StreamFrame::Error { message } => Err(tonic::Status::internal(message)),
+            frame => Ok(frame),
+        }))
     }
     
     /// 启动gRPC服务器
@@ -21,6 +51,17 @@ impl GrpcServer {
     }
 }
 
+/// 把API错误映射为gRPC状态
+fn to_status(error: ApiError) -> tonic::Status {
+    match error {
+        ApiError::Validation { message } => tonic::Status::invalid_argument(message),
+        ApiError::NotFound { resource } => tonic::Status::not_found(resource),
+        ApiError::Timeout => tonic::Status::deadline_exceeded("query timed out"),
+        ApiError::ServiceUnavailable => tonic::Status::unavailable("service unavailable"),
+        other => tonic::Status::internal(other.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -21,6 +21,7 @@ impl QueryHandler {
                 cpu_time_us: 0,
             },
             plan: None,
+            profile: None,
         };
         
         Ok(response)
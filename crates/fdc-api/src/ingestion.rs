@@ -0,0 +1,113 @@
+//! Bridges ingestion batch commits into the query engine
+
+use async_trait::async_trait;
+use fdc_core::error::Result;
+use fdc_ingestion::{BatchCommit, WriteListener};
+use fdc_query::QueryEngine;
+use std::sync::Arc;
+
+/// 把接入批次的提交交给查询引擎
+///
+/// 挂到`BatchProcessor::with_write_listener`后，每个批次提交都会使依赖写入表的缓存结果失效，
+/// 并增量维护以这些表为源的物化视图与连续查询。
+pub struct QueryEngineListener {
+    engine: Arc<QueryEngine>,
+}
+
+impl QueryEngineListener {
+    /// 创建新的监听器
+    pub fn new(engine: Arc<QueryEngine>) -> Self {
+        Self { engine }
+    }
+}
+
+#[async_trait]
+impl WriteListener for QueryEngineListener {
+    async fn batch_committed(&self, commit: &BatchCommit) -> Result<()> {
+        for table in &commit.tables {
+            // 没有结构化行的写入仍按整表失效
+            let rows = commit.rows.get(table).map(Vec::as_slice).unwrap_or_default();
+            self.engine.apply_ingested_rows(table, rows).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_core::types::Value;
+    use fdc_ingestion::{batch::{BatchItem, SimpleStorage}, BatchConfig, BatchProcessor, ParsedData, ValidationResult};
+    use fdc_query::{ColumnDefinition, ColumnType, QueryEngineConfig, TableDefinition};
+    use fdc_storage::{engine::StorageEngine, engines::memory::MemoryEngine};
+    use std::collections::HashMap;
+
+    struct NullStorage;
+
+    #[async_trait]
+    impl SimpleStorage for NullStorage {
+        async fn put(&self, _key: &[u8], _value: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trade(id: i64, symbol: &str, qty: i64) -> HashMap<String, Value> {
+        HashMap::from([
+            ("id".to_string(), Value::Int64(id)),
+            ("symbol".to_string(), Value::String(symbol.to_string())),
+            ("qty".to_string(), Value::Int64(qty)),
+        ])
+    }
+
+    async fn engine() -> (Arc<QueryEngine>, Arc<dyn StorageEngine>) {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = Arc::new(QueryEngine::new(storage.clone(), QueryEngineConfig::default()));
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("qty", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &[trade(1, "AAPL", 100)]).await.unwrap();
+        (engine, storage)
+    }
+
+    async fn ingest(engine: &Arc<QueryEngine>, rows: Vec<HashMap<String, Value>>) {
+        let processor = BatchProcessor::new(BatchConfig::default(), Arc::new(NullStorage))
+            .with_write_listener(Arc::new(QueryEngineListener::new(engine.clone())));
+        for row in rows {
+            let mut parsed = ParsedData::new(Value::Struct(row), "trade".to_string(), 0, 0);
+            parsed.metadata.insert("table".to_string(), "trades".to_string());
+            processor.add_item(BatchItem::new(parsed, ValidationResult::success(0))).await.unwrap();
+        }
+        processor.flush().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ingested_batch_evicts_cached_result() {
+        let (engine, _storage) = engine().await;
+        let sql = "SELECT symbol FROM trades";
+        engine.execute_sql(sql).await.unwrap();
+        engine.execute_sql(sql).await.unwrap();
+        assert_eq!(engine.get_cache_stats().await.unwrap().hits, 1);
+
+        ingest(&engine, vec![trade(2, "MSFT", 10)]).await;
+        assert_eq!(engine.get_cache_stats().await.unwrap().invalidations, 1);
+        engine.execute_sql(sql).await.unwrap();
+        assert_eq!(engine.get_cache_stats().await.unwrap().hits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_ingested_batch_maintains_incremental_view() {
+        let (engine, _storage) = engine().await;
+        engine.execute_sql(
+            "CREATE MATERIALIZED VIEW volume_by_symbol WITH (refresh = 'incremental') AS SELECT symbol, SUM(qty) AS volume FROM trades GROUP BY symbol"
+        ).await.unwrap();
+        let sql = "SELECT volume FROM volume_by_symbol WHERE symbol = 'AAPL'";
+        assert_eq!(engine.execute_sql(sql).await.unwrap().rows[0]["volume"], Value::Float64(100.0));
+        let mut deltas = engine.subscribe("volume_by_symbol").unwrap();
+
+        ingest(&engine, vec![trade(2, "AAPL", 50), trade(3, "MSFT", 10)]).await;
+        assert_eq!(engine.execute_sql(sql).await.unwrap().rows[0]["volume"], Value::Float64(150.0));
+        assert_eq!(deltas.try_recv().unwrap().upserted.len(), 2);
+    }
+}
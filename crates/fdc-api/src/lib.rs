@@ -15,12 +15,18 @@ pub mod handlers;       // 请求处理器
 pub mod models;         // API数据模型
 pub mod errors;         // API错误处理
 pub mod metrics;        // API指标
+pub mod streaming;      // 流式查询结果
+pub mod postgres;       // PostgreSQL线协议
+pub mod pg_catalog;     // pg_catalog与information_schema兼容层
+pub mod ingestion;      // 数据接入与查询引擎的衔接
 
 // 重新导出常用类型
 pub use server::{ApiServer, ServerConfig};
 pub use config::ApiConfig;
 pub use errors::{ApiError, ApiResult};
-pub use models::{ApiResponse, QueryRequest, QueryResponse};
+pub use models::{ApiResponse, QueryRequest, QueryResponse, StreamFrame};
+pub use postgres::PostgresServer;
+pub use ingestion::QueryEngineListener;
 
 /// 库版本信息
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -34,6 +40,9 @@ pub const DEFAULT_REST_PORT: u16 = 8080;
 /// 默认gRPC端口
 pub const DEFAULT_GRPC_PORT: u16 = 9090;
 
+/// 默认PostgreSQL线协议端口
+pub const DEFAULT_POSTGRES_PORT: u16 = 5432;
+
 /// 默认GraphQL端点
 pub const DEFAULT_GRAPHQL_ENDPOINT: &str = "/graphql";
 
@@ -50,6 +59,7 @@ mod tests {
         assert_eq!(NAME, "fdc-api");
         assert_eq!(DEFAULT_REST_PORT, 8080);
         assert_eq!(DEFAULT_GRPC_PORT, 9090);
+        assert_eq!(DEFAULT_POSTGRES_PORT, 5432);
         assert_eq!(DEFAULT_GRAPHQL_ENDPOINT, "/graphql");
         assert_eq!(DEFAULT_WEBSOCKET_ENDPOINT, "/ws");
     }
@@ -1,5 +1,6 @@
 //! API data models
 
+use fdc_core::types::Value;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -121,6 +122,24 @@ pub struct QueryRequest {
     pub options: Option<QueryOptions>,
 }
 
+impl QueryRequest {
+    /// 按请求的参数与选项构造执行上下文
+    pub fn execution_context(&self) -> fdc_query::ExecutionContext {
+        let options = self.options.clone().unwrap_or_default();
+        let mut context = fdc_query::ExecutionContext::new(Uuid::new_v4().to_string());
+        if let Some(timeout) = options.timeout {
+            context = context.with_timeout(std::time::Duration::from_secs(timeout));
+        }
+        if let Some(limit) = options.limit {
+            context = context.with_max_rows(limit as usize);
+        }
+        for (name, value) in self.parameters.iter().flatten() {
+            context = context.with_parameter(name.clone(), json_to_value(value));
+        }
+        context
+    }
+}
+
 /// 查询选项
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryOptions {
@@ -156,6 +175,9 @@ pub struct QueryResponse {
     pub stats: QueryStats,
     /// 查询计划（如果请求）
     pub plan: Option<String>,
+    /// 各算子的实际运行指标（EXPLAIN ANALYZE的JSON输出）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<serde_json::Value>,
 }
 
 /// 列信息
@@ -186,6 +208,54 @@ pub struct QueryStats {
     pub cpu_time_us: u64,
 }
 
+/// 流式查询的输出帧（NDJSON的一行、WebSocket的一条消息或gRPC流的一个元素）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamFrame {
+    /// 一批结果行
+    Batch {
+        /// 批次序号
+        sequence: u64,
+        /// 本批的行
+        rows: Vec<HashMap<String, serde_json::Value>>,
+    },
+    /// 结果结束
+    End {
+        /// 返回的总行数
+        rows_returned: u64,
+        /// 执行时间（毫秒）
+        execution_time_ms: u64,
+    },
+    /// 查询失败，流随之结束
+    Error {
+        /// 错误信息
+        message: String,
+    },
+}
+
+/// 游标声明响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CursorResponse {
+    /// 服务端游标名，后续翻页使用
+    pub cursor: String,
+}
+
+/// 游标翻页参数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FetchParams {
+    /// 本页行数，省略时读取一页默认大小
+    pub count: Option<usize>,
+}
+
+/// 游标翻页响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchResponse {
+    /// 本页的行
+    pub results: Vec<HashMap<String, serde_json::Value>>,
+    /// 游标是否已读完
+    pub done: bool,
+}
+
 /// 数据插入请求
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InsertRequest {
@@ -297,6 +367,101 @@ impl ComponentStatus {
     }
 }
 
+/// 把查询引擎的值转换为普通JSON值
+pub fn value_to_json(value: &Value) -> serde_json::Value {
+    use serde_json::Value as Json;
+    
+    let float = |f: f64| serde_json::Number::from_f64(f).map(Json::Number).unwrap_or(Json::Null);
+    match value {
+        Value::Null => Json::Null,
+        Value::Bool(b) => Json::Bool(*b),
+        Value::Int8(i) => Json::from(*i),
+        Value::Int16(i) => Json::from(*i),
+        Value::Int32(i) => Json::from(*i),
+        Value::Int64(i) => Json::from(*i),
+        Value::UInt8(i) => Json::from(*i),
+        Value::UInt16(i) => Json::from(*i),
+        Value::UInt32(i) => Json::from(*i),
+        Value::UInt64(i) => Json::from(*i),
+        // 超出JSON整数范围的128位整数以字符串表示
+        Value::Int128(i) => Json::String(i.to_string()),
+        Value::UInt128(i) => Json::String(i.to_string()),
+        Value::Float32(f) => float(*f as f64),
+        Value::Float64(f) => float(*f),
+        Value::Decimal(d) => Json::String(d.to_string()),
+        Value::Price(p) => Json::String(p.as_decimal().to_string()),
+        Value::Volume(v) => Json::from(v.as_u64()),
+        Value::String(s) => Json::String(s.clone()),
+        Value::Symbol(s) => Json::String(s.as_str().to_string()),
+        Value::ExchangeId(id) => Json::from(id.as_u16()),
+        Value::Timestamp(ts) => match ts.to_datetime() {
+            Some(datetime) => Json::String(datetime.to_rfc3339()),
+            None => Json::from(ts.as_nanos()),
+        },
+        Value::Array(items) | Value::List(items) => Json::Array(items.iter().map(value_to_json).collect()),
+        Value::Struct(fields) | Value::Map(fields) => Json::Object(
+            fields.iter().map(|(k, v)| (k.clone(), value_to_json(v))).collect(),
+        ),
+        Value::Binary(_) | Value::Custom(_) => serde_json::to_value(value).unwrap_or(Json::Null),
+    }
+}
+
+/// 把结果行转换为JSON对象
+pub fn row_to_json(row: &HashMap<String, Value>) -> HashMap<String, serde_json::Value> {
+    row.iter().map(|(k, v)| (k.clone(), value_to_json(v))).collect()
+}
+
+/// 把请求中的JSON参数转换为查询引擎的值
+pub fn json_to_value(json: &serde_json::Value) -> Value {
+    use serde_json::Value as Json;
+    
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Bool(*b),
+        Json::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => Value::Int64(i),
+            (None, Some(u)) => Value::UInt64(u),
+            _ => Value::Float64(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(s) => Value::String(s.clone()),
+        Json::Array(items) => Value::List(items.iter().map(json_to_value).collect()),
+        Json::Object(fields) => Value::Map(fields.iter().map(|(k, v)| (k.clone(), json_to_value(v))).collect()),
+    }
+}
+
+/// 值的类型名（用于列信息）
+pub fn value_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Int8(_) => "int8",
+        Value::Int16(_) => "int16",
+        Value::Int32(_) => "int32",
+        Value::Int64(_) => "int64",
+        Value::Int128(_) => "int128",
+        Value::UInt8(_) => "uint8",
+        Value::UInt16(_) => "uint16",
+        Value::UInt32(_) => "uint32",
+        Value::UInt64(_) => "uint64",
+        Value::UInt128(_) => "uint128",
+        Value::Float32(_) => "float32",
+        Value::Float64(_) => "float64",
+        Value::Decimal(_) => "decimal",
+        Value::String(_) => "string",
+        Value::Binary(_) => "binary",
+        Value::Timestamp(_) => "timestamp",
+        Value::Array(_) => "array",
+        Value::List(_) => "list",
+        Value::Struct(_) => "struct",
+        Value::Map(_) => "map",
+        Value::Price(_) => "price",
+        Value::Volume(_) => "volume",
+        Value::Symbol(_) => "symbol",
+        Value::ExchangeId(_) => "exchange_id",
+        Value::Custom(_) => "custom",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,455 @@
+//! PostgreSQL catalog compatibility shim
+//!
+//! Drivers and BI tools probe `pg_catalog` and `information_schema` right after
+//! connecting (type lookups, table browsers, column metadata). This module maps
+//! the query engine's table catalog onto those relations as system tables,
+//! registers the handful of catalog functions they call, and owns the mapping
+//! between engine types and PostgreSQL type OIDs used by the wire protocol.
+
+use fdc_core::{error::Result, types::Value};
+use fdc_query::{
+    Catalog, ColumnDefinition, ColumnType, QueryEngine, RustScalarFunction, RustSystemTable, TableDefinition, UdfSignature,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 对外报告的数据库名
+pub const DATABASE_NAME: &str = "fdc";
+
+/// 对外报告的默认模式
+pub const DEFAULT_SCHEMA: &str = "public";
+
+/// 对外报告的对象属主
+pub const OWNER_NAME: &str = "fdc";
+
+/// `pg_catalog`模式的OID
+pub const PG_CATALOG_NAMESPACE: i64 = 11;
+
+/// `public`模式的OID
+pub const PUBLIC_NAMESPACE: i64 = 2200;
+
+/// `information_schema`模式的OID
+pub const INFORMATION_SCHEMA_NAMESPACE: i64 = 13000;
+
+/// 用户表OID的起点（与PostgreSQL的FirstNormalObjectId一致）
+const FIRST_TABLE_OID: i64 = 16384;
+
+/// PostgreSQL类型OID
+pub mod oid {
+    pub const BOOL: u32 = 16;
+    pub const BYTEA: u32 = 17;
+    pub const NAME: u32 = 19;
+    pub const INT8: u32 = 20;
+    pub const INT2: u32 = 21;
+    pub const INT4: u32 = 23;
+    pub const TEXT: u32 = 25;
+    pub const OID: u32 = 26;
+    pub const JSON: u32 = 114;
+    pub const FLOAT4: u32 = 700;
+    pub const FLOAT8: u32 = 701;
+    pub const UNKNOWN: u32 = 705;
+    pub const VARCHAR: u32 = 1043;
+    pub const TIMESTAMP: u32 = 1114;
+    pub const TIMESTAMPTZ: u32 = 1184;
+    pub const NUMERIC: u32 = 1700;
+}
+
+/// `pg_type`中的一种类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PgType {
+    /// 类型OID
+    pub oid: u32,
+    /// 类型名（`pg_type.typname`）
+    pub name: &'static str,
+    /// SQL标准名（`information_schema.columns.data_type`）
+    pub sql_name: &'static str,
+    /// 定长类型的字节数，变长为-1
+    pub len: i16,
+    /// 类型分类（`pg_type.typcategory`）
+    pub category: &'static str,
+}
+
+/// 线协议会用到的全部类型
+pub const TYPES: &[PgType] = &[
+    PgType { oid: oid::BOOL, name: "bool", sql_name: "boolean", len: 1, category: "B" },
+    PgType { oid: oid::BYTEA, name: "bytea", sql_name: "bytea", len: -1, category: "U" },
+    PgType { oid: oid::NAME, name: "name", sql_name: "name", len: 64, category: "S" },
+    PgType { oid: oid::INT8, name: "int8", sql_name: "bigint", len: 8, category: "N" },
+    PgType { oid: oid::INT2, name: "int2", sql_name: "smallint", len: 2, category: "N" },
+    PgType { oid: oid::INT4, name: "int4", sql_name: "integer", len: 4, category: "N" },
+    PgType { oid: oid::TEXT, name: "text", sql_name: "text", len: -1, category: "S" },
+    PgType { oid: oid::OID, name: "oid", sql_name: "oid", len: 4, category: "N" },
+    PgType { oid: oid::JSON, name: "json", sql_name: "json", len: -1, category: "U" },
+    PgType { oid: oid::FLOAT4, name: "float4", sql_name: "real", len: 4, category: "N" },
+    PgType { oid: oid::FLOAT8, name: "float8", sql_name: "double precision", len: 8, category: "N" },
+    PgType { oid: oid::UNKNOWN, name: "unknown", sql_name: "unknown", len: -2, category: "X" },
+    PgType { oid: oid::VARCHAR, name: "varchar", sql_name: "character varying", len: -1, category: "S" },
+    PgType { oid: oid::TIMESTAMP, name: "timestamp", sql_name: "timestamp without time zone", len: 8, category: "D" },
+    PgType { oid: oid::TIMESTAMPTZ, name: "timestamptz", sql_name: "timestamp with time zone", len: 8, category: "D" },
+    PgType { oid: oid::NUMERIC, name: "numeric", sql_name: "numeric", len: -1, category: "N" },
+];
+
+/// 按OID查找类型
+pub fn pg_type(type_oid: u32) -> Option<&'static PgType> {
+    TYPES.iter().find(|t| t.oid == type_oid)
+}
+
+/// 表列类型对应的PostgreSQL类型OID
+pub fn column_type_oid(column_type: ColumnType) -> u32 {
+    match column_type {
+        ColumnType::Boolean => oid::BOOL,
+        ColumnType::Int64 | ColumnType::Volume => oid::INT8,
+        ColumnType::Float64 => oid::FLOAT8,
+        ColumnType::Decimal | ColumnType::Price => oid::NUMERIC,
+        ColumnType::String | ColumnType::Symbol => oid::TEXT,
+        ColumnType::Binary => oid::BYTEA,
+        ColumnType::Timestamp => oid::TIMESTAMPTZ,
+    }
+}
+
+/// 值对应的PostgreSQL类型OID（列类型未知时按首个非空值推断）
+pub fn value_type_oid(value: &Value) -> u32 {
+    match value {
+        Value::Bool(_) => oid::BOOL,
+        Value::Int8(_) | Value::Int16(_) | Value::UInt8(_) => oid::INT2,
+        Value::Int32(_) | Value::UInt16(_) | Value::ExchangeId(_) => oid::INT4,
+        Value::Int64(_) | Value::UInt32(_) | Value::Volume(_) => oid::INT8,
+        // 超出int8范围的整数以numeric表示
+        Value::UInt64(_) | Value::Int128(_) | Value::UInt128(_) => oid::NUMERIC,
+        Value::Float32(_) => oid::FLOAT4,
+        Value::Float64(_) => oid::FLOAT8,
+        Value::Decimal(_) | Value::Price(_) => oid::NUMERIC,
+        Value::Null | Value::String(_) | Value::Symbol(_) => oid::TEXT,
+        Value::Binary(_) => oid::BYTEA,
+        Value::Timestamp(_) => oid::TIMESTAMPTZ,
+        Value::Array(_) | Value::List(_) | Value::Struct(_) | Value::Map(_) | Value::Custom(_) => oid::JSON,
+    }
+}
+
+/// PostgreSQL类型OID对应的表列类型（用于参数绑定）
+pub fn oid_column_type(type_oid: u32) -> Option<ColumnType> {
+    Some(match type_oid {
+        oid::BOOL => ColumnType::Boolean,
+        oid::INT2 | oid::INT4 | oid::INT8 | oid::OID => ColumnType::Int64,
+        oid::FLOAT4 | oid::FLOAT8 => ColumnType::Float64,
+        oid::NUMERIC => ColumnType::Decimal,
+        oid::TEXT | oid::VARCHAR | oid::NAME => ColumnType::String,
+        oid::BYTEA => ColumnType::Binary,
+        oid::TIMESTAMP | oid::TIMESTAMPTZ => ColumnType::Timestamp,
+        _ => return None,
+    })
+}
+
+/// 在查询引擎上安装`pg_catalog`与`information_schema`兼容层
+///
+/// 重复安装是幂等的：已存在的系统表保留，函数被替换。
+pub fn install(engine: &QueryEngine, server_version: &str) -> Result<()> {
+    let catalog = engine.catalog().clone();
+    let system_tables = engine.system_tables();
+    let tables = catalog_tables(catalog);
+    for (schema, name, table) in tables {
+        let qualified = format!("{}.{}", schema, name);
+        for alias in [Some(qualified.as_str()), (schema == "pg_catalog").then_some(name)].into_iter().flatten() {
+            if !system_tables.contains(alias) {
+                system_tables.register(alias, table.clone())?;
+            }
+        }
+    }
+    register_functions(engine, server_version)
+}
+
+type SystemTableEntry = (&'static str, &'static str, Arc<RustSystemTable>);
+
+/// 构造全部兼容层系统表：(模式, 表名, 表)
+fn catalog_tables(catalog: Arc<Catalog>) -> Vec<SystemTableEntry> {
+    let definition = |name: &str, columns: &[(&str, ColumnType)]| {
+        TableDefinition::new(name, columns.iter().map(|(column, column_type)| ColumnDefinition::new(*column, *column_type)).collect())
+    };
+    let mut tables: Vec<SystemTableEntry> = Vec::new();
+
+    tables.push(("pg_catalog", "pg_namespace", Arc::new(RustSystemTable::new(
+        definition("pg_namespace", &[("oid", ColumnType::Int64), ("nspname", ColumnType::String), ("nspowner", ColumnType::Int64)]),
+        || Ok(namespaces().into_iter().map(|(oid, name)| row([
+            ("oid", Value::Int64(oid)),
+            ("nspname", text(name)),
+            ("nspowner", Value::Int64(10)),
+        ])).collect()),
+    ))));
+
+    let source = catalog.clone();
+    tables.push(("pg_catalog", "pg_class", Arc::new(RustSystemTable::new(
+        definition("pg_class", &[
+            ("oid", ColumnType::Int64), ("relname", ColumnType::String), ("relnamespace", ColumnType::Int64),
+            ("relkind", ColumnType::String), ("relowner", ColumnType::Int64), ("reltuples", ColumnType::Float64),
+            ("relhasindex", ColumnType::Boolean), ("relpersistence", ColumnType::String), ("relispartition", ColumnType::Boolean),
+        ]),
+        move || Ok(user_tables(&source).map(|(table_oid, table)| {
+            let tuples = source.statistics(&table.name).map(|s| s.row_count as f64).unwrap_or(-1.0);
+            row([
+                ("oid", Value::Int64(table_oid)),
+                ("relname", text(&table.name)),
+                ("relnamespace", Value::Int64(PUBLIC_NAMESPACE)),
+                ("relkind", text("r")),
+                ("relowner", Value::Int64(10)),
+                ("reltuples", Value::Float64(tuples)),
+                ("relhasindex", Value::Bool(table.primary_key.is_some())),
+                ("relpersistence", text("p")),
+                ("relispartition", Value::Bool(false)),
+            ])
+        }).collect()),
+    ))));
+
+    let source = catalog.clone();
+    tables.push(("pg_catalog", "pg_attribute", Arc::new(RustSystemTable::new(
+        definition("pg_attribute", &[
+            ("attrelid", ColumnType::Int64), ("attname", ColumnType::String), ("atttypid", ColumnType::Int64),
+            ("attnum", ColumnType::Int64), ("attlen", ColumnType::Int64), ("atttypmod", ColumnType::Int64),
+            ("attnotnull", ColumnType::Boolean), ("attisdropped", ColumnType::Boolean), ("atthasdef", ColumnType::Boolean),
+        ]),
+        move || Ok(user_tables(&source).flat_map(|(table_oid, table)| {
+            table.columns.into_iter().enumerate().map(move |(index, column)| {
+                let type_oid = column_type_oid(column.column_type);
+                row([
+                    ("attrelid", Value::Int64(table_oid)),
+                    ("attname", text(&column.name)),
+                    ("atttypid", Value::Int64(type_oid as i64)),
+                    ("attnum", Value::Int64(index as i64 + 1)),
+                    ("attlen", Value::Int64(pg_type(type_oid).map_or(-1, |t| t.len as i64))),
+                    ("atttypmod", Value::Int64(-1)),
+                    ("attnotnull", Value::Bool(!column.nullable)),
+                    ("attisdropped", Value::Bool(false)),
+                    ("atthasdef", Value::Bool(false)),
+                ])
+            })
+        }).collect()),
+    ))));
+
+    tables.push(("pg_catalog", "pg_type", Arc::new(RustSystemTable::new(
+        definition("pg_type", &[
+            ("oid", ColumnType::Int64), ("typname", ColumnType::String), ("typnamespace", ColumnType::Int64),
+            ("typlen", ColumnType::Int64), ("typtype", ColumnType::String), ("typcategory", ColumnType::String),
+            ("typbasetype", ColumnType::Int64), ("typelem", ColumnType::Int64), ("typarray", ColumnType::Int64),
+            ("typrelid", ColumnType::Int64), ("typnotnull", ColumnType::Boolean), ("typowner", ColumnType::Int64),
+        ]),
+        || Ok(TYPES.iter().map(|t| row([
+            ("oid", Value::Int64(t.oid as i64)),
+            ("typname", text(t.name)),
+            ("typnamespace", Value::Int64(PG_CATALOG_NAMESPACE)),
+            ("typlen", Value::Int64(t.len as i64)),
+            ("typtype", text(if t.oid == oid::UNKNOWN { "p" } else { "b" })),
+            ("typcategory", text(t.category)),
+            ("typbasetype", Value::Int64(0)),
+            ("typelem", Value::Int64(0)),
+            ("typarray", Value::Int64(0)),
+            ("typrelid", Value::Int64(0)),
+            ("typnotnull", Value::Bool(false)),
+            ("typowner", Value::Int64(10)),
+        ])).collect()),
+    ))));
+
+    tables.push(("pg_catalog", "pg_database", Arc::new(RustSystemTable::new(
+        definition("pg_database", &[
+            ("oid", ColumnType::Int64), ("datname", ColumnType::String), ("datdba", ColumnType::Int64),
+            ("encoding", ColumnType::Int64), ("datcollate", ColumnType::String), ("datctype", ColumnType::String),
+            ("datistemplate", ColumnType::Boolean), ("datallowconn", ColumnType::Boolean),
+        ]),
+        || Ok(vec![row([
+            ("oid", Value::Int64(FIRST_TABLE_OID - 1)),
+            ("datname", text(DATABASE_NAME)),
+            ("datdba", Value::Int64(10)),
+            // 6 = UTF8
+            ("encoding", Value::Int64(6)),
+            ("datcollate", text("C")),
+            ("datctype", text("C")),
+            ("datistemplate", Value::Bool(false)),
+            ("datallowconn", Value::Bool(true)),
+        ])]),
+    ))));
+
+    let source = catalog.clone();
+    tables.push(("pg_catalog", "pg_tables", Arc::new(RustSystemTable::new(
+        definition("pg_tables", &[
+            ("schemaname", ColumnType::String), ("tablename", ColumnType::String),
+            ("tableowner", ColumnType::String), ("hasindexes", ColumnType::Boolean),
+        ]),
+        move || Ok(user_tables(&source).map(|(_, table)| row([
+            ("schemaname", text(DEFAULT_SCHEMA)),
+            ("tablename", text(&table.name)),
+            ("tableowner", text(OWNER_NAME)),
+            ("hasindexes", Value::Bool(table.primary_key.is_some())),
+        ])).collect()),
+    ))));
+
+    tables.push(("information_schema", "schemata", Arc::new(RustSystemTable::new(
+        definition("schemata", &[
+            ("catalog_name", ColumnType::String), ("schema_name", ColumnType::String), ("schema_owner", ColumnType::String),
+        ]),
+        || Ok(namespaces().into_iter().map(|(_, name)| row([
+            ("catalog_name", text(DATABASE_NAME)),
+            ("schema_name", text(name)),
+            ("schema_owner", text(OWNER_NAME)),
+        ])).collect()),
+    ))));
+
+    let source = catalog.clone();
+    tables.push(("information_schema", "tables", Arc::new(RustSystemTable::new(
+        definition("tables", &[
+            ("table_catalog", ColumnType::String), ("table_schema", ColumnType::String),
+            ("table_name", ColumnType::String), ("table_type", ColumnType::String),
+        ]),
+        move || Ok(user_tables(&source).map(|(_, table)| row([
+            ("table_catalog", text(DATABASE_NAME)),
+            ("table_schema", text(DEFAULT_SCHEMA)),
+            ("table_name", text(&table.name)),
+            ("table_type", text("BASE TABLE")),
+        ])).collect()),
+    ))));
+
+    let source = catalog;
+    tables.push(("information_schema", "columns", Arc::new(RustSystemTable::new(
+        definition("columns", &[
+            ("table_catalog", ColumnType::String), ("table_schema", ColumnType::String),
+            ("table_name", ColumnType::String), ("column_name", ColumnType::String),
+            ("ordinal_position", ColumnType::Int64), ("column_default", ColumnType::String),
+            ("is_nullable", ColumnType::String), ("data_type", ColumnType::String), ("udt_name", ColumnType::String),
+        ]),
+        move || Ok(user_tables(&source).flat_map(|(_, table)| {
+            let table_name = table.name;
+            table.columns.into_iter().enumerate().map(move |(index, column)| {
+                let pg = pg_type(column_type_oid(column.column_type));
+                row([
+                    ("table_catalog", text(DATABASE_NAME)),
+                    ("table_schema", text(DEFAULT_SCHEMA)),
+                    ("table_name", text(&table_name)),
+                    ("column_name", text(&column.name)),
+                    ("ordinal_position", Value::Int64(index as i64 + 1)),
+                    ("column_default", Value::Null),
+                    ("is_nullable", text(if column.nullable { "YES" } else { "NO" })),
+                    ("data_type", text(pg.map_or("text", |t| t.sql_name))),
+                    ("udt_name", text(pg.map_or("text", |t| t.name))),
+                ])
+            })
+        }).collect()),
+    ))));
+
+    tables
+}
+
+/// 对外报告的模式：(OID, 名称)
+fn namespaces() -> [(i64, &'static str); 3] {
+    [
+        (PG_CATALOG_NAMESPACE, "pg_catalog"),
+        (PUBLIC_NAMESPACE, DEFAULT_SCHEMA),
+        (INFORMATION_SCHEMA_NAMESPACE, "information_schema"),
+    ]
+}
+
+/// 用户表及其OID（按表名排序分配，`pg_class`与`pg_attribute`一致）
+fn user_tables(catalog: &Catalog) -> impl Iterator<Item = (i64, TableDefinition)> {
+    catalog.list_tables().into_iter().enumerate().map(|(index, table)| (FIRST_TABLE_OID + index as i64, table))
+}
+
+fn row<const N: usize>(values: [(&str, Value); N]) -> HashMap<String, Value> {
+    values.into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+}
+
+fn text(value: &str) -> Value {
+    Value::String(value.to_string())
+}
+
+type CatalogFunction = Arc<dyn Fn(&[Value]) -> Result<Value> + Send + Sync>;
+
+fn catalog_function(function: impl Fn(&[Value]) -> Result<Value> + Send + Sync + 'static) -> CatalogFunction {
+    Arc::new(function)
+}
+
+/// 注册客户端常用的目录函数（带与不带`pg_catalog.`前缀各一份）
+fn register_functions(engine: &QueryEngine, server_version: &str) -> Result<()> {
+    let version = format!("PostgreSQL {} (fdc {})", server_version, crate::VERSION);
+    let functions: Vec<(&str, Vec<ColumnType>, ColumnType, CatalogFunction)> = vec![
+        ("version", vec![], ColumnType::String, catalog_function(move |_| Ok(text(&version)))),
+        ("current_database", vec![], ColumnType::String, catalog_function(|_| Ok(text(DATABASE_NAME)))),
+        ("current_schema", vec![], ColumnType::String, catalog_function(|_| Ok(text(DEFAULT_SCHEMA)))),
+        ("pg_table_is_visible", vec![ColumnType::Int64], ColumnType::Boolean, catalog_function(|_| Ok(Value::Bool(true)))),
+        ("pg_get_userbyid", vec![ColumnType::Int64], ColumnType::String, catalog_function(|_| Ok(text(OWNER_NAME)))),
+        ("obj_description", vec![ColumnType::Int64, ColumnType::String], ColumnType::String, catalog_function(|_| Ok(Value::Null))),
+        ("col_description", vec![ColumnType::Int64, ColumnType::Int64], ColumnType::String, catalog_function(|_| Ok(Value::Null))),
+        ("format_type", vec![ColumnType::Int64, ColumnType::Int64], ColumnType::String, catalog_function(|args| Ok(match &args[0] {
+            Value::Int64(type_oid) => pg_type(*type_oid as u32).map_or(Value::Null, |t| text(t.sql_name)),
+            _ => Value::Null,
+        }))),
+    ];
+    for (name, arg_types, return_type, function) in functions {
+        for qualified in [name.to_string(), format!("pg_catalog.{}", name)] {
+            let function = function.clone();
+            let signature = UdfSignature::new(qualified, arg_types.clone(), return_type);
+            engine.functions().register(Arc::new(RustScalarFunction::new(signature, move |args| function(args))), true)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_storage::engines::memory::MemoryEngine;
+    use fdc_query::QueryEngineConfig;
+
+    async fn engine() -> QueryEngine {
+        let storage = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let engine = QueryEngine::new(Arc::new(storage), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("price", ColumnType::Price),
+            ColumnDefinition::new("ts", ColumnType::Timestamp),
+        ]).with_primary_key("id")).unwrap();
+        install(&engine, "14.0").unwrap();
+        install(&engine, "14.0").unwrap();
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_catalog_tables() {
+        let engine = engine().await;
+
+        let result = engine.execute_sql(
+            "SELECT c.relname, n.nspname FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON c.relnamespace = n.oid",
+        ).await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0]["relname"], text("trades"));
+        assert_eq!(result.rows[0]["nspname"], text("public"));
+
+        let result = engine.execute_sql(
+            "SELECT column_name, data_type, is_nullable, ordinal_position FROM information_schema.columns WHERE table_name = 'trades' ORDER BY ordinal_position",
+        ).await.unwrap();
+        let columns: Vec<_> = result.rows.iter().map(|row| (row["column_name"].clone(), row["data_type"].clone())).collect();
+        assert_eq!(columns, vec![
+            (text("id"), text("bigint")),
+            (text("price"), text("numeric")),
+            (text("ts"), text("timestamp with time zone")),
+        ]);
+        assert_eq!(result.rows[0]["is_nullable"], text("NO"));
+
+        let result = engine.execute_sql("SELECT typname FROM pg_type WHERE oid = 1184").await.unwrap();
+        assert_eq!(result.rows[0]["typname"], text("timestamptz"));
+    }
+
+    #[tokio::test]
+    async fn test_catalog_functions() {
+        let engine = engine().await;
+        let result = engine.execute_sql("SELECT version() AS v, current_database() AS db, pg_catalog.format_type(20, -1) AS t").await.unwrap();
+        assert!(matches!(&result.rows[0]["v"], Value::String(v) if v.starts_with("PostgreSQL 14.0")));
+        assert_eq!(result.rows[0]["db"], text("fdc"));
+        assert_eq!(result.rows[0]["t"], text("bigint"));
+    }
+
+    #[test]
+    fn test_type_mapping() {
+        assert_eq!(value_type_oid(&Value::Int64(1)), oid::INT8);
+        assert_eq!(value_type_oid(&Value::Float64(1.0)), oid::FLOAT8);
+        assert_eq!(value_type_oid(&Value::Null), oid::TEXT);
+        assert_eq!(column_type_oid(ColumnType::Symbol), oid::TEXT);
+        assert_eq!(oid_column_type(oid::INT4), Some(ColumnType::Int64));
+        assert_eq!(oid_column_type(oid::UNKNOWN), None);
+    }
+}
@@ -0,0 +1,1633 @@
+//! PostgreSQL wire protocol server
+//!
+//! Speaks the v3 frontend/backend protocol on top of `QueryEngine`, so psql,
+//! JDBC/ODBC drivers and BI tools can connect without a custom client. Both the
+//! simple query flow and the extended flow (Parse/Bind/Describe/Execute/Sync)
+//! are supported, with text and binary parameter and result formats. Clients
+//! authenticate with a cleartext password checked against the configured API
+//! keys, and a CancelRequest carrying the session's BackendKeyData kills the
+//! query the session is running. SSL and GSSAPI encryption requests are declined.
+
+use crate::{
+    auth::AuthManager,
+    config::{AuthConfig, PostgresConfig},
+    errors::{ApiError, ApiResult},
+    models::value_to_json,
+    pg_catalog::{self, column_type_oid, oid, oid_column_type, value_type_oid},
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use fdc_core::{error::{Error, Result}, types::{TimestampNs, Value}};
+use fdc_query::{
+    parser::{scan_sql, split_statements},
+    transactions::{SERIALIZATION_FAILURE, TRANSACTION_ABORTED},
+    ColumnType, ExecutionContext, IsolationLevel, QueryEngine, TransactionStatus,
+};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{debug, info};
+
+/// 协议版本3.0
+const PROTOCOL_VERSION_3: i32 = 196608;
+
+/// SSLRequest的请求码
+const SSL_REQUEST_CODE: i32 = 80877103;
+
+/// GSSENCRequest的请求码
+const GSSENC_REQUEST_CODE: i32 = 80877104;
+
+/// CancelRequest的请求码
+const CANCEL_REQUEST_CODE: i32 = 80877102;
+
+/// PostgreSQL纪元（2000-01-01）相对Unix纪元的微秒数
+const POSTGRES_EPOCH_MICROS: i64 = 946_684_800_000_000;
+
+/// 待发送数据超过该字节数时提前写出（大结果集不整体缓存在内存中）
+const FLUSH_THRESHOLD: usize = 64 * 1024;
+
+/// 启动后通过ParameterStatus报告、可被SET修改并回报的参数
+const REPORTED_PARAMETERS: &[&str] = &[
+    "application_name",
+    "client_encoding",
+    "DateStyle",
+    "integer_datetimes",
+    "IntervalStyle",
+    "is_superuser",
+    "server_encoding",
+    "server_version",
+    "session_authorization",
+    "standard_conforming_strings",
+    "TimeZone",
+];
+
+/// PostgreSQL线协议服务器
+#[derive(Clone)]
+pub struct PostgresServer {
+    /// 查询引擎
+    engine: Arc<QueryEngine>,
+    /// 配置
+    config: PostgresConfig,
+    /// 启用认证时的认证管理器
+    auth: Option<Arc<AuthManager>>,
+    /// 各会话的取消键
+    keys: Arc<BackendKeys>,
+}
+
+impl PostgresServer {
+    /// 创建服务器，并在引擎上安装`pg_catalog`兼容层
+    pub fn new(engine: Arc<QueryEngine>, config: PostgresConfig) -> ApiResult<Self> {
+        pg_catalog::install(&engine, &config.server_version)?;
+        Ok(Self {
+            engine,
+            config,
+            auth: None,
+            keys: Arc::new(BackendKeys::default()),
+        })
+    }
+
+    /// 设置认证配置；启用时客户端以API密钥作为口令登录
+    pub fn with_auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = auth.enabled.then(|| Arc::new(AuthManager::new(auth)));
+        self
+    }
+
+    /// 监听`host`上的配置端口并处理连接
+    pub async fn start(self, host: &str) -> ApiResult<()> {
+        let addr = format!("{}:{}", host, self.config.port);
+        let listener = TcpListener::bind(&addr).await
+            .map_err(|e| ApiError::internal(format!("Failed to bind to {}: {}", addr, e)))?;
+        info!("PostgreSQL wire protocol listening on {}", addr);
+        self.serve(listener).await
+    }
+
+    /// 在已绑定的监听器上接受连接，每个连接一个任务
+    pub async fn serve(self, listener: TcpListener) -> ApiResult<()> {
+        loop {
+            let (socket, peer) = listener.accept().await
+                .map_err(|e| ApiError::network(format!("Failed to accept connection: {}", e)))?;
+            let _ = socket.set_nodelay(true);
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_connection(socket).await {
+                    debug!("PostgreSQL connection from {} closed: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    /// 处理一个连接直到客户端断开或发送Terminate
+    pub async fn handle_connection<S>(&self, stream: S) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let mut conn = Connection::new(stream, self.config.max_message_size);
+        let Some(startup) = self.startup(&mut conn).await? else {
+            return Ok(());
+        };
+        let user = startup.get("user").cloned().unwrap_or_else(|| pg_catalog::OWNER_NAME.to_string());
+
+        if let Some(auth) = &self.auth {
+            conn.send(b'R', &Writer::new().i32(3).finish());
+            conn.flush().await?;
+            let password = match conn.read_message().await? {
+                Some((b'p', body)) => Reader::new(&body).cstr()?,
+                _ => return Ok(()),
+            };
+            if !auth.validate_api_key(&password) {
+                conn.error("FATAL", "28P01", &format!("password authentication failed for user \"{}\"", user));
+                return conn.flush().await;
+            }
+        }
+
+        let (process_id, secret, running) = self.keys.register();
+        let mut session = Session::new(self, user, process_id, running, &startup);
+        conn.send(b'R', &Writer::new().i32(0).finish());
+        for name in REPORTED_PARAMETERS {
+            let value = session.setting(name).unwrap_or_default();
+            conn.parameter_status(name, &value);
+        }
+        conn.send(b'K', &Writer::new().i32(process_id).i32(secret).finish());
+        conn.ready_for_query(b'I');
+        let result = match conn.flush().await {
+            Ok(()) => session.run(&mut conn).await,
+            Err(e) => Err(e),
+        };
+        // 连接断开时回滚未提交的事务
+        self.engine.end_session(&session.session_id());
+        self.keys.unregister(process_id);
+        result
+    }
+
+    /// 处理启动阶段：拒绝加密请求、响应取消请求，返回启动参数
+    async fn startup<S>(&self, conn: &mut Connection<S>) -> Result<Option<HashMap<String, String>>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        loop {
+            let Some(body) = conn.read_startup().await? else {
+                return Ok(None);
+            };
+            let mut reader = Reader::new(&body);
+            match reader.i32()? {
+                SSL_REQUEST_CODE | GSSENC_REQUEST_CODE => conn.write_raw(b"N").await?,
+                CANCEL_REQUEST_CODE => {
+                    let (process_id, secret) = (reader.i32()?, reader.i32()?);
+                    if let Some(query_id) = self.keys.running_query(process_id, secret) {
+                        // 查询可能恰好已结束
+                        let _ = self.engine.kill_query(&query_id);
+                    }
+                    return Ok(None);
+                }
+                PROTOCOL_VERSION_3 => {
+                    let mut parameters = HashMap::new();
+                    loop {
+                        let name = reader.cstr()?;
+                        if name.is_empty() {
+                            break;
+                        }
+                        parameters.insert(name, reader.cstr()?);
+                    }
+                    return Ok(Some(parameters));
+                }
+                version => {
+                    conn.error("FATAL", "0A000", &format!(
+                        "unsupported frontend protocol {}.{}", version >> 16, version & 0xffff,
+                    ));
+                    conn.flush().await?;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// BackendKeyData登记：(进程号, 密钥)对应会话当前执行的查询
+#[derive(Default)]
+struct BackendKeys {
+    next_process_id: AtomicI32,
+    sessions: Mutex<HashMap<i32, (i32, RunningSlot)>>,
+}
+
+/// 会话当前执行的查询ID
+type RunningSlot = Arc<Mutex<Option<String>>>;
+
+impl BackendKeys {
+    /// 为新会话分配进程号与密钥
+    fn register(&self) -> (i32, i32, RunningSlot) {
+        let process_id = self.next_process_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let secret = uuid::Uuid::new_v4().as_u128() as i32;
+        let running = RunningSlot::default();
+        self.sessions.lock().insert(process_id, (secret, running.clone()));
+        (process_id, secret, running)
+    }
+
+    /// 会话结束时注销
+    fn unregister(&self, process_id: i32) {
+        self.sessions.lock().remove(&process_id);
+    }
+
+    /// 密钥匹配时返回会话正在执行的查询
+    fn running_query(&self, process_id: i32, secret: i32) -> Option<String> {
+        let sessions = self.sessions.lock();
+        let (expected, running) = sessions.get(&process_id)?;
+        if *expected != secret {
+            return None;
+        }
+        let query_id = running.lock().clone();
+        query_id
+    }
+}
+
+/// 结果列
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    name: String,
+    type_oid: u32,
+}
+
+/// 一条语句的执行结果
+enum Outcome {
+    /// 返回行的语句
+    Rows { fields: Vec<Field>, rows: Vec<HashMap<String, Value>>, tag: String },
+    /// 不返回行的语句
+    Command { tag: String },
+    /// 空语句
+    Empty,
+}
+
+/// Parse消息登记的语句
+struct Statement {
+    sql: String,
+    /// 各参数的类型OID（客户端声明或按表结构推断，未知为text）
+    parameter_types: Vec<u32>,
+    /// 按表结构推断的参数类型（客户端未声明类型时用于转换文本参数）
+    inferred_types: Vec<Option<ColumnType>>,
+}
+
+/// Bind消息创建的门户
+struct Portal {
+    sql: String,
+    parameters: HashMap<String, Value>,
+    result_formats: Vec<i16>,
+    /// 首次Describe或Execute时执行
+    outcome: Option<Outcome>,
+    /// 已发送的行数（Execute限定行数时分多次发送）
+    sent: usize,
+}
+
+/// 一个客户端会话
+struct Session<'a> {
+    server: &'a PostgresServer,
+    user: String,
+    process_id: i32,
+    running: RunningSlot,
+    /// 会话参数（键为小写参数名）
+    settings: HashMap<String, String>,
+    /// 连接建立时的会话参数（RESET恢复到此）
+    defaults: HashMap<String, String>,
+    statements: HashMap<String, Statement>,
+    portals: HashMap<String, Portal>,
+}
+
+impl<'a> Session<'a> {
+    fn new(
+        server: &'a PostgresServer,
+        user: String,
+        process_id: i32,
+        running: RunningSlot,
+        startup: &HashMap<String, String>,
+    ) -> Self {
+        let mut settings: HashMap<String, String> = [
+            ("application_name", ""),
+            ("client_encoding", "UTF8"),
+            ("datestyle", "ISO, MDY"),
+            ("integer_datetimes", "on"),
+            ("intervalstyle", "postgres"),
+            ("is_superuser", "off"),
+            ("server_encoding", "UTF8"),
+            ("server_version", server.config.server_version.as_str()),
+            ("session_authorization", user.as_str()),
+            ("standard_conforming_strings", "on"),
+            ("timezone", "UTC"),
+            ("default_transaction_isolation", server.engine.config().default_isolation.as_sql()),
+            ("search_path", "\"$user\", public"),
+        ].into_iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        // 启动包中的其他参数（如application_name）视同SET
+        for (name, value) in startup {
+            if !matches!(name.as_str(), "user" | "database" | "options" | "replication") {
+                settings.insert(name.to_lowercase(), value.clone());
+            }
+        }
+        Self {
+            server,
+            user,
+            process_id,
+            running,
+            defaults: settings.clone(),
+            settings,
+            statements: HashMap::new(),
+            portals: HashMap::new(),
+        }
+    }
+
+    fn setting(&self, name: &str) -> Option<String> {
+        self.settings.get(&name.to_lowercase()).cloned()
+    }
+
+    /// 引擎中的会话ID，事务以此为作用域
+    fn session_id(&self) -> String {
+        format!("pg-{}", self.process_id)
+    }
+
+    /// ReadyForQuery中的事务状态
+    fn transaction_status(&self) -> u8 {
+        match self.server.engine.transaction_status(&self.session_id()) {
+            TransactionStatus::Idle => b'I',
+            TransactionStatus::Active => b'T',
+            TransactionStatus::Failed => b'E',
+        }
+    }
+
+    /// 消息循环
+    async fn run<S>(&mut self, conn: &mut Connection<S>) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        // 扩展查询出错后丢弃消息直到Sync
+        let mut skip_until_sync = false;
+        while let Some((tag, body)) = conn.read_message().await? {
+            match tag {
+                b'X' => return Ok(()),
+                b'S' => {
+                    skip_until_sync = false;
+                    conn.ready_for_query(self.transaction_status());
+                    conn.flush().await?;
+                }
+                b'H' => conn.flush().await?,
+                b'Q' => {
+                    skip_until_sync = false;
+                    let sql = Reader::new(&body).cstr()?;
+                    self.simple_query(conn, &sql).await?;
+                    conn.ready_for_query(self.transaction_status());
+                    conn.flush().await?;
+                }
+                _ if skip_until_sync => {}
+                _ => {
+                    if let Err(e) = self.extended_message(conn, tag, &body).await {
+                        // 连接层错误直接断开，其余错误报告给客户端
+                        if matches!(e, Error::Io { .. }) {
+                            return Err(e);
+                        }
+                        conn.error_from(&e);
+                        skip_until_sync = true;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 简单查询：依次执行以分号分隔的各条语句，出错即停止
+    async fn simple_query<S>(&mut self, conn: &mut Connection<S>, sql: &str) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let statements = split_statements(sql);
+        if statements.is_empty() {
+            conn.send(b'I', &[]);
+            return Ok(());
+        }
+        for statement in statements {
+            match self.execute(conn, &statement, HashMap::new()).await {
+                Ok(Outcome::Rows { fields, rows, tag }) => {
+                    conn.row_description(&fields, &[]);
+                    for row in &rows {
+                        conn.data_row(&fields, &[], row)?;
+                        conn.flush_if_full().await?;
+                    }
+                    conn.command_complete(&rows_tag(&tag, rows.len()));
+                }
+                Ok(Outcome::Command { tag }) => conn.command_complete(&tag),
+                Ok(Outcome::Empty) => conn.send(b'I', &[]),
+                Err(e @ Error::Io { .. }) => return Err(e),
+                Err(e) => {
+                    conn.error_from(&e);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 扩展查询协议的一条消息
+    async fn extended_message<S>(&mut self, conn: &mut Connection<S>, tag: u8, body: &[u8]) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let mut reader = Reader::new(body);
+        match tag {
+            b'P' => {
+                let name = reader.cstr()?;
+                let sql = reader.cstr()?;
+                let declared: Vec<u32> = (0..reader.i16()?).map(|_| reader.i32().map(|oid| oid as u32)).collect::<Result<_>>()?;
+                let statement = self.parse(sql, declared).await;
+                self.statements.insert(name, statement);
+                conn.send(b'1', &[]);
+            }
+            b'B' => {
+                let portal = reader.cstr()?;
+                let name = reader.cstr()?;
+                let formats: Vec<i16> = (0..reader.i16()?).map(|_| reader.i16()).collect::<Result<_>>()?;
+                let count = reader.i16()?.max(0) as usize;
+                let statement = self.statements.get(&name)
+                    .ok_or_else(|| Error::not_found(format!("prepared statement \"{}\"", name)))?;
+                let mut parameters = HashMap::new();
+                for index in 0..count {
+                    let len = reader.i32()?;
+                    let raw = if len < 0 { None } else { Some(reader.take(len as usize)?) };
+                    let format = result_format(&formats, index);
+                    let type_oid = statement.parameter_types.get(index).copied().unwrap_or(oid::UNKNOWN);
+                    let inferred = statement.inferred_types.get(index).copied().flatten();
+                    parameters.insert((index + 1).to_string(), decode_parameter(raw, format, type_oid, inferred)?);
+                }
+                let result_formats: Vec<i16> = (0..reader.i16()?).map(|_| reader.i16()).collect::<Result<_>>()?;
+                let sql = statement.sql.clone();
+                self.portals.insert(portal, Portal { sql, parameters, result_formats, outcome: None, sent: 0 });
+                conn.send(b'2', &[]);
+            }
+            b'D' => {
+                let kind = reader.u8()?;
+                let name = reader.cstr()?;
+                if kind == b'S' {
+                    let statement = self.statements.get(&name)
+                        .ok_or_else(|| Error::not_found(format!("prepared statement \"{}\"", name)))?;
+                    let mut description = Writer::new();
+                    description.i16(statement.parameter_types.len() as i16);
+                    for type_oid in &statement.parameter_types {
+                        description.i32(*type_oid as i32);
+                    }
+                    conn.send(b't', &description.finish());
+                    let sql = statement.sql.clone();
+                    match self.describe_statement(&sql) {
+                        Some(fields) => conn.row_description(&fields, &[]),
+                        None => conn.send(b'n', &[]),
+                    }
+                } else {
+                    self.ensure_executed(conn, &name).await?;
+                    let portal = &self.portals[&name];
+                    match &portal.outcome {
+                        Some(Outcome::Rows { fields, .. }) => conn.row_description(fields, &portal.result_formats),
+                        _ => conn.send(b'n', &[]),
+                    }
+                }
+            }
+            b'E' => {
+                let name = reader.cstr()?;
+                let max_rows = reader.i32()?;
+                self.ensure_executed(conn, &name).await?;
+                let portal = self.portals.get_mut(&name).expect("portal executed above");
+                match &portal.outcome {
+                    Some(Outcome::Rows { fields, rows, tag }) => {
+                        let end = match usize::try_from(max_rows) {
+                            Ok(limit) if limit > 0 => (portal.sent + limit).min(rows.len()),
+                            _ => rows.len(),
+                        };
+                        for row in &rows[portal.sent..end] {
+                            conn.data_row(fields, &portal.result_formats, row)?;
+                            conn.flush_if_full().await?;
+                        }
+                        let suspended = end < rows.len();
+                        let tag = rows_tag(tag, end);
+                        portal.sent = end;
+                        if suspended {
+                            conn.send(b's', &[]);
+                        } else {
+                            conn.command_complete(&tag);
+                        }
+                    }
+                    Some(Outcome::Command { tag }) => conn.command_complete(tag),
+                    Some(Outcome::Empty) | None => conn.send(b'I', &[]),
+                }
+            }
+            b'C' => {
+                let kind = reader.u8()?;
+                let name = reader.cstr()?;
+                if kind == b'S' {
+                    self.statements.remove(&name);
+                } else {
+                    self.portals.remove(&name);
+                }
+                conn.send(b'3', &[]);
+            }
+            b'd' | b'c' | b'f' => return Err(Error::unimplemented("COPY is not supported over the PostgreSQL protocol")),
+            b'F' => return Err(Error::unimplemented("Function call messages are not supported")),
+            other => return Err(Error::network(format!("protocol violation: unexpected message type '{}'", other as char))),
+        }
+        Ok(())
+    }
+
+    /// 登记语句：确定参数个数与类型（客户端声明优先，否则按表结构推断）
+    async fn parse(&self, sql: String, declared: Vec<u32>) -> Statement {
+        let count = declared.len().max(placeholder_count(&sql));
+        let mut inferred = vec![None; count];
+        if count > 0 {
+            // 非查询语句等无法预处理时参数类型保持未知
+            if let Ok(prepared) = self.server.engine.prepare(&sql).await {
+                for parameter in &prepared.parameters {
+                    if let Some(slot) = parameter.name.parse::<usize>().ok().and_then(|n| inferred.get_mut(n.wrapping_sub(1))) {
+                        *slot = parameter.data_type;
+                    }
+                }
+            }
+        }
+        let parameter_types = (0..count).map(|index| match declared.get(index) {
+            Some(type_oid) if *type_oid != 0 => *type_oid,
+            _ => inferred[index].map_or(oid::TEXT, column_type_oid),
+        }).collect();
+        Statement { sql, parameter_types, inferred_types: inferred }
+    }
+
+    /// 描述尚未绑定参数的语句的结果列
+    ///
+    /// 只按投影静态推断，类型未知的列报告为text；无法推断（如通配符展开未知关系）时返回None，
+    /// 由调用方回复NoData。描述不会执行语句。
+    fn describe_statement(&self, sql: &str) -> Option<Vec<Field>> {
+        if !returns_rows(&first_keyword(sql)) {
+            return None;
+        }
+        let columns = self.server.engine.describe(sql).ok()??;
+        Some(columns.into_iter().map(|column| Field {
+            type_oid: column.data_type.map_or(oid::TEXT, column_type_oid),
+            name: column.name,
+        }).collect())
+    }
+
+    /// 门户尚未执行时执行
+    async fn ensure_executed<S>(&mut self, conn: &mut Connection<S>, name: &str) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let portal = self.portals.get(name).ok_or_else(|| Error::not_found(format!("portal \"{}\"", name)))?;
+        if portal.outcome.is_some() {
+            return Ok(());
+        }
+        let (sql, parameters) = (portal.sql.clone(), portal.parameters.clone());
+        let outcome = self.execute(conn, &sql, parameters).await?;
+        if let Some(portal) = self.portals.get_mut(name) {
+            portal.outcome = Some(outcome);
+        }
+        Ok(())
+    }
+
+    /// 执行一条语句：会话级命令在本地处理，其余交给查询引擎
+    async fn execute<S>(&mut self, conn: &mut Connection<S>, sql: &str, parameters: HashMap<String, Value>) -> Result<Outcome>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+        if sql.is_empty() {
+            return Ok(Outcome::Empty);
+        }
+        if let Some(outcome) = self.session_command(conn, sql)? {
+            return Ok(outcome);
+        }
+
+        let engine = &self.server.engine;
+        let mut context = ExecutionContext::new(uuid::Uuid::new_v4().to_string())
+            .with_timeout(engine.config().query_timeout)
+            .with_user_id(self.user.clone())
+            .with_session_id(self.session_id())
+            .with_isolation(self.default_isolation());
+        context.parameters = parameters;
+        // 失败事务中的COMMIT按回滚处理
+        let keyword = first_keyword(sql);
+        let rolls_back = matches!(keyword.as_str(), "COMMIT" | "END")
+            && engine.transaction_status(&self.session_id()) == TransactionStatus::Failed;
+        *self.running.lock() = Some(context.query_id.clone());
+        let result = engine.execute_sql_with_context(sql, context).await;
+        *self.running.lock() = None;
+        let result = result?;
+        if let Some(error) = result.error {
+            return Err(Error::query(error));
+        }
+
+        if returns_rows(&keyword) || !result.rows.is_empty() {
+            let fields = self.result_fields(sql, &result.rows);
+            let tag = match keyword.as_str() {
+                "SELECT" | "WITH" | "VALUES" | "TABLE" => "SELECT".to_string(),
+                _ => keyword,
+            };
+            Ok(Outcome::Rows { fields, rows: result.rows, tag })
+        } else if rolls_back {
+            Ok(Outcome::Command { tag: "ROLLBACK".to_string() })
+        } else {
+            Ok(Outcome::Command { tag: command_tag(sql, result.affected_rows) })
+        }
+    }
+
+    /// 结果列：列顺序取自投影（含通配符展开），类型优先按实际值推断
+    fn result_fields(&self, sql: &str, rows: &[HashMap<String, Value>]) -> Vec<Field> {
+        let described = self.server.engine.describe(sql).ok().flatten().unwrap_or_default();
+        let mut names: Vec<String> = Vec::new();
+        for column in &described {
+            let present = rows.first().map_or(true, |row| row.contains_key(&column.name));
+            if present && !names.contains(&column.name) {
+                names.push(column.name.clone());
+            }
+        }
+        if let Some(first) = rows.first() {
+            let mut extra: Vec<String> = first.keys().filter(|key| !names.contains(key)).cloned().collect();
+            extra.sort();
+            names.extend(extra);
+        }
+        names.into_iter().map(|name| {
+            let observed = rows.iter()
+                .filter_map(|row| row.get(&name))
+                .find(|value| !matches!(value, Value::Null))
+                .map(value_type_oid);
+            let declared = described.iter().find(|column| column.name == name).and_then(|column| column.data_type);
+            let type_oid = observed.or(declared.map(column_type_oid)).unwrap_or(oid::TEXT);
+            Field { name, type_oid }
+        }).collect()
+    }
+
+    /// 会话的默认隔离级别（`default_transaction_isolation`）
+    fn default_isolation(&self) -> IsolationLevel {
+        self.setting("default_transaction_isolation")
+            .and_then(|level| IsolationLevel::parse(&level).ok())
+            .unwrap_or(self.server.engine.config().default_isolation)
+    }
+
+    /// `SET TRANSACTION`：隔离级别在BEGIN时确定，事务中只接受与当前相同的级别；事务外不起作用
+    fn set_transaction_isolation(&self, level: IsolationLevel) -> Result<()> {
+        match self.server.engine.transactions().get(&self.session_id()) {
+            Some(transaction) if transaction.isolation() != level => Err(Error::unimplemented(
+                "changing the isolation level of a started transaction; use BEGIN ISOLATION LEVEL",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// 会话级命令：SET/RESET/SHOW会话参数与DISCARD
+    ///
+    /// 隔离级别相关的设置按`IsolationLevel::parse`校验，SERIALIZABLE报告为不支持。
+    fn session_command<S>(&mut self, conn: &mut Connection<S>, sql: &str) -> Result<Option<Outcome>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let keyword = first_keyword(sql);
+        let rest = sql.get(keyword.len()..).unwrap_or_default().trim();
+        let tag = |tag: &str| Ok(Some(Outcome::Command { tag: tag.to_string() }));
+        match keyword.as_str() {
+            "DISCARD" => {
+                self.statements.clear();
+                self.portals.clear();
+                tag("DISCARD ALL")
+            }
+            "SET" => {
+                if let Some(modes) = strip_keywords(rest, &["SESSION", "CHARACTERISTICS", "AS", "TRANSACTION"]) {
+                    if let Some(level) = IsolationLevel::parse_modes(modes)? {
+                        self.settings.insert("default_transaction_isolation".to_string(), level.as_sql().to_string());
+                    }
+                    return tag("SET");
+                }
+                if let Some(modes) = strip_keywords(rest, &["TRANSACTION"]) {
+                    if let Some(level) = IsolationLevel::parse_modes(modes)? {
+                        self.set_transaction_isolation(level)?;
+                    }
+                    return tag("SET");
+                }
+                if let Some((name, mut value)) = parse_set(rest) {
+                    if matches!(name.as_str(), "default_transaction_isolation" | "transaction_isolation") {
+                        let level = if value.eq_ignore_ascii_case("default") {
+                            self.server.engine.config().default_isolation
+                        } else {
+                            IsolationLevel::parse(&value)?
+                        };
+                        if name == "transaction_isolation" {
+                            self.set_transaction_isolation(level)?;
+                            return tag("SET");
+                        }
+                        value = level.as_sql().to_string();
+                    }
+                    let reported = REPORTED_PARAMETERS.iter().find(|p| p.eq_ignore_ascii_case(&name));
+                    if let Some(reported) = reported {
+                        conn.parameter_status(reported, &value);
+                    }
+                    self.settings.insert(name, value);
+                }
+                tag("SET")
+            }
+            "RESET" => {
+                let name = rest.to_lowercase();
+                if name == "all" {
+                    self.settings = self.defaults.clone();
+                } else {
+                    match self.defaults.get(&name) {
+                        Some(value) => self.settings.insert(name, value.clone()),
+                        None => self.settings.remove(&name),
+                    };
+                }
+                tag("RESET")
+            }
+            "SHOW" => {
+                let name = match rest.to_lowercase().as_str() {
+                    "transaction isolation level" => "transaction_isolation".to_string(),
+                    "time zone" => "timezone".to_string(),
+                    other => other.to_string(),
+                };
+                let value = match name.as_str() {
+                    "server_version_num" => Some(server_version_num(&self.server.config.server_version)),
+                    "transaction_isolation" => {
+                        let level = self.server.engine.transactions().get(&self.session_id())
+                            .map_or_else(|| self.default_isolation(), |transaction| transaction.isolation());
+                        Some(level.as_sql().to_string())
+                    }
+                    _ => self.setting(&name),
+                };
+                let Some(value) = value else {
+                    return Ok(None);
+                };
+                let column = rest.split_whitespace().last().unwrap_or(rest).to_lowercase();
+                let field = Field { name: column.clone(), type_oid: oid::TEXT };
+                let row = HashMap::from([(column, Value::String(value))]);
+                Ok(Some(Outcome::Rows { fields: vec![field], rows: vec![row], tag: "SHOW".to_string() }))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// 解析`SET [SESSION | LOCAL] name {TO | =} value`与`SET TIME ZONE value`
+fn parse_set(rest: &str) -> Option<(String, String)> {
+    let mut rest = rest.trim();
+    for prefix in ["SESSION ", "LOCAL "] {
+        if rest.get(..prefix.len()).is_some_and(|word| word.eq_ignore_ascii_case(prefix)) {
+            rest = rest[prefix.len()..].trim_start();
+        }
+    }
+    let (name, value) = if rest.get(..9).is_some_and(|word| word.eq_ignore_ascii_case("TIME ZONE")) {
+        ("timezone".to_string(), rest[9..].trim())
+    } else {
+        let split = rest.find('=').map(|at| (at, 1)).or_else(|| {
+            rest.to_ascii_lowercase().find(" to ").map(|at| (at, 4))
+        })?;
+        (rest[..split.0].trim().to_lowercase(), rest[split.0 + split.1..].trim())
+    };
+    let value = value.trim_matches(|c| c == '\'' || c == '"').to_string();
+    Some((name, value))
+}
+
+/// 去掉开头的关键字序列（不区分大小写），不匹配时返回None
+fn strip_keywords<'s>(text: &'s str, keywords: &[&str]) -> Option<&'s str> {
+    let mut rest = text.trim_start();
+    for keyword in keywords {
+        let word = rest.split_whitespace().next()?;
+        if !word.eq_ignore_ascii_case(keyword) {
+            return None;
+        }
+        rest = rest[word.len()..].trim_start();
+    }
+    Some(rest)
+}
+
+/// `server_version_num`形式的版本号（14.0 → 140000）
+fn server_version_num(version: &str) -> String {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit()).filter(|p| !p.is_empty());
+    let major: u32 = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    let minor: u32 = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    (major * 10000 + minor).to_string()
+}
+
+/// 语句的第一个关键字（大写）
+fn first_keyword(sql: &str) -> String {
+    sql.trim_start_matches(|c: char| c.is_whitespace() || c == '(')
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or_default()
+        .to_uppercase()
+}
+
+/// 以该关键字开头的语句是否返回行
+fn returns_rows(keyword: &str) -> bool {
+    matches!(keyword, "SELECT" | "WITH" | "VALUES" | "TABLE" | "SHOW" | "EXPLAIN" | "FETCH")
+}
+
+/// 返回行的语句的CommandComplete标签（SELECT与FETCH带行数）
+fn rows_tag(tag: &str, rows: usize) -> String {
+    match tag {
+        "SELECT" | "FETCH" => format!("{} {}", tag, rows),
+        _ => tag.to_string(),
+    }
+}
+
+/// 不返回行的语句的CommandComplete标签
+fn command_tag(sql: &str, affected_rows: u64) -> String {
+    let words: Vec<String> = sql.split_whitespace().take(6).map(str::to_uppercase).collect();
+    let keyword = words.first().cloned().unwrap_or_default();
+    match keyword.as_str() {
+        "INSERT" => format!("INSERT 0 {}", affected_rows),
+        "UPDATE" | "DELETE" | "MERGE" | "MOVE" | "COPY" => format!("{} {}", keyword, affected_rows),
+        "START" => "START TRANSACTION".to_string(),
+        "END" => "COMMIT".to_string(),
+        "ABORT" => "ROLLBACK".to_string(),
+        "CREATE" | "DROP" | "ALTER" => {
+            // CREATE OR REPLACE FUNCTION → CREATE FUNCTION；MATERIALIZED VIEW等两词对象保留两词
+            let mut object = words.iter().skip(1).filter(|w| !matches!(w.as_str(), "OR" | "REPLACE" | "TEMP" | "TEMPORARY" | "UNIQUE"));
+            match object.next().map(String::as_str) {
+                Some(first @ ("MATERIALIZED" | "CONTINUOUS")) => format!("{} {} {}", keyword, first, object.next().map_or("", |w| w.as_str())),
+                Some(first) => format!("{} {}", keyword, first.trim_end_matches(';')),
+                None => keyword,
+            }
+        }
+        _ => keyword,
+    }
+}
+
+/// 语句中最大的位置参数序号（`$n`，忽略引号与注释中的内容）
+fn placeholder_count(sql: &str) -> usize {
+    let mut count = 0;
+    scan_sql(sql, |at, c| {
+        if c == '$' {
+            let digits: String = sql[at + 1..].chars().take_while(char::is_ascii_digit).collect();
+            if let Ok(n) = digits.parse::<usize>() {
+                count = count.max(n);
+            }
+        }
+    });
+    count
+}
+
+/// 解码Bind消息中的一个参数
+fn decode_parameter(raw: Option<&[u8]>, format: i16, type_oid: u32, inferred: Option<ColumnType>) -> Result<Value> {
+    let Some(raw) = raw else {
+        return Ok(Value::Null);
+    };
+    let invalid = || Error::type_error(format!("invalid binary representation for type OID {}", type_oid));
+    if format == 1 {
+        let fixed = |len: usize| -> Result<&[u8]> { if raw.len() == len { Ok(raw) } else { Err(invalid()) } };
+        return Ok(match type_oid {
+            oid::BOOL => Value::Bool(fixed(1)?[0] != 0),
+            oid::INT2 => Value::Int64(i16::from_be_bytes(fixed(2)?.try_into().unwrap()) as i64),
+            oid::INT4 => Value::Int64(i32::from_be_bytes(fixed(4)?.try_into().unwrap()) as i64),
+            oid::OID => Value::Int64(u32::from_be_bytes(fixed(4)?.try_into().unwrap()) as i64),
+            oid::INT8 => Value::Int64(i64::from_be_bytes(fixed(8)?.try_into().unwrap())),
+            oid::FLOAT4 => Value::Float64(f32::from_be_bytes(fixed(4)?.try_into().unwrap()) as f64),
+            oid::FLOAT8 => Value::Float64(f64::from_be_bytes(fixed(8)?.try_into().unwrap())),
+            oid::BYTEA => Value::Binary(raw.to_vec()),
+            oid::TIMESTAMP | oid::TIMESTAMPTZ => {
+                let micros = i64::from_be_bytes(fixed(8)?.try_into().unwrap());
+                Value::Timestamp(TimestampNs::from_nanos((micros + POSTGRES_EPOCH_MICROS).saturating_mul(1000)))
+            }
+            oid::NUMERIC => ColumnType::Decimal.coerce(Value::String(decode_numeric(raw)?))?,
+            _ => {
+                let text = String::from_utf8(raw.to_vec()).map_err(|_| invalid())?;
+                match inferred {
+                    Some(column_type) => column_type.coerce(Value::String(text))?,
+                    None => Value::String(text),
+                }
+            }
+        });
+    }
+
+    let text = String::from_utf8(raw.to_vec())
+        .map_err(|_| Error::type_error("invalid UTF-8 in text parameter"))?;
+    match oid_column_type(type_oid).or(inferred) {
+        Some(ColumnType::Binary) => Ok(Value::Binary(decode_bytea(&text).ok_or_else(invalid)?)),
+        Some(ColumnType::Timestamp) => parse_timestamp(&text)
+            .map(Value::Timestamp)
+            .ok_or_else(|| Error::type_error(format!("invalid timestamp: {}", text))),
+        Some(column_type) => column_type.coerce(Value::String(text)),
+        None => Ok(Value::String(text)),
+    }
+}
+
+/// 解析PostgreSQL文本格式的时间戳（无时区视为UTC）
+fn parse_timestamp(text: &str) -> Option<TimestampNs> {
+    let text = text.trim();
+    let datetime = DateTime::parse_from_rfc3339(text).ok()
+        .or_else(|| DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z").ok())
+        .map(|datetime| datetime.naive_utc())
+        .or_else(|| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f").ok())
+        .or_else(|| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f").ok())
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))?;
+    datetime.and_utc().timestamp_nanos_opt().map(TimestampNs::from_nanos)
+}
+
+/// 解析bytea的文本格式（`\x`十六进制，否则按原始字节）
+fn decode_bytea(text: &str) -> Option<Vec<u8>> {
+    match text.strip_prefix("\\x") {
+        Some(hex) if hex.len() % 2 == 0 => (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect(),
+        Some(_) => None,
+        None => Some(text.as_bytes().to_vec()),
+    }
+}
+
+/// 值的文本格式编码，NULL返回None
+fn encode_text(value: &Value) -> Option<String> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => (if *b { "t" } else { "f" }).to_string(),
+        Value::Int8(v) => v.to_string(),
+        Value::Int16(v) => v.to_string(),
+        Value::Int32(v) => v.to_string(),
+        Value::Int64(v) => v.to_string(),
+        Value::Int128(v) => v.to_string(),
+        Value::UInt8(v) => v.to_string(),
+        Value::UInt16(v) => v.to_string(),
+        Value::UInt32(v) => v.to_string(),
+        Value::UInt64(v) => v.to_string(),
+        Value::UInt128(v) => v.to_string(),
+        Value::Float32(v) => encode_float(*v as f64),
+        Value::Float64(v) => encode_float(*v),
+        Value::Decimal(d) => d.to_string(),
+        Value::Price(p) => p.as_decimal().to_string(),
+        Value::Volume(v) => v.as_u64().to_string(),
+        Value::String(s) => s.clone(),
+        Value::Symbol(s) => s.as_str().to_string(),
+        Value::ExchangeId(id) => id.as_u16().to_string(),
+        Value::Binary(bytes) => {
+            let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("\\x{}", hex)
+        }
+        Value::Timestamp(ts) => match ts.to_datetime() {
+            Some(datetime) => datetime.format("%Y-%m-%d %H:%M:%S%.6f+00").to_string(),
+            None => ts.as_nanos().to_string(),
+        },
+        Value::Array(_) | Value::List(_) | Value::Struct(_) | Value::Map(_) | Value::Custom(_) => value_to_json(value).to_string(),
+    })
+}
+
+fn encode_float(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        (if value > 0.0 { "Infinity" } else { "-Infinity" }).to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// 值按列类型的二进制格式编码
+fn encode_binary(value: &Value, type_oid: u32) -> Result<Vec<u8>> {
+    let text = encode_text(value).unwrap_or_default();
+    let mismatch = || Error::type_error(format!("cannot encode {} as binary type OID {}", text, type_oid));
+    let integer = || text.parse::<i64>().map_err(|_| mismatch());
+    let float = || text.parse::<f64>().map_err(|_| mismatch());
+    Ok(match type_oid {
+        oid::BOOL => vec![u8::from(matches!(value, Value::Bool(true)) || text == "t")],
+        oid::INT2 => i16::try_from(integer()?).map_err(|_| mismatch())?.to_be_bytes().to_vec(),
+        oid::INT4 | oid::OID => i32::try_from(integer()?).map_err(|_| mismatch())?.to_be_bytes().to_vec(),
+        oid::INT8 => integer()?.to_be_bytes().to_vec(),
+        oid::FLOAT4 => (float()? as f32).to_be_bytes().to_vec(),
+        oid::FLOAT8 => float()?.to_be_bytes().to_vec(),
+        oid::NUMERIC => encode_numeric(&text)?,
+        oid::TIMESTAMP | oid::TIMESTAMPTZ => match value {
+            Value::Timestamp(ts) => (ts.as_nanos().div_euclid(1000) - POSTGRES_EPOCH_MICROS).to_be_bytes().to_vec(),
+            _ => return Err(mismatch()),
+        },
+        oid::BYTEA => match value {
+            Value::Binary(bytes) => bytes.clone(),
+            _ => text.into_bytes(),
+        },
+        _ => text.into_bytes(),
+    })
+}
+
+/// numeric的二进制格式：以10000为基的数字组
+fn encode_numeric(text: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::type_error(format!("invalid numeric: {}", text));
+    let mut writer = Writer::new();
+    if text.eq_ignore_ascii_case("nan") {
+        writer.i16(0).i16(0).u16(0xC000).i16(0);
+        return Ok(writer.finish());
+    }
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) || (integer.is_empty() && fraction.is_empty()) {
+        return Err(invalid());
+    }
+    let integer = format!("{}{}", "0".repeat((4 - integer.len() % 4) % 4), integer);
+    let fraction_padded = format!("{}{}", fraction, "0".repeat((4 - fraction.len() % 4) % 4));
+    let mut groups: Vec<i16> = integer.as_bytes().chunks(4).chain(fraction_padded.as_bytes().chunks(4))
+        .map(|chunk| std::str::from_utf8(chunk).unwrap().parse::<i16>().unwrap())
+        .collect();
+    let mut weight = (integer.len() / 4) as i16 - 1;
+    // 去掉前导与尾随的零组
+    while groups.first() == Some(&0) {
+        groups.remove(0);
+        weight -= 1;
+    }
+    while groups.last() == Some(&0) {
+        groups.pop();
+    }
+    if groups.is_empty() {
+        weight = 0;
+    }
+    let sign = if negative && !groups.is_empty() { 0x4000 } else { 0 };
+    writer.i16(groups.len() as i16).i16(weight).u16(sign).i16(fraction.len() as i16);
+    for group in groups {
+        writer.i16(group);
+    }
+    Ok(writer.finish())
+}
+
+/// 解码numeric的二进制格式为十进制文本
+fn decode_numeric(raw: &[u8]) -> Result<String> {
+    let mut reader = Reader::new(raw);
+    let count = reader.i16()?.max(0) as usize;
+    let weight = reader.i16()? as i32;
+    let sign = reader.i16()? as u16;
+    let scale = reader.i16()?.max(0) as usize;
+    let groups: Vec<i16> = (0..count).map(|_| reader.i16()).collect::<Result<_>>()?;
+    if sign == 0xC000 {
+        return Ok("NaN".to_string());
+    }
+    let group = |position: i32| -> i16 {
+        usize::try_from(position).ok().and_then(|p| groups.get(p)).copied().unwrap_or(0)
+    };
+    let mut text = String::new();
+    if sign == 0x4000 {
+        text.push('-');
+    }
+    if weight < 0 {
+        text.push('0');
+    } else {
+        text.push_str(&group(0).to_string());
+        for position in 1..=weight {
+            text.push_str(&format!("{:04}", group(position)));
+        }
+    }
+    if scale > 0 {
+        let mut fraction = String::new();
+        let mut position = weight + 1;
+        while fraction.len() < scale {
+            fraction.push_str(&format!("{:04}", group(position)));
+            position += 1;
+        }
+        fraction.truncate(scale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    Ok(text)
+}
+
+/// 后端消息体构造器
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn i16(&mut self, value: i16) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn i32(&mut self, value: i32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn cstr(&mut self, value: &str) -> &mut Self {
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+        self
+    }
+
+    fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+/// 前端消息体读取器
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(Error::network("protocol violation: message too short"));
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn cstr(&mut self) -> Result<String> {
+        let end = self.buf.iter().position(|b| *b == 0)
+            .ok_or_else(|| Error::network("protocol violation: unterminated string"))?;
+        let value = String::from_utf8(self.take(end)?.to_vec())
+            .map_err(|_| Error::network("protocol violation: invalid UTF-8"))?;
+        self.take(1)?;
+        Ok(value)
+    }
+}
+
+/// 带写缓冲的协议连接
+struct Connection<S> {
+    stream: S,
+    out: Vec<u8>,
+    max_message_size: usize,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Connection<S> {
+    fn new(stream: S, max_message_size: usize) -> Self {
+        Self {
+            stream,
+            out: Vec::new(),
+            max_message_size,
+        }
+    }
+
+    /// 读取启动阶段的消息（无类型字节），连接关闭返回None
+    async fn read_startup(&mut self) -> Result<Option<Vec<u8>>> {
+        let len = match self.stream.read_i32().await {
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        self.read_body(len).await.map(Some)
+    }
+
+    /// 读取一条前端消息，连接关闭返回None
+    async fn read_message(&mut self) -> Result<Option<(u8, Vec<u8>)>> {
+        let tag = match self.stream.read_u8().await {
+            Ok(tag) => tag,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let len = self.stream.read_i32().await?;
+        Ok(Some((tag, self.read_body(len).await?)))
+    }
+
+    async fn read_body(&mut self, len: i32) -> Result<Vec<u8>> {
+        let len = usize::try_from(len).ok().and_then(|len| len.checked_sub(4))
+            .filter(|len| *len <= self.max_message_size)
+            .ok_or_else(|| Error::network(format!("protocol violation: invalid message length {}", len)))?;
+        let mut body = vec![0; len];
+        self.stream.read_exact(&mut body).await?;
+        Ok(body)
+    }
+
+    /// 缓冲一条后端消息
+    fn send(&mut self, tag: u8, body: &[u8]) {
+        self.out.push(tag);
+        self.out.extend_from_slice(&((body.len() + 4) as i32).to_be_bytes());
+        self.out.extend_from_slice(body);
+    }
+
+    fn parameter_status(&mut self, name: &str, value: &str) {
+        self.send(b'S', &Writer::new().cstr(name).cstr(value).finish());
+    }
+
+    fn ready_for_query(&mut self, status: u8) {
+        self.send(b'Z', &[status]);
+    }
+
+    fn command_complete(&mut self, tag: &str) {
+        self.send(b'C', &Writer::new().cstr(tag).finish());
+    }
+
+    fn row_description(&mut self, fields: &[Field], formats: &[i16]) {
+        let mut body = Writer::new();
+        body.i16(fields.len() as i16);
+        for (index, field) in fields.iter().enumerate() {
+            let len = pg_catalog::pg_type(field.type_oid).map_or(-1, |t| t.len);
+            body.cstr(&field.name).i32(0).i16(0).i32(field.type_oid as i32).i16(len).i32(-1).i16(result_format(formats, index));
+        }
+        self.send(b'T', &body.finish());
+    }
+
+    fn data_row(&mut self, fields: &[Field], formats: &[i16], row: &HashMap<String, Value>) -> Result<()> {
+        let mut body = Writer::new();
+        body.i16(fields.len() as i16);
+        for (index, field) in fields.iter().enumerate() {
+            let value = row.get(&field.name).unwrap_or(&Value::Null);
+            let encoded = match (value, result_format(formats, index)) {
+                (Value::Null, _) => None,
+                (value, 1) => Some(encode_binary(value, field.type_oid)?),
+                (value, _) => encode_text(value).map(String::into_bytes),
+            };
+            match encoded {
+                Some(bytes) => body.i32(bytes.len() as i32).bytes(&bytes),
+                None => body.i32(-1),
+            };
+        }
+        self.send(b'D', &body.finish());
+        Ok(())
+    }
+
+    fn error(&mut self, severity: &str, code: &str, message: &str) {
+        let body = Writer::new()
+            .bytes(b"S").cstr(severity)
+            .bytes(b"V").cstr(severity)
+            .bytes(b"C").cstr(code)
+            .bytes(b"M").cstr(message)
+            .bytes(&[0])
+            .finish();
+        self.send(b'E', &body);
+    }
+
+    fn error_from(&mut self, error: &Error) {
+        self.error("ERROR", sqlstate(error), &error.to_string());
+    }
+
+    async fn write_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if !self.out.is_empty() {
+            self.stream.write_all(&self.out).await?;
+            self.out.clear();
+        }
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    async fn flush_if_full(&mut self) -> Result<()> {
+        if self.out.len() >= FLUSH_THRESHOLD {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// 第`index`列（或参数）的格式代码（0文本，1二进制）
+fn result_format(formats: &[i16], index: usize) -> i16 {
+    match formats {
+        [] => 0,
+        [format] => *format,
+        formats => formats.get(index).copied().unwrap_or(0),
+    }
+}
+
+/// 引擎错误对应的SQLSTATE
+fn sqlstate(error: &Error) -> &'static str {
+    match error {
+        Error::Validation { .. } | Error::Parse { .. } => "42601",
+        Error::Type { .. } => "42804",
+        Error::InvalidArgument { .. } => "22023",
+        Error::NotFound { .. } => "42704",
+        Error::AlreadyExists { .. } => "42710",
+        Error::PermissionDenied { .. } => "42501",
+        Error::ResourceExhausted { .. } | Error::Memory { .. } => "53000",
+        Error::Timeout { .. } | Error::Cancelled { .. } => "57014",
+        Error::Query { message } if message == SERIALIZATION_FAILURE => "40001",
+        Error::Query { message } if message == TRANSACTION_ABORTED => "25P02",
+        Error::Unimplemented { .. } => "0A000",
+        Error::Network { .. } => "08P01",
+        _ => "XX000",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_query::{ColumnDefinition, QueryEngineConfig, TableDefinition};
+    use fdc_storage::engine::StorageEngine;
+    use fdc_storage::engines::memory::MemoryEngine;
+    use tokio::io::DuplexStream;
+
+    async fn server(auth: AuthConfig) -> PostgresServer {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = Arc::new(QueryEngine::new(storage.clone(), QueryEngineConfig::default()));
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("price", ColumnType::Float64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (1..=5).map(|i| HashMap::from([
+            ("id".to_string(), Value::Int64(i)),
+            ("symbol".to_string(), Value::String(format!("S{}", i))),
+            ("price".to_string(), Value::Float64(10.0 * i as f64)),
+        ])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &rows).await.unwrap();
+        PostgresServer::new(engine, PostgresConfig::default()).unwrap().with_auth(auth)
+    }
+
+    /// 测试用的最小前端
+    struct Client {
+        stream: DuplexStream,
+    }
+
+    impl Client {
+        async fn connect(server: &PostgresServer, password: Option<&str>) -> (Self, Vec<(u8, Vec<u8>)>) {
+            let (client, backend) = tokio::io::duplex(1 << 20);
+            let server = server.clone();
+            tokio::spawn(async move { server.handle_connection(backend).await });
+            let mut client = Self { stream: client };
+            let startup = Writer::new().i32(PROTOCOL_VERSION_3).cstr("user").cstr("tester").cstr("application_name").cstr("psql").bytes(&[0]).finish();
+            client.stream.write_i32(startup.len() as i32 + 4).await.unwrap();
+            client.stream.write_all(&startup).await.unwrap();
+            if let Some(password) = password {
+                assert_eq!(client.read().await.0, b'R');
+                client.send(b'p', &Writer::new().cstr(password).finish()).await;
+            }
+            let messages = client.until(|tag| tag == b'Z' || tag == b'E').await;
+            (client, messages)
+        }
+
+        async fn send(&mut self, tag: u8, body: &[u8]) {
+            self.stream.write_u8(tag).await.unwrap();
+            self.stream.write_i32(body.len() as i32 + 4).await.unwrap();
+            self.stream.write_all(body).await.unwrap();
+        }
+
+        async fn read(&mut self) -> (u8, Vec<u8>) {
+            let tag = self.stream.read_u8().await.unwrap();
+            let len = self.stream.read_i32().await.unwrap();
+            let mut body = vec![0; len as usize - 4];
+            self.stream.read_exact(&mut body).await.unwrap();
+            (tag, body)
+        }
+
+        async fn until(&mut self, last: impl Fn(u8) -> bool) -> Vec<(u8, Vec<u8>)> {
+            let mut messages = Vec::new();
+            loop {
+                let message = self.read().await;
+                let done = last(message.0);
+                messages.push(message);
+                if done {
+                    return messages;
+                }
+            }
+        }
+
+        async fn query(&mut self, sql: &str) -> Vec<(u8, Vec<u8>)> {
+            self.send(b'Q', &Writer::new().cstr(sql).finish()).await;
+            self.until(|tag| tag == b'Z').await
+        }
+    }
+
+    fn tags(messages: &[(u8, Vec<u8>)]) -> String {
+        messages.iter().map(|(tag, _)| *tag as char).collect()
+    }
+
+    fn field_names(body: &[u8]) -> Vec<(String, u32)> {
+        let mut reader = Reader::new(body);
+        (0..reader.i16().unwrap()).map(|_| {
+            let name = reader.cstr().unwrap();
+            reader.take(6).unwrap();
+            let type_oid = reader.i32().unwrap() as u32;
+            reader.take(8).unwrap();
+            (name, type_oid)
+        }).collect()
+    }
+
+    fn columns(body: &[u8]) -> Vec<Option<Vec<u8>>> {
+        let mut reader = Reader::new(body);
+        (0..reader.i16().unwrap()).map(|_| {
+            let len = reader.i32().unwrap();
+            (len >= 0).then(|| reader.take(len as usize).unwrap().to_vec())
+        }).collect()
+    }
+
+    fn cstr(body: &[u8]) -> String {
+        Reader::new(body).cstr().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_simple_query_flow() {
+        let server = server(AuthConfig::default()).await;
+        let (mut client, startup) = Client::connect(&server, None).await;
+        assert_eq!(startup[0], (b'R', vec![0, 0, 0, 0]));
+        assert!(startup.iter().any(|(tag, body)| *tag == b'S' && body.starts_with(b"application_name\0psql\0")));
+        assert_eq!(tags(&startup[startup.len() - 2..]), "KZ");
+
+        let messages = client.query("SELECT id, symbol, price FROM trades WHERE id <= 2 ORDER BY id; SET application_name = 'bi'; ;").await;
+        assert_eq!(tags(&messages), "TDDCSCZ");
+        assert_eq!(field_names(&messages[0].1), vec![
+            ("id".to_string(), oid::INT8),
+            ("symbol".to_string(), oid::TEXT),
+            ("price".to_string(), oid::FLOAT8),
+        ]);
+        assert_eq!(columns(&messages[1].1), vec![Some(b"1".to_vec()), Some(b"S1".to_vec()), Some(b"10".to_vec())]);
+        assert_eq!(cstr(&messages[3].1), "SELECT 2");
+        assert_eq!(cstr(&messages[5].1), "SET");
+
+        let messages = client.query("SHOW application_name").await;
+        assert_eq!(columns(&messages[1].1), vec![Some(b"bi".to_vec())]);
+
+        let messages = client.query("SELECT relname FROM pg_catalog.pg_class").await;
+        assert_eq!(columns(&messages[1].1), vec![Some(b"trades".to_vec())]);
+
+        // 出错后后续语句不再执行
+        let messages = client.query("SELEC 1; SELECT 1").await;
+        assert_eq!(tags(&messages), "EZ");
+        assert_eq!(tags(&client.query("").await), "IZ");
+    }
+
+    #[tokio::test]
+    async fn test_transactions() {
+        let server = server(AuthConfig::default()).await;
+        let (mut client, _) = Client::connect(&server, None).await;
+        let (mut other, _) = Client::connect(&server, None).await;
+
+        let messages = client.query("BEGIN; INSERT INTO trades (id, symbol, price) VALUES (6, 'S6', 60.0)").await;
+        assert_eq!(tags(&messages), "CCZ");
+        assert_eq!(cstr(&messages[0].1), "BEGIN");
+        assert_eq!(messages[2].1, b"T");
+        // 未提交的写入对其他连接不可见
+        let messages = other.query("SELECT count(*) FROM trades").await;
+        assert_eq!(columns(&messages[1].1), vec![Some(b"5".to_vec())]);
+        let messages = client.query("SELECT count(*) FROM trades").await;
+        assert_eq!(columns(&messages[1].1), vec![Some(b"6".to_vec())]);
+
+        let messages = client.query("ROLLBACK").await;
+        assert_eq!(cstr(&messages[0].1), "ROLLBACK");
+        assert_eq!(messages[1].1, b"I");
+
+        // 事务内出错后进入失败状态，COMMIT报告为ROLLBACK
+        client.query("START TRANSACTION").await;
+        let messages = client.query("SELEC 1").await;
+        assert_eq!(tags(&messages), "EZ");
+        assert_eq!(messages[1].1, b"E");
+        let messages = client.query("SELECT 1").await;
+        assert!(String::from_utf8_lossy(&messages[0].1).contains("C25P02"));
+        let messages = client.query("COMMIT").await;
+        assert_eq!(cstr(&messages[0].1), "ROLLBACK");
+        assert_eq!(messages[1].1, b"I");
+
+        // 隔离级别设置经过校验，SERIALIZABLE报告为不支持
+        for sql in [
+            "SET TRANSACTION ISOLATION LEVEL SERIALIZABLE",
+            "SET SESSION CHARACTERISTICS AS TRANSACTION ISOLATION LEVEL SERIALIZABLE",
+            "SET default_transaction_isolation = 'serializable'",
+        ] {
+            let messages = client.query(sql).await;
+            assert_eq!(tags(&messages), "EZ");
+            assert!(String::from_utf8_lossy(&messages[0].1).contains("C0A000"));
+        }
+        client.query("SET SESSION CHARACTERISTICS AS TRANSACTION ISOLATION LEVEL READ COMMITTED").await;
+        client.query("BEGIN").await;
+        let messages = client.query("SHOW transaction_isolation").await;
+        assert_eq!(columns(&messages[1].1), vec![Some(b"read committed".to_vec())]);
+        let messages = client.query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ").await;
+        assert!(String::from_utf8_lossy(&messages[0].1).contains("C0A000"));
+        client.query("ROLLBACK").await;
+        client.query("SET default_transaction_isolation TO 'repeatable read'").await;
+        let messages = client.query("BEGIN; SET TRANSACTION ISOLATION LEVEL REPEATABLE READ; SHOW transaction_isolation; ROLLBACK").await;
+        assert_eq!(tags(&messages), "CCTDCCZ");
+        assert_eq!(columns(&messages[3].1), vec![Some(b"repeatable read".to_vec())]);
+
+        // 断开连接时回滚未提交事务
+        client.query("BEGIN").await;
+        assert_eq!(server.engine.transactions().len(), 1);
+        drop(client);
+        for _ in 0..100 {
+            if server.engine.transactions().is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(server.engine.transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_extended_query_flow() {
+        let server = server(AuthConfig::default()).await;
+        let (mut client, _) = Client::connect(&server, None).await;
+
+        let parse = Writer::new().cstr("by_id").cstr("SELECT symbol, price FROM trades WHERE id >= $1 ORDER BY symbol").i16(1).i32(oid::INT8 as i32).finish();
+        client.send(b'P', &parse).await;
+        client.send(b'D', &Writer::new().bytes(b"S").cstr("by_id").finish()).await;
+        // 参数与price列均为二进制格式，每次取2行
+        let bind = Writer::new().cstr("").cstr("by_id").i16(1).i16(1).i16(1).i32(8).bytes(&3i64.to_be_bytes()).i16(2).i16(0).i16(1).finish();
+        client.send(b'B', &bind).await;
+        client.send(b'D', &Writer::new().bytes(b"P").cstr("").finish()).await;
+        client.send(b'E', &Writer::new().cstr("").i32(2).finish()).await;
+        client.send(b'E', &Writer::new().cstr("").i32(2).finish()).await;
+        client.send(b'S', &[]).await;
+        let messages = client.until(|tag| tag == b'Z').await;
+        assert_eq!(tags(&messages), "1tT2TDDsDCZ");
+        assert_eq!(messages[1].1, Writer::new().i16(1).i32(oid::INT8 as i32).finish());
+        assert_eq!(field_names(&messages[2].1)[1], ("price".to_string(), oid::FLOAT8));
+        assert_eq!(columns(&messages[5].1), vec![Some(b"S3".to_vec()), Some(30f64.to_be_bytes().to_vec())]);
+        assert_eq!(cstr(&messages[9].1), "SELECT 3");
+
+        // 出错后丢弃消息直到Sync
+        client.send(b'B', &Writer::new().cstr("").cstr("missing").i16(0).i16(0).i16(0).finish()).await;
+        client.send(b'E', &Writer::new().cstr("").i32(0).finish()).await;
+        client.send(b'S', &[]).await;
+        assert_eq!(tags(&client.until(|tag| tag == b'Z').await), "EZ");
+
+        // 未声明类型的参数按表结构推断并从文本转换
+        client.send(b'P', &Writer::new().cstr("").cstr("SELECT symbol FROM trades WHERE id = $1").i16(0).finish()).await;
+        client.send(b'B', &Writer::new().cstr("").cstr("").i16(0).i16(1).i32(1).bytes(b"4").i16(0).finish()).await;
+        client.send(b'E', &Writer::new().cstr("").i32(0).finish()).await;
+        client.send(b'S', &[]).await;
+        let messages = client.until(|tag| tag == b'Z').await;
+        assert_eq!(tags(&messages), "12DCZ");
+        assert_eq!(columns(&messages[2].1), vec![Some(b"S4".to_vec())]);
+
+        // 描述语句只按投影推断，不执行查询
+        let executed = server.engine.get_query_stats().await.unwrap().total_queries;
+        client.send(b'P', &Writer::new().cstr("derived").cstr("SELECT * FROM (SELECT id FROM trades WHERE id > $1) t").i16(0).finish()).await;
+        client.send(b'D', &Writer::new().bytes(b"S").cstr("derived").finish()).await;
+        client.send(b'P', &Writer::new().cstr("upper").cstr("SELECT upper(symbol) AS name FROM trades WHERE id = $1").i16(0).finish()).await;
+        client.send(b'D', &Writer::new().bytes(b"S").cstr("upper").finish()).await;
+        client.send(b'S', &[]).await;
+        let messages = client.until(|tag| tag == b'Z').await;
+        assert_eq!(tags(&messages), "1tn1tTZ");
+        assert_eq!(field_names(&messages[5].1), vec![("name".to_string(), oid::TEXT)]);
+        assert_eq!(server.engine.get_query_stats().await.unwrap().total_queries, executed);
+    }
+
+    #[tokio::test]
+    async fn test_authentication_and_cancel_keys() {
+        let auth = AuthConfig { enabled: true, api_keys: vec!["secret-key".to_string()], ..Default::default() };
+        let server = server(auth).await;
+        let (_, messages) = Client::connect(&server, Some("wrong")).await;
+        assert_eq!(messages.last().unwrap().0, b'E');
+        assert!(String::from_utf8_lossy(&messages.last().unwrap().1).contains("28P01"));
+        let (_, messages) = Client::connect(&server, Some("secret-key")).await;
+        assert_eq!(messages.last().unwrap().0, b'Z');
+
+        let keys = BackendKeys::default();
+        let (process_id, secret, running) = keys.register();
+        *running.lock() = Some("q1".to_string());
+        assert_eq!(keys.running_query(process_id, secret), Some("q1".to_string()));
+        assert_eq!(keys.running_query(process_id, secret.wrapping_add(1)), None);
+        keys.unregister(process_id);
+        assert_eq!(keys.running_query(process_id, secret), None);
+    }
+
+    #[test]
+    fn test_statement_splitting() {
+        assert_eq!(
+            split_statements("SELECT ';' AS a; -- c;\nSELECT $$x;y$$; /* ; */ SELECT \"a;b\" FROM t;"),
+            vec!["SELECT ';' AS a", "-- c;\nSELECT $$x;y$$", "/* ; */ SELECT \"a;b\" FROM t"],
+        );
+        assert_eq!(placeholder_count("SELECT $1, '$3' FROM t WHERE a = $2"), 2);
+        assert_eq!(command_tag("INSERT INTO t VALUES (1)", 1), "INSERT 0 1");
+        assert_eq!(command_tag("create or replace function f", 0), "CREATE FUNCTION");
+        assert_eq!(command_tag("CREATE MATERIALIZED VIEW v AS SELECT 1", 0), "CREATE MATERIALIZED VIEW");
+        assert_eq!(parse_set("SESSION TimeZone TO 'UTC'"), Some(("timezone".to_string(), "UTC".to_string())));
+        assert_eq!(server_version_num("14.2"), "140002");
+    }
+
+    #[test]
+    fn test_value_encoding() {
+        for text in ["0", "1", "-12.5", "10000", "0.0001", "123456789.000120", "-0.5"] {
+            let encoded = encode_numeric(text).unwrap();
+            assert_eq!(decode_numeric(&encoded).unwrap(), text, "{}", text);
+        }
+        assert_eq!(encode_numeric("10000").unwrap(), Writer::new().i16(1).i16(1).u16(0).i16(0).i16(1).finish());
+
+        let ts = Value::Timestamp(TimestampNs::from_nanos(POSTGRES_EPOCH_MICROS * 1000 + 1_500_000));
+        assert_eq!(encode_binary(&ts, oid::TIMESTAMPTZ).unwrap(), 1500i64.to_be_bytes());
+        assert_eq!(encode_text(&ts).unwrap(), "2000-01-01 00:00:00.001500+00");
+        assert_eq!(encode_text(&Value::Binary(vec![0xde, 0xad])).unwrap(), "\\xdead");
+        assert_eq!(encode_text(&Value::Float64(f64::NEG_INFINITY)).unwrap(), "-Infinity");
+
+        assert_eq!(decode_parameter(Some(b"2000-01-01 00:00:01+00"), 0, oid::TIMESTAMPTZ, None).unwrap(),
+            Value::Timestamp(TimestampNs::from_nanos(POSTGRES_EPOCH_MICROS * 1000 + 1_000_000_000)));
+        assert_eq!(decode_parameter(Some(&7i32.to_be_bytes()), 1, oid::INT4, None).unwrap(), Value::Int64(7));
+        assert_eq!(decode_parameter(Some(b"\\x0102"), 0, oid::BYTEA, None).unwrap(), Value::Binary(vec![1, 2]));
+        assert_eq!(decode_parameter(Some(b"42"), 0, oid::UNKNOWN, Some(ColumnType::Int64)).unwrap(), Value::Int64(42));
+        assert_eq!(decode_parameter(None, 0, oid::INT8, None).unwrap(), Value::Null);
+    }
+}
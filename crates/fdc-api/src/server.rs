@@ -1,10 +1,14 @@
 //! API server management
 
-use crate::{config::ApiConfig, errors::ApiResult};
+use crate::{config::ApiConfig, errors::{ApiError, ApiResult}, ingestion::QueryEngineListener, postgres::PostgresServer};
 use axum::{
+    extract::{Path, Query, State},
     routing::{get, post},
     Router,
 };
+use fdc_ingestion::WriteListener;
+use fdc_query::QueryEngine;
+use futures::StreamExt;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tower::ServiceBuilder;
@@ -12,17 +16,26 @@ use tower_http::{
     cors::CorsLayer,
     trace::TraceLayer,
 };
-use tracing::info;
+use tracing::{error, info};
 
 /// API服务器配置
 pub type ServerConfig = crate::config::ServerConfig;
 
+/// 处理器共享的状态
+#[derive(Clone, Default)]
+pub struct AppState {
+    /// 查询引擎（未配置时查询端点返回服务不可用）
+    pub query_engine: Option<Arc<QueryEngine>>,
+}
+
 /// API服务器
 pub struct ApiServer {
     /// 配置
     config: Arc<ApiConfig>,
     /// 路由器
     router: Option<Router>,
+    /// 处理器共享的状态
+    state: AppState,
 }
 
 impl ApiServer {
@@ -31,9 +44,23 @@ impl ApiServer {
         Self {
             config: Arc::new(config),
             router: None,
+            state: AppState::default(),
         }
     }
     
+    /// 设置处理查询请求的查询引擎
+    pub fn with_query_engine(mut self, engine: Arc<QueryEngine>) -> Self {
+        self.state.query_engine = Some(engine);
+        self
+    }
+    
+    /// 数据接入的写入监听器，挂到`BatchProcessor::with_write_listener`后接入批次会使查询缓存
+    /// 失效并增量维护物化视图；未配置查询引擎时返回None
+    pub fn ingestion_listener(&self) -> Option<Arc<dyn WriteListener>> {
+        self.state.query_engine.clone()
+            .map(|engine| Arc::new(QueryEngineListener::new(engine)) as Arc<dyn WriteListener>)
+    }
+    
     /// 构建路由器
     pub fn build_router(&mut self) -> ApiResult<()> {
         let router = Router::new()
@@ -46,6 +73,14 @@ impl ApiServer {
             
             // 查询端点
             .route("/query", post(query_handler))
+            .route("/query/stream", post(stream_query_handler))
+            
+            // 服务端游标翻页
+            .route("/cursors", post(declare_cursor_handler))
+            .route("/cursors/:name", get(fetch_cursor_handler).delete(close_cursor_handler))
+            
+            // WebSocket流式查询
+            .route(&self.config.websocket.endpoint, get(crate::websocket::websocket_handler))
             
             // 数据插入端点
             .route("/insert", post(insert_handler))
@@ -58,7 +93,8 @@ impl ApiServer {
                 ServiceBuilder::new()
                     .layer(TraceLayer::new_for_http())
                     .layer(CorsLayer::permissive())
-            );
+            )
+            .with_state(self.state.clone());
         
         self.router = Some(router);
         Ok(())
@@ -72,7 +108,19 @@ impl ApiServer {
         
         let router = self.router.take().unwrap();
         let addr = format!("{}:{}", self.config.server.host, self.config.rest.port);
-        
+
+        // PostgreSQL线协议与REST共用同一个查询引擎
+        if let (true, Some(engine)) = (self.config.postgres.enabled, self.state.query_engine.clone()) {
+            let postgres = PostgresServer::new(engine, self.config.postgres.clone())?
+                .with_auth(self.config.auth.clone());
+            let host = self.config.server.host.clone();
+            tokio::spawn(async move {
+                if let Err(e) = postgres.start(&host).await {
+                    error!("PostgreSQL server stopped: {}", e);
+                }
+            });
+        }
+
         info!("Starting API server on {}", addr);
         
         let listener = TcpListener::bind(&addr).await
@@ -138,51 +186,129 @@ async fn version_handler() -> axum::Json<serde_json::Value> {
 }
 
 /// 查询处理器
+///
+/// `options.explain`为true时以剖析模式执行，`plan`返回带实际指标的文本计划，
+/// `profile`返回其JSON形式。
 async fn query_handler(
+    State(state): State<AppState>,
     axum::Json(request): axum::Json<crate::models::QueryRequest>,
-) -> Result<axum::Json<crate::models::ApiResponse<crate::models::QueryResponse>>, crate::errors::ApiError> {
-    use crate::models::{QueryResponse, ColumnInfo, QueryStats};
-    use std::collections::HashMap;
+) -> Result<axum::Json<crate::models::ApiResponse<crate::models::QueryResponse>>, ApiError> {
+    use crate::models::{row_to_json, value_type_name, QueryResponse, ColumnInfo, QueryStats};
+    use std::collections::BTreeMap;
     
-    // 简化实现：模拟查询处理
+    let engine = state.query_engine.as_ref().ok_or(ApiError::ServiceUnavailable)?;
     info!("Processing query: {}", request.query);
     
-    let response = QueryResponse {
-        results: vec![
-            {
-                let mut row = HashMap::new();
-                row.insert("symbol".to_string(), serde_json::Value::String("AAPL".to_string()));
-                row.insert("price".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(150.25).unwrap()));
-                row
-            }
-        ],
-        columns: vec![
-            ColumnInfo {
-                name: "symbol".to_string(),
-                data_type: "string".to_string(),
-                nullable: false,
-                description: Some("Stock symbol".to_string()),
-            },
-            ColumnInfo {
-                name: "price".to_string(),
-                data_type: "float64".to_string(),
+    let context = request.execution_context();
+    let options = request.options.unwrap_or_default();
+    
+    let (result, plan, profile) = if options.explain == Some(true) {
+        let (result, explained) = engine.execute_profiled(&request.query, context).await?;
+        (result, Some(explained.to_text()), Some(explained.to_json()?))
+    } else {
+        (engine.execute_sql_with_context(&request.query, context).await?, None, None)
+    };
+    if let Some(error) = result.error {
+        return Err(ApiError::query(error));
+    }
+    
+    // 列信息：类型取第一个非空值，出现空值即可空
+    let mut columns: BTreeMap<&str, ColumnInfo> = BTreeMap::new();
+    for row in &result.rows {
+        for (name, value) in row {
+            let column = columns.entry(name.as_str()).or_insert_with(|| ColumnInfo {
+                name: name.clone(),
+                data_type: "null".to_string(),
                 nullable: false,
-                description: Some("Stock price".to_string()),
-            },
-        ],
+                description: None,
+            });
+            match value {
+                fdc_core::types::Value::Null => column.nullable = true,
+                value if column.data_type == "null" => column.data_type = value_type_name(value).to_string(),
+                _ => {}
+            }
+        }
+    }
+    
+    let response = QueryResponse {
+        results: result.rows.iter().map(row_to_json).collect(),
+        columns: columns.into_values().collect(),
         stats: QueryStats {
-            execution_time_ms: 10,
-            rows_returned: 1,
-            rows_scanned: 1000,
-            memory_used: 1024,
-            cpu_time_us: 5000,
+            execution_time_ms: result.execution_time_us / 1000,
+            rows_returned: result.rows.len() as u64,
+            rows_scanned: result.stats.rows_scanned,
+            memory_used: result.stats.memory_used,
+            cpu_time_us: result.execution_time_us,
         },
-        plan: None,
+        plan,
+        profile,
     };
     
     Ok(axum::Json(crate::models::ApiResponse::success(response)))
 }
 
+/// 流式查询处理器：以NDJSON逐批返回结果，不在内存中缓冲整个结果集
+async fn stream_query_handler(
+    State(state): State<AppState>,
+    axum::Json(request): axum::Json<crate::models::QueryRequest>,
+) -> Result<axum::response::Response, ApiError> {
+    let engine = state.query_engine.clone().ok_or(ApiError::ServiceUnavailable)?;
+    info!("Streaming query: {}", request.query);
+    
+    let frames = crate::streaming::query_frames(engine, request).await?;
+    let body = axum::body::Body::from_stream(
+        crate::streaming::ndjson(frames).map(Ok::<_, std::convert::Infallible>),
+    );
+    axum::response::Response::builder()
+        .header(axum::http::header::CONTENT_TYPE, "application/x-ndjson")
+        .body(body)
+        .map_err(|e| ApiError::internal(e.to_string()))
+}
+
+/// REST游标所属的会话
+const REST_CURSOR_SESSION: &str = "rest";
+
+/// REST游标默认页大小
+const DEFAULT_FETCH_COUNT: usize = 1000;
+
+/// 声明服务端游标，返回用于翻页的游标名
+async fn declare_cursor_handler(
+    State(state): State<AppState>,
+    axum::Json(request): axum::Json<crate::models::QueryRequest>,
+) -> Result<axum::Json<crate::models::ApiResponse<crate::models::CursorResponse>>, ApiError> {
+    let engine = state.query_engine.as_ref().ok_or(ApiError::ServiceUnavailable)?;
+    let cursor = format!("c{}", uuid::Uuid::new_v4().simple());
+    let context = request.execution_context().with_session_id(REST_CURSOR_SESSION.to_string());
+    engine.declare_cursor(&cursor, &request.query, context).await?;
+    Ok(axum::Json(crate::models::ApiResponse::success(crate::models::CursorResponse { cursor })))
+}
+
+/// 从游标读取下一页
+async fn fetch_cursor_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<crate::models::FetchParams>,
+) -> Result<axum::Json<crate::models::ApiResponse<crate::models::FetchResponse>>, ApiError> {
+    let engine = state.query_engine.as_ref().ok_or(ApiError::ServiceUnavailable)?;
+    let count = fdc_query::FetchCount::Count(params.count.unwrap_or(DEFAULT_FETCH_COUNT));
+    let (rows, done) = engine.cursors().fetch(Some(REST_CURSOR_SESSION), &name, count).await?;
+    let response = crate::models::FetchResponse {
+        results: rows.iter().map(crate::models::row_to_json).collect(),
+        done,
+    };
+    Ok(axum::Json(crate::models::ApiResponse::success(response)))
+}
+
+/// 关闭游标并取消其查询
+async fn close_cursor_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<axum::Json<crate::models::ApiResponse<serde_json::Value>>, ApiError> {
+    let engine = state.query_engine.as_ref().ok_or(ApiError::ServiceUnavailable)?;
+    engine.cursors().close(Some(REST_CURSOR_SESSION), &name)?;
+    Ok(axum::Json(crate::models::ApiResponse::success(serde_json::json!({ "closed": name }))))
+}
+
 /// 插入处理器
 async fn insert_handler(
     axum::Json(request): axum::Json<crate::models::InsertRequest>,
@@ -247,4 +373,81 @@ mod tests {
         assert_eq!(value["name"], crate::NAME);
         assert_eq!(value["version"], crate::VERSION);
     }
+
+    #[tokio::test]
+    async fn test_query_handler_explain() {
+        use crate::models::{QueryOptions, QueryRequest};
+        use fdc_core::types::Value;
+        use fdc_query::{ColumnDefinition, ColumnType, QueryEngineConfig, TableDefinition};
+        use fdc_storage::engine::StorageEngine;
+        use fdc_storage::engines::memory::MemoryEngine;
+        use std::collections::HashMap;
+        
+        let result = query_handler(State(AppState::default()), axum::Json(QueryRequest {
+            query: "SELECT 1".to_string(),
+            parameters: None,
+            options: None,
+        })).await;
+        assert!(matches!(result, Err(ApiError::ServiceUnavailable)));
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = Arc::new(QueryEngine::new(storage.clone(), QueryEngineConfig::default()));
+        engine.catalog().register_table(TableDefinition::new("quotes", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("bid", ColumnType::Float64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (0..10).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(i));
+            row.insert("bid".to_string(), Value::Float64(100.0 + i as f64));
+            row
+        }).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "quotes", &rows).await.unwrap();
+        
+        let state = AppState { query_engine: Some(engine) };
+        let request = QueryRequest {
+            query: "SELECT id, bid FROM quotes WHERE bid > $1".to_string(),
+            parameters: Some(HashMap::from([("1".to_string(), serde_json::json!(104.5))])),
+            options: Some(QueryOptions { explain: Some(true), ..Default::default() }),
+        };
+        let response = query_handler(State(state), axum::Json(request)).await.unwrap().0.data;
+        assert_eq!(response.results.len(), 5);
+        assert_eq!(response.columns.len(), 2);
+        assert_eq!(response.columns[0].data_type, "float64");
+        assert!(response.plan.unwrap().contains("actual rows=10"));
+        assert_eq!(response.profile.unwrap()["rows"], 5);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_handlers() {
+        use crate::models::{FetchParams, QueryRequest};
+        use fdc_core::types::Value;
+        use fdc_query::{ColumnDefinition, ColumnType, QueryEngineConfig, TableDefinition};
+        use fdc_storage::engine::StorageEngine;
+        use fdc_storage::engines::memory::MemoryEngine;
+        use std::collections::HashMap;
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = Arc::new(QueryEngine::new(storage.clone(), QueryEngineConfig::default()));
+        engine.catalog().register_table(TableDefinition::new("quotes", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (0..25).map(|i| HashMap::from([("id".to_string(), Value::Int64(i))])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "quotes", &rows).await.unwrap();
+        
+        let state = AppState { query_engine: Some(engine) };
+        let request = QueryRequest { query: "SELECT id FROM quotes".to_string(), parameters: None, options: None };
+        let cursor = declare_cursor_handler(State(state.clone()), axum::Json(request)).await.unwrap().0.data.cursor;
+        
+        let fetch = |count| fetch_cursor_handler(State(state.clone()), Path(cursor.clone()), Query(FetchParams { count: Some(count) }));
+        let page = fetch(20).await.unwrap().0.data;
+        assert_eq!(page.results.len(), 20);
+        assert!(!page.done);
+        let page = fetch(20).await.unwrap().0.data;
+        assert_eq!(page.results.len(), 5);
+        assert!(page.done);
+        
+        close_cursor_handler(State(state.clone()), Path(cursor.clone())).await.unwrap();
+        assert!(fetch(1).await.is_err());
+    }
 }
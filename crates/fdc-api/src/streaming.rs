@@ -0,0 +1,96 @@
+//! Streaming query results shared by the REST, WebSocket and gRPC layers
+
+use crate::{
+    errors::ApiResult,
+    models::{row_to_json, QueryRequest, StreamFrame},
+};
+use fdc_query::{QueryEngine, RowStream};
+use futures::{Stream, StreamExt};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// 流式执行查询，把行批次转换为输出帧
+///
+/// 每个批次对应一个`Batch`帧，最后以`End`或`Error`帧结束。帧流由查询引擎的有界通道驱动，
+/// 调用方按自身的发送速度拉取即可获得背压；提前丢弃帧流会取消查询。
+pub async fn query_frames(
+    engine: Arc<QueryEngine>,
+    request: QueryRequest,
+) -> ApiResult<impl Stream<Item = StreamFrame> + Send + 'static> {
+    let context = request.execution_context();
+    let stream = engine.execute_stream(&request.query, context).await?;
+    Ok(frames(stream))
+}
+
+/// 把行批次流转换为输出帧流
+pub fn frames(stream: RowStream) -> impl Stream<Item = StreamFrame> + Send + 'static {
+    let started = Instant::now();
+    futures::stream::unfold(Some((stream, 0u64)), move |state| async move {
+        let (mut stream, rows_returned) = state?;
+        match stream.next_batch().await {
+            Some(Ok(batch)) => {
+                let rows_returned = rows_returned + batch.rows.len() as u64;
+                let frame = StreamFrame::Batch {
+                    sequence: batch.sequence,
+                    rows: batch.rows.iter().map(row_to_json).collect(),
+                };
+                Some((frame, Some((stream, rows_returned))))
+            }
+            Some(Err(error)) => Some((StreamFrame::Error { message: error.to_string() }, None)),
+            None => Some((
+                StreamFrame::End {
+                    rows_returned,
+                    execution_time_ms: started.elapsed().as_millis() as u64,
+                },
+                None,
+            )),
+        }
+    })
+}
+
+/// 把帧流编码为NDJSON（每帧一行）
+pub fn ndjson(frames: impl Stream<Item = StreamFrame> + Send + 'static) -> impl Stream<Item = String> + Send + 'static {
+    frames.map(|frame| {
+        let mut line = serde_json::to_string(&frame).unwrap_or_else(|e| {
+            serde_json::json!({ "type": "error", "message": e.to_string() }).to_string()
+        });
+        line.push('\n');
+        line
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_core::types::Value;
+    use fdc_query::{ColumnDefinition, ColumnType, QueryEngineConfig, TableDefinition};
+    use fdc_storage::engine::StorageEngine;
+    use fdc_storage::engines::memory::MemoryEngine;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_query_frames() {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let config = QueryEngineConfig { stream_batch_size: 4, ..Default::default() };
+        let engine = Arc::new(QueryEngine::new(storage.clone(), config));
+        engine.catalog().register_table(TableDefinition::new("bars", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("close", ColumnType::Float64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (0..10).map(|i| HashMap::from([
+            ("id".to_string(), Value::Int64(i)),
+            ("close".to_string(), Value::Float64(i as f64)),
+        ])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "bars", &rows).await.unwrap();
+
+        let request = QueryRequest { query: "SELECT id, close FROM bars".to_string(), parameters: None, options: None };
+        let frames: Vec<StreamFrame> = query_frames(engine.clone(), request).await.unwrap().collect().await;
+        assert_eq!(frames.len(), 4);
+        assert!(matches!(&frames[0], StreamFrame::Batch { sequence: 0, rows } if rows.len() == 4));
+        assert!(matches!(frames.last(), Some(StreamFrame::End { rows_returned: 10, .. })));
+
+        let lines: Vec<String> = ndjson(futures::stream::iter(frames)).collect().await;
+        assert!(lines.iter().all(|line| line.ends_with('\n')));
+        assert!(lines[3].contains("\"type\":\"end\""));
+    }
+}
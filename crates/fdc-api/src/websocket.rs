@@ -1,6 +1,19 @@
 //! WebSocket API implementation
 
-use crate::{config::WebSocketConfig, errors::ApiResult};
+use crate::{
+    config::WebSocketConfig,
+    errors::ApiResult,
+    models::{QueryRequest, StreamFrame},
+    server::AppState,
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use futures::StreamExt;
 
 /// WebSocket服务器
 pub struct WebSocketServer {
@@ -21,6 +34,50 @@ impl WebSocketServer {
     }
 }
 
+/// WebSocket流式查询处理器
+///
+/// 客户端每发送一条JSON格式的查询请求，服务端按批推送`StreamFrame`消息，以`end`或`error`帧结束。
+/// 发送等待客户端接收，慢客户端会让查询扫描随之暂停；连接断开时取消正在执行的查询。
+pub async fn websocket_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: AppState) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        if !stream_request(&mut socket, &state, &text).await {
+            break;
+        }
+    }
+}
+
+/// 执行一条请求并推送结果帧，连接已断开返回false
+async fn stream_request(socket: &mut WebSocket, state: &AppState, text: &str) -> bool {
+    let frames = match (serde_json::from_str::<QueryRequest>(text), state.query_engine.clone()) {
+        (Ok(request), Some(engine)) => crate::streaming::query_frames(engine, request).await.map_err(|e| e.to_string()),
+        (Err(error), _) => Err(format!("Invalid query request: {}", error)),
+        (_, None) => Err("Query engine is not available".to_string()),
+    };
+    let mut frames = match frames {
+        Ok(frames) => frames.boxed(),
+        Err(message) => futures::stream::iter(vec![StreamFrame::Error { message }]).boxed(),
+    };
+    while let Some(frame) = frames.next().await {
+        let text = match serde_json::to_string(&frame) {
+            Ok(text) => text,
+            Err(error) => error.to_string(),
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,6 +91,9 @@ pub enum Error {
     
     #[error("Unimplemented: {feature}")]
     Unimplemented { feature: String },
+    
+    #[error("Cancelled: {reason}")]
+    Cancelled { reason: String },
 }
 
 impl Error {
@@ -237,6 +240,13 @@ impl Error {
         }
     }
     
+    /// 创建取消错误
+    pub fn cancelled(reason: impl Into<String>) -> Self {
+        Self::Cancelled {
+            reason: reason.into(),
+        }
+    }
+    
     /// 检查是否为可重试错误
     pub fn is_retryable(&self) -> bool {
         matches!(
@@ -275,6 +285,7 @@ impl Error {
             Error::ResourceExhausted { .. } => "RESOURCE_EXHAUSTED",
             Error::InvalidArgument { .. } => "INVALID_ARGUMENT",
             Error::Unimplemented { .. } => "UNIMPLEMENTED",
+            Error::Cancelled { .. } => "CANCELLED",
         }
     }
 }
@@ -1,7 +1,7 @@
 //! Batch processing for high-throughput data ingestion
 
 use crate::{config::BatchConfig, parser::ParsedData, validator::ValidationResult};
-use fdc_core::error::{Error, Result};
+use fdc_core::{error::{Error, Result}, types::Value};
 // use fdc_storage::engine::StorageEngine; // 暂时注释掉
 
 /// 简化的存储接口
@@ -10,6 +10,7 @@ pub trait SimpleStorage: Send + Sync {
     async fn put(&self, key: &[u8], value: &[u8]) -> Result<()>;
 }
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::{mpsc, RwLock};
@@ -107,6 +108,46 @@ impl BatchItem {
     pub fn is_valid(&self) -> bool {
         self.validation_result.is_valid
     }
+    
+    /// 写入的目标表：元数据中的`table`，没有时使用数据类型
+    pub fn table(&self) -> &str {
+        self.parsed_data.metadata.get("table")
+            .map(String::as_str)
+            .unwrap_or(&self.parsed_data.data_type)
+    }
+}
+
+/// 一个批次中写入成功的数据
+#[derive(Debug, Clone, Default)]
+pub struct BatchCommit {
+    /// 批量ID
+    pub batch_id: String,
+    /// 写入成功的表名（去重，按首次出现顺序）
+    pub tables: Vec<String>,
+    /// 各表写入成功的行（只包含值为结构体或映射的数据项）
+    pub rows: HashMap<String, Vec<HashMap<String, Value>>>,
+}
+
+impl BatchCommit {
+    fn record(&mut self, table: String, value: &Value) {
+        if let Value::Struct(fields) | Value::Map(fields) = value {
+            self.rows.entry(table.clone()).or_default().push(fields.clone());
+        }
+        if !self.tables.contains(&table) {
+            self.tables.push(table);
+        }
+    }
+}
+
+/// 批次提交后的异步钩子
+///
+/// 处理器在返回批次结果之前等待钩子完成，查询侧在其中使依赖这些表的缓存结果失效并增量维护
+/// 物化视图（fdc-api的`QueryEngineListener`即把提交交给查询引擎的`apply_ingested_rows`）。
+/// 钩子出错不影响已写入的数据，只记录日志。
+#[async_trait::async_trait]
+pub trait WriteListener: Send + Sync {
+    /// 批次中至少有一项写入成功
+    async fn batch_committed(&self, commit: &BatchCommit) -> Result<()>;
 }
 
 /// 批量处理器统计信息
@@ -171,6 +212,8 @@ pub struct BatchProcessor {
     current_batch: Arc<RwLock<Vec<BatchItem>>>,
     /// 批次超时定时器
     batch_timer: Arc<RwLock<Option<Instant>>>,
+    /// 写入监听器
+    write_listener: Option<Arc<dyn WriteListener>>,
 }
 
 impl BatchProcessor {
@@ -182,9 +225,16 @@ impl BatchProcessor {
             stats: Arc::new(RwLock::new(BatchProcessorStats::default())),
             current_batch: Arc::new(RwLock::new(Vec::new())),
             batch_timer: Arc::new(RwLock::new(None)),
+            write_listener: None,
         }
     }
     
+    /// 设置写入监听器
+    pub fn with_write_listener(mut self, listener: Arc<dyn WriteListener>) -> Self {
+        self.write_listener = Some(listener);
+        self
+    }
+    
     /// 添加项目到批次
     pub async fn add_item(&self, item: BatchItem) -> Result<Option<BatchResult>> {
         let mut batch = self.current_batch.write().await;
@@ -243,21 +293,37 @@ impl BatchProcessor {
         
         for item in items {
             let storage_engine = self.storage_engine.clone();
+            let committed = self.write_listener.as_ref()
+                .map(|_| (item.table().to_string(), item.parsed_data.value.clone()));
             let handle = tokio::spawn(async move {
                 Self::process_item(storage_engine, item).await
             });
-            handles.push(handle);
+            handles.push((committed, handle));
         }
         
         // 等待所有项目处理完成
-        for handle in handles {
+        let mut commit = BatchCommit { batch_id: result.batch_id.clone(), ..Default::default() };
+        for (committed, handle) in handles {
             match handle.await {
-                Ok(Ok(_)) => result.record_success(),
+                Ok(Ok(_)) => {
+                    result.record_success();
+                    if let Some((table, value)) = committed {
+                        commit.record(table, &value);
+                    }
+                }
                 Ok(Err(e)) => result.record_failure(e.to_string()),
                 Err(e) => result.record_failure(format!("Task join error: {}", e)),
             }
         }
         
+        if let Some(listener) = &self.write_listener {
+            if !commit.tables.is_empty() {
+                if let Err(e) = listener.batch_committed(&commit).await {
+                    tracing::warn!("Write listener failed for batch {}: {}", commit.batch_id, e);
+                }
+            }
+        }
+        
         result.processing_time_ms = start_time.elapsed().as_millis() as u64;
         
         // 更新统计信息
@@ -361,4 +427,47 @@ mod tests {
         assert_eq!(stats.failed_messages, 1);
         assert_eq!(stats.success_rate(), 0.75);
     }
+
+    struct NullStorage;
+
+    #[async_trait::async_trait]
+    impl SimpleStorage for NullStorage {
+        async fn put(&self, _key: &[u8], _value: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingListener {
+        commits: std::sync::Mutex<Vec<BatchCommit>>,
+    }
+
+    #[async_trait::async_trait]
+    impl WriteListener for RecordingListener {
+        async fn batch_committed(&self, commit: &BatchCommit) -> Result<()> {
+            self.commits.lock().unwrap().push(commit.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_listener_awaited_on_commit() {
+        let listener = Arc::new(RecordingListener::default());
+        let processor = BatchProcessor::new(BatchConfig::default(), Arc::new(NullStorage))
+            .with_write_listener(listener.clone());
+
+        let row = HashMap::from([("id".to_string(), Value::Int64(1))]);
+        let mut parsed = ParsedData::new(Value::Struct(row.clone()), "trade".to_string(), 0, 0);
+        parsed.metadata.insert("table".to_string(), "trades".to_string());
+        processor.add_item(BatchItem::new(parsed, ValidationResult::success(0))).await.unwrap();
+        let invalid = ParsedData::new(Value::Int64(2), "quote".to_string(), 0, 0);
+        processor.add_item(BatchItem::new(invalid, ValidationResult::failure(Vec::new(), 0))).await.unwrap();
+        processor.flush().await.unwrap();
+
+        // 只有写入成功的项进入提交
+        let commits = listener.commits.lock().unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].tables, vec!["trades".to_string()]);
+        assert_eq!(commits[0].rows["trades"], vec![row]);
+    }
 }
@@ -19,7 +19,7 @@ pub use receiver::{DataReceiver, ReceiverType};
 pub use parser::{DataParser, ParsedData};
 pub use validator::{DataValidator, ValidationRule, ValidationResult};
 pub use buffer::{DataBuffer, BufferStats};
-pub use batch::{BatchProcessor, BatchResult};
+pub use batch::{BatchProcessor, BatchResult, BatchCommit, WriteListener};
 pub use backpressure::BackpressureController;
 pub use recovery::{RecoveryManager, RecoveryStrategy};
 pub use metrics::IngestionMetrics;
@@ -263,13 +263,15 @@ async fn demo_query_engine() -> Result<(), Box<dyn std::error::Error>> {
     
     println!("\n📈 Query execution results:");
     for (i, sql) in queries.iter().enumerate() {
-        let result = engine.execute_sql(sql).await?;
-        println!("  Query {}: {} ({}ms, {} rows)", 
-            i + 1, 
-            if result.is_success() { "SUCCESS" } else { "FAILED" },
-            result.execution_time_ms(),
-            result.row_count()
-        );
+        match engine.execute_sql(sql).await {
+            Ok(result) => println!("  Query {}: {} ({}ms, {} rows)", 
+                i + 1, 
+                if result.is_success() { "SUCCESS" } else { "FAILED" },
+                result.execution_time_ms(),
+                result.row_count()
+            ),
+            Err(e) => println!("  Query {}: ERROR ({})", i + 1, e),
+        }
     }
     
     // 获取缓存统计信息
@@ -0,0 +1,591 @@
+//! Query admission control: named resource pools with concurrency limits, priority queueing and memory budgets
+
+use crate::executor::ExecutionContext;
+use fdc_core::error::{Error, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// 未配置路由时使用的资源池
+pub const DEFAULT_POOL: &str = "default";
+
+/// 默认排队上限
+pub const DEFAULT_MAX_QUEUED: usize = 1000;
+
+/// 默认排队超时（秒）
+pub const DEFAULT_QUEUE_TIMEOUT_SECS: u64 = 30;
+
+/// 查询超出内存预算时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MemoryPolicy {
+    /// 能落盘的算子（哈希连接等）改为落盘执行
+    #[default]
+    Spill,
+    /// 直接使查询失败
+    Fail,
+}
+
+/// 资源池配置
+///
+/// 例如低延迟、高优先级的`trading`池与并发低、内存受限的`research`池。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourcePool {
+    /// 池名
+    pub name: String,
+    /// 池内最大并发查询数
+    pub max_concurrency: usize,
+    /// 最大排队查询数，超出后直接拒绝
+    pub max_queued: usize,
+    /// 排队超时
+    pub queue_timeout: Duration,
+    /// 每个查询的内存预算（字节），None表示不限制
+    pub query_memory_limit: Option<usize>,
+    /// 超出内存预算时的处理方式
+    pub memory_policy: MemoryPolicy,
+    /// 优先级，数值越大越先获得空闲的执行槽位
+    pub priority: u8,
+}
+
+impl ResourcePool {
+    /// 创建不限内存、优先级为0的资源池
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            max_concurrency: crate::DEFAULT_MAX_CONCURRENT_QUERIES,
+            max_queued: DEFAULT_MAX_QUEUED,
+            queue_timeout: Duration::from_secs(DEFAULT_QUEUE_TIMEOUT_SECS),
+            query_memory_limit: None,
+            memory_policy: MemoryPolicy::default(),
+            priority: 0,
+        }
+    }
+
+    /// 设置池内最大并发查询数
+    pub fn with_max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = max;
+        self
+    }
+
+    /// 设置最大排队查询数
+    pub fn with_max_queued(mut self, max: usize) -> Self {
+        self.max_queued = max;
+        self
+    }
+
+    /// 设置排队超时
+    pub fn with_queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = timeout;
+        self
+    }
+
+    /// 设置每个查询的内存预算与超出后的处理方式
+    pub fn with_memory_limit(mut self, bytes: usize, policy: MemoryPolicy) -> Self {
+        self.query_memory_limit = Some(bytes);
+        self.memory_policy = policy;
+        self
+    }
+
+    /// 设置优先级
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// 准入控制配置：资源池与按用户、会话的路由
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdmissionConfig {
+    /// 资源池
+    pub pools: Vec<ResourcePool>,
+    /// 没有匹配路由时使用的池
+    pub default_pool: String,
+    /// 用户ID到池名
+    #[serde(default)]
+    pub user_pools: HashMap<String, String>,
+    /// 会话ID到池名（优先于用户路由）
+    #[serde(default)]
+    pub session_pools: HashMap<String, String>,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            pools: vec![ResourcePool::new(DEFAULT_POOL)],
+            default_pool: DEFAULT_POOL.to_string(),
+            user_pools: HashMap::new(),
+            session_pools: HashMap::new(),
+        }
+    }
+}
+
+impl AdmissionConfig {
+    /// 添加资源池，同名的池被替换
+    pub fn with_pool(mut self, pool: ResourcePool) -> Self {
+        self.pools.retain(|p| p.name != pool.name);
+        self.pools.push(pool);
+        self
+    }
+
+    /// 设置默认池
+    pub fn with_default_pool(mut self, pool: impl Into<String>) -> Self {
+        self.default_pool = pool.into();
+        self
+    }
+
+    /// 把用户的查询路由到池
+    pub fn route_user(mut self, user_id: impl Into<String>, pool: impl Into<String>) -> Self {
+        self.user_pools.insert(user_id.into(), pool.into());
+        self
+    }
+
+    /// 把会话的查询路由到池
+    pub fn route_session(mut self, session_id: impl Into<String>, pool: impl Into<String>) -> Self {
+        self.session_pools.insert(session_id.into(), pool.into());
+        self
+    }
+
+    /// 验证配置：池名唯一、路由目标存在、并发上限大于0
+    pub fn validate(&self) -> Result<()> {
+        let mut names = std::collections::HashSet::new();
+        for pool in &self.pools {
+            if !names.insert(pool.name.as_str()) {
+                return Err(Error::config(format!("Duplicate resource pool {}", pool.name)));
+            }
+            if pool.max_concurrency == 0 {
+                return Err(Error::config(format!("Resource pool {} must allow at least one query", pool.name)));
+            }
+        }
+        let targets = std::iter::once(&self.default_pool).chain(self.user_pools.values()).chain(self.session_pools.values());
+        for target in targets {
+            if !names.contains(target.as_str()) {
+                return Err(Error::config(format!("Unknown resource pool {}", target)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 单个查询的内存预算
+///
+/// 克隆的预算共享同一计数。物化中间结果的算子用`reserve`登记内存：
+/// `Fail`策略下超出预算立即报错；`Spill`策略下登记总是成功，
+/// 能落盘的算子按`spill_threshold`决定是否落盘。
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBudget {
+    state: Arc<BudgetState>,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    limit: Option<usize>,
+    policy: MemoryPolicy,
+    used: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// 不限制内存的预算
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// 上限为`limit`字节的预算
+    pub fn new(limit: usize, policy: MemoryPolicy) -> Self {
+        Self {
+            state: Arc::new(BudgetState { limit: Some(limit), policy, ..Default::default() }),
+        }
+    }
+
+    /// 内存上限
+    pub fn limit(&self) -> Option<usize> {
+        self.state.limit
+    }
+
+    /// 超出预算时的处理方式
+    pub fn policy(&self) -> MemoryPolicy {
+        self.state.policy
+    }
+
+    /// 当前登记的内存
+    pub fn used(&self) -> usize {
+        self.state.used.load(Ordering::Acquire)
+    }
+
+    /// 登记内存的峰值
+    pub fn peak(&self) -> usize {
+        self.state.peak.load(Ordering::Acquire)
+    }
+
+    /// 剩余预算，不限制时为None
+    pub fn remaining(&self) -> Option<usize> {
+        self.state.limit.map(|limit| limit.saturating_sub(self.used()))
+    }
+
+    /// 登记`bytes`字节；`Fail`策略下超出预算时返回错误且不登记
+    pub fn reserve(&self, bytes: usize) -> Result<()> {
+        let state = &self.state;
+        let previous = match (state.limit, state.policy) {
+            (Some(limit), MemoryPolicy::Fail) => state.used
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                    used.checked_add(bytes).filter(|total| *total <= limit)
+                })
+                .map_err(|used| Error::memory(format!(
+                    "Query memory budget exceeded: {} bytes requested, {} of {} bytes in use", bytes, used, limit
+                )))?,
+            _ => state.used.fetch_add(bytes, Ordering::AcqRel),
+        };
+        state.peak.fetch_max(previous + bytes, Ordering::AcqRel);
+        Ok(())
+    }
+
+    /// 释放登记的内存
+    pub fn release(&self, bytes: usize) {
+        let _ = self.state.used.fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| Some(used.saturating_sub(bytes)));
+    }
+
+    /// `Spill`策略下能落盘的算子在内存中保留的字节数上限（`default`与剩余预算中较小者）
+    pub fn spill_threshold(&self, default: usize) -> usize {
+        match (self.remaining(), self.policy()) {
+            (Some(remaining), MemoryPolicy::Spill) => default.min(remaining),
+            _ => default,
+        }
+    }
+}
+
+/// 资源池的运行状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// 池名
+    pub name: String,
+    /// 正在执行的查询数
+    pub running: usize,
+    /// 排队中的查询数
+    pub queued: usize,
+    /// 累计准入的查询数
+    pub admitted: u64,
+    /// 因排队已满被拒绝的查询数
+    pub rejected: u64,
+    /// 排队超时或排队时被取消的查询数
+    pub timed_out: u64,
+}
+
+#[derive(Debug, Default)]
+struct PoolCounters {
+    running: usize,
+    queued: usize,
+    admitted: u64,
+    rejected: u64,
+    timed_out: u64,
+}
+
+/// 排队中的查询
+struct Waiter {
+    pool: String,
+    sender: oneshot::Sender<AdmissionPermit>,
+}
+
+#[derive(Default)]
+struct AdmissionState {
+    /// 所有池正在执行的查询总数
+    running: usize,
+    pools: HashMap<String, PoolCounters>,
+    /// 按(优先级降序, 排队顺序)排列的等待队列
+    queue: BTreeMap<(Reverse<u8>, u64), Waiter>,
+    next_ticket: u64,
+}
+
+/// 准入控制器
+///
+/// 查询先按会话、用户路由到资源池；池内与全局（`max_concurrent_queries`）都有空闲槽位时立即执行，
+/// 否则进入等待队列。槽位释放时按优先级从高到低、同优先级先到先得地唤醒
+/// 所在池仍有空闲槽位的查询。
+pub struct AdmissionController {
+    config: AdmissionConfig,
+    pools: HashMap<String, ResourcePool>,
+    max_concurrent: usize,
+    state: Mutex<AdmissionState>,
+}
+
+impl AdmissionController {
+    /// 按配置创建准入控制器，`max_concurrent`为所有池合计的并发上限
+    ///
+    /// 配置应先经`AdmissionConfig::validate`检查；路由到不存在的池的查询在准入时报错。
+    pub fn new(config: AdmissionConfig, max_concurrent: usize) -> Self {
+        let pools = config.pools.iter().map(|pool| (pool.name.clone(), pool.clone())).collect();
+        Self {
+            config,
+            pools,
+            max_concurrent: max_concurrent.max(1),
+            state: Mutex::new(AdmissionState::default()),
+        }
+    }
+
+    /// 准入配置
+    pub fn config(&self) -> &AdmissionConfig {
+        &self.config
+    }
+
+    /// 查询所属的资源池：上下文显式指定的池、会话路由、用户路由，最后是默认池
+    pub fn route(&self, context: &ExecutionContext) -> Result<&ResourcePool> {
+        let name = context.resource_pool.as_ref()
+            .or_else(|| context.session_id.as_ref().and_then(|session| self.config.session_pools.get(session)))
+            .or_else(|| context.user_id.as_ref().and_then(|user| self.config.user_pools.get(user)))
+            .unwrap_or(&self.config.default_pool);
+        self.pools.get(name).ok_or_else(|| Error::not_found(format!("Resource pool {}", name)))
+    }
+
+    /// 为查询申请执行槽位，需要排队时等待到被唤醒、排队超时或查询被取消
+    pub async fn admit(self: &Arc<Self>, context: &ExecutionContext) -> Result<AdmissionPermit> {
+        let pool = self.route(context)?;
+        let (ticket, receiver) = {
+            let mut state = self.state.lock();
+            let global_free = state.running < self.max_concurrent;
+            let counters = state.pools.entry(pool.name.clone()).or_default();
+            // 槽位释放时会立即唤醒能执行的等待者，所以有空闲槽位时不会有应当先执行的排队查询
+            if global_free && counters.running < pool.max_concurrency {
+                return Ok(self.grant(&mut state, pool));
+            }
+            if counters.queued >= pool.max_queued {
+                counters.rejected += 1;
+                return Err(Error::resource_exhausted(format!(
+                    "resource pool {} queue is full ({} queries)", pool.name, pool.max_queued
+                )));
+            }
+            counters.queued += 1;
+            let ticket = (Reverse(pool.priority), state.next_ticket);
+            state.next_ticket += 1;
+            let (sender, receiver) = oneshot::channel();
+            state.queue.insert(ticket, Waiter { pool: pool.name.clone(), sender });
+            (ticket, receiver)
+        };
+
+        let outcome = tokio::select! {
+            permit = receiver => permit.map_err(|_| Error::internal("admission controller dropped a queued query")),
+            _ = tokio::time::sleep(pool.queue_timeout) => Err(Error::timeout(pool.queue_timeout.as_millis() as u64)),
+            error = context.cancellation.expired() => Err(error),
+        };
+        if outcome.is_err() {
+            // 超时或取消时离开队列；若恰好已被唤醒，许可随接收端一起析构并归还槽位
+            let mut state = self.state.lock();
+            if state.queue.remove(&ticket).is_some() {
+                let counters = state.pools.entry(pool.name.clone()).or_default();
+                counters.queued -= 1;
+                counters.timed_out += 1;
+            }
+        }
+        outcome
+    }
+
+    /// 各资源池的运行状态，按配置顺序排列
+    pub fn status(&self) -> Vec<PoolStatus> {
+        let state = self.state.lock();
+        self.config.pools.iter().map(|pool| {
+            let counters = state.pools.get(&pool.name);
+            PoolStatus {
+                name: pool.name.clone(),
+                running: counters.map_or(0, |c| c.running),
+                queued: counters.map_or(0, |c| c.queued),
+                admitted: counters.map_or(0, |c| c.admitted),
+                rejected: counters.map_or(0, |c| c.rejected),
+                timed_out: counters.map_or(0, |c| c.timed_out),
+            }
+        }).collect()
+    }
+
+    /// 占用一个槽位并创建许可
+    fn grant(self: &Arc<Self>, state: &mut AdmissionState, pool: &ResourcePool) -> AdmissionPermit {
+        state.running += 1;
+        let counters = state.pools.entry(pool.name.clone()).or_default();
+        counters.running += 1;
+        counters.admitted += 1;
+        let memory = match pool.query_memory_limit {
+            Some(limit) => MemoryBudget::new(limit, pool.memory_policy),
+            None => MemoryBudget::unlimited(),
+        };
+        AdmissionPermit { controller: self.clone(), pool: pool.name.clone(), memory }
+    }
+
+    /// 归还槽位并唤醒能执行的等待者
+    fn release(self: &Arc<Self>, pool: &str) {
+        let mut woken = Vec::new();
+        {
+            let mut state = self.state.lock();
+            state.running = state.running.saturating_sub(1);
+            if let Some(counters) = state.pools.get_mut(pool) {
+                counters.running = counters.running.saturating_sub(1);
+            }
+            let tickets: Vec<_> = state.queue.keys().copied().collect();
+            for ticket in tickets {
+                if state.running >= self.max_concurrent {
+                    break;
+                }
+                let pool = &self.pools[&state.queue[&ticket].pool];
+                if state.pools.get(&pool.name).map_or(0, |c| c.running) >= pool.max_concurrency {
+                    continue;
+                }
+                let waiter = state.queue.remove(&ticket).expect("ticket is queued");
+                if let Some(counters) = state.pools.get_mut(&pool.name) {
+                    counters.queued -= 1;
+                }
+                woken.push((waiter.sender, self.grant(&mut state, pool)));
+            }
+        }
+        // 在锁外交付许可：等待者已离开时许可被退回并析构，析构会再次获取锁
+        for (sender, permit) in woken {
+            let _ = sender.send(permit);
+        }
+    }
+}
+
+impl std::fmt::Debug for AdmissionController {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdmissionController")
+            .field("pools", &self.config.pools)
+            .field("max_concurrent", &self.max_concurrent)
+            .finish()
+    }
+}
+
+/// 执行槽位许可，析构时归还槽位
+#[derive(Debug)]
+pub struct AdmissionPermit {
+    controller: Arc<AdmissionController>,
+    pool: String,
+    memory: MemoryBudget,
+}
+
+impl AdmissionPermit {
+    /// 所在资源池
+    pub fn pool(&self) -> &str {
+        &self.pool
+    }
+
+    /// 查询的内存预算
+    pub fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory
+    }
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        self.controller.release(&self.pool);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(max_concurrent: usize) -> Arc<AdmissionController> {
+        let config = AdmissionConfig::default()
+            .with_pool(ResourcePool::new("trading").with_max_concurrency(2).with_priority(10))
+            .with_pool(
+                ResourcePool::new("research")
+                    .with_max_concurrency(1)
+                    .with_max_queued(1)
+                    .with_queue_timeout(Duration::from_millis(50))
+                    .with_memory_limit(1024, MemoryPolicy::Fail),
+            )
+            .route_user("quant", "research")
+            .route_session("desk-1", "trading");
+        Arc::new(AdmissionController::new(config, max_concurrent))
+    }
+
+    fn context(user: &str) -> ExecutionContext {
+        ExecutionContext::new(uuid::Uuid::new_v4().to_string()).with_user_id(user.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_routing_and_pool_limits() {
+        let controller = controller(10);
+        let research = controller.admit(&context("quant")).await.unwrap();
+        assert_eq!(research.pool(), "research");
+        assert_eq!(research.memory_budget().limit(), Some(1024));
+        let trading = controller.admit(&context("quant").with_session_id("desk-1".to_string())).await.unwrap();
+        assert_eq!(trading.pool(), "trading");
+        assert_eq!(controller.admit(&context("ops")).await.unwrap().pool(), DEFAULT_POOL);
+
+        // research池已满：第一个排队后超时，队列已满时第二个直接被拒绝
+        let queued = tokio::spawn({
+            let controller = controller.clone();
+            async move { controller.admit(&context("quant")).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(matches!(controller.admit(&context("quant")).await, Err(Error::ResourceExhausted { .. })));
+        assert!(matches!(queued.await.unwrap(), Err(Error::Timeout { .. })));
+
+        // 归还槽位后可以再次准入
+        drop(research);
+        assert!(controller.admit(&context("quant")).await.is_ok());
+        let status = controller.status();
+        let research = status.iter().find(|s| s.name == "research").unwrap();
+        assert_eq!((research.running, research.queued, research.rejected, research.timed_out), (0, 0, 1, 1));
+        assert!(AdmissionConfig::default().route_user("x", "missing").validate().is_err());
+        assert!(controller.admit(&context("ops").with_resource_pool("missing".to_string())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_priority_scheduling() {
+        let controller = controller(1);
+        let running = controller.admit(&context("ops")).await.unwrap();
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut handles = Vec::new();
+        for (name, context) in [
+            ("default", context("ops")),
+            ("trading", context("ops").with_resource_pool("trading".to_string())),
+        ] {
+            let controller = controller.clone();
+            let order_tx = order_tx.clone();
+            handles.push(tokio::spawn(async move {
+                let permit = controller.admit(&context).await.unwrap();
+                order_tx.send(name).unwrap();
+                drop(permit);
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // 后到的高优先级查询先获得槽位
+        drop(running);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(order_rx.recv().await, Some("trading"));
+        assert_eq!(order_rx.recv().await, Some("default"));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_while_queued() {
+        let controller = controller(1);
+        let _running = controller.admit(&context("ops")).await.unwrap();
+        let queued = context("ops");
+        let token = queued.cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            token.cancel("KILL QUERY");
+        });
+        assert!(matches!(controller.admit(&queued).await, Err(Error::Cancelled { .. })));
+        assert_eq!(controller.status()[0].queued, 0);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = MemoryBudget::new(100, MemoryPolicy::Fail);
+        budget.reserve(60).unwrap();
+        assert!(matches!(budget.reserve(50), Err(Error::Memory { .. })));
+        assert_eq!(budget.used(), 60);
+        budget.release(60);
+        assert_eq!((budget.used(), budget.peak()), (0, 60));
+
+        let budget = MemoryBudget::new(100, MemoryPolicy::Spill);
+        budget.reserve(80).unwrap();
+        assert_eq!(budget.spill_threshold(1 << 20), 20);
+        budget.reserve(80).unwrap();
+        assert_eq!(budget.spill_threshold(1 << 20), 0);
+        assert_eq!(MemoryBudget::unlimited().spill_threshold(64), 64);
+    }
+}
@@ -1,6 +1,12 @@
 //! Aggregate functions
 
-use fdc_core::{error::Result, types::Value};
+use crate::{
+    financial::{FinancialKind, FinancialState},
+    statistical::{StatisticalKind, StatisticalState},
+    udaf::UserAggregate,
+    udf::FunctionRegistry,
+};
+use fdc_core::{error::{Error, Result}, types::Value};
 use serde::{Deserialize, Serialize};
 
 /// 聚合函数类型
@@ -13,9 +19,78 @@ pub enum AggregateFunction {
     Max,
     First,
     Last,
+    /// VWAP、TWAP、OHLC等金融聚合
+    Financial(FinancialKind),
+    /// 方差、相关、分位数、近似不同值个数与直方图等统计聚合
+    Statistical(StatisticalKind),
+    /// 用户自定义聚合（只存在于查询执行期间，不参与序列化）
+    #[serde(skip)]
+    UserDefined(UserAggregate),
 }
 
 impl AggregateFunction {
+    /// 按SQL函数名查找聚合函数
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "COUNT" => Some(AggregateFunction::Count),
+            "SUM" => Some(AggregateFunction::Sum),
+            "AVG" => Some(AggregateFunction::Avg),
+            "MIN" => Some(AggregateFunction::Min),
+            "MAX" => Some(AggregateFunction::Max),
+            "FIRST" => Some(AggregateFunction::First),
+            "LAST" => Some(AggregateFunction::Last),
+            "VWAP" => Some(AggregateFunction::Financial(FinancialKind::Vwap)),
+            "TWAP" => Some(AggregateFunction::Financial(FinancialKind::Twap)),
+            "OHLC" => Some(AggregateFunction::Financial(FinancialKind::Ohlc)),
+            "FIRST_BY" => Some(AggregateFunction::Financial(FinancialKind::FirstBy)),
+            "LAST_BY" => Some(AggregateFunction::Financial(FinancialKind::LastBy)),
+            "SPREAD" => Some(AggregateFunction::Financial(FinancialKind::Spread)),
+            "WEIGHTED_AVG" => Some(AggregateFunction::Financial(FinancialKind::WeightedAvg)),
+            other => StatisticalKind::from_name(other).map(AggregateFunction::Statistical),
+        }
+    }
+
+    /// 参数个数（`COUNT(*)`除外）
+    pub fn arity(&self) -> usize {
+        match self {
+            AggregateFunction::Financial(kind) => kind.arity(),
+            AggregateFunction::Statistical(kind) => kind.arity(),
+            AggregateFunction::UserDefined(function) => function.signature().arg_types.len(),
+            _ => 1,
+        }
+    }
+
+    /// 是否只有原生执行器能计算（DataFusion后端没有对应实现）
+    pub fn is_native_only(&self) -> bool {
+        matches!(
+            self,
+            AggregateFunction::Financial(_) | AggregateFunction::Statistical(_) | AggregateFunction::UserDefined(_)
+        )
+    }
+
+    /// 按SQL函数名查找内置聚合，找不到时查找注册表中的用户自定义聚合
+    pub fn resolve(name: &str, functions: Option<&FunctionRegistry>) -> Option<Self> {
+        Self::from_name(name).or_else(|| {
+            functions?.get_aggregate(name).map(|f| AggregateFunction::UserDefined(UserAggregate::new(f)))
+        })
+    }
+
+    /// 创建可增量更新的聚合状态
+    pub fn accumulator(&self) -> Accumulator {
+        match self {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum => Accumulator::Sum(None),
+            AggregateFunction::Avg => Accumulator::Avg { sum: 0.0, count: 0 },
+            AggregateFunction::Min => Accumulator::Min(None),
+            AggregateFunction::Max => Accumulator::Max(None),
+            AggregateFunction::First => Accumulator::First(None),
+            AggregateFunction::Last => Accumulator::Last(None),
+            AggregateFunction::Financial(kind) => Accumulator::Financial(FinancialState::new(*kind)),
+            AggregateFunction::Statistical(kind) => Accumulator::Statistical(StatisticalState::new(*kind)),
+            AggregateFunction::UserDefined(function) => Accumulator::User { function: function.clone(), state: None },
+        }
+    }
+
     pub fn apply(&self, values: &[Value]) -> Result<Value> {
         match self {
             AggregateFunction::Count => Ok(Value::Int64(values.len() as i64)),
@@ -27,6 +102,9 @@ impl AggregateFunction {
                 fdc_core::error::Error::validation("No values for FIRST aggregate")),
             AggregateFunction::Last => values.last().cloned().ok_or_else(|| 
                 fdc_core::error::Error::validation("No values for LAST aggregate")),
+            AggregateFunction::Financial(kind) => FinancialState::evaluate(*kind, values),
+            AggregateFunction::Statistical(kind) => StatisticalState::evaluate(*kind, values),
+            AggregateFunction::UserDefined(function) => function.evaluate(values),
         }
     }
     
@@ -68,6 +146,160 @@ impl AggregateFunction {
     }
 }
 
+/// 可增量更新的聚合状态，用于物化视图维护
+///
+/// 与`apply`语义一致：忽略NULL输入，没有非NULL输入时COUNT为0、其余为NULL。
+/// 同一聚合的两个状态可以合并，用于汇总各分片、分区的部分结果。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Accumulator {
+    Count(i64),
+    Sum(Option<f64>),
+    Avg { sum: f64, count: u64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+    First(Option<Value>),
+    Last(Option<Value>),
+    /// 金融聚合的状态
+    Financial(FinancialState),
+    /// 统计聚合的状态（近似聚合为可合并的草图）
+    Statistical(StatisticalState),
+    /// 用户自定义聚合的状态（尚未累加任何输入时为None）
+    User { function: UserAggregate, state: Option<Value> },
+}
+
+impl Accumulator {
+    /// 累加一个输入值
+    pub fn update(&mut self, value: &Value) -> Result<()> {
+        if matches!(value, Value::Null) {
+            return Ok(());
+        }
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => *sum = Some(sum.unwrap_or(0.0) + numeric(value, "SUM")?),
+            Accumulator::Avg { sum, count } => {
+                *sum += numeric(value, "AVG")?;
+                *count += 1;
+            }
+            Accumulator::Min(current) => {
+                if current.as_ref().map_or(true, |c| value < c) {
+                    *current = Some(value.clone());
+                }
+            }
+            Accumulator::Max(current) => {
+                if current.as_ref().map_or(true, |c| value > c) {
+                    *current = Some(value.clone());
+                }
+            }
+            Accumulator::First(current) => {
+                if current.is_none() {
+                    *current = Some(value.clone());
+                }
+            }
+            Accumulator::Last(current) => *current = Some(value.clone()),
+            Accumulator::Financial(state) => state.update(value)?,
+            Accumulator::Statistical(state) => state.update(value)?,
+            Accumulator::User { function, state } => {
+                let current = match state.take() {
+                    Some(current) => current,
+                    None => function.init()?,
+                };
+                *state = Some(function.accumulate(current, std::slice::from_ref(value))?);
+            }
+        }
+        Ok(())
+    }
+
+    /// 把另一个同类聚合状态合并进来，`other`累加的输入视为排在当前输入之后
+    pub fn merge(&mut self, other: &Accumulator) -> Result<()> {
+        match (self, other) {
+            (Accumulator::Count(count), Accumulator::Count(other)) => *count += other,
+            (Accumulator::Sum(sum), Accumulator::Sum(other)) => {
+                if let Some(other) = other {
+                    *sum = Some(sum.unwrap_or(0.0) + other);
+                }
+            }
+            (Accumulator::Avg { sum, count }, Accumulator::Avg { sum: other_sum, count: other_count }) => {
+                *sum += other_sum;
+                *count += other_count;
+            }
+            (Accumulator::Min(current), Accumulator::Min(Some(other))) => {
+                if current.as_ref().map_or(true, |c| other < c) {
+                    *current = Some(other.clone());
+                }
+            }
+            (Accumulator::Max(current), Accumulator::Max(Some(other))) => {
+                if current.as_ref().map_or(true, |c| other > c) {
+                    *current = Some(other.clone());
+                }
+            }
+            (Accumulator::First(current), Accumulator::First(other)) => {
+                if current.is_none() {
+                    *current = other.clone();
+                }
+            }
+            (Accumulator::Last(current), Accumulator::Last(other)) => {
+                if other.is_some() {
+                    *current = other.clone();
+                }
+            }
+            (Accumulator::Min(_), Accumulator::Min(None)) | (Accumulator::Max(_), Accumulator::Max(None)) => {}
+            (Accumulator::Financial(state), Accumulator::Financial(other)) => state.merge(other)?,
+            (Accumulator::Statistical(state), Accumulator::Statistical(other)) => state.merge(other)?,
+            (Accumulator::User { function, state }, Accumulator::User { function: other_function, state: other })
+                if function == other_function =>
+            {
+                if let Some(other) = other {
+                    *state = Some(match state.take() {
+                        Some(current) => function.merge(current, other.clone())?,
+                        None => other.clone(),
+                    });
+                }
+            }
+            (current, other) => {
+                return Err(Error::validation(format!("Cannot merge aggregate states {:?} and {:?}", current, other)));
+            }
+        }
+        Ok(())
+    }
+
+    /// 当前的聚合结果
+    pub fn finish(&self) -> Result<Value> {
+        Ok(match self {
+            Accumulator::Count(count) => Value::Int64(*count),
+            Accumulator::Sum(sum) => sum.map(Value::Float64).unwrap_or(Value::Null),
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, count } => Value::Float64(sum / *count as f64),
+            Accumulator::Min(value) | Accumulator::Max(value)
+            | Accumulator::First(value) | Accumulator::Last(value) => value.clone().unwrap_or(Value::Null),
+            Accumulator::Financial(state) => state.finish(),
+            Accumulator::Statistical(state) => state.finish(),
+            Accumulator::User { state: None, .. } => Value::Null,
+            Accumulator::User { function, state: Some(state) } => function.finalize(state.clone())?,
+        })
+    }
+}
+
+/// 聚合调用在一行上的输入：`COUNT(*)`没有参数时为true，单参数为参数值，
+/// 多参数为参数数组（任一参数为NULL时整个输入为NULL，不参与聚合）
+pub fn aggregate_input(mut values: Vec<Value>) -> Value {
+    match values.len() {
+        0 => Value::Bool(true),
+        1 => values.remove(0),
+        _ if values.iter().any(|v| matches!(v, Value::Null)) => Value::Null,
+        _ => Value::Array(values),
+    }
+}
+
+fn numeric(value: &Value, function: &str) -> Result<f64> {
+    match value {
+        Value::Int32(v) => Ok(*v as f64),
+        Value::Int64(v) => Ok(*v as f64),
+        Value::Float32(v) => Ok(*v as f64),
+        Value::Float64(v) => Ok(*v),
+        _ => Err(fdc_core::error::Error::validation(format!("{} requires numeric values", function))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,4 +317,48 @@ mod tests {
         let result = AggregateFunction::Sum.apply(&values).unwrap();
         assert_eq!(result, Value::Float64(6.0));
     }
+
+    #[test]
+    fn test_accumulator_matches_apply() {
+        let values = vec![Value::Int64(4), Value::Null, Value::Int64(1), Value::Int64(7)];
+        let non_null: Vec<Value> = values.iter().filter(|v| !matches!(v, Value::Null)).cloned().collect();
+        for function in [AggregateFunction::Count, AggregateFunction::Sum, AggregateFunction::Avg,
+                         AggregateFunction::Min, AggregateFunction::Max, AggregateFunction::First, AggregateFunction::Last] {
+            let mut accumulator = function.accumulator();
+            for value in &values {
+                accumulator.update(value).unwrap();
+            }
+            assert_eq!(accumulator.finish().unwrap(), function.apply(&non_null).unwrap(), "{:?}", function);
+        }
+        assert_eq!(AggregateFunction::Max.accumulator().finish().unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_merge_accumulators() {
+        let values = vec![Value::Int64(4), Value::Int64(1), Value::Null, Value::Int64(7), Value::Int64(2)];
+        let non_null: Vec<Value> = values.iter().filter(|v| !matches!(v, Value::Null)).cloned().collect();
+        for function in [AggregateFunction::Count, AggregateFunction::Sum, AggregateFunction::Avg,
+                         AggregateFunction::Min, AggregateFunction::Max, AggregateFunction::First, AggregateFunction::Last] {
+            // 按分片分别累加后合并，结果与整体计算一致
+            let (mut left, mut right) = (function.accumulator(), function.accumulator());
+            for value in &values[..2] {
+                left.update(value).unwrap();
+            }
+            for value in &values[2..] {
+                right.update(value).unwrap();
+            }
+            left.merge(&right).unwrap();
+            left.merge(&function.accumulator()).unwrap();
+            assert_eq!(left.finish().unwrap(), function.apply(&non_null).unwrap(), "{:?}", function);
+        }
+        assert!(AggregateFunction::Sum.accumulator().merge(&AggregateFunction::Count.accumulator()).is_err());
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(AggregateFunction::from_name("count"), Some(AggregateFunction::Count));
+        assert_eq!(AggregateFunction::from_name("upper"), None);
+        assert_eq!(AggregateFunction::from_name("vwap"), Some(AggregateFunction::Financial(FinancialKind::Vwap)));
+        assert_eq!(AggregateFunction::from_name("last_by").map(|f| f.arity()), Some(2));
+    }
 }
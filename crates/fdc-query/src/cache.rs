@@ -1,11 +1,19 @@
 //! Query result caching system
 
+use crate::catalog::TableWrite;
+use crate::cost::{AccessPath, TimeRange};
 use crate::executor::ExecutionResult;
 use fdc_core::error::Result;
+use parking_lot::Mutex;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 
+/// 待处理的写入通知上限，超过后合并为表级失效
+pub const MAX_PENDING_WRITES: usize = 1024;
+
 /// 缓存策略
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CachePolicy {
@@ -25,6 +33,9 @@ pub struct CacheStats {
     pub hits: u64,
     pub misses: u64,
     pub evictions: u64,
+    /// 因表写入而失效的条目数
+    #[serde(default)]
+    pub invalidations: u64,
     pub size: usize,
     pub capacity: usize,
 }
@@ -36,12 +47,143 @@ impl CacheStats {
     }
 }
 
+/// 缓存结果依赖的表及时间范围
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheDependencies {
+    /// 表名（小写） -> 读取的时间范围，None表示依赖整张表
+    pub tables: HashMap<String, Option<TimeRange>>,
+}
+
+impl CacheDependencies {
+    /// 根据语句引用的表（每次引用出现一次）和优化器选定的访问路径计算依赖
+    ///
+    /// 只有表的每次引用都有同一时间列上的剪枝范围时才记录范围，否则依赖整张表。
+    pub fn from_relations(relations: &[String], access_paths: &[AccessPath]) -> Self {
+        let mut tables = HashMap::new();
+        for table in relations {
+            let table = table.to_lowercase();
+            if tables.contains_key(&table) {
+                continue;
+            }
+            let references = relations.iter().filter(|r| r.to_lowercase() == table).count();
+            let paths: Vec<&AccessPath> = access_paths.iter().filter(|p| p.table.to_lowercase() == table).collect();
+            let range = if paths.len() == references {
+                union_ranges(paths.iter().map(|p| p.time_range.as_ref()))
+            } else {
+                None
+            };
+            tables.insert(table, range);
+        }
+        Self { tables }
+    }
+
+    /// 是否依赖指定表
+    pub fn depends_on(&self, table: &str) -> bool {
+        self.tables.contains_key(&table.to_lowercase())
+    }
+
+    /// 写入是否可能改变依赖的数据
+    pub fn affected_by(&self, write: &TableWrite) -> bool {
+        match self.tables.get(&write.table) {
+            None => false,
+            Some(None) => true,
+            Some(Some(range)) => match write.time_ranges.get(&range.column) {
+                // 写入范围未知时保守地视为重叠
+                None => true,
+                Some((start, end)) => {
+                    *end >= range.start.unwrap_or(i64::MIN) && *start <= range.end.unwrap_or(i64::MAX)
+                }
+            },
+        }
+    }
+}
+
+/// 同一时间列上范围的并集，任一为None或列不同时返回None
+fn union_ranges<'a>(ranges: impl Iterator<Item = Option<&'a TimeRange>>) -> Option<TimeRange> {
+    let mut union: Option<TimeRange> = None;
+    for range in ranges {
+        let range = range?;
+        union = Some(match union {
+            None => range.clone(),
+            Some(current) if current.column == range.column => TimeRange {
+                column: current.column,
+                start: current.start.zip(range.start).map(|(a, b)| a.min(b)),
+                end: current.end.zip(range.end).map(|(a, b)| a.max(b)),
+                scanned_fraction: current.scanned_fraction.max(range.scanned_fraction),
+            },
+            Some(_) => return None,
+        });
+    }
+    union
+}
+
+/// 是否带有`/*+ NO_CACHE */`提示
+pub fn has_no_cache_hint(sql: &str) -> bool {
+    static HINT: OnceLock<Regex> = OnceLock::new();
+    HINT.get_or_init(|| Regex::new(r"(?is)/\*\+.*?\bNO_CACHE\b.*?\*/").expect("valid regex"))
+        .is_match(sql)
+}
+
+#[derive(Debug, Default)]
+struct InvalidationLog {
+    pending: Vec<TableWrite>,
+    /// 写入序号
+    sequence: u64,
+    /// 表名 -> 最近一次写入的序号
+    last_write: HashMap<String, u64>,
+}
+
+/// 缓存失效句柄
+///
+/// 写入路径（目录、数据接入）持有该句柄，写入时只记录通知而不等待缓存锁；
+/// 缓存在下一次访问时应用待处理的失效。
+#[derive(Debug, Clone, Default)]
+pub struct CacheInvalidator {
+    log: Arc<Mutex<InvalidationLog>>,
+}
+
+impl CacheInvalidator {
+    /// 记录一次表写入
+    pub fn invalidate(&self, write: &TableWrite) {
+        let mut log = self.log.lock();
+        log.sequence += 1;
+        let sequence = log.sequence;
+        log.last_write.insert(write.table.clone(), sequence);
+        log.pending.push(write.clone());
+        if log.pending.len() > MAX_PENDING_WRITES {
+            let mut tables: Vec<String> = log.pending.drain(..).map(|w| w.table).collect();
+            tables.sort();
+            tables.dedup();
+            log.pending = tables.into_iter().map(TableWrite::new).collect();
+        }
+    }
+
+    /// 当前写入序号
+    pub fn sequence(&self) -> u64 {
+        self.log.lock().sequence
+    }
+
+    fn take_pending(&self) -> Vec<TableWrite> {
+        std::mem::take(&mut self.log.lock().pending)
+    }
+
+    /// 序号`since`之后是否写入过任一依赖表
+    fn written_since(&self, dependencies: &CacheDependencies, since: u64) -> bool {
+        let log = self.log.lock();
+        dependencies.tables.keys().any(|table| log.last_write.get(table).is_some_and(|seq| *seq > since))
+    }
+}
+
 /// 缓存条目
 #[derive(Debug, Clone)]
 struct CacheEntry {
     result: ExecutionResult,
+    dependencies: CacheDependencies,
     created_at: Instant,
-    last_accessed: Instant,
+    /// 插入时的逻辑时钟
+    inserted: u64,
+    /// 最近访问时的逻辑时钟
+    last_accessed: u64,
     access_count: u64,
 }
 
@@ -50,6 +192,9 @@ pub struct QueryCache {
     policy: CachePolicy,
     capacity: usize,
     entries: HashMap<String, CacheEntry>,
+    invalidator: CacheInvalidator,
+    /// 逻辑时钟，用于确定LRU/FIFO顺序
+    clock: u64,
     stats: CacheStats,
 }
 
@@ -59,13 +204,34 @@ impl QueryCache {
             policy,
             capacity,
             entries: HashMap::new(),
+            invalidator: CacheInvalidator::default(),
+            clock: 0,
             stats: CacheStats { capacity, ..Default::default() },
         }
     }
-    
+
+    /// 供写入路径使用的失效句柄
+    pub fn invalidator(&self) -> CacheInvalidator {
+        self.invalidator.clone()
+    }
+
+    /// 当前写入序号，执行查询前获取并传给`put_with_dependencies`
+    pub fn write_sequence(&self) -> u64 {
+        self.invalidator.sequence()
+    }
+
     pub fn get(&mut self, query_hash: &str) -> Option<ExecutionResult> {
+        self.apply_pending();
+        if let CachePolicy::TTL(ttl) = self.policy {
+            if self.entries.get(query_hash).is_some_and(|entry| entry.created_at.elapsed() >= ttl) {
+                self.entries.remove(query_hash);
+                self.stats.evictions += 1;
+                self.stats.size = self.entries.len();
+            }
+        }
+        self.clock += 1;
         if let Some(entry) = self.entries.get_mut(query_hash) {
-            entry.last_accessed = Instant::now();
+            entry.last_accessed = self.clock;
             entry.access_count += 1;
             self.stats.hits += 1;
             Some(entry.result.clone())
@@ -74,34 +240,99 @@ impl QueryCache {
             None
         }
     }
-    
+
     pub fn put(&mut self, query_hash: String, result: ExecutionResult) {
-        if self.entries.len() >= self.capacity {
-            self.evict_one();
+        let sequence = self.write_sequence();
+        self.put_with_dependencies(query_hash, result, CacheDependencies::default(), sequence);
+    }
+
+    /// 缓存结果并记录依赖；`since`之后依赖表已被写入时结果可能已过期，不缓存并返回false
+    pub fn put_with_dependencies(
+        &mut self,
+        query_hash: String,
+        result: ExecutionResult,
+        dependencies: CacheDependencies,
+        since: u64,
+    ) -> bool {
+        self.apply_pending();
+        if self.capacity == 0 || self.invalidator.written_since(&dependencies, since) {
+            return false;
+        }
+        if !self.entries.contains_key(&query_hash) {
+            while self.entries.len() >= self.capacity {
+                self.evict_one();
+            }
         }
-        
+
+        self.clock += 1;
         let entry = CacheEntry {
             result,
+            dependencies,
             created_at: Instant::now(),
-            last_accessed: Instant::now(),
+            inserted: self.clock,
+            last_accessed: self.clock,
             access_count: 1,
         };
-        
+
         self.entries.insert(query_hash, entry);
         self.stats.size = self.entries.len();
+        true
+    }
+
+    /// 使受写入影响的条目失效，返回失效的条目数
+    pub fn invalidate(&mut self, write: &TableWrite) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| !entry.dependencies.affected_by(write));
+        let removed = before - self.entries.len();
+        self.stats.invalidations += removed as u64;
+        self.stats.size = self.entries.len();
+        removed
+    }
+
+    fn apply_pending(&mut self) {
+        for write in self.invalidator.take_pending() {
+            self.invalidate(&write);
+        }
     }
-    
+
+    /// 按缓存策略淘汰一个条目
     fn evict_one(&mut self) {
-        if let Some(key) = self.entries.keys().next().cloned() {
+        let victim = match self.policy {
+            CachePolicy::LRU => self.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_accessed)
+                .map(|(key, _)| key.clone()),
+            CachePolicy::LFU => self.entries.iter()
+                .min_by_key(|(_, entry)| (entry.access_count, entry.last_accessed))
+                .map(|(key, _)| key.clone()),
+            CachePolicy::FIFO => self.entries.iter()
+                .min_by_key(|(_, entry)| entry.inserted)
+                .map(|(key, _)| key.clone()),
+            CachePolicy::TTL(ttl) => {
+                // 先清理全部过期条目，没有过期条目时淘汰最早插入的
+                let before = self.entries.len();
+                self.entries.retain(|_, entry| entry.created_at.elapsed() < ttl);
+                let expired = before - self.entries.len();
+                if expired > 0 {
+                    self.stats.evictions += expired as u64;
+                    self.stats.size = self.entries.len();
+                    return;
+                }
+                self.entries.iter()
+                    .min_by_key(|(_, entry)| entry.inserted)
+                    .map(|(key, _)| key.clone())
+            }
+        };
+        if let Some(key) = victim {
             self.entries.remove(&key);
             self.stats.evictions += 1;
+            self.stats.size = self.entries.len();
         }
     }
-    
+
     pub fn stats(&self) -> &CacheStats {
         &self.stats
     }
-    
+
     pub fn clear(&mut self) {
         self.entries.clear();
         self.stats.size = 0;
@@ -111,19 +342,110 @@ impl QueryCache {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use crate::cost::AccessMethod;
 
     #[test]
     fn test_cache_basic_operations() {
         let mut cache = QueryCache::new(CachePolicy::LRU, 2);
-        
+
         let result = ExecutionResult::success(Vec::new(), 1000);
         cache.put("query1".to_string(), result.clone());
-        
+
         assert_eq!(cache.get("query1").unwrap().execution_time_us, 1000);
         assert_eq!(cache.get("query2"), None);
-        
+
         assert_eq!(cache.stats().hits, 1);
         assert_eq!(cache.stats().misses, 1);
     }
+
+    fn result(id: u64) -> ExecutionResult {
+        ExecutionResult::success(Vec::new(), id)
+    }
+
+    fn trades_since(start: i64) -> CacheDependencies {
+        let path = AccessPath {
+            table: "trades".to_string(),
+            qualifier: "trades".to_string(),
+            method: AccessMethod::SeqScan,
+            filters: Vec::new(),
+            time_range: Some(TimeRange { column: "ts".to_string(), start: Some(start), end: None, scanned_fraction: 0.5 }),
+            partitions: None,
+            table_rows: 0,
+            estimated_rows: 0.0,
+            cost: 0.0,
+        };
+        CacheDependencies::from_relations(&["trades".to_string()], &[path])
+    }
+
+    #[test]
+    fn test_eviction_policies() {
+        let mut lru = QueryCache::new(CachePolicy::LRU, 2);
+        lru.put("a".to_string(), result(1));
+        lru.put("b".to_string(), result(2));
+        lru.get("a");
+        lru.put("c".to_string(), result(3));
+        assert!(lru.get("a").is_some());
+        assert!(lru.get("b").is_none());
+
+        let mut lfu = QueryCache::new(CachePolicy::LFU, 2);
+        lfu.put("a".to_string(), result(1));
+        lfu.put("b".to_string(), result(2));
+        lfu.get("b");
+        lfu.get("b");
+        lfu.get("a");
+        lfu.put("c".to_string(), result(3));
+        assert!(lfu.get("a").is_none());
+        assert!(lfu.get("b").is_some());
+
+        let mut fifo = QueryCache::new(CachePolicy::FIFO, 2);
+        fifo.put("a".to_string(), result(1));
+        fifo.put("b".to_string(), result(2));
+        fifo.get("a");
+        fifo.put("c".to_string(), result(3));
+        assert!(fifo.get("a").is_none());
+        assert_eq!(fifo.stats().evictions, 1);
+
+        let mut ttl = QueryCache::new(CachePolicy::TTL(Duration::ZERO), 2);
+        ttl.put("a".to_string(), result(1));
+        assert!(ttl.get("a").is_none());
+    }
+
+    #[test]
+    fn test_write_invalidation() {
+        let mut cache = QueryCache::new(CachePolicy::LRU, 8);
+        let sequence = cache.write_sequence();
+        let dependencies = CacheDependencies::from_relations(&["quotes".to_string()], &[]);
+        assert!(cache.put_with_dependencies("quotes".to_string(), result(1), dependencies, sequence));
+        assert!(cache.put_with_dependencies("recent".to_string(), result(2), trades_since(1_000), sequence));
+        cache.put("other".to_string(), result(3));
+
+        // 写入早于查询范围的历史数据不影响结果
+        let invalidator = cache.invalidator();
+        invalidator.invalidate(&TableWrite::new("TRADES").with_time_range("ts", 0, 999));
+        assert!(cache.get("recent").is_some());
+        invalidator.invalidate(&TableWrite::new("trades").with_time_range("ts", 500, 1_000));
+        assert!(cache.get("recent").is_none());
+
+        invalidator.invalidate(&TableWrite::new("quotes"));
+        assert!(cache.get("quotes").is_none());
+        assert!(cache.get("other").is_some());
+        assert_eq!(cache.stats().invalidations, 2);
+
+        // 执行期间依赖表被写入的结果不缓存
+        let dependencies = CacheDependencies::from_relations(&["quotes".to_string()], &[]);
+        assert!(!cache.put_with_dependencies("quotes".to_string(), result(4), dependencies, sequence));
+    }
+
+    #[test]
+    fn test_dependencies_and_hint() {
+        // 表被引用两次但只有一条访问路径（例如子查询），依赖整张表
+        let relations = vec!["trades".to_string(), "trades".to_string()];
+        let dependencies = CacheDependencies::from_relations(&relations, &[]);
+        assert_eq!(dependencies.tables.get("trades"), Some(&None));
+        assert!(dependencies.depends_on("TRADES"));
+
+        assert!(has_no_cache_hint("SELECT /*+ NO_CACHE */ * FROM trades"));
+        assert!(has_no_cache_hint("SELECT /*+ parallel(4) no_cache */ 1"));
+        assert!(!has_no_cache_hint("SELECT /* NO_CACHE */ * FROM trades"));
+    }
 }
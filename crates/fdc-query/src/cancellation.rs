@@ -0,0 +1,353 @@
+//! Cooperative query cancellation, enforced timeouts and the running query registry
+
+use crate::executor::ExecutionContext;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use fdc_core::error::{Error, Result};
+use parking_lot::Mutex;
+use regex::Regex;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// 两次检查取消状态之间处理的行数
+pub const CHECK_INTERVAL: usize = 1024;
+
+/// 协作式取消令牌
+///
+/// 克隆的令牌共享同一状态。算子在逐行处理时调用`tick`，令牌被取消或超过截止时间后
+/// 返回错误，查询随之退出并释放中间结果；等待存储IO时用`run`与取消信号竞争。
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<TokenState>,
+}
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    reason: Mutex<Option<String>>,
+    /// 截止时间与对应的超时时长
+    deadline: OnceLock<(Instant, Duration)>,
+    ticks: AtomicUsize,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    /// 创建新的取消令牌
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从现在起计时，超过`timeout`后令牌失效；只有第一次调用生效
+    pub fn start_deadline(&self, timeout: Duration) {
+        if self.state.deadline.set((Instant::now() + timeout, timeout)).is_ok() {
+            self.state.notify.notify_waiters();
+        }
+    }
+
+    /// 截止时间
+    pub fn deadline(&self) -> Option<Instant> {
+        self.state.deadline.get().map(|(deadline, _)| *deadline)
+    }
+
+    /// 取消查询；重复取消保留第一次的原因
+    pub fn cancel(&self, reason: impl Into<String>) {
+        let mut current = self.state.reason.lock();
+        if current.is_none() {
+            *current = Some(reason.into());
+        }
+        drop(current);
+        self.state.cancelled.store(true, Ordering::Release);
+        self.state.notify.notify_waiters();
+    }
+
+    /// 是否已被取消
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Acquire)
+    }
+
+    /// 检查令牌：已取消返回取消错误，超过截止时间返回超时错误
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            let reason = self.state.reason.lock().clone().unwrap_or_else(|| "query cancelled".to_string());
+            return Err(Error::cancelled(reason));
+        }
+        if let Some((deadline, timeout)) = self.state.deadline.get() {
+            if Instant::now() >= *deadline {
+                return Err(Error::timeout(timeout.as_millis() as u64));
+            }
+        }
+        Ok(())
+    }
+
+    /// 记录处理了一行，每`CHECK_INTERVAL`行检查一次令牌
+    pub fn tick(&self) -> Result<()> {
+        if self.state.ticks.fetch_add(1, Ordering::Relaxed) % CHECK_INTERVAL == 0 {
+            self.check()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 等待令牌被取消或超时，返回对应的错误
+    pub async fn expired(&self) -> Error {
+        loop {
+            // 先注册等待再检查，避免错过检查与等待之间的通知
+            let notified = self.state.notify.notified();
+            if let Err(error) = self.check() {
+                return error;
+            }
+            match self.deadline() {
+                Some(deadline) => tokio::select! {
+                    _ = notified => {}
+                    _ = tokio::time::sleep_until(deadline.into()) => {}
+                },
+                None => notified.await,
+            }
+        }
+    }
+
+    /// 执行future，令牌先失效时丢弃future并返回取消或超时错误
+    pub async fn run<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        self.check()?;
+        tokio::select! {
+            biased;
+            error = self.expired() => Err(error),
+            result = future => result,
+        }
+    }
+}
+
+/// 正在执行的查询
+#[derive(Debug, Clone)]
+pub struct RunningQuery {
+    /// 查询ID
+    pub query_id: String,
+    /// SQL文本
+    pub sql: String,
+    /// 用户ID
+    pub user_id: Option<String>,
+    /// 会话ID
+    pub session_id: Option<String>,
+    /// 所在资源池
+    pub pool: Option<String>,
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    started: Instant,
+    /// 取消令牌
+    pub token: CancellationToken,
+}
+
+impl RunningQuery {
+    /// 根据执行上下文创建
+    pub fn new(sql: impl Into<String>, context: &ExecutionContext) -> Self {
+        Self {
+            query_id: context.query_id.clone(),
+            sql: sql.into(),
+            user_id: context.user_id.clone(),
+            session_id: context.session_id.clone(),
+            pool: context.resource_pool.clone(),
+            started_at: Utc::now(),
+            started: Instant::now(),
+            token: context.cancellation.clone(),
+        }
+    }
+
+    /// 已执行时间
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// 状态：`running`或已请求取消但尚未退出的`cancelling`
+    pub fn state(&self) -> &'static str {
+        if self.token.is_cancelled() {
+            "cancelling"
+        } else {
+            "running"
+        }
+    }
+}
+
+/// 正在执行的查询注册表
+#[derive(Debug, Default)]
+pub struct QueryRegistry {
+    queries: DashMap<String, RunningQuery>,
+}
+
+impl QueryRegistry {
+    /// 创建新的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记查询，返回的守卫在析构时注销查询（包括future被中途丢弃的情况）
+    pub fn register(self: &Arc<Self>, query: RunningQuery) -> Result<RunningQueryGuard> {
+        let query_id = query.query_id.clone();
+        match self.queries.entry(query_id.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => {
+                return Err(Error::already_exists(format!("query {}", query_id)));
+            }
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(query);
+            }
+        }
+        Ok(RunningQueryGuard { registry: self.clone(), query_id })
+    }
+
+    /// 取消查询，查询不存在时返回false
+    pub fn cancel(&self, query_id: &str, reason: impl Into<String>) -> bool {
+        match self.queries.get(query_id) {
+            Some(query) => {
+                query.token.cancel(reason);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 按开始时间排序的正在执行的查询
+    pub fn list(&self) -> Vec<RunningQuery> {
+        let mut queries: Vec<RunningQuery> = self.queries.iter().map(|entry| entry.value().clone()).collect();
+        queries.sort_by_key(|query| query.started);
+        queries
+    }
+
+    /// 获取查询
+    pub fn get(&self, query_id: &str) -> Option<RunningQuery> {
+        self.queries.get(query_id).map(|entry| entry.value().clone())
+    }
+
+    /// 正在执行的查询数
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    /// 是否没有正在执行的查询
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+}
+
+/// 注册表中查询的守卫
+#[derive(Debug)]
+pub struct RunningQueryGuard {
+    registry: Arc<QueryRegistry>,
+    query_id: String,
+}
+
+impl Drop for RunningQueryGuard {
+    fn drop(&mut self) {
+        self.registry.queries.remove(&self.query_id);
+    }
+}
+
+/// SQL级的查询管理命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryCommand {
+    /// `KILL QUERY <id>`
+    Kill {
+        /// 要取消的查询ID
+        query_id: String,
+    },
+    /// `SHOW QUERIES`
+    ShowQueries,
+}
+
+impl QueryCommand {
+    /// 识别KILL QUERY / SHOW QUERIES，其他语句返回None
+    pub fn parse(sql: &str) -> Option<Self> {
+        static KILL: OnceLock<Regex> = OnceLock::new();
+        static SHOW: OnceLock<Regex> = OnceLock::new();
+        let kill = KILL.get_or_init(|| {
+            Regex::new(r#"(?i)^\s*KILL\s+QUERY\s+(?:'([^']+)'|"([^"]+)"|([A-Za-z0-9_.:\-]+))\s*;?\s*$"#).expect("valid regex")
+        });
+        let show = SHOW.get_or_init(|| {
+            Regex::new(r"(?i)^\s*SHOW\s+(?:FULL\s+)?(?:QUERIES|PROCESSLIST)\s*;?\s*$").expect("valid regex")
+        });
+        if let Some(captures) = kill.captures(sql) {
+            let query_id = (1..=3).find_map(|i| captures.get(i))?.as_str().to_string();
+            return Some(Self::Kill { query_id });
+        }
+        show.is_match(sql).then_some(Self::ShowQueries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_cancel_and_deadline() {
+        let token = CancellationToken::new();
+        assert!(token.check().is_ok());
+
+        let clone = token.clone();
+        clone.cancel("killed by admin");
+        clone.cancel("second reason");
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(Error::Cancelled { reason }) if reason == "killed by admin"));
+
+        let token = CancellationToken::new();
+        token.start_deadline(Duration::ZERO);
+        token.start_deadline(Duration::from_secs(60));
+        assert!(matches!(token.check(), Err(Error::Timeout { duration_ms: 0 })));
+        assert!(token.tick().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_is_interrupted() {
+        let token = CancellationToken::new();
+        token.start_deadline(Duration::from_millis(20));
+        let outcome = token.run(async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        }).await;
+        assert!(matches!(outcome, Err(Error::Timeout { .. })));
+
+        let token = CancellationToken::new();
+        let killer = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            killer.cancel("KILL QUERY");
+        });
+        let outcome = token.run(std::future::pending::<Result<()>>()).await;
+        assert!(matches!(outcome, Err(Error::Cancelled { .. })));
+    }
+
+    #[test]
+    fn test_registry_guard() {
+        let registry = Arc::new(QueryRegistry::new());
+        let context = ExecutionContext::new("q1".to_string()).with_user_id("desk".to_string());
+        let guard = registry.register(RunningQuery::new("SELECT 1", &context)).unwrap();
+        assert!(registry.register(RunningQuery::new("SELECT 1", &context)).is_err());
+
+        let listed = registry.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].user_id.as_deref(), Some("desk"));
+        assert_eq!(listed[0].state(), "running");
+
+        assert!(registry.cancel("q1", "killed"));
+        assert!(context.cancellation.is_cancelled());
+        assert_eq!(registry.get("q1").unwrap().state(), "cancelling");
+        assert!(!registry.cancel("missing", "killed"));
+
+        drop(guard);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_parse_query_commands() {
+        assert_eq!(
+            QueryCommand::parse("KILL QUERY 'a1b2-c3';"),
+            Some(QueryCommand::Kill { query_id: "a1b2-c3".to_string() })
+        );
+        assert_eq!(
+            QueryCommand::parse("kill query 6f1c2d3e-0000-4000-8000-000000000001"),
+            Some(QueryCommand::Kill { query_id: "6f1c2d3e-0000-4000-8000-000000000001".to_string() })
+        );
+        assert_eq!(QueryCommand::parse("SHOW QUERIES"), Some(QueryCommand::ShowQueries));
+        assert_eq!(QueryCommand::parse("SELECT * FROM queries"), None);
+    }
+}
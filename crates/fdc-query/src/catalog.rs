@@ -0,0 +1,1211 @@
+//! Table catalog and row storage layout for the query engine
+
+use crate::{
+    cancellation::CancellationToken,
+    executor::split_conjunction,
+    expressions::{value_as_decimal, value_as_f64, value_as_i64, value_as_str, value_as_timestamp},
+    partitions::{Partition, PartitionSpec},
+    security::SecurityCatalog,
+    statistics::{TableStatistics, DEFAULT_HISTOGRAM_BUCKETS},
+    transactions::{IsolationLevel, Transaction, VersionStore},
+};
+use fdc_core::{error::{Error, Result}, types::Value};
+use fdc_storage::engine::{BatchOperation, StorageEngine};
+use parking_lot::RwLock;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{DataType, Expr};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+
+/// 表数据在存储引擎中的键前缀
+pub const TABLE_KEY_PREFIX: &str = "tbl:";
+
+/// Decimal的最大小数位数
+const DECIMAL_MAX_SCALE: u32 = 28;
+
+/// 列类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColumnType {
+    Boolean,
+    Int64,
+    Float64,
+    Decimal,
+    String,
+    Binary,
+    Timestamp,
+    Price,
+    Volume,
+    Symbol,
+}
+
+impl ColumnType {
+    /// 从SQL数据类型映射（用于`PREPARE name (INT, TEXT)`等声明）
+    pub fn from_sql_type(data_type: &DataType) -> Option<Self> {
+        Some(match data_type {
+            DataType::Boolean | DataType::Bool => ColumnType::Boolean,
+            DataType::TinyInt(_) | DataType::SmallInt(_) | DataType::Int(_) | DataType::Integer(_)
+            | DataType::BigInt(_) | DataType::Int2(_) | DataType::Int4(_) | DataType::Int8(_)
+            | DataType::Int64 => ColumnType::Int64,
+            DataType::Float(_) | DataType::Real | DataType::Double | DataType::DoublePrecision
+            | DataType::Float4 | DataType::Float8 | DataType::Float64 => ColumnType::Float64,
+            DataType::Decimal(_) | DataType::Numeric(_) | DataType::Dec(_) => ColumnType::Decimal,
+            DataType::Char(_) | DataType::Varchar(_) | DataType::Text | DataType::String(_) => ColumnType::String,
+            DataType::Binary(_) | DataType::Varbinary(_) | DataType::Blob(_) | DataType::Bytea => ColumnType::Binary,
+            DataType::Timestamp(_, _) | DataType::Datetime(_) => ColumnType::Timestamp,
+            _ => return None,
+        })
+    }
+
+    /// 由值推断列类型（NULL与复合类型返回None）
+    pub fn of_value(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::Bool(_) => ColumnType::Boolean,
+            Value::Int8(_) | Value::Int16(_) | Value::Int32(_) | Value::Int64(_)
+            | Value::UInt8(_) | Value::UInt16(_) | Value::UInt32(_) => ColumnType::Int64,
+            Value::Float32(_) | Value::Float64(_) => ColumnType::Float64,
+            Value::Decimal(_) => ColumnType::Decimal,
+            Value::String(_) => ColumnType::String,
+            Value::Binary(_) => ColumnType::Binary,
+            Value::Timestamp(_) => ColumnType::Timestamp,
+            Value::Price(_) => ColumnType::Price,
+            Value::Volume(_) | Value::UInt64(_) => ColumnType::Volume,
+            Value::Symbol(_) => ColumnType::Symbol,
+            _ => return None,
+        })
+    }
+
+    /// 主键编码是否保持值的比较顺序（见`encode_key_value`）
+    pub fn has_ordered_key_encoding(&self) -> bool {
+        !matches!(self, ColumnType::Binary)
+    }
+
+    /// 将值转换为该列类型，无法无损转换时返回类型错误
+    pub fn coerce(&self, value: Value) -> Result<Value> {
+        let mismatch = |value: &Value| Error::type_error(format!("Cannot convert {:?} to {:?}", value, self));
+        if matches!(value, Value::Null) {
+            return Ok(Value::Null);
+        }
+        let text = value_as_str(&value);
+        Ok(match self {
+            ColumnType::Boolean => match (&value, text.as_deref().map(str::to_lowercase).as_deref()) {
+                (Value::Bool(b), _) => Value::Bool(*b),
+                (_, Some("true" | "t" | "1")) => Value::Bool(true),
+                (_, Some("false" | "f" | "0")) => Value::Bool(false),
+                _ => return Err(mismatch(&value)),
+            },
+            ColumnType::Int64 => match text {
+                Some(text) => Value::Int64(text.trim().parse().map_err(|_| mismatch(&value))?),
+                None => match value_as_decimal(&value) {
+                    Some(d) if d.fract().is_zero() => Value::Int64(value_as_i64(&value).ok_or_else(|| mismatch(&value))?),
+                    _ => return Err(mismatch(&value)),
+                },
+            },
+            ColumnType::Float64 => match text {
+                Some(text) => Value::Float64(text.trim().parse().map_err(|_| mismatch(&value))?),
+                None => Value::Float64(value_as_f64(&value).ok_or_else(|| mismatch(&value))?),
+            },
+            ColumnType::Decimal | ColumnType::Price => match text {
+                Some(text) => Value::Decimal(text.trim().parse().map_err(|_| mismatch(&value))?),
+                None => Value::Decimal(value_as_decimal(&value).ok_or_else(|| mismatch(&value))?),
+            },
+            ColumnType::Volume => match value_as_i64(&value).or_else(|| text.and_then(|t| t.trim().parse().ok())) {
+                Some(v) if v >= 0 => Value::UInt64(v as u64),
+                _ => return Err(mismatch(&value)),
+            },
+            ColumnType::String | ColumnType::Symbol => Value::String(text.ok_or_else(|| mismatch(&value))?),
+            ColumnType::Binary => match value {
+                Value::Binary(b) => Value::Binary(b),
+                Value::String(s) => Value::Binary(s.into_bytes()),
+                other => return Err(mismatch(&other)),
+            },
+            ColumnType::Timestamp => Value::Timestamp(value_as_timestamp(&value).ok_or_else(|| mismatch(&value))?),
+        })
+    }
+}
+
+/// 列定义
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnDefinition {
+    /// 列名
+    pub name: String,
+    /// 列类型
+    pub column_type: ColumnType,
+    /// 是否可为空
+    pub nullable: bool,
+}
+
+impl ColumnDefinition {
+    /// 创建新的列定义
+    pub fn new(name: impl Into<String>, column_type: ColumnType) -> Self {
+        Self {
+            name: name.into(),
+            column_type,
+            nullable: true,
+        }
+    }
+
+    /// 设置为非空列
+    pub fn not_null(mut self) -> Self {
+        self.nullable = false;
+        self
+    }
+}
+
+/// 表定义
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableDefinition {
+    /// 表名
+    pub name: String,
+    /// 列定义
+    pub columns: Vec<ColumnDefinition>,
+    /// 主键列（用于点查和行键编码）
+    pub primary_key: Option<String>,
+    /// 已落盘的Parquet数据段
+    pub parquet_segments: Vec<PathBuf>,
+    /// 分区定义；分区表的每个分区占用独立的键范围`tbl:<table>:<partition>:`
+    #[serde(default)]
+    pub partitioning: Option<PartitionSpec>,
+}
+
+impl TableDefinition {
+    /// 创建新的表定义
+    pub fn new(name: impl Into<String>, columns: Vec<ColumnDefinition>) -> Self {
+        Self {
+            name: name.into(),
+            columns,
+            primary_key: None,
+            parquet_segments: Vec::new(),
+            partitioning: None,
+        }
+    }
+
+    /// 设置主键列
+    pub fn with_primary_key(mut self, column: impl Into<String>) -> Self {
+        self.primary_key = Some(column.into());
+        self
+    }
+
+    /// 添加Parquet数据段
+    pub fn with_parquet_segment(mut self, path: impl Into<PathBuf>) -> Self {
+        self.parquet_segments.push(path.into());
+        self
+    }
+
+    /// 设置分区方式
+    pub fn with_partitioning(mut self, spec: PartitionSpec) -> Self {
+        self.partitioning = Some(spec);
+        self
+    }
+
+    /// 是否为分区表
+    pub fn is_partitioned(&self) -> bool {
+        self.partitioning.is_some()
+    }
+
+    /// 行所属分区的分区名，非分区表返回None
+    pub fn partition_name(&self, row: &HashMap<String, Value>) -> Option<String> {
+        self.partitioning.as_ref()
+            .and_then(|spec| spec.partition_of(row).ok())
+            .map(|partition| partition.name)
+    }
+
+    /// 查找列定义
+    pub fn column(&self, name: &str) -> Option<&ColumnDefinition> {
+        self.columns.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// 按行键扫描时结果按其有序的主键列
+    ///
+    /// 分区表的行按分区再按主键存放；二进制主键的比较顺序与键编码不一致，均不视为有序。
+    pub fn key_order(&self) -> Option<&str> {
+        let primary_key = self.primary_key.as_deref().filter(|_| !self.is_partitioned())?;
+        let column = self.column(primary_key)?;
+        column.column_type.has_ordered_key_encoding().then_some(primary_key)
+    }
+
+    /// 获取列名列表
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// 表数据的键范围 [start, end)
+    pub fn key_range(&self) -> (Vec<u8>, Vec<u8>) {
+        let start = table_key_prefix(&self.name);
+        let mut end = start.clone();
+        if let Some(last) = end.last_mut() {
+            *last += 1;
+        }
+        (start, end)
+    }
+
+    /// 分区数据的键范围 [start, end)
+    pub fn partition_key_range(&self, partition: &str) -> (Vec<u8>, Vec<u8>) {
+        let start = partition_key_prefix(&self.name, partition);
+        let mut end = start.clone();
+        if let Some(last) = end.last_mut() {
+            *last += 1;
+        }
+        (start, end)
+    }
+
+    /// 计算行在存储引擎中的键；分区表的行键以分区前缀开头
+    pub fn row_key(&self, row: &HashMap<String, Value>) -> Vec<u8> {
+        let mut key = match self.partition_name(row) {
+            Some(partition) => partition_key_prefix(&self.name, &partition),
+            None => table_key_prefix(&self.name),
+        };
+        match self.primary_key.as_ref().and_then(|pk| row.get(pk)) {
+            Some(value) => key.extend_from_slice(&encode_key_value(value)),
+            None => key.extend_from_slice(uuid::Uuid::new_v4().to_string().as_bytes()),
+        }
+        key
+    }
+
+    /// 把查询中的主键值转换为主键列的类型，使其编码与写入时转换后的行键一致；无法转换时返回None
+    pub fn coerce_key(&self, value: &Value) -> Option<Value> {
+        let column = self.column(self.primary_key.as_deref()?)?;
+        column.column_type.coerce(value.clone()).ok()
+    }
+
+    /// 计算主键值对应的行键
+    pub fn primary_key_lookup(&self, value: &Value) -> Vec<u8> {
+        let mut key = table_key_prefix(&self.name);
+        key.extend_from_slice(&encode_key_value(value));
+        key
+    }
+
+    /// 计算主键值在给定分区中的行键
+    pub fn partition_key_lookup(&self, partition: &str, value: &Value) -> Vec<u8> {
+        let mut key = partition_key_prefix(&self.name, partition);
+        key.extend_from_slice(&encode_key_value(value));
+        key
+    }
+
+    /// 检查行是否满足非空约束
+    pub fn validate_row(&self, row: &HashMap<String, Value>) -> Result<()> {
+        for column in &self.columns {
+            if !column.nullable && matches!(row.get(&column.name), None | Some(Value::Null)) {
+                return Err(Error::validation(format!(
+                    "Column {}.{} does not allow NULL", self.name, column.name
+                )));
+            }
+        }
+        if let Some(spec) = &self.partitioning {
+            spec.partition_of(row)?;
+        }
+        Ok(())
+    }
+}
+
+/// 一次表写入，用于使依赖该表的缓存结果失效
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableWrite {
+    /// 表名（小写）
+    pub table: String,
+    /// 写入行在各时间列上的范围（纳秒，闭区间）；不含某列时视为该列范围未知
+    pub time_ranges: HashMap<String, (i64, i64)>,
+}
+
+impl TableWrite {
+    /// 范围未知的写入
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into().to_lowercase(),
+            time_ranges: HashMap::new(),
+        }
+    }
+
+    /// 记录写入行在时间列上的范围
+    pub fn with_time_range(mut self, column: impl Into<String>, start: i64, end: i64) -> Self {
+        self.time_ranges.insert(column.into(), (start.min(end), start.max(end)));
+        self
+    }
+
+    /// 按表结构统计写入行在各时间列上的范围
+    pub fn from_rows(definition: &TableDefinition, rows: &[HashMap<String, Value>]) -> Self {
+        let mut write = Self::new(&definition.name);
+        for column in definition.columns.iter().filter(|c| c.column_type == ColumnType::Timestamp) {
+            let mut times = rows.iter()
+                .filter_map(|row| row.get(&column.name).and_then(value_as_timestamp))
+                .map(|ts| ts.as_nanos());
+            if let Some(first) = times.next() {
+                let (start, end) = times.fold((first, first), |(lo, hi), ts| (lo.min(ts), hi.max(ts)));
+                write = write.with_time_range(column.name.clone(), start, end);
+            }
+        }
+        write
+    }
+}
+
+/// 表写入的监听器
+pub type WriteListener = Arc<dyn Fn(&TableWrite) + Send + Sync>;
+
+/// 新写入行的观察者（例如物化视图的增量维护）
+#[async_trait::async_trait]
+pub trait InsertObserver: Send + Sync {
+    /// 行已写入`table`
+    async fn rows_inserted(&self, table: &str, rows: &[HashMap<String, Value>]) -> Result<()>;
+}
+
+/// 表目录
+pub struct Catalog {
+    tables: RwLock<HashMap<String, TableDefinition>>,
+    statistics: RwLock<HashMap<String, TableStatistics>>,
+    write_listeners: RwLock<Vec<WriteListener>>,
+    /// 目录只持有弱引用，观察者由其所有者（查询引擎）保持存活
+    insert_observers: RwLock<Vec<Weak<dyn InsertObserver>>>,
+    /// 分区表已创建的分区（表名 -> 分区名 -> 分区）
+    partitions: RwLock<HashMap<String, BTreeMap<String, Partition>>>,
+    /// 为活跃快照保留前像的撤销日志，目录写入都经由它提交
+    versions: Arc<VersionStore>,
+    /// 表的授权、行策略与列掩码
+    security: SecurityCatalog,
+}
+
+impl Catalog {
+    /// 创建空目录
+    pub fn new() -> Self {
+        Self {
+            tables: RwLock::new(HashMap::new()),
+            statistics: RwLock::new(HashMap::new()),
+            write_listeners: RwLock::new(Vec::new()),
+            insert_observers: RwLock::new(Vec::new()),
+            partitions: RwLock::new(HashMap::new()),
+            versions: Arc::new(VersionStore::default()),
+            security: SecurityCatalog::default(),
+        }
+    }
+
+    /// 订阅`insert_rows`写入的新行
+    pub fn observe_inserts(&self, observer: Weak<dyn InsertObserver>) {
+        let mut observers = self.insert_observers.write();
+        observers.retain(|o| o.strong_count() > 0);
+        observers.push(observer);
+    }
+
+    /// 订阅表写入通知（例如查询结果缓存失效）
+    pub fn on_write(&self, listener: WriteListener) {
+        self.write_listeners.write().push(listener);
+    }
+
+    /// 通知表已被写入
+    ///
+    /// `insert_rows`与`drop_table`会自动通知；绕过目录直接写存储的路径（例如数据接入）
+    /// 写入后需调用此方法。
+    pub fn notify_write(&self, write: &TableWrite) {
+        for listener in self.write_listeners.read().iter() {
+            listener(write);
+        }
+    }
+
+    /// 注册表
+    pub fn register_table(&self, table: TableDefinition) -> Result<()> {
+        if let Some(spec) = &table.partitioning {
+            spec.validate(&table)?;
+        }
+        let mut tables = self.tables.write();
+        let name = table.name.to_lowercase();
+        if tables.contains_key(&name) {
+            return Err(Error::already_exists(format!("table {}", table.name)));
+        }
+        tables.insert(name, table);
+        Ok(())
+    }
+
+    /// 删除表定义
+    pub fn drop_table(&self, name: &str) -> Result<TableDefinition> {
+        self.statistics.write().remove(&name.to_lowercase());
+        self.partitions.write().remove(&name.to_lowercase());
+        let definition = self.tables.write().remove(&name.to_lowercase())
+            .ok_or_else(|| Error::not_found(format!("table {}", name)))?;
+        self.security.remove_table(name);
+        self.notify_write(&TableWrite::new(name));
+        Ok(definition)
+    }
+
+    /// 表的访问控制
+    pub fn security(&self) -> &SecurityCatalog {
+        &self.security
+    }
+
+    /// 获取表定义
+    pub fn get_table(&self, name: &str) -> Option<TableDefinition> {
+        self.tables.read().get(&name.to_lowercase()).cloned()
+    }
+
+    /// 表是否存在
+    pub fn contains_table(&self, name: &str) -> bool {
+        self.tables.read().contains_key(&name.to_lowercase())
+    }
+
+    /// 列出所有表
+    pub fn list_tables(&self) -> Vec<TableDefinition> {
+        let mut tables: Vec<_> = self.tables.read().values().cloned().collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        tables
+    }
+
+    /// 分区表现有的分区，按分区名排序
+    pub fn partitions(&self, table: &str) -> Vec<Partition> {
+        self.partitions.read().get(&table.to_lowercase())
+            .map(|partitions| partitions.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// 按WHERE条件剪枝，返回需要扫描的分区；非分区表或条件不约束分区列时返回None
+    pub fn prune_partitions(&self, definition: &TableDefinition, selection: &Expr) -> Option<Vec<String>> {
+        let spec = definition.partitioning.as_ref()?;
+        let predicates: Vec<Expr> = split_conjunction(selection).into_iter().cloned().collect();
+        spec.prune(&self.partitions(&definition.name), &predicates)
+    }
+
+    /// 为写入的行创建缺失的分区
+    fn create_partitions(&self, definition: &TableDefinition, rows: &[HashMap<String, Value>]) -> Result<()> {
+        let Some(spec) = &definition.partitioning else {
+            return Ok(());
+        };
+        let mut tables = self.partitions.write();
+        let partitions = tables.entry(definition.name.to_lowercase()).or_default();
+        for row in rows {
+            let partition = spec.partition_of(row)?;
+            match partitions.get(&partition.name) {
+                // 列表分区的不同取值规范化后可能得到同一分区名
+                Some(existing) if existing.value != partition.value => {
+                    return Err(Error::validation(format!(
+                        "Values '{}' and '{}' of {}.{} map to the same partition {}",
+                        existing.value.as_deref().unwrap_or_default(),
+                        partition.value.as_deref().unwrap_or_default(),
+                        definition.name, spec.column, partition.name,
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    tracing::debug!("Created partition {} of table {}", partition.name, definition.name);
+                    partitions.insert(partition.name.clone(), partition);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 查找分区表的分区
+    fn partition(&self, table: &str, name: &str) -> Result<(TableDefinition, Partition)> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+        if !definition.is_partitioned() {
+            return Err(Error::validation(format!("Table {} is not partitioned", definition.name)));
+        }
+        let partition = self.partitions.read().get(&definition.name.to_lowercase())
+            .and_then(|partitions| partitions.get(name))
+            .cloned()
+            .ok_or_else(|| Error::not_found(format!("partition {} of table {}", name, definition.name)))?;
+        Ok((definition, partition))
+    }
+
+    /// 移除分区元数据并使依赖其时间范围的缓存结果失效
+    fn remove_partition(&self, definition: &TableDefinition, partition: &Partition) {
+        if let Some(partitions) = self.partitions.write().get_mut(&definition.name.to_lowercase()) {
+            partitions.remove(&partition.name);
+        }
+        let write = match (&definition.partitioning, partition.range) {
+            (Some(spec), Some((start, end))) => TableWrite::new(&definition.name).with_time_range(spec.column.clone(), start, end - 1),
+            _ => TableWrite::new(&definition.name),
+        };
+        self.notify_write(&write);
+    }
+
+    /// 删除分区及其全部行，返回删除的行数
+    ///
+    /// 按分区的键范围整段删除，不解码行也不求值条件，用于按时间淘汰历史数据。
+    pub async fn drop_partition(&self, storage: &dyn StorageEngine, table: &str, partition: &str) -> Result<u64> {
+        let (definition, partition) = self.partition(table, partition)?;
+        let (start, end) = definition.partition_key_range(&partition.name);
+        let operations: Vec<BatchOperation> = storage.scan(Some(&start), Some(&end), None).await?
+            .into_iter()
+            .map(|(key, _)| BatchOperation::Delete { key })
+            .collect();
+        let count = operations.len() as u64;
+        if !operations.is_empty() {
+            self.versions.apply(storage, operations).await?;
+        }
+        self.remove_partition(&definition, &partition);
+        Ok(count)
+    }
+
+    /// 把分区分离为独立的表，返回移动的行数
+    ///
+    /// 分区的行移到`target`（默认为`<表名>_<分区名>`）下；新表沿用原表的列与主键，但不再分区。
+    pub async fn detach_partition(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        partition: &str,
+        target: Option<&str>,
+    ) -> Result<u64> {
+        let (definition, partition) = self.partition(table, partition)?;
+        let target = target.map(str::to_string).unwrap_or_else(|| format!("{}_{}", definition.name, partition.name));
+        let mut detached = TableDefinition::new(target, definition.columns.clone());
+        detached.primary_key = definition.primary_key.clone();
+        self.register_table(detached.clone())?;
+
+        let (start, end) = definition.partition_key_range(&partition.name);
+        let entries = storage.scan(Some(&start), Some(&end), None).await?;
+        let mut operations = Vec::with_capacity(entries.len() * 2);
+        let mut rows = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let row = decode_row(&value)?;
+            operations.push(BatchOperation::Delete { key });
+            operations.push(BatchOperation::Put { key: detached.row_key(&row), value });
+            rows.push(row);
+        }
+        if !operations.is_empty() {
+            if let Err(e) = self.versions.apply(storage, operations).await {
+                self.tables.write().remove(&detached.name.to_lowercase());
+                return Err(e);
+            }
+        }
+        self.remove_partition(&definition, &partition);
+        self.notify_write(&TableWrite::from_rows(&detached, &rows));
+        Ok(rows.len() as u64)
+    }
+
+    /// 获取表统计信息（需先执行ANALYZE）
+    pub fn statistics(&self, name: &str) -> Option<TableStatistics> {
+        self.statistics.read().get(&name.to_lowercase()).cloned()
+    }
+
+    /// 设置表统计信息
+    pub fn set_statistics(&self, statistics: TableStatistics) {
+        self.statistics.write().insert(statistics.table.to_lowercase(), statistics);
+    }
+
+    /// 扫描全表收集行数、NDV、最值和等深直方图
+    pub async fn analyze_table(&self, storage: &dyn StorageEngine, table: &str) -> Result<TableStatistics> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+        let rows = self.scan_rows(storage, table).await?;
+        let statistics = TableStatistics::compute(&definition, &rows, DEFAULT_HISTOGRAM_BUCKETS);
+        self.set_statistics(statistics.clone());
+        Ok(statistics)
+    }
+
+    /// 写入行
+    pub async fn insert_rows(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        rows: &[HashMap<String, Value>],
+    ) -> Result<u64> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        let mut operations = Vec::with_capacity(rows.len());
+        for row in rows {
+            definition.validate_row(row)?;
+            operations.push(BatchOperation::Put {
+                key: definition.row_key(row),
+                value: encode_row(row)?,
+            });
+        }
+        self.create_partitions(&definition, rows)?;
+
+        let count = operations.len() as u64;
+        self.versions.apply(storage, operations).await?;
+        self.notify_write(&TableWrite::from_rows(&definition, rows));
+        self.notify_inserted(&definition.name, rows).await;
+        Ok(count)
+    }
+
+    /// 通知插入观察者；行已提交，观察者失败只记录日志，不影响写入结果
+    async fn notify_inserted(&self, table: &str, rows: &[HashMap<String, Value>]) {
+        let observers: Vec<Arc<dyn InsertObserver>> = self.insert_observers.read().iter().filter_map(Weak::upgrade).collect();
+        for observer in observers {
+            if let Err(e) = observer.rows_inserted(table, rows).await {
+                tracing::warn!("Insert observer failed for table {}: {}", table, e);
+            }
+        }
+    }
+
+    /// 按给定的键后缀写入或删除行（键为`tbl:<table>:<后缀>`）
+    pub async fn write_keyed_rows(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        puts: &[(String, HashMap<String, Value>)],
+        deletes: &[String],
+    ) -> Result<()> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+        if puts.is_empty() && deletes.is_empty() {
+            return Ok(());
+        }
+
+        let keyed = |suffix: &str| {
+            let mut key = table_key_prefix(&definition.name);
+            key.extend_from_slice(suffix.as_bytes());
+            key
+        };
+        let mut operations = Vec::with_capacity(puts.len() + deletes.len());
+        for suffix in deletes {
+            operations.push(BatchOperation::Delete { key: keyed(suffix) });
+        }
+        for (suffix, row) in puts {
+            operations.push(BatchOperation::Put { key: keyed(suffix), value: encode_row(row)? });
+        }
+        self.versions.apply(storage, operations).await?;
+
+        // 删除的行时间范围未知，按整表失效
+        let write = if deletes.is_empty() {
+            let rows: Vec<HashMap<String, Value>> = puts.iter().map(|(_, row)| row.clone()).collect();
+            TableWrite::from_rows(&definition, &rows)
+        } else {
+            TableWrite::new(&definition.name)
+        };
+        self.notify_write(&write);
+        Ok(())
+    }
+
+    /// 用给定的行替换表的全部内容
+    pub async fn replace_rows(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        rows: &[(String, HashMap<String, Value>)],
+    ) -> Result<()> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        let (start, end) = definition.key_range();
+        let prefix_len = start.len();
+        let kept: std::collections::HashSet<&str> = rows.iter().map(|(suffix, _)| suffix.as_str()).collect();
+        let deletes: Vec<String> = storage.scan(Some(&start), Some(&end), None).await?
+            .into_iter()
+            .map(|(key, _)| String::from_utf8_lossy(&key[prefix_len..]).into_owned())
+            .filter(|suffix| !kept.contains(suffix.as_str()))
+            .collect();
+        self.write_keyed_rows(storage, table, rows, &deletes).await
+    }
+
+    /// 扫描表的所有行
+    pub async fn scan_rows(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+    ) -> Result<Vec<HashMap<String, Value>>> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        let (start, end) = definition.key_range();
+        let entries = storage.scan(Some(&start), Some(&end), None).await?;
+        entries.iter().map(|(_, value)| decode_row(value)).collect()
+    }
+
+    /// 可取消地扫描表，最多返回`limit`行
+    ///
+    /// 等待存储时与取消信号竞争，解码时逐行检查令牌；已读取的键值在返回错误时立即释放。
+    pub async fn scan_rows_with(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        cancellation: &CancellationToken,
+        limit: Option<usize>,
+    ) -> Result<Vec<HashMap<String, Value>>> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        let (start, end) = definition.key_range();
+        let entries = cancellation.run(storage.scan(Some(&start), Some(&end), limit)).await?;
+        let mut rows = Vec::with_capacity(entries.len());
+        for (_, value) in &entries {
+            cancellation.tick()?;
+            rows.push(decode_row(value)?);
+        }
+        Ok(rows)
+    }
+
+    /// 按主键闭区间`[lower, upper]`扫描表（None表示该侧不限），最多返回`limit`行
+    ///
+    /// 只有键编码保序的非分区表才能按键范围读取，其他表或边界无法转换为主键类型时扫描全部行，
+    /// 由调用方再过滤。
+    pub async fn scan_key_range(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        lower: Option<&Value>,
+        upper: Option<&Value>,
+        limit: Option<usize>,
+    ) -> Result<Vec<HashMap<String, Value>>> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        let (mut start, mut end) = definition.key_range();
+        // 外层None表示边界无法转换
+        let coerce = |bound: Option<&Value>| match bound {
+            Some(value) => definition.coerce_key(value).map(Some),
+            None => Some(None),
+        };
+        if let (Some(_), Some(lower), Some(upper)) = (definition.key_order(), coerce(lower), coerce(upper)) {
+            if let Some(lower) = lower {
+                start = definition.primary_key_lookup(&lower);
+            }
+            if let Some(upper) = upper {
+                // 紧跟在上界键之后的最小键
+                end = definition.primary_key_lookup(&upper);
+                end.push(0);
+            }
+        }
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let entries = storage.scan(Some(&start), Some(&end), limit).await?;
+        entries.iter().map(|(_, value)| decode_row(value)).collect()
+    }
+
+    /// 可取消地扫描分区表的指定分区，最多返回`limit`行
+    pub async fn scan_partitions_with(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        partitions: &[String],
+        cancellation: &CancellationToken,
+        limit: Option<usize>,
+    ) -> Result<Vec<HashMap<String, Value>>> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        let mut rows = Vec::new();
+        for partition in partitions {
+            let remaining = match limit {
+                Some(limit) if rows.len() >= limit => break,
+                Some(limit) => Some(limit - rows.len()),
+                None => None,
+            };
+            let (start, end) = definition.partition_key_range(partition);
+            let entries = cancellation.run(storage.scan(Some(&start), Some(&end), remaining)).await?;
+            rows.reserve(entries.len());
+            for (_, value) in &entries {
+                cancellation.tick()?;
+                rows.push(decode_row(value)?);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// 按键顺序分页扫描：返回`after`之后的至多`limit`行及每行的存储键，`after`为None时从表头开始
+    pub async fn scan_page(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        after: Option<&[u8]>,
+        limit: usize,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<(Vec<u8>, HashMap<String, Value>)>> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        let (mut start, end) = definition.key_range();
+        if let Some(after) = after {
+            // 紧跟在上一页最后一个键之后的最小键
+            start = after.to_vec();
+            start.push(0);
+        }
+        let entries = cancellation.run(storage.scan(Some(&start), Some(&end), Some(limit))).await?;
+        let mut rows = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            cancellation.tick()?;
+            let row = decode_row(&value)?;
+            rows.push((key, row));
+        }
+        Ok(rows)
+    }
+
+    /// 按主键点查
+    pub async fn lookup_row(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        key: &Value,
+    ) -> Result<Option<HashMap<String, Value>>> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        for key in self.lookup_keys(&definition, key) {
+            if let Some(bytes) = storage.get(&key).await? {
+                return Ok(Some(decode_row(&bytes)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 主键值可能对应的行键：主键即分区列时直接定位分区，否则逐个分区探测
+    fn lookup_keys(&self, definition: &TableDefinition, value: &Value) -> Vec<Vec<u8>> {
+        let Some(spec) = &definition.partitioning else {
+            return vec![definition.primary_key_lookup(value)];
+        };
+        let keyed_by_partition = definition.primary_key.as_ref().is_some_and(|pk| pk.eq_ignore_ascii_case(&spec.column));
+        let partitions = if keyed_by_partition {
+            let row = HashMap::from([(spec.column.clone(), value.clone())]);
+            spec.partition_of(&row).map(|partition| vec![partition.name]).unwrap_or_default()
+        } else {
+            self.partitions(&definition.name).into_iter().map(|partition| partition.name).collect()
+        };
+        partitions.iter().map(|partition| definition.partition_key_lookup(partition, value)).collect()
+    }
+
+    /// 开始事务；快照隔离的事务在此刻取得已提交状态的快照
+    pub async fn begin_transaction(&self, isolation: IsolationLevel) -> Transaction {
+        let snapshot = match isolation {
+            IsolationLevel::Snapshot => Some(self.versions.snapshot().await),
+            IsolationLevel::ReadCommitted => None,
+        };
+        Transaction::new(isolation, snapshot)
+    }
+
+    /// 在事务中扫描表：快照（或最新已提交数据）叠加事务自身的写入，按行键排序并带行键返回
+    pub async fn scan_rows_in(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        transaction: &Transaction,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<(Vec<u8>, HashMap<String, Value>)>> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        let (start, end) = definition.key_range();
+        let mut entries: BTreeMap<Vec<u8>, Vec<u8>> = cancellation.run(storage.scan(Some(&start), Some(&end), None)).await?
+            .into_iter()
+            .collect();
+        if let Some(snapshot) = transaction.snapshot() {
+            snapshot.restore(&mut entries, &start, &end).await;
+        }
+        let mut rows = BTreeMap::new();
+        for (key, value) in entries {
+            cancellation.tick()?;
+            let row = decode_row(&value)?;
+            rows.insert(key, row);
+        }
+        transaction.overlay(&definition.name, &mut rows);
+        Ok(rows.into_iter().collect())
+    }
+
+    /// 在事务中按主键点查
+    pub async fn lookup_row_in(
+        &self,
+        storage: &dyn StorageEngine,
+        table: &str,
+        key: &Value,
+        transaction: &Transaction,
+    ) -> Result<Option<HashMap<String, Value>>> {
+        let definition = self.get_table(table)
+            .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+
+        for key in self.lookup_keys(&definition, key) {
+            let row = match transaction.pending(&definition.name, &key) {
+                Some(pending) => pending,
+                None => {
+                    let mut entries = BTreeMap::new();
+                    if let Some(value) = storage.get(&key).await? {
+                        entries.insert(key.clone(), value);
+                    }
+                    if let Some(snapshot) = transaction.snapshot() {
+                        let mut end = key.clone();
+                        end.push(0);
+                        snapshot.restore(&mut entries, &key, &end).await;
+                    }
+                    entries.remove(&key).map(|value| decode_row(&value)).transpose()?
+                }
+            };
+            if row.is_some() {
+                return Ok(row);
+            }
+        }
+        Ok(None)
+    }
+
+    /// 提交事务缓冲的写入，返回写入的行数
+    ///
+    /// 所有表的写入合成一个批次交给存储引擎，支持事务的引擎上整体生效或整体失败。
+    /// 写入前检查写冲突：快照隔离的事务检查快照之后的提交，其他事务核对写入前读到的行是否已被改动。
+    /// 冲突时返回序列化错误且不写入任何行。
+    pub async fn commit_transaction(&self, storage: &dyn StorageEngine, transaction: &Transaction) -> Result<u64> {
+        let writes = transaction.writes();
+        let mut operations = Vec::new();
+        // 写入前读到的已提交行，提交时须仍是当前值
+        let mut expected = Vec::new();
+        let mut tables = Vec::with_capacity(writes.len());
+        for (table, rows) in writes {
+            let definition = self.get_table(&table)
+                .ok_or_else(|| Error::not_found(format!("table {}", table)))?;
+            let mut written = Vec::new();
+            let mut inserted = Vec::new();
+            // 被更新或删除的已提交行，其时间范围同样需要失效
+            let mut before = Vec::new();
+            for (key, write) in rows {
+                if let Some(row) = &write.before {
+                    expected.push((key.clone(), row.clone()));
+                }
+                match write.row {
+                    Some(row) => {
+                        definition.validate_row(&row)?;
+                        operations.push(BatchOperation::Put { key, value: encode_row(&row)? });
+                        if write.inserted {
+                            inserted.push(row.clone());
+                        }
+                        written.push(row);
+                    }
+                    None => operations.push(BatchOperation::Delete { key }),
+                }
+                before.extend(write.before);
+            }
+            self.create_partitions(&definition, &written)?;
+            tables.push((definition, written, inserted, before));
+        }
+        if operations.is_empty() {
+            return Ok(0);
+        }
+
+        let count = operations.len() as u64;
+        self.versions.commit(storage, operations, transaction.snapshot(), &expected).await?;
+        for (definition, written, inserted, before) in tables {
+            let touched: Vec<_> = written.iter().chain(&before).cloned().collect();
+            self.notify_write(&TableWrite::from_rows(&definition, &touched));
+            if !inserted.is_empty() {
+                self.notify_inserted(&definition.name, &inserted).await;
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn table_key_prefix(table: &str) -> Vec<u8> {
+    format!("{}{}:", TABLE_KEY_PREFIX, table.to_lowercase()).into_bytes()
+}
+
+fn partition_key_prefix(table: &str, partition: &str) -> Vec<u8> {
+    format!("{}{}:{}:", TABLE_KEY_PREFIX, table.to_lowercase(), partition).into_bytes()
+}
+
+/// 保序编码主键值，使存储引擎中的键顺序与值顺序一致
+pub(crate) fn encode_key_value(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Int8(v) => encode_i64(*v as i64),
+        Value::Int16(v) => encode_i64(*v as i64),
+        Value::Int32(v) => encode_i64(*v as i64),
+        Value::Int64(v) => encode_i64(*v),
+        Value::UInt8(v) => encode_u64(*v as u64),
+        Value::UInt16(v) => encode_u64(*v as u64),
+        Value::UInt32(v) => encode_u64(*v as u64),
+        Value::UInt64(v) => encode_u64(*v),
+        Value::Volume(v) => encode_u64(v.as_u64()),
+        Value::Float32(v) => encode_f64(*v as f64),
+        Value::Float64(v) => encode_f64(*v),
+        Value::Decimal(d) => encode_decimal(d),
+        Value::Price(p) => encode_decimal(&p.as_decimal()),
+        Value::Bool(b) => vec![if *b { b'1' } else { b'0' }],
+        Value::Timestamp(ts) => encode_i64(ts.as_nanos()),
+        Value::String(s) => s.as_bytes().to_vec(),
+        Value::Symbol(s) => s.as_str().as_bytes().to_vec(),
+        Value::Binary(bytes) => bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>().into_bytes(),
+        other => serde_json::to_vec(other).unwrap_or_default(),
+    }
+}
+
+fn encode_i64(v: i64) -> Vec<u8> {
+    format!("{:016x}", (v as u64) ^ (1 << 63)).into_bytes()
+}
+
+fn encode_u64(v: u64) -> Vec<u8> {
+    format!("{:016x}", v).into_bytes()
+}
+
+fn encode_i128(v: i128) -> Vec<u8> {
+    format!("{:032x}", (v as u128) ^ (1 << 127)).into_bytes()
+}
+
+/// IEEE 754位模式：正数翻转符号位，负数按位取反，使字节序与数值序一致
+fn encode_f64(v: f64) -> Vec<u8> {
+    // -0.0与0.0视为同一主键
+    let bits = if v == 0.0 { 0 } else { v.to_bits() };
+    let ordered = if bits >> 63 == 1 { !bits } else { bits ^ (1 << 63) };
+    encode_u64(ordered)
+}
+
+/// 小数按整数部分与放大到28位小数的小数部分依次编码（两部分同号，按字典序即按数值序）
+fn encode_decimal(d: &Decimal) -> Vec<u8> {
+    let scale = d.scale();
+    let unit = 10i128.pow(scale);
+    let mantissa = d.mantissa();
+    let fraction = (mantissa % unit) * 10i128.pow(DECIMAL_MAX_SCALE - scale);
+    let mut key = encode_i128(mantissa / unit);
+    key.extend_from_slice(&encode_i128(fraction));
+    key
+}
+
+/// 编码一行数据
+pub fn encode_row(row: &HashMap<String, Value>) -> Result<Vec<u8>> {
+    serde_json::to_vec(row).map_err(|e| Error::serialization(format!("Failed to encode row: {}", e)))
+}
+
+/// 解码一行数据
+pub fn decode_row(bytes: &[u8]) -> Result<HashMap<String, Value>> {
+    serde_json::from_slice(bytes).map_err(|e| Error::serialization(format!("Failed to decode row: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_storage::engines::memory::MemoryEngine;
+
+    fn trades_table() -> TableDefinition {
+        TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("price", ColumnType::Float64),
+        ]).with_primary_key("id")
+    }
+
+    fn trade(id: i64, symbol: &str, price: f64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Int64(id));
+        row.insert("symbol".to_string(), Value::String(symbol.to_string()));
+        row.insert("price".to_string(), Value::Float64(price));
+        row
+    }
+
+    #[test]
+    fn test_register_table() {
+        let catalog = Catalog::new();
+        catalog.register_table(trades_table()).unwrap();
+
+        assert!(catalog.contains_table("TRADES"));
+        assert!(catalog.register_table(trades_table()).is_err());
+        assert_eq!(catalog.list_tables().len(), 1);
+    }
+
+    #[test]
+    fn test_key_encoding_preserves_order() {
+        assert!(encode_key_value(&Value::Int64(-5)) < encode_key_value(&Value::Int64(3)));
+        assert!(encode_key_value(&Value::Int64(9)) < encode_key_value(&Value::Int64(10)));
+
+        let floats = [-10.5, -9.0, -0.25, 0.0, 0.25, 9.0, 10.5, f64::MAX];
+        for pair in floats.windows(2) {
+            assert!(encode_key_value(&Value::Float64(pair[0])) < encode_key_value(&Value::Float64(pair[1])));
+        }
+        assert_eq!(encode_key_value(&Value::Float64(-0.0)), encode_key_value(&Value::Float64(0.0)));
+
+        assert!(encode_key_value(&Value::UInt64(9)) < encode_key_value(&Value::UInt64(10)));
+        assert!(encode_key_value(&Value::UInt64(i64::MAX as u64)) < encode_key_value(&Value::UInt64(u64::MAX)));
+        // 不同宽度的无符号整数编码一致
+        assert_eq!(encode_key_value(&Value::UInt8(7)), encode_key_value(&Value::UInt64(7)));
+        assert!(encode_key_value(&Value::UInt32(u32::MAX)) < encode_key_value(&Value::UInt64(u32::MAX as u64 + 1)));
+
+        let decimals = ["-10.5", "-9.75", "-9.7", "-0.001", "0", "0.001", "9.7", "9.75", "10.5"];
+        for pair in decimals.windows(2) {
+            let (a, b): (Decimal, Decimal) = (pair[0].parse().unwrap(), pair[1].parse().unwrap());
+            assert!(encode_key_value(&Value::Decimal(a)) < encode_key_value(&Value::Decimal(b)), "{} < {}", a, b);
+        }
+        let (a, b): (Decimal, Decimal) = ("1.50".parse().unwrap(), "1.5".parse().unwrap());
+        assert_eq!(encode_key_value(&Value::Decimal(a)), encode_key_value(&Value::Decimal(b)));
+    }
+
+    #[tokio::test]
+    async fn test_scan_key_range() {
+        let storage = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let catalog = Catalog::new();
+        catalog.register_table(trades_table()).unwrap();
+        let rows: Vec<_> = (1..=10).map(|i| trade(i, "AAPL", 100.0)).collect();
+        catalog.insert_rows(&storage, "trades", &rows).await.unwrap();
+
+        let ids = |rows: Vec<HashMap<String, Value>>| rows.iter().map(|row| row["id"].clone()).collect::<Vec<_>>();
+        let found = catalog.scan_key_range(&storage, "trades", Some(&Value::Int64(3)), Some(&Value::Int64(5)), None).await.unwrap();
+        assert_eq!(ids(found), vec![Value::Int64(3), Value::Int64(4), Value::Int64(5)]);
+        let found = catalog.scan_key_range(&storage, "trades", Some(&Value::Int64(8)), None, Some(2)).await.unwrap();
+        assert_eq!(ids(found), vec![Value::Int64(8), Value::Int64(9)]);
+        let found = catalog.scan_key_range(&storage, "trades", Some(&Value::Int64(6)), Some(&Value::Int64(2)), None).await.unwrap();
+        assert!(found.is_empty());
+        // 边界按主键类型转换；无法转换时扫描全部行
+        let found = catalog.scan_key_range(&storage, "trades", Some(&Value::String("9".to_string())), None, None).await.unwrap();
+        assert_eq!(ids(found), vec![Value::Int64(9), Value::Int64(10)]);
+        let found = catalog.scan_key_range(&storage, "trades", Some(&Value::String("nine".to_string())), None, None).await.unwrap();
+        assert_eq!(found.len(), 10);
+    }
+
+    #[test]
+    fn test_column_type_coercion() {
+        assert_eq!(ColumnType::Int64.coerce(Value::String("42".to_string())).unwrap(), Value::Int64(42));
+        assert_eq!(ColumnType::Float64.coerce(Value::Int32(3)).unwrap(), Value::Float64(3.0));
+        assert_eq!(ColumnType::String.coerce(Value::Null).unwrap(), Value::Null);
+        assert!(ColumnType::Int64.coerce(Value::Float64(1.5)).is_err());
+        assert!(ColumnType::Int64.coerce(Value::String("1; DROP TABLE trades".to_string())).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_insert_scan_and_lookup() {
+        let storage = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let catalog = Catalog::new();
+        catalog.register_table(trades_table()).unwrap();
+
+        let rows = vec![trade(2, "AAPL", 190.5), trade(1, "MSFT", 410.0)];
+        assert_eq!(catalog.insert_rows(&storage, "trades", &rows).await.unwrap(), 2);
+
+        let scanned = catalog.scan_rows(&storage, "trades").await.unwrap();
+        assert_eq!(scanned.len(), 2);
+        assert_eq!(scanned[0].get("id"), Some(&Value::Int64(1)));
+
+        let found = catalog.lookup_row(&storage, "trades", &Value::Int64(2)).await.unwrap();
+        assert_eq!(found.unwrap().get("symbol"), Some(&Value::String("AAPL".to_string())));
+
+        let statistics = catalog.analyze_table(&storage, "trades").await.unwrap();
+        assert_eq!(statistics.row_count, 2);
+        assert_eq!(catalog.statistics("TRADES").unwrap().distinct_count("symbol"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_table() {
+        use crate::partitions::{PartitionSpec, TimeGranularity};
+        use fdc_core::time::intervals;
+        use fdc_core::types::TimestampNs;
+
+        let storage = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let catalog = Catalog::new();
+        let table = trades_table()
+            .with_partitioning(PartitionSpec::time("ts", TimeGranularity::Day));
+        assert!(catalog.register_table(table.clone()).is_err());
+        let mut table = table;
+        table.columns.push(ColumnDefinition::new("ts", ColumnType::Timestamp).not_null());
+        catalog.register_table(table).unwrap();
+
+        let rows: Vec<_> = (0..6).map(|i| {
+            let mut row = trade(i, "AAPL", 100.0 + i as f64);
+            row.insert("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(i / 2 * intervals::DAY)));
+            row
+        }).collect();
+        catalog.insert_rows(&storage, "trades", &rows).await.unwrap();
+        let names: Vec<String> = catalog.partitions("trades").into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["p19700101", "p19700102", "p19700103"]);
+
+        // 主键不是分区列时逐个分区点查
+        let found = catalog.lookup_row(&storage, "trades", &Value::Int64(3)).await.unwrap();
+        assert_eq!(found.unwrap().get("price"), Some(&Value::Float64(103.0)));
+        let token = CancellationToken::new();
+        let scanned = catalog.scan_partitions_with(&storage, "trades", &names[1..2], &token, None).await.unwrap();
+        assert_eq!(scanned.len(), 2);
+
+        assert_eq!(catalog.drop_partition(&storage, "trades", "p19700101").await.unwrap(), 2);
+        assert!(catalog.drop_partition(&storage, "trades", "p19700101").await.is_err());
+        assert_eq!(catalog.detach_partition(&storage, "trades", "p19700102", None).await.unwrap(), 2);
+        assert_eq!(catalog.scan_rows(&storage, "trades").await.unwrap().len(), 2);
+        assert_eq!(catalog.scan_rows(&storage, "trades_p19700102").await.unwrap().len(), 2);
+        assert!(!catalog.get_table("trades_p19700102").unwrap().is_partitioned());
+        assert_eq!(catalog.partitions("trades").len(), 1);
+    }
+}
@@ -0,0 +1,775 @@
+//! Statistics-driven cost model for access paths and join ordering
+
+use crate::{
+    catalog::{Catalog, ColumnType, TableDefinition},
+    executor::{point_lookup_key, split_conjunction},
+    expressions::{value_as_timestamp, ExpressionEvaluator},
+    partitions::{Partition, PartitionScan},
+    statistics::{
+        ColumnStatistics, TableStatistics, DEFAULT_EQUALITY_SELECTIVITY, DEFAULT_RANGE_SELECTIVITY,
+        DEFAULT_ROW_COUNT,
+    },
+};
+use fdc_core::types::{TimestampNs, Value};
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{
+    visit_expressions, BinaryOperator, Expr, JoinConstraint, JoinOperator, SetExpr, Statement, TableFactor,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::ControlFlow;
+
+/// 每毫秒可处理的行数（用于把成本换算为预估耗时）
+pub const ROWS_PER_MS: f64 = 10_000.0;
+
+/// 哈希连接构建侧每行相对探测侧的成本倍数
+pub const HASH_BUILD_FACTOR: f64 = 2.0;
+
+/// 穷举连接顺序的最大表数，超过时使用贪心算法
+pub const MAX_EXHAUSTIVE_JOIN_TABLES: usize = 6;
+
+/// 表访问方式
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccessMethod {
+    /// 全表扫描
+    SeqScan,
+    /// 主键点查
+    PrimaryKeyLookup { column: String },
+}
+
+/// 时间范围剪枝结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+    /// 时间列
+    pub column: String,
+    /// 起始时间（纳秒）
+    pub start: Option<i64>,
+    /// 结束时间（纳秒）
+    pub end: Option<i64>,
+    /// 需要扫描的数据比例
+    pub scanned_fraction: f64,
+}
+
+/// 选定的表访问路径
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessPath {
+    /// 表名
+    pub table: String,
+    /// 连接中的限定名（别名或表名）
+    pub qualifier: String,
+    /// 访问方式
+    pub method: AccessMethod,
+    /// 下推到扫描的谓词
+    pub filters: Vec<String>,
+    /// 时间范围剪枝
+    pub time_range: Option<TimeRange>,
+    /// 分区剪枝（分区表）
+    pub partitions: Option<PartitionScan>,
+    /// 表行数
+    pub table_rows: u64,
+    /// 预估输出行数
+    pub estimated_rows: f64,
+    /// 扫描成本
+    pub cost: f64,
+}
+
+/// 一个连接步骤的估算
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JoinEstimate {
+    /// 本步骤连接进来的关系
+    pub qualifier: String,
+    /// 本步骤应用的连接条件
+    pub conditions: Vec<String>,
+    /// 预估输出行数
+    pub estimated_rows: f64,
+    /// 本步骤成本
+    pub cost: f64,
+}
+
+/// FROM中的一个关系
+struct Relation {
+    table: String,
+    qualifier: String,
+    definition: Option<TableDefinition>,
+    statistics: Option<TableStatistics>,
+    /// 只引用本关系的谓词
+    local: Vec<Expr>,
+    method: AccessMethod,
+    time_range: Option<TimeRange>,
+    /// 分区表现有的分区
+    partitions: Vec<Partition>,
+    /// 剪枝后需要扫描的分区
+    scanned_partitions: Option<Vec<String>>,
+}
+
+/// 引用多个关系的谓词
+struct CrossPredicate {
+    relations: BTreeSet<usize>,
+    text: String,
+    selectivity: f64,
+}
+
+/// 基于表统计信息的代价模型
+///
+/// 初始状态对应未优化的计划：按书写顺序连接、全表扫描、WHERE在连接之后求值。
+/// 各优化规则依次修改状态，`cost()`给出当前选择下的总成本。
+pub struct CostModel {
+    relations: Vec<Relation>,
+    cross: Vec<CrossPredicate>,
+    /// 无法归属到具体关系的谓词数量
+    unattributed: usize,
+    pushdown: bool,
+    order: Vec<usize>,
+}
+
+impl CostModel {
+    /// 从SELECT语句构建代价模型；仅支持由普通表和内连接组成、且至少一张表已注册的查询
+    pub fn from_statement(statement: &Statement, catalog: &Catalog) -> Option<Self> {
+        let Statement::Query(query) = statement else {
+            return None;
+        };
+        let SetExpr::Select(select) = query.body.as_ref() else {
+            return None;
+        };
+
+        let mut factors = Vec::new();
+        let mut conjuncts: Vec<Expr> = Vec::new();
+        for item in &select.from {
+            factors.push(&item.relation);
+            for join in &item.joins {
+                match &join.join_operator {
+                    JoinOperator::Inner(JoinConstraint::On(on)) => conjuncts.extend(split_conjunction(on).into_iter().cloned()),
+                    JoinOperator::Inner(JoinConstraint::None) | JoinOperator::CrossJoin => {}
+                    _ => return None,
+                }
+                factors.push(&join.relation);
+            }
+        }
+        if let Some(selection) = &select.selection {
+            conjuncts.extend(split_conjunction(selection).into_iter().cloned());
+        }
+
+        let mut relations = Vec::with_capacity(factors.len());
+        for factor in factors {
+            let TableFactor::Table { name, alias, .. } = factor else {
+                return None;
+            };
+            let table = name.0.last()?.value.clone();
+            let qualifier = alias.as_ref().map(|a| a.name.value.clone()).unwrap_or_else(|| table.clone());
+            relations.push(Relation {
+                definition: catalog.get_table(&table),
+                statistics: catalog.statistics(&table),
+                partitions: catalog.partitions(&table),
+                scanned_partitions: None,
+                table,
+                qualifier,
+                local: Vec::new(),
+                method: AccessMethod::SeqScan,
+                time_range: None,
+            });
+        }
+        if relations.is_empty() || relations.iter().all(|r| r.definition.is_none()) {
+            return None;
+        }
+
+        let mut model = Self {
+            order: (0..relations.len()).collect(),
+            relations,
+            cross: Vec::new(),
+            unattributed: 0,
+            pushdown: false,
+        };
+        for conjunct in conjuncts {
+            match model.attribute(&conjunct) {
+                Some(referenced) if referenced.len() == 1 => {
+                    let index = *referenced.iter().next().unwrap_or(&0);
+                    model.relations[index].local.push(conjunct);
+                }
+                Some(referenced) if referenced.len() > 1 => {
+                    let selectivity = model.cross_selectivity(&conjunct);
+                    model.cross.push(CrossPredicate { relations: referenced, text: conjunct.to_string(), selectivity });
+                }
+                // 常量谓词
+                Some(_) => {}
+                None => model.unattributed += 1,
+            }
+        }
+        Some(model)
+    }
+
+    /// 当前选择下的总成本
+    pub fn cost(&self) -> f64 {
+        self.evaluate(&self.order).0
+    }
+
+    /// 当前选择下的预估结果行数
+    pub fn estimated_rows(&self) -> f64 {
+        self.evaluate(&self.order).1
+    }
+
+    /// 把单表谓词下推到扫描，返回是否有可下推的谓词
+    pub fn push_down_predicates(&mut self) -> bool {
+        self.pushdown = true;
+        self.relations.len() > 1 && self.relations.iter().any(|r| !r.local.is_empty())
+    }
+
+    /// 为存在`主键 = 常量`谓词的单表查询选择主键点查，返回选中的索引
+    ///
+    /// 原生执行器只在单表查询上使用主键点查，连接中的关系始终全表扫描。
+    pub fn select_access_paths(&mut self) -> Vec<String> {
+        let mut selected = Vec::new();
+        if self.relations.len() != 1 {
+            return selected;
+        }
+        let evaluator = ExpressionEvaluator::new();
+        let relation = &mut self.relations[0];
+        let Some(primary_key) = relation.definition.as_ref().and_then(|d| d.primary_key.clone()) else {
+            return selected;
+        };
+        // 未绑定的参数同样可以走点查
+        let lookup = relation.local.iter()
+            .any(|expr| !matches!(point_lookup_key(expr, &primary_key, &evaluator), Ok(None)));
+        if lookup {
+            selected.push(format!("{}: primary({})", relation.table, primary_key));
+            relation.method = AccessMethod::PrimaryKeyLookup { column: primary_key };
+        }
+        selected
+    }
+
+    /// 按时间列上的范围谓词剪枝，分区表同时剪去不可能命中的分区，返回剪枝描述
+    pub fn prune_time_ranges(&mut self) -> Vec<String> {
+        let mut pruned = Vec::new();
+        for relation in &mut self.relations {
+            let Some(definition) = &relation.definition else {
+                continue;
+            };
+            for column in definition.columns.iter().filter(|c| c.column_type == ColumnType::Timestamp) {
+                let (start, end) = time_bounds(&relation.local, &column.name);
+                if start.is_none() && end.is_none() {
+                    continue;
+                }
+                let scanned_fraction = relation.statistics.as_ref()
+                    .and_then(|s| s.column(&column.name))
+                    .map(|stats| {
+                        let start = start.map(|(ts, inclusive)| (Value::Timestamp(TimestampNs::from_nanos(ts)), inclusive));
+                        let end = end.map(|(ts, inclusive)| (Value::Timestamp(TimestampNs::from_nanos(ts)), inclusive));
+                        stats.overlapping_fraction(start.as_ref().map(|(v, i)| (v, *i)), end.as_ref().map(|(v, i)| (v, *i)))
+                    })
+                    .unwrap_or(1.0);
+                pruned.push(format!("{}.{}: {:.1}% scanned", relation.qualifier, column.name, scanned_fraction * 100.0));
+                let narrower = relation.time_range.as_ref().map_or(true, |r| scanned_fraction < r.scanned_fraction);
+                if narrower {
+                    relation.time_range = Some(TimeRange {
+                        column: column.name.clone(),
+                        start: start.map(|(ts, _)| ts),
+                        end: end.map(|(ts, _)| ts),
+                        scanned_fraction,
+                    });
+                }
+            }
+            if let Some(spec) = &definition.partitioning {
+                if let Some(scanned) = spec.prune(&relation.partitions, &relation.local) {
+                    pruned.push(format!("{}: {} of {} partitions", relation.qualifier, scanned.len(), relation.partitions.len()));
+                    relation.scanned_partitions = Some(scanned);
+                }
+            }
+        }
+        pruned
+    }
+
+    /// 基于基数估算选择连接顺序，返回顺序是否改变
+    pub fn reorder_joins(&mut self) -> bool {
+        let n = self.relations.len();
+        if n < 2 {
+            return false;
+        }
+        let best = if n <= MAX_EXHAUSTIVE_JOIN_TABLES {
+            let mut best = self.order.clone();
+            let mut best_cost = self.evaluate(&best).0;
+            for candidate in permutations(n) {
+                let cost = self.evaluate(&candidate).0;
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = candidate;
+                }
+            }
+            best
+        } else {
+            self.greedy_order()
+        };
+        let changed = best != self.order;
+        self.order = best;
+        changed
+    }
+
+    /// 当前连接顺序（限定名）
+    pub fn join_order(&self) -> Vec<String> {
+        self.order.iter().map(|&i| self.relations[i].qualifier.clone()).collect()
+    }
+
+    /// 按连接顺序列出访问路径
+    pub fn access_paths(&self) -> Vec<AccessPath> {
+        self.order.iter().map(|&i| {
+            let relation = &self.relations[i];
+            AccessPath {
+                table: relation.table.clone(),
+                qualifier: relation.qualifier.clone(),
+                method: relation.method.clone(),
+                filters: if self.pushdown { relation.local.iter().map(|e| e.to_string()).collect() } else { Vec::new() },
+                time_range: relation.time_range.clone(),
+                partitions: relation.scanned_partitions.clone().map(|scanned| PartitionScan {
+                    scanned,
+                    total: relation.partitions.len(),
+                }),
+                table_rows: self.table_rows(i),
+                estimated_rows: self.output_rows(i),
+                cost: self.scan_cost(i),
+            }
+        }).collect()
+    }
+
+    /// 各连接步骤的估算
+    pub fn join_estimates(&self) -> Vec<JoinEstimate> {
+        self.evaluate(&self.order).2
+    }
+
+    /// 计算给定连接顺序的成本、结果行数和连接步骤
+    fn evaluate(&self, order: &[usize]) -> (f64, f64, Vec<JoinEstimate>) {
+        let mut cost: f64 = order.iter().map(|&i| self.scan_cost(i)).sum();
+        let mut rows = self.output_rows(order[0]);
+        let mut joined = BTreeSet::from([order[0]]);
+        let mut steps = Vec::with_capacity(order.len().saturating_sub(1));
+
+        for &next in &order[1..] {
+            joined.insert(next);
+            let applied: Vec<&CrossPredicate> = self.cross.iter()
+                .filter(|p| p.relations.contains(&next) && p.relations.is_subset(&joined))
+                .collect();
+            let input = self.output_rows(next);
+            let output = applied.iter().fold(rows * input, |acc, p| acc * p.selectivity);
+            // 左深树：累积结果为探测侧，新关系为构建侧
+            let step_cost = rows + input * HASH_BUILD_FACTOR + output;
+            cost += step_cost;
+            steps.push(JoinEstimate {
+                qualifier: self.relations[next].qualifier.clone(),
+                conditions: applied.iter().map(|p| p.text.clone()).collect(),
+                estimated_rows: output,
+                cost: step_cost,
+            });
+            rows = output;
+        }
+
+        // 未下推的单表谓词在连接之后过滤
+        if !self.pushdown {
+            cost += rows;
+            rows = order.iter().fold(rows, |acc, &i| acc * self.local_selectivity(i));
+        }
+        rows *= DEFAULT_RANGE_SELECTIVITY.powi(self.unattributed as i32);
+        (cost, rows, steps)
+    }
+
+    /// 贪心：从最小的关系开始，每次连接使中间结果最小的关系
+    fn greedy_order(&self) -> Vec<usize> {
+        let mut remaining: Vec<usize> = (0..self.relations.len()).collect();
+        remaining.sort_by(|&a, &b| self.output_rows(a).total_cmp(&self.output_rows(b)));
+        let mut order = vec![remaining.remove(0)];
+        while !remaining.is_empty() {
+            let (position, _) = remaining.iter().enumerate()
+                .map(|(position, &candidate)| {
+                    let mut trial = order.clone();
+                    trial.push(candidate);
+                    (position, self.evaluate(&trial).1)
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 0.0));
+            order.push(remaining.remove(position));
+        }
+        order
+    }
+
+    fn table_rows(&self, index: usize) -> u64 {
+        self.relations[index].statistics.as_ref().map(|s| s.row_count).unwrap_or(DEFAULT_ROW_COUNT)
+    }
+
+    fn scan_cost(&self, index: usize) -> f64 {
+        let relation = &self.relations[index];
+        match relation.method {
+            AccessMethod::PrimaryKeyLookup { .. } => 1.0,
+            AccessMethod::SeqScan => {
+                let mut fraction = relation.time_range.as_ref().map(|r| r.scanned_fraction).unwrap_or(1.0);
+                // 时间范围与分区剪枝取扫描比例较小者
+                if let (Some(scanned), false) = (&relation.scanned_partitions, relation.partitions.is_empty()) {
+                    fraction = fraction.min(scanned.len() as f64 / relation.partitions.len() as f64);
+                }
+                self.table_rows(index) as f64 * fraction
+            }
+        }
+    }
+
+    /// 关系输入到连接的行数
+    fn output_rows(&self, index: usize) -> f64 {
+        let rows = self.table_rows(index) as f64;
+        if let AccessMethod::PrimaryKeyLookup { .. } = self.relations[index].method {
+            return rows.min(1.0);
+        }
+        if !self.pushdown {
+            return rows;
+        }
+        (rows * self.local_selectivity(index)).max(rows.min(1.0))
+    }
+
+    fn local_selectivity(&self, index: usize) -> f64 {
+        let relation = &self.relations[index];
+        relation.local.iter().map(|expr| self.selectivity(relation, expr)).product()
+    }
+
+    /// 单表谓词的选择率
+    fn selectivity(&self, relation: &Relation, expr: &Expr) -> f64 {
+        let row_count = relation.statistics.as_ref().map(|s| s.row_count).unwrap_or(DEFAULT_ROW_COUNT);
+        match expr {
+            Expr::Nested(inner) => self.selectivity(relation, inner),
+            Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+                self.selectivity(relation, left) * self.selectivity(relation, right)
+            }
+            Expr::BinaryOp { left, op: BinaryOperator::Or, right } => {
+                let (a, b) = (self.selectivity(relation, left), self.selectivity(relation, right));
+                a + b - a * b
+            }
+            Expr::UnaryOp { op: sqlparser::ast::UnaryOperator::Not, expr } => 1.0 - self.selectivity(relation, expr),
+            Expr::BinaryOp { left, op, right } => {
+                let default = match op {
+                    BinaryOperator::Eq => DEFAULT_EQUALITY_SELECTIVITY,
+                    BinaryOperator::NotEq => 1.0 - DEFAULT_EQUALITY_SELECTIVITY,
+                    _ => DEFAULT_RANGE_SELECTIVITY,
+                };
+                let (column, value, op) = match (column_constant(relation, left, right), column_constant(relation, right, left)) {
+                    (Some((column, value)), _) => (column, value, op.clone()),
+                    (None, Some((column, value))) => match flip(op) {
+                        Some(op) => (column, value, op),
+                        None => return default,
+                    },
+                    _ => return default,
+                };
+                let Some(stats) = column_statistics(relation, &column) else {
+                    return default;
+                };
+                match op {
+                    BinaryOperator::Eq => stats.equality_selectivity(&value, row_count),
+                    BinaryOperator::NotEq => 1.0 - stats.equality_selectivity(&value, row_count),
+                    BinaryOperator::Lt => stats.range_selectivity(None, Some((&value, false)), row_count),
+                    BinaryOperator::LtEq => stats.range_selectivity(None, Some((&value, true)), row_count),
+                    BinaryOperator::Gt => stats.range_selectivity(Some((&value, false)), None, row_count),
+                    BinaryOperator::GtEq => stats.range_selectivity(Some((&value, true)), None, row_count),
+                    _ => default,
+                }
+            }
+            Expr::Between { expr, negated, low, high } => {
+                let bounds = (column_constant(relation, expr, low), column_constant(relation, expr, high));
+                let selectivity = match bounds {
+                    (Some((column, low)), Some((_, high))) => column_statistics(relation, &column)
+                        .map(|stats| stats.range_selectivity(Some((&low, true)), Some((&high, true)), row_count))
+                        .unwrap_or(DEFAULT_RANGE_SELECTIVITY),
+                    _ => DEFAULT_RANGE_SELECTIVITY,
+                };
+                if *negated { 1.0 - selectivity } else { selectivity }
+            }
+            Expr::InList { expr, list, negated } => {
+                let selectivity = list.iter()
+                    .map(|item| match column_constant(relation, expr, item) {
+                        Some((column, value)) => column_statistics(relation, &column)
+                            .map(|stats| stats.equality_selectivity(&value, row_count))
+                            .unwrap_or(DEFAULT_EQUALITY_SELECTIVITY),
+                        None => DEFAULT_EQUALITY_SELECTIVITY,
+                    })
+                    .sum::<f64>()
+                    .min(1.0);
+                if *negated { 1.0 - selectivity } else { selectivity }
+            }
+            Expr::IsNull(inner) | Expr::IsNotNull(inner) => {
+                let null_fraction = column_name(inner)
+                    .and_then(|column| column_statistics(relation, &column))
+                    .filter(|_| row_count > 0)
+                    .map(|stats| stats.null_count as f64 / row_count as f64)
+                    .unwrap_or(DEFAULT_EQUALITY_SELECTIVITY);
+                if matches!(expr, Expr::IsNull(_)) { null_fraction } else { 1.0 - null_fraction }
+            }
+            _ => DEFAULT_RANGE_SELECTIVITY,
+        }
+    }
+
+    /// 跨关系谓词的选择率：等值连接为`1 / max(NDV)`
+    fn cross_selectivity(&self, expr: &Expr) -> f64 {
+        let Expr::BinaryOp { left, op: BinaryOperator::Eq, right } = expr else {
+            return DEFAULT_RANGE_SELECTIVITY;
+        };
+        let distinct = |side: &Expr| -> Option<u64> {
+            let index = *self.attribute(side)?.iter().next()?;
+            let column = column_name(side)?;
+            let relation = &self.relations[index];
+            Some(match &relation.statistics {
+                Some(stats) => stats.distinct_count(&column),
+                None => DEFAULT_ROW_COUNT,
+            })
+        };
+        match (distinct(left), distinct(right)) {
+            (Some(a), Some(b)) => 1.0 / a.max(b).max(1) as f64,
+            _ => DEFAULT_EQUALITY_SELECTIVITY,
+        }
+    }
+
+    /// 谓词引用的关系集合，存在无法归属的列时返回None
+    fn attribute(&self, expr: &Expr) -> Option<BTreeSet<usize>> {
+        let mut referenced = BTreeSet::new();
+        let mut resolved = true;
+        let _ = visit_expressions(expr, |e| {
+            let index = match e {
+                Expr::CompoundIdentifier(idents) if idents.len() >= 2 => {
+                    let qualifier = &idents[idents.len() - 2].value;
+                    self.relations.iter().position(|r| {
+                        r.qualifier.eq_ignore_ascii_case(qualifier) || r.table.eq_ignore_ascii_case(qualifier)
+                    })
+                }
+                Expr::Identifier(ident) => self.owner_of(&ident.value),
+                _ => return ControlFlow::<()>::Continue(()),
+            };
+            match index {
+                Some(index) => {
+                    referenced.insert(index);
+                }
+                None => resolved = false,
+            }
+            ControlFlow::Continue(())
+        });
+        resolved.then_some(referenced)
+    }
+
+    /// 未限定列名所属的唯一关系
+    fn owner_of(&self, column: &str) -> Option<usize> {
+        if self.relations.len() == 1 {
+            return Some(0);
+        }
+        let mut owners = self.relations.iter().enumerate()
+            .filter(|(_, r)| r.definition.as_ref().is_some_and(|d| d.column(column).is_some()))
+            .map(|(i, _)| i);
+        match (owners.next(), owners.next()) {
+            (Some(owner), None) => Some(owner),
+            _ => None,
+        }
+    }
+}
+
+/// 谓词引用的关系限定名；存在未限定的列时返回None
+pub(crate) fn referenced_qualifiers(expr: &Expr) -> Option<BTreeSet<String>> {
+    let mut qualifiers = BTreeSet::new();
+    let mut qualified = true;
+    let _ = visit_expressions(expr, |e| {
+        match e {
+            Expr::CompoundIdentifier(idents) if idents.len() >= 2 => {
+                qualifiers.insert(idents[idents.len() - 2].value.to_lowercase());
+            }
+            Expr::Identifier(_) | Expr::CompoundIdentifier(_) => qualified = false,
+            _ => {}
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    qualified.then_some(qualifiers)
+}
+
+/// 全部排列（用于穷举连接顺序）
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    fn extend(prefix: &mut Vec<usize>, used: &mut [bool], out: &mut Vec<Vec<usize>>) {
+        if prefix.len() == used.len() {
+            out.push(prefix.clone());
+            return;
+        }
+        for i in 0..used.len() {
+            if !used[i] {
+                used[i] = true;
+                prefix.push(i);
+                extend(prefix, used, out);
+                prefix.pop();
+                used[i] = false;
+            }
+        }
+    }
+    let mut out = Vec::new();
+    extend(&mut Vec::with_capacity(n), &mut vec![false; n], &mut out);
+    out
+}
+
+fn column_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(ident) => Some(ident.value.clone()),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|i| i.value.clone()),
+        Expr::Nested(inner) => column_name(inner),
+        _ => None,
+    }
+}
+
+fn column_statistics<'a>(relation: &'a Relation, column: &str) -> Option<&'a ColumnStatistics> {
+    relation.statistics.as_ref()?.column(column)
+}
+
+/// 识别`列`与`常量`的组合，常量按列类型转换
+fn column_constant(relation: &Relation, column: &Expr, constant: &Expr) -> Option<(String, Value)> {
+    let name = column_name(column)?;
+    let column_type = relation.definition.as_ref()?.column(&name)?.column_type;
+    let value = ExpressionEvaluator::new().evaluate(constant, &HashMap::new()).ok()?;
+    Some((name, column_type.coerce(value).ok()?))
+}
+
+/// 交换比较运算符两侧
+fn flip(op: &BinaryOperator) -> Option<BinaryOperator> {
+    Some(match op {
+        BinaryOperator::Eq => BinaryOperator::Eq,
+        BinaryOperator::NotEq => BinaryOperator::NotEq,
+        BinaryOperator::Lt => BinaryOperator::Gt,
+        BinaryOperator::LtEq => BinaryOperator::GtEq,
+        BinaryOperator::Gt => BinaryOperator::Lt,
+        BinaryOperator::GtEq => BinaryOperator::LtEq,
+        _ => return None,
+    })
+}
+
+pub(crate) type TimeBound = Option<(i64, bool)>;
+
+/// 从单表谓词中提取时间列的上下界（纳秒，是否包含）
+pub(crate) fn time_bounds(predicates: &[Expr], column: &str) -> (TimeBound, TimeBound) {
+    let evaluator = ExpressionEvaluator::new();
+    let constant = |expr: &Expr| -> Option<i64> {
+        let value = evaluator.evaluate(expr, &HashMap::new()).ok()?;
+        value_as_timestamp(&value).map(|ts| ts.as_nanos())
+    };
+    let is_column = |expr: &Expr| column_name(expr).is_some_and(|name| name.eq_ignore_ascii_case(column));
+
+    let mut start: TimeBound = None;
+    let mut end: TimeBound = None;
+    let mut tighten_start = |bound: (i64, bool)| {
+        if start.map_or(true, |current| bound.0 > current.0 || (bound.0 == current.0 && !bound.1)) {
+            start = Some(bound);
+        }
+    };
+    let mut ends = Vec::new();
+    for predicate in predicates {
+        match predicate {
+            Expr::BinaryOp { left, op, right } => {
+                let (op, value) = if is_column(left) {
+                    (op.clone(), constant(right))
+                } else if is_column(right) {
+                    match flip(op) {
+                        Some(op) => (op, constant(left)),
+                        None => continue,
+                    }
+                } else {
+                    continue;
+                };
+                let Some(value) = value else {
+                    continue;
+                };
+                match op {
+                    BinaryOperator::Gt => tighten_start((value, false)),
+                    BinaryOperator::GtEq => tighten_start((value, true)),
+                    BinaryOperator::Lt => ends.push((value, false)),
+                    BinaryOperator::LtEq => ends.push((value, true)),
+                    BinaryOperator::Eq => {
+                        tighten_start((value, true));
+                        ends.push((value, true));
+                    }
+                    _ => {}
+                }
+            }
+            Expr::Between { expr, negated: false, low, high } if is_column(expr) => {
+                if let (Some(low), Some(high)) = (constant(low), constant(high)) {
+                    tighten_start((low, true));
+                    ends.push((high, true));
+                }
+            }
+            _ => {}
+        }
+    }
+    for bound in ends {
+        if end.map_or(true, |current: (i64, bool)| bound.0 < current.0 || (bound.0 == current.0 && !bound.1)) {
+            end = Some(bound);
+        }
+    }
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ColumnDefinition;
+    use crate::executor::parse_statement;
+    use std::collections::HashMap;
+
+    fn catalog() -> Catalog {
+        let catalog = Catalog::new();
+        catalog.register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("ts", ColumnType::Timestamp),
+        ]).with_primary_key("id")).unwrap();
+        catalog.register_table(TableDefinition::new("symbols", vec![
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("sector", ColumnType::String),
+        ])).unwrap();
+
+        let trades: Vec<_> = (0..1000).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(i));
+            row.insert("symbol".to_string(), Value::String(format!("S{}", i % 10)));
+            row.insert("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(i * 1_000_000_000)));
+            row
+        }).collect();
+        let symbols: Vec<_> = (0..10).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("symbol".to_string(), Value::String(format!("S{}", i)));
+            row.insert("sector".to_string(), Value::String(if i == 0 { "tech" } else { "other" }.to_string()));
+            row
+        }).collect();
+        for (name, rows) in [("trades", trades), ("symbols", symbols)] {
+            let definition = catalog.get_table(name).unwrap();
+            catalog.set_statistics(TableStatistics::compute(&definition, &rows, 16));
+        }
+        catalog
+    }
+
+    fn model(catalog: &Catalog, sql: &str) -> CostModel {
+        CostModel::from_statement(&parse_statement(sql).unwrap(), catalog).unwrap()
+    }
+
+    #[test]
+    fn test_point_lookup_and_time_pruning() {
+        let catalog = catalog();
+
+        let mut lookup = model(&catalog, "SELECT * FROM trades WHERE id = 42");
+        assert_eq!(lookup.cost(), 1000.0 + 1000.0);
+        assert_eq!(lookup.select_access_paths(), vec!["trades: primary(id)".to_string()]);
+        lookup.push_down_predicates();
+        assert!(lookup.cost() < 3.0);
+
+        let mut range = model(&catalog, "SELECT * FROM trades WHERE ts >= '1970-01-01 00:15:00'");
+        assert_eq!(range.prune_time_ranges().len(), 1);
+        let path = &range.access_paths()[0];
+        let fraction = path.time_range.as_ref().unwrap().scanned_fraction;
+        assert!(fraction > 0.05 && fraction < 0.2, "{}", fraction);
+        assert!(path.cost < 200.0);
+    }
+
+    #[test]
+    fn test_join_reordering() {
+        let catalog = catalog();
+        let mut model = model(&catalog,
+            "SELECT * FROM symbols s JOIN trades t ON t.symbol = s.symbol WHERE s.sector = 'tech'");
+        let original = model.cost();
+
+        // 过滤后的symbols只剩一行，应作为构建侧
+        model.push_down_predicates();
+        assert!(model.reorder_joins());
+        assert_eq!(model.join_order(), vec!["t".to_string(), "s".to_string()]);
+        assert!(model.cost() < original);
+
+        let steps = model.join_estimates();
+        assert_eq!(steps[0].conditions, vec!["t.symbol = s.symbol".to_string()]);
+        assert!((steps[0].estimated_rows - 100.0).abs() < 1.0, "{}", steps[0].estimated_rows);
+    }
+}
@@ -0,0 +1,616 @@
+//! DataFusion-backed analytical execution
+
+use crate::{
+    cancellation::{QueryRegistry, RunningQuery},
+    catalog::{Catalog, ColumnType, TableDefinition},
+    executor::{ExecutionResult, ExecutionStats, QueryExecutor, ExecutionContext},
+    expressions::{compare_values, value_as_decimal, value_as_f64, value_as_i64, value_as_str, value_as_timestamp},
+    optimizer::OptimizedPlan,
+};
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        array::{
+            Array, ArrayRef, BinaryArray, BooleanArray, Decimal128Array, Float64Array, Int64Array,
+            StringArray, TimestampNanosecondArray, UInt64Array,
+        },
+        compute::cast,
+        datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+        record_batch::RecordBatch,
+        util::display::array_value_to_string,
+    },
+    catalog::Session,
+    common::{ParamValues, ScalarValue},
+    datasource::{TableProvider, TableType},
+    error::DataFusionError,
+    logical_expr::{Between, BinaryExpr, Expr, Operator, TableProviderFilterPushDown},
+    parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder,
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+    prelude::SessionContext,
+};
+use fdc_core::{error::{Error, Result}, types::{TimestampNs, Value}};
+use fdc_storage::engine::StorageEngine;
+use parking_lot::Mutex;
+use rust_decimal::Decimal;
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Decimal/Price列映射到Arrow时使用的精度
+const DECIMAL_PRECISION: u8 = 38;
+/// Decimal/Price列映射到Arrow时使用的小数位
+const DECIMAL_SCALE: i8 = 10;
+
+/// 列类型到Arrow类型的映射
+pub fn arrow_data_type(column_type: ColumnType) -> DataType {
+    match column_type {
+        ColumnType::Boolean => DataType::Boolean,
+        ColumnType::Int64 => DataType::Int64,
+        ColumnType::Float64 => DataType::Float64,
+        ColumnType::Decimal | ColumnType::Price => DataType::Decimal128(DECIMAL_PRECISION, DECIMAL_SCALE),
+        ColumnType::String | ColumnType::Symbol => DataType::Utf8,
+        ColumnType::Binary => DataType::Binary,
+        ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Nanosecond, None),
+        ColumnType::Volume => DataType::UInt64,
+    }
+}
+
+/// 表定义对应的Arrow schema
+pub fn arrow_schema(table: &TableDefinition) -> SchemaRef {
+    let fields: Vec<Field> = table.columns.iter()
+        .map(|c| Field::new(&c.name, arrow_data_type(c.column_type), c.nullable))
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+/// 下推到存储扫描的列范围（闭区间，None表示该侧不限）
+#[derive(Debug, Clone, Default)]
+struct ColumnRange {
+    lower: Option<Value>,
+    upper: Option<Value>,
+}
+
+impl ColumnRange {
+    /// 与另一范围取交集
+    fn intersect(&mut self, other: ColumnRange) {
+        if let Some(lower) = other.lower {
+            if self.lower.as_ref().map_or(true, |current| compare_values(&lower, current) == Some(Ordering::Greater)) {
+                self.lower = Some(lower);
+            }
+        }
+        if let Some(upper) = other.upper {
+            if self.upper.as_ref().map_or(true, |current| compare_values(&upper, current) == Some(Ordering::Less)) {
+                self.upper = Some(upper);
+            }
+        }
+    }
+
+    /// 值是否落在范围内；NULL不满足任何范围条件
+    fn contains(&self, value: Option<&Value>) -> bool {
+        let Some(value) = value.filter(|v| !matches!(v, Value::Null)) else {
+            return false;
+        };
+        let above = self.lower.as_ref().map_or(true, |lower| compare_values(value, lower) != Some(Ordering::Less));
+        let below = self.upper.as_ref().map_or(true, |upper| compare_values(value, upper) != Some(Ordering::Greater));
+        above && below
+    }
+}
+
+/// DataFusion字面量转换为行值（不支持的类型返回None）
+fn literal_value(scalar: &ScalarValue) -> Option<Value> {
+    Some(match scalar {
+        ScalarValue::Boolean(Some(b)) => Value::Bool(*b),
+        ScalarValue::Int8(Some(v)) => Value::Int64(*v as i64),
+        ScalarValue::Int16(Some(v)) => Value::Int64(*v as i64),
+        ScalarValue::Int32(Some(v)) => Value::Int64(*v as i64),
+        ScalarValue::Int64(Some(v)) => Value::Int64(*v),
+        ScalarValue::UInt8(Some(v)) => Value::UInt64(*v as u64),
+        ScalarValue::UInt16(Some(v)) => Value::UInt64(*v as u64),
+        ScalarValue::UInt32(Some(v)) => Value::UInt64(*v as u64),
+        ScalarValue::UInt64(Some(v)) => Value::UInt64(*v),
+        ScalarValue::Float32(Some(v)) => Value::Float64(*v as f64),
+        ScalarValue::Float64(Some(v)) => Value::Float64(*v),
+        ScalarValue::Decimal128(Some(mantissa), _, scale) => {
+            Value::Decimal(Decimal::try_from_i128_with_scale(*mantissa, (*scale).max(0) as u32).ok()?)
+        }
+        ScalarValue::Utf8(Some(s)) | ScalarValue::LargeUtf8(Some(s)) => Value::String(s.clone()),
+        ScalarValue::TimestampSecond(Some(v), _) => Value::Timestamp(TimestampNs::from_nanos(v.checked_mul(1_000_000_000)?)),
+        ScalarValue::TimestampMillisecond(Some(v), _) => Value::Timestamp(TimestampNs::from_nanos(v.checked_mul(1_000_000)?)),
+        ScalarValue::TimestampMicrosecond(Some(v), _) => Value::Timestamp(TimestampNs::from_nanos(v.checked_mul(1_000)?)),
+        ScalarValue::TimestampNanosecond(Some(v), _) => Value::Timestamp(TimestampNs::from_nanos(*v)),
+        _ => return None,
+    })
+}
+
+/// 由存储引擎行数据和Parquet数据段组成的DataFusion表
+pub struct StorageTableProvider {
+    table: TableDefinition,
+    schema: SchemaRef,
+    storage: Arc<dyn StorageEngine>,
+    catalog: Arc<Catalog>,
+}
+
+impl StorageTableProvider {
+    /// 创建新的表提供者
+    pub fn new(table: TableDefinition, storage: Arc<dyn StorageEngine>, catalog: Arc<Catalog>) -> Self {
+        let schema = arrow_schema(&table);
+        Self { table, schema, storage, catalog }
+    }
+
+    /// 可下推的过滤条件：主键或时间列与字面量的比较、BETWEEN
+    ///
+    /// 返回列名与该条件限定的范围；字面量已转换为列类型。
+    fn range_filter(&self, filter: &Expr) -> Option<(String, ColumnRange)> {
+        let (column, range) = match filter {
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let (column, op, literal) = match (left.as_ref(), right.as_ref()) {
+                    (Expr::Column(column), Expr::Literal(literal)) => (column, *op, literal),
+                    (Expr::Literal(literal), Expr::Column(column)) => (column, op.swap()?, literal),
+                    _ => return None,
+                };
+                let value = literal_value(literal)?;
+                let range = match op {
+                    Operator::Eq => ColumnRange { lower: Some(value.clone()), upper: Some(value) },
+                    Operator::Gt | Operator::GtEq => ColumnRange { lower: Some(value), upper: None },
+                    Operator::Lt | Operator::LtEq => ColumnRange { lower: None, upper: Some(value) },
+                    _ => return None,
+                };
+                (column, range)
+            }
+            Expr::Between(Between { expr, negated: false, low, high }) => match (expr.as_ref(), low.as_ref(), high.as_ref()) {
+                (Expr::Column(column), Expr::Literal(low), Expr::Literal(high)) => {
+                    (column, ColumnRange { lower: Some(literal_value(low)?), upper: Some(literal_value(high)?) })
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let definition = self.table.column(&column.name)?;
+        let is_key = self.table.key_order().is_some_and(|key| key.eq_ignore_ascii_case(&definition.name));
+        if !is_key && definition.column_type != ColumnType::Timestamp {
+            return None;
+        }
+        let coerce = |value: Option<Value>| match value {
+            Some(value) => definition.column_type.coerce(value).ok().map(Some),
+            None => Some(None),
+        };
+        let range = ColumnRange { lower: coerce(range.lower)?, upper: coerce(range.upper)? };
+        Some((definition.name.clone(), range))
+    }
+
+    /// 汇总下推的过滤条件为各列的范围
+    fn column_ranges(&self, filters: &[Expr]) -> HashMap<String, ColumnRange> {
+        let mut ranges: HashMap<String, ColumnRange> = HashMap::new();
+        for (column, range) in filters.iter().filter_map(|filter| self.range_filter(filter)) {
+            ranges.entry(column).or_default().intersect(range);
+        }
+        ranges
+    }
+
+    /// 读取表数据为RecordBatch
+    ///
+    /// 主键范围直接限定存储扫描的键区间，时间范围在解码后逐行过滤；过滤以`Inexact`方式下推，
+    /// DataFusion仍会复核。`limit`只在没有过滤条件时由DataFusion传入，读够即停止。
+    async fn load_batches(&self, ranges: &HashMap<String, ColumnRange>, limit: Option<usize>) -> Result<Vec<RecordBatch>> {
+        let mut batches = Vec::new();
+        let mut remaining = limit.unwrap_or(usize::MAX);
+
+        for segment in &self.table.parquet_segments {
+            if remaining == 0 {
+                return Ok(batches);
+            }
+            for batch in read_parquet_segment(segment, &self.schema)? {
+                remaining = remaining.saturating_sub(batch.num_rows());
+                batches.push(batch);
+                if remaining == 0 {
+                    break;
+                }
+            }
+        }
+        if remaining == 0 {
+            return Ok(batches);
+        }
+
+        let key = self.table.key_order().and_then(|key| ranges.get(key));
+        let filtered = ranges.len() > usize::from(key.is_some());
+        let scan_limit = limit.filter(|_| !filtered).map(|_| remaining);
+        let mut rows = self.catalog.scan_key_range(
+            self.storage.as_ref(),
+            &self.table.name,
+            key.and_then(|range| range.lower.as_ref()),
+            key.and_then(|range| range.upper.as_ref()),
+            scan_limit,
+        ).await?;
+        rows.retain(|row| ranges.iter().all(|(column, range)| range.contains(row.get(column))));
+        rows.truncate(remaining);
+        if !rows.is_empty() {
+            batches.push(rows_to_record_batch(&self.table, &self.schema, &rows)?);
+        }
+
+        Ok(batches)
+    }
+}
+
+impl std::fmt::Debug for StorageTableProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StorageTableProvider")
+            .field("table", &self.table.name)
+            .field("schema", &self.schema)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl TableProvider for StorageTableProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(&self, filters: &[&Expr]) -> datafusion::error::Result<Vec<TableProviderFilterPushDown>> {
+        Ok(filters.iter()
+            .map(|filter| match self.range_filter(filter) {
+                Some(_) => TableProviderFilterPushDown::Inexact,
+                None => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+        let ranges = self.column_ranges(filters);
+        let batches = self.load_batches(&ranges, limit).await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let exec = MemoryExec::try_new(&[batches], self.schema.clone(), projection.cloned())?;
+        Ok(Arc::new(exec))
+    }
+}
+
+/// 读取Parquet数据段并转换为表schema
+fn read_parquet_segment(path: &std::path::Path, schema: &SchemaRef) -> Result<Vec<RecordBatch>> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| Error::query(format!("Failed to open parquet segment {}: {}", path.display(), e)))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(|builder| builder.build())
+        .map_err(|e| Error::query(format!("Failed to read parquet segment {}: {}", path.display(), e)))?;
+
+    let mut batches = Vec::new();
+    for batch in reader {
+        let batch = batch.map_err(|e| Error::query(format!("Failed to decode parquet batch: {}", e)))?;
+        let mut columns = Vec::with_capacity(schema.fields().len());
+        for field in schema.fields() {
+            let column = match batch.column_by_name(field.name()) {
+                Some(column) => cast(column, field.data_type())
+                    .map_err(|e| Error::query(format!("Failed to cast column {}: {}", field.name(), e)))?,
+                None => datafusion::arrow::array::new_null_array(field.data_type(), batch.num_rows()),
+            };
+            columns.push(column);
+        }
+        batches.push(RecordBatch::try_new(schema.clone(), columns)
+            .map_err(|e| Error::query(format!("Invalid parquet batch: {}", e)))?);
+    }
+    Ok(batches)
+}
+
+/// 行数据转换为RecordBatch
+fn rows_to_record_batch(
+    table: &TableDefinition,
+    schema: &SchemaRef,
+    rows: &[HashMap<String, Value>],
+) -> Result<RecordBatch> {
+    let columns: Vec<ArrayRef> = table.columns.iter().map(|column| {
+        let values = rows.iter().map(|row| row.get(&column.name).filter(|v| !matches!(v, Value::Null)));
+        let array: ArrayRef = match column.column_type {
+            ColumnType::Boolean => Arc::new(values.map(|v| v.and_then(|v| match v {
+                Value::Bool(b) => Some(*b),
+                _ => None,
+            })).collect::<BooleanArray>()),
+            ColumnType::Int64 => Arc::new(values.map(|v| v.and_then(value_as_i64)).collect::<Int64Array>()),
+            ColumnType::Float64 => Arc::new(values.map(|v| v.and_then(value_as_f64)).collect::<Float64Array>()),
+            ColumnType::Decimal | ColumnType::Price => {
+                let mantissas: Decimal128Array = values.map(|v| v.and_then(value_as_decimal).map(|d| {
+                    let mut d = d;
+                    d.rescale(DECIMAL_SCALE as u32);
+                    d.mantissa()
+                })).collect();
+                Arc::new(mantissas.with_precision_and_scale(DECIMAL_PRECISION, DECIMAL_SCALE)
+                    .map_err(|e| Error::query(format!("Invalid decimal column {}: {}", column.name, e)))?)
+            }
+            ColumnType::String | ColumnType::Symbol => {
+                Arc::new(values.map(|v| v.and_then(value_as_str)).collect::<StringArray>())
+            }
+            ColumnType::Binary => Arc::new(BinaryArray::from_iter(values.map(|v| v.and_then(|v| match v {
+                Value::Binary(b) => Some(b.clone()),
+                _ => None,
+            })))),
+            ColumnType::Timestamp => Arc::new(values
+                .map(|v| v.and_then(value_as_timestamp).map(|ts| ts.as_nanos()))
+                .collect::<TimestampNanosecondArray>()),
+            ColumnType::Volume => Arc::new(values.map(|v| v.and_then(|v| match v {
+                Value::Volume(volume) => Some(volume.as_u64()),
+                other => value_as_i64(other).map(|i| i as u64),
+            })).collect::<UInt64Array>()),
+        };
+        Ok(array)
+    }).collect::<Result<_>>()?;
+
+    RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| Error::query(format!("Failed to build record batch for {}: {}", table.name, e)))
+}
+
+/// RecordBatch转换为行数据
+fn record_batch_to_rows(batch: &RecordBatch) -> Vec<HashMap<String, Value>> {
+    let schema = batch.schema();
+    let mut rows = vec![HashMap::with_capacity(schema.fields().len()); batch.num_rows()];
+
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        for (i, row) in rows.iter_mut().enumerate() {
+            row.insert(field.name().clone(), array_value(column, i));
+        }
+    }
+    rows
+}
+
+/// 读取数组中的单个值
+fn array_value(array: &ArrayRef, i: usize) -> Value {
+    if array.is_null(i) {
+        return Value::Null;
+    }
+    let any = array.as_any();
+    match array.data_type() {
+        DataType::Boolean => Value::Bool(any.downcast_ref::<BooleanArray>().map(|a| a.value(i)).unwrap_or_default()),
+        DataType::Int64 => Value::Int64(any.downcast_ref::<Int64Array>().map(|a| a.value(i)).unwrap_or_default()),
+        DataType::UInt64 => Value::UInt64(any.downcast_ref::<UInt64Array>().map(|a| a.value(i)).unwrap_or_default()),
+        DataType::Float64 => Value::Float64(any.downcast_ref::<Float64Array>().map(|a| a.value(i)).unwrap_or_default()),
+        DataType::Utf8 => Value::String(any.downcast_ref::<StringArray>().map(|a| a.value(i).to_string()).unwrap_or_default()),
+        DataType::Binary => Value::Binary(any.downcast_ref::<BinaryArray>().map(|a| a.value(i).to_vec()).unwrap_or_default()),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => Value::Timestamp(TimestampNs::from_nanos(
+            any.downcast_ref::<TimestampNanosecondArray>().map(|a| a.value(i)).unwrap_or_default(),
+        )),
+        DataType::Decimal128(_, scale) => any.downcast_ref::<Decimal128Array>()
+            .and_then(|a| Decimal::try_from_i128_with_scale(a.value(i), (*scale).max(0) as u32).ok())
+            .map(Value::Decimal)
+            .unwrap_or(Value::Null),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 | DataType::UInt32 => {
+            match cast(array, &DataType::Int64) {
+                Ok(widened) => array_value(&widened, i),
+                Err(_) => Value::Null,
+            }
+        }
+        DataType::Float32 => match cast(array, &DataType::Float64) {
+            Ok(widened) => array_value(&widened, i),
+            Err(_) => Value::Null,
+        },
+        _ => array_value_to_string(array, i).map(Value::String).unwrap_or(Value::Null),
+    }
+}
+
+/// 将绑定参数转换为DataFusion参数：`$1..$n`按位置，其余按名称
+fn param_values(parameters: &HashMap<String, Value>) -> Result<ParamValues> {
+    let mut positions = Vec::with_capacity(parameters.len());
+    for name in parameters.keys() {
+        match name.parse::<usize>() {
+            Ok(position) if position > 0 => positions.push(position),
+            _ => {
+                return Ok(ParamValues::Map(parameters.iter()
+                    .map(|(name, value)| (name.clone(), scalar_value(value)))
+                    .collect()));
+            }
+        }
+    }
+    positions.sort_unstable();
+    if positions.iter().enumerate().any(|(i, position)| *position != i + 1) {
+        return Err(Error::validation("Positional parameters must be numbered $1..$n without gaps"));
+    }
+    Ok(ParamValues::List(positions.iter()
+        .map(|position| scalar_value(&parameters[&position.to_string()]))
+        .collect()))
+}
+
+/// 将单个值转换为DataFusion标量
+fn scalar_value(value: &Value) -> ScalarValue {
+    match value {
+        Value::Null => ScalarValue::Null,
+        Value::Bool(b) => ScalarValue::Boolean(Some(*b)),
+        Value::UInt8(_) | Value::UInt16(_) | Value::UInt32(_) | Value::UInt64(_) => {
+            ScalarValue::UInt64(value_as_i64(value).map(|i| i as u64))
+        }
+        Value::Volume(volume) => ScalarValue::UInt64(Some(volume.as_u64())),
+        Value::Float32(_) | Value::Float64(_) => ScalarValue::Float64(value_as_f64(value)),
+        Value::Decimal(_) | Value::Price(_) => match value_as_decimal(value) {
+            Some(mut d) => {
+                d.rescale(DECIMAL_SCALE as u32);
+                ScalarValue::Decimal128(Some(d.mantissa()), DECIMAL_PRECISION, DECIMAL_SCALE)
+            }
+            None => ScalarValue::Null,
+        },
+        Value::Timestamp(ts) => ScalarValue::TimestampNanosecond(Some(ts.as_nanos()), None),
+        Value::Binary(bytes) => ScalarValue::Binary(Some(bytes.clone())),
+        Value::Int8(_) | Value::Int16(_) | Value::Int32(_) | Value::Int64(_) | Value::Int128(_) => {
+            ScalarValue::Int64(value_as_i64(value))
+        }
+        other => ScalarValue::Utf8(value_as_str(other)),
+    }
+}
+
+/// 基于DataFusion的分析型查询执行器
+///
+/// 目录中的表注册为`StorageTableProvider`，由DataFusion完成连接、聚合、
+/// 窗口函数和子查询等复杂SQL。会话在表定义不变时复用。
+pub struct DataFusionExecutor {
+    /// 存储引擎
+    storage_engine: Arc<dyn StorageEngine>,
+    /// 表目录
+    catalog: Arc<Catalog>,
+    /// 正在执行的查询
+    running_queries: Arc<QueryRegistry>,
+    /// 缓存的会话及其注册时的表定义
+    session: Mutex<Option<(Vec<TableDefinition>, SessionContext)>>,
+}
+
+impl DataFusionExecutor {
+    /// 创建新的DataFusion执行器
+    pub fn new(storage_engine: Arc<dyn StorageEngine>, catalog: Arc<Catalog>) -> Self {
+        Self {
+            storage_engine,
+            catalog,
+            running_queries: Arc::new(QueryRegistry::new()),
+            session: Mutex::new(None),
+        }
+    }
+
+    /// 注册了目录中所有表的会话；表定义（含Parquet数据段）变化后重建
+    fn session_context(&self) -> Result<SessionContext> {
+        let tables = self.catalog.list_tables();
+        let mut session = self.session.lock();
+        if let Some((registered, ctx)) = session.as_ref() {
+            if *registered == tables {
+                return Ok(ctx.clone());
+            }
+        }
+
+        let ctx = SessionContext::new();
+        for table in &tables {
+            let provider = StorageTableProvider::new(table.clone(), self.storage_engine.clone(), self.catalog.clone());
+            ctx.register_table(table.name.as_str(), Arc::new(provider))
+                .map_err(|e| Error::query(format!("Failed to register table {}: {}", table.name, e)))?;
+        }
+        *session = Some((tables, ctx.clone()));
+        Ok(ctx)
+    }
+
+    /// 执行SQL，`max_rows`作为最外层LIMIT交给DataFusion下推
+    async fn run(&self, sql: &str, parameters: &HashMap<String, Value>, max_rows: Option<usize>) -> Result<Vec<RecordBatch>> {
+        let ctx = self.session_context()?;
+        let mut df = ctx.sql(sql).await.map_err(|e| Error::query(e.to_string()))?;
+        if !parameters.is_empty() {
+            df = df.with_param_values(param_values(parameters)?)
+                .map_err(|e| Error::query(e.to_string()))?;
+        }
+        if let Some(max_rows) = max_rows {
+            df = df.limit(0, Some(max_rows)).map_err(|e| Error::query(e.to_string()))?;
+        }
+        df.collect().await.map_err(|e| Error::query(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl QueryExecutor for DataFusionExecutor {
+    async fn execute(&self, plan: OptimizedPlan, context: ExecutionContext) -> Result<ExecutionResult> {
+        let start_time = Instant::now();
+        context.cancellation.start_deadline(context.timeout);
+        let _running = self.running_queries.register(RunningQuery::new(plan.original_query.sql.clone(), &context))?;
+
+        // 取消或超时时丢弃DataFusion的执行future，其算子流随之停止
+        let batches = context.cancellation.run(self.run(&plan.original_query.sql, &context.parameters, context.max_rows)).await?;
+
+        let mut rows: Vec<_> = batches.iter().flat_map(record_batch_to_rows).collect();
+        if let Some(max_rows) = context.max_rows {
+            rows.truncate(max_rows);
+        }
+
+        let stats = ExecutionStats {
+            rows_scanned: batches.iter().map(|b| b.num_rows() as u64).sum(),
+            ..Default::default()
+        };
+        let mut result = ExecutionResult::success(rows, start_time.elapsed().as_micros() as u64);
+        result.stats = stats;
+        Ok(result)
+    }
+
+    async fn cancel(&self, query_id: &str) -> Result<()> {
+        self.running_queries.cancel(query_id, format!("query {} cancelled", query_id));
+        Ok(())
+    }
+
+    async fn get_stats(&self) -> Result<HashMap<String, u64>> {
+        let mut stats = HashMap::new();
+        stats.insert("running_queries".to_string(), self.running_queries.len() as u64);
+        stats.insert("registered_tables".to_string(), self.catalog.list_tables().len() as u64);
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ColumnDefinition;
+    use crate::parser::{ParsedQuery, QueryType};
+    use fdc_storage::engines::memory::MemoryEngine;
+
+    #[tokio::test]
+    async fn test_datafusion_aggregate() {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let catalog = Arc::new(Catalog::new());
+        catalog.register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("qty", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+
+        let rows: Vec<_> = (0..6).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(i));
+            row.insert("symbol".to_string(), Value::String(if i % 2 == 0 { "AAPL" } else { "MSFT" }.to_string()));
+            row.insert("qty".to_string(), Value::Int64(10));
+            row
+        }).collect();
+        catalog.insert_rows(storage.as_ref(), "trades", &rows).await.unwrap();
+
+        let executor = DataFusionExecutor::new(storage, catalog);
+        let sql = "SELECT symbol, SUM(qty) AS total FROM trades GROUP BY symbol ORDER BY symbol";
+        let plan = OptimizedPlan::new(ParsedQuery::new(QueryType::Select, sql.to_string()));
+        let result = executor.execute(plan, ExecutionContext::new("q".to_string())).await.unwrap();
+
+        assert_eq!(result.row_count(), 2);
+        assert_eq!(result.rows[0].get("total"), Some(&Value::Int64(30)));
+    }
+
+    #[tokio::test]
+    async fn test_datafusion_filter_pushdown() {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let catalog = Arc::new(Catalog::new());
+        catalog.register_table(TableDefinition::new("ticks", vec![
+            ColumnDefinition::new("id", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("ts", ColumnType::Timestamp),
+        ]).with_primary_key("id")).unwrap();
+
+        let rows: Vec<_> = (0..100).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(i));
+            row.insert("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(i * 1_000_000_000)));
+            row
+        }).collect();
+        catalog.insert_rows(storage.as_ref(), "ticks", &rows).await.unwrap();
+
+        let executor = DataFusionExecutor::new(storage, catalog.clone());
+        let provider = StorageTableProvider::new(catalog.get_table("ticks").unwrap(), executor.storage_engine.clone(), catalog);
+        let filter = datafusion::prelude::col("id").gt_eq(datafusion::prelude::lit(90i64));
+        assert_eq!(provider.supports_filters_pushdown(&[&filter]).unwrap(), vec![TableProviderFilterPushDown::Inexact]);
+        let ranges = provider.column_ranges(&[filter]);
+        let batches = provider.load_batches(&ranges, None).await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+        let batches = provider.load_batches(&HashMap::new(), Some(5)).await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+
+        let sql = "SELECT id FROM ticks WHERE id BETWEEN 10 AND 12 ORDER BY id";
+        let plan = OptimizedPlan::new(ParsedQuery::new(QueryType::Select, sql.to_string()));
+        let result = executor.execute(plan, ExecutionContext::new("q".to_string())).await.unwrap();
+        assert_eq!(result.row_count(), 3);
+    }
+}
@@ -0,0 +1,877 @@
+//! Scatter-gather execution of SELECT queries across shards
+//!
+//! 表按分片键分布在`ShardManager`管理的多个分片上。查询被拆成下推到每个分片并行执行的片段
+//! （过滤、列裁剪、部分聚合、Top-N），汇集后在协调节点上合并：部分聚合做最终聚合，
+//! 有序的片段结果多路归并，连接的各关系按连接键重分区后并行连接。
+
+use crate::{
+    cancellation::{QueryRegistry, RunningQuery},
+    catalog::{Catalog, TableDefinition, TableWrite},
+    cost::referenced_qualifiers,
+    executor::{
+        parse_statement, relation_qualifier, select_item_expr, split_conjunction, DefaultQueryExecutor,
+        ExecutionContext, ExecutionResult, QueryExecutor, SelectInput,
+    },
+    grouping::{collect_aggregates_with, merge_partial_groups, partial_group_rows, AggregateCall, PartialGroup},
+    joins::{normalize_key_value, JoinOperations, Row},
+    optimizer::OptimizedPlan,
+    parser::{ParsedQuery, QueryFeatures, QueryType},
+    planner::{ExecutionPlan, JoinType, PlanNode},
+    sampling::extract_sample_by,
+    sorts::{merge_sorted, resolve_order_by, SortOrder},
+    udf::FunctionRegistry,
+    windows::collect_window_calls_with,
+};
+use async_trait::async_trait;
+use fdc_core::error::{Error, Result};
+use fdc_storage::{engine::StorageEngine, ShardManager};
+use parking_lot::RwLock;
+use sqlparser::ast::{
+    visit_expressions, BinaryOperator, Expr, GroupByExpr, JoinConstraint, JoinOperator, OrderByExpr, Query,
+    Select, SetExpr, Statement, TableFactor, TableWithJoins,
+};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task::JoinSet;
+
+/// 分片执行器：在一个分片的数据上执行下推的查询片段
+///
+/// 进程内的`LocalShard`直接调用本地原生执行器；远程分片可以实现同一接口，
+/// 把SQL片段与聚合调用发送到其他节点执行并返回行或部分聚合状态。
+#[async_trait]
+pub trait ShardExecutor: Send + Sync {
+    /// 分片ID（与`ShardManager`计算出的分片号一致）
+    fn shard_id(&self) -> u32;
+
+    /// 在分片上创建表
+    async fn create_table(&self, definition: &TableDefinition) -> Result<()>;
+
+    /// 写入路由到本分片的行
+    async fn insert_rows(&self, table: &str, rows: &[Row]) -> Result<u64>;
+
+    /// 执行下推的SELECT片段，返回结果行
+    async fn scan(&self, sql: &str, context: &ExecutionContext) -> Result<Vec<Row>>;
+
+    /// 执行下推的SELECT片段并在结果上计算部分聚合
+    async fn partial_aggregate(
+        &self,
+        sql: &str,
+        group_by: &[Expr],
+        aggregates: &[AggregateCall],
+        context: &ExecutionContext,
+    ) -> Result<Vec<PartialGroup>>;
+}
+
+/// 进程内分片：独立的存储引擎、表目录与原生执行器
+pub struct LocalShard {
+    /// 分片ID
+    id: u32,
+    /// 存储引擎
+    storage: Arc<dyn StorageEngine>,
+    /// 表目录
+    catalog: Arc<Catalog>,
+    /// 原生执行器
+    executor: DefaultQueryExecutor,
+}
+
+impl LocalShard {
+    /// 在给定的存储引擎上创建分片
+    pub fn new(id: u32, storage: Arc<dyn StorageEngine>) -> Self {
+        let catalog = Arc::new(Catalog::new());
+        let executor = DefaultQueryExecutor::with_catalog(storage.clone(), catalog.clone());
+        Self { id, storage, catalog, executor }
+    }
+
+    /// 设置下推片段中可调用的用户自定义函数（通常与协调节点共享）
+    pub fn with_functions(mut self, functions: Arc<FunctionRegistry>) -> Self {
+        self.executor = DefaultQueryExecutor::with_catalog(self.storage.clone(), self.catalog.clone())
+            .with_functions(functions);
+        self
+    }
+
+    /// 获取分片的表目录
+    pub fn catalog(&self) -> &Arc<Catalog> {
+        &self.catalog
+    }
+}
+
+#[async_trait]
+impl ShardExecutor for LocalShard {
+    fn shard_id(&self) -> u32 {
+        self.id
+    }
+
+    async fn create_table(&self, definition: &TableDefinition) -> Result<()> {
+        self.catalog.register_table(definition.clone())
+    }
+
+    async fn insert_rows(&self, table: &str, rows: &[Row]) -> Result<u64> {
+        self.catalog.insert_rows(self.storage.as_ref(), table, rows).await
+    }
+
+    async fn scan(&self, sql: &str, context: &ExecutionContext) -> Result<Vec<Row>> {
+        self.executor.select_rows(sql, context).await
+    }
+
+    async fn partial_aggregate(
+        &self,
+        sql: &str,
+        group_by: &[Expr],
+        aggregates: &[AggregateCall],
+        context: &ExecutionContext,
+    ) -> Result<Vec<PartialGroup>> {
+        let rows = self.executor.select_rows(sql, context).await?;
+        partial_group_rows(rows, group_by, aggregates, &self.executor.evaluator(context))
+    }
+}
+
+/// 分片表：表定义与分片键列
+#[derive(Debug, Clone)]
+struct ShardedTable {
+    definition: TableDefinition,
+    shard_key: String,
+}
+
+/// 下推到各分片执行的查询片段
+#[derive(Debug, Clone)]
+struct Fragment {
+    /// 关系的列限定名（连接时以此限定汇集的行）
+    qualifier: String,
+    /// 分片上执行的SELECT
+    sql: String,
+}
+
+/// 汇集各分片结果的方式
+#[derive(Debug, Clone)]
+enum Gather {
+    /// 拼接各分片的行
+    Union,
+    /// 各分片结果已按排序键有序，多路归并并最多保留`limit`行
+    MergeSort { keys: Vec<Expr>, orders: Vec<SortOrder>, limit: Option<usize> },
+    /// 各分片返回部分聚合状态，汇集后合并为最终结果
+    FinalAggregate { group_by: Vec<Expr>, aggregates: Vec<AggregateCall> },
+    /// 各关系分别汇集，按连接键重分区后逐个并行连接
+    ShuffleJoin { joins: Vec<JoinOperator> },
+}
+
+/// 分发-汇集执行计划
+#[derive(Debug, Clone)]
+struct ScatterPlan {
+    /// 每个关系一个片段
+    fragments: Vec<Fragment>,
+    /// 汇集方式
+    gather: Gather,
+}
+
+/// 跨分片的分发-汇集执行器
+///
+/// 协调节点上的原生执行器在汇集的输入上完成片段之后的步骤（HAVING、窗口、排序、LIMIT、投影）。
+pub struct DistributedExecutor {
+    /// 分片管理器（决定行所属的分片）
+    shard_manager: Arc<ShardManager>,
+    /// 按分片ID排列的分片
+    shards: Vec<Arc<dyn ShardExecutor>>,
+    /// 协调节点的原生执行器
+    coordinator: Arc<DefaultQueryExecutor>,
+    /// 分片表（表名小写）
+    tables: RwLock<HashMap<String, ShardedTable>>,
+    /// 正在执行的查询
+    running_queries: Arc<QueryRegistry>,
+}
+
+impl DistributedExecutor {
+    /// 创建分布式执行器，分片ID必须恰好覆盖`0..分片数`
+    pub fn new(
+        shard_manager: Arc<ShardManager>,
+        mut shards: Vec<Arc<dyn ShardExecutor>>,
+        coordinator: Arc<DefaultQueryExecutor>,
+    ) -> Result<Self> {
+        shards.sort_by_key(|shard| shard.shard_id());
+        let count = shard_manager.get_shard_count() as usize;
+        if shards.len() != count || shards.iter().enumerate().any(|(i, shard)| shard.shard_id() as usize != i) {
+            let ids: Vec<u32> = shards.iter().map(|shard| shard.shard_id()).collect();
+            return Err(Error::config(format!("Expected shards 0..{} for the shard manager, got {:?}", count, ids)));
+        }
+        Ok(Self {
+            shard_manager,
+            shards,
+            coordinator,
+            tables: RwLock::new(HashMap::new()),
+            running_queries: Arc::new(QueryRegistry::new()),
+        })
+    }
+
+    /// 分片数
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// 在所有分片上创建表，行按`shard_key`列的值分布
+    pub async fn create_table(&self, definition: TableDefinition, shard_key: &str) -> Result<()> {
+        let name = definition.name.to_lowercase();
+        if self.tables.read().contains_key(&name) {
+            return Err(Error::already_exists(format!("sharded table {}", definition.name)));
+        }
+        let shard_key = definition.columns.iter()
+            .find(|column| column.name.eq_ignore_ascii_case(shard_key))
+            .map(|column| column.name.clone())
+            .ok_or_else(|| Error::validation(format!("Shard key {} is not a column of {}", shard_key, definition.name)))?;
+        for shard in &self.shards {
+            shard.create_table(&definition).await?;
+        }
+        self.tables.write().insert(name, ShardedTable { definition, shard_key });
+        Ok(())
+    }
+
+    /// 是否为分片表
+    pub fn is_sharded(&self, table: &str) -> bool {
+        self.tables.read().contains_key(&table.to_lowercase())
+    }
+
+    /// 查询是否只读取分片表（引擎据此把查询路由到分布式执行）
+    pub fn covers(&self, query: &ParsedQuery) -> bool {
+        query.query_type == QueryType::Select
+            && !query.tables.is_empty()
+            && query.tables.iter().all(|table| self.is_sharded(table))
+    }
+
+    /// 按分片键把行路由到各分片写入，返回写入的行数
+    pub async fn insert_rows(&self, table: &str, rows: &[Row]) -> Result<u64> {
+        let shard_key = self.tables.read().get(&table.to_lowercase())
+            .map(|table| table.shard_key.clone())
+            .ok_or_else(|| Error::not_found(format!("sharded table {}", table)))?;
+        let mut routed: Vec<Vec<Row>> = vec![Vec::new(); self.shards.len()];
+        for row in rows {
+            let key = row.get(&shard_key)
+                .and_then(normalize_key_value)
+                .ok_or_else(|| Error::validation(format!("Shard key {} must not be NULL", shard_key)))?;
+            let shard = self.shard_manager.get_shard_key(key.as_bytes()).shard_id as usize;
+            routed[shard].push(row.clone());
+        }
+        let mut written = 0;
+        for (shard, rows) in self.shards.iter().zip(routed) {
+            if !rows.is_empty() {
+                written += shard.insert_rows(table, &rows).await?;
+            }
+        }
+        // 协调节点上缓存的查询结果随之失效
+        self.coordinator.catalog().notify_write(&TableWrite::new(table));
+        Ok(written)
+    }
+
+    /// 分发-汇集执行SELECT并返回结果行
+    pub async fn select_rows(&self, sql: &str, context: &ExecutionContext) -> Result<Vec<Row>> {
+        let plan = self.scatter_plan(sql)?;
+        let evaluator = self.coordinator.evaluator(context);
+        let mut fragments = plan.fragments.into_iter();
+        let input = match plan.gather {
+            Gather::Union => {
+                let fragment = fragments.next().ok_or_else(|| Error::internal("Missing query fragment"))?;
+                SelectInput::Rows(self.gather_rows(fragment.sql, context).await?.concat())
+            }
+            Gather::MergeSort { keys, orders, limit } => {
+                let fragment = fragments.next().ok_or_else(|| Error::internal("Missing query fragment"))?;
+                let runs = self.gather_rows(fragment.sql, context).await?;
+                SelectInput::Rows(merge_sorted(runs, &keys, &orders, &evaluator, limit)?)
+            }
+            Gather::FinalAggregate { group_by, aggregates } => {
+                let fragment = fragments.next().ok_or_else(|| Error::internal("Missing query fragment"))?;
+                let sql = Arc::new(fragment.sql);
+                let calls = Arc::new((group_by, aggregates));
+                let partials = self.scatter(|shard| {
+                    let (sql, calls, context) = (sql.clone(), calls.clone(), context.clone());
+                    async move { shard.partial_aggregate(&sql, &calls.0, &calls.1, &context).await }
+                }).await?;
+                SelectInput::Grouped(merge_partial_groups(partials, &calls.0, &calls.1)?)
+            }
+            Gather::ShuffleJoin { joins } => {
+                let gathered = futures::future::try_join_all(fragments.map(|fragment| async move {
+                    let rows = self.gather_rows(fragment.sql, context).await?.concat();
+                    Ok::<_, Error>(JoinOperations::qualify(rows, &fragment.qualifier))
+                })).await?;
+                let mut relations = gathered.into_iter();
+                let mut joined = relations.next().unwrap_or_default();
+                for (right, operator) in relations.zip(&joins) {
+                    joined = self.coordinator.shuffle_join(joined, right, operator, &evaluator, self.shards.len())?;
+                }
+                SelectInput::Rows(joined)
+            }
+        };
+        self.coordinator.select_from(sql, input, context).await
+    }
+
+    /// 以Gather/Exchange节点描述查询的分发-汇集计划
+    pub fn explain(&self, sql: &str) -> Result<ExecutionPlan> {
+        let plan = self.scatter_plan(sql)?;
+        let shards = self.shards.len();
+        let exchange = |fragment: &Fragment| ExecutionPlan::new(PlanNode::Exchange { shards, fragment: fragment.sql.clone() });
+        let first = plan.fragments.first().ok_or_else(|| Error::internal("Missing query fragment"))?;
+        let (merge, child) = match &plan.gather {
+            Gather::Union => ("union".to_string(), exchange(first)),
+            Gather::MergeSort { keys, orders, limit } => {
+                let keys: Vec<String> = keys.iter().zip(orders)
+                    .map(|(key, order)| if order.ascending { key.to_string() } else { format!("{} DESC", key) })
+                    .collect();
+                let mut merge = format!("merge sort by {}", keys.join(", "));
+                if let Some(limit) = limit {
+                    merge.push_str(&format!(" limit {}", limit));
+                }
+                (merge, exchange(first))
+            }
+            Gather::FinalAggregate { group_by, aggregates } => {
+                let calls: Vec<&str> = aggregates.iter().map(|call| call.key.as_str()).collect();
+                let mut child = exchange(first);
+                child.add_property("partial_aggregate".to_string(), calls.join(", "));
+                let mut merge = format!("final aggregate [{}]", calls.join(", "));
+                if !group_by.is_empty() {
+                    let keys: Vec<String> = group_by.iter().map(|e| e.to_string()).collect();
+                    merge.push_str(&format!(" group by {}", keys.join(", ")));
+                }
+                (merge, child)
+            }
+            Gather::ShuffleJoin { joins } => {
+                let shuffled = |input: ExecutionPlan, keys: Vec<String>| {
+                    if keys.is_empty() {
+                        return input;
+                    }
+                    let mut shuffle = ExecutionPlan::new(PlanNode::Shuffle { keys, partitions: shards });
+                    shuffle.add_child(input);
+                    shuffle
+                };
+                let mut joined = vec![first.qualifier.to_lowercase()];
+                let mut left = exchange(first);
+                for (fragment, operator) in plan.fragments.iter().skip(1).zip(joins) {
+                    let right_side = [fragment.qualifier.to_lowercase()];
+                    let (join_type, condition) = describe_join(operator);
+                    let mut join = ExecutionPlan::new(PlanNode::Join { join_type, condition });
+                    join.add_child(shuffled(left, shuffle_keys(operator, &joined)));
+                    join.add_child(shuffled(exchange(fragment), shuffle_keys(operator, &right_side)));
+                    joined.push(fragment.qualifier.to_lowercase());
+                    left = join;
+                }
+                ("shuffle join".to_string(), left)
+            }
+        };
+        let mut root = ExecutionPlan::new(PlanNode::Gather { merge });
+        root.add_child(child);
+        Ok(root)
+    }
+
+    /// 在所有分片上执行片段并按分片顺序返回各自的行
+    async fn gather_rows(&self, sql: String, context: &ExecutionContext) -> Result<Vec<Vec<Row>>> {
+        let sql = Arc::new(sql);
+        self.scatter(|shard| {
+            let (sql, context) = (sql.clone(), context.clone());
+            async move { shard.scan(&sql, &context).await }
+        }).await
+    }
+
+    /// 在每个分片上并行执行一个任务，按分片顺序返回结果
+    ///
+    /// 任一分片出错即返回；查询被取消或超时时本future被丢弃。两种情况下`JoinSet`释放时
+    /// 都会中止其余仍在运行的分片任务。
+    async fn scatter<T, F, Fut>(&self, call: F) -> Result<Vec<T>>
+    where
+        F: Fn(Arc<dyn ShardExecutor>) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut tasks = JoinSet::new();
+        for (index, shard) in self.shards.iter().enumerate() {
+            let task = call(shard.clone());
+            tasks.spawn(async move { (index, task.await) });
+        }
+        let mut results: Vec<Option<T>> = self.shards.iter().map(|_| None).collect();
+        while let Some(joined) = tasks.join_next().await {
+            let (index, result) = joined.map_err(|e| Error::internal(format!("Shard task failed: {}", e)))?;
+            results[index] = Some(result?);
+        }
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    /// 查找FROM中关系对应的分片表
+    fn sharded_table(&self, relation: &TableFactor) -> Result<ShardedTable> {
+        let name = match relation {
+            TableFactor::Table { name, .. } => name.to_string(),
+            other => return Err(Error::unimplemented(format!("Relation not supported by distributed execution: {}", other))),
+        };
+        self.tables.read().get(&name.to_lowercase())
+            .cloned()
+            .ok_or_else(|| Error::not_found(format!("sharded table {}", name)))
+    }
+
+    /// 把查询拆成分片片段与汇集方式
+    fn scatter_plan(&self, sql: &str) -> Result<ScatterPlan> {
+        let (stripped, sample_by) = extract_sample_by(sql)?;
+        let statement = parse_statement(&stripped)?;
+        let features = QueryFeatures::from_statement(&statement);
+        if features.has_cte || features.has_subquery || features.has_set_operation {
+            return Err(Error::unimplemented(
+                "Subqueries, CTEs and set operations are not supported by distributed execution",
+            ));
+        }
+        let query = match &statement {
+            Statement::Query(query) => query,
+            _ => return Err(Error::validation("Expected a SELECT statement")),
+        };
+        let select = match query.body.as_ref() {
+            SetExpr::Select(select) => select,
+            other => return Err(Error::unimplemented(format!("Query body not supported by distributed execution: {}", other))),
+        };
+        let from = match select.from.as_slice() {
+            [from] => from,
+            _ => return Err(Error::unimplemented("Distributed execution requires exactly one FROM item")),
+        };
+        if !from.joins.is_empty() {
+            return self.join_plan(from, select);
+        }
+        let table = self.sharded_table(&from.relation)?;
+
+        let functions = Some(self.coordinator.functions().as_ref());
+        let order_exprs: Vec<&Expr> = query.order_by.iter().flat_map(|o| o.exprs.iter().map(|e| &e.expr)).collect();
+        let projection_exprs = select.projection.iter().filter_map(select_item_expr);
+        let aggregates = collect_aggregates_with(
+            projection_exprs.clone().chain(select.having.as_ref()).chain(order_exprs.iter().copied()),
+            functions,
+        )?;
+        let windows = collect_window_calls_with(
+            projection_exprs.chain(order_exprs.iter().copied()),
+            &select.named_window,
+            functions,
+        )?;
+        let group_by = match &select.group_by {
+            GroupByExpr::Expressions(exprs, _) => exprs.clone(),
+            GroupByExpr::All(_) => return Err(Error::unimplemented("GROUP BY ALL")),
+        };
+
+        // 列裁剪：只取查询引用到的列；通配符与SAMPLE BY需要整行
+        let whole_rows = sample_by.is_some() || select.projection.iter().any(|item| select_item_expr(item).is_none());
+        let columns = if whole_rows {
+            "*".to_string()
+        } else {
+            referenced_columns(select, query, &table).join(", ")
+        };
+        let mut fragment = format!("SELECT {} FROM {}", columns, from.relation);
+        if let Some(selection) = &select.selection {
+            fragment.push_str(&format!(" WHERE {}", selection));
+        }
+
+        // 窗口、DISTINCT与SAMPLE BY需要看到全部行，分片只做过滤
+        let ship_rows = sample_by.is_some()
+            || !windows.is_empty()
+            || select.distinct.is_some()
+            || aggregates.iter().any(|call| call.distinct);
+        let gather = if ship_rows {
+            Gather::Union
+        } else if features.has_aggregate || !aggregates.is_empty() {
+            Gather::FinalAggregate { group_by, aggregates }
+        } else {
+            let limit = pushdown_limit(query);
+            let gather = match &query.order_by {
+                Some(order_by) => {
+                    let keys = resolve_order_by(&order_by.exprs, &select.projection);
+                    let items: Vec<String> = order_by.exprs.iter().zip(&keys)
+                        .map(|(item, key)| OrderByExpr { expr: key.clone(), ..item.clone() }.to_string())
+                        .collect();
+                    fragment.push_str(&format!(" ORDER BY {}", items.join(", ")));
+                    let orders = order_by.exprs.iter().map(SortOrder::from_order_by).collect();
+                    Gather::MergeSort { keys, orders, limit }
+                }
+                None => Gather::Union,
+            };
+            if let Some(limit) = limit {
+                fragment.push_str(&format!(" LIMIT {}", limit));
+            }
+            gather
+        };
+
+        Ok(ScatterPlan {
+            fragments: vec![Fragment { qualifier: relation_qualifier(&from.relation)?, sql: fragment }],
+            gather,
+        })
+    }
+
+    /// 连接查询：每个关系单独下推单表谓词，汇集后按连接键重分区连接
+    fn join_plan(&self, from: &TableWithJoins, select: &Select) -> Result<ScatterPlan> {
+        let relations: Vec<&TableFactor> = std::iter::once(&from.relation)
+            .chain(from.joins.iter().map(|join| &join.relation))
+            .collect();
+        for relation in &relations {
+            self.sharded_table(relation)?;
+        }
+
+        // 外连接的WHERE作用于补NULL之后的行，只有全部为内连接时才能把单表谓词下推到分片
+        let inner_only = from.joins.iter().all(|join| matches!(join.join_operator, JoinOperator::Inner(_)));
+        let conjuncts = match &select.selection {
+            Some(selection) if inner_only => split_conjunction(selection),
+            _ => Vec::new(),
+        };
+        let fragments = relations.iter()
+            .map(|relation| {
+                let qualifier = relation_qualifier(relation)?;
+                let local: Vec<String> = conjuncts.iter()
+                    .filter(|conjunct| referenced_qualifiers(conjunct)
+                        .is_some_and(|referenced| referenced.len() == 1 && referenced.contains(&qualifier.to_lowercase())))
+                    .map(|conjunct| format!("({})", conjunct))
+                    .collect();
+                let mut sql = format!("SELECT * FROM {}", relation);
+                if !local.is_empty() {
+                    sql.push_str(&format!(" WHERE {}", local.join(" AND ")));
+                }
+                Ok(Fragment { qualifier, sql })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ScatterPlan {
+            fragments,
+            gather: Gather::ShuffleJoin { joins: from.joins.iter().map(|join| join.join_operator.clone()).collect() },
+        })
+    }
+}
+
+#[async_trait]
+impl QueryExecutor for DistributedExecutor {
+    async fn execute(&self, plan: OptimizedPlan, context: ExecutionContext) -> Result<ExecutionResult> {
+        if plan.original_query.query_type != QueryType::Select {
+            return Err(Error::unimplemented("Distributed execution only supports SELECT"));
+        }
+        let start_time = Instant::now();
+        context.cancellation.start_deadline(context.timeout);
+        let _running = self.running_queries.register(RunningQuery::new(plan.original_query.sql.clone(), &context))?;
+
+        let mut rows = context.cancellation.run(self.select_rows(&plan.original_query.sql, &context)).await?;
+        if let Some(max_rows) = context.max_rows {
+            rows.truncate(max_rows);
+        }
+        Ok(ExecutionResult::success(rows, start_time.elapsed().as_micros() as u64))
+    }
+
+    async fn cancel(&self, query_id: &str) -> Result<()> {
+        self.running_queries.cancel(query_id, format!("query {} cancelled", query_id));
+        Ok(())
+    }
+
+    async fn get_stats(&self) -> Result<HashMap<String, u64>> {
+        let mut stats = HashMap::new();
+        stats.insert("running_queries".to_string(), self.running_queries.len() as u64);
+        stats.insert("shards".to_string(), self.shards.len() as u64);
+        Ok(stats)
+    }
+}
+
+/// 查询引用到的表列（按表定义顺序）；一个都没有时取分片键列
+fn referenced_columns(select: &Select, query: &Query, table: &ShardedTable) -> Vec<String> {
+    let mut referenced = HashSet::new();
+    let mut collect = |e: &Expr| {
+        match e {
+            Expr::Identifier(ident) => {
+                referenced.insert(ident.value.to_lowercase());
+            }
+            Expr::CompoundIdentifier(idents) => {
+                if let Some(ident) = idents.last() {
+                    referenced.insert(ident.value.to_lowercase());
+                }
+            }
+            _ => {}
+        }
+        ControlFlow::<()>::Continue(())
+    };
+    let _ = visit_expressions(select, &mut collect);
+    let _ = visit_expressions(&query.order_by, &mut collect);
+    let columns: Vec<String> = table.definition.columns.iter()
+        .filter(|column| referenced.contains(&column.name.to_lowercase()))
+        .map(|column| column.name.clone())
+        .collect();
+    if columns.is_empty() {
+        vec![table.shard_key.clone()]
+    } else {
+        columns
+    }
+}
+
+/// 可以下推到分片的行数上限（OFFSET + LIMIT，均为常量时）
+fn pushdown_limit(query: &Query) -> Option<usize> {
+    let constant = |expr: &Expr| match expr {
+        Expr::Value(sqlparser::ast::Value::Number(n, _)) => n.parse::<usize>().ok(),
+        _ => None,
+    };
+    let limit = constant(query.limit.as_ref()?)?;
+    let offset = match &query.offset {
+        Some(offset) => constant(&offset.value)?,
+        None => 0,
+    };
+    Some(offset + limit)
+}
+
+/// 计划中连接节点的类型与条件文本
+fn describe_join(operator: &JoinOperator) -> (JoinType, String) {
+    let condition = |constraint: &JoinConstraint| match constraint {
+        JoinConstraint::On(on) => on.to_string(),
+        JoinConstraint::Using(idents) => {
+            let names: Vec<&str> = idents.iter().map(|i| i.value.as_str()).collect();
+            format!("USING ({})", names.join(", "))
+        }
+        JoinConstraint::Natural => "NATURAL".to_string(),
+        JoinConstraint::None => String::new(),
+    };
+    match operator {
+        JoinOperator::Inner(c) => (JoinType::Inner, condition(c)),
+        JoinOperator::LeftOuter(c) => (JoinType::Left, condition(c)),
+        JoinOperator::RightOuter(c) => (JoinType::Right, condition(c)),
+        JoinOperator::FullOuter(c) => (JoinType::Full, condition(c)),
+        JoinOperator::LeftSemi(c) => (JoinType::LeftSemi, condition(c)),
+        JoinOperator::LeftAnti(c) => (JoinType::LeftAnti, condition(c)),
+        JoinOperator::AsOf { match_condition, .. } => (JoinType::Left, format!("ASOF {}", match_condition)),
+        _ => (JoinType::Cross, String::new()),
+    }
+}
+
+/// 连接条件中属于一侧（由列限定名集合给出）的等值键，用于描述重分区
+fn shuffle_keys(operator: &JoinOperator, side: &[String]) -> Vec<String> {
+    let on = match operator {
+        JoinOperator::Inner(JoinConstraint::On(on)) | JoinOperator::LeftOuter(JoinConstraint::On(on))
+        | JoinOperator::RightOuter(JoinConstraint::On(on)) | JoinOperator::FullOuter(JoinConstraint::On(on))
+        | JoinOperator::LeftSemi(JoinConstraint::On(on)) | JoinOperator::LeftAnti(JoinConstraint::On(on)) => on,
+        _ => return Vec::new(),
+    };
+    let on_side = |expr: &Expr| match expr {
+        Expr::CompoundIdentifier(idents) if idents.len() >= 2 => {
+            side.contains(&idents[idents.len() - 2].value.to_lowercase())
+        }
+        _ => false,
+    };
+    split_conjunction(on).into_iter()
+        .filter_map(|conjunct| match conjunct {
+            Expr::BinaryOp { left, op: BinaryOperator::Eq, .. } if on_side(left) => Some(left.to_string()),
+            Expr::BinaryOp { op: BinaryOperator::Eq, right, .. } if on_side(right) => Some(right.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{ColumnDefinition, ColumnType};
+    use fdc_core::types::Value;
+    use fdc_storage::{engines::memory::MemoryEngine, ShardStrategy};
+
+    async fn memory() -> Arc<dyn StorageEngine> {
+        Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap())
+    }
+
+    fn trades() -> TableDefinition {
+        TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("price", ColumnType::Float64),
+            ColumnDefinition::new("size", ColumnType::Int64),
+        ]).with_primary_key("id")
+    }
+
+    fn quotes() -> TableDefinition {
+        TableDefinition::new("quotes", vec![
+            ColumnDefinition::new("trade_id", ColumnType::Int64),
+            ColumnDefinition::new("bid", ColumnType::Float64),
+        ]).with_primary_key("trade_id")
+    }
+
+    fn data() -> (Vec<Row>, Vec<Row>) {
+        let symbols = ["AAPL", "MSFT", "TSLA"];
+        let trades = (1..=40i64).map(|i| HashMap::from([
+            ("id".to_string(), Value::Int64(i)),
+            ("symbol".to_string(), Value::String(symbols[i as usize % 3].to_string())),
+            ("price".to_string(), Value::Float64(100.0 + (i % 7) as f64 * 0.5)),
+            ("size".to_string(), Value::Int64(i * 13 % 50 + 1)),
+        ])).collect();
+        let quotes = (1..=40i64).step_by(2).map(|i| HashMap::from([
+            ("trade_id".to_string(), Value::Int64(i)),
+            ("bid".to_string(), Value::Float64(99.75 + (i % 7) as f64 * 0.5)),
+        ])).collect();
+        (trades, quotes)
+    }
+
+    /// 三个本地分片上的分布式执行器，以及装有全部数据的单节点执行器作为对照
+    async fn cluster() -> (DistributedExecutor, Vec<Arc<LocalShard>>, DefaultQueryExecutor) {
+        let mut shards = Vec::new();
+        for id in 0..3 {
+            shards.push(Arc::new(LocalShard::new(id, memory().await)));
+        }
+        let distributed = DistributedExecutor::new(
+            Arc::new(ShardManager::new(3, ShardStrategy::Hash)),
+            shards.iter().map(|shard| shard.clone() as Arc<dyn ShardExecutor>).collect(),
+            Arc::new(DefaultQueryExecutor::new(memory().await)),
+        ).unwrap();
+        distributed.create_table(trades(), "id").await.unwrap();
+        distributed.create_table(quotes(), "trade_id").await.unwrap();
+
+        let storage = memory().await;
+        let catalog = Arc::new(Catalog::new());
+        catalog.register_table(trades()).unwrap();
+        catalog.register_table(quotes()).unwrap();
+
+        let (trade_rows, quote_rows) = data();
+        assert_eq!(distributed.insert_rows("trades", &trade_rows).await.unwrap(), 40);
+        distributed.insert_rows("quotes", &quote_rows).await.unwrap();
+        catalog.insert_rows(storage.as_ref(), "trades", &trade_rows).await.unwrap();
+        catalog.insert_rows(storage.as_ref(), "quotes", &quote_rows).await.unwrap();
+        (distributed, shards, DefaultQueryExecutor::with_catalog(storage, catalog))
+    }
+
+    fn assert_rows_match(sql: &str, actual: &[Row], expected: &[Row]) {
+        assert_eq!(actual.len(), expected.len(), "{}", sql);
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert_eq!(actual.len(), expected.len(), "{}", sql);
+            for (column, value) in expected {
+                match (&actual[column], value) {
+                    (Value::Float64(a), Value::Float64(b)) => assert!((a - b).abs() < 1e-9, "{}: {} {} vs {}", sql, column, a, b),
+                    (a, b) => assert_eq!(a, b, "{}: {}", sql, column),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scatter_gather_matches_single_node() {
+        let (distributed, shards, reference) = cluster().await;
+        let context = ExecutionContext::new("q".to_string());
+
+        // 数据确实分布在所有分片上
+        let mut total = 0;
+        for shard in &shards {
+            let rows = shard.scan("SELECT id FROM trades", &context).await.unwrap();
+            assert!(!rows.is_empty());
+            total += rows.len();
+        }
+        assert_eq!(total, 40);
+
+        for sql in [
+            "SELECT symbol, COUNT(*) AS n, SUM(size) AS volume, AVG(price) AS avg_price, MAX(price) AS high, \
+             stddev_samp(price) AS sd FROM trades WHERE size > 5 GROUP BY symbol HAVING COUNT(*) > 1 ORDER BY symbol",
+            "SELECT COUNT(*) AS n, MIN(price) AS low FROM trades WHERE symbol = 'NONE'",
+            "SELECT symbol, COUNT(DISTINCT price) AS prices FROM trades GROUP BY symbol ORDER BY symbol",
+            "SELECT id, price FROM trades ORDER BY price DESC, id LIMIT 5 OFFSET 2",
+            "SELECT t.id, t.symbol, q.bid FROM trades t JOIN quotes q ON t.id = q.trade_id WHERE t.size > 10 ORDER BY t.id",
+            "SELECT t.id, q.bid FROM trades t LEFT JOIN quotes q ON t.id = q.trade_id ORDER BY t.id",
+            "SELECT t.symbol, SUM(q.bid) AS bids FROM trades t JOIN quotes q ON t.id = q.trade_id GROUP BY t.symbol ORDER BY t.symbol",
+        ] {
+            let actual = distributed.select_rows(sql, &context).await.unwrap();
+            let expected = reference.select_rows(sql, &context).await.unwrap();
+            assert!(!expected.is_empty(), "{}", sql);
+            assert_rows_match(sql, &actual, &expected);
+        }
+
+        // 没有ORDER BY的LIMIT下推到各分片，汇集后再截断
+        let rows = distributed.select_rows("SELECT id FROM trades WHERE price > 101 LIMIT 3", &context).await.unwrap();
+        assert_eq!(rows.len(), 3);
+    }
+
+    /// 分片0立即失败，分片1长时间运行；记录分片1的任务是否跑完或被中止
+    struct FailingShard {
+        id: u32,
+        completed: Arc<std::sync::atomic::AtomicBool>,
+        dropped: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    struct DropFlag(Arc<std::sync::atomic::AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl ShardExecutor for FailingShard {
+        fn shard_id(&self) -> u32 {
+            self.id
+        }
+
+        async fn create_table(&self, _definition: &TableDefinition) -> Result<()> {
+            Ok(())
+        }
+
+        async fn insert_rows(&self, _table: &str, rows: &[Row]) -> Result<u64> {
+            Ok(rows.len() as u64)
+        }
+
+        async fn scan(&self, _sql: &str, _context: &ExecutionContext) -> Result<Vec<Row>> {
+            if self.id == 0 {
+                // 等分片1开始运行后再失败
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                return Err(Error::query("shard 0 failed"));
+            }
+            let _guard = DropFlag(self.dropped.clone());
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            self.completed.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(Vec::new())
+        }
+
+        async fn partial_aggregate(
+            &self,
+            _sql: &str,
+            _group_by: &[Expr],
+            _aggregates: &[AggregateCall],
+            _context: &ExecutionContext,
+        ) -> Result<Vec<PartialGroup>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shard_error_aborts_other_shards() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let completed = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicBool::new(false));
+        let shards: Vec<Arc<dyn ShardExecutor>> = (0..2)
+            .map(|id| Arc::new(FailingShard { id, completed: completed.clone(), dropped: dropped.clone() }) as Arc<dyn ShardExecutor>)
+            .collect();
+        let distributed = DistributedExecutor::new(
+            Arc::new(ShardManager::new(2, ShardStrategy::Hash)),
+            shards,
+            Arc::new(DefaultQueryExecutor::new(memory().await)),
+        ).unwrap();
+        distributed.create_table(trades(), "id").await.unwrap();
+
+        let context = ExecutionContext::new("q".to_string());
+        assert!(distributed.select_rows("SELECT id FROM trades", &context).await.is_err());
+        // 中止在运行时下一次调度分片任务时生效
+        for _ in 0..100 {
+            if dropped.load(Ordering::SeqCst) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(!completed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_distributed_plan() {
+        let (distributed, _, _) = cluster().await;
+
+        let plan = distributed.explain("SELECT symbol, AVG(price) FROM trades WHERE size > 5 GROUP BY symbol").unwrap();
+        assert!(matches!(plan.root, PlanNode::Gather { .. }));
+        let text = plan.render();
+        assert!(text.contains("Gather: final aggregate [AVG(price)] group by symbol"), "{}", text);
+        assert!(text.contains("Exchange to 3 shards: SELECT symbol, price, size FROM trades WHERE size > 5"), "{}", text);
+
+        let text = distributed.explain("SELECT id FROM trades ORDER BY price DESC LIMIT 3").unwrap().render();
+        assert!(text.contains("merge sort by price DESC limit 3"), "{}", text);
+        assert!(text.contains("ORDER BY price DESC LIMIT 3"), "{}", text);
+
+        let sql = "SELECT t.id, q.bid FROM trades t JOIN quotes q ON t.id = q.trade_id WHERE q.bid > 100";
+        let text = distributed.explain(sql).unwrap().render();
+        assert!(text.contains("Shuffle into 3 partitions by t.id"), "{}", text);
+        assert!(text.contains("SELECT * FROM quotes AS q WHERE (q.bid > 100)"), "{}", text);
+
+        assert!(distributed.explain("SELECT * FROM unknown").is_err());
+        let shards: Vec<Arc<dyn ShardExecutor>> = vec![Arc::new(LocalShard::new(1, memory().await))];
+        let coordinator = Arc::new(DefaultQueryExecutor::new(memory().await));
+        assert!(DistributedExecutor::new(Arc::new(ShardManager::new(1, ShardStrategy::Hash)), shards, coordinator).is_err());
+    }
+}
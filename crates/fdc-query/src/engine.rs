@@ -1,20 +1,50 @@
 //! Main query engine implementation
 
 use crate::{
-    parser::{SqlParser, ParsedQuery},
+    admission::{AdmissionConfig, AdmissionController},
+    cancellation::{QueryCommand, QueryRegistry, RunningQuery},
+    catalog::{Catalog, InsertObserver, TableWrite},
+    distributed::{DistributedExecutor, ShardExecutor},
+    parser::{split_statements, SqlParser, ParsedQuery},
     optimizer::{QueryOptimizer, OptimizedPlan},
-    executor::{QueryExecutor, DefaultQueryExecutor, ExecutionContext, ExecutionResult},
+    partitions::{PartitionCommand, PartitionsTable, PARTITIONS_TABLE},
+    executor::{parse_statement, QueryExecutor, DefaultQueryExecutor, ExecutionContext, ExecutionResult},
+    expressions::ExpressionEvaluator,
+    prepared::{describe_columns, infer_parameters, PreparedCommand, PreparedStatement, PreparedStatementRegistry, ResultColumn},
+    profile::{annotate, ExplainAnalyze, ExplainCommand, ExplainFormat},
+    sampling::extract_sample_by,
+    security::{QuerySecurity, SecurityCommand},
+    statistics::{AnalyzeCommand, TableStatistics},
     planner::{QueryPlanner, ExecutionPlan},
-    cache::{QueryCache, CachePolicy},
+    cache::{has_no_cache_hint, CacheDependencies, CachePolicy, QueryCache},
     metrics::QueryMetrics,
+    query_log::{plan_hash, PendingQuery, QueryLog, QueryLogConfig, QueryLogEntry, QUERY_LOG_TABLE},
+    views::{ViewCommand, ViewDelta, ViewManager},
+    streaming::{Cursor, CursorCommand, CursorRegistry, RowStream, STREAM_CHANNEL_CAPACITY},
+    udf::{FunctionCommand, FunctionRegistry},
+    system_tables::SystemTables,
+    transactions::{IsolationLevel, Transaction, TransactionCommand, TransactionManager, TransactionStatus, TRANSACTION_ABORTED},
 };
-use fdc_core::error::{Error, Result};
-use fdc_storage::engine::StorageEngine;
+use fdc_core::{error::{Error, Result}, types::Value};
+use fdc_storage::{engine::StorageEngine, ShardManager};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// 查询执行后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ExecutionBackend {
+    /// 原生执行器（点查、单表扫描）
+    Native,
+    /// DataFusion分析型执行器（需要启用`datafusion`特性）
+    DataFusion,
+    /// 根据查询特征自动选择
+    #[default]
+    Auto,
+}
+
 /// 查询引擎配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryEngineConfig {
@@ -34,6 +64,35 @@ pub struct QueryEngineConfig {
     pub enable_optimization: bool,
     /// 是否启用指标收集
     pub enable_metrics: bool,
+    /// 执行后端选择
+    #[serde(default)]
+    pub execution_backend: ExecutionBackend,
+    /// 流式结果每批的行数
+    #[serde(default = "default_stream_batch_size")]
+    pub stream_batch_size: usize,
+    /// 服务端游标空闲超时，超时后回收并取消其查询
+    #[serde(default = "default_cursor_idle_timeout")]
+    pub cursor_idle_timeout: Duration,
+    /// 资源池与准入控制，所有池合计的并发上限为`max_concurrent_queries`
+    #[serde(default)]
+    pub admission: AdmissionConfig,
+    /// 查询历史与慢查询日志
+    #[serde(default)]
+    pub query_log: QueryLogConfig,
+    /// BEGIN未指定隔离级别时使用的隔离级别
+    #[serde(default)]
+    pub default_isolation: IsolationLevel,
+    /// 不受访问控制约束、可以执行GRANT与策略命令的用户
+    #[serde(default)]
+    pub superusers: Vec<String>,
+}
+
+fn default_stream_batch_size() -> usize {
+    crate::streaming::DEFAULT_STREAM_BATCH_SIZE
+}
+
+fn default_cursor_idle_timeout() -> Duration {
+    Duration::from_secs(crate::streaming::DEFAULT_CURSOR_IDLE_TIMEOUT_SECS)
 }
 
 impl Default for QueryEngineConfig {
@@ -47,6 +106,13 @@ impl Default for QueryEngineConfig {
             max_result_size: crate::DEFAULT_MAX_RESULT_SIZE,
             enable_optimization: true,
             enable_metrics: true,
+            execution_backend: ExecutionBackend::default(),
+            stream_batch_size: default_stream_batch_size(),
+            cursor_idle_timeout: default_cursor_idle_timeout(),
+            admission: AdmissionConfig::default(),
+            query_log: QueryLogConfig::default(),
+            default_isolation: IsolationLevel::default(),
+            superusers: Vec::new(),
         }
     }
 }
@@ -61,32 +127,103 @@ pub struct QueryEngine {
     optimizer: Arc<RwLock<QueryOptimizer>>,
     /// 查询计划器
     planner: QueryPlanner,
-    /// 查询执行器
+    /// 表目录
+    catalog: Arc<Catalog>,
+    /// 存储引擎
+    storage_engine: Arc<dyn StorageEngine>,
+    /// 查询执行器（原生）
     executor: Arc<dyn QueryExecutor>,
+    /// 原生执行器（流式执行使用）
+    native_executor: Arc<DefaultQueryExecutor>,
+    /// 分析型查询执行器
+    analytical_executor: Option<Arc<dyn QueryExecutor>>,
+    /// 分片表的分发-汇集执行器
+    distributed: Option<Arc<DistributedExecutor>>,
+    /// 预处理语句
+    prepared: Arc<PreparedStatementRegistry>,
+    /// 正在执行的查询
+    queries: Arc<QueryRegistry>,
+    /// 准入控制
+    admission: Arc<AdmissionController>,
+    /// 物化视图与连续查询
+    views: Arc<ViewManager>,
+    /// 服务端游标
+    cursors: Arc<CursorRegistry>,
+    /// 用户自定义函数
+    functions: Arc<FunctionRegistry>,
+    /// 虚拟系统表
+    system_tables: Arc<SystemTables>,
     /// 查询缓存
     cache: Arc<RwLock<QueryCache>>,
     /// 查询指标
     metrics: Arc<RwLock<QueryMetrics>>,
+    /// 查询历史
+    query_log: Arc<QueryLog>,
+    /// 各会话进行中的事务
+    transactions: Arc<TransactionManager>,
 }
 
 impl QueryEngine {
     /// 创建新的查询引擎
     pub fn new(storage_engine: Arc<dyn StorageEngine>, config: QueryEngineConfig) -> Self {
-        let cache = Arc::new(RwLock::new(QueryCache::new(
-            config.cache_policy.clone(),
-            config.cache_capacity,
-        )));
+        let cache = QueryCache::new(config.cache_policy.clone(), config.cache_capacity);
+        let invalidator = cache.invalidator();
+        let cache = Arc::new(RwLock::new(cache));
         
         let metrics = Arc::new(RwLock::new(QueryMetrics::new()));
+        let catalog = Arc::new(Catalog::new());
+        // 表写入后使依赖该表的缓存结果失效
+        catalog.on_write(Arc::new(move |write: &TableWrite| invalidator.invalidate(write)));
+        
+        let functions = Arc::new(FunctionRegistry::new());
+        let system_tables = Arc::new(SystemTables::new());
+        let query_log = Arc::new(QueryLog::new(config.query_log.clone()));
+        system_tables.register(QUERY_LOG_TABLE, query_log.clone())
+            .expect("system table registry starts empty");
+        system_tables.register(PARTITIONS_TABLE, Arc::new(PartitionsTable::new(catalog.clone())))
+            .expect("system table registry starts empty");
+        register_query_log_table(&catalog, &config.query_log);
+        let native_executor = Arc::new(
+            DefaultQueryExecutor::with_catalog(storage_engine.clone(), catalog.clone())
+                .with_functions(functions.clone())
+                .with_system_tables(system_tables.clone()),
+        );
+        let views = Arc::new(ViewManager::new(catalog.clone(), storage_engine.clone(), native_executor.clone()));
+        let observer: std::sync::Weak<dyn InsertObserver> = Arc::downgrade(&views);
+        catalog.observe_inserts(observer);
+        
+        #[cfg(feature = "datafusion")]
+        let analytical_executor: Option<Arc<dyn QueryExecutor>> = Some(Arc::new(
+            crate::datafusion_executor::DataFusionExecutor::new(storage_engine.clone(), catalog.clone()),
+        ));
+        #[cfg(not(feature = "datafusion"))]
+        let analytical_executor: Option<Arc<dyn QueryExecutor>> = None;
+        
+        let cursors = Arc::new(CursorRegistry::new(config.cursor_idle_timeout));
+        let admission = Arc::new(AdmissionController::new(config.admission.clone(), config.max_concurrent_queries));
         
         Self {
             config,
             parser: SqlParser::new(),
-            optimizer: Arc::new(RwLock::new(QueryOptimizer::new())),
-            planner: QueryPlanner::new(),
-            executor: Arc::new(DefaultQueryExecutor::new(storage_engine)),
+            optimizer: Arc::new(RwLock::new(QueryOptimizer::new().with_catalog(catalog.clone()))),
+            planner: QueryPlanner::new().with_catalog(catalog.clone()),
+            executor: native_executor.clone(),
+            native_executor,
+            storage_engine,
+            catalog,
+            analytical_executor,
+            distributed: None,
+            prepared: Arc::new(PreparedStatementRegistry::default()),
+            queries: Arc::new(QueryRegistry::new()),
+            admission,
+            views,
+            cursors,
+            functions,
+            system_tables,
             cache,
             metrics,
+            query_log,
+            transactions: Arc::new(TransactionManager::new()),
         }
     }
     
@@ -99,24 +236,200 @@ impl QueryEngine {
         self.execute_sql_with_context(sql, context).await
     }
     
-    /// 使用上下文执行SQL查询
+    /// 使用上下文执行SQL查询，执行结果记入查询历史
+    ///
+    /// 含多条语句时按脚本执行（见`execute_script`），返回最后一条语句的结果。
     pub async fn execute_sql_with_context(&self, sql: &str, context: ExecutionContext) -> Result<ExecutionResult> {
-        let start_time = std::time::Instant::now();
-        
-        // 记录查询开始
-        if self.config.enable_metrics {
-            let mut metrics = self.metrics.write().await;
-            metrics.record_query_start();
+        if split_statements(sql).len() > 1 {
+            let mut results = self.execute_script(sql, context).await?;
+            return Ok(results.pop().unwrap_or_else(|| ExecutionResult::success(Vec::new(), 0)));
+        }
+        self.execute_statement(sql, context).await
+    }
+    
+    /// 依次执行以分号分隔的多条语句，返回各语句的结果；某条语句出错时停止并返回该错误
+    ///
+    /// 有会话ID时脚本中的事务属于该会话；没有时脚本自成事务作用域，脚本结束或出错时
+    /// 仍未提交的事务被回滚。脚本中的语句共享上下文的取消令牌与超时。
+    pub async fn execute_script(&self, sql: &str, mut context: ExecutionContext) -> Result<Vec<ExecutionResult>> {
+        let scoped = context.session_id.is_none();
+        if scoped {
+            context.session_id = Some(format!("script-{}", context.query_id));
+        }
+        let mut results = Vec::new();
+        let mut outcome = Ok(());
+        for (index, statement) in split_statements(sql).iter().enumerate() {
+            let mut statement_context = context.clone();
+            statement_context.query_id = format!("{}-{}", context.query_id, index + 1);
+            match self.execute_statement(statement, statement_context).await {
+                Ok(result) => results.push(result),
+                Err(error) => {
+                    outcome = Err(error);
+                    break;
+                }
+            }
+        }
+        if scoped {
+            let session_id = context.session_id.as_deref().unwrap_or_default();
+            if self.transactions.end_session(session_id) && outcome.is_ok() {
+                outcome = Err(Error::validation("Script ended inside a transaction block; the transaction was rolled back"));
+            }
+        }
+        outcome.map(|_| results)
+    }
+    
+    /// 执行单条语句并记入查询历史；会话在事务中时语句在该事务中执行，失败的语句使事务进入失败状态
+    async fn execute_statement(&self, sql: &str, mut context: ExecutionContext) -> Result<ExecutionResult> {
+        let pending = PendingQuery::new(sql, &context);
+        let mut plan_hash = None;
+        let outcome = match TransactionCommand::parse(sql) {
+            Ok(Some(command)) => self.execute_transaction_command(command, &context).await,
+            Ok(None) => match self.attach_transaction(&mut context) {
+                Ok(transaction) => {
+                    let outcome = self.dispatch_sql(sql, context, &mut plan_hash).await;
+                    fail_on_error(transaction.as_deref(), &outcome);
+                    outcome
+                }
+                Err(error) => Err(error),
+            },
+            Err(error) => Err(error),
+        };
+        self.log_query(pending.finish(&outcome, plan_hash)).await;
+        outcome
+    }
+    
+    /// 把会话进行中的事务挂到上下文上；事务已失败时拒绝执行
+    fn attach_transaction(&self, context: &mut ExecutionContext) -> Result<Option<Arc<Transaction>>> {
+        if context.transaction.is_none() {
+            context.transaction = context.session_id.as_deref().and_then(|session_id| self.transactions.get(session_id));
+        }
+        match &context.transaction {
+            Some(transaction) if transaction.is_failed() => Err(Error::query(TRANSACTION_ABORTED)),
+            transaction => Ok(transaction.clone()),
+        }
+    }
+    
+    /// 执行BEGIN/COMMIT/ROLLBACK/SAVEPOINT/RELEASE；事务以会话为作用域
+    ///
+    /// COMMIT返回写入的行数。失败的事务在COMMIT时回滚；不在事务中时COMMIT与ROLLBACK不做任何事。
+    async fn execute_transaction_command(&self, command: TransactionCommand, context: &ExecutionContext) -> Result<ExecutionResult> {
+        let session_id = context.session_id.as_deref()
+            .ok_or_else(|| Error::validation("Transactions require a session ID or a multi-statement script"))?;
+        let in_transaction = || {
+            let transaction = self.transactions.get(session_id)
+                .ok_or_else(|| Error::validation("Savepoints can only be used in transaction blocks"))?;
+            Ok::<_, Error>(transaction)
+        };
+        let mut result = ExecutionResult::success(Vec::new(), 0);
+        match command {
+            TransactionCommand::Begin { isolation } => {
+                let transaction = self.catalog.begin_transaction(isolation.or(context.isolation).unwrap_or(self.config.default_isolation)).await;
+                self.transactions.begin(session_id, transaction)?;
+            }
+            TransactionCommand::Commit => {
+                if let Some(transaction) = self.transactions.take(session_id) {
+                    if !transaction.is_failed() {
+                        result.affected_rows = self.catalog.commit_transaction(self.storage_engine.as_ref(), &transaction).await?;
+                    }
+                }
+            }
+            TransactionCommand::Rollback { savepoint: None } => {
+                self.transactions.take(session_id);
+            }
+            TransactionCommand::Rollback { savepoint: Some(name) } => in_transaction()?.rollback_to(&name)?,
+            TransactionCommand::Savepoint { name } => {
+                let transaction = in_transaction()?;
+                if transaction.is_failed() {
+                    return Err(Error::query(TRANSACTION_ABORTED));
+                }
+                transaction.savepoint(&name);
+            }
+            TransactionCommand::Release { name } => {
+                let transaction = in_transaction()?;
+                if transaction.is_failed() {
+                    return Err(Error::query(TRANSACTION_ABORTED));
+                }
+                transaction.release(&name)?;
+            }
+        }
+        Ok(result)
+    }
+    
+    /// 会话的事务状态
+    pub fn transaction_status(&self, session_id: &str) -> TransactionStatus {
+        self.transactions.status(session_id)
+    }
+    
+    /// 会话结束：回滚其未提交的事务
+    pub fn end_session(&self, session_id: &str) {
+        if self.transactions.end_session(session_id) {
+            tracing::debug!("Rolled back open transaction of session {}", session_id);
+        }
+    }
+    
+    /// 获取各会话进行中的事务
+    pub fn transactions(&self) -> &Arc<TransactionManager> {
+        &self.transactions
+    }
+    
+    /// 按语句类型分派执行；生成了执行计划时把计划形状的哈希写入`plan_hash`
+    async fn dispatch_sql(&self, sql: &str, context: ExecutionContext, plan_hash: &mut Option<String>) -> Result<ExecutionResult> {
+        // KILL QUERY / SHOW QUERIES
+        if let Some(command) = QueryCommand::parse(sql) {
+            return self.execute_query_command(command, &context);
+        }
+        // CREATE FUNCTION / DROP FUNCTION
+        if let Some(command) = FunctionCommand::parse(sql)? {
+            return self.execute_function_command(command).await;
+        }
+        // DECLARE / FETCH / CLOSE游标
+        if let Some(command) = CursorCommand::parse(sql)? {
+            return self.execute_cursor_command(command, context).await;
+        }
+        // GRANT / REVOKE、CREATE/DROP POLICY、列掩码与用户属性
+        if let Some(command) = SecurityCommand::parse(sql)? {
+            return self.execute_security_command(command, &context).await;
+        }
+        // CREATE TABLE ... PARTITION BY、ALTER TABLE ... DROP/DETACH PARTITION
+        if let Some(command) = PartitionCommand::parse(sql)? {
+            return self.execute_partition_command(command, &context).await;
+        }
+        // CREATE/DROP/REFRESH MATERIALIZED VIEW、CREATE/DROP CONTINUOUS QUERY
+        if let Some(command) = ViewCommand::parse(sql)? {
+            return self.execute_view_command(command, &context).await;
+        }
+        // PREPARE / EXECUTE / DEALLOCATE
+        if let Some(command) = PreparedCommand::parse(sql)? {
+            return self.execute_prepared_command(command, context).await;
+        }
+        if let Some(command) = AnalyzeCommand::parse(sql) {
+            let rows = self.analyze(command.table.as_deref()).await?.into_iter().map(|statistics| {
+                let mut row = HashMap::new();
+                row.insert("table".to_string(), Value::String(statistics.table));
+                row.insert("row_count".to_string(), Value::UInt64(statistics.row_count));
+                row.insert("columns".to_string(), Value::UInt64(statistics.columns.len() as u64));
+                row
+            }).collect();
+            return Ok(ExecutionResult::success(rows, 0));
+        }
+        if let Some(command) = ExplainCommand::parse(sql) {
+            return self.execute_explain(command, context).await;
         }
         
-        // 检查缓存
-        if self.config.enable_cache {
-            let query_hash = self.calculate_query_hash(sql);
-            let mut cache = self.cache.write().await;
-            if let Some(cached_result) = cache.get(&query_hash) {
+        // 检查缓存（参数与受访问控制约束的用户参与缓存键）；`/*+ NO_CACHE */`提示跳过缓存
+        let query_hash = self.calculate_query_hash(sql, &context.parameters, self.restricted_user(&context));
+        // 事务中的读取包含未提交的写入，不经过缓存
+        let use_cache = self.config.enable_cache
+            && context.enable_cache
+            && context.transaction.is_none()
+            && !has_no_cache_hint(sql);
+        if use_cache {
+            let cached = self.cache.write().await.get(&query_hash);
+            if let Some(cached_result) = cached {
                 // 记录缓存命中
                 if self.config.enable_metrics {
                     let mut metrics = self.metrics.write().await;
+                    metrics.record_query_start();
                     metrics.record_cache_hit();
                 }
                 return Ok(cached_result);
@@ -127,34 +440,461 @@ impl QueryEngine {
         let parsed_query = self.parser.parse(sql)?;
         
         // 优化查询
-        let optimized_plan = if self.config.enable_optimization {
-            let mut optimizer = self.optimizer.write().await;
-            optimizer.optimize(parsed_query)?
-        } else {
-            OptimizedPlan::new(parsed_query)
-        };
+        let optimized_plan = self.optimize(parsed_query).await?;
         
         // 创建执行计划
-        let _execution_plan = self.planner.create_plan(&optimized_plan)?;
+        let execution_plan = self.planner.create_plan(&optimized_plan)?;
+        *plan_hash = Some(self::plan_hash(&execution_plan));
+        
+        // 只缓存只读查询，并记录结果依赖的表和时间范围；系统表的内容随引擎状态变化，不缓存
+        let cacheable = use_cache
+            && optimized_plan.original_query.is_readonly
+            && !self.system_tables.references_any(&optimized_plan.original_query.tables);
+        let dependencies = if cacheable {
+            let relations = self.parser.referenced_tables(sql)
+                .unwrap_or_else(|_| optimized_plan.original_query.tables.clone());
+            Some(CacheDependencies::from_relations(&relations, &optimized_plan.access_paths))
+        } else {
+            None
+        };
+        let write_sequence = self.cache.read().await.write_sequence();
         
         // 执行查询
-        let result = self.executor.execute(optimized_plan, context).await?;
+        let result = self.run_plan(optimized_plan, context).await?;
         
         // 缓存结果
-        if self.config.enable_cache && result.is_success() {
-            let query_hash = self.calculate_query_hash(sql);
-            let mut cache = self.cache.write().await;
-            cache.put(query_hash, result.clone());
+        if let Some(dependencies) = dependencies {
+            if result.is_success() {
+                let mut cache = self.cache.write().await;
+                cache.put_with_dependencies(query_hash, result.clone(), dependencies, write_sequence);
+            }
         }
         
-        // 记录查询完成
+        Ok(result)
+    }
+    
+    /// 流式执行SELECT，返回按批产出结果的行批次流
+    ///
+    /// 查询在后台任务中执行，结果经有界通道分批交付：消费者读得慢时扫描随之暂停，
+    /// 丢弃流即取消查询。流式结果不按`max_rows`截断，也不进入查询缓存。
+    pub async fn execute_stream(&self, sql: &str, context: ExecutionContext) -> Result<RowStream> {
+        context.cancellation.start_deadline(context.timeout);
+        self.start_stream(sql, context)
+    }
+    
+    /// 声明服务端游标；游标不受语句超时约束，空闲超过`cursor_idle_timeout`后被回收
+    pub async fn declare_cursor(&self, name: &str, sql: &str, context: ExecutionContext) -> Result<()> {
+        let session_id = context.session_id.clone();
+        let stream = self.start_stream(sql, context)?;
+        self.cursors.declare(session_id.as_deref(), name, Cursor::new(sql, stream))
+    }
+    
+    /// 获取服务端游标注册表
+    pub fn cursors(&self) -> &Arc<CursorRegistry> {
+        &self.cursors
+    }
+    
+    /// 在后台任务中执行SELECT并把结果写入行批次流
+    fn start_stream(&self, sql: &str, mut context: ExecutionContext) -> Result<RowStream> {
+        let parsed_query = self.parser.parse(sql)?;
+        if parsed_query.query_type != crate::parser::QueryType::Select {
+            return Err(Error::validation("Only SELECT queries can be streamed"));
+        }
+        self.authorize(&parsed_query, &mut context)?;
+        let analytical = match self.sharded_executor(&parsed_query) {
+            _ if context.transaction.is_some() || context.security.is_some() => None,
+            Some(executor) => Some(executor),
+            None => match self.select_backend(&parsed_query)? {
+                ExecutionBackend::DataFusion => self.analytical_executor.clone(),
+                _ => None,
+            },
+        };
+        context.max_rows = None;
+        let pool = self.admission.route(&context)?.name.clone();
+        context.resource_pool = Some(pool);
+        
+        let running = self.queries.register(RunningQuery::new(sql, &context))?;
+        let (mut sender, stream) = RowStream::channel(context.query_id.clone(), context.cancellation.clone(), STREAM_CHANNEL_CAPACITY);
+        let admission = self.admission.clone();
+        let native = self.native_executor.clone();
+        let metrics = self.config.enable_metrics.then(|| self.metrics.clone());
+        let batch_size = self.config.stream_batch_size;
+        let sql = sql.to_string();
+        let pending = PendingQuery::new(&sql, &context);
+        let query_log = self.query_log.clone();
+        
+        tokio::spawn(async move {
+            let _running = running;
+            // 流在整个生命周期内占用资源池的执行槽位
+            let permit = match admission.admit(&context).await {
+                Ok(permit) => permit,
+                Err(error) => return sender.fail(error).await,
+            };
+            context.memory = permit.memory_budget().clone();
+            let start_time = std::time::Instant::now();
+            if let Some(metrics) = &metrics {
+                metrics.write().await.record_query_start();
+            }
+            
+            let outcome = match analytical {
+                // 分析型后端与分布式执行先完整执行，再分批交付
+                Some(executor) => match executor.execute(OptimizedPlan::new(parsed_query), context).await {
+                    Ok(result) => {
+                        sender.send_all(result.rows, batch_size).await;
+                        Ok(())
+                    }
+                    Err(error) => Err(error),
+                },
+                None => native.stream_select(&sql, &context, batch_size, &mut sender).await,
+            };
+            let success = outcome.is_ok();
+            let error = outcome.as_ref().err().map(|error| error.to_string());
+            if let Err(error) = outcome {
+                sender.fail(error).await;
+            }
+            
+            if let Some(metrics) = &metrics {
+                metrics.write().await.record_query_complete(start_time.elapsed(), success);
+            }
+            // 流式查询只记入内存中的查询历史
+            query_log.record(pending.finish_with(sender.rows_sent(), 0, error, None));
+        });
+        
+        Ok(stream)
+    }
+    
+    /// 设置`CREATE FUNCTION ... LANGUAGE wasm`解析插件所用的插件注册表
+    pub fn with_plugin_registry(self, plugins: Arc<fdc_wasm::PluginRegistry>) -> Self {
+        self.functions.set_plugin_registry(plugins);
+        self
+    }
+    
+    /// 把数据分布到多个分片上，只读取分片表的SELECT改为分发到各分片执行
+    ///
+    /// 协调节点复用引擎的原生执行器（含已注册的自定义函数）；分片表通过`distributed()`创建和写入。
+    pub fn with_shards(mut self, shard_manager: Arc<ShardManager>, shards: Vec<Arc<dyn ShardExecutor>>) -> Result<Self> {
+        let distributed = DistributedExecutor::new(shard_manager, shards, self.native_executor.clone())?;
+        self.distributed = Some(Arc::new(distributed));
+        Ok(self)
+    }
+    
+    /// 获取分布式执行器（未配置分片时为None）
+    pub fn distributed(&self) -> Option<&Arc<DistributedExecutor>> {
+        self.distributed.as_ref()
+    }
+    
+    /// 查询只读取分片表时返回分布式执行器
+    fn sharded_executor(&self, query: &ParsedQuery) -> Option<Arc<dyn QueryExecutor>> {
+        self.distributed.as_ref()
+            .filter(|distributed| distributed.covers(query))
+            .map(|distributed| distributed.clone() as Arc<dyn QueryExecutor>)
+    }
+    
+    /// 获取准入控制器（资源池运行状态）
+    pub fn admission(&self) -> &Arc<AdmissionController> {
+        &self.admission
+    }
+    
+    /// 获取用户自定义函数注册表（Rust实现的函数可直接注册）
+    pub fn functions(&self) -> &Arc<FunctionRegistry> {
+        &self.functions
+    }
+    
+    /// 获取查询历史
+    pub fn query_log(&self) -> &Arc<QueryLog> {
+        &self.query_log
+    }
+    
+    /// 记录一条查询历史，配置了持久化日志表时同时写入该表
+    async fn log_query(&self, entry: QueryLogEntry) {
+        let Some(entry) = self.query_log.record(entry) else {
+            return;
+        };
+        if let Some(table) = &self.config.query_log.persist_table {
+            // 日志写入失败不影响查询结果
+            if let Err(e) = self.catalog.insert_rows(self.storage_engine.as_ref(), table, &[entry.to_row()]).await {
+                tracing::warn!("Failed to persist query log entry {}: {}", entry.query_id, e);
+            }
+        }
+    }
+    
+    /// 获取虚拟系统表注册表
+    pub fn system_tables(&self) -> &Arc<SystemTables> {
+        &self.system_tables
+    }
+    
+    /// 执行SQL级的CREATE FUNCTION / DROP FUNCTION
+    async fn execute_function_command(&self, command: FunctionCommand) -> Result<ExecutionResult> {
+        match command {
+            FunctionCommand::Create { signature, plugin, export, or_replace } => {
+                self.functions.register_wasm(signature, &plugin, &export, or_replace)?;
+            }
+            FunctionCommand::Drop { name, if_exists } => self.functions.drop_function(&name, if_exists)?,
+            FunctionCommand::CreateAggregate { signature, plugin, prefix, or_replace } => {
+                self.functions.register_wasm_aggregate(signature, &plugin, &prefix, or_replace)?;
+            }
+            FunctionCommand::DropAggregate { name, if_exists } => self.functions.drop_aggregate(&name, if_exists)?,
+        }
+        // 函数定义变化后缓存的结果可能已过期
+        self.cache.write().await.clear();
+        Ok(ExecutionResult::success(Vec::new(), 0))
+    }
+    
+    /// 执行SQL级的DECLARE / FETCH / CLOSE
+    async fn execute_cursor_command(&self, command: CursorCommand, context: ExecutionContext) -> Result<ExecutionResult> {
+        let session_id = context.session_id.clone();
+        match command {
+            CursorCommand::Declare { name, sql } => {
+                self.declare_cursor(&name, &sql, context).await?;
+                Ok(ExecutionResult::success(Vec::new(), 0))
+            }
+            CursorCommand::Fetch { name, count } => {
+                let start_time = std::time::Instant::now();
+                let (rows, _) = self.cursors.fetch(session_id.as_deref(), &name, count).await?;
+                Ok(ExecutionResult::success(rows, start_time.elapsed().as_micros() as u64))
+            }
+            CursorCommand::Close { name } => {
+                let closed = match name {
+                    Some(name) => self.cursors.close(session_id.as_deref(), &name).map(|_| 1)?,
+                    None => self.cursors.close_all(session_id.as_deref()),
+                };
+                let mut result = ExecutionResult::success(Vec::new(), 0);
+                result.affected_rows = closed as u64;
+                Ok(result)
+            }
+        }
+    }
+    
+    /// 收集表统计信息；未指定表时分析目录中的所有表
+    pub async fn analyze(&self, table: Option<&str>) -> Result<Vec<TableStatistics>> {
+        let tables = match table {
+            Some(table) => vec![table.to_string()],
+            None => self.catalog.list_tables().into_iter().map(|t| t.name).collect(),
+        };
+        let mut analyzed = Vec::with_capacity(tables.len());
+        for table in tables {
+            analyzed.push(self.catalog.analyze_table(self.storage_engine.as_ref(), &table).await?);
+        }
+        Ok(analyzed)
+    }
+    
+    /// 预处理SQL：解析、优化并按表结构推断参数类型，相同SQL复用已缓存的语句
+    pub async fn prepare(&self, sql: &str) -> Result<Arc<PreparedStatement>> {
+        if let Some(statement) = self.prepared.get_by_sql(sql) {
+            return Ok(statement);
+        }
+        let statement = Arc::new(self.build_prepared(sql).await?);
+        self.prepared.cache(statement.clone());
+        Ok(statement)
+    }
+    
+    /// 按位置绑定参数并执行预处理语句
+    pub async fn execute_prepared(&self, statement: &PreparedStatement, values: Vec<Value>) -> Result<ExecutionResult> {
+        let parameters = statement.bind(values)?;
+        self.execute_bound(statement, parameters, self.default_context()).await
+    }
+    
+    /// 按名称绑定参数并执行预处理语句
+    pub async fn execute_prepared_named(
+        &self,
+        statement: &PreparedStatement,
+        values: HashMap<String, Value>,
+    ) -> Result<ExecutionResult> {
+        let parameters = statement.bind_named(values)?;
+        self.execute_bound(statement, parameters, self.default_context()).await
+    }
+    
+    /// 使用已绑定的参数执行预处理语句（跳过解析与优化），执行结果记入查询历史
+    pub async fn execute_bound(
+        &self,
+        statement: &PreparedStatement,
+        parameters: HashMap<String, Value>,
+        mut context: ExecutionContext,
+    ) -> Result<ExecutionResult> {
+        let pending = PendingQuery::new(&statement.sql, &context);
+        let hash = self.planner.create_plan(&statement.plan).ok().map(|plan| plan_hash(&plan));
+        let outcome = match self.attach_transaction(&mut context) {
+            Ok(transaction) => {
+                let outcome = self.run_bound(statement, parameters, context).await;
+                fail_on_error(transaction.as_deref(), &outcome);
+                outcome
+            }
+            Err(error) => Err(error),
+        };
+        self.log_query(pending.finish(&outcome, hash)).await;
+        outcome
+    }
+    
+    /// 执行预处理语句的缓存计划
+    async fn run_bound(
+        &self,
+        statement: &PreparedStatement,
+        parameters: HashMap<String, Value>,
+        mut context: ExecutionContext,
+    ) -> Result<ExecutionResult> {
+        context.parameters.extend(parameters);
+        statement.record_execution();
+        self.run_plan(statement.plan.clone(), context).await
+    }
+    
+    /// 推断查询结果的列（需要在执行前描述结果的客户端使用，如PostgreSQL线协议）
+    ///
+    /// 返回None表示无法静态确定，应按执行结果推断。
+    pub fn describe(&self, sql: &str) -> Result<Option<Vec<ResultColumn>>> {
+        let (stripped, _) = extract_sample_by(sql)?;
+        let statement = parse_statement(&stripped)?;
+        Ok(describe_columns(&statement, |name| {
+            self.catalog.get_table(name)
+                .or_else(|| self.system_tables.get(name).map(|table| table.definition()))
+        }))
+    }
+    
+    /// 删除命名预处理语句
+    pub fn deallocate(&self, name: &str) -> Result<()> {
+        self.prepared.deallocate(name)
+    }
+    
+    /// 获取预处理语句注册表
+    pub fn prepared_statements(&self) -> &PreparedStatementRegistry {
+        &self.prepared
+    }
+    
+    /// 执行SQL级的PREPARE/EXECUTE/DEALLOCATE
+    async fn execute_prepared_command(&self, command: PreparedCommand, context: ExecutionContext) -> Result<ExecutionResult> {
+        match command {
+            PreparedCommand::Prepare { name, data_types, sql } => {
+                let statement = self.build_prepared(&sql).await?
+                    .with_name(name)
+                    .with_declared_types(&data_types)?;
+                self.prepared.register(Arc::new(statement))?;
+                Ok(ExecutionResult::success(Vec::new(), 0))
+            }
+            PreparedCommand::Execute { name, parameters } => {
+                let statement = self.prepared.get(&name)
+                    .ok_or_else(|| Error::not_found(format!("Prepared statement {} does not exist", name)))?;
+                // 参数只能是常量表达式，按值绑定而不拼接SQL
+                let evaluator = ExpressionEvaluator::new().with_parameters(context.parameters.clone());
+                let values = parameters.iter()
+                    .map(|expr| evaluator.evaluate(expr, &HashMap::new()))
+                    .collect::<Result<Vec<_>>>()?;
+                let bound = statement.bind(values)?;
+                self.run_bound(&statement, bound, context).await
+            }
+            PreparedCommand::Deallocate { name } => {
+                self.prepared.deallocate(&name)?;
+                Ok(ExecutionResult::success(Vec::new(), 0))
+            }
+        }
+    }
+    
+    async fn build_prepared(&self, sql: &str) -> Result<PreparedStatement> {
+        let parsed_query = self.parser.parse(sql)?;
+        let optimized_plan = self.optimize(parsed_query).await?;
+        self.planner.create_plan(&optimized_plan)?;
+        
+        let (stripped, _) = extract_sample_by(sql)?;
+        let parameters = infer_parameters(&parse_statement(&stripped)?, &self.catalog);
+        Ok(PreparedStatement::new(sql, optimized_plan, parameters))
+    }
+    
+    /// 优化解析后的查询
+    async fn optimize(&self, parsed_query: ParsedQuery) -> Result<OptimizedPlan> {
+        if self.config.enable_optimization {
+            let mut optimizer = self.optimizer.write().await;
+            optimizer.optimize(parsed_query)
+        } else {
+            Ok(OptimizedPlan::new(parsed_query))
+        }
+    }
+    
+    /// 在选定的后端上执行优化后的计划并记录指标
+    async fn run_plan(&self, optimized_plan: OptimizedPlan, mut context: ExecutionContext) -> Result<ExecutionResult> {
+        self.authorize(&optimized_plan.original_query, &mut context)?;
+        // 按资源池准入，排队时间不计入语句超时
+        let permit = self.admission.admit(&context).await?;
+        context.resource_pool = Some(permit.pool().to_string());
+        context.memory = permit.memory_budget().clone();
+        let start_time = std::time::Instant::now();
+        
+        // 事务的快照与未提交写入只在原生执行器中可见，行策略与掩码也只由原生执行器施加
+        let native_only = context.transaction.is_some() || context.security.is_some();
+        let sharded = if native_only { None } else { self.sharded_executor(&optimized_plan.original_query) };
+        let executor = match &sharded {
+            Some(executor) => executor,
+            None if native_only => &self.executor,
+            None => match self.select_backend(&optimized_plan.original_query)? {
+                ExecutionBackend::DataFusion => self.analytical_executor.as_ref()
+                    .ok_or_else(|| Error::unimplemented("DataFusion backend is not enabled"))?,
+                _ => &self.executor,
+            },
+        };
+        
+        // 登记到SHOW QUERIES，超时从此刻开始计时
+        context.cancellation.start_deadline(context.timeout);
+        let _running = self.queries.register(RunningQuery::new(optimized_plan.original_query.sql.clone(), &context))?;
+        // 事务中的写入在COMMIT时由目录通知
+        let written_tables = if optimized_plan.original_query.is_readonly || context.transaction.is_some() {
+            Vec::new()
+        } else {
+            optimized_plan.original_query.tables.clone()
+        };
+        
+        // 记录查询开始
         if self.config.enable_metrics {
             let mut metrics = self.metrics.write().await;
-            let execution_time = start_time.elapsed();
-            metrics.record_query_complete(execution_time, result.is_success());
+            metrics.record_query_start();
         }
+        let outcome = executor.execute(optimized_plan, context).await;
+        drop(permit);
         
-        Ok(result)
+        // 写入语句结束后使相关缓存失效；失败、取消或超时的语句可能已写入部分数据，同样失效
+        // （目录写入会另外带上时间范围通知）
+        for table in &written_tables {
+            self.catalog.notify_write(&TableWrite::new(table));
+        }
+        
+        // 记录查询完成，出错的查询计为失败
+        if self.config.enable_metrics {
+            let mut metrics = self.metrics.write().await;
+            metrics.record_query_complete(start_time.elapsed(), outcome.as_ref().is_ok_and(|result| result.is_success()));
+        }
+        
+        outcome
+    }
+    
+    /// 受访问控制约束的用户；没有用户ID的上下文（嵌入式调用）与超级用户不受约束
+    fn restricted_user<'a>(&self, context: &'a ExecutionContext) -> Option<&'a str> {
+        context.user_id.as_deref()
+            .filter(|user| !self.config.superusers.iter().any(|superuser| superuser.eq_ignore_ascii_case(user)))
+    }
+    
+    /// 检查用户执行查询所需的权限，并把计划器解析的行策略条件与列掩码放入上下文
+    fn authorize(&self, query: &ParsedQuery, context: &mut ExecutionContext) -> Result<()> {
+        if context.security.is_some() {
+            return Ok(());
+        }
+        let security = match self.restricted_user(context) {
+            Some(user) => {
+                let security = self.planner.secure(query, user)?;
+                // 按用户归属的系统表（查询日志）只能读到自己的行
+                let owned = query.tables.iter()
+                    .any(|table| self.system_tables.get(table).is_some_and(|system| system.owner_column().is_some()));
+                if owned {
+                    let security = security.map_or_else(QuerySecurity::default, |security| (*security).clone());
+                    Some(Arc::new(security.with_owner(user)))
+                } else {
+                    security
+                }
+            }
+            None => None,
+        };
+        context.security = security;
+        Ok(())
+    }
+    
+    fn default_context(&self) -> ExecutionContext {
+        ExecutionContext::new(uuid::Uuid::new_v4().to_string())
+            .with_timeout(self.config.query_timeout)
     }
     
     /// 验证SQL语法
@@ -174,20 +914,298 @@ impl QueryEngine {
     
     /// 获取查询计划
     pub async fn explain_query(&self, sql: &str) -> Result<ExecutionPlan> {
+        self.explain_query_with_context(sql, self.default_context()).await
+    }
+    
+    /// 按上下文中的用户生成执行计划，标注注入的行策略条件与列掩码
+    pub async fn explain_query_with_context(&self, sql: &str, mut context: ExecutionContext) -> Result<ExecutionPlan> {
         let parsed_query = self.parser.parse(sql)?;
-        let optimized_plan = if self.config.enable_optimization {
-            let mut optimizer = self.optimizer.write().await;
-            optimizer.optimize(parsed_query)?
-        } else {
-            OptimizedPlan::new(parsed_query)
+        self.authorize(&parsed_query, &mut context)?;
+        if context.security.is_none() {
+            if let Some(distributed) = self.distributed.as_ref().filter(|d| d.covers(&parsed_query)) {
+                return distributed.explain(sql);
+            }
+        }
+        let optimized_plan = self.optimize(parsed_query).await?;
+        
+        let mut plan = self.planner.create_plan(&optimized_plan)?;
+        if let Some(security) = &context.security {
+            self.planner.annotate_security(&mut plan, &optimized_plan.original_query, security);
+        }
+        Ok(plan)
+    }
+    
+    /// 执行查询并返回标注了各算子实际指标的执行计划（不读写查询缓存）
+    pub async fn explain_analyze(&self, sql: &str) -> Result<ExplainAnalyze> {
+        self.explain_analyze_with_context(sql, self.default_context()).await
+    }
+    
+    /// 使用上下文执行EXPLAIN ANALYZE
+    pub async fn explain_analyze_with_context(&self, sql: &str, context: ExecutionContext) -> Result<ExplainAnalyze> {
+        self.execute_profiled(sql, context).await.map(|(_, explained)| explained)
+    }
+    
+    /// 开启剖析执行查询，同时返回结果与标注了实际指标的执行计划（不读写查询缓存）
+    ///
+    /// 只有原生执行器提供算子级指标，分析型后端只报告总耗时与行数。
+    pub async fn execute_profiled(&self, sql: &str, mut context: ExecutionContext) -> Result<(ExecutionResult, ExplainAnalyze)> {
+        let start_time = std::time::Instant::now();
+        let parsed_query = self.parser.parse(sql)?;
+        self.authorize(&parsed_query, &mut context)?;
+        let optimized_plan = self.optimize(parsed_query).await?;
+        let mut plan = self.planner.create_plan(&optimized_plan)?;
+        let backend = match &context.security {
+            Some(security) => {
+                self.planner.annotate_security(&mut plan, &optimized_plan.original_query, security);
+                ExecutionBackend::Native
+            }
+            None => self.select_backend(&optimized_plan.original_query)?,
         };
+        let optimization = optimized_plan.stats.clone();
+        let planning_time_us = start_time.elapsed().as_micros() as u64;
         
-        self.planner.create_plan(&optimized_plan)
+        let result = self.run_plan(optimized_plan, context.with_profiling(true)).await?;
+        if let Some(error) = &result.error {
+            return Err(Error::query(error.clone()));
+        }
+        let plan = match &result.stats.operators {
+            Some(operators) => annotate(plan, operators),
+            None => plan,
+        };
+        let explained = ExplainAnalyze {
+            plan,
+            backend,
+            planning_time_us,
+            execution_time_us: result.execution_time_us,
+            rows: result.row_count() as u64,
+            optimization,
+        };
+        Ok((result, explained))
+    }
+    
+    /// 执行SQL级的EXPLAIN [ANALYZE]，文本格式每行一条记录，JSON格式单条记录
+    async fn execute_explain(&self, command: ExplainCommand, context: ExecutionContext) -> Result<ExecutionResult> {
+        let encode = |e: serde_json::Error| Error::serialization(format!("Failed to encode plan: {}", e));
+        let output = match (command.analyze, command.format) {
+            (true, ExplainFormat::Text) => self.explain_analyze_with_context(&command.sql, context).await?.to_text(),
+            (true, ExplainFormat::Json) => self.explain_analyze_with_context(&command.sql, context).await?.to_json()?.to_string(),
+            (false, ExplainFormat::Text) => self.explain_query_with_context(&command.sql, context).await?.render(),
+            (false, ExplainFormat::Json) => {
+                serde_json::to_string(&self.explain_query_with_context(&command.sql, context).await?).map_err(encode)?
+            }
+        };
+        let lines: Vec<&str> = match command.format {
+            ExplainFormat::Text => output.lines().collect(),
+            ExplainFormat::Json => vec![output.as_str()],
+        };
+        let rows = lines.into_iter().map(|line| {
+            let mut row = HashMap::new();
+            row.insert("plan".to_string(), Value::String(line.to_string()));
+            row
+        }).collect();
+        Ok(ExecutionResult::success(rows, 0))
+    }
+    
+    /// 为查询选择执行后端
+    ///
+    /// `Auto`模式下，包含聚合、连接、子查询、CTE、窗口函数或集合运算的SELECT
+    /// 交给DataFusion（可用时），点查和简单扫描走原生执行器。ASOF等时序连接
+    /// 与SAMPLE BY只有原生执行器支持，始终走原生路径。
+    pub fn select_backend(&self, query: &ParsedQuery) -> Result<ExecutionBackend> {
+        // 自定义函数与系统表只在原生执行器中注册
+        if !self.functions.is_empty() && self.functions.is_referenced(&query.sql) {
+            return Ok(ExecutionBackend::Native);
+        }
+        if self.system_tables.references_any(&query.tables) {
+            return Ok(ExecutionBackend::Native);
+        }
+        match self.config.execution_backend {
+            ExecutionBackend::Native => Ok(ExecutionBackend::Native),
+            ExecutionBackend::DataFusion => {
+                if self.analytical_executor.is_none() {
+                    return Err(Error::unimplemented(
+                        "DataFusion backend requested but fdc-query was built without the `datafusion` feature",
+                    ));
+                }
+                if query.query_type == crate::parser::QueryType::Select && !query.features.requires_native() {
+                    Ok(ExecutionBackend::DataFusion)
+                } else {
+                    Ok(ExecutionBackend::Native)
+                }
+            }
+            ExecutionBackend::Auto => {
+                if self.analytical_executor.is_some()
+                    && query.query_type == crate::parser::QueryType::Select
+                    && query.features.is_analytical()
+                    && !query.features.requires_native()
+                {
+                    Ok(ExecutionBackend::DataFusion)
+                } else {
+                    Ok(ExecutionBackend::Native)
+                }
+            }
+        }
+    }
+    
+    /// 获取表目录
+    pub fn catalog(&self) -> &Arc<Catalog> {
+        &self.catalog
     }
     
     /// 取消查询
     pub async fn cancel_query(&self, query_id: &str) -> Result<()> {
-        self.executor.cancel(query_id).await
+        self.queries.cancel(query_id, format!("query {} cancelled", query_id));
+        self.executor.cancel(query_id).await?;
+        if let Some(executor) = &self.analytical_executor {
+            executor.cancel(query_id).await?;
+        }
+        Ok(())
+    }
+    
+    /// 终止正在执行的查询；查询会在下一个检查点以取消错误退出
+    pub fn kill_query(&self, query_id: &str) -> Result<()> {
+        if self.queries.cancel(query_id, format!("query {} killed", query_id)) {
+            Ok(())
+        } else {
+            Err(Error::not_found(format!("running query {}", query_id)))
+        }
+    }
+    
+    /// 正在执行的查询，按开始时间排序
+    pub fn running_queries(&self) -> Vec<RunningQuery> {
+        self.queries.list()
+    }
+    
+    /// 物化视图与连续查询
+    pub fn views(&self) -> &Arc<ViewManager> {
+        &self.views
+    }
+    
+    /// 订阅物化视图或连续查询的结果增量
+    pub fn subscribe(&self, view: &str) -> Result<tokio::sync::broadcast::Receiver<ViewDelta>> {
+        self.views.subscribe(view)
+    }
+    
+    /// 数据接入批次提交后调用：使相关缓存失效并增量维护以该表为源的视图
+    pub async fn apply_ingested_rows(&self, table: &str, rows: &[HashMap<String, Value>]) -> Result<()> {
+        let write = match self.catalog.get_table(table) {
+            Some(definition) => TableWrite::from_rows(&definition, rows),
+            None => TableWrite::new(table),
+        };
+        self.catalog.notify_write(&write);
+        self.views.apply(table, rows).await
+    }
+    
+    /// 执行视图管理命令
+    async fn execute_view_command(&self, command: ViewCommand, context: &ExecutionContext) -> Result<ExecutionResult> {
+        let affected_rows = match command {
+            ViewCommand::Create { kind, name, refresh, if_not_exists, sql } => {
+                // 视图结果不受源表的访问控制约束，受约束的用户不能在受控表上建视图
+                if let Some(user) = self.restricted_user(context) {
+                    let (query, _) = extract_sample_by(&sql)?;
+                    let tables = self.parser.referenced_tables(&query)?;
+                    if let Some(table) = tables.iter().find(|table| self.catalog.security().is_secured(table)) {
+                        return Err(Error::permission_denied(format!(
+                            "user {} cannot create a view over access-controlled table {}", user, table
+                        )));
+                    }
+                }
+                self.views.create(kind, &name, refresh, &sql, if_not_exists).await?
+            }
+            ViewCommand::Drop { kind, name, if_exists } => {
+                self.views.drop_view(kind, &name, if_exists).await?;
+                0
+            }
+            ViewCommand::Refresh { name } => self.views.refresh(&name).await?,
+        };
+        let mut result = ExecutionResult::success(Vec::new(), 0);
+        result.affected_rows = affected_rows;
+        Ok(result)
+    }
+    
+    /// 执行分区表DDL
+    async fn execute_partition_command(&self, command: PartitionCommand, context: &ExecutionContext) -> Result<ExecutionResult> {
+        let storage = self.storage_engine.as_ref();
+        // 整段删除或移走分区绕过行策略，受约束的用户不能对受控表执行
+        let guarded = match &command {
+            PartitionCommand::CreateTable { .. } => None,
+            PartitionCommand::DropPartition { table, .. } | PartitionCommand::DetachPartition { table, .. } => Some(table),
+        };
+        if let (Some(user), Some(table)) = (self.restricted_user(context), guarded) {
+            if self.catalog.security().is_secured(table) {
+                return Err(Error::permission_denied(format!(
+                    "user {} cannot drop or detach partitions of access-controlled table {}", user, table
+                )));
+            }
+        }
+        let affected_rows = match command {
+            PartitionCommand::CreateTable { definition, if_not_exists } => {
+                if !(if_not_exists && self.catalog.contains_table(&definition.name)) {
+                    self.catalog.register_table(definition)?;
+                }
+                0
+            }
+            PartitionCommand::DropPartition { table, partition, if_exists } => {
+                let exists = self.catalog.partitions(&table).iter().any(|p| p.name == partition);
+                if if_exists && !exists && self.catalog.contains_table(&table) {
+                    0
+                } else {
+                    self.catalog.drop_partition(storage, &table, &partition).await?
+                }
+            }
+            PartitionCommand::DetachPartition { table, partition, target } => {
+                self.catalog.detach_partition(storage, &table, &partition, target.as_deref()).await?
+            }
+        };
+        let mut result = ExecutionResult::success(Vec::new(), 0);
+        result.affected_rows = affected_rows;
+        Ok(result)
+    }
+    
+    /// 执行访问控制命令；只有超级用户与不带用户ID的调用方可以修改访问控制
+    async fn execute_security_command(&self, command: SecurityCommand, context: &ExecutionContext) -> Result<ExecutionResult> {
+        if let Some(user) = self.restricted_user(context) {
+            return Err(Error::permission_denied(format!("user {} cannot change access control", user)));
+        }
+        command.apply(&self.catalog)?;
+        // 权限与策略变化后按用户缓存的结果可能已过期
+        self.cache.write().await.clear();
+        Ok(ExecutionResult::success(Vec::new(), 0))
+    }
+    
+    /// 执行SQL级的KILL QUERY / SHOW QUERIES；受访问控制约束的用户只能终止和列出自己的查询
+    fn execute_query_command(&self, command: QueryCommand, context: &ExecutionContext) -> Result<ExecutionResult> {
+        let user = self.restricted_user(context);
+        let owned = |query: &RunningQuery| user.map_or(true, |user| query.user_id.as_deref() == Some(user));
+        match command {
+            QueryCommand::Kill { query_id } => {
+                let running = self.running_queries().into_iter().find(|query| query.query_id == query_id)
+                    .ok_or_else(|| Error::not_found(format!("running query {}", query_id)))?;
+                if !owned(&running) {
+                    return Err(Error::permission_denied(format!("query {} belongs to another user", query_id)));
+                }
+                self.kill_query(&query_id)?;
+                let mut result = ExecutionResult::success(Vec::new(), 0);
+                result.affected_rows = 1;
+                Ok(result)
+            }
+            QueryCommand::ShowQueries => {
+                let optional = |value: &Option<String>| value.clone().map(Value::String).unwrap_or(Value::Null);
+                let rows = self.running_queries().into_iter().filter(|query| owned(query)).map(|query| {
+                    let mut row = HashMap::new();
+                    row.insert("query_id".to_string(), Value::String(query.query_id.clone()));
+                    row.insert("user_id".to_string(), optional(&query.user_id));
+                    row.insert("session_id".to_string(), optional(&query.session_id));
+                    row.insert("pool".to_string(), optional(&query.pool));
+                    row.insert("query".to_string(), Value::String(query.sql.clone()));
+                    row.insert("state".to_string(), Value::String(query.state().to_string()));
+                    row.insert("started_at".to_string(), Value::String(query.started_at.to_rfc3339()));
+                    row.insert("elapsed_ms".to_string(), Value::UInt64(query.elapsed().as_millis() as u64));
+                    row
+                }).collect();
+                Ok(ExecutionResult::success(rows, 0))
+            }
+        }
     }
     
     /// 获取查询统计信息
@@ -209,13 +1227,20 @@ impl QueryEngine {
         Ok(())
     }
     
-    /// 计算查询哈希
-    fn calculate_query_hash(&self, sql: &str) -> String {
+    /// 计算查询哈希（SQL文本与绑定参数）
+    fn calculate_query_hash(&self, sql: &str, parameters: &HashMap<String, Value>, user: Option<&str>) -> String {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
         
         let mut hasher = DefaultHasher::new();
         sql.hash(&mut hasher);
+        user.hash(&mut hasher);
+        let mut names: Vec<&String> = parameters.keys().collect();
+        names.sort();
+        for name in names {
+            name.hash(&mut hasher);
+            serde_json::to_string(&parameters[name]).unwrap_or_default().hash(&mut hasher);
+        }
         format!("{:x}", hasher.finish())
     }
     
@@ -224,12 +1249,34 @@ impl QueryEngine {
         &self.config
     }
     
-    /// 更新配置
+    /// 更新配置；准入配置变化时新查询使用新的资源池，已准入的查询不受影响
     pub fn update_config(&mut self, config: QueryEngineConfig) {
+        if config.admission != self.config.admission || config.max_concurrent_queries != self.config.max_concurrent_queries {
+            self.admission = Arc::new(AdmissionController::new(config.admission.clone(), config.max_concurrent_queries));
+        }
+        register_query_log_table(&self.catalog, &config.query_log);
+        self.query_log.set_config(config.query_log.clone());
         self.config = config;
     }
 }
 
+/// 事务中的语句失败后，事务只接受COMMIT（按回滚处理）与ROLLBACK
+fn fail_on_error(transaction: Option<&Transaction>, outcome: &Result<ExecutionResult>) {
+    if let (Some(transaction), Err(_)) = (transaction, outcome) {
+        transaction.fail();
+    }
+}
+
+/// 配置了持久化日志表且表不存在时创建
+fn register_query_log_table(catalog: &Catalog, config: &QueryLogConfig) {
+    if let Some(table) = &config.persist_table {
+        if !catalog.contains_table(table) {
+            // 并发创建时另一方已注册，忽略
+            let _ = catalog.register_table(QueryLogEntry::table_definition(table));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -294,4 +1341,1065 @@ mod tests {
         let plan = engine.explain_query("SELECT * FROM users WHERE id = 1").await.unwrap();
         assert!(plan.estimated_cost > 0.0);
     }
+
+    #[tokio::test]
+    async fn test_backend_selection() {
+        let memory_engine = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let engine = QueryEngine::new(Arc::new(memory_engine), QueryEngineConfig::default());
+        
+        let point = engine.parse_sql("SELECT * FROM users WHERE id = 1").unwrap();
+        assert_eq!(engine.select_backend(&point).unwrap(), ExecutionBackend::Native);
+        
+        let analytical = engine.parse_sql("SELECT user_id, SUM(amount) FROM orders GROUP BY user_id").unwrap();
+        let expected = if cfg!(feature = "datafusion") { ExecutionBackend::DataFusion } else { ExecutionBackend::Native };
+        assert_eq!(engine.select_backend(&analytical).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_catalog_table_query() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use fdc_core::types::Value;
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+        ]).with_primary_key("id")).unwrap();
+        
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Int64(7));
+        row.insert("symbol".to_string(), Value::String("AAPL".to_string()));
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &[row]).await.unwrap();
+        
+        let result = engine.execute_sql("SELECT symbol FROM trades WHERE id = 7").await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0].get("symbol"), Some(&Value::String("AAPL".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_stream_and_cursor_paging() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let config = QueryEngineConfig { stream_batch_size: 8, ..Default::default() };
+        let engine = QueryEngine::new(storage.clone(), config);
+        engine.catalog().register_table(TableDefinition::new("ticks", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("price", ColumnType::Float64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (0..50).map(|i| HashMap::from([
+            ("id".to_string(), Value::Int64(i)),
+            ("price".to_string(), Value::Float64(i as f64)),
+        ])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "ticks", &rows).await.unwrap();
+
+        // 分页扫描：每批不超过8行，过滤与LIMIT在流上生效
+        let mut stream = engine.execute_stream(
+            "SELECT id FROM ticks WHERE price >= 10 LIMIT 30",
+            ExecutionContext::new("stream-1".to_string()),
+        ).await.unwrap();
+        let mut total = 0;
+        while let Some(batch) = stream.next_batch().await {
+            let batch = batch.unwrap();
+            assert!(batch.rows.len() <= 8);
+            total += batch.rows.len();
+        }
+        assert_eq!(total, 30);
+        assert!(engine.execute_stream("DELETE FROM ticks", ExecutionContext::new("stream-2".to_string())).await.is_err());
+
+        // SQL游标按会话分页读取
+        let context = || ExecutionContext::new(uuid::Uuid::new_v4().to_string()).with_session_id("s1".to_string());
+        engine.execute_sql_with_context("DECLARE c1 CURSOR FOR SELECT id, price FROM ticks", context()).await.unwrap();
+        let page = engine.execute_sql_with_context("FETCH 20 FROM c1", context()).await.unwrap();
+        assert_eq!(page.rows.len(), 20);
+        let page = engine.execute_sql_with_context("FETCH ALL FROM c1", context()).await.unwrap();
+        assert_eq!(page.rows.len(), 30);
+        let page = engine.execute_sql_with_context("FETCH NEXT FROM c1", context()).await.unwrap();
+        assert!(page.rows.is_empty());
+        assert!(engine.execute_sql("FETCH NEXT FROM c1").await.is_err());
+        let closed = engine.execute_sql_with_context("CLOSE c1", context()).await.unwrap();
+        assert_eq!(closed.affected_rows, 1);
+        assert!(engine.cursors().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_user_defined_scalar_function() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use crate::udf::{RustScalarFunction, UdfSignature};
+
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("fills", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("price", ColumnType::Float64),
+            ColumnDefinition::new("qty", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (1..=4).map(|i| HashMap::from([
+            ("id".to_string(), Value::Int64(i)),
+            ("price".to_string(), Value::Float64(10.0)),
+            ("qty".to_string(), Value::Int64(i)),
+        ])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "fills", &rows).await.unwrap();
+
+        let signature = UdfSignature::new("notional", vec![ColumnType::Float64, ColumnType::Float64], ColumnType::Float64);
+        engine.functions().register(Arc::new(RustScalarFunction::new(signature, |args| {
+            Ok(match (&args[0], &args[1]) {
+                (Value::Float64(price), Value::Float64(qty)) => Value::Float64(price * qty),
+                _ => Value::Null,
+            })
+        })), false).unwrap();
+
+        let result = engine.execute_sql("SELECT id, notional(price, qty) AS value FROM fills WHERE notional(price, qty) > 15").await.unwrap();
+        assert_eq!(result.rows.len(), 3);
+        assert!(result.rows.iter().all(|row| row.len() == 2));
+        let result = engine.execute_sql("SELECT * FROM fills WHERE notional(price, qty) >= 40").await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0].len(), 3);
+
+        // 未配置插件注册表时无法创建WASM函数
+        assert!(engine.execute_sql("CREATE FUNCTION bs(DOUBLE) RETURNS DOUBLE LANGUAGE wasm AS 'pricing.bs'").await.is_err());
+        engine.execute_sql("DROP FUNCTION notional").await.unwrap();
+        assert!(engine.execute_sql("SELECT notional(price, qty) FROM fills").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_user_defined_aggregate_function() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use crate::expressions::value_as_f64;
+        use crate::udaf::RustAggregateFunction;
+        use crate::udf::UdfSignature;
+        use fdc_core::types::TimestampNs;
+
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("fills", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("ts", ColumnType::Timestamp),
+            ColumnDefinition::new("price", ColumnType::Float64),
+            ColumnDefinition::new("qty", ColumnType::Float64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (0..6).map(|i| HashMap::from([
+            ("id".to_string(), Value::Int64(i)),
+            ("symbol".to_string(), Value::String(if i % 2 == 0 { "AAPL" } else { "MSFT" }.to_string())),
+            ("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(i * 30_000_000_000))),
+            ("price".to_string(), Value::Float64(10.0 + i as f64)),
+            ("qty".to_string(), Value::Float64(1.0 + i as f64)),
+        ])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "fills", &rows).await.unwrap();
+
+        // 成交量加权均价：状态为[Σ价格×数量, Σ数量]
+        let pair = |notional: f64, volume: f64| Value::Array(vec![Value::Float64(notional), Value::Float64(volume)]);
+        let parts = |state: &Value| match state {
+            Value::Array(parts) => (value_as_f64(&parts[0]).unwrap_or(0.0), value_as_f64(&parts[1]).unwrap_or(0.0)),
+            _ => (0.0, 0.0),
+        };
+        let signature = UdfSignature::new("my_vwap", vec![ColumnType::Float64, ColumnType::Float64], ColumnType::Float64);
+        engine.functions().register_aggregate(Arc::new(RustAggregateFunction::new(
+            signature,
+            pair(0.0, 0.0),
+            move |state, input| {
+                let (notional, volume) = parts(&state);
+                let (price, qty) = parts(input);
+                Ok(pair(notional + price * qty, volume + qty))
+            },
+            move |a, b| {
+                let ((na, va), (nb, vb)) = (parts(&a), parts(&b));
+                Ok(pair(na + nb, va + vb))
+            },
+            move |state| {
+                let (notional, volume) = parts(&state);
+                Ok(if volume == 0.0 { Value::Null } else { Value::Float64(notional / volume) })
+            },
+        )), false).unwrap();
+        assert!(engine.functions().register_aggregate(engine.functions().get_aggregate("my_vwap").unwrap(), false).is_err());
+
+        let result = engine.execute_sql(
+            "SELECT symbol, my_vwap(price, qty) AS vwap FROM fills GROUP BY symbol HAVING my_vwap(price, qty) > 12 ORDER BY symbol",
+        ).await.unwrap();
+        assert_eq!(result.rows.len(), 2);
+        // AAPL: (10×1 + 12×3 + 14×5) / 9
+        assert_eq!(result.rows[0]["vwap"], Value::Float64(116.0 / 9.0));
+
+        let result = engine.execute_sql(
+            "SELECT id, my_vwap(price, qty) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) AS rolling FROM fills ORDER BY id",
+        ).await.unwrap();
+        assert_eq!(result.rows[1]["rolling"], Value::Float64((10.0 + 22.0) / 3.0));
+
+        let result = engine.execute_sql("SELECT ts, my_vwap(price, qty) AS vwap FROM fills SAMPLE BY 1m").await.unwrap();
+        assert_eq!(result.rows.len(), 3);
+        assert_eq!(result.rows[0]["vwap"], Value::Float64(32.0 / 3.0));
+
+        assert!(engine.execute_sql("SELECT my_vwap(price) FROM fills").await.is_err());
+        assert!(engine.execute_sql("CREATE AGGREGATE agg(DOUBLE) RETURNS DOUBLE LANGUAGE wasm AS 'stats.agg'").await.is_err());
+        engine.execute_sql("DROP AGGREGATE my_vwap").await.unwrap();
+        assert!(engine.execute_sql("DROP AGGREGATE my_vwap").await.is_err());
+        engine.execute_sql("DROP AGGREGATE IF EXISTS my_vwap").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_financial_aggregates() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use fdc_core::types::{Price, TimestampNs, Volume};
+
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("ts", ColumnType::Timestamp),
+            ColumnDefinition::new("price", ColumnType::Price),
+            ColumnDefinition::new("size", ColumnType::Volume),
+        ]).with_primary_key("id")).unwrap();
+        let price = |text: &str| Value::Price(Price::new(text.parse().unwrap()));
+        let trades: [(&str, i64, &str, u64); 4] = [("AAPL", 0, "100.10", 300), ("AAPL", 20, "100.30", 100), ("AAPL", 70, "100.00", 200), ("MSFT", 10, "50.00", 10)];
+        let rows: Vec<_> = trades.iter().enumerate().map(|(i, (symbol, seconds, px, size))| HashMap::from([
+            ("id".to_string(), Value::Int64(i as i64)),
+            ("symbol".to_string(), Value::String(symbol.to_string())),
+            ("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(seconds * 1_000_000_000))),
+            ("price".to_string(), price(px)),
+            ("size".to_string(), Value::Volume(Volume::new(*size))),
+        ])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &rows).await.unwrap();
+
+        let sql = "SELECT symbol, vwap(price, size) AS vwap, ohlc(price, ts) AS bar, last_by(price, ts) AS last \
+                   FROM trades GROUP BY symbol ORDER BY symbol";
+        let query = engine.parser.parse(sql).unwrap();
+        assert!(query.features.has_native_aggregate);
+        assert_eq!(engine.select_backend(&query).unwrap(), ExecutionBackend::Native);
+        let result = engine.execute_sql(sql).await.unwrap();
+        assert_eq!(result.rows.len(), 2);
+        // (100.10×300 + 100.30×100 + 100.00×200) / 600 = 100.1
+        assert_eq!(result.rows[0]["vwap"], price("100.1"));
+        assert_eq!(result.rows[0]["last"], price("100.00"));
+        let Value::Struct(bar) = &result.rows[0]["bar"] else { panic!("ohlc should return a struct") };
+        assert_eq!((&bar["high"], &bar["low"]), (&price("100.30"), &price("100.00")));
+        assert_eq!((&bar["open"], &bar["close"]), (&price("100.10"), &price("100.00")));
+
+        let result = engine.execute_sql(
+            "SELECT ts, twap(price, ts) AS twap, spread(price, price) AS spread FROM trades WHERE symbol = 'AAPL' SAMPLE BY 1m",
+        ).await.unwrap();
+        assert_eq!(result.rows.len(), 2);
+        // 100.10持续20秒，最后一笔100.30不计权重
+        assert_eq!(result.rows[0]["twap"], price("100.10"));
+        assert_eq!(result.rows[0]["spread"], price("0"));
+        assert!(engine.execute_sql("SELECT vwap(price) FROM trades").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_statistical_aggregates() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("returns", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("x", ColumnType::Float64),
+            ColumnDefinition::new("y", ColumnType::Float64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (1..=4).flat_map(|i| ["AAPL", "MSFT"].map(|symbol| (i, symbol))).enumerate()
+            .map(|(id, (i, symbol))| HashMap::from([
+                ("id".to_string(), Value::Int64(id as i64)),
+                ("symbol".to_string(), Value::String(symbol.to_string())),
+                ("x".to_string(), Value::Float64(i as f64)),
+                ("y".to_string(), Value::Float64(if symbol == "AAPL" { 2.0 * i as f64 } else { -(i as f64) })),
+            ]))
+            .collect();
+        engine.catalog().insert_rows(storage.as_ref(), "returns", &rows).await.unwrap();
+
+        let sql = "SELECT symbol, corr(y, x) AS corr, var_pop(x) AS var, percentile_cont(x, 0.5) AS median, \
+                   percentile_disc(0.5) WITHIN GROUP (ORDER BY x) AS disc, approx_count_distinct(y) AS distinct_y \
+                   FROM returns GROUP BY symbol ORDER BY symbol";
+        assert_eq!(engine.select_backend(&engine.parser.parse(sql).unwrap()).unwrap(), ExecutionBackend::Native);
+        let result = engine.execute_sql(sql).await.unwrap();
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.rows[0]["corr"], Value::Float64(1.0));
+        assert_eq!(result.rows[1]["corr"], Value::Float64(-1.0));
+        assert_eq!(result.rows[0]["var"], Value::Float64(1.25));
+        assert_eq!(result.rows[0]["median"], Value::Float64(2.5));
+        assert_eq!(result.rows[0]["disc"], Value::Float64(2.0));
+        assert_eq!(result.rows[0]["distinct_y"], Value::Int64(4));
+
+        let result = engine.execute_sql("SELECT histogram(x, 2) AS h, approx_percentile_cont(x, 1.0) AS p100 FROM returns").await.unwrap();
+        let Value::Array(bins) = &result.rows[0]["h"] else { panic!("histogram should return an array") };
+        assert_eq!(bins.len(), 2);
+        assert_eq!(result.rows[0]["p100"], Value::Float64(4.0));
+        assert!(engine.execute_sql("SELECT percentile_cont(x, 2) FROM returns").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_invalidated_by_writes() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+        ]).with_primary_key("id")).unwrap();
+        let trade = |id: i64, symbol: &str| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(id));
+            row.insert("symbol".to_string(), Value::String(symbol.to_string()));
+            row
+        };
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &[trade(1, "AAPL")]).await.unwrap();
+        
+        let sql = "SELECT symbol FROM trades";
+        assert_eq!(engine.execute_sql(sql).await.unwrap().rows.len(), 1);
+        assert_eq!(engine.execute_sql(sql).await.unwrap().rows.len(), 1);
+        assert_eq!(engine.get_cache_stats().await.unwrap().hits, 1);
+        
+        // 写入后不再返回过期结果
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &[trade(2, "MSFT")]).await.unwrap();
+        assert_eq!(engine.execute_sql(sql).await.unwrap().rows.len(), 2);
+        let stats = engine.get_cache_stats().await.unwrap();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.invalidations, 1);
+        
+        // NO_CACHE提示既不读也不写缓存
+        let hinted = "SELECT /*+ NO_CACHE */ symbol FROM trades";
+        engine.execute_sql(hinted).await.unwrap();
+        engine.execute_sql(hinted).await.unwrap();
+        assert_eq!(engine.get_cache_stats().await.unwrap().hits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_invalidated_by_rows_leaving_time_range() {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage, QueryEngineConfig::default());
+        engine.execute_sql("CREATE TABLE quotes (id BIGINT PRIMARY KEY, ts TIMESTAMP)").await.unwrap();
+        engine.execute_sql("INSERT INTO quotes VALUES (1, '2024-01-15 09:30:00'), (2, '2024-01-16 09:30:00')").await.unwrap();
+        let old_range = "SELECT id FROM quotes WHERE ts < '2024-01-16 00:00:00'";
+        let later = "SELECT id FROM quotes WHERE ts >= '2024-02-01 00:00:00'";
+        for sql in [old_range, later, old_range, later] {
+            engine.execute_sql(sql).await.unwrap();
+        }
+        assert_eq!(engine.get_cache_stats().await.unwrap().hits, 2);
+
+        // 行移出旧范围：按更新前的行失效旧范围上的缓存，无关范围上的缓存保留
+        engine.execute_sql("UPDATE quotes SET ts = '2024-01-17 09:30:00' WHERE id = 1").await.unwrap();
+        assert!(engine.execute_sql(old_range).await.unwrap().rows.is_empty());
+        engine.execute_sql(later).await.unwrap();
+        assert_eq!(engine.get_cache_stats().await.unwrap().hits, 3);
+
+        // 删除同样只失效被删除行所在的范围
+        engine.execute_sql(old_range).await.unwrap();
+        engine.execute_sql("DELETE FROM quotes WHERE id = 2").await.unwrap();
+        engine.execute_sql(old_range).await.unwrap();
+        engine.execute_sql(later).await.unwrap();
+        assert_eq!(engine.get_cache_stats().await.unwrap().hits, 6);
+    }
+
+    #[tokio::test]
+    async fn test_materialized_view_through_sql() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("qty", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        let trade = |id: i64, symbol: &str, qty: i64| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(id));
+            row.insert("symbol".to_string(), Value::String(symbol.to_string()));
+            row.insert("qty".to_string(), Value::Int64(qty));
+            row
+        };
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &[trade(1, "AAPL", 100)]).await.unwrap();
+        
+        let created = engine.execute_sql(
+            "CREATE MATERIALIZED VIEW volume_by_symbol WITH (refresh = 'incremental') AS SELECT symbol, SUM(qty) AS volume FROM trades GROUP BY symbol"
+        ).await.unwrap();
+        assert_eq!(created.affected_rows, 1);
+        let sql = "SELECT volume FROM volume_by_symbol WHERE symbol = 'AAPL'";
+        assert_eq!(engine.execute_sql(sql).await.unwrap().rows[0]["volume"], Value::Float64(100.0));
+        
+        // 目录写入与数据接入提交都会增量维护视图，并使视图上的缓存结果失效
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &[trade(2, "AAPL", 50)]).await.unwrap();
+        assert_eq!(engine.execute_sql(sql).await.unwrap().rows[0]["volume"], Value::Float64(150.0));
+        let mut deltas = engine.subscribe("volume_by_symbol").unwrap();
+        engine.apply_ingested_rows("trades", &[trade(3, "AAPL", 25), trade(4, "MSFT", 10)]).await.unwrap();
+        assert_eq!(engine.execute_sql(sql).await.unwrap().rows[0]["volume"], Value::Float64(175.0));
+        assert_eq!(deltas.try_recv().unwrap().upserted.len(), 2);
+        
+        engine.execute_sql("DROP MATERIALIZED VIEW volume_by_symbol").await.unwrap();
+        assert!(engine.catalog().get_table("volume_by_symbol").is_none());
+        assert!(engine.views().list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prepared_statements() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+        ]).with_primary_key("id")).unwrap();
+        
+        let rows: Vec<_> = [(1, "AAPL"), (2, "MSFT")].iter().map(|(id, symbol)| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(*id));
+            row.insert("symbol".to_string(), Value::String(symbol.to_string()));
+            row
+        }).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &rows).await.unwrap();
+        
+        // API：参数按列类型强制转换，同一SQL复用缓存的计划
+        let statement = engine.prepare("SELECT symbol FROM trades WHERE id = $1").await.unwrap();
+        assert_eq!(statement.parameters[0].data_type, Some(ColumnType::Int64));
+        let result = engine.execute_prepared(&statement, vec![Value::String("2".to_string())]).await.unwrap();
+        assert_eq!(result.rows[0].get("symbol"), Some(&Value::String("MSFT".to_string())));
+        assert!(Arc::ptr_eq(&statement, &engine.prepare("SELECT symbol FROM trades WHERE id = $1").await.unwrap()));
+        assert!(engine.execute_prepared(&statement, vec![]).await.is_err());
+        
+        // SQL：PREPARE / EXECUTE / DEALLOCATE
+        engine.execute_sql("PREPARE by_id (BIGINT) AS SELECT symbol FROM trades WHERE id = $1").await.unwrap();
+        let first = engine.execute_sql("EXECUTE by_id (1)").await.unwrap();
+        let second = engine.execute_sql("EXECUTE by_id (2)").await.unwrap();
+        assert_eq!(first.rows[0].get("symbol"), Some(&Value::String("AAPL".to_string())));
+        assert_eq!(second.rows[0].get("symbol"), Some(&Value::String("MSFT".to_string())));
+        assert_eq!(engine.prepared_statements().get("by_id").unwrap().execution_count(), 2);
+        
+        engine.execute_sql("DEALLOCATE by_id").await.unwrap();
+        assert!(engine.execute_sql("EXECUTE by_id (1)").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_analyze_and_cost_based_join() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use crate::planner::PlanNode;
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage.clone(), QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("fills", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("account", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        engine.catalog().register_table(TableDefinition::new("accounts", vec![
+            ColumnDefinition::new("account", ColumnType::Int64),
+            ColumnDefinition::new("desk", ColumnType::String),
+        ]).with_primary_key("account")).unwrap();
+        
+        let fills: Vec<_> = (0..200).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(i));
+            row.insert("account".to_string(), Value::Int64(i % 20));
+            row
+        }).collect();
+        let accounts: Vec<_> = (0..20).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("account".to_string(), Value::Int64(i));
+            row.insert("desk".to_string(), Value::String(if i < 2 { "rates" } else { "equities" }.to_string()));
+            row
+        }).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "fills", &fills).await.unwrap();
+        engine.catalog().insert_rows(storage.as_ref(), "accounts", &accounts).await.unwrap();
+        
+        let analyzed = engine.execute_sql("ANALYZE").await.unwrap();
+        assert_eq!(analyzed.rows.len(), 2);
+        assert_eq!(engine.catalog().statistics("fills").unwrap().row_count, 200);
+        
+        let sql = "SELECT f.id FROM accounts a JOIN fills f ON f.account = a.account WHERE a.desk = 'rates'";
+        let parsed = engine.parse_sql(sql).unwrap();
+        let optimized = engine.optimize(parsed).await.unwrap();
+        assert_eq!(optimized.join_order(), vec!["f".to_string(), "a".to_string()]);
+        assert!(optimized.stats.optimized_cost < optimized.stats.original_cost);
+        
+        let plan = engine.explain_query(sql).await.unwrap();
+        assert!(matches!(plan.root, PlanNode::Filter { .. }));
+        let join = &plan.children[0];
+        assert!(matches!(join.root, PlanNode::Join { .. }));
+        assert_eq!(join.estimated_rows, 20);
+        
+        let mut config = engine.config().clone();
+        config.execution_backend = ExecutionBackend::Native;
+        let mut engine = engine;
+        engine.update_config(config);
+        let result = engine.execute_sql(sql).await.unwrap();
+        assert_eq!(result.rows.len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_explain_analyze() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use crate::planner::PlanNode;
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let config = QueryEngineConfig { execution_backend: ExecutionBackend::Native, ..Default::default() };
+        let engine = QueryEngine::new(storage.clone(), config);
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("qty", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        let trades: Vec<_> = (0..30).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(i));
+            row.insert("symbol".to_string(), Value::String(["AAPL", "MSFT", "IBM"][i as usize % 3].to_string()));
+            row.insert("qty".to_string(), Value::Int64(i * 10));
+            row
+        }).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "trades", &trades).await.unwrap();
+        
+        let sql = "SELECT symbol, SUM(qty) AS total FROM trades WHERE qty >= 100 GROUP BY symbol";
+        let explained = engine.explain_analyze(sql).await.unwrap();
+        assert_eq!(explained.rows, 3);
+        assert_eq!(explained.backend, ExecutionBackend::Native);
+        
+        // Projection -> Aggregate -> Filter -> TableScan
+        let plan = &explained.plan;
+        assert!(matches!(plan.root, PlanNode::Projection { .. }));
+        let aggregate = &plan.children[0];
+        assert!(matches!(aggregate.root, PlanNode::Aggregate { .. }));
+        assert_eq!(aggregate.actual.as_ref().unwrap().rows, 3);
+        let filter = &aggregate.children[0];
+        assert_eq!(filter.actual.as_ref().unwrap().rows, 20);
+        let scan = filter.children[0].actual.as_ref().unwrap();
+        assert_eq!(scan.rows, 30);
+        assert!(scan.bytes_read > 0);
+        
+        let json = explained.to_json().unwrap();
+        assert_eq!(json["plan"]["children"][0]["actual"]["rows"], 3);
+        assert!(explained.to_text().contains("actual rows=30"));
+        
+        let result = engine.execute_sql(&format!("EXPLAIN ANALYZE {}", sql)).await.unwrap();
+        assert!(result.rows.len() >= 4);
+        assert!(matches!(&result.rows[0]["plan"], Value::String(line) if line.starts_with("Projection")));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_query_timeout_and_kill() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let config = QueryEngineConfig { execution_backend: ExecutionBackend::Native, ..Default::default() };
+        let engine = Arc::new(QueryEngine::new(storage.clone(), config));
+        engine.catalog().register_table(TableDefinition::new("ticks", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        let ticks: Vec<_> = (0..3000).map(|i| HashMap::from([("id".to_string(), Value::Int64(i))])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "ticks", &ticks).await.unwrap();
+        
+        // 没有输出行、需要O(n²)次比较的嵌套循环连接
+        let sql = "SELECT a.id FROM ticks a JOIN ticks b ON a.id < b.id AND a.id < 0";
+        
+        let context = ExecutionContext::new("slow".to_string()).with_timeout(Duration::from_millis(20));
+        let outcome = engine.execute_sql_with_context(sql, context).await;
+        assert!(matches!(outcome, Err(Error::Timeout { duration_ms: 20 })));
+        assert!(engine.running_queries().is_empty());
+        // 超时的查询计为失败，并发计数回落
+        let stats = engine.get_query_stats().await.unwrap();
+        assert_eq!(stats.concurrent_queries, 0);
+        assert_eq!(stats.failed_queries, 1);
+        
+        let background = engine.clone();
+        let running = tokio::spawn(async move {
+            let context = ExecutionContext::new("backtest".to_string()).with_user_id("quant".to_string());
+            background.execute_sql_with_context(sql, context).await
+        });
+        while engine.running_queries().is_empty() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        
+        let shown = engine.execute_sql("SHOW QUERIES").await.unwrap();
+        assert_eq!(shown.rows.len(), 1);
+        assert_eq!(shown.rows[0]["query_id"], Value::String("backtest".to_string()));
+        assert_eq!(shown.rows[0]["user_id"], Value::String("quant".to_string()));
+        assert_eq!(shown.rows[0]["state"], Value::String("running".to_string()));
+        
+        // 其他受约束的用户既看不到也不能终止该查询
+        let other = || ExecutionContext::new(uuid::Uuid::new_v4().to_string()).with_user_id("intern".to_string());
+        assert!(engine.execute_sql_with_context("SHOW QUERIES", other()).await.unwrap().rows.is_empty());
+        let denied = engine.execute_sql_with_context("KILL QUERY 'backtest'", other()).await;
+        assert!(matches!(denied, Err(Error::PermissionDenied { .. })));
+        let owner = ExecutionContext::new(uuid::Uuid::new_v4().to_string()).with_user_id("quant".to_string());
+        assert_eq!(engine.execute_sql_with_context("SHOW QUERIES", owner).await.unwrap().rows.len(), 1);
+        
+        engine.execute_sql("KILL QUERY 'backtest'").await.unwrap();
+        assert!(matches!(running.await.unwrap(), Err(Error::Cancelled { .. })));
+        assert!(engine.running_queries().is_empty());
+        assert!(engine.execute_sql("KILL QUERY 'backtest'").await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_resource_pools() {
+        use crate::admission::{MemoryPolicy, ResourcePool};
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let admission = AdmissionConfig::default()
+            .with_pool(ResourcePool::new("trading").with_priority(10))
+            .with_pool(ResourcePool::new("research").with_max_concurrency(1).with_max_queued(0).with_memory_limit(64 * 1024, MemoryPolicy::Fail))
+            .route_user("quant", "research")
+            .route_user("desk", "trading");
+        admission.validate().unwrap();
+        let config = QueryEngineConfig { execution_backend: ExecutionBackend::Native, admission, ..Default::default() };
+        let engine = Arc::new(QueryEngine::new(storage.clone(), config));
+        engine.catalog().register_table(TableDefinition::new("ticks", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        let ticks: Vec<_> = (0..3000).map(|i| HashMap::from([("id".to_string(), Value::Int64(i))])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "ticks", &ticks).await.unwrap();
+        let context = |id: &str, user: &str| ExecutionContext::new(id.to_string()).with_user_id(user.to_string());
+        
+        // research池的查询超出内存预算时失败，trading池不受限制
+        let scan = "SELECT id FROM ticks";
+        assert!(matches!(engine.execute_sql_with_context(scan, context("scan", "quant")).await, Err(Error::Memory { .. })));
+        assert_eq!(engine.execute_sql_with_context(scan, context("scan", "desk")).await.unwrap().rows.len(), 3000);
+        
+        let background = engine.clone();
+        let running = tokio::spawn(async move {
+            let sql = "SELECT a.id FROM ticks a JOIN ticks b ON a.id < b.id AND a.id < 0";
+            background.execute_sql_with_context(sql, context("backtest", "quant")).await
+        });
+        while engine.running_queries().is_empty() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let shown = engine.execute_sql("SHOW QUERIES").await.unwrap();
+        assert_eq!(shown.rows[0]["pool"], Value::String("research".to_string()));
+        
+        // research池已满且不允许排队，trading池的查询照常执行
+        let lookup = "SELECT id FROM ticks WHERE id = 7";
+        assert!(matches!(
+            engine.execute_sql_with_context(lookup, context("second", "quant")).await,
+            Err(Error::ResourceExhausted { .. })
+        ));
+        assert_eq!(engine.execute_sql_with_context(lookup, context("quote", "desk")).await.unwrap().rows.len(), 1);
+        
+        engine.execute_sql("KILL QUERY 'backtest'").await.unwrap();
+        assert!(running.await.unwrap().is_err());
+        let research = engine.admission().status().into_iter().find(|pool| pool.name == "research").unwrap();
+        assert_eq!((research.running, research.rejected), (0, 1));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_sharded_tables() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use crate::distributed::LocalShard;
+        use fdc_storage::ShardStrategy;
+        
+        let mut shards: Vec<Arc<dyn ShardExecutor>> = Vec::new();
+        for id in 0..4 {
+            shards.push(Arc::new(LocalShard::new(id, Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap()))));
+        }
+        let storage = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage, QueryEngineConfig::default())
+            .with_shards(Arc::new(ShardManager::new(4, ShardStrategy::Hash)), shards)
+            .unwrap();
+        let distributed = engine.distributed().unwrap();
+        distributed.create_table(TableDefinition::new("ticks", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("price", ColumnType::Float64),
+        ]).with_primary_key("id"), "symbol").await.unwrap();
+        let rows: Vec<_> = (0..100i64).map(|i| HashMap::from([
+            ("id".to_string(), Value::Int64(i)),
+            ("symbol".to_string(), Value::String(format!("S{}", i % 10))),
+            ("price".to_string(), Value::Float64(i as f64)),
+        ])).collect();
+        distributed.insert_rows("ticks", &rows).await.unwrap();
+        
+        let sql = "SELECT symbol, COUNT(*) AS n, SUM(price) AS total FROM ticks GROUP BY symbol ORDER BY total DESC LIMIT 2";
+        let result = engine.execute_sql(sql).await.unwrap();
+        assert_eq!(result.row_count(), 2);
+        assert_eq!(result.rows[0]["symbol"], Value::String("S9".to_string()));
+        assert_eq!(result.rows[0]["n"], Value::Int64(10));
+        assert_eq!(result.rows[0]["total"], Value::Float64(540.0));
+        
+        // 写入分片表后缓存的结果失效
+        distributed.insert_rows("ticks", &[HashMap::from([
+            ("id".to_string(), Value::Int64(100)),
+            ("symbol".to_string(), Value::String("S9".to_string())),
+            ("price".to_string(), Value::Float64(5.0)),
+        ])]).await.unwrap();
+        let result = engine.execute_sql(sql).await.unwrap();
+        assert_eq!(result.rows[0]["n"], Value::Int64(11));
+        
+        let plan = engine.explain_query(sql).await.unwrap();
+        assert!(plan.render().contains("Exchange to 4 shards"));
+    }
+    
+    #[tokio::test]
+    async fn test_system_tables() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use crate::system_tables::RustSystemTable;
+        use std::sync::atomic::{AtomicI64, Ordering};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage, QueryEngineConfig::default());
+        let counter = Arc::new(AtomicI64::new(0));
+        let scans = counter.clone();
+        let definition = TableDefinition::new("system.counter", vec![ColumnDefinition::new("value", ColumnType::Int64)]);
+        engine.system_tables().register("system.counter", Arc::new(RustSystemTable::new(definition, move || {
+            let value = scans.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(vec![HashMap::from([("value".to_string(), Value::Int64(value))])])
+        }))).unwrap();
+        
+        // 每次执行都重新扫描，不命中缓存
+        for expected in 1..=2 {
+            let result = engine.execute_sql("SELECT value FROM system.counter").await.unwrap();
+            assert_eq!(result.rows[0]["value"], Value::Int64(expected));
+        }
+        
+        let columns = engine.describe("SELECT * FROM system.counter").unwrap().unwrap();
+        assert_eq!(columns[0].name, "value");
+        assert_eq!(columns[0].data_type, Some(ColumnType::Int64));
+    }
+    
+    #[tokio::test]
+    async fn test_query_log() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let config = QueryEngineConfig {
+            enable_cache: false,
+            query_log: QueryLogConfig::default()
+                .with_slow_query_threshold(Duration::ZERO)
+                .with_persist_table("query_history"),
+            ..Default::default()
+        };
+        let engine = QueryEngine::new(storage.clone(), config);
+        engine.catalog().register_table(TableDefinition::new("orders", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("qty", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        let rows: Vec<_> = (1..=3).map(|i| HashMap::from([
+            ("id".to_string(), Value::Int64(i)),
+            ("qty".to_string(), Value::Int64(i * 10)),
+        ])).collect();
+        engine.catalog().insert_rows(storage.as_ref(), "orders", &rows).await.unwrap();
+        
+        let context = |id: &str| ExecutionContext::new(id.to_string())
+            .with_user_id("alice".to_string())
+            .with_session_id("s1".to_string());
+        engine.execute_sql_with_context("SELECT qty FROM orders WHERE id = 1", context("q1")).await.unwrap();
+        engine.execute_sql_with_context("select qty from orders where id = 2", context("q2")).await.unwrap();
+        assert!(engine.execute_sql_with_context("SELEC qty", context("q3")).await.is_err());
+        
+        let entries = engine.query_log().entries();
+        assert_eq!(entries.iter().map(|e| e.query_id.as_str()).collect::<Vec<_>>(), vec!["q1", "q2", "q3"]);
+        assert_eq!(entries[0].fingerprint, entries[1].fingerprint);
+        assert_eq!(entries[0].normalized_sql, "SELECT qty FROM orders WHERE id = ?");
+        assert!(entries[0].plan_hash.is_some());
+        assert_eq!(entries[0].plan_hash, entries[1].plan_hash);
+        assert_eq!(entries[0].rows, 1);
+        assert!(entries[2].error.is_some() && entries[2].plan_hash.is_none());
+        assert!(entries.iter().all(|e| e.slow && e.session_id.as_deref() == Some("s1")));
+        
+        let result = engine.execute_sql(
+            "SELECT query_id, rows FROM system.query_log WHERE user_id = 'alice' AND error IS NULL ORDER BY query_id",
+        ).await.unwrap();
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.rows[0]["query_id"], Value::String("q1".to_string()));
+        assert_eq!(result.rows[0]["rows"], Value::Int64(1));
+        
+        // 持久化日志表包含之前的全部记录
+        let result = engine.execute_sql("SELECT query_id FROM query_history WHERE user_id = 'alice'").await.unwrap();
+        assert_eq!(result.rows.len(), 3);
+        
+        // 受约束的用户只能读到自己的日志
+        let bob = |id: &str| ExecutionContext::new(id.to_string()).with_user_id("bob".to_string());
+        engine.execute_sql_with_context("SELECT qty FROM orders WHERE id = 3", bob("q4")).await.unwrap();
+        let result = engine.execute_sql_with_context("SELECT query_id FROM system.query_log", bob("q5")).await.unwrap();
+        assert_eq!(result.rows, vec![HashMap::from([("query_id".to_string(), Value::String("q4".to_string()))])]);
+        let result = engine.execute_sql_with_context("SELECT query_id FROM system.query_log", context("q6")).await.unwrap();
+        assert_eq!(result.rows.len(), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_autocommit_updates() {
+        use crate::transactions::SERIALIZATION_FAILURE;
+
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let config = QueryEngineConfig { enable_cache: false, ..Default::default() };
+        let engine = Arc::new(QueryEngine::new(storage, config));
+        engine.execute_sql("CREATE TABLE counters (id BIGINT PRIMARY KEY, qty BIGINT)").await.unwrap();
+        engine.execute_sql("INSERT INTO counters VALUES (1, 0)").await.unwrap();
+
+        // 并发的读-改-写语句提交时发现读到的行已变化则重做，不丢失更新
+        let tasks: Vec<_> = (0..8).map(|_| {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                for _ in 0..10 {
+                    engine.execute_sql("UPDATE counters SET qty = qty + 1 WHERE id = 1").await.unwrap();
+                }
+            })
+        }).collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let result = engine.execute_sql("SELECT qty FROM counters WHERE id = 1").await.unwrap();
+        assert_eq!(result.rows[0]["qty"], Value::Int64(80));
+
+        // 读已提交的显式事务在提交时报告冲突
+        let run = |session: &str, sql: &str| {
+            let context = ExecutionContext::new(uuid::Uuid::new_v4().to_string()).with_session_id(session.to_string());
+            engine.execute_sql_with_context(sql, context)
+        };
+        run("a", "BEGIN ISOLATION LEVEL READ COMMITTED").await.unwrap();
+        run("a", "UPDATE counters SET qty = qty + 1 WHERE id = 1").await.unwrap();
+        engine.execute_sql("UPDATE counters SET qty = qty + 100 WHERE id = 1").await.unwrap();
+        let error = run("a", "COMMIT").await.unwrap_err();
+        assert!(error.to_string().contains(SERIALIZATION_FAILURE));
+        let result = engine.execute_sql("SELECT qty FROM counters WHERE id = 1").await.unwrap();
+        assert_eq!(result.rows[0]["qty"], Value::Int64(180));
+    }
+
+    #[tokio::test]
+    async fn test_sql_transactions() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use crate::transactions::SERIALIZATION_FAILURE;
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage, QueryEngineConfig::default());
+        engine.catalog().register_table(TableDefinition::new("accounts", vec![
+            ColumnDefinition::new("id", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("balance", ColumnType::Int64).not_null(),
+        ]).with_primary_key("id")).unwrap();
+        engine.catalog().register_table(TableDefinition::new("fees", vec![
+            ColumnDefinition::new("id", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("amount", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        engine.execute_sql("INSERT INTO accounts (id, balance) VALUES (1, 100), (2, 50)").await.unwrap();
+        
+        let run = |session: &str, sql: &str| {
+            let context = ExecutionContext::new(uuid::Uuid::new_v4().to_string()).with_session_id(session.to_string());
+            engine.execute_sql_with_context(sql, context)
+        };
+        let balances = |result: ExecutionResult| -> Vec<Value> {
+            result.rows.into_iter().map(|mut row| row.remove("balance").unwrap()).collect()
+        };
+        
+        // 结算调整跨两张表，提交前其他会话看不到
+        run("s1", "BEGIN").await.unwrap();
+        assert_eq!(run("s1", "UPDATE accounts SET balance = balance - 30 WHERE id = 1").await.unwrap().affected_rows, 1);
+        run("s1", "INSERT INTO fees VALUES (1, 30)").await.unwrap();
+        assert_eq!(engine.transaction_status("s1"), TransactionStatus::Active);
+        assert_eq!(balances(run("s1", "SELECT balance FROM accounts WHERE id = 1").await.unwrap()), vec![Value::Int64(70)]);
+        assert_eq!(balances(run("s2", "SELECT balance FROM accounts WHERE id = 1").await.unwrap()), vec![Value::Int64(100)]);
+        assert_eq!(run("s1", "COMMIT").await.unwrap().affected_rows, 2);
+        assert_eq!(balances(run("s2", "SELECT balance FROM accounts WHERE id = 1").await.unwrap()), vec![Value::Int64(70)]);
+        assert_eq!(run("s2", "SELECT * FROM fees").await.unwrap().rows.len(), 1);
+        
+        // 快照隔离：快照之后的提交不可见，提交同一行时先提交者胜出
+        run("s2", "BEGIN ISOLATION LEVEL REPEATABLE READ").await.unwrap();
+        run("s1", "UPDATE accounts SET balance = 60 WHERE id = 2").await.unwrap();
+        let snapshot = balances(run("s2", "SELECT id, balance FROM accounts ORDER BY id").await.unwrap());
+        assert_eq!(snapshot, vec![Value::Int64(70), Value::Int64(50)]);
+        run("s2", "UPDATE accounts SET balance = balance + 1 WHERE id = 2").await.unwrap();
+        let error = run("s2", "COMMIT").await.unwrap_err();
+        assert!(error.to_string().contains(SERIALIZATION_FAILURE));
+        assert_eq!(engine.transaction_status("s2"), TransactionStatus::Idle);
+        assert_eq!(balances(run("s2", "SELECT balance FROM accounts WHERE id = 2").await.unwrap()), vec![Value::Int64(60)]);
+        
+        // 失败的语句使事务只接受ROLLBACK；COMMIT按回滚处理
+        run("s1", "BEGIN").await.unwrap();
+        run("s1", "DELETE FROM fees").await.unwrap();
+        assert!(run("s1", "INSERT INTO fees (id, missing) VALUES (2, 1)").await.is_err());
+        assert_eq!(engine.transaction_status("s1"), TransactionStatus::Failed);
+        assert!(run("s1", "SELECT * FROM fees").await.unwrap_err().to_string().contains(TRANSACTION_ABORTED));
+        assert_eq!(run("s1", "COMMIT").await.unwrap().affected_rows, 0);
+        assert_eq!(run("s1", "SELECT * FROM fees").await.unwrap().rows.len(), 1);
+        
+        // 保存点
+        run("s1", "BEGIN").await.unwrap();
+        run("s1", "INSERT INTO fees VALUES (4, 5)").await.unwrap();
+        run("s1", "SAVEPOINT before_cleanup").await.unwrap();
+        run("s1", "DELETE FROM fees").await.unwrap();
+        assert!(run("s1", "SELECT * FROM fees").await.unwrap().rows.is_empty());
+        run("s1", "ROLLBACK TO SAVEPOINT before_cleanup").await.unwrap();
+        run("s1", "COMMIT").await.unwrap();
+        assert_eq!(run("s1", "SELECT * FROM fees").await.unwrap().rows.len(), 2);
+        
+        // 没有会话的脚本自成事务作用域：出错或未提交时整体回滚
+        let results = engine.execute_script(
+            "BEGIN; UPDATE accounts SET balance = balance - 10 WHERE id = 1; INSERT INTO fees VALUES (5, 10); COMMIT;",
+            ExecutionContext::new("script-1".to_string()),
+        ).await.unwrap();
+        assert_eq!(results.iter().map(|r| r.affected_rows).collect::<Vec<_>>(), vec![0, 1, 1, 2]);
+        assert!(engine.execute_sql("BEGIN; UPDATE accounts SET balance = 0; INSERT INTO fees VALUES (6, 'x'); COMMIT").await.is_err());
+        assert!(engine.execute_sql("BEGIN; UPDATE accounts SET balance = 0").await.is_err());
+        assert!(engine.transactions().is_empty());
+        let result = engine.execute_sql("SELECT balance FROM accounts ORDER BY id").await.unwrap();
+        assert_eq!(balances(result), vec![Value::Int64(60), Value::Int64(60)]);
+        assert!(engine.execute_sql("BEGIN").await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_access_control() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let config = QueryEngineConfig { superusers: vec!["admin".to_string()], ..Default::default() };
+        let engine = QueryEngine::new(storage, config);
+        engine.catalog().register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("desk", ColumnType::String),
+            ColumnDefinition::new("account_id", ColumnType::String),
+            ColumnDefinition::new("qty", ColumnType::Int64),
+        ]).with_primary_key("id")).unwrap();
+        engine.execute_sql(
+            "INSERT INTO trades VALUES (1, 'rates', 'AC100001', 10), (2, 'fx', 'AC200002', 20), (3, 'rates', 'AC300003', 30)",
+        ).await.unwrap();
+        
+        let run = |user: &str, sql: &str| {
+            let context = ExecutionContext::new(uuid::Uuid::new_v4().to_string()).with_user_id(user.to_string());
+            engine.execute_sql_with_context(sql, context)
+        };
+        let column = |result: ExecutionResult, name: &str| -> Vec<Value> {
+            result.rows.into_iter().map(|mut row| row.remove(name).unwrap()).collect()
+        };
+        let ids = |values: &[i64]| values.iter().map(|id| Value::Int64(*id)).collect::<Vec<_>>();
+        
+        // 设置访问控制之前表对所有用户开放
+        assert_eq!(run("carol", "SELECT * FROM trades").await.unwrap().rows.len(), 3);
+        
+        // 只有超级用户可以修改访问控制
+        let error = run("alice", "GRANT SELECT ON trades TO alice").await.unwrap_err();
+        assert!(error.to_string().contains("Permission denied"));
+        for sql in [
+            "GRANT SELECT, UPDATE (qty) ON trades TO alice, bob",
+            "CREATE POLICY desk_rows ON trades USING (desk = current_user_desk())",
+            "ALTER TABLE trades ALTER COLUMN account_id SET MASK USING (mask_show_last(account_id, 4))",
+            "ALTER USER alice SET desk = 'rates'",
+            "ALTER USER bob SET desk = 'fx'",
+        ] {
+            run("admin", sql).await.unwrap();
+        }
+        
+        // 每个交易台只看到自己的行，账号被掩码；结果按用户缓存
+        let sql = "SELECT id, account_id FROM trades ORDER BY id";
+        let alice = run("alice", sql).await.unwrap();
+        assert_eq!(column(alice.clone(), "id"), ids(&[1, 3]));
+        assert_eq!(column(alice, "account_id")[0], Value::String("XXXX0001".to_string()));
+        assert_eq!(column(run("bob", sql).await.unwrap(), "id"), ids(&[2]));
+        assert_eq!(run("alice", "SELECT desk FROM trades GROUP BY desk").await.unwrap().rows.len(), 1);
+        // 掩码列上的条件按掩码后的值求值，不能探测原值
+        assert!(run("alice", "SELECT id FROM trades WHERE account_id = 'AC100001'").await.unwrap().rows.is_empty());
+        let error = run("carol", "SELECT id FROM trades").await.unwrap_err();
+        assert!(error.to_string().contains("SELECT on table trades"));
+        // 超级用户与不带用户的调用不受约束
+        assert_eq!(column(run("admin", sql).await.unwrap(), "account_id")[0], Value::String("AC100001".to_string()));
+        assert_eq!(engine.execute_sql("SELECT * FROM trades").await.unwrap().rows.len(), 3);
+        
+        // EXPLAIN显示注入的行策略条件
+        let plan = column(run("alice", "EXPLAIN SELECT * FROM trades").await.unwrap(), "plan");
+        assert!(plan.iter().any(|line| matches!(line, Value::String(line) if line.contains("policy: "))));
+        
+        // 写入：只能修改授权的列与可见的行，新行必须满足策略
+        assert_eq!(run("alice", "UPDATE trades SET qty = qty + 1").await.unwrap().affected_rows, 2);
+        assert!(run("alice", "UPDATE trades SET desk = 'fx' WHERE id = 1").await.is_err());
+        assert!(run("alice", "DELETE FROM trades WHERE id = 1").await.is_err());
+        run("admin", "GRANT INSERT ON trades TO alice").await.unwrap();
+        let error = run("alice", "INSERT INTO trades VALUES (4, 'fx', 'AC400004', 1)").await.unwrap_err();
+        assert!(error.to_string().contains("row-level security policy"));
+        run("alice", "INSERT INTO trades VALUES (4, 'rates', 'AC400004', 1)").await.unwrap();
+        let quantities = column(run("admin", "SELECT qty FROM trades ORDER BY id").await.unwrap(), "qty");
+        assert_eq!(quantities, ids(&[11, 20, 31, 1]));
+        
+        // 受约束的用户不能在受控表上建视图；删除表时移除其访问控制
+        let error = run("alice", "CREATE MATERIALIZED VIEW desk_qty AS SELECT desk, sum(qty) AS qty FROM trades GROUP BY desk")
+            .await.unwrap_err();
+        assert!(error.to_string().contains("Permission denied"));
+        engine.catalog().drop_table("trades").unwrap();
+        assert!(!engine.catalog().security().has_access_control());
+    }
+    
+    #[tokio::test]
+    async fn test_primary_key_lookup_coerces_literals() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage, QueryEngineConfig::default());
+        for (table, key_type) in [("levels", ColumnType::Price), ("ticks", ColumnType::Timestamp), ("lots", ColumnType::Volume)] {
+            engine.catalog().register_table(TableDefinition::new(table, vec![
+                ColumnDefinition::new("k", key_type).not_null(),
+                ColumnDefinition::new("tag", ColumnType::String),
+            ]).with_primary_key("k")).unwrap();
+        }
+        engine.execute_sql("INSERT INTO levels VALUES (9.75, 'a'), (10.5, 'b'), (11, 'c')").await.unwrap();
+        engine.execute_sql("INSERT INTO ticks VALUES ('2024-01-15 09:30:00', 'a'), ('2024-01-15 09:31:00', 'b')").await.unwrap();
+        engine.execute_sql("INSERT INTO lots VALUES (5, 'a'), (6, 'b')").await.unwrap();
+
+        // 字面量按主键列类型转换后点查，只读取一行
+        for (sql, tag) in [
+            ("SELECT tag FROM levels WHERE k = 10.5", "b"),
+            ("SELECT tag FROM levels WHERE k = 11", "c"),
+            ("SELECT tag FROM ticks WHERE k = '2024-01-15 09:31:00'", "b"),
+            ("SELECT tag FROM lots WHERE k = 5", "a"),
+        ] {
+            let result = engine.execute_sql(sql).await.unwrap();
+            assert_eq!(result.rows.len(), 1, "{}", sql);
+            assert_eq!(result.rows[0]["tag"], Value::String(tag.to_string()));
+            assert_eq!(result.stats.rows_scanned, 1, "{}", sql);
+        }
+        // 无法转换为主键类型时按常规扫描过滤
+        assert!(engine.execute_sql("SELECT tag FROM lots WHERE k = -1").await.unwrap().rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_tables() {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let engine = QueryEngine::new(storage, QueryEngineConfig::default());
+        engine.execute_sql(
+            "CREATE TABLE ticks (id BIGINT PRIMARY KEY, exchange TEXT, price DOUBLE, ts TIMESTAMP) PARTITION BY DAY(ts)",
+        ).await.unwrap();
+        engine.execute_sql(
+            "INSERT INTO ticks VALUES (1, 'NYSE', 10.0, '2024-01-15 09:30:00'), (2, 'NYSE', 11.0, '2024-01-15 16:00:00'), \
+             (3, 'LSE', 12.0, '2024-01-16 08:00:00'), (4, 'NYSE', 13.0, '2024-01-17 09:30:00')",
+        ).await.unwrap();
+        let ids = |result: ExecutionResult| -> Vec<Value> {
+            result.rows.into_iter().map(|mut row| row.remove("id").unwrap()).collect()
+        };
+        
+        // 分区在写入时自动创建，按年龄映射到存储层级
+        let partitions = engine.execute_sql(
+            "SELECT partition_name, tier FROM system.partitions WHERE table_name = 'ticks' ORDER BY partition_name",
+        ).await.unwrap();
+        let names: Vec<Value> = partitions.rows.iter().map(|row| row["partition_name"].clone()).collect();
+        assert_eq!(names, ["p20240115", "p20240116", "p20240117"].map(|name| Value::String(name.to_string())));
+        assert!(partitions.rows.iter().all(|row| row["tier"] == Value::String("L4-Cold".to_string())));
+        
+        // 时间范围之外的分区不扫描
+        let sql = "SELECT id FROM ticks WHERE ts >= '2024-01-16 00:00:00' AND ts < '2024-01-17 00:00:00'";
+        let result = engine.execute_sql(sql).await.unwrap();
+        assert_eq!(result.stats.rows_scanned, 1);
+        assert_eq!(ids(result), vec![Value::Int64(3)]);
+        let plan = engine.execute_sql(&format!("EXPLAIN {}", sql)).await.unwrap();
+        assert!(plan.rows.iter().any(|row| matches!(&row["plan"], Value::String(line) if line.contains("partitions: 1 of 3 (p20240116)"))));
+        
+        // 主键点查与跨分区的UPDATE
+        assert_eq!(ids(engine.execute_sql("SELECT id FROM ticks WHERE id = 4").await.unwrap()), vec![Value::Int64(4)]);
+        engine.execute_sql("UPDATE ticks SET ts = '2024-01-16 12:00:00' WHERE id = 4").await.unwrap();
+        let result = engine.execute_sql("SELECT id FROM ticks WHERE ts >= '2024-01-16 00:00:00' ORDER BY id").await.unwrap();
+        assert_eq!(ids(result), vec![Value::Int64(3), Value::Int64(4)]);
+        
+        // DROP PARTITION整段删除，DETACH PARTITION移到独立的表
+        let dropped = engine.execute_sql("ALTER TABLE ticks DROP PARTITION p20240115").await.unwrap();
+        assert_eq!(dropped.affected_rows, 2);
+        assert_eq!(engine.execute_sql("ALTER TABLE ticks DROP PARTITION IF EXISTS p20240115").await.unwrap().affected_rows, 0);
+        assert!(engine.execute_sql("ALTER TABLE ticks DROP PARTITION p20240115").await.is_err());
+        engine.execute_sql("ALTER TABLE ticks DETACH PARTITION p20240116 INTO ticks_archive").await.unwrap();
+        assert!(engine.execute_sql("SELECT id FROM ticks").await.unwrap().rows.is_empty());
+        let archived = engine.execute_sql("SELECT id FROM ticks_archive ORDER BY id").await.unwrap();
+        assert_eq!(ids(archived), vec![Value::Int64(3), Value::Int64(4)]);
+        
+        // 按交易所的列表分区
+        engine.execute_sql(
+            "CREATE TABLE quotes (id BIGINT, exchange TEXT, bid DOUBLE, PRIMARY KEY (id)) PARTITION BY LIST (exchange)",
+        ).await.unwrap();
+        engine.execute_sql("INSERT INTO quotes VALUES (1, 'NYSE', 1.0), (2, 'LSE', 2.0), (3, 'NYSE', 3.0)").await.unwrap();
+        let result = engine.execute_sql("SELECT id FROM quotes WHERE exchange = 'LSE'").await.unwrap();
+        assert_eq!(result.stats.rows_scanned, 1);
+        assert_eq!(ids(result), vec![Value::Int64(2)]);
+        assert!(engine.execute_sql("INSERT INTO quotes VALUES (4, NULL, 1.0)").await.is_err());
+    }
 }
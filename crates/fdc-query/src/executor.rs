@@ -1,10 +1,42 @@
 //! Query executor for executing optimized queries
 
-use crate::optimizer::OptimizedPlan;
-use fdc_core::{error::{Error, Result}, types::Value};
+use crate::{
+    admission::MemoryBudget,
+    cancellation::{CancellationToken, QueryRegistry, RunningQuery},
+    catalog::{encode_row, Catalog, TableDefinition},
+    cost::referenced_qualifiers,
+    expressions::{expr_output_name, value_as_timestamp, ExpressionEvaluator},
+    grouping::{collect_aggregates_with, group_rows, group_rows_spilling, AggregateCall, AggregateConfig},
+    joins::{estimate_row_size, partition_of, JoinConfig, JoinOperations, JoinPredicate, JoinSpec, JoinType, Row},
+    time_joins::{rewrite_asof_sql, time_of, AsofDirection, AsofJoinStream, AsofSpec},
+    optimizer::OptimizedPlan,
+    parser::QueryFeatures,
+    prepared::{CachedStatement, StatementCache},
+    profile::{OperatorKind, OperatorProfile},
+    sampling::{extract_sample_by, Alignment, SampleBy},
+    security::{Privilege, QuerySecurity},
+    sorts::{external_sort, resolve_order_by, sort_rows, top_n_rows, SortConfig, SortOrder},
+    streaming::BatchSender,
+    subqueries::{QueryScope, SubqueryBinding, SubqueryStage},
+    system_tables::SystemTables,
+    transactions::{IsolationLevel, Transaction, SERIALIZATION_FAILURE},
+    udf::FunctionRegistry,
+    windows::{apply_window_functions, collect_window_calls_with},
+};
+use fdc_core::{error::{Error, Result}, types::{TimestampNs, Value}};
 use fdc_storage::engine::StorageEngine;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sqlparser::{
+    ast::{
+        Assignment, AssignmentTarget, BinaryOperator, Expr, FromTable, GroupByExpr, Ident, Insert,
+        JoinConstraint, JoinOperator, ObjectName, Query, Select, SelectItem, SetExpr, Statement, TableFactor,
+        TableWithJoins,
+    },
+    dialect::GenericDialect,
+    parser::Parser,
+};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use async_trait::async_trait;
@@ -74,6 +106,9 @@ pub struct ExecutionStats {
     pub rows_sorted: u64,
     /// 聚合的行数
     pub rows_aggregated: u64,
+    /// 连接产生的行数
+    #[serde(default)]
+    pub rows_joined: u64,
     /// 使用的内存（字节）
     pub memory_used: u64,
     /// 磁盘I/O次数
@@ -82,6 +117,9 @@ pub struct ExecutionStats {
     pub cache_hits: u64,
     /// 缓存未命中次数
     pub cache_misses: u64,
+    /// 按执行顺序记录的算子剖析，仅在开启剖析时为Some
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operators: Option<Vec<OperatorProfile>>,
 }
 
 impl ExecutionStats {
@@ -103,6 +141,18 @@ impl ExecutionStats {
             0.0
         }
     }
+    
+    /// 是否在收集算子剖析
+    pub fn is_profiling(&self) -> bool {
+        self.operators.is_some()
+    }
+    
+    /// 记录一个算子的剖析；未开启剖析时不会调用`profile`
+    pub fn record_operator(&mut self, profile: impl FnOnce() -> OperatorProfile) {
+        if let Some(operators) = &mut self.operators {
+            operators.push(profile());
+        }
+    }
 }
 
 /// 执行上下文
@@ -122,6 +172,20 @@ pub struct ExecutionContext {
     pub max_rows: Option<usize>,
     /// 是否启用缓存
     pub enable_cache: bool,
+    /// 是否收集算子级剖析（EXPLAIN ANALYZE）
+    pub profile: bool,
+    /// 取消令牌，克隆的上下文共享同一令牌
+    pub cancellation: CancellationToken,
+    /// 显式指定的资源池，未指定时按会话、用户路由
+    pub resource_pool: Option<String>,
+    /// 内存预算，克隆的上下文共享同一计数
+    pub memory: MemoryBudget,
+    /// 所在的事务，读取该事务的视图，写入缓冲到事务中随COMMIT提交
+    pub transaction: Option<Arc<Transaction>>,
+    /// 会话的默认隔离级别，BEGIN未指定时使用；未设置时使用引擎配置
+    pub isolation: Option<IsolationLevel>,
+    /// 计划器为当前用户解析的行条件与列掩码，扫描受控表时施加
+    pub security: Option<Arc<QuerySecurity>>,
 }
 
 impl ExecutionContext {
@@ -135,6 +199,13 @@ impl ExecutionContext {
             timeout: Duration::from_secs(30),
             max_rows: Some(10000),
             enable_cache: true,
+            profile: false,
+            cancellation: CancellationToken::new(),
+            resource_pool: None,
+            memory: MemoryBudget::unlimited(),
+            transaction: None,
+            isolation: None,
+            security: None,
         }
     }
     
@@ -167,6 +238,48 @@ impl ExecutionContext {
         self.max_rows = Some(max_rows);
         self
     }
+    
+    /// 开启或关闭算子级剖析
+    pub fn with_profiling(mut self, profile: bool) -> Self {
+        self.profile = profile;
+        self
+    }
+    
+    /// 使用外部持有的取消令牌
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+    
+    /// 指定资源池
+    pub fn with_resource_pool(mut self, pool: String) -> Self {
+        self.resource_pool = Some(pool);
+        self
+    }
+    
+    /// 设置内存预算
+    pub fn with_memory_budget(mut self, memory: MemoryBudget) -> Self {
+        self.memory = memory;
+        self
+    }
+    
+    /// 在事务中执行
+    pub fn with_transaction(mut self, transaction: Arc<Transaction>) -> Self {
+        self.transaction = Some(transaction);
+        self
+    }
+    
+    /// 设置会话的默认隔离级别
+    pub fn with_isolation(mut self, isolation: IsolationLevel) -> Self {
+        self.isolation = Some(isolation);
+        self
+    }
+    
+    /// 施加访问控制
+    pub fn with_security(mut self, security: Arc<QuerySecurity>) -> Self {
+        self.security = Some(security);
+        self
+    }
 }
 
 /// 查询执行器特征
@@ -182,74 +295,1092 @@ pub trait QueryExecutor: Send + Sync {
     async fn get_stats(&self) -> Result<HashMap<String, u64>>;
 }
 
-/// 默认查询执行器
+/// 行及其已有序的列
+type SortedRows = (Vec<Row>, Option<String>);
+
+/// 原生SELECT管线的输入
+pub(crate) enum SelectInput {
+    /// 扫描并连接FROM中的关系
+    Scan,
+    /// 调用方已扫描并连接好的行（仍执行WHERE及之后的全部步骤）
+    Rows(Vec<Row>),
+    /// 已完成分组聚合的行（格式同`group_rows`的输出，从HAVING开始执行）
+    Grouped(Vec<Row>),
+}
+
+/// 默认查询执行器（原生执行路径）
+///
+/// 处理扫描、连接（含ASOF）、过滤、分组聚合、SAMPLE BY、窗口函数、投影和主键点查，
+/// 以及CTE（含递归CTE）、子查询和集合运算（见`subqueries`模块）。
 pub struct DefaultQueryExecutor {
     /// 存储引擎
     storage_engine: Arc<dyn StorageEngine>,
+    /// 表目录
+    catalog: Arc<Catalog>,
+    /// 连接执行配置
+    join_config: JoinConfig,
+    /// 排序执行配置
+    sort_config: SortConfig,
+    /// 分组聚合执行配置
+    aggregate_config: AggregateConfig,
+    /// 已解析语句缓存
+    statements: Arc<StatementCache>,
     /// 正在执行的查询
-    running_queries: Arc<dashmap::DashMap<String, Instant>>,
+    running_queries: Arc<QueryRegistry>,
+    /// 用户自定义函数
+    functions: Arc<FunctionRegistry>,
+    /// 虚拟系统表
+    system_tables: Arc<SystemTables>,
 }
 
 impl DefaultQueryExecutor {
     /// 创建新的查询执行器
     pub fn new(storage_engine: Arc<dyn StorageEngine>) -> Self {
+        Self::with_catalog(storage_engine, Arc::new(Catalog::new()))
+    }
+    
+    /// 使用共享表目录创建查询执行器
+    pub fn with_catalog(storage_engine: Arc<dyn StorageEngine>, catalog: Arc<Catalog>) -> Self {
         Self {
             storage_engine,
-            running_queries: Arc::new(dashmap::DashMap::new()),
+            catalog,
+            join_config: JoinConfig::default(),
+            sort_config: SortConfig::default(),
+            aggregate_config: AggregateConfig::default(),
+            statements: Arc::new(StatementCache::default()),
+            running_queries: Arc::new(QueryRegistry::new()),
+            functions: Arc::new(FunctionRegistry::new()),
+            system_tables: Arc::new(SystemTables::new()),
         }
     }
     
+    /// 设置连接执行配置（内存预算、落盘目录）
+    pub fn with_join_config(mut self, join_config: JoinConfig) -> Self {
+        self.join_config = join_config;
+        self
+    }
+    
+    /// 设置排序执行配置（内存预算、落盘目录）
+    pub fn with_sort_config(mut self, sort_config: SortConfig) -> Self {
+        self.sort_config = sort_config;
+        self
+    }
+    
+    /// 设置分组聚合执行配置（内存预算、落盘目录）
+    pub fn with_aggregate_config(mut self, aggregate_config: AggregateConfig) -> Self {
+        self.aggregate_config = aggregate_config;
+        self
+    }
+    
+    /// 设置可在SQL中调用的用户自定义函数
+    pub fn with_functions(mut self, functions: Arc<FunctionRegistry>) -> Self {
+        self.functions = functions;
+        self
+    }
+    
+    /// 获取用户自定义函数注册表
+    pub fn functions(&self) -> &Arc<FunctionRegistry> {
+        &self.functions
+    }
+    
+    /// 设置虚拟系统表注册表
+    pub fn with_system_tables(mut self, system_tables: Arc<SystemTables>) -> Self {
+        self.system_tables = system_tables;
+        self
+    }
+    
+    /// 获取表目录
+    pub fn catalog(&self) -> &Arc<Catalog> {
+        &self.catalog
+    }
+    
+    /// 按执行上下文创建表达式求值器
+    pub(crate) fn evaluator(&self, context: &ExecutionContext) -> ExpressionEvaluator {
+        ExpressionEvaluator::new()
+            .with_parameters(context.parameters.clone())
+            .with_cancellation(context.cancellation.clone())
+            .with_memory_budget(context.memory.clone())
+            .with_functions(self.functions.clone())
+            .with_transaction(context.transaction.clone())
+            .with_user(context.user_id.as_ref().map(|user| Arc::new(self.catalog.security().session_user(user))))
+            .with_security(context.security.clone())
+    }
+    
     /// 执行SELECT查询
     async fn execute_select(&self, plan: &OptimizedPlan, context: &ExecutionContext) -> Result<ExecutionResult> {
         let start_time = Instant::now();
         
-        // 记录查询开始
-        self.running_queries.insert(context.query_id.clone(), start_time);
+        let (mut rows, stats) = self.run_select(&plan.original_query.sql, &plan.join_order(), context, SelectInput::Scan).await?;
         
-        // 简化的SELECT执行逻辑
-        let mut rows = Vec::new();
-        let mut stats = ExecutionStats::default();
+        // 应用限制
+        if let Some(max_rows) = context.max_rows {
+            if rows.len() > max_rows {
+                rows.truncate(max_rows);
+            }
+        }
+        
+        let execution_time = start_time.elapsed().as_micros() as u64;
+        let mut result = ExecutionResult::success(rows, execution_time);
+        result.stats = stats;
         
-        // 模拟数据扫描
-        for table in &plan.original_query.tables {
-            // 扫描表数据（简化实现）
-            let scan_result = self.scan_table(table, &context.parameters).await?;
-            rows.extend(scan_result);
-            stats.rows_scanned += 100; // 模拟扫描100行
+        Ok(result)
+    }
+    
+    /// 用原生执行器执行SELECT并返回结果行（物化视图全量刷新使用）
+    pub async fn select_rows(&self, sql: &str, context: &ExecutionContext) -> Result<Vec<HashMap<String, Value>>> {
+        Ok(self.run_select(sql, &[], context, SelectInput::Scan).await?.0)
+    }
+    
+    /// 在调用方提供的输入上执行SELECT的其余步骤（分布式执行的汇集阶段使用）
+    pub(crate) async fn select_from(&self, sql: &str, input: SelectInput, context: &ExecutionContext) -> Result<Vec<Row>> {
+        Ok(self.run_select(sql, &[], context, input).await?.0)
+    }
+    
+    /// 以行批次流式执行SELECT，每批经有界通道交给消费者
+    ///
+    /// 单个目录表上结果行与输入行一一对应的查询按主键顺序分页扫描，每页过滤、投影后立即发送，
+    /// 内存中只保留一页；聚合、排序、连接等其余查询先完整执行再分批发送。
+    pub async fn stream_select(&self, sql: &str, context: &ExecutionContext, batch_size: usize, sender: &mut BatchSender) -> Result<()> {
+        let batch_size = batch_size.max(1);
+        let evaluator = self.evaluator(context);
+        let cached = self.cached_statement(sql)?;
+        if let Some((table, select, limit)) = self.pipelined_scan(&cached, &evaluator)? {
+            return self.stream_pages(&table, select, limit, &evaluator, batch_size, sender).await;
         }
         
+        let (rows, _) = self.run_select(sql, &[], context, SelectInput::Scan).await?;
+        sender.send_all(rows, batch_size).await;
+        Ok(())
+    }
+    
+    /// 判断语句能否按页流式扫描，可以时返回表名、SELECT与LIMIT
+    fn pipelined_scan<'a>(&self, cached: &'a CachedStatement, evaluator: &ExpressionEvaluator) -> Result<Option<(String, &'a Select, Option<usize>)>> {
+        let query = match &cached.statement {
+            Statement::Query(query) if cached.sample_by.is_none() => query,
+            _ => return Ok(None),
+        };
+        let features = QueryFeatures::from_statement(&cached.statement);
+        let select = match query.body.as_ref() {
+            SetExpr::Select(select) => select,
+            _ => return Ok(None),
+        };
+        if features.has_aggregate
+            || features.has_subquery
+            || features.has_cte
+            || select.distinct.is_some()
+            || select.having.is_some()
+            || query.order_by.is_some()
+            || query.offset.is_some()
+            || !matches!(&select.group_by, GroupByExpr::Expressions(exprs, _) if exprs.is_empty())
+        {
+            return Ok(None);
+        }
+        let sources = select.projection.iter().filter_map(select_item_expr);
+        let functions = Some(self.functions.as_ref());
+        if !collect_aggregates_with(sources.clone(), functions)?.is_empty()
+            || !collect_window_calls_with(sources, &select.named_window, functions)?.is_empty()
+        {
+            return Ok(None);
+        }
+        let table = match select.from.as_slice() {
+            [TableWithJoins { relation: TableFactor::Table { name, .. }, joins }] if joins.is_empty() => name.to_string(),
+            _ => return Ok(None),
+        };
+        let definition = match self.catalog.get_table(&table) {
+            // 事务中的读取需要合并本事务的写入，走常规路径
+            Some(_) if evaluator.transaction().is_some() => return Ok(None),
+            // 受控表的行策略与掩码在常规扫描中施加
+            Some(_) if evaluator.security().is_some_and(|security| security.table(&table).is_some()) => return Ok(None),
+            Some(definition) => definition,
+            None => return Ok(None),
+        };
+        // 主键点查由常规路径直接读取单行，分区剪枝也只在常规扫描中进行
+        if let (Some(primary_key), Some(selection)) = (&definition.primary_key, &select.selection) {
+            if point_lookup_key(selection, primary_key, evaluator)?.is_some() {
+                return Ok(None);
+            }
+        }
+        if let Some(selection) = &select.selection {
+            if self.catalog.prune_partitions(&definition, selection).is_some() {
+                return Ok(None);
+            }
+        }
+        let limit = match &query.limit {
+            Some(limit) => Some(
+                crate::expressions::value_as_i64(&evaluator.evaluate(limit, &HashMap::new())?)
+                    .filter(|v| *v >= 0)
+                    .map(|v| v as usize)
+                    .ok_or_else(|| Error::validation(format!("Invalid LIMIT value: {}", limit)))?,
+            ),
+            None => None,
+        };
+        Ok(Some((table, select, limit)))
+    }
+    
+    /// 按页扫描目录表，每页过滤、投影后发送；消费者关闭或达到LIMIT时停止
+    async fn stream_pages(
+        &self,
+        table: &str,
+        select: &Select,
+        limit: Option<usize>,
+        evaluator: &ExpressionEvaluator,
+        batch_size: usize,
+        sender: &mut BatchSender,
+    ) -> Result<()> {
+        let mut remaining = limit.unwrap_or(usize::MAX);
+        let mut after: Option<Vec<u8>> = None;
+        while remaining > 0 {
+            let page = self.catalog.scan_page(
+                self.storage_engine.as_ref(), table, after.as_deref(), batch_size, evaluator.cancellation(),
+            ).await?;
+            let exhausted = page.len() < batch_size;
+            after = page.last().map(|(key, _)| key.clone());
+            
+            let mut rows: Vec<Row> = page.into_iter().map(|(_, row)| row).collect();
+            let mut hidden = self.functions.precompute(&mut rows, select.selection.iter(), evaluator)?;
+            if let Some(selection) = &select.selection {
+                rows = filter_rows(rows, selection, evaluator)?;
+            }
+            rows.truncate(remaining);
+            hidden.extend(self.functions.precompute(&mut rows, select.projection.iter().filter_map(select_item_expr), evaluator)?);
+            let mut batch = rows.iter()
+                .map(|row| {
+                    evaluator.checkpoint()?;
+                    project_row(&select.projection, row, evaluator)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            strip_hidden_columns(&mut batch, &hidden, &select.projection);
+            remaining -= batch.len();
+            if !sender.send(batch).await || exhausted {
+                break;
+            }
+        }
+        Ok(())
+    }
+    
+    /// 取语句缓存中的解析结果，同一SQL文本（参数单独绑定）只解析一次
+    fn cached_statement(&self, sql: &str) -> Result<Arc<CachedStatement>> {
+        self.statements.get_or_parse(sql, |sql| {
+            let (sql, sample_by) = extract_sample_by(sql)?;
+            Ok(CachedStatement { statement: parse_statement(&sql)?, sample_by })
+        })
+    }
+    
+    /// 原生执行SELECT（扫描、连接、过滤、分组聚合、SAMPLE BY、投影）
+    ///
+    /// 含CTE、子查询或集合运算的查询交给`run_query`逐层求值。
+    async fn run_select(
+        &self,
+        sql: &str,
+        join_order: &[String],
+        context: &ExecutionContext,
+        input: SelectInput,
+    ) -> Result<(Vec<HashMap<String, Value>>, ExecutionStats)> {
+        let cached = self.cached_statement(sql)?;
+        let (statement, sample_by) = (&cached.statement, &cached.sample_by);
+        let query = match statement {
+            Statement::Query(query) => query,
+            _ => return Err(Error::validation("Expected a SELECT statement")),
+        };
+        let features = QueryFeatures::from_statement(statement);
+        if features.has_cte || features.has_subquery || features.has_set_operation {
+            if sample_by.is_some() || !matches!(input, SelectInput::Scan) {
+                return Err(Error::unimplemented("Subqueries, CTEs and set operations combined with SAMPLE BY or pre-scanned input"));
+            }
+            let (_, rows) = self.run_query(query, &QueryScope::default(), context).await?;
+            return Ok((rows, ExecutionStats::default()));
+        }
+        self.run_select_body(query, sample_by.as_ref(), join_order, context, input, &QueryScope::default(), &[]).await
+    }
+    
+    /// 对单个SELECT（查询体为`SetExpr::Select`）执行原生管线
+    ///
+    /// `scope`提供CTE与派生表的物化结果；`subqueries`是已改写为合成列的子查询表达式，
+    /// 分别在WHERE、HAVING和投影之前按行求值。
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn run_select_body(
+        &self,
+        query: &Query,
+        sample_by: Option<&SampleBy>,
+        join_order: &[String],
+        context: &ExecutionContext,
+        input: SelectInput,
+        scope: &QueryScope,
+        subqueries: &[SubqueryBinding],
+    ) -> Result<(Vec<HashMap<String, Value>>, ExecutionStats)> {
+        let features = QueryFeatures::from_query(query);
+        let select = match query.body.as_ref() {
+            SetExpr::Select(select) => select,
+            other => return Err(Error::unimplemented(format!("Query body not supported by the native backend: {}", other))),
+        };
+        if sample_by.is_some() && !subqueries.is_empty() {
+            return Err(Error::unimplemented("Subqueries combined with SAMPLE BY"));
+        }
+        
+        let evaluator = self.evaluator(context);
+        let mut stats = ExecutionStats {
+            operators: context.profile.then(Vec::new),
+            ..Default::default()
+        };
+        
+        let group_by = match &select.group_by {
+            GroupByExpr::Expressions(exprs, _) => exprs.clone(),
+            GroupByExpr::All(_) => return Err(Error::unimplemented("GROUP BY ALL")),
+        };
+        let aggregate_sources = select.projection.iter()
+            .filter_map(select_item_expr)
+            .chain(select.having.as_ref())
+            .chain(query.order_by.iter().flat_map(|o| o.exprs.iter().map(|e| &e.expr)))
+            // HAVING与投影中`x IN (子查询)`的操作数在分组后的行上求值，其中的聚合由本查询计算
+            .chain(subqueries.iter().filter(|b| b.stage != SubqueryStage::Where).filter_map(SubqueryBinding::operand));
+        let aggregates = collect_aggregates_with(aggregate_sources, Some(self.functions.as_ref()))?;
+        let window_sources = select.projection.iter()
+            .filter_map(select_item_expr)
+            .chain(query.order_by.iter().flat_map(|o| o.exprs.iter().map(|e| &e.expr)));
+        let window_calls = collect_window_calls_with(window_sources, &select.named_window, Some(self.functions.as_ref()))?;
+        
+        // 结果行与输入行一一对应时，产出max_rows行后即可停止扫描和过滤
+        let pipelined = !features.has_aggregate
+            && aggregates.is_empty()
+            && window_calls.is_empty()
+            && sample_by.is_none()
+            && select.distinct.is_none()
+            && select.having.is_none()
+            && query.order_by.is_none()
+            && query.offset.is_none();
+        let row_budget = context.max_rows.filter(|_| pipelined);
+        
+        // 扫描与连接
+        let (mut rows, grouped) = match input {
+            SelectInput::Scan => (match select.from.as_slice() {
+                [] => vec![HashMap::new()],
+                [from] if from.joins.is_empty() => {
+                    let scan_limit = row_budget.filter(|_| select.selection.is_none());
+                    self.scan_relation(&from.relation, select.selection.as_ref(), scan_limit, scope, &evaluator, &mut stats).await?.0
+                }
+                from => self.scan_joined(from, select.selection.as_ref(), join_order, scope, &evaluator, &mut stats).await?,
+            }, false),
+            SelectInput::Rows(rows) => (rows, false),
+            SelectInput::Grouped(rows) if sample_by.is_none() => (rows, true),
+            SelectInput::Grouped(_) => return Err(Error::unimplemented("Pre-aggregated input combined with SAMPLE BY")),
+        };
+        // 物化的输入计入查询的内存预算
+        evaluator.memory().reserve(rows.iter().map(estimate_row_size).sum())?;
+        
+        // 子查询与自定义函数按批预计算，过滤与投影时直接取值
+        let selection = select.selection.as_ref().filter(|_| !grouped);
+        let mut hidden = self.bind_subqueries(&mut rows, subqueries, SubqueryStage::Where, scope, context).await?;
+        hidden.extend(self.functions.precompute(&mut rows, selection, &evaluator)?);
+        
         // 应用过滤条件
-        if plan.original_query.sql.to_lowercase().contains("where") {
-            let filtered_rows = self.apply_filters(&rows, &plan.original_query.sql)?;
-            stats.rows_filtered = (rows.len() - filtered_rows.len()) as u64;
-            rows = filtered_rows;
+        if let Some(selection) = selection {
+            let started = Instant::now();
+            let mut evaluated = 0;
+            let mut filtered = Vec::with_capacity(rows.len());
+            for row in rows {
+                if row_budget.is_some_and(|budget| filtered.len() >= budget) {
+                    break;
+                }
+                evaluator.checkpoint()?;
+                evaluated += 1;
+                if evaluator.evaluate_predicate(selection, &row)? {
+                    filtered.push(row);
+                }
+            }
+            stats.rows_filtered = (evaluated - filtered.len()) as u64;
+            rows = filtered;
+            stats.record_operator(|| OperatorProfile::new(OperatorKind::Filter, selection.to_string(), started, &rows));
+        } else if let Some(budget) = row_budget {
+            rows.truncate(budget);
+        }
+        
+        // 分组聚合
+        if let Some(sample) = sample_by {
+            if !window_calls.is_empty() {
+                return Err(Error::unimplemented("Window functions combined with SAMPLE BY"));
+            }
+            let started = Instant::now();
+            let (rows, mut stats) = self.run_sample_by(sample, rows, select, query, &aggregates, &evaluator, stats)?;
+            stats.record_operator(|| OperatorProfile::new(OperatorKind::SampleBy, sample.interval_text.clone(), started, &rows));
+            return Ok((rows, stats));
+        }
+        
+        if features.has_aggregate || !aggregates.is_empty() {
+            let started = Instant::now();
+            let mut spills = 0;
+            if !grouped {
+                stats.rows_aggregated = rows.len() as u64;
+                let config = self.aggregate_config_for(&evaluator);
+                (rows, spills) = group_rows_spilling(rows, &group_by, &aggregates, &evaluator, &config)?;
+            }
+            hidden.extend(self.bind_subqueries(&mut rows, subqueries, SubqueryStage::Having, scope, context).await?);
+            if let Some(having) = &select.having {
+                rows = filter_rows(rows, having, &evaluator)?;
+            }
+            stats.record_operator(|| {
+                OperatorProfile::new(OperatorKind::Aggregate, join_exprs(&group_by), started, &rows).with_spills(spills as u64)
+            });
+        } else if let Some(having) = &select.having {
+            hidden.extend(self.bind_subqueries(&mut rows, subqueries, SubqueryStage::Having, scope, context).await?);
+            rows = filter_rows(rows, having, &evaluator)?;
+        }
+        
+        // 投影与排序中的子查询在窗口函数和排序之前求值
+        hidden.extend(self.bind_subqueries(&mut rows, subqueries, SubqueryStage::Projection, scope, context).await?);
+        
+        // 窗口函数（在分组与HAVING之后、排序与投影之前计算）
+        if !window_calls.is_empty() {
+            let started = Instant::now();
+            apply_window_functions(&mut rows, &window_calls, &evaluator)?;
+            stats.record_operator(|| {
+                let functions: Vec<String> = window_calls.iter().map(|call| call.key.clone()).collect();
+                OperatorProfile::new(OperatorKind::Window, functions.join(", "), started, &rows)
+            });
         }
         
         // 应用排序
-        if plan.original_query.sql.to_lowercase().contains("order by") {
-            rows = self.apply_sorting(rows)?;
+        if let Some(order_by) = &query.order_by {
+            let started = Instant::now();
+            evaluator.cancellation().check()?;
+            let keys = resolve_order_by(&order_by.exprs, &select.projection);
+            let orders: Vec<SortOrder> = order_by.exprs.iter().map(SortOrder::from_order_by).collect();
             stats.rows_sorted = rows.len() as u64;
+            // ORDER BY ... LIMIT只需保留前OFFSET+LIMIT行，用有界堆代替完整排序
+            let (offset, limit) = offset_limit(query, &evaluator)?;
+            let top_n = limit.map(|limit| limit.saturating_add(offset));
+            let mut spills = 0;
+            rows = match top_n {
+                Some(n) if n < rows.len() => top_n_rows(rows, &keys, &orders, &evaluator, n)?,
+                _ => {
+                    let config = self.sort_config_for(&evaluator);
+                    let (sorted, spilled) = external_sort(rows, &keys, &orders, &evaluator, &config, top_n)?;
+                    spills = spilled;
+                    sorted
+                }
+            };
+            stats.record_operator(|| {
+                let keys: Vec<String> = order_by.exprs.iter().map(|e| e.to_string()).collect();
+                OperatorProfile::new(OperatorKind::Sort, keys.join(", "), started, &rows).with_spills(spills as u64)
+            });
         }
         
-        // 应用限制
-        if let Some(max_rows) = context.max_rows {
-            if rows.len() > max_rows {
-                rows.truncate(max_rows);
+        // 应用OFFSET/LIMIT
+        if query.limit.is_some() || query.offset.is_some() {
+            let started = Instant::now();
+            rows = apply_offset_limit(rows, query, &evaluator)?;
+            stats.record_operator(|| OperatorProfile::new(OperatorKind::Limit, "", started, &rows));
+        }
+        
+        // 应用投影
+        let started = Instant::now();
+        hidden.extend(self.functions.precompute(&mut rows, select.projection.iter().filter_map(select_item_expr), &evaluator)?);
+        let mut rows = rows.iter()
+            .map(|row| {
+                evaluator.checkpoint()?;
+                project_row(&select.projection, row, &evaluator)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        strip_hidden_columns(&mut rows, &hidden, &select.projection);
+        stats.record_operator(|| {
+            let columns: Vec<String> = select.projection.iter().map(select_item_name).collect();
+            OperatorProfile::new(OperatorKind::Projection, columns.join(", "), started, &rows)
+        });
+        
+        Ok((rows, stats))
+    }
+    
+    /// 执行`SAMPLE BY`：按时间桶与其余非聚合列分组，投影后按FILL补齐空桶
+    #[allow(clippy::too_many_arguments)]
+    fn run_sample_by(
+        &self,
+        sample: &SampleBy,
+        mut rows: Vec<HashMap<String, Value>>,
+        select: &Select,
+        query: &Query,
+        aggregates: &[AggregateCall],
+        evaluator: &ExpressionEvaluator,
+        mut stats: ExecutionStats,
+    ) -> Result<(Vec<HashMap<String, Value>>, ExecutionStats)> {
+        let columns = column_names(&rows);
+        let time_item = select.projection.iter().position(|item| {
+            select_item_expr(item)
+                .and_then(column_ref)
+                .and_then(|name| find_column(&name, &columns))
+                .is_some_and(|column| rows.iter().any(|r| matches!(r.get(&column), Some(Value::Timestamp(_)))))
+        });
+        let time_column = match time_item {
+            Some(index) => select_item_expr(&select.projection[index]).and_then(column_ref).and_then(|n| find_column(&n, &columns)),
+            None => ["ts", "timestamp", "time"].iter().find_map(|n| find_column(n, &columns)),
+        };
+        let Some(time_column) = time_column else {
+            if rows.is_empty() {
+                return Ok((Vec::new(), stats));
             }
+            return Err(Error::validation("SAMPLE BY requires a timestamp column in the select list"));
+        };
+        
+        // 计算每行所属的桶
+        let origin = match sample.alignment {
+            Alignment::FirstObservation => rows.iter()
+                .filter_map(|r| r.get(&time_column).and_then(value_as_timestamp))
+                .map(|ts| ts.as_nanos())
+                .min(),
+            Alignment::Calendar { .. } => None,
+        };
+        let mut bucketed = Vec::with_capacity(rows.len());
+        for mut row in rows.drain(..) {
+            evaluator.checkpoint()?;
+            let Some(ts) = row.get(&time_column).and_then(value_as_timestamp) else { continue };
+            let bucket = sample.bucket(ts.as_nanos(), origin)?;
+            row.insert(BUCKET_COLUMN.to_string(), Value::Timestamp(TimestampNs::from_nanos(bucket)));
+            bucketed.push(row);
         }
         
-        // 移除查询记录
-        self.running_queries.remove(&context.query_id);
+        // 分组键：时间桶 + 其余不含聚合的投影列
+        let mut key_items = Vec::new();
+        let mut value_items = Vec::new();
+        for (index, item) in select.projection.iter().enumerate() {
+            let Some(expr) = select_item_expr(item) else {
+                return Err(Error::validation("SAMPLE BY does not support wildcard projections"));
+            };
+            if Some(index) == time_item {
+                continue;
+            }
+            if collect_aggregates_with([expr], Some(self.functions.as_ref()))?.is_empty() {
+                key_items.push(index);
+            } else {
+                value_items.push(index);
+            }
+        }
+        let mut group_by = vec![Expr::Identifier(Ident::new(BUCKET_COLUMN))];
+        group_by.extend(key_items.iter().filter_map(|&i| select_item_expr(&select.projection[i])).cloned());
         
-        let execution_time = start_time.elapsed().as_micros() as u64;
-        let mut result = ExecutionResult::success(rows, execution_time);
-        result.stats = stats;
+        stats.rows_aggregated = bucketed.len() as u64;
+        let mut rows = group_rows(bucketed, &group_by, aggregates, evaluator)?;
+        for row in &mut rows {
+            // 投影中的时间列取桶起点
+            if let Some(bucket) = row.get(BUCKET_COLUMN).cloned() {
+                row.insert(time_column.clone(), bucket);
+            }
+        }
+        if let Some(having) = &select.having {
+            rows = filter_rows(rows, having, evaluator)?;
+        }
         
-        Ok(result)
+        let output_name = |index: usize| select_item_name(&select.projection[index]);
+        let mut projected = rows.iter()
+            .map(|row| {
+                let mut output = project_row(&select.projection, row, evaluator)?;
+                output.insert(BUCKET_COLUMN.to_string(), row.get(BUCKET_COLUMN).cloned().unwrap_or(Value::Null));
+                Ok(output)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        
+        let key_columns: Vec<String> = key_items.iter().map(|&i| output_name(i)).collect();
+        let value_columns: Vec<String> = value_items.iter().map(|&i| output_name(i)).collect();
+        projected = sample.fill_rows(projected, BUCKET_COLUMN, &key_columns, &value_columns)?;
+        
+        if let Some(order_by) = &query.order_by {
+            // 投影后的行按输出列名排序，位置编号对应输出列
+            let keys: Vec<Expr> = order_by.exprs.iter().map(|item| match &item.expr {
+                Expr::Value(sqlparser::ast::Value::Number(n, _)) => n.parse::<usize>().ok()
+                    .and_then(|position| position.checked_sub(1))
+                    .filter(|&index| index < select.projection.len())
+                    .map(|index| Expr::Identifier(Ident::new(output_name(index))))
+                    .unwrap_or_else(|| item.expr.clone()),
+                other => other.clone(),
+            }).collect();
+            let orders: Vec<SortOrder> = order_by.exprs.iter().map(SortOrder::from_order_by).collect();
+            projected = sort_rows(projected, &keys, &orders, evaluator)?;
+        } else {
+            projected.sort_by_key(|row| row.get(BUCKET_COLUMN).and_then(value_as_timestamp));
+        }
+        stats.rows_sorted = projected.len() as u64;
+        
+        let time_output = time_item.map(output_name);
+        let mut projected = apply_offset_limit(projected, query, evaluator)?;
+        for row in &mut projected {
+            if let Some(bucket) = row.remove(BUCKET_COLUMN) {
+                if let Some(name) = &time_output {
+                    row.insert(name.clone(), bucket);
+                }
+            }
+        }
+        Ok((projected, stats))
+    }
+    
+    /// 扫描FROM中的关系，能走主键点查时直接读取单行
+    ///
+    /// 返回的第二项是行已按其有序的列（目录表全表扫描时为主键）。`limit`限制全表扫描读取的行数。
+    async fn scan_relation(
+        &self,
+        relation: &TableFactor,
+        selection: Option<&Expr>,
+        limit: Option<usize>,
+        scope: &QueryScope,
+        evaluator: &ExpressionEvaluator,
+        stats: &mut ExecutionStats,
+    ) -> Result<(Vec<HashMap<String, Value>>, Option<String>)> {
+        let table = match relation {
+            TableFactor::Table { name, .. } => name.to_string(),
+            // 派生表已由`run_query`按别名物化到作用域中
+            TableFactor::Derived { alias: Some(alias), .. } if scope.relation(&alias.name.value).is_some() => alias.name.value.clone(),
+            other => return Err(Error::unimplemented(format!("Relation not supported by the native backend: {}", other))),
+        };
+        
+        let started = Instant::now();
+        let cancellation = evaluator.cancellation();
+        let (rows, sorted_by) = match (scope.relation(&table), self.catalog.get_table(&table)) {
+            // CTE与派生表的物化结果优先于同名表
+            (Some(relation), _) => {
+                let mut rows = relation.rows.clone();
+                if let Some(limit) = limit {
+                    rows.truncate(limit);
+                }
+                (rows, None)
+            }
+            // 系统表每次扫描即时生成
+            (None, None) if self.system_tables.contains(&table) => {
+                let system = self.system_tables.get(&table);
+                let mut rows = system.as_ref().map(|system| system.scan()).transpose()?.unwrap_or_default();
+                let owner = evaluator.security().and_then(|security| security.owner());
+                if let (Some(owner), Some(column)) = (owner, system.as_ref().and_then(|system| system.owner_column())) {
+                    rows.retain(|row| matches!(row.get(column), Some(Value::String(user)) if user == owner));
+                }
+                if let Some(limit) = limit {
+                    rows.truncate(limit);
+                }
+                (rows, None)
+            }
+            (None, None) => {
+                let mut rows = cancellation.run(self.scan_table(&table)).await?;
+                if let Some(limit) = limit {
+                    rows.truncate(limit);
+                }
+                (rows, None)
+            }
+            (None, Some(definition)) => {
+                let access = evaluator.security().and_then(|security| security.table(&table));
+                // 掩码后主键值与存储中的键不同，不能点查，也不再按主键有序
+                let masked_key = match (&definition.primary_key, access) {
+                    (Some(primary_key), Some(access)) => access.masks_column(primary_key),
+                    _ => false,
+                };
+                // 键值按主键列类型转换后才与写入时的行键一致，无法转换时走常规扫描
+                let key = match (&definition.primary_key, selection) {
+                    (Some(primary_key), Some(selection)) if !masked_key => point_lookup_key(selection, primary_key, evaluator)?
+                        .and_then(|key| definition.coerce_key(&key)),
+                    _ => None,
+                };
+                // 行策略过滤后才能截断
+                let scan_limit = limit.filter(|_| access.map_or(true, |access| access.row_filter(Privilege::Select).is_none()));
+                // 只有键编码保序的非分区表，扫描结果才按主键有序
+                let primary_key = definition.key_order().map(str::to_string);
+                stats.disk_io_count += 1;
+                let storage = self.storage_engine.as_ref();
+                let (rows, sorted_by) = match (key, evaluator.transaction()) {
+                    (Some(key), None) => {
+                        let row = cancellation.run(self.catalog.lookup_row(storage, &table, &key)).await?;
+                        (row.into_iter().collect(), None)
+                    }
+                    (Some(key), Some(transaction)) => {
+                        let row = cancellation.run(self.catalog.lookup_row_in(storage, &table, &key, transaction)).await?;
+                        (row.into_iter().collect(), None)
+                    }
+                    (None, None) => {
+                        // 只扫描WHERE条件可能命中的分区
+                        let rows = match selection.and_then(|selection| self.catalog.prune_partitions(&definition, selection)) {
+                            Some(partitions) => {
+                                self.catalog.scan_partitions_with(storage, &table, &partitions, cancellation, scan_limit).await?
+                            }
+                            None => self.catalog.scan_rows_with(storage, &table, cancellation, scan_limit).await?,
+                        };
+                        (rows, primary_key)
+                    }
+                    // 事务视图需要完整的快照与本事务写入合并后才能截断
+                    (None, Some(transaction)) => {
+                        let entries = self.catalog.scan_rows_in(storage, &table, transaction, cancellation).await?;
+                        let rows = entries.into_iter().map(|(_, row)| row).take(scan_limit.unwrap_or(usize::MAX)).collect();
+                        (rows, primary_key)
+                    }
+                };
+                match access {
+                    // 行策略与列掩码在连接、过滤与聚合之前施加
+                    Some(access) => {
+                        let mut rows = access.secure_scan(rows, evaluator)?;
+                        if let Some(limit) = limit {
+                            rows.truncate(limit);
+                        }
+                        (rows, sorted_by.filter(|_| !masked_key))
+                    }
+                    None => (rows, sorted_by),
+                }
+            }
+        };
+        stats.rows_scanned += rows.len() as u64;
+        stats.record_operator(|| {
+            // 读取量按行的存储编码长度计算
+            let bytes_read: usize = rows.iter().map(|row| encode_row(row).map(|b| b.len()).unwrap_or(0)).sum();
+            OperatorProfile::new(OperatorKind::Scan, table.clone(), started, &rows).with_bytes_read(bytes_read as u64)
+        });
+        Ok((rows, sorted_by))
     }
     
-    /// 扫描表数据
-    async fn scan_table(&self, table: &str, _parameters: &HashMap<String, Value>) -> Result<Vec<HashMap<String, Value>>> {
+    /// 扫描并连接FROM中的多个关系，列名以表别名限定
+    async fn scan_joined(
+        &self,
+        from: &[TableWithJoins],
+        selection: Option<&Expr>,
+        join_order: &[String],
+        scope: &QueryScope,
+        evaluator: &ExpressionEvaluator,
+        stats: &mut ExecutionStats,
+    ) -> Result<Vec<HashMap<String, Value>>> {
+        if let [item] = from {
+            if let Some(rows) = self.scan_inner_joins(item, selection, join_order, scope, evaluator, stats).await? {
+                return Ok(rows);
+            }
+        }
+        
+        let mut result: Option<Vec<Row>> = None;
+        
+        for item in from {
+            let mut left = self.scan_qualified(&item.relation, scope, evaluator, stats).await?;
+            for join in &item.joins {
+                let right = self.scan_qualified(&join.relation, scope, evaluator, stats).await?;
+                let joined = self.join_relations(left, right, &join.join_operator, evaluator, stats)?;
+                left = (joined.0, None);
+            }
+            
+            // 逗号分隔的FROM项按笛卡尔积连接
+            let checkpoint = |_: &Row| evaluator.checkpoint().map(|_| true);
+            result = Some(match result {
+                None => left.0,
+                Some(acc) => JoinOperations::nested_loop_join(&acc, &left.0, JoinType::Cross, Some(&checkpoint))?,
+            });
+        }
+        
+        Ok(result.unwrap_or_default())
+    }
+    
+    /// 只含内连接的FROM项：单表谓词下推到扫描，并按优化器给出的顺序连接
+    ///
+    /// 连接条件中存在未限定的列时无法安全重排，返回None由调用方按书写顺序执行。
+    async fn scan_inner_joins(
+        &self,
+        item: &TableWithJoins,
+        selection: Option<&Expr>,
+        join_order: &[String],
+        scope: &QueryScope,
+        evaluator: &ExpressionEvaluator,
+        stats: &mut ExecutionStats,
+    ) -> Result<Option<Vec<Row>>> {
+        let mut conditions = Vec::new();
+        for join in &item.joins {
+            match &join.join_operator {
+                JoinOperator::Inner(JoinConstraint::On(on)) => conditions.extend(split_conjunction(on)),
+                _ => return Ok(None),
+            }
+        }
+        let factors: Vec<&TableFactor> = std::iter::once(&item.relation)
+            .chain(item.joins.iter().map(|j| &j.relation))
+            .collect();
+        let qualifiers = factors.iter()
+            .map(|f| relation_qualifier(f).map(|q| q.to_lowercase()))
+            .collect::<Result<Vec<_>>>()?;
+        
+        let mut pending = Vec::with_capacity(conditions.len());
+        for condition in conditions {
+            match referenced_qualifiers(condition) {
+                Some(referenced) if referenced.iter().all(|q| qualifiers.contains(q)) => pending.push((referenced, condition.clone())),
+                _ => return Ok(None),
+            }
+        }
+        let local: Vec<(String, &Expr)> = selection.map(split_conjunction).unwrap_or_default().into_iter()
+            .filter_map(|conjunct| {
+                let referenced = referenced_qualifiers(conjunct)?;
+                match referenced.len() {
+                    1 => referenced.into_iter().next().map(|q| (q, conjunct)),
+                    _ => None,
+                }
+            })
+            .collect();
+        
+        let mut order: Vec<usize> = join_order.iter()
+            .filter_map(|q| qualifiers.iter().position(|x| x.eq_ignore_ascii_case(q)))
+            .collect();
+        let mut check = order.clone();
+        check.sort_unstable();
+        check.dedup();
+        if check.len() != qualifiers.len() || order.len() != qualifiers.len() {
+            order = (0..qualifiers.len()).collect();
+        }
+        
+        let mut joined = BTreeSet::new();
+        let mut result: Option<(Vec<Row>, Option<String>)> = None;
+        for index in order {
+            let qualifier = &qualifiers[index];
+            let mut scanned = self.scan_qualified(factors[index], scope, evaluator, stats).await?;
+            for (_, predicate) in local.iter().filter(|(q, _)| q == qualifier) {
+                scanned.0 = filter_rows(scanned.0, predicate, evaluator)?;
+            }
+            joined.insert(qualifier.clone());
+            
+            result = Some(match result {
+                None => scanned,
+                Some(left) => {
+                    let (ready, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut pending).into_iter()
+                        .partition(|(referenced, _)| referenced.is_subset(&joined));
+                    pending = rest;
+                    let operator = match ready.into_iter().map(|(_, c)| c).reduce(|a, b| Expr::BinaryOp {
+                        left: Box::new(a),
+                        op: BinaryOperator::And,
+                        right: Box::new(b),
+                    }) {
+                        Some(on) => JoinOperator::Inner(JoinConstraint::On(on)),
+                        None => JoinOperator::CrossJoin,
+                    };
+                    self.join_relations(left, scanned, &operator, evaluator, stats)?
+                }
+            });
+        }
+        Ok(result.map(|(rows, _)| rows))
+    }
+    
+    /// 扫描关系并以别名限定列名
+    async fn scan_qualified(
+        &self,
+        relation: &TableFactor,
+        scope: &QueryScope,
+        evaluator: &ExpressionEvaluator,
+        stats: &mut ExecutionStats,
+    ) -> Result<(Vec<Row>, Option<String>)> {
+        let qualifier = relation_qualifier(relation)?;
+        let (rows, sorted_by) = self.scan_relation(relation, None, None, scope, evaluator, stats).await?;
+        Ok((
+            JoinOperations::qualify(rows, &qualifier),
+            sorted_by.map(|column| format!("{}.{}", qualifier, column)),
+        ))
+    }
+    
+    /// 按JOIN运算符连接两个已限定的输入
+    fn join_relations(
+        &self,
+        left: (Vec<Row>, Option<String>),
+        right: (Vec<Row>, Option<String>),
+        operator: &JoinOperator,
+        evaluator: &ExpressionEvaluator,
+        stats: &mut ExecutionStats,
+    ) -> Result<(Vec<Row>, Option<String>)> {
+        let started = Instant::now();
+        let build_bytes = if stats.is_profiling() {
+            right.0.iter().map(estimate_row_size).sum::<usize>()
+        } else {
+            0
+        };
+        let (joined, hashed) = self.join_inputs(left, right, operator, evaluator)?;
+        stats.rows_joined += joined.0.len() as u64;
+        stats.record_operator(|| {
+            let condition = match operator {
+                JoinOperator::Inner(JoinConstraint::On(on)) | JoinOperator::LeftOuter(JoinConstraint::On(on))
+                | JoinOperator::RightOuter(JoinConstraint::On(on)) | JoinOperator::FullOuter(JoinConstraint::On(on)) => on.to_string(),
+                JoinOperator::AsOf { match_condition, .. } => match_condition.to_string(),
+                _ => String::new(),
+            };
+            OperatorProfile::new(OperatorKind::Join, condition, started, &joined.0)
+                .with_memory_peak(build_bytes as u64)
+                .with_spills(if hashed { self.join_config_for(evaluator).spill_partitions_for(build_bytes) as u64 } else { 0 })
+        });
+        Ok(joined)
+    }
+    
+    /// 按等值连接键把两侧哈希分区后并行连接各分区（分布式执行的Shuffle连接）
+    ///
+    /// 同一键值的行落在同一分区，各分区独立连接的结果之并即为完整结果；
+    /// 没有等值键（交叉连接、ASOF或纯非等值条件）时在单个分区上连接。
+    pub(crate) fn shuffle_join(
+        &self,
+        left: Vec<Row>,
+        right: Vec<Row>,
+        operator: &JoinOperator,
+        evaluator: &ExpressionEvaluator,
+        partitions: usize,
+    ) -> Result<Vec<Row>> {
+        let constraint = match operator {
+            JoinOperator::Inner(c) | JoinOperator::LeftOuter(c) | JoinOperator::RightOuter(c)
+            | JoinOperator::FullOuter(c) | JoinOperator::LeftSemi(c) | JoinOperator::LeftAnti(c) => Some(c),
+            _ => None,
+        };
+        let condition = match constraint {
+            Some(constraint) if partitions > 1 => JoinCondition::analyze(Some(constraint), &left, &right)?,
+            _ => return Ok(self.join_inputs((left, None), (right, None), operator, evaluator)?.0.0),
+        };
+        if condition.left_keys.is_empty() {
+            return Ok(self.join_inputs((left, None), (right, None), operator, evaluator)?.0.0);
+        }
+        
+        let split = |rows: Vec<Row>, keys: &[String]| {
+            let mut parts = vec![Vec::new(); partitions];
+            for row in rows {
+                parts[partition_of(&row, keys, partitions)].push(row);
+            }
+            parts
+        };
+        let lefts = split(left, &condition.left_keys);
+        let rights = split(right, &condition.right_keys);
+        let parts = std::thread::scope(|scope| {
+            let handles: Vec<_> = lefts.into_iter().zip(rights)
+                .map(|(left, right)| scope.spawn(move || {
+                    self.join_inputs((left, None), (right, None), operator, evaluator).map(|((rows, _), _)| rows)
+                }))
+                .collect();
+            handles.into_iter()
+                .map(|handle| handle.join().map_err(|_| Error::internal("Join partition worker panicked"))?)
+                .collect::<Result<Vec<_>>>()
+        })?;
+        Ok(parts.concat())
+    }
+    
+    /// 查询使用的连接配置：构建侧在查询剩余的内存预算内保留在内存中，超出后落盘
+    fn join_config_for(&self, evaluator: &ExpressionEvaluator) -> JoinConfig {
+        let memory_budget = evaluator.memory().spill_threshold(self.join_config.memory_budget);
+        self.join_config.clone().with_memory_budget(memory_budget)
+    }
+    
+    /// 查询使用的排序配置：在查询剩余的内存预算内排序，超出后落盘归并
+    fn sort_config_for(&self, evaluator: &ExpressionEvaluator) -> SortConfig {
+        let memory_budget = evaluator.memory().spill_threshold(self.sort_config.memory_budget);
+        self.sort_config.clone().with_memory_budget(memory_budget)
+    }
+    
+    /// 查询使用的分组聚合配置：在查询剩余的内存预算内分组，超出后按键分区落盘
+    fn aggregate_config_for(&self, evaluator: &ExpressionEvaluator) -> AggregateConfig {
+        let memory_budget = evaluator.memory().spill_threshold(self.aggregate_config.memory_budget);
+        self.aggregate_config.clone().with_memory_budget(memory_budget)
+    }
+    
+    /// 执行连接，第二项表示是否使用了哈希连接（右侧为构建侧）
+    fn join_inputs(
+        &self,
+        left: (Vec<Row>, Option<String>),
+        right: (Vec<Row>, Option<String>),
+        operator: &JoinOperator,
+        evaluator: &ExpressionEvaluator,
+    ) -> Result<(SortedRows, bool)> {
+        let (join_type, constraint) = match operator {
+            JoinOperator::AsOf { match_condition, constraint } => {
+                return Ok((self.asof_join(left.0, right.0, match_condition, constraint, evaluator)?, false));
+            }
+            JoinOperator::Inner(c) => (JoinType::Inner, Some(c)),
+            JoinOperator::LeftOuter(c) => (JoinType::Left, Some(c)),
+            JoinOperator::RightOuter(c) => (JoinType::Right, Some(c)),
+            JoinOperator::FullOuter(c) => (JoinType::Full, Some(c)),
+            JoinOperator::LeftSemi(c) => (JoinType::LeftSemi, Some(c)),
+            JoinOperator::LeftAnti(c) => (JoinType::LeftAnti, Some(c)),
+            JoinOperator::CrossJoin => (JoinType::Cross, None),
+            other => return Err(Error::unimplemented(format!("Join operator not supported: {:?}", other))),
+        };
+        
+        let (left_rows, left_sorted) = left;
+        let (right_rows, right_sorted) = right;
+        let condition = JoinCondition::analyze(constraint, &left_rows, &right_rows)?;
+        
+        // 剩余谓词同时作为逐行的取消检查点
+        let residual_expr = condition.residual.clone();
+        let residual = move |row: &Row| {
+            evaluator.checkpoint()?;
+            match &residual_expr {
+                Some(expr) => evaluator.evaluate_predicate(expr, row),
+                None => Ok(true),
+            }
+        };
+        
+        let mut hashed = false;
+        let rows = if condition.left_keys.is_empty() {
+            JoinOperations::nested_loop_join(&left_rows, &right_rows, join_type, Some(&residual))?
+        } else {
+            let spec = JoinSpec::new(join_type, condition.left_keys.clone(), condition.right_keys.clone());
+            let residual: Option<&JoinPredicate<'_>> = Some(&residual);
+            let presorted = condition.left_keys.len() == 1
+                && left_sorted.as_deref() == Some(condition.left_keys[0].as_str())
+                && right_sorted.as_deref() == Some(condition.right_keys[0].as_str());
+            if presorted {
+                JoinOperations::sort_merge_join(&left_rows, &right_rows, &spec, true, residual)?
+            } else {
+                hashed = true;
+                JoinOperations::hash_join(left_rows, right_rows, &spec, &self.join_config_for(evaluator), residual)?
+            }
+        };
+        Ok(((rows, None), hashed))
+    }
+    
+    /// 执行`ASOF JOIN ... MATCH_CONDITION(...)`
+    ///
+    /// 匹配条件中第一个左右时间列之间的不等式决定匹配方向，`左 - 右 <= 常量`
+    /// 形式的条件作为容差，其余条件在选出的最近行上判断。
+    fn asof_join(
+        &self,
+        mut left: Vec<Row>,
+        mut right: Vec<Row>,
+        match_condition: &Expr,
+        constraint: &JoinConstraint,
+        evaluator: &ExpressionEvaluator,
+    ) -> Result<(Vec<Row>, Option<String>)> {
+        let left_columns = column_names(&left);
+        let right_columns = column_names(&right);
+        let by = JoinCondition::analyze(Some(constraint), &left, &right)?;
+        
+        let mut spec: Option<AsofSpec> = None;
+        let mut residual = Vec::new();
+        for conjunct in split_conjunction(match_condition) {
+            if spec.is_none() {
+                if let Some(found) = asof_time_condition(conjunct, &left_columns, &right_columns) {
+                    spec = Some(found);
+                    continue;
+                }
+            }
+            if let Some(spec) = spec.as_mut() {
+                if let Some(tolerance) = asof_tolerance(conjunct, spec, &left_columns, &right_columns, evaluator)? {
+                    spec.tolerance = Some(tolerance);
+                    continue;
+                }
+            }
+            residual.push(conjunct.clone());
+        }
+        residual.extend(by.residual);
+        
+        let mut spec = spec.ok_or_else(|| Error::validation(
+            "MATCH_CONDITION must compare a left and a right time column with >=, >, <= or <",
+        ))?;
+        spec.left_by = by.left_keys;
+        spec.right_by = by.right_keys;
+        
+        let residual = residual.into_iter().reduce(|a, b| Expr::BinaryOp {
+            left: Box::new(a),
+            op: BinaryOperator::And,
+            right: Box::new(b),
+        });
+        let predicate = |row: &Row| {
+            evaluator.checkpoint()?;
+            match &residual {
+                Some(expr) => evaluator.evaluate_predicate(expr, row),
+                None => Ok(true),
+            }
+        };
+        
+        // 归并要求两侧按时间有序
+        left.sort_by_key(|row| time_of(row, &spec.left_time));
+        right.sort_by_key(|row| time_of(row, &spec.right_time));
+        
+        let rows = AsofJoinStream::new(left.into_iter(), right.into_iter(), spec)?
+            .with_right_columns(right_columns)
+            .with_residual(&predicate)
+            .collect::<Result<Vec<_>>>()?;
+        Ok((rows, None))
+    }
+    
+    /// 扫描未注册表的演示数据
+    async fn scan_table(&self, table: &str) -> Result<Vec<HashMap<String, Value>>> {
         // 简化实现：返回模拟数据
         let mut rows = Vec::new();
         
@@ -280,104 +1411,648 @@ impl DefaultQueryExecutor {
         Ok(rows)
     }
     
-    /// 应用过滤条件
-    fn apply_filters(&self, rows: &[HashMap<String, Value>], _sql: &str) -> Result<Vec<HashMap<String, Value>>> {
-        // 简化实现：返回前一半数据（模拟过滤）
-        let filtered_count = rows.len() / 2;
-        Ok(rows.iter().take(filtered_count).cloned().collect())
-    }
-    
-    /// 应用排序
-    fn apply_sorting(&self, mut rows: Vec<HashMap<String, Value>>) -> Result<Vec<HashMap<String, Value>>> {
-        // 简化实现：按第一个字段排序
-        rows.sort_by(|a, b| {
-            if let (Some(a_val), Some(b_val)) = (a.values().next(), b.values().next()) {
-                match (a_val, b_val) {
-                    (Value::Int64(a), Value::Int64(b)) => a.cmp(b),
-                    (Value::String(a), Value::String(b)) => a.cmp(b),
-                    _ => std::cmp::Ordering::Equal,
-                }
-            } else {
-                std::cmp::Ordering::Equal
-            }
-        });
-        
-        Ok(rows)
-    }
-    
-    /// 执行INSERT查询
-    async fn execute_insert(&self, _plan: &OptimizedPlan, _context: &ExecutionContext) -> Result<ExecutionResult> {
+    /// 执行INSERT/UPDATE/DELETE
+    ///
+    /// 写入先缓冲在事务中：显式事务内随COMMIT一起提交，否则作为隐式事务在语句结束时提交。
+    /// 隐式事务提交时发现读到的行已被并发提交改动，则重新执行语句。
+    async fn execute_write(&self, plan: &OptimizedPlan, context: &ExecutionContext) -> Result<ExecutionResult> {
         let start_time = Instant::now();
+        let statement = parse_statement(&plan.original_query.sql)?;
+        let evaluator = self.evaluator(context);
         
-        // 简化的INSERT执行逻辑
-        let affected_rows = 1; // 模拟插入1行
+        let affected_rows = match &context.transaction {
+            Some(transaction) => self.write_statement(&statement, transaction, context, &evaluator).await?,
+            None => loop {
+                let transaction = Transaction::autocommit();
+                let affected_rows = self.write_statement(&statement, &transaction, context, &evaluator).await?;
+                match self.catalog.commit_transaction(self.storage_engine.as_ref(), &transaction).await {
+                    Ok(_) => break affected_rows,
+                    Err(Error::Query { message }) if message == SERIALIZATION_FAILURE => evaluator.checkpoint()?,
+                    Err(error) => return Err(error),
+                }
+            },
+        };
         
         let execution_time = start_time.elapsed().as_micros() as u64;
         let mut result = ExecutionResult::success(Vec::new(), execution_time);
         result.affected_rows = affected_rows;
-        
         Ok(result)
     }
     
-    /// 执行UPDATE查询
-    async fn execute_update(&self, _plan: &OptimizedPlan, _context: &ExecutionContext) -> Result<ExecutionResult> {
-        let start_time = Instant::now();
-        
-        // 简化的UPDATE执行逻辑
-        let affected_rows = 5; // 模拟更新5行
-        
-        let execution_time = start_time.elapsed().as_micros() as u64;
-        let mut result = ExecutionResult::success(Vec::new(), execution_time);
-        result.affected_rows = affected_rows;
-        
-        Ok(result)
+    /// 把一条写入语句缓冲到事务中，返回影响的行数
+    async fn write_statement(
+        &self,
+        statement: &Statement,
+        transaction: &Transaction,
+        context: &ExecutionContext,
+        evaluator: &ExpressionEvaluator,
+    ) -> Result<u64> {
+        Ok(match statement {
+            Statement::Insert(insert) => self.insert_into(insert, transaction, context, evaluator).await?,
+            Statement::Update { table, assignments, from, selection, returning, .. } => {
+                if from.is_some() || returning.is_some() {
+                    return Err(Error::unimplemented("UPDATE with FROM or RETURNING"));
+                }
+                let definition = self.writable_table(dml_target(table)?)?;
+                self.update_rows(&definition, assignments, selection.as_ref(), transaction, evaluator).await?
+            }
+            Statement::Delete(delete) => {
+                let from = match &delete.from {
+                    FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from) => from,
+                };
+                if !delete.tables.is_empty() || delete.using.is_some() || delete.returning.is_some() || delete.limit.is_some() {
+                    return Err(Error::unimplemented("Multi-table DELETE, DELETE with USING, RETURNING or LIMIT"));
+                }
+                let definition = match from.as_slice() {
+                    [item] => self.writable_table(dml_target(item)?)?,
+                    _ => return Err(Error::unimplemented("DELETE from multiple tables")),
+                };
+                self.delete_rows(&definition, delete.selection.as_ref(), transaction, evaluator).await?
+            }
+            _ => return Err(Error::validation("Expected an INSERT, UPDATE or DELETE statement")),
+        })
     }
     
-    /// 执行DELETE查询
-    async fn execute_delete(&self, _plan: &OptimizedPlan, _context: &ExecutionContext) -> Result<ExecutionResult> {
-        let start_time = Instant::now();
+    /// 写入语句的目标表，只接受目录中注册的表
+    fn writable_table(&self, name: &str) -> Result<TableDefinition> {
+        if self.system_tables.contains(name) {
+            return Err(Error::permission_denied(format!("system table {} is read-only", name)));
+        }
+        self.catalog.get_table(name).ok_or_else(|| Error::not_found(format!("table {}", name)))
+    }
+    
+    /// INSERT：VALUES列表或查询结果按列位置写入，未列出的列为NULL
+    async fn insert_into(
+        &self,
+        insert: &Insert,
+        transaction: &Transaction,
+        context: &ExecutionContext,
+        evaluator: &ExpressionEvaluator,
+    ) -> Result<u64> {
+        if insert.on.is_some() || insert.returning.is_some() {
+            return Err(Error::unimplemented("INSERT with ON CONFLICT or RETURNING"));
+        }
+        let definition = self.writable_table(&insert.table_name.to_string())?;
+        let columns = if insert.columns.is_empty() {
+            definition.column_names()
+        } else {
+            insert.columns.iter()
+                .map(|column| {
+                    definition.column(&column.value).map(|c| c.name.clone())
+                        .ok_or_else(|| Error::not_found(format!("column {}.{}", definition.name, column.value)))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        let access = evaluator.security().and_then(|security| security.table(&definition.name));
+        let source = insert.source.as_ref()
+            .ok_or_else(|| Error::unimplemented("INSERT without VALUES or a query"))?;
+        let tuples: Vec<Vec<Value>> = match source.body.as_ref() {
+            SetExpr::Values(values) => values.rows.iter()
+                .map(|exprs| exprs.iter().map(|expr| evaluator.evaluate(expr, &HashMap::new())).collect())
+                .collect::<Result<_>>()?,
+            _ => {
+                let (names, rows) = self.run_query(source, &QueryScope::default(), context).await?;
+                rows.into_iter()
+                    .map(|mut row| names.iter().map(|name| row.remove(name).unwrap_or(Value::Null)).collect())
+                    .collect()
+            }
+        };
         
-        // 简化的DELETE执行逻辑
-        let affected_rows = 3; // 模拟删除3行
+        for values in &tuples {
+            evaluator.checkpoint()?;
+            if values.len() != columns.len() {
+                return Err(Error::validation(format!(
+                    "INSERT into {} has {} values for {} columns", definition.name, values.len(), columns.len()
+                )));
+            }
+            let mut row: Row = definition.columns.iter().map(|column| (column.name.clone(), Value::Null)).collect();
+            for (column, value) in columns.iter().zip(values) {
+                row.insert(column.clone(), coerce_column(&definition, column, value.clone())?);
+            }
+            definition.validate_row(&row)?;
+            if let Some(access) = access {
+                access.check(&definition.name, Privilege::Insert, &row, evaluator)?;
+            }
+            transaction.put(&definition.name, definition.row_key(&row), row, true, None);
+        }
+        Ok(tuples.len() as u64)
+    }
+    
+    /// UPDATE：赋值表达式按更新前的行求值；主键或分区改变时行移到新键下
+    async fn update_rows(
+        &self,
+        definition: &TableDefinition,
+        assignments: &[Assignment],
+        selection: Option<&Expr>,
+        transaction: &Transaction,
+        evaluator: &ExpressionEvaluator,
+    ) -> Result<u64> {
+        let targets = assignments.iter()
+            .map(|assignment| match &assignment.target {
+                AssignmentTarget::ColumnName(name) => {
+                    let column = name.0.last().map(|ident| ident.value.as_str()).unwrap_or_default();
+                    definition.column(column).map(|c| (c.name.clone(), &assignment.value))
+                        .ok_or_else(|| Error::not_found(format!("column {}.{}", definition.name, column)))
+                }
+                AssignmentTarget::Tuple(_) => Err(Error::unimplemented("UPDATE with tuple assignment")),
+            })
+            .collect::<Result<Vec<_>>>()?;
         
-        let execution_time = start_time.elapsed().as_micros() as u64;
-        let mut result = ExecutionResult::success(Vec::new(), execution_time);
-        result.affected_rows = affected_rows;
+        let access = evaluator.security().and_then(|security| security.table(&definition.name));
+        let entries = self.catalog.scan_rows_in(self.storage_engine.as_ref(), &definition.name, transaction, evaluator.cancellation()).await?;
+        let mut updated = 0;
+        for (key, row) in entries {
+            evaluator.checkpoint()?;
+            let visible = match access {
+                // 只能更新行策略可见的行，WHERE与赋值表达式读取掩码后的值
+                Some(access) if !access.admits(Privilege::Update, &row, evaluator)? => continue,
+                Some(access) => Cow::Owned(access.masked(&row, evaluator)?),
+                None => Cow::Borrowed(&row),
+            };
+            if let Some(selection) = selection {
+                if !evaluator.evaluate_predicate(selection, &visible)? {
+                    continue;
+                }
+            }
+            let mut new_row = row.clone();
+            for (column, expr) in &targets {
+                let value = evaluator.evaluate(expr, &visible)?;
+                new_row.insert(column.clone(), coerce_column(definition, column, value)?);
+            }
+            definition.validate_row(&new_row)?;
+            if let Some(access) = access {
+                access.check(&definition.name, Privilege::Update, &new_row, evaluator)?;
+            }
+            let new_key = match &definition.primary_key {
+                Some(_) => definition.row_key(&new_row),
+                // 没有主键的分区表只在行换到其他分区时换键
+                None if definition.partition_name(&row) != definition.partition_name(&new_row) => definition.row_key(&new_row),
+                None => key.clone(),
+            };
+            if new_key != key {
+                transaction.delete(&definition.name, key, row);
+                transaction.put(&definition.name, new_key, new_row, false, None);
+            } else {
+                transaction.put(&definition.name, new_key, new_row, false, Some(row));
+            }
+            updated += 1;
+        }
+        Ok(updated)
+    }
+    
+    /// DELETE：删除满足条件的行，没有WHERE时删除全部行
+    async fn delete_rows(
+        &self,
+        definition: &TableDefinition,
+        selection: Option<&Expr>,
+        transaction: &Transaction,
+        evaluator: &ExpressionEvaluator,
+    ) -> Result<u64> {
+        let access = evaluator.security().and_then(|security| security.table(&definition.name));
+        let entries = self.catalog.scan_rows_in(self.storage_engine.as_ref(), &definition.name, transaction, evaluator.cancellation()).await?;
+        let mut deleted = 0;
+        for (key, row) in entries {
+            evaluator.checkpoint()?;
+            let visible = match access {
+                // 只能删除行策略可见的行，WHERE读取掩码后的值
+                Some(access) if !access.admits(Privilege::Delete, &row, evaluator)? => continue,
+                Some(access) => Cow::Owned(access.masked(&row, evaluator)?),
+                None => Cow::Borrowed(&row),
+            };
+            if let Some(selection) = selection {
+                if !evaluator.evaluate_predicate(selection, &visible)? {
+                    continue;
+                }
+            }
+            transaction.delete(&definition.name, key, row);
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+}
+
+/// UPDATE/DELETE目标：单个不带连接的表
+fn dml_target(item: &TableWithJoins) -> Result<&ObjectName> {
+    match &item.relation {
+        TableFactor::Table { name, .. } if item.joins.is_empty() => Ok(name),
+        other => Err(Error::unimplemented(format!("Write target not supported: {}", other))),
+    }
+}
+
+/// 按列类型转换写入的值
+fn coerce_column(definition: &TableDefinition, column: &str, value: Value) -> Result<Value> {
+    match definition.column(column) {
+        Some(column) => column.column_type.coerce(value),
+        None => Ok(value),
+    }
+}
+
+/// 解析单条SQL语句
+pub(crate) fn parse_statement(sql: &str) -> Result<Statement> {
+    let sql = rewrite_asof_sql(sql)?;
+    let mut statements = Parser::parse_sql(&GenericDialect {}, &sql)
+        .map_err(|e| Error::validation(format!("SQL parsing failed: {}", e)))?;
+    if statements.len() != 1 {
+        return Err(Error::validation("Expected exactly one SQL statement"));
+    }
+    Ok(statements.remove(0))
+}
+
+/// 从WHERE条件中提取`主键 = 常量`形式的点查键
+pub fn point_lookup_key(selection: &Expr, primary_key: &str, evaluator: &ExpressionEvaluator) -> Result<Option<Value>> {
+    match selection {
+        Expr::Nested(inner) => point_lookup_key(inner, primary_key, evaluator),
+        Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+            match point_lookup_key(left, primary_key, evaluator)? {
+                Some(key) => Ok(Some(key)),
+                None => point_lookup_key(right, primary_key, evaluator),
+            }
+        }
+        Expr::BinaryOp { left, op: BinaryOperator::Eq, right } => {
+            let is_key = |e: &Expr| match e {
+                Expr::Identifier(ident) => ident.value.eq_ignore_ascii_case(primary_key),
+                Expr::CompoundIdentifier(idents) => idents.last()
+                    .map(|i| i.value.eq_ignore_ascii_case(primary_key))
+                    .unwrap_or(false),
+                _ => false,
+            };
+            let is_constant = |e: &Expr| matches!(e, Expr::Value(_));
+            let empty = HashMap::new();
+            if is_key(left) && is_constant(right) {
+                Ok(Some(evaluator.evaluate(right, &empty)?))
+            } else if is_key(right) && is_constant(left) {
+                Ok(Some(evaluator.evaluate(left, &empty)?))
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 关系在连接中使用的列限定名（别名优先，否则为表名）
+pub(crate) fn relation_qualifier(relation: &TableFactor) -> Result<String> {
+    match relation {
+        TableFactor::Table { alias: Some(alias), .. } => Ok(alias.name.value.clone()),
+        TableFactor::Table { name, .. } => Ok(name.0.last().map(|i| i.value.clone()).unwrap_or_default()),
+        TableFactor::Derived { alias: Some(alias), .. } => Ok(alias.name.value.clone()),
+        other => Err(Error::unimplemented(format!("Relation not supported by the native backend: {}", other))),
+    }
+}
+
+/// 拆分后的连接条件：等值键与剩余谓词
+struct JoinCondition {
+    left_keys: Vec<String>,
+    right_keys: Vec<String>,
+    residual: Option<Expr>,
+}
+
+impl JoinCondition {
+    fn analyze(constraint: Option<&JoinConstraint>, left: &[Row], right: &[Row]) -> Result<Self> {
+        let left_columns = column_names(left);
+        let right_columns = column_names(right);
+        let mut condition = Self { left_keys: Vec::new(), right_keys: Vec::new(), residual: None };
         
-        Ok(result)
+        match constraint {
+            None | Some(JoinConstraint::None) => {}
+            Some(JoinConstraint::On(expr)) => {
+                let mut residual = Vec::new();
+                for conjunct in split_conjunction(expr) {
+                    match equi_key(conjunct, &left_columns, &right_columns) {
+                        Some((l, r)) => {
+                            condition.left_keys.push(l);
+                            condition.right_keys.push(r);
+                        }
+                        None => residual.push(conjunct.clone()),
+                    }
+                }
+                condition.residual = residual.into_iter().reduce(|a, b| Expr::BinaryOp {
+                    left: Box::new(a),
+                    op: BinaryOperator::And,
+                    right: Box::new(b),
+                });
+            }
+            Some(JoinConstraint::Using(idents)) => {
+                for ident in idents {
+                    let l = find_column(&ident.value, &left_columns);
+                    let r = find_column(&ident.value, &right_columns);
+                    match (l, r) {
+                        (Some(l), Some(r)) => {
+                            condition.left_keys.push(l);
+                            condition.right_keys.push(r);
+                        }
+                        _ => return Err(Error::validation(format!("USING column {} not found on both sides", ident.value))),
+                    }
+                }
+            }
+            Some(JoinConstraint::Natural) => {
+                let mut names: Vec<&str> = right_columns.iter()
+                    .map(|c| c.rsplit_once('.').map(|(_, n)| n).unwrap_or(c))
+                    .collect();
+                names.sort_unstable();
+                names.dedup();
+                for name in names {
+                    if let (Some(l), Some(r)) = (find_column(name, &left_columns), find_column(name, &right_columns)) {
+                        condition.left_keys.push(l);
+                        condition.right_keys.push(r);
+                    }
+                }
+            }
+        }
+        Ok(condition)
+    }
+}
+
+fn column_names(rows: &[Row]) -> Vec<String> {
+    let mut names: Vec<String> = rows.iter().flat_map(|r| r.keys().cloned()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// 拆分AND连接的谓词
+pub(crate) fn split_conjunction(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+            let mut parts = split_conjunction(left);
+            parts.extend(split_conjunction(right));
+            parts
+        }
+        Expr::Nested(inner) => split_conjunction(inner),
+        other => vec![other],
     }
 }
 
+/// 在一侧的列中查找列引用，返回行中的实际列名
+fn find_column(name: &str, columns: &[String]) -> Option<String> {
+    if let Some(column) = columns.iter().find(|c| c.eq_ignore_ascii_case(name)) {
+        return Some(column.clone());
+    }
+    if name.contains('.') {
+        return None;
+    }
+    let suffix = format!(".{}", name.to_lowercase());
+    let mut matches = columns.iter().filter(|c| c.to_lowercase().ends_with(&suffix));
+    match (matches.next(), matches.next()) {
+        (Some(column), None) => Some(column.clone()),
+        _ => None,
+    }
+}
+
+/// 列引用表达式的名称
+fn column_ref(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(ident) => Some(ident.value.clone()),
+        Expr::CompoundIdentifier(idents) => Some(idents.iter().map(|i| i.value.as_str()).collect::<Vec<_>>().join(".")),
+        Expr::Nested(inner) => column_ref(inner),
+        _ => None,
+    }
+}
+
+/// 识别ASOF匹配条件中左右时间列之间的不等式
+fn asof_time_condition(expr: &Expr, left: &[String], right: &[String]) -> Option<AsofSpec> {
+    let Expr::BinaryOp { left: a, op, right: b } = expr else {
+        return None;
+    };
+    let (a, b) = (column_ref(a)?, column_ref(b)?);
+    // 统一为`左 op 右`
+    let (left_time, right_time, op) = match (find_column(&a, left), find_column(&b, right)) {
+        (Some(l), Some(r)) => (l, r, op.clone()),
+        _ => {
+            let flipped = match op {
+                BinaryOperator::Gt => BinaryOperator::Lt,
+                BinaryOperator::GtEq => BinaryOperator::LtEq,
+                BinaryOperator::Lt => BinaryOperator::Gt,
+                BinaryOperator::LtEq => BinaryOperator::GtEq,
+                _ => return None,
+            };
+            (find_column(&b, left)?, find_column(&a, right)?, flipped)
+        }
+    };
+    let (direction, inclusive) = match op {
+        BinaryOperator::GtEq => (AsofDirection::Backward, true),
+        BinaryOperator::Gt => (AsofDirection::Backward, false),
+        BinaryOperator::LtEq => (AsofDirection::Forward, true),
+        BinaryOperator::Lt => (AsofDirection::Forward, false),
+        _ => return None,
+    };
+    Some(AsofSpec::new(left_time, right_time).with_direction(direction, inclusive))
+}
+
+/// 识别`新时间 - 旧时间 <= 常量`形式的ASOF容差
+fn asof_tolerance(
+    expr: &Expr,
+    spec: &AsofSpec,
+    left: &[String],
+    right: &[String],
+    evaluator: &ExpressionEvaluator,
+) -> Result<Option<i64>> {
+    let Expr::BinaryOp { left: diff, op: BinaryOperator::LtEq, right: bound } = expr else {
+        return Ok(None);
+    };
+    let Expr::BinaryOp { left: newer, op: BinaryOperator::Minus, right: older } = diff.as_ref() else {
+        return Ok(None);
+    };
+    let (Some(newer), Some(older)) = (column_ref(newer), column_ref(older)) else {
+        return Ok(None);
+    };
+    let (expected_newer, expected_older) = match spec.direction {
+        AsofDirection::Backward => (find_column(&newer, left), find_column(&older, right)),
+        AsofDirection::Forward => (find_column(&newer, right), find_column(&older, left)),
+    };
+    let times_match = match spec.direction {
+        AsofDirection::Backward => expected_newer.as_deref() == Some(spec.left_time.as_str())
+            && expected_older.as_deref() == Some(spec.right_time.as_str()),
+        AsofDirection::Forward => expected_newer.as_deref() == Some(spec.right_time.as_str())
+            && expected_older.as_deref() == Some(spec.left_time.as_str()),
+    };
+    if !times_match {
+        return Ok(None);
+    }
+    let bound = evaluator.evaluate(bound, &HashMap::new())?;
+    Ok(crate::expressions::value_as_i64(&bound))
+}
+
+/// 识别`左列 = 右列`形式的等值连接键
+fn equi_key(expr: &Expr, left: &[String], right: &[String]) -> Option<(String, String)> {
+    let Expr::BinaryOp { left: a, op: BinaryOperator::Eq, right: b } = expr else {
+        return None;
+    };
+    let (a, b) = (column_ref(a)?, column_ref(b)?);
+    match (find_column(&a, left), find_column(&b, right)) {
+        (Some(l), Some(r)) => Some((l, r)),
+        _ => match (find_column(&b, left), find_column(&a, right)) {
+            (Some(l), Some(r)) => Some((l, r)),
+            _ => None,
+        },
+    }
+}
+
+/// 对一行应用SELECT投影
+/// 去掉通配符投影带出的自定义函数预计算列（显式投影的同名列保留）
+fn strip_hidden_columns(rows: &mut [Row], hidden: &[String], projection: &[SelectItem]) {
+    let has_wildcard = projection.iter().any(|item| matches!(item, SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..)));
+    if hidden.is_empty() || !has_wildcard {
+        return;
+    }
+    let explicit: BTreeSet<String> = projection.iter().filter_map(|item| match item {
+        SelectItem::UnnamedExpr(expr) => Some(expr_output_name(expr)),
+        SelectItem::ExprWithAlias { alias, .. } => Some(alias.value.clone()),
+        _ => None,
+    }).collect();
+    for row in rows {
+        for key in hidden.iter().filter(|key| !explicit.contains(*key)) {
+            row.remove(key);
+        }
+    }
+}
+
+pub(crate) fn project_row(
+    projection: &[SelectItem],
+    row: &HashMap<String, Value>,
+    evaluator: &ExpressionEvaluator,
+) -> Result<HashMap<String, Value>> {
+    let mut output = HashMap::new();
+    for item in projection {
+        match item {
+            SelectItem::Wildcard(_) => {
+                // 连接结果中未冲突的列去掉表限定名
+                for (key, value) in row {
+                    let name = match key.rsplit_once('.') {
+                        Some((_, bare)) if resolves_uniquely(row, bare) => bare.to_string(),
+                        _ => key.clone(),
+                    };
+                    output.insert(name, value.clone());
+                }
+            }
+            SelectItem::QualifiedWildcard(name, _) => {
+                let prefix = format!("{}.", name);
+                let qualified: Vec<_> = row.iter().filter(|(k, _)| k.starts_with(&prefix)).collect();
+                if qualified.is_empty() {
+                    output.extend(row.iter().map(|(k, v)| (k.clone(), v.clone())));
+                } else {
+                    output.extend(qualified.into_iter().map(|(k, v)| (k[prefix.len()..].to_string(), v.clone())));
+                }
+            }
+            SelectItem::UnnamedExpr(expr) => {
+                let mut name = expr_output_name(expr);
+                if output.contains_key(&name) {
+                    name = expr.to_string();
+                }
+                output.insert(name, evaluator.evaluate(expr, row)?);
+            }
+            SelectItem::ExprWithAlias { expr, alias } => {
+                output.insert(alias.value.clone(), evaluator.evaluate(expr, row)?);
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// SAMPLE BY执行期间保存桶起点的内部列
+pub(crate) const BUCKET_COLUMN: &str = "__bucket";
+
+/// 投影项中的表达式（通配符返回None）
+pub(crate) fn select_item_expr(item: &SelectItem) -> Option<&Expr> {
+    match item {
+        SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => Some(expr),
+        _ => None,
+    }
+}
+
+/// 投影项的输出列名
+fn join_exprs(exprs: &[Expr]) -> String {
+    exprs.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+pub(crate) fn select_item_name(item: &SelectItem) -> String {
+    match item {
+        SelectItem::ExprWithAlias { alias, .. } => alias.value.clone(),
+        SelectItem::UnnamedExpr(expr) => expr_output_name(expr),
+        other => other.to_string(),
+    }
+}
+
+/// 按谓词过滤行
+fn filter_rows(rows: Vec<HashMap<String, Value>>, predicate: &Expr, evaluator: &ExpressionEvaluator) -> Result<Vec<HashMap<String, Value>>> {
+    let mut filtered = Vec::with_capacity(rows.len());
+    for row in rows {
+        evaluator.checkpoint()?;
+        if evaluator.evaluate_predicate(predicate, &row)? {
+            filtered.push(row);
+        }
+    }
+    Ok(filtered)
+}
+
+fn resolves_uniquely(row: &HashMap<String, Value>, bare: &str) -> bool {
+    let suffix = format!(".{}", bare);
+    row.keys().filter(|k| k.as_str() == bare || k.ends_with(&suffix)).count() == 1
+}
+
+/// 应用OFFSET和LIMIT子句
+pub(crate) fn apply_offset_limit(
+    rows: Vec<HashMap<String, Value>>,
+    query: &Query,
+    evaluator: &ExpressionEvaluator,
+) -> Result<Vec<HashMap<String, Value>>> {
+    let (offset, limit) = offset_limit(query, evaluator)?;
+    let rows = rows.into_iter().skip(offset);
+    Ok(match limit {
+        Some(limit) => rows.take(limit).collect(),
+        None => rows.collect(),
+    })
+}
+
+/// 求OFFSET与LIMIT的值（没有OFFSET时为0）
+pub(crate) fn offset_limit(query: &Query, evaluator: &ExpressionEvaluator) -> Result<(usize, Option<usize>)> {
+    let empty = HashMap::new();
+    let as_count = |expr: &Expr| -> Result<usize> {
+        crate::expressions::value_as_i64(&evaluator.evaluate(expr, &empty)?)
+            .filter(|v| *v >= 0)
+            .map(|v| v as usize)
+            .ok_or_else(|| Error::validation(format!("Invalid LIMIT/OFFSET value: {}", expr)))
+    };
+    
+    let offset = match &query.offset {
+        Some(offset) => as_count(&offset.value)?,
+        None => 0,
+    };
+    let limit = match &query.limit {
+        Some(limit) => Some(as_count(limit)?),
+        None => None,
+    };
+    Ok((offset, limit))
+}
+
 #[async_trait]
 impl QueryExecutor for DefaultQueryExecutor {
     async fn execute(&self, plan: OptimizedPlan, context: ExecutionContext) -> Result<ExecutionResult> {
-        // 检查超时
-        let start_time = Instant::now();
+        // 超时从执行开始计时，由算子在执行过程中检查；引擎已开始计时则沿用其截止时间
+        context.cancellation.start_deadline(context.timeout);
+        let _running = self.running_queries.register(RunningQuery::new(plan.original_query.sql.clone(), &context))?;
         
-        let result = match plan.original_query.query_type {
-            crate::parser::QueryType::Select => self.execute_select(&plan, &context).await,
-            crate::parser::QueryType::Insert => self.execute_insert(&plan, &context).await,
-            crate::parser::QueryType::Update => self.execute_update(&plan, &context).await,
-            crate::parser::QueryType::Delete => self.execute_delete(&plan, &context).await,
-            _ => Err(Error::unimplemented("Query type not supported")),
+        let execution = async {
+            match plan.original_query.query_type {
+                crate::parser::QueryType::Select => self.execute_select(&plan, &context).await,
+                crate::parser::QueryType::Insert
+                | crate::parser::QueryType::Update
+                | crate::parser::QueryType::Delete => self.execute_write(&plan, &context).await,
+                _ => Err(Error::unimplemented("Query type not supported")),
+            }
         };
-        
-        // 检查是否超时
-        if start_time.elapsed() > context.timeout {
-            return Ok(ExecutionResult::error("Query timeout".to_string()));
-        }
-        
-        result
+        context.cancellation.run(execution).await
     }
     
     async fn cancel(&self, query_id: &str) -> Result<()> {
-        self.running_queries.remove(query_id);
+        self.running_queries.cancel(query_id, format!("query {} cancelled", query_id));
         Ok(())
     }
     
     async fn get_stats(&self) -> Result<HashMap<String, u64>> {
         let mut stats = HashMap::new();
         stats.insert("running_queries".to_string(), self.running_queries.len() as u64);
+        stats.insert("cached_statements".to_string(), self.statements.len() as u64);
         Ok(stats)
     }
 }
@@ -421,6 +2096,185 @@ mod tests {
         assert!(result.is_success());
     }
 
+    #[tokio::test]
+    async fn test_native_point_lookup() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let catalog = Arc::new(Catalog::new());
+        catalog.register_table(TableDefinition::new("quotes", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("bid", ColumnType::Float64),
+        ]).with_primary_key("id")).unwrap();
+        
+        let rows: Vec<_> = (1..=5).map(|i| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Int64(i));
+            row.insert("bid".to_string(), Value::Float64(100.0 + i as f64));
+            row
+        }).collect();
+        catalog.insert_rows(storage.as_ref(), "quotes", &rows).await.unwrap();
+        
+        let executor = DefaultQueryExecutor::with_catalog(storage, catalog);
+        let query = ParsedQuery::new(QueryType::Select, "SELECT bid FROM quotes WHERE id = 3".to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await.unwrap();
+        
+        assert_eq!(result.row_count(), 1);
+        assert_eq!(result.rows[0].get("bid"), Some(&Value::Float64(103.0)));
+        assert_eq!(result.stats.rows_scanned, 1);
+        
+        let query = ParsedQuery::new(QueryType::Select, "SELECT id FROM quotes WHERE bid > 102 LIMIT 2".to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await.unwrap();
+        assert_eq!(result.row_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_native_join_with_aliases() {
+        let memory_engine = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let executor = DefaultQueryExecutor::new(Arc::new(memory_engine));
+        
+        let sql = "SELECT u.name, o.amount, o.id FROM users u JOIN orders o ON u.id = o.user_id WHERE o.amount > 1500";
+        let query = ParsedQuery::new(QueryType::Select, sql.to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await.unwrap();
+        assert_eq!(result.row_count(), 5);
+        assert!(result.rows.iter().all(|r| r.contains_key("name") && r.contains_key("id")));
+        
+        let sql = "SELECT * FROM users u LEFT JOIN orders o ON u.id = o.user_id AND o.amount > 1900";
+        let query = ParsedQuery::new(QueryType::Select, sql.to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await.unwrap();
+        assert_eq!(result.row_count(), 10);
+        assert_eq!(result.stats.rows_joined, 10);
+        assert!(result.rows[0].contains_key("u.id") && result.rows[0].contains_key("email"));
+    }
+
+    #[tokio::test]
+    async fn test_merge_join_on_float_key() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let catalog = Arc::new(Catalog::new());
+        for name in ["bids", "asks"] {
+            catalog.register_table(TableDefinition::new(name, vec![
+                ColumnDefinition::new("px", ColumnType::Float64).not_null(),
+                ColumnDefinition::new("qty", ColumnType::Int64),
+            ]).with_primary_key("px")).unwrap();
+        }
+        let rows = |prices: &[f64]| -> Vec<HashMap<String, Value>> {
+            prices.iter().map(|px| HashMap::from([
+                ("px".to_string(), Value::Float64(*px)),
+                ("qty".to_string(), Value::Int64(*px as i64)),
+            ])).collect()
+        };
+        catalog.insert_rows(storage.as_ref(), "bids", &rows(&[-1.5, 9.0, 10.5, 100.25])).await.unwrap();
+        catalog.insert_rows(storage.as_ref(), "asks", &rows(&[-1.5, 2.0, 10.5, 100.25])).await.unwrap();
+        
+        let executor = DefaultQueryExecutor::with_catalog(storage, catalog);
+        let sql = "SELECT b.px FROM bids b JOIN asks a ON b.px = a.px";
+        let query = ParsedQuery::new(QueryType::Select, sql.to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await.unwrap();
+        let mut prices: Vec<f64> = result.rows.iter()
+            .map(|row| match row.get("px") {
+                Some(Value::Float64(px)) => *px,
+                other => panic!("unexpected px {:?}", other),
+            })
+            .collect();
+        prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(prices, vec![-1.5, 10.5, 100.25]);
+    }
+
+    #[tokio::test]
+    async fn test_native_asof_join() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        use fdc_core::types::TimestampNs;
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let catalog = Arc::new(Catalog::new());
+        for name in ["trades", "quotes"] {
+            catalog.register_table(TableDefinition::new(name, vec![
+                ColumnDefinition::new("ts", ColumnType::Timestamp),
+                ColumnDefinition::new("symbol", ColumnType::String),
+                ColumnDefinition::new("px", ColumnType::Float64),
+            ])).unwrap();
+        }
+        let row = |ts: i64, symbol: &str, px: f64| {
+            let mut row = HashMap::new();
+            row.insert("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(ts * 1_000_000_000)));
+            row.insert("symbol".to_string(), Value::String(symbol.to_string()));
+            row.insert("px".to_string(), Value::Float64(px));
+            row
+        };
+        catalog.insert_rows(storage.as_ref(), "quotes", &[row(1, "AAPL", 1.0), row(2, "MSFT", 2.0), row(3, "AAPL", 3.0)]).await.unwrap();
+        catalog.insert_rows(storage.as_ref(), "trades", &[row(2, "AAPL", 10.0), row(9, "AAPL", 11.0)]).await.unwrap();
+        
+        let executor = DefaultQueryExecutor::with_catalog(storage, catalog);
+        let sql = "SELECT t.px AS trade_px, q.px AS quote_px FROM trades t \
+                   ASOF JOIN quotes q MATCH_CONDITION(t.ts >= q.ts) BY symbol TOLERANCE '5s'";
+        let query = ParsedQuery::new(QueryType::Select, sql.to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await.unwrap();
+        
+        assert_eq!(result.row_count(), 2);
+        let quote_for = |trade_px: f64| result.rows.iter()
+            .find(|r| r.get("trade_px") == Some(&Value::Float64(trade_px)))
+            .and_then(|r| r.get("quote_px").cloned());
+        assert_eq!(quote_for(10.0), Some(Value::Float64(1.0)));
+        assert_eq!(quote_for(11.0), Some(Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_native_sample_by() {
+        use crate::catalog::{ColumnDefinition, ColumnType, TableDefinition};
+        
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let catalog = Arc::new(Catalog::new());
+        catalog.register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("ts", ColumnType::Timestamp),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("px", ColumnType::Float64),
+        ])).unwrap();
+        let row = |seconds: i64, symbol: &str, px: f64| HashMap::from([
+            ("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(seconds * 1_000_000_000))),
+            ("symbol".to_string(), Value::String(symbol.to_string())),
+            ("px".to_string(), Value::Float64(px)),
+        ]);
+        catalog.insert_rows(storage.as_ref(), "trades", &[
+            row(5, "AAPL", 1.0), row(50, "AAPL", 3.0), row(130, "AAPL", 5.0), row(10, "MSFT", 7.0),
+        ]).await.unwrap();
+        
+        let executor = DefaultQueryExecutor::with_catalog(storage, catalog);
+        let sql = "SELECT ts, avg(px) AS px, count(*) AS n FROM trades WHERE symbol = 'AAPL' SAMPLE BY 1m FILL(PREV, 0)";
+        let query = ParsedQuery::new(QueryType::Select, sql.to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await.unwrap();
+        
+        let minute = |m: i64| Value::Timestamp(TimestampNs::from_nanos(m * 60_000_000_000));
+        let summary: Vec<_> = result.rows.iter()
+            .map(|r| (r["ts"].clone(), r["px"].clone(), r["n"].clone()))
+            .collect();
+        assert_eq!(summary, vec![
+            (minute(0), Value::Float64(2.0), Value::Int64(2)),
+            (minute(1), Value::Float64(2.0), Value::Int64(0)),
+            (minute(2), Value::Float64(5.0), Value::Int64(1)),
+        ]);
+        assert!(!result.rows[0].contains_key("__bucket"));
+    }
+
+    #[tokio::test]
+    async fn test_native_window_functions() {
+        let memory_engine = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let executor = DefaultQueryExecutor::new(Arc::new(memory_engine));
+        
+        let sql = "SELECT id, amount / LAG(amount) OVER w - 1 AS ret, \
+                   AVG(amount) OVER (w ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) AS ma \
+                   FROM orders WHERE user_id = 2 WINDOW w AS (PARTITION BY user_id ORDER BY id)";
+        let query = ParsedQuery::new(QueryType::Select, sql.to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await.unwrap();
+        
+        // user_id = 2 的订单：id 1 (100) 和 id 11 (1100)
+        let row = |id: i64| result.rows.iter().find(|r| r["id"] == Value::Int64(id)).unwrap();
+        assert_eq!(row(1)["ret"], Value::Null);
+        assert_eq!(row(11)["ret"], Value::Float64(10.0));
+        assert_eq!(row(11)["ma"], Value::Float64(600.0));
+    }
+
     #[tokio::test]
     async fn test_query_cancellation() {
         let memory_engine = MemoryEngine::new(HashMap::new()).await.unwrap();
@@ -430,6 +2284,50 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_cancellation_and_row_budget() {
+        let memory_engine = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let executor = DefaultQueryExecutor::new(Arc::new(memory_engine));
+        
+        // 无排序与聚合时，产出max_rows行后即停止扫描
+        let query = ParsedQuery::new(QueryType::Select, "SELECT * FROM orders".to_string());
+        let context = ExecutionContext::new("q".to_string()).with_max_rows(5);
+        let result = executor.execute(OptimizedPlan::new(query), context).await.unwrap();
+        assert_eq!(result.row_count(), 5);
+        assert_eq!(result.stats.rows_scanned, 5);
+        
+        let query = ParsedQuery::new(QueryType::Select, "SELECT * FROM orders".to_string());
+        let context = ExecutionContext::new("q".to_string());
+        context.cancellation.cancel("killed");
+        let outcome = executor.execute(OptimizedPlan::new(query), context).await;
+        assert!(matches!(outcome, Err(Error::Cancelled { .. })));
+        assert_eq!(executor.get_stats().await.unwrap()["running_queries"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_spilling_sort_and_aggregation() {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let in_memory = DefaultQueryExecutor::new(storage.clone());
+        let spilling = DefaultQueryExecutor::new(storage)
+            .with_sort_config(SortConfig::default().with_memory_budget(0))
+            .with_aggregate_config(AggregateConfig::default().with_memory_budget(0));
+        
+        for sql in [
+            "SELECT user_id, SUM(amount) AS total, COUNT(*) FROM orders GROUP BY user_id ORDER BY total DESC, user_id",
+            "SELECT id, amount FROM orders ORDER BY amount DESC NULLS LAST, id LIMIT 3 OFFSET 2",
+            "SELECT id, user_id FROM orders ORDER BY user_id, 1 DESC",
+        ] {
+            let run = |executor: &DefaultQueryExecutor| {
+                let query = ParsedQuery::new(QueryType::Select, sql.to_string());
+                executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string()))
+            };
+            let expected = run(&in_memory).await.unwrap();
+            let result = run(&spilling).await.unwrap();
+            assert!(expected.row_count() > 0, "{}", sql);
+            assert_eq!(result.rows, expected.rows, "{}", sql);
+        }
+    }
+
     #[tokio::test]
     async fn test_executor_stats() {
         let memory_engine = MemoryEngine::new(HashMap::new()).await.unwrap();
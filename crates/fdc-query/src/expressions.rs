@@ -0,0 +1,833 @@
+//! Scalar expression evaluation over rows
+
+use crate::{
+    admission::MemoryBudget, cancellation::CancellationToken, functions::BuiltinFunctions,
+    security::{QuerySecurity, SessionUser}, transactions::Transaction, udf::FunctionRegistry,
+};
+use fdc_core::{
+    error::{Error, Result},
+    time::{intervals, TimeUtils},
+    types::{TimestampNs, Value},
+};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use sqlparser::ast::{self, BinaryOperator, DataType, Expr, UnaryOperator};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 表达式求值器
+pub struct ExpressionEvaluator {
+    /// 内置函数
+    functions: BuiltinFunctions,
+    /// 绑定的查询参数
+    parameters: HashMap<String, Value>,
+    /// 所属查询的取消令牌
+    cancellation: CancellationToken,
+    /// 所属查询的内存预算
+    memory: MemoryBudget,
+    /// 用户自定义函数
+    udfs: Option<Arc<FunctionRegistry>>,
+    /// 所属查询所在的事务，扫描目录表时读取事务视图
+    transaction: Option<Arc<Transaction>>,
+    /// 执行查询的用户，供`CURRENT_USER`与行策略中的用户属性函数使用
+    user: Option<Arc<SessionUser>>,
+    /// 计划器为当前用户解析的行条件与列掩码
+    security: Option<Arc<QuerySecurity>>,
+}
+
+impl ExpressionEvaluator {
+    /// 创建新的表达式求值器
+    pub fn new() -> Self {
+        Self {
+            functions: BuiltinFunctions::new(),
+            parameters: HashMap::new(),
+            cancellation: CancellationToken::new(),
+            memory: MemoryBudget::unlimited(),
+            udfs: None,
+            transaction: None,
+            user: None,
+            security: None,
+        }
+    }
+
+    /// 设置查询参数
+    pub fn with_parameters(mut self, parameters: HashMap<String, Value>) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// 设置所属查询的取消令牌
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// 设置所属查询的内存预算
+    pub fn with_memory_budget(mut self, memory: MemoryBudget) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// 设置可调用的用户自定义函数
+    pub fn with_functions(mut self, udfs: Arc<FunctionRegistry>) -> Self {
+        self.udfs = Some(udfs);
+        self
+    }
+
+    /// 设置所属查询所在的事务
+    pub fn with_transaction(mut self, transaction: Option<Arc<Transaction>>) -> Self {
+        self.transaction = transaction;
+        self
+    }
+
+    /// 设置执行查询的用户
+    pub fn with_user(mut self, user: Option<Arc<SessionUser>>) -> Self {
+        self.user = user;
+        self
+    }
+
+    /// 设置当前用户的行条件与列掩码
+    pub fn with_security(mut self, security: Option<Arc<QuerySecurity>>) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// 用户自定义函数
+    pub fn functions(&self) -> Option<&Arc<FunctionRegistry>> {
+        self.udfs.as_ref()
+    }
+
+    /// 所属查询的取消令牌
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// 所属查询的内存预算
+    pub fn memory(&self) -> &MemoryBudget {
+        &self.memory
+    }
+
+    /// 所属查询所在的事务
+    pub fn transaction(&self) -> Option<&Arc<Transaction>> {
+        self.transaction.as_ref()
+    }
+
+    /// 执行查询的用户
+    pub fn user(&self) -> Option<&Arc<SessionUser>> {
+        self.user.as_ref()
+    }
+
+    /// 当前用户的行条件与列掩码
+    pub fn security(&self) -> Option<&Arc<QuerySecurity>> {
+        self.security.as_ref()
+    }
+
+    /// 逐行处理时的取消检查点
+    pub fn checkpoint(&self) -> Result<()> {
+        self.cancellation.tick()
+    }
+
+    /// 对一行数据求值表达式
+    pub fn evaluate(&self, expr: &Expr, row: &HashMap<String, Value>) -> Result<Value> {
+        match expr {
+            Expr::Identifier(ident) => Ok(resolve_column(row, &ident.value).cloned().unwrap_or(Value::Null)),
+            Expr::CompoundIdentifier(idents) => {
+                let name = idents.iter().map(|i| i.value.as_str()).collect::<Vec<_>>().join(".");
+                Ok(resolve_column(row, &name).cloned().unwrap_or(Value::Null))
+            }
+            Expr::Value(literal) => self.literal(literal),
+            Expr::Nested(inner) => self.evaluate(inner, row),
+            Expr::UnaryOp { op, expr } => {
+                let value = self.evaluate(expr, row)?;
+                match op {
+                    UnaryOperator::Not => Ok(match truth_value(&value) {
+                        Some(b) => Value::Bool(!b),
+                        None => Value::Null,
+                    }),
+                    UnaryOperator::Minus => negate(&value),
+                    UnaryOperator::Plus => Ok(value),
+                    other => Err(Error::unimplemented(format!("Unary operator {}", other))),
+                }
+            }
+            Expr::BinaryOp { left, op, right } => {
+                // AND/OR短路求值
+                match op {
+                    BinaryOperator::And => {
+                        let l = truth_value(&self.evaluate(left, row)?);
+                        if l == Some(false) {
+                            return Ok(Value::Bool(false));
+                        }
+                        let r = truth_value(&self.evaluate(right, row)?);
+                        return Ok(match (l, r) {
+                            (_, Some(false)) => Value::Bool(false),
+                            (Some(true), Some(true)) => Value::Bool(true),
+                            _ => Value::Null,
+                        });
+                    }
+                    BinaryOperator::Or => {
+                        let l = truth_value(&self.evaluate(left, row)?);
+                        if l == Some(true) {
+                            return Ok(Value::Bool(true));
+                        }
+                        let r = truth_value(&self.evaluate(right, row)?);
+                        return Ok(match (l, r) {
+                            (_, Some(true)) => Value::Bool(true),
+                            (Some(false), Some(false)) => Value::Bool(false),
+                            _ => Value::Null,
+                        });
+                    }
+                    _ => {}
+                }
+                let l = self.evaluate(left, row)?;
+                let r = self.evaluate(right, row)?;
+                binary_op(&l, op, &r)
+            }
+            Expr::IsNull(inner) => Ok(Value::Bool(matches!(self.evaluate(inner, row)?, Value::Null))),
+            Expr::IsNotNull(inner) => Ok(Value::Bool(!matches!(self.evaluate(inner, row)?, Value::Null))),
+            Expr::IsTrue(inner) => Ok(Value::Bool(truth_value(&self.evaluate(inner, row)?) == Some(true))),
+            Expr::IsFalse(inner) => Ok(Value::Bool(truth_value(&self.evaluate(inner, row)?) == Some(false))),
+            Expr::IsDistinctFrom(a, b) => {
+                let (a, b) = (self.evaluate(a, row)?, self.evaluate(b, row)?);
+                Ok(Value::Bool(compare_values(&a, &b) != Some(Ordering::Equal) && !(a == Value::Null && b == Value::Null)))
+            }
+            Expr::IsNotDistinctFrom(a, b) => {
+                let (a, b) = (self.evaluate(a, row)?, self.evaluate(b, row)?);
+                Ok(Value::Bool(compare_values(&a, &b) == Some(Ordering::Equal) || (a == Value::Null && b == Value::Null)))
+            }
+            Expr::InList { expr, list, negated } => {
+                let value = self.evaluate(expr, row)?;
+                if value == Value::Null {
+                    return Ok(Value::Null);
+                }
+                let mut found = false;
+                for item in list {
+                    if compare_values(&value, &self.evaluate(item, row)?) == Some(Ordering::Equal) {
+                        found = true;
+                        break;
+                    }
+                }
+                Ok(Value::Bool(found != *negated))
+            }
+            Expr::Between { expr, negated, low, high } => {
+                let value = self.evaluate(expr, row)?;
+                let low = self.evaluate(low, row)?;
+                let high = self.evaluate(high, row)?;
+                match (compare_values(&value, &low), compare_values(&value, &high)) {
+                    (Some(l), Some(h)) => {
+                        let inside = l != Ordering::Less && h != Ordering::Greater;
+                        Ok(Value::Bool(inside != *negated))
+                    }
+                    _ => Ok(Value::Null),
+                }
+            }
+            Expr::Like { negated, expr, pattern, .. } => self.like(expr, pattern, *negated, false, row),
+            Expr::ILike { negated, expr, pattern, .. } => self.like(expr, pattern, *negated, true, row),
+            Expr::Case { operand, conditions, results, else_result } => {
+                let operand = match operand {
+                    Some(op) => Some(self.evaluate(op, row)?),
+                    None => None,
+                };
+                for (condition, result) in conditions.iter().zip(results) {
+                    let matched = match &operand {
+                        Some(op) => compare_values(op, &self.evaluate(condition, row)?) == Some(Ordering::Equal),
+                        None => truth_value(&self.evaluate(condition, row)?) == Some(true),
+                    };
+                    if matched {
+                        return self.evaluate(result, row);
+                    }
+                }
+                match else_result {
+                    Some(e) => self.evaluate(e, row),
+                    None => Ok(Value::Null),
+                }
+            }
+            Expr::Cast { expr, data_type, .. } => cast_value(self.evaluate(expr, row)?, data_type),
+            Expr::TypedString { data_type, value } => cast_value(Value::String(value.clone()), data_type),
+            Expr::Interval(interval) => Ok(Value::Int64(interval_to_nanos(interval)?)),
+            Expr::Function(function) => {
+                // 聚合等已预先计算的调用直接从行中取值
+                if let Some(value) = row.get(&expr.to_string()) {
+                    return Ok(value.clone());
+                }
+                let name = function.name.to_string();
+                let args = function_args(function)?
+                    .iter()
+                    .map(|arg| self.evaluate(arg, row))
+                    .collect::<Result<Vec<_>>>()?;
+                if !self.functions.contains(&name) {
+                    // 未批量预计算的自定义函数逐行调用
+                    if let Some(result) = self.udfs.as_ref().and_then(|udfs| udfs.call(&name, &args)) {
+                        return result;
+                    }
+                    if let Some(result) = SessionUser::call(self.user.as_deref(), &name, &args) {
+                        return result;
+                    }
+                }
+                self.functions.call(&name, &args)
+            }
+            other => Err(Error::unimplemented(format!("Expression not supported: {}", other))),
+        }
+    }
+
+    /// 求值谓词（NULL视为false）
+    pub fn evaluate_predicate(&self, expr: &Expr, row: &HashMap<String, Value>) -> Result<bool> {
+        Ok(truth_value(&self.evaluate(expr, row)?) == Some(true))
+    }
+
+    fn like(&self, expr: &Expr, pattern: &Expr, negated: bool, case_insensitive: bool, row: &HashMap<String, Value>) -> Result<Value> {
+        let value = self.evaluate(expr, row)?;
+        let pattern = self.evaluate(pattern, row)?;
+        match (value_as_str(&value), value_as_str(&pattern)) {
+            (Some(v), Some(p)) => {
+                let matched = if case_insensitive {
+                    like_match(&v.to_lowercase(), &p.to_lowercase())
+                } else {
+                    like_match(&v, &p)
+                };
+                Ok(Value::Bool(matched != negated))
+            }
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// 转换字面量，占位符从绑定参数中读取
+    fn literal(&self, literal: &ast::Value) -> Result<Value> {
+        match literal {
+            ast::Value::Placeholder(name) => {
+                let key = name.trim_start_matches(['$', ':', '?', '@']);
+                self.parameters.get(key)
+                    .or_else(|| self.parameters.get(name))
+                    .cloned()
+                    .ok_or_else(|| Error::validation(format!("No value bound for parameter {}", name)))
+            }
+            other => literal_to_value(other),
+        }
+    }
+}
+
+impl Default for ExpressionEvaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 按名称解析列，支持`alias.column`限定名与未限定名互相匹配
+pub fn resolve_column<'a>(row: &'a HashMap<String, Value>, name: &str) -> Option<&'a Value> {
+    if let Some(value) = row.get(name) {
+        return Some(value);
+    }
+
+    // 未限定名：匹配唯一的`*.name`列
+    if !name.contains('.') {
+        let suffix = format!(".{}", name);
+        let mut matches = row.iter().filter(|(k, _)| k.ends_with(&suffix));
+        let first = matches.next();
+        if matches.next().is_none() {
+            if let Some((_, value)) = first {
+                return Some(value);
+            }
+        }
+    } else if let Some((_, column)) = name.rsplit_once('.') {
+        // 限定名但行中只有未限定列
+        if let Some(value) = row.get(column) {
+            return Some(value);
+        }
+    }
+
+    // 大小写不敏感回退
+    row.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v)
+}
+
+/// 表达式的输出列名
+pub fn expr_output_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|i| i.value.clone()).unwrap_or_default(),
+        other => other.to_string(),
+    }
+}
+
+/// 提取函数调用的参数表达式
+pub fn function_args(function: &ast::Function) -> Result<Vec<&Expr>> {
+    match &function.args {
+        ast::FunctionArguments::None => Ok(Vec::new()),
+        ast::FunctionArguments::List(list) => list.args.iter().map(|arg| match arg {
+            ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(e))
+            | ast::FunctionArg::Named { arg: ast::FunctionArgExpr::Expr(e), .. } => Ok(e),
+            other => Err(Error::unimplemented(format!("Function argument not supported: {}", other))),
+        }).collect(),
+        ast::FunctionArguments::Subquery(_) => Err(Error::unimplemented("Subquery function arguments")),
+    }
+}
+
+/// SQL三值逻辑的真值
+pub fn truth_value(value: &Value) -> Option<bool> {
+    match value {
+        Value::Null => None,
+        Value::Bool(b) => Some(*b),
+        other => value_as_f64(other).map(|v| v != 0.0),
+    }
+}
+
+/// 转换SQL字面量
+pub fn literal_to_value(literal: &ast::Value) -> Result<Value> {
+    match literal {
+        ast::Value::Number(n, _) => {
+            if let Ok(i) = n.parse::<i64>() {
+                Ok(Value::Int64(i))
+            } else if let Ok(f) = n.parse::<f64>() {
+                Ok(Value::Float64(f))
+            } else {
+                Err(Error::parse(format!("Invalid number literal: {}", n)))
+            }
+        }
+        ast::Value::SingleQuotedString(s)
+        | ast::Value::DoubleQuotedString(s)
+        | ast::Value::EscapedStringLiteral(s)
+        | ast::Value::NationalStringLiteral(s) => Ok(Value::String(s.clone())),
+        ast::Value::Boolean(b) => Ok(Value::Bool(*b)),
+        ast::Value::Null => Ok(Value::Null),
+        ast::Value::Placeholder(p) => Err(Error::validation(format!("No value bound for parameter {}", p))),
+        other => Err(Error::unimplemented(format!("Literal not supported: {}", other))),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Numeric {
+    Int(i128),
+    Float(f64),
+    Decimal(Decimal),
+}
+
+fn numeric(value: &Value) -> Option<Numeric> {
+    match value {
+        Value::Int8(v) => Some(Numeric::Int(*v as i128)),
+        Value::Int16(v) => Some(Numeric::Int(*v as i128)),
+        Value::Int32(v) => Some(Numeric::Int(*v as i128)),
+        Value::Int64(v) => Some(Numeric::Int(*v as i128)),
+        Value::Int128(v) => Some(Numeric::Int(*v)),
+        Value::UInt8(v) => Some(Numeric::Int(*v as i128)),
+        Value::UInt16(v) => Some(Numeric::Int(*v as i128)),
+        Value::UInt32(v) => Some(Numeric::Int(*v as i128)),
+        Value::UInt64(v) => Some(Numeric::Int(*v as i128)),
+        Value::Volume(v) => Some(Numeric::Int(v.as_u64() as i128)),
+        Value::Float32(v) => Some(Numeric::Float(*v as f64)),
+        Value::Float64(v) => Some(Numeric::Float(*v)),
+        Value::Decimal(d) => Some(Numeric::Decimal(*d)),
+        Value::Price(p) => Some(Numeric::Decimal(p.as_decimal())),
+        _ => None,
+    }
+}
+
+impl Numeric {
+    fn to_f64(self) -> f64 {
+        match self {
+            Numeric::Int(i) => i as f64,
+            Numeric::Float(f) => f,
+            Numeric::Decimal(d) => d.to_f64().unwrap_or(f64::NAN),
+        }
+    }
+
+    fn to_decimal(self) -> Option<Decimal> {
+        match self {
+            Numeric::Int(i) => Decimal::from_i128(i),
+            Numeric::Float(f) => Decimal::from_f64(f),
+            Numeric::Decimal(d) => Some(d),
+        }
+    }
+}
+
+/// 数值转换为f64
+pub fn value_as_f64(value: &Value) -> Option<f64> {
+    numeric(value).map(Numeric::to_f64)
+}
+
+/// 数值转换为i64
+pub fn value_as_i64(value: &Value) -> Option<i64> {
+    match numeric(value)? {
+        Numeric::Int(i) => i64::try_from(i).ok(),
+        Numeric::Float(f) => Some(f as i64),
+        Numeric::Decimal(d) => d.to_i64(),
+    }
+}
+
+/// 数值转换为Decimal
+pub fn value_as_decimal(value: &Value) -> Option<Decimal> {
+    numeric(value).and_then(Numeric::to_decimal)
+}
+
+/// 字符串类值
+pub fn value_as_str(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Symbol(s) => Some(s.as_str().to_string()),
+        _ => None,
+    }
+}
+
+/// 时间类值转换为纳秒时间戳
+pub fn value_as_timestamp(value: &Value) -> Option<TimestampNs> {
+    match value {
+        Value::Timestamp(ts) => Some(*ts),
+        Value::String(s) => TimeUtils::parse_timestamp(s).ok(),
+        other => value_as_i64(other).map(TimestampNs::from_nanos),
+    }
+}
+
+/// 比较两个值（NULL参与比较返回None）
+pub fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Timestamp(x), Value::Timestamp(y)) => Some(x.cmp(y)),
+        (Value::Timestamp(x), other) | (other, Value::Timestamp(x)) => {
+            let y = value_as_timestamp(other)?;
+            let ordering = x.cmp(&y);
+            Some(if matches!(a, Value::Timestamp(_)) { ordering } else { ordering.reverse() })
+        }
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        _ => {
+            if let (Some(x), Some(y)) = (numeric(a), numeric(b)) {
+                return match (x, y) {
+                    (Numeric::Int(x), Numeric::Int(y)) => Some(x.cmp(&y)),
+                    (Numeric::Float(_), _) | (_, Numeric::Float(_)) => x.to_f64().partial_cmp(&y.to_f64()),
+                    _ => Some(x.to_decimal()?.cmp(&y.to_decimal()?)),
+                };
+            }
+            if let (Some(x), Some(y)) = (value_as_str(a), value_as_str(b)) {
+                return Some(x.cmp(&y));
+            }
+            if a == b {
+                Some(Ordering::Equal)
+            } else {
+                a.partial_cmp(b)
+            }
+        }
+    }
+}
+
+fn negate(value: &Value) -> Result<Value> {
+    match numeric(value) {
+        Some(Numeric::Int(i)) => Ok(Value::Int64(-(i as i64))),
+        Some(Numeric::Float(f)) => Ok(Value::Float64(-f)),
+        Some(Numeric::Decimal(d)) => Ok(Value::Decimal(-d)),
+        None if *value == Value::Null => Ok(Value::Null),
+        None => Err(Error::type_error(format!("Cannot negate {:?}", value))),
+    }
+}
+
+/// 二元运算
+pub fn binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Value> {
+    let comparison = |f: fn(Ordering) -> bool| -> Value {
+        match compare_values(left, right) {
+            Some(ordering) => Value::Bool(f(ordering)),
+            None => Value::Null,
+        }
+    };
+
+    match op {
+        BinaryOperator::Eq => Ok(comparison(|o| o == Ordering::Equal)),
+        BinaryOperator::NotEq => Ok(comparison(|o| o != Ordering::Equal)),
+        BinaryOperator::Lt => Ok(comparison(|o| o == Ordering::Less)),
+        BinaryOperator::LtEq => Ok(comparison(|o| o != Ordering::Greater)),
+        BinaryOperator::Gt => Ok(comparison(|o| o == Ordering::Greater)),
+        BinaryOperator::GtEq => Ok(comparison(|o| o != Ordering::Less)),
+        BinaryOperator::StringConcat => {
+            if *left == Value::Null || *right == Value::Null {
+                return Ok(Value::Null);
+            }
+            Ok(Value::String(format!("{}{}", display_value(left), display_value(right))))
+        }
+        BinaryOperator::Plus
+        | BinaryOperator::Minus
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::Modulo => arithmetic(left, op, right),
+        other => Err(Error::unimplemented(format!("Binary operator {}", other))),
+    }
+}
+
+fn arithmetic(left: &Value, op: &BinaryOperator, right: &Value) -> Result<Value> {
+    if *left == Value::Null || *right == Value::Null {
+        return Ok(Value::Null);
+    }
+
+    // 时间戳运算：ts ± 纳秒、ts - ts
+    match (left, op, right) {
+        (Value::Timestamp(a), BinaryOperator::Minus, Value::Timestamp(b)) => {
+            return Ok(Value::Int64(TimeUtils::diff_nanos(*a, *b)));
+        }
+        (Value::Timestamp(ts), BinaryOperator::Plus, other) | (other, BinaryOperator::Plus, Value::Timestamp(ts)) => {
+            let nanos = value_as_i64(other).ok_or_else(|| Error::type_error("Timestamp arithmetic requires an interval"))?;
+            return Ok(Value::Timestamp(TimeUtils::add_nanos(*ts, nanos)));
+        }
+        (Value::Timestamp(ts), BinaryOperator::Minus, other) => {
+            let nanos = value_as_i64(other).ok_or_else(|| Error::type_error("Timestamp arithmetic requires an interval"))?;
+            return Ok(Value::Timestamp(TimeUtils::sub_nanos(*ts, nanos)));
+        }
+        _ => {}
+    }
+
+    let (a, b) = match (numeric(left), numeric(right)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(Error::type_error(format!("Arithmetic requires numeric operands: {:?} {} {:?}", left, op, right))),
+    };
+
+    match (a, b) {
+        (Numeric::Int(x), Numeric::Int(y)) => {
+            let result = match op {
+                BinaryOperator::Plus => x.checked_add(y),
+                BinaryOperator::Minus => x.checked_sub(y),
+                BinaryOperator::Multiply => x.checked_mul(y),
+                BinaryOperator::Divide => {
+                    if y == 0 {
+                        return Err(Error::validation("Division by zero"));
+                    }
+                    x.checked_div(y)
+                }
+                _ => {
+                    if y == 0 {
+                        return Err(Error::validation("Division by zero"));
+                    }
+                    x.checked_rem(y)
+                }
+            };
+            result.and_then(|v| i64::try_from(v).ok())
+                .map(Value::Int64)
+                .ok_or_else(|| Error::validation("Integer overflow"))
+        }
+        (Numeric::Float(_), _) | (_, Numeric::Float(_)) => {
+            let (x, y) = (a.to_f64(), b.to_f64());
+            Ok(Value::Float64(match op {
+                BinaryOperator::Plus => x + y,
+                BinaryOperator::Minus => x - y,
+                BinaryOperator::Multiply => x * y,
+                BinaryOperator::Divide => x / y,
+                _ => x % y,
+            }))
+        }
+        _ => {
+            let x = a.to_decimal().ok_or_else(|| Error::type_error("Invalid decimal operand"))?;
+            let y = b.to_decimal().ok_or_else(|| Error::type_error("Invalid decimal operand"))?;
+            let result = match op {
+                BinaryOperator::Plus => x.checked_add(y),
+                BinaryOperator::Minus => x.checked_sub(y),
+                BinaryOperator::Multiply => x.checked_mul(y),
+                BinaryOperator::Divide => x.checked_div(y),
+                _ => x.checked_rem(y),
+            };
+            result.map(Value::Decimal).ok_or_else(|| Error::validation("Decimal overflow or division by zero"))
+        }
+    }
+}
+
+/// 值的文本表示
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Symbol(s) => s.to_string(),
+        Value::Timestamp(ts) => ts.to_string(),
+        Value::Price(p) => p.to_string(),
+        Value::Volume(v) => v.to_string(),
+        Value::Decimal(d) => d.to_string(),
+        other => match numeric(other) {
+            Some(Numeric::Int(i)) => i.to_string(),
+            Some(Numeric::Float(f)) => f.to_string(),
+            _ => format!("{:?}", other),
+        },
+    }
+}
+
+/// 类型转换
+pub fn cast_value(value: Value, data_type: &DataType) -> Result<Value> {
+    if value == Value::Null {
+        return Ok(Value::Null);
+    }
+    let invalid = |v: &Value| Error::type_error(format!("Cannot cast {:?} to {}", v, data_type));
+
+    match data_type {
+        DataType::TinyInt(_) | DataType::SmallInt(_) | DataType::Int(_) | DataType::Integer(_)
+        | DataType::BigInt(_) | DataType::Int2(_) | DataType::Int4(_) | DataType::Int8(_)
+        | DataType::Int64 | DataType::Int32 => match &value {
+            Value::String(s) => s.trim().parse::<i64>().map(Value::Int64).map_err(|_| invalid(&value)),
+            Value::Timestamp(ts) => Ok(Value::Int64(ts.as_nanos())),
+            Value::Bool(b) => Ok(Value::Int64(*b as i64)),
+            other => value_as_i64(other).map(Value::Int64).ok_or_else(|| invalid(other)),
+        },
+        DataType::Float(_) | DataType::Real | DataType::Double | DataType::DoublePrecision
+        | DataType::Float4 | DataType::Float8 | DataType::Float32 | DataType::Float64 => match &value {
+            Value::String(s) => s.trim().parse::<f64>().map(Value::Float64).map_err(|_| invalid(&value)),
+            other => value_as_f64(other).map(Value::Float64).ok_or_else(|| invalid(other)),
+        },
+        DataType::Decimal(_) | DataType::Numeric(_) | DataType::Dec(_) => match &value {
+            Value::String(s) => s.trim().parse::<Decimal>().map(Value::Decimal).map_err(|_| invalid(&value)),
+            other => value_as_decimal(other).map(Value::Decimal).ok_or_else(|| invalid(other)),
+        },
+        DataType::Varchar(_) | DataType::Text | DataType::String(_) | DataType::Char(_)
+        | DataType::Character(_) | DataType::CharacterVarying(_) => Ok(Value::String(display_value(&value))),
+        DataType::Bool | DataType::Boolean => match &value {
+            Value::String(s) => match s.to_lowercase().as_str() {
+                "true" | "t" | "1" => Ok(Value::Bool(true)),
+                "false" | "f" | "0" => Ok(Value::Bool(false)),
+                _ => Err(invalid(&value)),
+            },
+            other => truth_value(other).map(Value::Bool).ok_or_else(|| invalid(other)),
+        },
+        DataType::Timestamp(_, _) | DataType::Datetime(_) | DataType::Date => {
+            value_as_timestamp(&value).map(Value::Timestamp).ok_or_else(|| invalid(&value))
+        }
+        DataType::Interval => match &value {
+            Value::String(s) => parse_interval_nanos(s).map(Value::Int64),
+            other => value_as_i64(other).map(Value::Int64).ok_or_else(|| invalid(other)),
+        },
+        other => Err(Error::unimplemented(format!("CAST to {}", other))),
+    }
+}
+
+/// INTERVAL表达式转换为纳秒
+pub fn interval_to_nanos(interval: &ast::Interval) -> Result<i64> {
+    let text = match interval.value.as_ref() {
+        Expr::Value(ast::Value::SingleQuotedString(s)) => s.clone(),
+        Expr::Value(ast::Value::Number(n, _)) => n.clone(),
+        other => return Err(Error::unimplemented(format!("Interval value not supported: {}", other))),
+    };
+
+    match &interval.leading_field {
+        Some(field) => {
+            let amount: f64 = text.trim().parse()
+                .map_err(|_| Error::parse(format!("Invalid interval: {}", text)))?;
+            let unit = datetime_field_nanos(field)?;
+            Ok((amount * unit as f64) as i64)
+        }
+        None => parse_interval_nanos(&text),
+    }
+}
+
+fn datetime_field_nanos(field: &ast::DateTimeField) -> Result<i64> {
+    use ast::DateTimeField as F;
+    match field {
+        F::Nanosecond | F::Nanoseconds => Ok(intervals::NANOSECOND),
+        F::Microsecond | F::Microseconds => Ok(intervals::MICROSECOND),
+        F::Millisecond | F::Milliseconds => Ok(intervals::MILLISECOND),
+        F::Second => Ok(intervals::SECOND),
+        F::Minute => Ok(intervals::MINUTE),
+        F::Hour => Ok(intervals::HOUR),
+        F::Day => Ok(intervals::DAY),
+        F::Week(_) => Ok(intervals::WEEK),
+        other => Err(Error::unimplemented(format!("Interval unit {} has no fixed length", other))),
+    }
+}
+
+/// 解析间隔文本，例如`5 minutes`、`1m`、`250ms`、`1 hour 30 minutes`
+pub fn parse_interval_nanos(text: &str) -> Result<i64> {
+    let invalid = || Error::parse(format!("Invalid interval: {}", text));
+    let mut total: i64 = 0;
+    let mut tokens = text.split_whitespace().peekable();
+    let mut parsed_any = false;
+
+    while let Some(token) = tokens.next() {
+        // 数字与单位可以连写（1m）或分开（1 minute）
+        let split = token.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-')).unwrap_or(token.len());
+        let (number, mut unit) = token.split_at(split);
+        let amount: f64 = number.parse().map_err(|_| invalid())?;
+        if unit.is_empty() {
+            unit = tokens.next().ok_or_else(invalid)?;
+        }
+        let nanos = match unit.to_lowercase().trim_end_matches(',') {
+            "ns" | "nanosecond" | "nanoseconds" => intervals::NANOSECOND,
+            "us" | "microsecond" | "microseconds" => intervals::MICROSECOND,
+            "ms" | "millisecond" | "milliseconds" => intervals::MILLISECOND,
+            "s" | "sec" | "secs" | "second" | "seconds" => intervals::SECOND,
+            "m" | "min" | "mins" | "minute" | "minutes" => intervals::MINUTE,
+            "h" | "hr" | "hour" | "hours" => intervals::HOUR,
+            "d" | "day" | "days" => intervals::DAY,
+            "w" | "week" | "weeks" => intervals::WEEK,
+            _ => return Err(invalid()),
+        };
+        total += (amount * nanos as f64) as i64;
+        parsed_any = true;
+    }
+
+    if parsed_any { Ok(total) } else { Err(invalid()) }
+}
+
+/// SQL LIKE模式匹配（`%`与`_`通配符）
+pub fn like_match(value: &str, pattern: &str) -> bool {
+    let v: Vec<char> = value.chars().collect();
+    let p: Vec<char> = pattern.chars().collect();
+    let (mut vi, mut pi) = (0, 0);
+    let (mut star, mut mark) = (None, 0);
+
+    while vi < v.len() {
+        if pi < p.len() && (p[pi] == '_' || p[pi] == v[vi]) {
+            vi += 1;
+            pi += 1;
+        } else if pi < p.len() && p[pi] == '%' {
+            star = Some(pi);
+            mark = vi;
+            pi += 1;
+        } else if let Some(s) = star {
+            pi = s + 1;
+            mark += 1;
+            vi = mark;
+        } else {
+            return false;
+        }
+    }
+    while pi < p.len() && p[pi] == '%' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::{dialect::GenericDialect, parser::Parser};
+
+    fn parse_expr(sql: &str) -> Expr {
+        Parser::new(&GenericDialect {}).try_with_sql(sql).unwrap().parse_expr().unwrap()
+    }
+
+    fn row() -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("t.price".to_string(), Value::Float64(101.5));
+        row.insert("t.qty".to_string(), Value::Int64(200));
+        row.insert("t.symbol".to_string(), Value::String("AAPL".to_string()));
+        row
+    }
+
+    #[test]
+    fn test_arithmetic_and_comparison() {
+        let evaluator = ExpressionEvaluator::new();
+        assert_eq!(evaluator.evaluate(&parse_expr("price * qty"), &row()).unwrap(), Value::Float64(20300.0));
+        assert!(evaluator.evaluate_predicate(&parse_expr("t.qty > 100 AND symbol = 'AAPL'"), &row()).unwrap());
+        assert!(!evaluator.evaluate_predicate(&parse_expr("missing = 1"), &row()).unwrap());
+    }
+
+    #[test]
+    fn test_in_between_like() {
+        let evaluator = ExpressionEvaluator::new();
+        assert!(evaluator.evaluate_predicate(&parse_expr("qty IN (100, 200)"), &row()).unwrap());
+        assert!(evaluator.evaluate_predicate(&parse_expr("price BETWEEN 100 AND 102"), &row()).unwrap());
+        assert!(evaluator.evaluate_predicate(&parse_expr("symbol LIKE 'AA%'"), &row()).unwrap());
+    }
+
+    #[test]
+    fn test_placeholder_binding() {
+        let mut parameters = HashMap::new();
+        parameters.insert("1".to_string(), Value::Int64(200));
+        let evaluator = ExpressionEvaluator::new().with_parameters(parameters);
+        assert!(evaluator.evaluate_predicate(&parse_expr("qty = $1"), &row()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval_nanos("1m").unwrap(), intervals::MINUTE);
+        assert_eq!(parse_interval_nanos("5 minutes").unwrap(), 5 * intervals::MINUTE);
+        assert_eq!(parse_interval_nanos("1 hour 30 minutes").unwrap(), 90 * intervals::MINUTE);
+        assert!(parse_interval_nanos("soon").is_err());
+    }
+}
@@ -0,0 +1,429 @@
+//! Financial aggregates: VWAP, TWAP, OHLC, FIRST_BY/LAST_BY, SPREAD and WEIGHTED_AVG
+//!
+//! 价格类输入（`Price`、`Decimal`、整数、`Volume`）以`Decimal`精确累加，结果保持输入的价格类型；
+//! 出现浮点输入（或Decimal溢出）后整体退化为f64计算。
+
+use crate::expressions::{compare_values, value_as_decimal, value_as_f64, value_as_timestamp};
+use fdc_core::{error::{Error, Result}, types::{Price, Value}};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// 金融聚合的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinancialKind {
+    /// `vwap(price, volume)`：成交量加权均价
+    Vwap,
+    /// `twap(price, ts)`：时间加权均价
+    Twap,
+    /// `ohlc(price, ts)`：开高低收，开盘与收盘按时间选取
+    Ohlc,
+    /// `first_by(value, ts)`：时间最早的值
+    FirstBy,
+    /// `last_by(value, ts)`：时间最晚的值
+    LastBy,
+    /// `spread(bid, ask)`：平均买卖价差
+    Spread,
+    /// `weighted_avg(value, weight)`：加权平均
+    WeightedAvg,
+}
+
+impl FinancialKind {
+    /// SQL函数名
+    pub fn name(&self) -> &'static str {
+        match self {
+            FinancialKind::Vwap => "VWAP",
+            FinancialKind::Twap => "TWAP",
+            FinancialKind::Ohlc => "OHLC",
+            FinancialKind::FirstBy => "FIRST_BY",
+            FinancialKind::LastBy => "LAST_BY",
+            FinancialKind::Spread => "SPREAD",
+            FinancialKind::WeightedAvg => "WEIGHTED_AVG",
+        }
+    }
+
+    /// 参数个数，所有金融聚合都接受两个参数
+    pub fn arity(&self) -> usize {
+        2
+    }
+}
+
+/// 精确或近似的数值累加
+///
+/// 所有输入都能精确表示时按`Decimal`累加；`price`记录被累加的值是否全部为`Value::Price`，
+/// 决定结果以`Price`还是`Decimal`返回。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Total {
+    exact: Option<Decimal>,
+    approx: f64,
+    price: bool,
+}
+
+impl Default for Total {
+    fn default() -> Self {
+        Self { exact: Some(Decimal::ZERO), approx: 0.0, price: true }
+    }
+}
+
+impl Total {
+    /// 累加`value × weight`，`weight`为None时累加`value`本身
+    fn add(&mut self, value: &Value, weight: Option<&Value>, function: FinancialKind) -> Result<()> {
+        let invalid = || Error::validation(format!("{} requires numeric values", function.name()));
+        let mut approx = value_as_f64(value).ok_or_else(invalid)?;
+        let mut exact = exact_decimal(value);
+        if let Some(weight) = weight {
+            approx *= value_as_f64(weight).ok_or_else(invalid)?;
+            exact = exact.zip(exact_decimal(weight)).and_then(|(v, w)| v.checked_mul(w));
+        }
+        self.approx += approx;
+        self.price &= matches!(value, Value::Price(_));
+        self.exact = self.exact.zip(exact).and_then(|(total, value)| total.checked_add(value));
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Total) {
+        self.approx += other.approx;
+        self.price &= other.price;
+        self.exact = self.exact.zip(other.exact).and_then(|(a, b)| a.checked_add(b));
+    }
+
+    /// `self / divisor`，除数为零时为NULL
+    fn ratio(&self, divisor: &Total) -> Value {
+        if let (Some(numerator), Some(denominator)) = (self.exact, divisor.exact) {
+            if denominator.is_zero() {
+                return Value::Null;
+            }
+            if let Some(quotient) = numerator.checked_div(denominator) {
+                let quotient = quotient.normalize();
+                return if self.price { Value::Price(Price::new(quotient)) } else { Value::Decimal(quotient) };
+            }
+        }
+        if divisor.approx == 0.0 {
+            Value::Null
+        } else {
+            Value::Float64(self.approx / divisor.approx)
+        }
+    }
+
+    fn count(count: u64) -> Total {
+        Total { exact: Some(Decimal::from(count)), approx: count as f64, price: false }
+    }
+}
+
+/// 浮点值不参与精确累加
+fn exact_decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::Float32(_) | Value::Float64(_) => None,
+        other => value_as_decimal(other),
+    }
+}
+
+/// 金融聚合的可合并状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum FinancialState {
+    /// VWAP与WEIGHTED_AVG：Σ值×权重与Σ权重
+    Weighted { kind: FinancialKind, numerator: Total, weight: Total },
+    /// TWAP：(时间戳, 价格)观测点
+    Twap { points: Vec<(i64, Value)> },
+    /// OHLC：开、高、低、收
+    Ohlc { bar: Option<OhlcBar> },
+    /// FIRST_BY / LAST_BY：(排序键, 值)
+    By { kind: FinancialKind, current: Option<(Value, Value)> },
+    /// SPREAD：Σ(ask - bid)与报价数
+    Spread { sum: Total, count: u64 },
+}
+
+impl FinancialState {
+    /// 空状态
+    pub fn new(kind: FinancialKind) -> Self {
+        match kind {
+            FinancialKind::Vwap | FinancialKind::WeightedAvg => {
+                FinancialState::Weighted { kind, numerator: Total::default(), weight: Total::default() }
+            }
+            FinancialKind::Twap => FinancialState::Twap { points: Vec::new() },
+            FinancialKind::Ohlc => FinancialState::Ohlc { bar: None },
+            FinancialKind::FirstBy | FinancialKind::LastBy => FinancialState::By { kind, current: None },
+            FinancialKind::Spread => FinancialState::Spread { sum: Total::default(), count: 0 },
+        }
+    }
+
+    /// 在一组非NULL输入上计算聚合
+    pub fn evaluate(kind: FinancialKind, inputs: &[Value]) -> Result<Value> {
+        let mut state = Self::new(kind);
+        for input in inputs {
+            state.update(input)?;
+        }
+        Ok(state.finish())
+    }
+
+    /// 累加一个输入（双参数聚合的输入为参数数组），NULL被忽略
+    pub fn update(&mut self, input: &Value) -> Result<()> {
+        if matches!(input, Value::Null) {
+            return Ok(());
+        }
+        match self {
+            FinancialState::Weighted { kind, numerator, weight } => {
+                let [value, w] = pair(input, *kind)?;
+                numerator.add(value, Some(w), *kind)?;
+                weight.add(w, None, *kind)?;
+            }
+            FinancialState::Twap { points } => {
+                let [price, ts] = pair(input, FinancialKind::Twap)?;
+                let ts = value_as_timestamp(ts)
+                    .ok_or_else(|| Error::validation("TWAP requires a timestamp as its second argument"))?;
+                value_as_f64(price).ok_or_else(|| Error::validation("TWAP requires numeric values"))?;
+                points.push((ts.as_nanos(), price.clone()));
+            }
+            FinancialState::Ohlc { bar } => {
+                let [price, ts] = pair(input, FinancialKind::Ohlc)?;
+                let ts = value_as_timestamp(ts)
+                    .ok_or_else(|| Error::validation("OHLC requires a timestamp as its second argument"))?;
+                value_as_f64(price).ok_or_else(|| Error::validation("OHLC requires numeric values"))?;
+                let point = OhlcBar::point(ts.as_nanos(), price);
+                match bar {
+                    None => *bar = Some(point),
+                    Some(bar) => bar.merge(&point),
+                }
+            }
+            FinancialState::By { kind, current } => {
+                let [value, key] = pair(input, *kind)?;
+                if matches!(key, Value::Null) {
+                    return Ok(());
+                }
+                replace_by(*kind, current, key, value);
+            }
+            FinancialState::Spread { sum, count } => {
+                let [bid, ask] = pair(input, FinancialKind::Spread)?;
+                let difference = match (bid, ask) {
+                    (Value::Price(bid), Value::Price(ask)) => Value::Price(Price::new(ask.as_decimal() - bid.as_decimal())),
+                    _ => match (exact_decimal(bid), exact_decimal(ask)) {
+                        (Some(bid), Some(ask)) => Value::Decimal(ask - bid),
+                        _ => Value::Float64(
+                            value_as_f64(ask).zip(value_as_f64(bid)).map(|(a, b)| a - b)
+                                .ok_or_else(|| Error::validation("SPREAD requires numeric values"))?,
+                        ),
+                    },
+                };
+                sum.add(&difference, None, FinancialKind::Spread)?;
+                *count += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// 合并另一个同类状态，`other`的输入视为排在当前输入之后（OHLC与合并顺序无关）
+    pub fn merge(&mut self, other: &FinancialState) -> Result<()> {
+        match (self, other) {
+            (
+                FinancialState::Weighted { kind, numerator, weight },
+                FinancialState::Weighted { kind: other_kind, numerator: other_numerator, weight: other_weight },
+            ) if kind == other_kind => {
+                numerator.merge(other_numerator);
+                weight.merge(other_weight);
+            }
+            (FinancialState::Twap { points }, FinancialState::Twap { points: other }) => points.extend(other.iter().cloned()),
+            (FinancialState::Ohlc { bar }, FinancialState::Ohlc { bar: Some(other) }) => match bar {
+                None => *bar = Some(other.clone()),
+                Some(bar) => bar.merge(other),
+            },
+            (FinancialState::Ohlc { .. }, FinancialState::Ohlc { bar: None }) => {}
+            (FinancialState::By { kind, current }, FinancialState::By { kind: other_kind, current: other })
+                if kind == other_kind =>
+            {
+                if let Some((key, value)) = other {
+                    replace_by(*kind, current, key, value);
+                }
+            }
+            (FinancialState::Spread { sum, count }, FinancialState::Spread { sum: other_sum, count: other_count }) => {
+                sum.merge(other_sum);
+                *count += other_count;
+            }
+            (current, other) => {
+                return Err(Error::validation(format!("Cannot merge aggregate states {:?} and {:?}", current, other)));
+            }
+        }
+        Ok(())
+    }
+
+    /// 当前的聚合结果，没有输入时为NULL
+    pub fn finish(&self) -> Value {
+        match self {
+            FinancialState::Weighted { numerator, weight, .. } => numerator.ratio(weight),
+            FinancialState::Twap { points } => twap(points),
+            FinancialState::Ohlc { bar: None } => Value::Null,
+            FinancialState::Ohlc { bar: Some(bar) } => Value::Struct(HashMap::from([
+                ("open".to_string(), bar.open.1.clone()),
+                ("high".to_string(), bar.high.clone()),
+                ("low".to_string(), bar.low.clone()),
+                ("close".to_string(), bar.close.1.clone()),
+            ])),
+            FinancialState::By { current, .. } => current.as_ref().map(|(_, value)| value.clone()).unwrap_or(Value::Null),
+            FinancialState::Spread { count: 0, .. } => Value::Null,
+            FinancialState::Spread { sum, count } => sum.ratio(&Total::count(*count)),
+        }
+    }
+}
+
+/// OHLC的可合并状态
+///
+/// 开盘与收盘保留(时间戳, 价格)，按时间而不是输入到达或合并的顺序选取，
+/// 单机、落盘、并行与分布式的部分状态以任意顺序合并结果都相同。
+#[derive(Debug, Clone, PartialEq)]
+pub struct OhlcBar {
+    open: (i64, Value),
+    high: Value,
+    low: Value,
+    close: (i64, Value),
+}
+
+impl OhlcBar {
+    fn point(ts: i64, price: &Value) -> Self {
+        Self { open: (ts, price.clone()), high: price.clone(), low: price.clone(), close: (ts, price.clone()) }
+    }
+
+    fn merge(&mut self, other: &OhlcBar) {
+        if earlier(&other.open, &self.open) {
+            self.open = other.open.clone();
+        }
+        if earlier(&self.close, &other.close) {
+            self.close = other.close.clone();
+        }
+        if compare_values(&other.high, &self.high) == Some(Ordering::Greater) {
+            self.high = other.high.clone();
+        }
+        if compare_values(&other.low, &self.low) == Some(Ordering::Less) {
+            self.low = other.low.clone();
+        }
+    }
+}
+
+/// 按(时间戳, 价格)比较观测点；同一时间戳的成交以价格定先后，使结果与输入顺序无关
+fn earlier(a: &(i64, Value), b: &(i64, Value)) -> bool {
+    a.0.cmp(&b.0).then_with(|| compare_values(&a.1, &b.1).unwrap_or(Ordering::Equal)) == Ordering::Less
+}
+
+/// 取出双参数聚合的两个参数
+fn pair(input: &Value, kind: FinancialKind) -> Result<[&Value; 2]> {
+    match input {
+        Value::Array(values) if values.len() == 2 => Ok([&values[0], &values[1]]),
+        _ => Err(Error::validation(format!("{} takes exactly 2 arguments", kind.name()))),
+    }
+}
+
+/// FIRST_BY保留排序键最小的值（相同键保留先到的），LAST_BY保留排序键最大的值（相同键取后到的）
+fn replace_by(kind: FinancialKind, current: &mut Option<(Value, Value)>, key: &Value, value: &Value) {
+    let replace = match current {
+        None => true,
+        Some((current_key, _)) => match compare_values(key, current_key) {
+            Some(Ordering::Less) => kind == FinancialKind::FirstBy,
+            Some(Ordering::Greater) | Some(Ordering::Equal) => kind == FinancialKind::LastBy,
+            None => false,
+        },
+    };
+    if replace {
+        *current = Some((key.clone(), value.clone()));
+    }
+}
+
+/// 时间加权均价：每个价格按持续到下一个观测点的时长加权；
+/// 所有观测点时间相同（或只有一个）时退化为算术平均
+fn twap(points: &[(i64, Value)]) -> Value {
+    if points.is_empty() {
+        return Value::Null;
+    }
+    let mut sorted: Vec<&(i64, Value)> = points.iter().collect();
+    sorted.sort_by_key(|(ts, _)| *ts);
+    let (mut numerator, mut weight) = (Total::default(), Total::default());
+    for window in sorted.windows(2) {
+        let duration = Value::Int64(window[1].0 - window[0].0);
+        // 输入在update时已校验为数值
+        let _ = numerator.add(&window[0].1, Some(&duration), FinancialKind::Twap);
+        let _ = weight.add(&duration, None, FinancialKind::Twap);
+    }
+    if weight.approx == 0.0 {
+        let mut sum = Total::default();
+        for (_, price) in &sorted {
+            let _ = sum.add(price, None, FinancialKind::Twap);
+        }
+        return sum.ratio(&Total::count(sorted.len() as u64));
+    }
+    numerator.ratio(&weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_core::types::{TimestampNs, Volume};
+
+    fn price(text: &str) -> Value {
+        Value::Price(Price::new(text.parse().unwrap()))
+    }
+
+    fn args(a: Value, b: Value) -> Value {
+        Value::Array(vec![a, b])
+    }
+
+    #[test]
+    fn test_vwap_keeps_decimal_precision() {
+        let inputs = vec![
+            args(price("100.10"), Value::Volume(Volume::new(300))),
+            args(price("100.20"), Value::Volume(Volume::new(100))),
+        ];
+        // (100.10×300 + 100.20×100) / 400 = 100.125
+        assert_eq!(FinancialState::evaluate(FinancialKind::Vwap, &inputs).unwrap(), price("100.125"));
+
+        let floats = vec![args(Value::Float64(1.0), Value::Int64(1)), args(Value::Float64(2.0), Value::Int64(3))];
+        assert_eq!(FinancialState::evaluate(FinancialKind::WeightedAvg, &floats).unwrap(), Value::Float64(1.75));
+        assert_eq!(FinancialState::evaluate(FinancialKind::Vwap, &[]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_twap_ohlc_and_by() {
+        let ts = |seconds: i64| Value::Timestamp(TimestampNs::from_nanos(seconds * 1_000_000_000));
+        // 10持续30秒，20持续10秒，最后一个观测点不计权重
+        let inputs = vec![args(price("20"), ts(30)), args(price("10"), ts(0)), args(price("30"), ts(40))];
+        assert_eq!(FinancialState::evaluate(FinancialKind::Twap, &inputs).unwrap(), price("12.5"));
+        assert_eq!(FinancialState::evaluate(FinancialKind::LastBy, &inputs).unwrap(), price("30"));
+        assert_eq!(FinancialState::evaluate(FinancialKind::FirstBy, &inputs).unwrap(), price("10"));
+
+        // 开盘与收盘按时间戳而不是到达顺序选取
+        let ticks = vec![args(price("11"), ts(3)), args(price("12"), ts(1)), args(price("10"), ts(0)), args(price("9"), ts(2))];
+        let Value::Struct(bar) = FinancialState::evaluate(FinancialKind::Ohlc, &ticks).unwrap() else { panic!() };
+        assert_eq!((&bar["open"], &bar["high"], &bar["low"], &bar["close"]), (&price("10"), &price("12"), &price("9"), &price("11")));
+
+        let quotes = vec![args(price("9.98"), price("10.02")), args(price("9.99"), price("10.01"))];
+        assert_eq!(FinancialState::evaluate(FinancialKind::Spread, &quotes).unwrap(), price("0.03"));
+    }
+
+    #[test]
+    fn test_merge_financial_states() {
+        let ts = |seconds: i64| Value::Timestamp(TimestampNs::from_nanos(seconds * 1_000_000_000));
+        let ticks: Vec<Value> = [("10", 0), ("12", 1), ("9", 2), ("11", 3), ("8", 3)]
+            .into_iter()
+            .map(|(px, seconds)| args(price(px), ts(seconds)))
+            .collect();
+        let expected = FinancialState::evaluate(FinancialKind::Ohlc, &ticks).unwrap();
+        let Value::Struct(bar) = &expected else { panic!() };
+        // 同一时间戳按价格定先后
+        assert_eq!((&bar["open"], &bar["close"]), (&price("10"), &price("11")));
+        // 后半段的部分状态先合并，结果与按时间整体计算一致
+        let (mut early, mut late) = (FinancialState::new(FinancialKind::Ohlc), FinancialState::new(FinancialKind::Ohlc));
+        for value in &ticks[..2] {
+            early.update(value).unwrap();
+        }
+        for value in ticks[2..].iter().rev() {
+            late.update(value).unwrap();
+        }
+        late.merge(&early).unwrap();
+        assert_eq!(late.finish(), expected);
+
+        let mut vwap = FinancialState::new(FinancialKind::Vwap);
+        vwap.update(&args(price("10"), Value::Int64(1))).unwrap();
+        let mut other = FinancialState::new(FinancialKind::Vwap);
+        other.update(&args(price("13"), Value::Int64(2))).unwrap();
+        vwap.merge(&other).unwrap();
+        assert_eq!(vwap.finish(), price("12"));
+        assert!(vwap.merge(&FinancialState::new(FinancialKind::WeightedAvg)).is_err());
+    }
+}
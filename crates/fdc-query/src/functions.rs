@@ -21,9 +21,23 @@ impl BuiltinFunctions {
         functions.insert("LOWER".to_string(), lower as fn(&[Value]) -> Result<Value>);
         functions.insert("LENGTH".to_string(), length as fn(&[Value]) -> Result<Value>);
         
+        // 时间函数
+        functions.insert("TIME_BUCKET".to_string(), crate::sampling::time_bucket as fn(&[Value]) -> Result<Value>);
+        
+        // 掩码函数
+        functions.insert("MASK".to_string(), crate::security::mask as fn(&[Value]) -> Result<Value>);
+        functions.insert("MASK_SHOW_FIRST".to_string(), crate::security::mask_show_first as fn(&[Value]) -> Result<Value>);
+        functions.insert("MASK_SHOW_LAST".to_string(), crate::security::mask_show_last as fn(&[Value]) -> Result<Value>);
+        functions.insert("MASK_HASH".to_string(), crate::security::mask_hash as fn(&[Value]) -> Result<Value>);
+        
         Self { functions }
     }
     
+    /// 是否为内置函数
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(&name.to_uppercase())
+    }
+    
     pub fn call(&self, name: &str, args: &[Value]) -> Result<Value> {
         if let Some(func) = self.functions.get(&name.to_uppercase()) {
             func(args)
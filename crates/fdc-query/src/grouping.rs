@@ -0,0 +1,485 @@
+//! Hash grouping and aggregate evaluation for the native executor
+
+use crate::{
+    aggregates::{aggregate_input, Accumulator, AggregateFunction},
+    expressions::ExpressionEvaluator,
+    joins::{estimate_row_size, normalize_key_value, Row},
+    spill::SpillFiles,
+    udf::FunctionRegistry,
+};
+use fdc_core::{error::{Error, Result}, types::Value};
+use sqlparser::ast::{
+    visit_expressions, DuplicateTreatment, Expr, FunctionArg, FunctionArgExpr, FunctionArguments,
+};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+
+/// 查询中出现的一次聚合调用
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateCall {
+    /// 结果在分组行中的列名（即调用表达式文本）
+    pub key: String,
+    /// 聚合函数
+    pub function: AggregateFunction,
+    /// 参数表达式（`COUNT(*)`为空）
+    pub arguments: Vec<Expr>,
+    /// 是否为`DISTINCT`聚合
+    pub distinct: bool,
+}
+
+impl AggregateCall {
+    /// 在一行上求聚合的输入值（见`aggregate_input`）
+    pub fn input(&self, row: &Row, evaluator: &ExpressionEvaluator) -> Result<Value> {
+        let values = self.arguments.iter()
+            .map(|arg| evaluator.evaluate(arg, row))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(aggregate_input(values))
+    }
+}
+
+/// 收集表达式中的内置聚合调用（忽略带OVER的窗口函数），按出现顺序去重
+pub fn collect_aggregates<'a>(exprs: impl IntoIterator<Item = &'a Expr>) -> Result<Vec<AggregateCall>> {
+    collect_aggregates_with(exprs, None)
+}
+
+/// 收集表达式中的聚合调用，`functions`中注册的用户自定义聚合也会被识别
+pub fn collect_aggregates_with<'a>(
+    exprs: impl IntoIterator<Item = &'a Expr>,
+    functions: Option<&FunctionRegistry>,
+) -> Result<Vec<AggregateCall>> {
+    let mut calls: Vec<AggregateCall> = Vec::new();
+    let mut error = None;
+    for expr in exprs {
+        let _ = visit_expressions(expr, |e| {
+            let Expr::Function(function) = e else {
+                return ControlFlow::Continue(());
+            };
+            if function.over.is_some() {
+                return ControlFlow::Continue(());
+            }
+            let Some(aggregate) = AggregateFunction::resolve(&function.name.to_string(), functions) else {
+                return ControlFlow::Continue(());
+            };
+            let key = e.to_string();
+            if calls.iter().any(|c| c.key == key) {
+                return ControlFlow::Continue(());
+            }
+            let (arguments, distinct) = match &function.args {
+                FunctionArguments::List(list) => {
+                    let arguments = match list.args.as_slice() {
+                        [] | [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)] => Vec::new(),
+                        args => {
+                            let exprs: Vec<Expr> = args.iter().filter_map(|arg| match arg {
+                                FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) => Some(arg.clone()),
+                                _ => None,
+                            }).collect();
+                            if exprs.len() != args.len() {
+                                error = Some(Error::validation(format!("Invalid arguments to {}", function.name)));
+                                return ControlFlow::Break(());
+                            }
+                            exprs
+                        }
+                    };
+                    (arguments, list.duplicate_treatment == Some(DuplicateTreatment::Distinct))
+                }
+                _ => (Vec::new(), false),
+            };
+            // `percentile_cont(p) WITHIN GROUP (ORDER BY x)`等价于`percentile_cont(x, p)`
+            let arguments = match function.within_group.as_slice() {
+                [] => arguments,
+                [order] if order.asc != Some(false) => std::iter::once(order.expr.clone()).chain(arguments).collect(),
+                _ => {
+                    error = Some(Error::validation(format!(
+                        "{} WITHIN GROUP requires a single ascending ORDER BY expression", function.name
+                    )));
+                    return ControlFlow::Break(());
+                }
+            };
+            let arity = aggregate.arity();
+            // COUNT(*)不带参数，其余聚合的参数个数必须与定义一致
+            if arguments.len() != arity && !(arguments.is_empty() && aggregate == AggregateFunction::Count) {
+                error = Some(Error::validation(format!("{} takes exactly {} argument(s)", function.name, arity)));
+                return ControlFlow::Break(());
+            }
+            calls.push(AggregateCall { key, function: aggregate, arguments, distinct });
+            ControlFlow::Continue(())
+        });
+        if let Some(error) = error.take() {
+            return Err(error);
+        }
+    }
+    Ok(calls)
+}
+
+/// 分组聚合执行配置
+#[derive(Debug, Clone)]
+pub struct AggregateConfig {
+    /// 哈希分组在内存中保留的字节数上限，超出后按分组键哈希分区落盘
+    pub memory_budget: usize,
+    /// 落盘分区数
+    pub spill_partitions: usize,
+    /// 落盘目录
+    pub spill_dir: PathBuf,
+}
+
+impl Default for AggregateConfig {
+    fn default() -> Self {
+        Self {
+            memory_budget: 64 * 1024 * 1024,
+            spill_partitions: 16,
+            spill_dir: std::env::temp_dir(),
+        }
+    }
+}
+
+impl AggregateConfig {
+    /// 设置内存预算
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    /// 设置落盘目录
+    pub fn with_spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = dir.into();
+        self
+    }
+
+    /// 输入为`input_bytes`字节时分组落盘的分区数，放得进内存时为0
+    pub fn spill_partitions_for(&self, input_bytes: usize) -> usize {
+        if input_bytes <= self.memory_budget || self.spill_partitions <= 1 {
+            0
+        } else {
+            self.spill_partitions
+        }
+    }
+}
+
+/// 按分组表达式对行进行哈希分组并计算聚合
+///
+/// 每个分组输出一行：以组内第一行为基础，加入分组表达式的值（以表达式文本为列名）
+/// 和各聚合调用的结果，供后续HAVING与投影按表达式文本直接取值。
+/// 分组顺序为首次出现的顺序；没有GROUP BY时即使输入为空也输出一行。
+pub fn group_rows(
+    rows: Vec<Row>,
+    group_by: &[Expr],
+    aggregates: &[AggregateCall],
+    evaluator: &ExpressionEvaluator,
+) -> Result<Vec<Row>> {
+    let groups = hash_group(rows.into_iter().enumerate(), group_by, aggregates, evaluator)?;
+    collect_groups(groups, group_by, aggregates)
+}
+
+/// 与`group_rows`相同，但输入超出内存预算时先按分组键哈希把行分区落盘，再逐个分区分组
+///
+/// 同一分组的行落在同一分区，结果（含分组顺序）与`group_rows`一致。
+/// 返回的第二项是落盘的分区数（未落盘时为0）。
+pub fn group_rows_spilling(
+    rows: Vec<Row>,
+    group_by: &[Expr],
+    aggregates: &[AggregateCall],
+    evaluator: &ExpressionEvaluator,
+    config: &AggregateConfig,
+) -> Result<(Vec<Row>, usize)> {
+    let input_bytes: usize = rows.iter().map(estimate_row_size).sum();
+    // 没有GROUP BY时只有一个分组，无法按键分区
+    let partitions = if group_by.is_empty() { 0 } else { config.spill_partitions_for(input_bytes) };
+    if partitions == 0 {
+        return Ok((group_rows(rows, group_by, aggregates, evaluator)?, 0));
+    }
+
+    tracing::debug!(
+        "Aggregation input ({} bytes) exceeds budget ({} bytes), spilling to {} partitions",
+        input_bytes, config.memory_budget, partitions
+    );
+    let spill = SpillFiles::new(&config.spill_dir, "aggregate", partitions);
+    spill.write_partitioned(rows.into_iter().enumerate(), |(_, row)| {
+        let (_, normalized) = group_key(row, group_by, evaluator)?;
+        Ok(key_partition(&normalized, partitions))
+    })?;
+    let mut groups = Vec::new();
+    for partition in 0..partitions {
+        let rows: Vec<(usize, Row)> = spill.read(partition)?;
+        groups.extend(hash_group(rows, group_by, aggregates, evaluator)?);
+    }
+    // 恢复按首次出现排列的分组顺序
+    groups.sort_by_key(|(ordinal, _)| *ordinal);
+    Ok((collect_groups(groups, group_by, aggregates)?, partitions))
+}
+
+/// 哈希分组并求出各组聚合结果，每组附带组内第一行的输入序号
+fn hash_group(
+    rows: impl IntoIterator<Item = (usize, Row)>,
+    group_by: &[Expr],
+    aggregates: &[AggregateCall],
+    evaluator: &ExpressionEvaluator,
+) -> Result<Vec<(usize, Row)>> {
+    let mut index: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    let mut groups: Vec<(usize, Row, Vec<Vec<Value>>)> = Vec::new();
+
+    for (ordinal, row) in rows {
+        evaluator.checkpoint()?;
+        let (keys, normalized) = group_key(&row, group_by, evaluator)?;
+
+        let mut inputs = Vec::with_capacity(aggregates.len());
+        for call in aggregates {
+            // COUNT(*)的输入恒为true，统计所有行
+            inputs.push(call.input(&row, evaluator)?);
+        }
+
+        let position = *index.entry(normalized).or_insert_with(|| {
+            let mut base = row.clone();
+            for (expr, key) in group_by.iter().zip(keys) {
+                base.insert(expr.to_string(), key);
+            }
+            groups.push((ordinal, base, vec![Vec::new(); aggregates.len()]));
+            groups.len() - 1
+        });
+        for (values, input) in groups[position].2.iter_mut().zip(inputs) {
+            if !matches!(input, Value::Null) {
+                values.push(input);
+            }
+        }
+    }
+
+    groups.into_iter()
+        .map(|(ordinal, mut row, values)| {
+            for (call, values) in aggregates.iter().zip(values) {
+                row.insert(call.key.clone(), finish(call, values)?);
+            }
+            Ok((ordinal, row))
+        })
+        .collect()
+}
+
+/// 去掉输入序号；没有GROUP BY且输入为空时补出一行空聚合结果
+fn collect_groups(groups: Vec<(usize, Row)>, group_by: &[Expr], aggregates: &[AggregateCall]) -> Result<Vec<Row>> {
+    if groups.is_empty() && group_by.is_empty() {
+        let mut row = Row::new();
+        for call in aggregates {
+            row.insert(call.key.clone(), finish(call, Vec::new())?);
+        }
+        return Ok(vec![row]);
+    }
+    Ok(groups.into_iter().map(|(_, row)| row).collect())
+}
+
+/// 求一行的分组键及其规范化形式
+fn group_key(row: &Row, group_by: &[Expr], evaluator: &ExpressionEvaluator) -> Result<(Vec<Value>, Vec<Option<String>>)> {
+    let keys = group_by.iter()
+        .map(|expr| evaluator.evaluate(expr, row))
+        .collect::<Result<Vec<_>>>()?;
+    let normalized = keys.iter().map(normalize_key_value).collect();
+    Ok((keys, normalized))
+}
+
+fn key_partition(key: &[Option<String>], partitions: usize) -> usize {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % partitions as u64) as usize
+}
+
+/// 一个分组的部分聚合结果：分片在本地数据上计算，汇集后与其他分片的同键分组合并
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialGroup {
+    /// 规范化后的分组键
+    pub key: Vec<Option<String>>,
+    /// 组内第一行，已加入分组表达式的值
+    pub row: Row,
+    /// 各聚合调用的部分状态，与聚合调用一一对应
+    pub states: Vec<Accumulator>,
+}
+
+/// 按分组表达式计算可合并的部分聚合状态（不支持DISTINCT聚合）
+pub fn partial_group_rows(
+    rows: Vec<Row>,
+    group_by: &[Expr],
+    aggregates: &[AggregateCall],
+    evaluator: &ExpressionEvaluator,
+) -> Result<Vec<PartialGroup>> {
+    if let Some(call) = aggregates.iter().find(|call| call.distinct) {
+        return Err(Error::unimplemented(format!("Partial aggregation of DISTINCT aggregate {}", call.key)));
+    }
+    let mut index: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    let mut groups: Vec<PartialGroup> = Vec::new();
+    for row in rows {
+        evaluator.checkpoint()?;
+        let (keys, normalized) = group_key(&row, group_by, evaluator)?;
+        let position = match index.get(&normalized) {
+            Some(&position) => position,
+            None => {
+                let mut base = row.clone();
+                for (expr, key) in group_by.iter().zip(keys) {
+                    base.insert(expr.to_string(), key);
+                }
+                index.insert(normalized.clone(), groups.len());
+                groups.push(PartialGroup {
+                    key: normalized,
+                    row: base,
+                    states: aggregates.iter().map(|call| call.function.accumulator()).collect(),
+                });
+                groups.len() - 1
+            }
+        };
+        for (state, call) in groups[position].states.iter_mut().zip(aggregates) {
+            state.update(&call.input(&row, evaluator)?)?;
+        }
+    }
+    Ok(groups)
+}
+
+/// 合并各分片的部分聚合并求出最终结果，输出格式与`group_rows`相同
+///
+/// 分组顺序为按分片顺序首次出现的顺序；没有GROUP BY时即使所有分片都为空也输出一行。
+pub fn merge_partial_groups(
+    partials: Vec<Vec<PartialGroup>>,
+    group_by: &[Expr],
+    aggregates: &[AggregateCall],
+) -> Result<Vec<Row>> {
+    let mut index: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    let mut groups: Vec<PartialGroup> = Vec::new();
+    for group in partials.into_iter().flatten() {
+        if group.states.len() != aggregates.len() {
+            return Err(Error::internal("Partial aggregate state does not match the aggregate calls"));
+        }
+        match index.get(&group.key) {
+            Some(&position) => {
+                for (state, other) in groups[position].states.iter_mut().zip(&group.states) {
+                    state.merge(other)?;
+                }
+            }
+            None => {
+                index.insert(group.key.clone(), groups.len());
+                groups.push(group);
+            }
+        }
+    }
+
+    if groups.is_empty() && group_by.is_empty() {
+        groups.push(PartialGroup {
+            key: Vec::new(),
+            row: Row::new(),
+            states: aggregates.iter().map(|call| call.function.accumulator()).collect(),
+        });
+    }
+
+    groups.into_iter()
+        .map(|group| {
+            let mut row = group.row;
+            for (call, state) in aggregates.iter().zip(&group.states) {
+                row.insert(call.key.clone(), state.finish()?);
+            }
+            Ok(row)
+        })
+        .collect()
+}
+
+fn finish(call: &AggregateCall, mut values: Vec<Value>) -> Result<Value> {
+    if call.distinct {
+        let mut seen = HashSet::new();
+        values.retain(|v| seen.insert(normalize_key_value(v)));
+    }
+    if values.is_empty() {
+        return Ok(match call.function {
+            AggregateFunction::Count => Value::Int64(0),
+            _ => Value::Null,
+        });
+    }
+    call.function.apply(&values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::{dialect::GenericDialect, parser::Parser};
+
+    fn exprs(sql: &str) -> Vec<Expr> {
+        Parser::new(&GenericDialect {})
+            .try_with_sql(sql).unwrap()
+            .parse_comma_separated(Parser::parse_expr).unwrap()
+    }
+
+    fn row(symbol: &str, price: Option<f64>) -> Row {
+        HashMap::from([
+            ("symbol".to_string(), Value::String(symbol.to_string())),
+            ("price".to_string(), price.map(Value::Float64).unwrap_or(Value::Null)),
+        ])
+    }
+
+    #[test]
+    fn test_group_rows() {
+        let projection = exprs("symbol, COUNT(*), COUNT(price), SUM(price), COUNT(DISTINCT price)");
+        let calls = collect_aggregates(&projection).unwrap();
+        assert_eq!(calls.len(), 4);
+
+        let rows = vec![row("A", Some(1.0)), row("B", Some(5.0)), row("A", None), row("A", Some(1.0))];
+        let grouped = group_rows(rows, &exprs("symbol"), &calls, &ExpressionEvaluator::new()).unwrap();
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0]["symbol"], Value::String("A".to_string()));
+        assert_eq!(grouped[0]["COUNT(*)"], Value::Int64(3));
+        assert_eq!(grouped[0]["COUNT(price)"], Value::Int64(2));
+        assert_eq!(grouped[0]["SUM(price)"], Value::Float64(2.0));
+        assert_eq!(grouped[0]["COUNT(DISTINCT price)"], Value::Int64(1));
+    }
+
+    #[test]
+    fn test_global_aggregate_on_empty_input() {
+        let calls = collect_aggregates(&exprs("COUNT(*), MAX(price)")).unwrap();
+        let grouped = group_rows(Vec::new(), &[], &calls, &ExpressionEvaluator::new()).unwrap();
+        assert_eq!(grouped.len(), 1);
+        assert_eq!(grouped[0]["COUNT(*)"], Value::Int64(0));
+        assert_eq!(grouped[0]["MAX(price)"], Value::Null);
+    }
+
+    #[test]
+    fn test_merge_partial_groups() {
+        let calls = collect_aggregates(&exprs("COUNT(*), SUM(price), AVG(price), MAX(price)")).unwrap();
+        let group_by = exprs("symbol");
+        let evaluator = ExpressionEvaluator::new();
+        let rows = vec![row("A", Some(1.0)), row("B", Some(5.0)), row("A", None), row("A", Some(3.0)), row("B", Some(7.0))];
+
+        let expected = group_rows(rows.clone(), &group_by, &calls, &evaluator).unwrap();
+        let (left, right) = rows.split_at(2);
+        let partials = vec![
+            partial_group_rows(left.to_vec(), &group_by, &calls, &evaluator).unwrap(),
+            partial_group_rows(right.to_vec(), &group_by, &calls, &evaluator).unwrap(),
+        ];
+        let merged = merge_partial_groups(partials, &group_by, &calls).unwrap();
+        assert_eq!(merged.len(), 2);
+        for (merged, expected) in merged.iter().zip(&expected) {
+            for call in &calls {
+                assert_eq!(merged[&call.key], expected[&call.key], "{}", call.key);
+            }
+        }
+
+        let empty = merge_partial_groups(vec![Vec::new(), Vec::new()], &[], &calls).unwrap();
+        assert_eq!(empty.len(), 1);
+        assert_eq!(empty[0]["COUNT(*)"], Value::Int64(0));
+    }
+
+    #[test]
+    fn test_spilling_group_rows() {
+        let calls = collect_aggregates(&exprs("COUNT(*), SUM(price), COUNT(DISTINCT price)")).unwrap();
+        let group_by = exprs("symbol");
+        let evaluator = ExpressionEvaluator::new();
+        let symbols = ["E", "B", "D", "A", "C"];
+        let rows: Vec<Row> = (0..30).map(|i| row(symbols[i % 5], (i % 4 != 0).then_some((i % 3) as f64))).collect();
+
+        let expected = group_rows(rows.clone(), &group_by, &calls, &evaluator).unwrap();
+        let (grouped, spilled) = group_rows_spilling(rows.clone(), &group_by, &calls, &evaluator, &AggregateConfig::default()).unwrap();
+        assert_eq!(spilled, 0);
+        assert_eq!(grouped, expected);
+
+        let config = AggregateConfig::default().with_memory_budget(0);
+        let (grouped, spilled) = group_rows_spilling(rows, &group_by, &calls, &evaluator, &config).unwrap();
+        assert_eq!(spilled, 16);
+        assert_eq!(grouped, expected);
+        assert_eq!(grouped[0]["symbol"], Value::String("E".to_string()));
+
+        let (global, spilled) = group_rows_spilling(Vec::new(), &[], &calls, &evaluator, &config).unwrap();
+        assert_eq!(spilled, 0);
+        assert_eq!(global[0]["COUNT(*)"], Value::Int64(0));
+    }
+}
@@ -1,60 +1,669 @@
 //! Join operations
 
-use fdc_core::{error::Result, types::Value};
-use std::collections::HashMap;
+use crate::expressions::compare_values;
+use crate::spill::{PartitionedWriter, SpillFiles};
+use crate::time_joins::{AsofJoinStream, AsofSpec, WindowJoinSpec, WindowJoinStream};
+use fdc_core::{error::{Error, Result}, types::Value};
+use rust_decimal::prelude::ToPrimitive;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+pub use crate::planner::JoinType;
+
+/// 行数据
+pub type Row = HashMap<String, Value>;
+
+/// 连接的附加条件（ON子句中无法作为等值键的部分）
+pub type JoinPredicate<'a> = dyn Fn(&Row) -> Result<bool> + 'a;
+
+/// 连接键定义
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinSpec {
+    /// 连接类型
+    pub join_type: JoinType,
+    /// 左侧键列
+    pub left_keys: Vec<String>,
+    /// 右侧键列
+    pub right_keys: Vec<String>,
+}
+
+impl JoinSpec {
+    /// 创建新的连接定义
+    pub fn new(join_type: JoinType, left_keys: Vec<String>, right_keys: Vec<String>) -> Self {
+        Self { join_type, left_keys, right_keys }
+    }
+
+    /// 单列等值连接
+    pub fn on(join_type: JoinType, left_key: impl Into<String>, right_key: impl Into<String>) -> Self {
+        Self::new(join_type, vec![left_key.into()], vec![right_key.into()])
+    }
+}
+
+/// 连接执行配置
+#[derive(Debug, Clone)]
+pub struct JoinConfig {
+    /// 哈希表构建侧的内存预算（字节），超出后分区落盘
+    pub memory_budget: usize,
+    /// 落盘分区数
+    pub spill_partitions: usize,
+    /// 落盘目录
+    pub spill_dir: PathBuf,
+}
+
+impl Default for JoinConfig {
+    fn default() -> Self {
+        Self {
+            memory_budget: 64 * 1024 * 1024,
+            spill_partitions: 16,
+            spill_dir: std::env::temp_dir(),
+        }
+    }
+}
+
+impl JoinConfig {
+    /// 设置内存预算
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    /// 设置落盘目录
+    pub fn with_spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = dir.into();
+        self
+    }
+    
+    /// 构建侧为`build_bytes`字节时哈希连接落盘的分区数，放得进内存时为0
+    pub fn spill_partitions_for(&self, build_bytes: usize) -> usize {
+        if build_bytes <= self.memory_budget || self.spill_partitions <= 1 {
+            0
+        } else {
+            self.spill_partitions
+        }
+    }
+}
 
 /// 连接操作
 pub struct JoinOperations;
 
 impl JoinOperations {
+    /// 单列内连接，右侧列以`right_`为前缀（兼容旧接口）
     pub fn inner_join(
         left: &[HashMap<String, Value>],
         right: &[HashMap<String, Value>],
         left_key: &str,
         right_key: &str,
     ) -> Result<Vec<HashMap<String, Value>>> {
-        let mut result = Vec::new();
-        
-        for left_row in left {
-            if let Some(left_value) = left_row.get(left_key) {
-                for right_row in right {
-                    if let Some(right_value) = right_row.get(right_key) {
-                        if left_value == right_value {
-                            let mut joined_row = left_row.clone();
-                            for (key, value) in right_row {
-                                joined_row.insert(format!("right_{}", key), value.clone());
-                            }
-                            result.push(joined_row);
-                        }
+        let right: Vec<Row> = right.iter()
+            .map(|row| row.iter().map(|(k, v)| (format!("right_{}", k), v.clone())).collect())
+            .collect();
+        let spec = JoinSpec::on(JoinType::Inner, left_key, format!("right_{}", right_key));
+        Self::hash_join(left.to_vec(), right, &spec, &JoinConfig::default(), None)
+    }
+
+    /// 用表别名限定列名（`alias.column`）
+    pub fn qualify(rows: Vec<Row>, alias: &str) -> Vec<Row> {
+        rows.into_iter()
+            .map(|row| row.into_iter().map(|(k, v)| (format!("{}.{}", alias, k), v)).collect())
+            .collect()
+    }
+
+    /// 哈希连接：右侧为构建侧，构建侧超出内存预算时按键哈希分区落盘（Grace Hash Join）
+    ///
+    /// 构建侧边读入边累计大小，超出预算后已缓冲的行与其后的行直接写入分区文件并释放；
+    /// 落盘后探测侧同样逐行分区写出，再逐个分区读回构建侧建表、流式读取探测侧连接。
+    pub fn hash_join(
+        left: Vec<Row>,
+        right: Vec<Row>,
+        spec: &JoinSpec,
+        config: &JoinConfig,
+        residual: Option<&JoinPredicate<'_>>,
+    ) -> Result<Vec<Row>> {
+        grace_hash_join(left, right, spec, config, residual).map(|(rows, _)| rows)
+    }
+
+    /// 排序归并连接：适用于已按连接键排序的输入（例如按时间键存储的表）
+    ///
+    /// `assume_sorted`为false时先对两侧排序。
+    pub fn sort_merge_join(
+        left: &[Row],
+        right: &[Row],
+        spec: &JoinSpec,
+        assume_sorted: bool,
+        residual: Option<&JoinPredicate<'_>>,
+    ) -> Result<Vec<Row>> {
+        validate_spec(spec)?;
+        let layout = JoinLayout::new(left, right);
+
+        let mut left: Vec<&Row> = left.iter().collect();
+        let mut right: Vec<&Row> = right.iter().collect();
+        if !assume_sorted {
+            left.sort_by(|a, b| compare_keys(a, &spec.left_keys, b, &spec.left_keys));
+            right.sort_by(|a, b| compare_keys(a, &spec.right_keys, b, &spec.right_keys));
+        }
+
+        let mut output = Vec::new();
+        let mut right_matched = vec![false; right.len()];
+        let (mut i, mut j) = (0, 0);
+
+        while i < left.len() {
+            if has_null_key(left[i], &spec.left_keys) {
+                emit_unmatched_left(&mut output, left[i], spec, &layout);
+                i += 1;
+                continue;
+            }
+            while j < right.len()
+                && (has_null_key(right[j], &spec.right_keys)
+                    || compare_keys(right[j], &spec.right_keys, left[i], &spec.left_keys) == Ordering::Less)
+            {
+                j += 1;
+            }
+
+            // 找出左右两侧键相等的分组
+            let left_end = (i..left.len())
+                .find(|&k| compare_keys(left[k], &spec.left_keys, left[i], &spec.left_keys) != Ordering::Equal)
+                .unwrap_or(left.len());
+            let right_end = (j..right.len())
+                .find(|&k| compare_keys(right[k], &spec.right_keys, left[i], &spec.left_keys) != Ordering::Equal)
+                .unwrap_or(right.len());
+
+            for left_row in &left[i..left_end] {
+                let mut matched = false;
+                for (offset, right_row) in right[j..right_end].iter().enumerate() {
+                    let joined = merge_rows(left_row, right_row);
+                    if !passes(residual, &joined)? {
+                        continue;
                     }
+                    matched = true;
+                    right_matched[j + offset] = true;
+                    if emits_pairs(&spec.join_type) {
+                        output.push(joined);
+                    }
+                }
+                finish_left_row(&mut output, left_row, matched, spec, &layout);
+            }
+            i = left_end;
+        }
+
+        if matches!(spec.join_type, JoinType::Right | JoinType::Full) {
+            for (row, _) in right.iter().zip(&right_matched).filter(|(_, m)| !**m) {
+                output.push(pad_right(row, &layout));
+            }
+        }
+        Ok(output)
+    }
+
+    /// ASOF连接：两侧按时间列升序，详见[`AsofJoinStream`]
+    pub fn asof_join(left: Vec<Row>, right: Vec<Row>, spec: AsofSpec) -> Result<Vec<Row>> {
+        let right_columns = column_set(&right);
+        AsofJoinStream::new(left.into_iter(), right.into_iter(), spec)?
+            .with_right_columns(right_columns)
+            .collect()
+    }
+
+    /// 窗口连接：两侧按时间列升序，详见[`WindowJoinStream`]
+    pub fn window_join(left: Vec<Row>, right: Vec<Row>, spec: WindowJoinSpec) -> Result<Vec<Row>> {
+        WindowJoinStream::new(left.into_iter(), right.into_iter(), spec)?.collect()
+    }
+
+    /// 嵌套循环连接：用于CROSS JOIN和无等值条件的连接
+    pub fn nested_loop_join(
+        left: &[Row],
+        right: &[Row],
+        join_type: JoinType,
+        predicate: Option<&JoinPredicate<'_>>,
+    ) -> Result<Vec<Row>> {
+        let spec = JoinSpec::new(join_type, Vec::new(), Vec::new());
+        let layout = JoinLayout::new(left, right);
+        let mut output = Vec::new();
+        let mut right_matched = vec![false; right.len()];
+
+        for left_row in left {
+            let mut matched = false;
+            for (index, right_row) in right.iter().enumerate() {
+                let joined = merge_rows(left_row, right_row);
+                if !passes(predicate, &joined)? {
+                    continue;
+                }
+                matched = true;
+                right_matched[index] = true;
+                if emits_pairs(&spec.join_type) {
+                    output.push(joined);
+                }
+            }
+            finish_left_row(&mut output, left_row, matched, &spec, &layout);
+        }
+
+        if matches!(spec.join_type, JoinType::Right | JoinType::Full) {
+            for (row, _) in right.iter().zip(&right_matched).filter(|(_, m)| !**m) {
+                output.push(pad_right(row, &layout));
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// 两侧的列集合，用于外连接补NULL
+struct JoinLayout {
+    left_columns: Vec<String>,
+    right_columns: Vec<String>,
+}
+
+impl JoinLayout {
+    fn new(left: &[Row], right: &[Row]) -> Self {
+        Self {
+            left_columns: column_set(left),
+            right_columns: column_set(right),
+        }
+    }
+}
+
+fn column_set(rows: &[Row]) -> Vec<String> {
+    let mut columns: HashSet<&String> = HashSet::new();
+    for row in rows {
+        columns.extend(row.keys());
+    }
+    columns.into_iter().cloned().collect()
+}
+
+fn validate_spec(spec: &JoinSpec) -> Result<()> {
+    if spec.left_keys.len() != spec.right_keys.len() || spec.left_keys.is_empty() {
+        return Err(Error::validation("Join requires the same non-zero number of keys on both sides"));
+    }
+    if spec.join_type == JoinType::Cross {
+        return Err(Error::validation("CROSS JOIN has no join keys; use nested_loop_join"));
+    }
+    Ok(())
+}
+
+/// 哈希连接的落盘情况
+#[derive(Debug, Default, Clone, PartialEq)]
+struct JoinSpill {
+    /// 落盘分区数，未落盘时为0
+    partitions: usize,
+    /// 写入分区文件的构建侧行数
+    build_rows: usize,
+    /// 写入分区文件的探测侧行数
+    probe_rows: usize,
+    /// 构建侧同时驻留内存的最大估算字节数
+    peak_build_bytes: usize,
+}
+
+fn grace_hash_join(
+    left: Vec<Row>,
+    right: Vec<Row>,
+    spec: &JoinSpec,
+    config: &JoinConfig,
+    residual: Option<&JoinPredicate<'_>>,
+) -> Result<(Vec<Row>, JoinSpill)> {
+    validate_spec(spec)?;
+    let layout = JoinLayout::new(&left, &right);
+    let mut spill = JoinSpill::default();
+
+    let mut buffered = Vec::new();
+    let mut buffered_bytes = 0;
+    let mut build_files: Option<(SpillFiles, PartitionedWriter)> = None;
+    for row in right {
+        if let Some((_, writer)) = build_files.as_mut() {
+            writer.write(partition_of(&row, &spec.right_keys, spill.partitions), &row)?;
+            spill.build_rows += 1;
+            continue;
+        }
+        buffered_bytes += estimate_row_size(&row);
+        buffered.push(row);
+        spill.peak_build_bytes = spill.peak_build_bytes.max(buffered_bytes);
+
+        let partitions = config.spill_partitions_for(buffered_bytes);
+        if partitions > 0 {
+            tracing::debug!(
+                "Hash join build side exceeds budget ({} bytes), spilling to {} partitions",
+                config.memory_budget, partitions
+            );
+            let files = SpillFiles::new(&config.spill_dir, "join-right", partitions);
+            let mut writer = files.partitioned_writer()?;
+            // 写出后立即释放已缓冲的行
+            for row in std::mem::take(&mut buffered) {
+                writer.write(partition_of(&row, &spec.right_keys, partitions), &row)?;
+                spill.build_rows += 1;
+            }
+            buffered_bytes = 0;
+            spill.partitions = partitions;
+            build_files = Some((files, writer));
+        }
+    }
+
+    let Some((build_files, writer)) = build_files else {
+        let rows = hash_join_in_memory(left.into_iter().map(Ok), &buffered, spec, &layout, residual)?;
+        return Ok((rows, spill));
+    };
+    writer.finish()?;
+
+    let partitions = spill.partitions;
+    let probe_files = SpillFiles::new(&config.spill_dir, "join-left", partitions);
+    let mut writer = probe_files.partitioned_writer()?;
+    for row in left {
+        writer.write(partition_of(&row, &spec.left_keys, partitions), &row)?;
+        spill.probe_rows += 1;
+    }
+    writer.finish()?;
+
+    let mut output = Vec::new();
+    for partition in 0..partitions {
+        let build: Vec<Row> = build_files.read(partition)?;
+        spill.peak_build_bytes = spill.peak_build_bytes.max(build.iter().map(estimate_row_size).sum());
+        output.extend(hash_join_in_memory(probe_files.reader(partition)?, &build, spec, &layout, residual)?);
+    }
+    Ok((output, spill))
+}
+
+/// 用`right`建哈希表，逐行探测`left`
+fn hash_join_in_memory(
+    left: impl IntoIterator<Item = Result<Row>>,
+    right: &[Row],
+    spec: &JoinSpec,
+    layout: &JoinLayout,
+    residual: Option<&JoinPredicate<'_>>,
+) -> Result<Vec<Row>> {
+    let mut table: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for (index, row) in right.iter().enumerate() {
+        if let Some(key) = hash_key(row, &spec.right_keys) {
+            table.entry(key).or_default().push(index);
+        }
+    }
+
+    let mut output = Vec::new();
+    let mut right_matched = vec![false; right.len()];
+    for left_row in left {
+        let left_row = left_row?;
+        let mut matched = false;
+        if let Some(candidates) = hash_key(&left_row, &spec.left_keys).and_then(|key| table.get(&key)) {
+            for &index in candidates {
+                let joined = merge_rows(&left_row, &right[index]);
+                if !passes(residual, &joined)? {
+                    continue;
+                }
+                matched = true;
+                right_matched[index] = true;
+                if emits_pairs(&spec.join_type) {
+                    output.push(joined);
                 }
             }
         }
-        
-        Ok(result)
+        finish_left_row(&mut output, &left_row, matched, spec, layout);
+    }
+
+    if matches!(spec.join_type, JoinType::Right | JoinType::Full) {
+        for (row, _) in right.iter().zip(&right_matched).filter(|(_, m)| !**m) {
+            output.push(pad_right(row, layout));
+        }
     }
+    Ok(output)
+}
+
+/// 该连接类型是否输出匹配的左右行对
+fn emits_pairs(join_type: &JoinType) -> bool {
+    !matches!(join_type, JoinType::LeftSemi | JoinType::LeftAnti)
+}
+
+/// 处理完一个左侧行后，按连接类型补充输出
+fn finish_left_row(output: &mut Vec<Row>, left_row: &Row, matched: bool, spec: &JoinSpec, layout: &JoinLayout) {
+    match spec.join_type {
+        JoinType::LeftSemi if matched => output.push(left_row.clone()),
+        JoinType::LeftAnti if !matched => output.push(left_row.clone()),
+        JoinType::Left | JoinType::Full if !matched => output.push(pad_left(left_row, layout)),
+        _ => {}
+    }
+}
+
+fn emit_unmatched_left(output: &mut Vec<Row>, left_row: &Row, spec: &JoinSpec, layout: &JoinLayout) {
+    finish_left_row(output, left_row, false, spec, layout);
+}
+
+fn passes(predicate: Option<&JoinPredicate<'_>>, row: &Row) -> Result<bool> {
+    match predicate {
+        Some(predicate) => predicate(row),
+        None => Ok(true),
+    }
+}
+
+fn merge_rows(left: &Row, right: &Row) -> Row {
+    let mut joined = left.clone();
+    joined.extend(right.iter().map(|(k, v)| (k.clone(), v.clone())));
+    joined
+}
+
+/// 左侧行补齐右侧NULL列
+fn pad_left(left: &Row, layout: &JoinLayout) -> Row {
+    let mut row = left.clone();
+    for column in &layout.right_columns {
+        row.entry(column.clone()).or_insert(Value::Null);
+    }
+    row
+}
+
+/// 右侧行补齐左侧NULL列
+fn pad_right(right: &Row, layout: &JoinLayout) -> Row {
+    let mut row = right.clone();
+    for column in &layout.left_columns {
+        row.entry(column.clone()).or_insert(Value::Null);
+    }
+    row
+}
+
+fn has_null_key(row: &Row, keys: &[String]) -> bool {
+    keys.iter().any(|k| matches!(row.get(k), None | Some(Value::Null)))
+}
+
+fn compare_keys(a: &Row, a_keys: &[String], b: &Row, b_keys: &[String]) -> Ordering {
+    for (a_key, b_key) in a_keys.iter().zip(b_keys) {
+        // NULL键排在最前，便于归并时跳过
+        let ordering = match (a.get(a_key).filter(|v| !matches!(v, Value::Null)), b.get(b_key).filter(|v| !matches!(v, Value::Null))) {
+            (Some(x), Some(y)) => compare_values(x, y).unwrap_or(Ordering::Equal),
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// 计算可哈希的连接键；包含NULL的键不参与匹配
+fn hash_key(row: &Row, keys: &[String]) -> Option<Vec<String>> {
+    keys.iter().map(|k| row.get(k).and_then(normalize_key_value)).collect()
+}
+
+/// 规范化键值，使数值类型之间（如Int32与Int64）可以互相匹配
+pub(crate) fn normalize_key_value(value: &Value) -> Option<String> {
+    let integral = |i: i128| format!("n:{}", i);
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => format!("b:{}", b),
+        Value::Int8(v) => integral(*v as i128),
+        Value::Int16(v) => integral(*v as i128),
+        Value::Int32(v) => integral(*v as i128),
+        Value::Int64(v) => integral(*v as i128),
+        Value::Int128(v) => integral(*v),
+        Value::UInt8(v) => integral(*v as i128),
+        Value::UInt16(v) => integral(*v as i128),
+        Value::UInt32(v) => integral(*v as i128),
+        Value::UInt64(v) => integral(*v as i128),
+        Value::Volume(v) => integral(v.as_u64() as i128),
+        Value::Float32(v) => normalize_float(*v as f64),
+        Value::Float64(v) => normalize_float(*v),
+        Value::Decimal(d) => normalize_decimal(*d),
+        Value::Price(p) => normalize_decimal(p.as_decimal()),
+        Value::String(s) => format!("s:{}", s),
+        Value::Symbol(s) => format!("s:{}", s.as_str()),
+        Value::Timestamp(ts) => format!("t:{}", ts.as_nanos()),
+        other => format!("j:{}", serde_json::to_string(other).unwrap_or_default()),
+    })
+}
+
+fn normalize_float(v: f64) -> String {
+    if v.fract() == 0.0 && v.abs() < 1e18 {
+        format!("n:{}", v as i128)
+    } else {
+        format!("n:{}", v)
+    }
+}
+
+fn normalize_decimal(d: rust_decimal::Decimal) -> String {
+    let d = d.normalize();
+    match (d.fract().is_zero(), d.to_i128()) {
+        (true, Some(i)) => format!("n:{}", i),
+        _ => format!("n:{}", d),
+    }
+}
+
+/// 按连接键的哈希把行分到`partitions`个分区之一（键含NULL的行归入0号分区）
+pub(crate) fn partition_of(row: &Row, keys: &[String], partitions: usize) -> usize {
+    use std::hash::{Hash, Hasher};
+    match hash_key(row, keys) {
+        Some(key) => {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            key.hash(&mut hasher);
+            (hasher.finish() % partitions as u64) as usize
+        }
+        None => 0,
+    }
+}
+
+/// 粗略估算行占用的内存
+pub fn estimate_row_size(row: &Row) -> usize {
+    row.iter()
+        .map(|(k, v)| {
+            k.len() + std::mem::size_of::<Value>() + match v {
+                Value::String(s) => s.len(),
+                Value::Binary(b) => b.len(),
+                _ => 0,
+            }
+        })
+        .sum()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn row(values: &[(&str, Value)]) -> Row {
+        values.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    fn users() -> Vec<Row> {
+        (1..=4).map(|i| row(&[("u.id", Value::Int64(i)), ("u.desk", Value::Int64(i % 2))])).collect()
+    }
+
+    fn orders() -> Vec<Row> {
+        vec![
+            row(&[("o.user_id", Value::Int32(1)), ("o.desk", Value::Int64(1)), ("o.amount", Value::Float64(10.0))]),
+            row(&[("o.user_id", Value::Int32(1)), ("o.desk", Value::Int64(0)), ("o.amount", Value::Float64(20.0))]),
+            row(&[("o.user_id", Value::Int32(3)), ("o.desk", Value::Int64(1)), ("o.amount", Value::Float64(30.0))]),
+            row(&[("o.user_id", Value::Int32(9)), ("o.desk", Value::Int64(1)), ("o.amount", Value::Float64(40.0))]),
+        ]
+    }
+
     #[test]
     fn test_inner_join() {
         let mut left_row = HashMap::new();
         left_row.insert("id".to_string(), Value::Int32(1));
         left_row.insert("name".to_string(), Value::String("Alice".to_string()));
-        
+
         let mut right_row = HashMap::new();
         right_row.insert("user_id".to_string(), Value::Int32(1));
         right_row.insert("order_id".to_string(), Value::Int32(100));
-        
+
         let left = vec![left_row];
         let right = vec![right_row];
-        
+
         let result = JoinOperations::inner_join(&left, &right, "id", "user_id").unwrap();
         assert_eq!(result.len(), 1);
         assert!(result[0].contains_key("name"));
         assert!(result[0].contains_key("right_order_id"));
     }
+
+    #[test]
+    fn test_join_types() {
+        let config = JoinConfig::default();
+        let count = |join_type| {
+            let spec = JoinSpec::on(join_type, "u.id", "o.user_id");
+            JoinOperations::hash_join(users(), orders(), &spec, &config, None).unwrap().len()
+        };
+
+        assert_eq!(count(JoinType::Inner), 3);
+        assert_eq!(count(JoinType::Left), 5);
+        assert_eq!(count(JoinType::Right), 4);
+        assert_eq!(count(JoinType::Full), 6);
+        assert_eq!(count(JoinType::LeftSemi), 2);
+        assert_eq!(count(JoinType::LeftAnti), 2);
+
+        let spec = JoinSpec::on(JoinType::Left, "u.id", "o.user_id");
+        let rows = JoinOperations::hash_join(users(), orders(), &spec, &config, None).unwrap();
+        let unmatched = rows.iter().find(|r| r.get("u.id") == Some(&Value::Int64(2))).unwrap();
+        assert_eq!(unmatched.get("o.amount"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn test_multi_key_join_and_spill() {
+        let spec = JoinSpec::new(
+            JoinType::Inner,
+            vec!["u.id".to_string(), "u.desk".to_string()],
+            vec!["o.user_id".to_string(), "o.desk".to_string()],
+        );
+        let in_memory = JoinOperations::hash_join(users(), orders(), &spec, &JoinConfig::default(), None).unwrap();
+        assert_eq!(in_memory.len(), 2);
+
+        let spilling = JoinConfig::default().with_memory_budget(0);
+        let spilled = JoinOperations::hash_join(users(), orders(), &spec, &spilling, None).unwrap();
+        assert_eq!(spilled.len(), 2);
+    }
+
+    #[test]
+    fn test_grace_join_spills_build_side_incrementally() {
+        let dir = tempfile::tempdir().unwrap();
+        let probe: Vec<Row> = (0..64).map(|i| row(&[("u.id", Value::Int64(i))])).collect();
+        let build: Vec<Row> = (0..64).map(|i| row(&[("o.user_id", Value::Int64(i % 32)), ("o.seq", Value::Int64(i))])).collect();
+        let row_bytes = estimate_row_size(&build[0]);
+        let budget = row_bytes * 16;
+        let config = JoinConfig::default().with_memory_budget(budget).with_spill_dir(dir.path());
+        let spec = JoinSpec::on(JoinType::Left, "u.id", "o.user_id");
+
+        let (rows, spill) = grace_hash_join(probe.clone(), build.clone(), &spec, &config, None).unwrap();
+        assert_eq!(rows.len(), 96);
+        assert_eq!(spill.partitions, 16);
+        // 超出预算后构建侧全部写出，驻留内存的构建行不超过预算加一行
+        assert_eq!(spill.build_rows, 64);
+        assert_eq!(spill.probe_rows, 64);
+        assert!(spill.peak_build_bytes <= budget + row_bytes);
+        // 落盘文件在连接结束后删除
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let (in_memory, spill) = grace_hash_join(probe, build, &spec, &JoinConfig::default(), None).unwrap();
+        assert_eq!(spill, JoinSpill { peak_build_bytes: row_bytes * 64, ..JoinSpill::default() });
+        assert_eq!(in_memory.len(), 96);
+    }
+
+    #[test]
+    fn test_sort_merge_join_matches_hash_join() {
+        for join_type in [JoinType::Inner, JoinType::Left, JoinType::Right, JoinType::Full, JoinType::LeftAnti] {
+            let spec = JoinSpec::on(join_type, "u.id", "o.user_id");
+            let merged = JoinOperations::sort_merge_join(&users(), &orders(), &spec, false, None).unwrap();
+            let hashed = JoinOperations::hash_join(users(), orders(), &spec, &JoinConfig::default(), None).unwrap();
+            assert_eq!(merged.len(), hashed.len());
+        }
+    }
+
+    #[test]
+    fn test_cross_and_residual_join() {
+        let cross = JoinOperations::nested_loop_join(&users(), &orders(), JoinType::Cross, None).unwrap();
+        assert_eq!(cross.len(), 16);
+
+        let large = |row: &Row| Ok(matches!(row.get("o.amount"), Some(Value::Float64(a)) if *a > 15.0));
+        let spec = JoinSpec::on(JoinType::Inner, "u.id", "o.user_id");
+        let rows = JoinOperations::hash_join(users(), orders(), &spec, &JoinConfig::default(), Some(&large)).unwrap();
+        assert_eq!(rows.len(), 2);
+    }
 }
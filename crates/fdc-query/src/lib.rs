@@ -15,20 +15,77 @@ pub mod joins;          // 连接操作
 pub mod filters;        // 过滤器
 pub mod projections;    // 投影操作
 pub mod sorts;          // 排序操作
+pub mod spill;          // 中间结果落盘
 pub mod metrics;        // 查询指标
+pub mod query_log;      // 查询历史与慢查询日志
 pub mod config;         // 配置管理
+pub mod catalog;        // 表目录
+pub mod expressions;    // 表达式求值
+pub mod time_joins;     // ASOF与窗口连接
+pub mod sampling;       // 时间分桶与降采样
+pub mod grouping;       // 分组聚合
+pub mod financial;      // 金融聚合
+pub mod sketches;       // 近似聚合草图
+pub mod statistical;    // 统计聚合
+pub mod windows;        // 窗口函数
+pub mod subqueries;     // CTE、子查询与集合运算
+pub mod system_tables;  // 虚拟系统表
+pub mod transactions;   // SQL事务与快照隔离
+pub mod security;       // 表、列与行级访问控制
+pub mod prepared;       // 预处理语句
+pub mod statistics;     // 表统计信息
+pub mod cost;           // 代价模型
+pub mod profile;        // 查询剖析
+pub mod cancellation;   // 查询取消与超时
+pub mod admission;      // 准入控制与资源池
+pub mod distributed;    // 分片间的分发-汇集执行
+pub mod views;          // 物化视图与连续查询
+pub mod streaming;      // 流式结果与服务端游标
+pub mod udf;            // 用户自定义函数
+pub mod udaf;           // 用户自定义聚合函数
+pub mod partitions;     // 分区表与分区剪枝
+#[cfg(feature = "datafusion")]
+pub mod datafusion_executor; // DataFusion分析型执行后端
 
 // 重新导出常用类型
-pub use engine::{QueryEngine, QueryEngineConfig};
-pub use parser::{SqlParser, ParsedQuery, QueryType};
+pub use engine::{QueryEngine, QueryEngineConfig, ExecutionBackend};
+pub use parser::{SqlParser, ParsedQuery, QueryType, QueryFeatures};
 pub use optimizer::{QueryOptimizer, OptimizationRule, OptimizedPlan};
 pub use executor::{QueryExecutor, ExecutionContext, ExecutionResult};
 pub use planner::{QueryPlanner, ExecutionPlan, PlanNode};
-pub use cache::{QueryCache, CachePolicy, CacheStats};
+pub use cache::{QueryCache, CachePolicy, CacheStats, CacheDependencies, CacheInvalidator};
 pub use functions::BuiltinFunctions;
-pub use aggregates::AggregateFunction;
+pub use aggregates::{AggregateFunction, Accumulator};
+pub use financial::{FinancialKind, FinancialState};
+pub use sketches::{HyperLogLog, StreamingHistogram, TDigest};
+pub use statistical::{StatisticalKind, StatisticalState};
 pub use metrics::QueryMetrics;
+pub use query_log::{QueryLog, QueryLogConfig, QueryLogEntry, QUERY_LOG_TABLE};
 pub use config::QueryConfig;
+pub use catalog::{Catalog, TableDefinition, ColumnDefinition, ColumnType, TableWrite};
+pub use expressions::ExpressionEvaluator;
+pub use time_joins::{AsofSpec, AsofDirection, WindowJoinSpec, WindowAggregate};
+pub use sampling::{SampleBy, BucketInterval, FillStrategy, Alignment};
+pub use windows::WindowFunction;
+pub use sorts::{SortConfig, SortOrder};
+pub use grouping::AggregateConfig;
+pub use prepared::{PreparedStatement, ParameterInfo, PreparedCommand, ResultColumn};
+pub use statistics::{TableStatistics, ColumnStatistics, HistogramBucket};
+pub use cost::{AccessPath, AccessMethod, CostModel};
+pub use profile::{ExplainAnalyze, ExplainFormat, OperatorMetrics, OperatorProfile};
+pub use cancellation::{CancellationToken, QueryCommand, QueryRegistry, RunningQuery};
+pub use admission::{AdmissionConfig, AdmissionController, AdmissionPermit, MemoryBudget, MemoryPolicy, PoolStatus, ResourcePool};
+pub use distributed::{DistributedExecutor, LocalShard, ShardExecutor};
+pub use views::{RefreshMode, ViewCommand, ViewDelta, ViewInfo, ViewKind, ViewManager};
+pub use udf::{FunctionCommand, FunctionRegistry, RustScalarFunction, ScalarUdf, UdfSignature, WasmScalarFunction};
+pub use udaf::{AggregateUdf, RustAggregateFunction, UserAggregate, WasmAggregateFunction};
+pub use system_tables::{RustSystemTable, SystemTable, SystemTables};
+pub use partitions::{Partition, PartitionCommand, PartitionKind, PartitionScan, PartitionSpec, PartitionsTable, TierPolicy, TimeGranularity, PARTITIONS_TABLE};
+pub use transactions::{IsolationLevel, Transaction, TransactionCommand, TransactionManager, TransactionStatus};
+pub use security::{Privilege, QuerySecurity, RowPolicy, SecurityCatalog, SecurityCommand, SessionUser, TableSecurity};
+pub use streaming::{BatchSender, Cursor, CursorCommand, CursorInfo, CursorRegistry, FetchCount, RowBatch, RowStream};
+#[cfg(feature = "datafusion")]
+pub use datafusion_executor::DataFusionExecutor;
 
 /// 库版本信息
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -48,6 +105,12 @@ pub const DEFAULT_MAX_CONCURRENT_QUERIES: usize = 100;
 /// 默认结果集大小限制 (10MB)
 pub const DEFAULT_MAX_RESULT_SIZE: usize = 10 * 1024 * 1024;
 
+/// 默认在内存中保留的查询历史条数
+pub const DEFAULT_QUERY_LOG_CAPACITY: usize = 1000;
+
+/// 默认慢查询阈值 (1秒)
+pub const DEFAULT_SLOW_QUERY_THRESHOLD_MS: u64 = 1000;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -60,5 +123,7 @@ mod tests {
         assert_eq!(DEFAULT_QUERY_TIMEOUT_SECS, 30);
         assert_eq!(DEFAULT_MAX_CONCURRENT_QUERIES, 100);
         assert_eq!(DEFAULT_MAX_RESULT_SIZE, 10 * 1024 * 1024);
+        assert_eq!(DEFAULT_QUERY_LOG_CAPACITY, 1000);
+        assert_eq!(DEFAULT_SLOW_QUERY_THRESHOLD_MS, 1000);
     }
 }
@@ -1,9 +1,16 @@
 //! Query optimizer for performance optimization
 
-use crate::parser::ParsedQuery;
+use crate::{
+    catalog::Catalog,
+    cost::{AccessPath, CostModel, JoinEstimate, ROWS_PER_MS},
+    executor::parse_statement,
+    parser::{ParsedQuery, QueryType},
+    sampling::extract_sample_by,
+};
 use fdc_core::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// 优化规则类型
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -86,6 +93,12 @@ pub struct OptimizedPlan {
     pub estimated_cost: f64,
     /// 预估执行时间（毫秒）
     pub estimated_time_ms: u64,
+    /// 基于统计信息选定的访问路径（按连接顺序）
+    #[serde(default)]
+    pub access_paths: Vec<AccessPath>,
+    /// 各连接步骤的估算
+    #[serde(default)]
+    pub join_estimates: Vec<JoinEstimate>,
 }
 
 impl OptimizedPlan {
@@ -100,6 +113,8 @@ impl OptimizedPlan {
             hints: HashMap::new(),
             estimated_cost: 1000.0, // 默认成本
             estimated_time_ms: 100,  // 默认100ms
+            access_paths: Vec::new(),
+            join_estimates: Vec::new(),
         }
     }
     
@@ -126,6 +141,11 @@ impl OptimizedPlan {
     pub fn has_rule(&self, rule: &OptimizationRule) -> bool {
         self.applied_rules.contains(rule)
     }
+    
+    /// 优化器选定的连接顺序（关系限定名），没有代价模型时为空
+    pub fn join_order(&self) -> Vec<String> {
+        self.access_paths.iter().map(|p| p.qualifier.clone()).collect()
+    }
 }
 
 /// 查询优化器
@@ -134,6 +154,8 @@ pub struct QueryOptimizer {
     enabled_rules: Vec<OptimizationRule>,
     /// 统计信息缓存
     stats_cache: HashMap<String, f64>,
+    /// 表目录（提供ANALYZE收集的统计信息）
+    catalog: Option<Arc<Catalog>>,
 }
 
 impl QueryOptimizer {
@@ -146,11 +168,19 @@ impl QueryOptimizer {
                 OptimizationRule::ConstantFolding,
                 OptimizationRule::IndexSelection,
                 OptimizationRule::JoinReordering,
+                OptimizationRule::PartitionPruning,
             ],
             stats_cache: HashMap::new(),
+            catalog: None,
         }
     }
     
+    /// 使用表目录中的统计信息进行基于代价的优化
+    pub fn with_catalog(mut self, catalog: Arc<Catalog>) -> Self {
+        self.catalog = Some(catalog);
+        self
+    }
+    
     /// 启用优化规则
     pub fn enable_rule(&mut self, rule: OptimizationRule) {
         if !self.enabled_rules.contains(&rule) {
@@ -164,25 +194,57 @@ impl QueryOptimizer {
     }
     
     /// 优化查询
+    ///
+    /// 查询涉及的表已注册到目录时使用代价模型，成本完全由统计信息推导；
+    /// 否则退回到基于SQL特征的启发式估算。
     pub fn optimize(&mut self, query: ParsedQuery) -> Result<OptimizedPlan> {
         let start_time = std::time::Instant::now();
         let mut plan = OptimizedPlan::new(query);
+        let mut model = self.cost_model(&plan.original_query);
+        if let Some(model) = &model {
+            plan.estimated_cost = model.cost();
+        }
+        let original_cost = plan.estimated_cost;
         
         // 应用启用的优化规则
         for rule in &self.enabled_rules.clone() {
-            self.apply_rule(rule, &mut plan)?;
+            self.apply_rule(rule, &mut plan, model.as_mut())?;
+        }
+        
+        if let Some(model) = &model {
+            let cost = model.cost();
+            plan.set_cost_estimate(cost, (cost / ROWS_PER_MS).ceil() as u64);
+            plan.access_paths = model.access_paths();
+            plan.join_estimates = model.join_estimates();
         }
         
         // 设置优化统计信息
         let optimization_time = start_time.elapsed().as_micros() as u64;
         plan.stats.set_optimization_time(optimization_time);
-        plan.stats.set_costs(1000.0, plan.estimated_cost);
+        plan.stats.set_costs(original_cost, plan.estimated_cost);
         
         Ok(plan)
     }
     
+    /// 为目录中的表构建代价模型
+    fn cost_model(&self, query: &ParsedQuery) -> Option<CostModel> {
+        let catalog = self.catalog.as_ref()?;
+        if query.query_type != QueryType::Select {
+            return None;
+        }
+        let (sql, _) = extract_sample_by(&query.sql).ok()?;
+        CostModel::from_statement(&parse_statement(&sql).ok()?, catalog)
+    }
+    
     /// 应用优化规则
-    fn apply_rule(&mut self, rule: &OptimizationRule, plan: &mut OptimizedPlan) -> Result<()> {
+    fn apply_rule(&mut self, rule: &OptimizationRule, plan: &mut OptimizedPlan, model: Option<&mut CostModel>) -> Result<()> {
+        if let Some(model) = model {
+            if self.apply_costed_rule(rule, plan, model) {
+                plan.add_rule(rule.clone());
+                return Ok(());
+            }
+        }
+        
         match rule {
             OptimizationRule::PredicatePushdown => {
                 self.apply_predicate_pushdown(plan)?;
@@ -214,6 +276,40 @@ impl QueryOptimizer {
         Ok(())
     }
     
+    /// 基于代价模型应用规则，规则不由模型处理时返回false
+    fn apply_costed_rule(&mut self, rule: &OptimizationRule, plan: &mut OptimizedPlan, model: &mut CostModel) -> bool {
+        match rule {
+            OptimizationRule::PredicatePushdown => {
+                if model.push_down_predicates() {
+                    plan.add_hint("predicate_pushdown".to_string(), "applied".to_string());
+                }
+            }
+            OptimizationRule::IndexSelection => {
+                let selected = model.select_access_paths();
+                if !selected.is_empty() {
+                    plan.add_hint("index_selection".to_string(), selected.join(", "));
+                }
+            }
+            OptimizationRule::PartitionPruning => {
+                let pruned = model.prune_time_ranges();
+                if !pruned.is_empty() {
+                    plan.add_hint("partition_pruning".to_string(), pruned.join(", "));
+                }
+            }
+            OptimizationRule::JoinReordering => {
+                if model.reorder_joins() {
+                    plan.add_hint("join_reordering".to_string(), model.join_order().join(", "));
+                }
+            }
+            // 投影、常量折叠等规则不改变代价模型中的成本
+            OptimizationRule::ProjectionPushdown
+            | OptimizationRule::ConstantFolding
+            | OptimizationRule::SubqueryOptimization
+            | OptimizationRule::AggregateOptimization => return false,
+        }
+        true
+    }
+    
     /// 应用谓词下推优化
     fn apply_predicate_pushdown(&mut self, plan: &mut OptimizedPlan) -> Result<()> {
         // 简化实现：如果查询涉及多表，降低成本
@@ -1,9 +1,16 @@
 //! SQL parser for query engine
 
+use crate::aggregates::AggregateFunction;
+use crate::sampling::{extract_sample_by, SampleBy};
 use fdc_core::error::{Error, Result};
 use serde::{Deserialize, Serialize};
-use sqlparser::{ast::Statement, dialect::GenericDialect, parser::Parser};
+use sqlparser::{
+    ast::{Expr, JoinOperator, Query, SetExpr, Statement, TableFactor, Visit, Visitor},
+    dialect::GenericDialect,
+    parser::Parser,
+};
 use std::collections::HashMap;
+use std::ops::ControlFlow;
 
 /// 查询类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -18,6 +25,8 @@ pub enum QueryType {
     Show,
     Describe,
     Explain,
+    /// 事务控制（BEGIN/COMMIT/ROLLBACK/SAVEPOINT/RELEASE）
+    Transaction,
 }
 
 /// 解析后的查询
@@ -31,10 +40,16 @@ pub struct ParsedQuery {
     pub ast: String, // 简化为字符串表示
     /// 涉及的表
     pub tables: Vec<String>,
-    /// 查询参数
+    /// 查询参数（绑定名 -> 占位符原文，例如`1 -> $1`、`sym -> :sym`）
     pub parameters: HashMap<String, String>,
     /// 是否只读查询
     pub is_readonly: bool,
+    /// 查询特征
+    #[serde(default)]
+    pub features: QueryFeatures,
+    /// SAMPLE BY子句
+    #[serde(default)]
+    pub sample_by: Option<SampleBy>,
 }
 
 impl ParsedQuery {
@@ -47,6 +62,8 @@ impl ParsedQuery {
             tables: Vec::new(),
             parameters: HashMap::new(),
             is_readonly: matches!(query_type, QueryType::Select | QueryType::Show | QueryType::Describe | QueryType::Explain),
+            features: QueryFeatures::default(),
+            sample_by: None,
         }
     }
     
@@ -73,6 +90,143 @@ impl ParsedQuery {
     }
 }
 
+/// 查询特征，用于选择执行后端
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryFeatures {
+    /// 包含WITH子句
+    pub has_cte: bool,
+    /// 包含子查询（WHERE/SELECT/FROM中）
+    pub has_subquery: bool,
+    /// 包含窗口函数
+    pub has_window_function: bool,
+    /// 出现的窗口函数名（大写、去重）
+    #[serde(default)]
+    pub window_functions: Vec<String>,
+    /// 包含聚合函数或GROUP BY
+    pub has_aggregate: bool,
+    /// 包含UNION/INTERSECT/EXCEPT
+    pub has_set_operation: bool,
+    /// JOIN数量
+    pub join_count: usize,
+    /// 包含ASOF等时序连接（仅原生执行器支持）
+    #[serde(default)]
+    pub has_time_series_join: bool,
+    /// 包含SAMPLE BY降采样（仅原生执行器支持）
+    #[serde(default)]
+    pub has_sample_by: bool,
+    /// 包含VWAP等金融聚合（仅原生执行器支持）
+    #[serde(default)]
+    pub has_native_aggregate: bool,
+}
+
+impl QueryFeatures {
+    /// 从语句中收集查询特征
+    pub fn from_statement(statement: &Statement) -> Self {
+        let mut collector = FeatureCollector::default();
+        let _ = statement.visit(&mut collector);
+        collector.features
+    }
+
+    /// 从查询（可以是子查询）中收集查询特征
+    pub fn from_query(query: &Query) -> Self {
+        let mut collector = FeatureCollector::default();
+        let _ = query.visit(&mut collector);
+        collector.features
+    }
+
+    /// 是否为需要分析型后端的复杂查询
+    pub fn is_analytical(&self) -> bool {
+        self.has_cte
+            || self.has_subquery
+            || self.has_window_function
+            || self.has_aggregate
+            || self.has_set_operation
+            || self.join_count > 0
+    }
+
+    /// 是否只能由原生执行器处理（时序扩展语法）
+    pub fn requires_native(&self) -> bool {
+        self.has_time_series_join || self.has_sample_by || self.has_native_aggregate
+    }
+}
+
+/// AST遍历器，收集查询特征
+#[derive(Default)]
+struct FeatureCollector {
+    features: QueryFeatures,
+    query_depth: usize,
+}
+
+impl Visitor for FeatureCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        if self.query_depth > 0 {
+            self.features.has_subquery = true;
+        }
+        self.query_depth += 1;
+
+        if query.with.is_some() {
+            self.features.has_cte = true;
+        }
+        match query.body.as_ref() {
+            SetExpr::SetOperation { .. } => self.features.has_set_operation = true,
+            SetExpr::Select(select) => {
+                self.features.join_count += select.from.iter().map(|t| t.joins.len()).sum::<usize>();
+                if select.from.iter().flat_map(|t| &t.joins).any(|j| matches!(j.join_operator, JoinOperator::AsOf { .. })) {
+                    self.features.has_time_series_join = true;
+                }
+                let has_group_by = match &select.group_by {
+                    sqlparser::ast::GroupByExpr::All(_) => true,
+                    sqlparser::ast::GroupByExpr::Expressions(exprs, _) => !exprs.is_empty(),
+                };
+                if has_group_by || select.having.is_some() {
+                    self.features.has_aggregate = true;
+                }
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.query_depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<()> {
+        if matches!(table_factor, TableFactor::Derived { .. }) {
+            self.features.has_subquery = true;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if let Expr::Function(function) = expr {
+            let aggregate = AggregateFunction::from_name(&function.name.to_string());
+            if aggregate.as_ref().is_some_and(AggregateFunction::is_native_only) {
+                self.features.has_native_aggregate = true;
+            }
+            if function.over.is_some() {
+                self.features.has_window_function = true;
+                let name = function.name.to_string().to_uppercase();
+                if !self.features.window_functions.contains(&name) {
+                    self.features.window_functions.push(name);
+                }
+            } else if aggregate.is_some() {
+                self.features.has_aggregate = true;
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// 交给sqlparser之前处理时序扩展语法：取出SAMPLE BY子句并改写ASOF JOIN
+fn preprocess_sql(sql: &str) -> Result<(String, Option<SampleBy>)> {
+    let (sql, sample_by) = extract_sample_by(sql)?;
+    Ok((crate::time_joins::rewrite_asof_sql(&sql)?, sample_by))
+}
+
 /// SQL解析器
 pub struct SqlParser {
     dialect: GenericDialect,
@@ -88,8 +242,9 @@ impl SqlParser {
     
     /// 解析SQL语句
     pub fn parse(&self, sql: &str) -> Result<ParsedQuery> {
-        // 使用sqlparser解析SQL
-        let statements = Parser::parse_sql(&self.dialect, sql)
+        // 使用sqlparser解析SQL（先取出SAMPLE BY并改写ASOF JOIN扩展子句）
+        let (rewritten, sample_by) = preprocess_sql(sql)?;
+        let statements = Parser::parse_sql(&self.dialect, &rewritten)
             .map_err(|e| Error::validation(format!("SQL parsing failed: {}", e)))?;
         
         if statements.is_empty() {
@@ -97,11 +252,16 @@ impl SqlParser {
         }
         
         if statements.len() > 1 {
-            return Err(Error::validation("Multiple statements not supported"));
+            return Err(Error::validation("Multiple statements must be split with split_statements and parsed one at a time"));
         }
         
         let statement = &statements[0];
         let mut parsed_query = self.analyze_statement(statement, sql)?;
+        parsed_query.features.has_sample_by = sample_by.is_some();
+        for placeholder in crate::prepared::collect_placeholders(statement) {
+            parsed_query.add_parameter(crate::prepared::parameter_name(&placeholder).to_string(), placeholder.clone());
+        }
+        parsed_query.sample_by = sample_by;
         
         // 设置AST字符串表示
         parsed_query.ast = format!("{:?}", statement);
@@ -109,12 +269,28 @@ impl SqlParser {
         Ok(parsed_query)
     }
     
+    /// 语句引用的全部表（包括子查询和CTE中的表），每次引用出现一次
+    pub fn referenced_tables(&self, sql: &str) -> Result<Vec<String>> {
+        let (rewritten, _) = preprocess_sql(sql)?;
+        let statements = Parser::parse_sql(&self.dialect, &rewritten)
+            .map_err(|e| Error::validation(format!("SQL parsing failed: {}", e)))?;
+        let mut tables = Vec::new();
+        let _ = sqlparser::ast::visit_relations(&statements, |name| {
+            if let Some(table) = name.0.last() {
+                tables.push(table.value.to_lowercase());
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        Ok(tables)
+    }
+    
     /// 分析SQL语句
     fn analyze_statement(&self, statement: &Statement, sql: &str) -> Result<ParsedQuery> {
         match statement {
             Statement::Query(query) => {
                 let mut parsed = ParsedQuery::new(QueryType::Select, sql.to_string());
                 self.extract_tables_from_query(query, &mut parsed);
+                parsed.features = QueryFeatures::from_statement(statement);
                 Ok(parsed)
             }
             Statement::Insert { .. } => {
@@ -154,6 +330,13 @@ impl SqlParser {
                 }
                 Ok(parsed)
             }
+            Statement::StartTransaction { .. }
+            | Statement::Commit { .. }
+            | Statement::Rollback { .. }
+            | Statement::Savepoint { .. }
+            | Statement::ReleaseSavepoint { .. } => {
+                Ok(ParsedQuery::new(QueryType::Transaction, sql.to_string()))
+            }
             Statement::ShowTables { .. } => {
                 Ok(ParsedQuery::new(QueryType::Show, sql.to_string()))
             }
@@ -164,28 +347,66 @@ impl SqlParser {
         }
     }
     
-    /// 从查询中提取表名
-    fn extract_tables_from_query(&self, query: &sqlparser::ast::Query, parsed: &mut ParsedQuery) {
-        if let sqlparser::ast::SetExpr::Select(select) = &*query.body {
-            for table_with_joins in &select.from {
-                self.extract_table_name(&table_with_joins.relation, parsed);
-                
-                // 处理JOIN
-                for join in &table_with_joins.joins {
-                    self.extract_table_name(&join.relation, parsed);
+    /// 从查询中提取表名：先按出现顺序提取FROM中的表（含派生表与集合运算各分支），
+    /// 再补充表达式子查询引用的表；CTE名称不是表，不会被提取
+    fn extract_tables_from_query(&self, query: &Query, parsed: &mut ParsedQuery) {
+        let mut ctes = Vec::new();
+        self.extract_tables_from_body(query, &mut ctes, parsed);
+        let _ = sqlparser::ast::visit_relations(query, |name| {
+            let table = name.to_string();
+            if !ctes.iter().any(|cte: &String| cte.eq_ignore_ascii_case(&table)) {
+                parsed.add_table(table);
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    fn extract_tables_from_body(&self, query: &Query, ctes: &mut Vec<String>, parsed: &mut ParsedQuery) {
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                ctes.push(cte.alias.name.value.clone());
+                self.extract_tables_from_body(&cte.query, ctes, parsed);
+            }
+        }
+        self.extract_tables_from_set_expr(&query.body, ctes, parsed);
+    }
+
+    fn extract_tables_from_set_expr(&self, body: &SetExpr, ctes: &mut Vec<String>, parsed: &mut ParsedQuery) {
+        match body {
+            SetExpr::Select(select) => {
+                for table_with_joins in &select.from {
+                    self.extract_table_name(&table_with_joins.relation, ctes, parsed);
+                    
+                    // 处理JOIN
+                    for join in &table_with_joins.joins {
+                        self.extract_table_name(&join.relation, ctes, parsed);
+                    }
                 }
             }
+            SetExpr::SetOperation { left, right, .. } => {
+                self.extract_tables_from_set_expr(left, ctes, parsed);
+                self.extract_tables_from_set_expr(right, ctes, parsed);
+            }
+            SetExpr::Query(query) => self.extract_tables_from_body(query, ctes, parsed),
+            _ => {}
         }
     }
     
     /// 提取表名
-    fn extract_table_name(&self, table_factor: &sqlparser::ast::TableFactor, parsed: &mut ParsedQuery) {
+    fn extract_table_name(&self, table_factor: &TableFactor, ctes: &mut Vec<String>, parsed: &mut ParsedQuery) {
         match table_factor {
-            sqlparser::ast::TableFactor::Table { name, .. } => {
-                parsed.add_table(name.to_string());
+            TableFactor::Table { name, .. } => {
+                let table = name.to_string();
+                if !ctes.iter().any(|cte| cte.eq_ignore_ascii_case(&table)) {
+                    parsed.add_table(table);
+                }
             }
-            sqlparser::ast::TableFactor::Derived { .. } => {
-                // 子查询，暂时忽略
+            TableFactor::Derived { subquery, .. } => self.extract_tables_from_body(subquery, ctes, parsed),
+            TableFactor::NestedJoin { table_with_joins, .. } => {
+                self.extract_table_name(&table_with_joins.relation, ctes, parsed);
+                for join in &table_with_joins.joins {
+                    self.extract_table_name(&join.relation, ctes, parsed);
+                }
             }
             _ => {
                 // 其他类型暂时忽略
@@ -195,14 +416,16 @@ impl SqlParser {
     
     /// 验证SQL语法
     pub fn validate(&self, sql: &str) -> Result<()> {
-        Parser::parse_sql(&self.dialect, sql)
+        let (sql, _) = preprocess_sql(sql)?;
+        Parser::parse_sql(&self.dialect, &sql)
             .map_err(|e| Error::validation(format!("SQL validation failed: {}", e)))?;
         Ok(())
     }
     
     /// 格式化SQL
     pub fn format(&self, sql: &str) -> Result<String> {
-        let statements = Parser::parse_sql(&self.dialect, sql)
+        let (sql, sample_by) = preprocess_sql(sql)?;
+        let statements = Parser::parse_sql(&self.dialect, &sql)
             .map_err(|e| Error::validation(format!("SQL parsing failed: {}", e)))?;
         
         if statements.is_empty() {
@@ -210,7 +433,10 @@ impl SqlParser {
         }
         
         // 简化的格式化，实际应该使用专门的格式化器
-        Ok(format!("{}", statements[0]))
+        match sample_by {
+            Some(sample_by) => Ok(format!("{} {}", statements[0], sample_by)),
+            None => Ok(format!("{}", statements[0])),
+        }
     }
     
     /// 从SQL中提取表名（简化实现）
@@ -255,6 +481,79 @@ impl Default for SqlParser {
     }
 }
 
+/// 按顶层分号拆分语句（忽略引号、美元引号与注释中的分号），去掉空语句
+pub fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    scan_sql(sql, |at, c| {
+        if c == ';' {
+            statements.push(sql[start..at].trim().to_string());
+            start = at + 1;
+        }
+    });
+    statements.push(sql[start..].trim().to_string());
+    statements.retain(|statement| !statement.is_empty());
+    statements
+}
+
+/// 依次回调SQL中不在字符串、引号标识符、美元引号和注释内的字符
+pub fn scan_sql(sql: &str, mut visit: impl FnMut(usize, char)) {
+    let bytes = sql.as_bytes();
+    let mut chars = sql.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                // 引号内的成对引号是转义
+                while let Some((_, inner)) = chars.next() {
+                    if inner == c {
+                        if chars.peek().map(|(_, next)| *next) == Some(c) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            '-' if bytes.get(at + 1) == Some(&b'-') => {
+                for (_, inner) in chars.by_ref() {
+                    if inner == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if bytes.get(at + 1) == Some(&b'*') => {
+                chars.next();
+                while let Some((inner_at, inner)) = chars.next() {
+                    if inner == '*' && bytes.get(inner_at + 1) == Some(&b'/') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            '$' if !bytes.get(at + 1).is_some_and(u8::is_ascii_digit) => {
+                // 美元引号：$tag$ ... $tag$
+                let tag_end = sql[at + 1..].find(|ch: char| !(ch.is_alphanumeric() || ch == '_')).map(|i| at + 1 + i);
+                match tag_end.filter(|end| bytes[*end] == b'$') {
+                    Some(end) => {
+                        let delimiter = &sql[at..=end];
+                        match sql[end + 1..].find(delimiter) {
+                            Some(close) => {
+                                let resume = end + 1 + close + delimiter.len();
+                                while chars.peek().is_some_and(|(i, _)| *i < resume) {
+                                    chars.next();
+                                }
+                            }
+                            None => return,
+                        }
+                    }
+                    None => visit(at, c),
+                }
+            }
+            _ => visit(at, c),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -265,6 +564,23 @@ mod tests {
         assert!(parser.validate("SELECT 1").is_ok());
     }
 
+    #[test]
+    fn test_transaction_statements_and_scripts() {
+        let parser = SqlParser::new();
+        for sql in ["BEGIN", "START TRANSACTION", "COMMIT", "ROLLBACK", "SAVEPOINT sp", "RELEASE SAVEPOINT sp"] {
+            let parsed = parser.parse(sql).unwrap();
+            assert_eq!(parsed.query_type, QueryType::Transaction, "{}", sql);
+            assert!(!parsed.is_readonly);
+        }
+
+        let script = "BEGIN; UPDATE accounts SET balance = balance - 10 WHERE id = 1; -- ;\nCOMMIT;";
+        assert!(parser.parse(script).is_err());
+        assert_eq!(
+            split_statements(script),
+            vec!["BEGIN", "UPDATE accounts SET balance = balance - 10 WHERE id = 1", "-- ;\nCOMMIT"],
+        );
+    }
+
     #[test]
     fn test_simple_select() {
         let parser = SqlParser::new();
@@ -288,6 +604,48 @@ mod tests {
         assert!(result.is_multi_table());
     }
 
+    #[test]
+    fn test_query_features() {
+        let parser = SqlParser::new();
+        let simple = parser.parse("SELECT price FROM trades WHERE id = 1").unwrap();
+        assert!(!simple.features.is_analytical());
+
+        let analytical = parser.parse(
+            "WITH t AS (SELECT symbol, price FROM trades) \
+             SELECT symbol, AVG(price) OVER (PARTITION BY symbol) FROM t \
+             WHERE symbol IN (SELECT symbol FROM watchlist)",
+        ).unwrap();
+        assert!(analytical.features.has_cte);
+        assert!(analytical.features.has_window_function);
+        assert_eq!(analytical.features.window_functions, vec!["AVG".to_string()]);
+        assert!(analytical.features.has_subquery);
+        assert!(analytical.features.is_analytical());
+
+        let grouped = parser.parse("SELECT symbol, COUNT(*) FROM trades GROUP BY symbol").unwrap();
+        assert!(grouped.features.has_aggregate);
+
+        let parameterised = parser.parse("SELECT * FROM trades WHERE id = $1 AND symbol = :sym").unwrap();
+        assert_eq!(parameterised.parameters.get("1"), Some(&"$1".to_string()));
+        assert_eq!(parameterised.parameters.get("sym"), Some(&":sym".to_string()));
+
+        let sampled = parser.parse("SELECT ts, AVG(price) FROM trades SAMPLE BY 1h FILL(LINEAR)").unwrap();
+        assert!(sampled.features.has_sample_by && sampled.features.requires_native());
+        assert!(sampled.sample_by.is_some());
+    }
+
+    #[test]
+    fn test_tables_from_subqueries_and_set_operations() {
+        let parser = SqlParser::new();
+        let result = parser.parse(
+            "WITH recent AS (SELECT * FROM trades WHERE ts > 0) \
+             SELECT r.symbol FROM recent r JOIN (SELECT symbol FROM quotes) q ON q.symbol = r.symbol \
+             WHERE r.symbol IN (SELECT symbol FROM watchlist) \
+             UNION SELECT symbol FROM archive",
+        ).unwrap();
+        assert_eq!(result.tables, vec!["trades", "quotes", "archive", "watchlist"]);
+        assert!(result.features.has_set_operation);
+    }
+
     #[test]
     fn test_insert_query() {
         let parser = SqlParser::new();
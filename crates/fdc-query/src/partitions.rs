@@ -0,0 +1,672 @@
+//! Partitioned tables
+//!
+//! A table created with `PARTITION BY DAY(ts)` (or `HOUR(ts)`/`MONTH(ts)`, or
+//! `LIST (exchange)`) stores each partition under its own key range
+//! `tbl:<table>:<partition>:`. Partitions are created when the first row for
+//! them is written, are mapped to storage tiers by age, can be dropped or
+//! detached as a whole for fast retention, and are skipped by scans whose
+//! WHERE clause cannot match them.
+
+use crate::{
+    catalog::{Catalog, ColumnDefinition, ColumnType, TableDefinition},
+    cost::time_bounds,
+    expressions::{display_value, parse_interval_nanos, value_as_timestamp, ExpressionEvaluator},
+    joins::Row,
+    sampling::month_start_nanos,
+    security::split_top_level,
+    system_tables::SystemTable,
+};
+use chrono::{DateTime, Datelike, Utc};
+use fdc_core::{
+    error::{Error, Result},
+    time::intervals,
+    types::{TimestampNs, Value},
+};
+use fdc_storage::tier::StorageTier;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlparser::{
+    ast::{BinaryOperator, Expr},
+    dialect::GenericDialect,
+    parser::Parser,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+
+/// 分区信息系统表名
+pub const PARTITIONS_TABLE: &str = "system.partitions";
+
+/// 时间分区粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeGranularity {
+    Hour,
+    Day,
+    Month,
+}
+
+impl TimeGranularity {
+    /// 解析`HOUR`、`DAY`、`MONTH`
+    pub fn parse(text: &str) -> Result<Self> {
+        match text.to_uppercase().as_str() {
+            "HOUR" => Ok(TimeGranularity::Hour),
+            "DAY" => Ok(TimeGranularity::Day),
+            "MONTH" => Ok(TimeGranularity::Month),
+            _ => Err(Error::parse(format!("Unknown partition granularity: {}", text))),
+        }
+    }
+
+    /// 时间戳所在分区的范围 [start, end)（纳秒）
+    pub fn bounds(&self, ts: i64) -> Result<(i64, i64)> {
+        let fixed = |interval: i64| {
+            let start = ts - ts.rem_euclid(interval);
+            (start, start + interval)
+        };
+        match self {
+            TimeGranularity::Hour => Ok(fixed(intervals::HOUR)),
+            TimeGranularity::Day => Ok(fixed(intervals::DAY)),
+            TimeGranularity::Month => {
+                let date = DateTime::<Utc>::from_timestamp_nanos(ts).date_naive();
+                let index = date.year() as i64 * 12 + date.month0() as i64;
+                Ok((month_start_nanos(index)?, month_start_nanos(index + 1)?))
+            }
+        }
+    }
+
+    /// 起始于`start`的分区名；同一粒度下分区名的字典序与时间顺序一致
+    pub fn partition_name(&self, start: i64) -> String {
+        let format = match self {
+            TimeGranularity::Hour => "p%Y%m%d%H",
+            TimeGranularity::Day => "p%Y%m%d",
+            TimeGranularity::Month => "p%Y%m",
+        };
+        DateTime::<Utc>::from_timestamp_nanos(start).format(format).to_string()
+    }
+}
+
+/// 分区方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PartitionKind {
+    /// 按时间列截断到小时、天或月
+    Time(TimeGranularity),
+    /// 按列值逐值分区（例如交易所）
+    List,
+}
+
+/// 按分区年龄映射存储层级
+///
+/// 分区结束后经过的时间小于某个阈值时放在该阈值对应的层级，比所有阈值都旧的分区放在L4。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TierPolicy {
+    /// (最大年龄（纳秒）, 层级)，按年龄递增
+    pub thresholds: Vec<(i64, StorageTier)>,
+}
+
+impl Default for TierPolicy {
+    fn default() -> Self {
+        Self {
+            thresholds: vec![
+                (intervals::DAY, StorageTier::L1),
+                (7 * intervals::DAY, StorageTier::L2),
+                (90 * intervals::DAY, StorageTier::L3),
+            ],
+        }
+    }
+}
+
+impl TierPolicy {
+    /// 设置层级的最大年龄
+    pub fn with_threshold(mut self, tier: StorageTier, max_age: i64) -> Self {
+        self.thresholds.retain(|(_, t)| *t != tier);
+        self.thresholds.push((max_age, tier));
+        self.thresholds.sort_by_key(|(_, tier)| tier.priority());
+        self
+    }
+
+    /// 分区在`now`时所在的存储层级；列表分区没有时间，始终在最热的层级
+    pub fn tier_of(&self, partition: &Partition, now: i64) -> StorageTier {
+        let hottest = || self.thresholds.first().map(|(_, tier)| tier.clone()).unwrap_or(StorageTier::L1);
+        let Some((_, end)) = partition.range else {
+            return hottest();
+        };
+        // 仍在写入的分区年龄为负
+        let age = now - end;
+        self.thresholds.iter()
+            .find(|(max_age, _)| age < *max_age)
+            .map(|(_, tier)| tier.clone())
+            .unwrap_or(StorageTier::L4)
+    }
+}
+
+/// 表的分区定义
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartitionSpec {
+    /// 分区列
+    pub column: String,
+    /// 分区方式
+    pub kind: PartitionKind,
+    /// 分区到存储层级的映射
+    pub tiering: TierPolicy,
+}
+
+impl PartitionSpec {
+    /// 按时间列分区
+    pub fn time(column: impl Into<String>, granularity: TimeGranularity) -> Self {
+        Self {
+            column: column.into(),
+            kind: PartitionKind::Time(granularity),
+            tiering: TierPolicy::default(),
+        }
+    }
+
+    /// 按列值分区
+    pub fn list(column: impl Into<String>) -> Self {
+        Self {
+            column: column.into(),
+            kind: PartitionKind::List,
+            tiering: TierPolicy::default(),
+        }
+    }
+
+    /// 设置存储层级映射
+    pub fn with_tiering(mut self, tiering: TierPolicy) -> Self {
+        self.tiering = tiering;
+        self
+    }
+
+    /// 检查分区列存在且类型匹配
+    pub fn validate(&self, table: &TableDefinition) -> Result<()> {
+        let column = table.column(&self.column)
+            .ok_or_else(|| Error::not_found(format!("partition column {}.{}", table.name, self.column)))?;
+        if matches!(self.kind, PartitionKind::Time(_)) && column.column_type != ColumnType::Timestamp {
+            return Err(Error::validation(format!(
+                "Time partition column {}.{} must be a TIMESTAMP", table.name, column.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// 行所属的分区
+    pub fn partition_of(&self, row: &HashMap<String, Value>) -> Result<Partition> {
+        let value = row.get(&self.column)
+            .filter(|value| !matches!(value, Value::Null))
+            .ok_or_else(|| Error::validation(format!("Partition column {} does not allow NULL", self.column)))?;
+        match self.kind {
+            PartitionKind::Time(granularity) => {
+                let ts = value_as_timestamp(value)
+                    .ok_or_else(|| Error::type_error(format!("Partition column {} is not a timestamp: {:?}", self.column, value)))?;
+                let (start, end) = granularity.bounds(ts.as_nanos())?;
+                Ok(Partition { name: granularity.partition_name(start), range: Some((start, end)), value: None })
+            }
+            PartitionKind::List => {
+                let text = display_value(value);
+                Ok(Partition { name: list_partition_name(&text), range: None, value: Some(text) })
+            }
+        }
+    }
+
+    /// 按WHERE中的合取谓词选出需要扫描的分区，谓词不约束分区列时返回None
+    pub fn prune(&self, partitions: &[Partition], predicates: &[Expr]) -> Option<Vec<String>> {
+        let kept = match self.kind {
+            PartitionKind::Time(_) => {
+                let (start, end) = time_bounds(predicates, &self.column);
+                if start.is_none() && end.is_none() {
+                    return None;
+                }
+                partitions.iter()
+                    .filter(|partition| match partition.range {
+                        Some((lo, hi)) => {
+                            start.map_or(true, |(ts, _)| hi > ts)
+                                && end.map_or(true, |(ts, inclusive)| lo < ts || (inclusive && lo == ts))
+                        }
+                        None => true,
+                    })
+                    .map(|partition| partition.name.clone())
+                    .collect()
+            }
+            PartitionKind::List => {
+                let names = list_bounds(predicates, &self.column)?;
+                partitions.iter()
+                    .filter(|partition| names.contains(&partition.name))
+                    .map(|partition| partition.name.clone())
+                    .collect()
+            }
+        };
+        Some(kept)
+    }
+
+    /// 分区定义的SQL文本，例如`DAY(ts)`、`LIST (exchange)`
+    pub fn describe(&self) -> String {
+        match self.kind {
+            PartitionKind::Time(granularity) => {
+                let granularity = format!("{:?}", granularity).to_uppercase();
+                format!("{}({})", granularity, self.column)
+            }
+            PartitionKind::List => format!("LIST ({})", self.column),
+        }
+    }
+}
+
+/// 一个分区
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Partition {
+    /// 分区名（时间分区如`p20240115`，列表分区如`p_nyse`），也是分区键前缀的一部分
+    pub name: String,
+    /// 时间分区覆盖的范围 [start, end)（纳秒）
+    pub range: Option<(i64, i64)>,
+    /// 列表分区的取值
+    pub value: Option<String>,
+}
+
+/// 扫描涉及的分区
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartitionScan {
+    /// 剪枝后需要扫描的分区
+    pub scanned: Vec<String>,
+    /// 表的分区总数
+    pub total: usize,
+}
+
+/// 列表分区的分区名：取值转小写，非字母数字的字符替换为下划线
+fn list_partition_name(value: &str) -> String {
+    let normalized: String = value.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    format!("p_{}", normalized)
+}
+
+/// 从`列 = 常量`与`列 IN (常量, ...)`谓词中求出列表分区列可能的分区名，没有这类谓词时返回None
+fn list_bounds(predicates: &[Expr], column: &str) -> Option<HashSet<String>> {
+    let evaluator = ExpressionEvaluator::new();
+    let name_of = |expr: &Expr| -> Option<String> {
+        match evaluator.evaluate(expr, &HashMap::new()).ok()? {
+            Value::Null => None,
+            value => Some(list_partition_name(&display_value(&value))),
+        }
+    };
+    let is_column = |expr: &Expr| match expr {
+        Expr::Identifier(ident) => ident.value.eq_ignore_ascii_case(column),
+        Expr::CompoundIdentifier(idents) => idents.last().is_some_and(|ident| ident.value.eq_ignore_ascii_case(column)),
+        _ => false,
+    };
+
+    let mut names: Option<HashSet<String>> = None;
+    for predicate in predicates {
+        let allowed: Option<HashSet<String>> = match predicate {
+            Expr::BinaryOp { left, op: BinaryOperator::Eq, right } if is_column(left) => name_of(right).map(|name| HashSet::from([name])),
+            Expr::BinaryOp { left, op: BinaryOperator::Eq, right } if is_column(right) => name_of(left).map(|name| HashSet::from([name])),
+            Expr::InList { expr, list, negated: false } if is_column(expr) => list.iter().map(name_of).collect(),
+            _ => None,
+        };
+        if let Some(allowed) = allowed {
+            names = Some(match names {
+                Some(names) => names.intersection(&allowed).cloned().collect(),
+                None => allowed,
+            });
+        }
+    }
+    names
+}
+
+/// 分区表的DDL
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartitionCommand {
+    /// `CREATE TABLE [IF NOT EXISTS] name (columns) PARTITION BY DAY(ts) [WITH (l1 = '1 day', ...)]`
+    CreateTable {
+        /// 表定义
+        definition: TableDefinition,
+        /// 已存在时不报错
+        if_not_exists: bool,
+    },
+    /// `ALTER TABLE name DROP PARTITION [IF EXISTS] partition`
+    DropPartition {
+        /// 表名
+        table: String,
+        /// 分区名
+        partition: String,
+        /// 不存在时不报错
+        if_exists: bool,
+    },
+    /// `ALTER TABLE name DETACH PARTITION partition [INTO target]`
+    DetachPartition {
+        /// 表名
+        table: String,
+        /// 分区名
+        partition: String,
+        /// 接收分区数据的独立表，默认为`<表名>_<分区名>`
+        target: Option<String>,
+    },
+}
+
+impl PartitionCommand {
+    /// 识别分区表的DDL；其他语句返回None
+    pub fn parse(sql: &str) -> Result<Option<Self>> {
+        static CREATE: OnceLock<Regex> = OnceLock::new();
+        static ALTER: OnceLock<Regex> = OnceLock::new();
+        let create = CREATE.get_or_init(|| {
+            Regex::new(concat!(
+                r"(?is)^\s*CREATE\s+TABLE\s+(IF\s+NOT\s+EXISTS\s+)?([A-Za-z_][A-Za-z0-9_.]*)\s*\((.*)\)\s*",
+                r"PARTITION\s+BY\s+(?:LIST\s*\(\s*([A-Za-z_][A-Za-z0-9_]*)\s*\)|([A-Za-z]+)\s*\(\s*([A-Za-z_][A-Za-z0-9_]*)\s*\))\s*",
+                r"(?:WITH\s*\((.*)\)\s*)?;?\s*$",
+            ))
+            .expect("valid regex")
+        });
+        let alter = ALTER.get_or_init(|| {
+            Regex::new(concat!(
+                r"(?is)^\s*ALTER\s+TABLE\s+([A-Za-z_][A-Za-z0-9_.]*)\s+(DROP|DETACH)\s+PARTITION\s+(IF\s+EXISTS\s+)?",
+                r"([A-Za-z0-9_]+)(?:\s+INTO\s+([A-Za-z_][A-Za-z0-9_.]*))?\s*;?\s*$",
+            ))
+            .expect("valid regex")
+        });
+
+        if let Some(caps) = create.captures(sql) {
+            let name = caps[2].to_lowercase();
+            let (mut columns, primary_key) = parse_columns(&caps[3])?;
+            let (column, kind) = match (caps.get(4), caps.get(5), caps.get(6)) {
+                (Some(column), _, _) => (column.as_str(), PartitionKind::List),
+                (_, Some(granularity), Some(column)) => {
+                    (column.as_str(), PartitionKind::Time(TimeGranularity::parse(granularity.as_str())?))
+                }
+                _ => return Err(Error::parse(format!("Invalid PARTITION BY clause in: {}", sql.trim()))),
+            };
+            // 分区列不能为空，否则行无处安放
+            let column = columns.iter_mut()
+                .find(|c| c.name.eq_ignore_ascii_case(column))
+                .map(|c| {
+                    c.nullable = false;
+                    c.name.clone()
+                })
+                .ok_or_else(|| Error::not_found(format!("partition column {}.{}", name, column)))?;
+            let tiering = caps.get(7).map(|m| parse_tiering(m.as_str())).transpose()?.unwrap_or_default();
+            let spec = PartitionSpec { column, kind, tiering };
+
+            let mut definition = TableDefinition::new(name, columns).with_partitioning(spec);
+            if let Some(primary_key) = primary_key {
+                definition = definition.with_primary_key(primary_key);
+            }
+            return Ok(Some(PartitionCommand::CreateTable { definition, if_not_exists: caps.get(1).is_some() }));
+        }
+        if let Some(caps) = alter.captures(sql) {
+            let table = caps[1].to_lowercase();
+            let partition = caps[4].to_lowercase();
+            let target = caps.get(5).map(|m| m.as_str().to_lowercase());
+            return Ok(Some(if caps[2].eq_ignore_ascii_case("DROP") {
+                if target.is_some() {
+                    return Err(Error::parse("INTO is only valid for DETACH PARTITION"));
+                }
+                PartitionCommand::DropPartition { table, partition, if_exists: caps.get(3).is_some() }
+            } else {
+                if caps.get(3).is_some() {
+                    return Err(Error::parse("IF EXISTS is only valid for DROP PARTITION"));
+                }
+                PartitionCommand::DetachPartition { table, partition, target }
+            }));
+        }
+        Ok(None)
+    }
+}
+
+/// 解析列定义列表：`name TYPE [NOT NULL] [PRIMARY KEY]`与`PRIMARY KEY (column)`
+fn parse_columns(text: &str) -> Result<(Vec<ColumnDefinition>, Option<String>)> {
+    static TABLE_KEY: OnceLock<Regex> = OnceLock::new();
+    static CONSTRAINT: OnceLock<Regex> = OnceLock::new();
+    let table_key = TABLE_KEY.get_or_init(|| {
+        Regex::new(r"(?is)^PRIMARY\s+KEY\s*\((.*)\)$").expect("valid regex")
+    });
+    let constraint = CONSTRAINT.get_or_init(|| {
+        Regex::new(r"(?i)\s+(NOT\s+NULL|NULL|PRIMARY\s+KEY)\b").expect("valid regex")
+    });
+
+    let mut columns = Vec::new();
+    let mut primary_key = None;
+    let mut set_primary_key = |column: String| {
+        match primary_key.replace(column) {
+            Some(_) => Err(Error::validation("Multiple primary keys are not allowed")),
+            None => Ok(()),
+        }
+    };
+    for part in split_top_level(text) {
+        let part = part.trim();
+        if let Some(caps) = table_key.captures(part) {
+            let key = caps[1].trim();
+            if key.contains(',') {
+                return Err(Error::unimplemented("Composite primary keys"));
+            }
+            set_primary_key(key.to_string())?;
+            continue;
+        }
+        let (name, rest) = part.split_once(char::is_whitespace)
+            .ok_or_else(|| Error::parse(format!("Invalid column definition: {}", part)))?;
+        let name = name.trim_matches('"').to_string();
+        let type_end = constraint.find(rest).map(|m| m.start()).unwrap_or(rest.len());
+        let mut column = ColumnDefinition::new(name.clone(), parse_column_type(rest[..type_end].trim())?);
+        for caps in constraint.captures_iter(&rest[type_end..]) {
+            match caps[1].split_whitespace().next().unwrap_or_default().to_uppercase().as_str() {
+                "NOT" => column.nullable = false,
+                "PRIMARY" => {
+                    column.nullable = false;
+                    set_primary_key(name.clone())?;
+                }
+                _ => {}
+            }
+        }
+        if !constraint.replace_all(&rest[type_end..], "").trim().is_empty() {
+            return Err(Error::unimplemented(format!("Column constraint in: {}", part)));
+        }
+        columns.push(column);
+    }
+
+    if let Some(key) = &primary_key {
+        let column = columns.iter_mut()
+            .find(|c| c.name.eq_ignore_ascii_case(key))
+            .ok_or_else(|| Error::not_found(format!("primary key column {}", key)))?;
+        column.nullable = false;
+        primary_key = Some(column.name.clone());
+    }
+    Ok((columns, primary_key))
+}
+
+/// 解析列类型；`PRICE`、`VOLUME`、`SYMBOL`为金融类型
+fn parse_column_type(text: &str) -> Result<ColumnType> {
+    match text.to_uppercase().as_str() {
+        "PRICE" => return Ok(ColumnType::Price),
+        "VOLUME" => return Ok(ColumnType::Volume),
+        "SYMBOL" => return Ok(ColumnType::Symbol),
+        _ => {}
+    }
+    let data_type = Parser::new(&GenericDialect {})
+        .try_with_sql(text)
+        .and_then(|mut parser| parser.parse_data_type())
+        .map_err(|e| Error::validation(format!("Invalid type {}: {}", text, e)))?;
+    ColumnType::from_sql_type(&data_type)
+        .ok_or_else(|| Error::unimplemented(format!("Column type {}", data_type)))
+}
+
+/// 解析`WITH (l1 = '1 day', l2 = '7 days', l3 = '90 days')`形式的层级年龄阈值
+fn parse_tiering(text: &str) -> Result<TierPolicy> {
+    let mut policy = TierPolicy { thresholds: Vec::new() };
+    for option in text.split(',').map(str::trim).filter(|option| !option.is_empty()) {
+        let (key, value) = option.split_once('=')
+            .ok_or_else(|| Error::parse(format!("Invalid partition option: {}", option)))?;
+        let tier = match key.trim().to_uppercase().as_str() {
+            "L1" => StorageTier::L1,
+            "L2" => StorageTier::L2,
+            "L3" => StorageTier::L3,
+            other => return Err(Error::validation(format!("Unknown partition option: {}", other.to_lowercase()))),
+        };
+        let age = parse_interval_nanos(value.trim().trim_matches('\''))?;
+        policy = policy.with_threshold(tier, age);
+    }
+    if policy.thresholds.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+        return Err(Error::validation("Storage tier ages must increase from L1 to L3"));
+    }
+    Ok(policy)
+}
+
+/// `system.partitions`：分区表的分区、覆盖范围与当前存储层级
+pub struct PartitionsTable {
+    catalog: Arc<Catalog>,
+}
+
+impl PartitionsTable {
+    /// 创建分区信息系统表
+    pub fn new(catalog: Arc<Catalog>) -> Self {
+        Self { catalog }
+    }
+}
+
+impl SystemTable for PartitionsTable {
+    fn definition(&self) -> TableDefinition {
+        TableDefinition::new(PARTITIONS_TABLE, vec![
+            ColumnDefinition::new("table_name", ColumnType::String),
+            ColumnDefinition::new("partition_name", ColumnType::String),
+            ColumnDefinition::new("partition_by", ColumnType::String),
+            ColumnDefinition::new("range_start", ColumnType::Timestamp),
+            ColumnDefinition::new("range_end", ColumnType::Timestamp),
+            ColumnDefinition::new("value", ColumnType::String),
+            ColumnDefinition::new("tier", ColumnType::String),
+        ])
+    }
+
+    fn scan(&self) -> Result<Vec<Row>> {
+        let now = TimestampNs::now().as_nanos();
+        let timestamp = |ts: Option<i64>| ts.map(|ts| Value::Timestamp(TimestampNs::from_nanos(ts))).unwrap_or(Value::Null);
+        let mut rows = Vec::new();
+        for table in self.catalog.list_tables() {
+            let Some(spec) = &table.partitioning else {
+                continue;
+            };
+            for partition in self.catalog.partitions(&table.name) {
+                let mut row = HashMap::new();
+                row.insert("table_name".to_string(), Value::String(table.name.clone()));
+                row.insert("partition_name".to_string(), Value::String(partition.name.clone()));
+                row.insert("partition_by".to_string(), Value::String(spec.describe()));
+                row.insert("range_start".to_string(), timestamp(partition.range.map(|(start, _)| start)));
+                row.insert("range_end".to_string(), timestamp(partition.range.map(|(_, end)| end)));
+                row.insert("value".to_string(), partition.value.clone().map(Value::String).unwrap_or(Value::Null));
+                row.insert("tier".to_string(), Value::String(spec.tiering.tier_of(&partition, now).name().to_string()));
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::split_conjunction;
+
+    fn ts(text: &str) -> i64 {
+        fdc_core::time::TimeUtils::parse_timestamp(text).unwrap().as_nanos()
+    }
+
+    fn where_clause(sql: &str) -> Vec<Expr> {
+        let expr = Parser::new(&GenericDialect {}).try_with_sql(sql).unwrap().parse_expr().unwrap();
+        split_conjunction(&expr).into_iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_time_partition_bounds_and_names() {
+        let spec = PartitionSpec::time("ts", TimeGranularity::Day);
+        let row = HashMap::from([("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(ts("2024-01-15 13:45:00"))))]);
+        let partition = spec.partition_of(&row).unwrap();
+        assert_eq!(partition.name, "p20240115");
+        assert_eq!(partition.range, Some((ts("2024-01-15 00:00:00"), ts("2024-01-16 00:00:00"))));
+        assert!(spec.partition_of(&HashMap::from([("ts".to_string(), Value::Null)])).is_err());
+
+        let (start, end) = TimeGranularity::Month.bounds(ts("2024-02-29 23:00:00")).unwrap();
+        assert_eq!((start, end), (ts("2024-02-01 00:00:00"), ts("2024-03-01 00:00:00")));
+        assert_eq!(TimeGranularity::Month.partition_name(start), "p202402");
+        assert_eq!(TimeGranularity::Hour.partition_name(ts("2024-02-29 23:00:00")), "p2024022923");
+
+        let list = PartitionSpec::list("exchange");
+        let partition = list.partition_of(&HashMap::from([("exchange".to_string(), Value::String("NYSE Arca".to_string()))])).unwrap();
+        assert_eq!(partition.name, "p_nyse_arca");
+        assert_eq!(partition.value.as_deref(), Some("NYSE Arca"));
+    }
+
+    #[test]
+    fn test_prune_partitions() {
+        let spec = PartitionSpec::time("ts", TimeGranularity::Day);
+        let partitions: Vec<Partition> = ["2024-01-14", "2024-01-15", "2024-01-16"].iter()
+            .map(|day| {
+                let row = HashMap::from([("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(ts(&format!("{} 12:00:00", day)))))]);
+                spec.partition_of(&row).unwrap()
+            })
+            .collect();
+
+        let kept = spec.prune(&partitions, &where_clause("ts >= '2024-01-15 00:00:00' AND ts < '2024-01-16 00:00:00' AND price > 1"));
+        assert_eq!(kept, Some(vec!["p20240115".to_string()]));
+        let kept = spec.prune(&partitions, &where_clause("t.ts > '2024-01-15 08:00:00'"));
+        assert_eq!(kept, Some(vec!["p20240115".to_string(), "p20240116".to_string()]));
+        assert_eq!(spec.prune(&partitions, &where_clause("price > 1")), None);
+
+        let list = PartitionSpec::list("exchange");
+        let partitions: Vec<Partition> = ["NYSE", "NASDAQ", "LSE"].iter()
+            .map(|exchange| list.partition_of(&HashMap::from([("exchange".to_string(), Value::String(exchange.to_string()))])).unwrap())
+            .collect();
+        let kept = list.prune(&partitions, &where_clause("exchange IN ('NYSE', 'LSE', 'CME')"));
+        assert_eq!(kept, Some(vec!["p_nyse".to_string(), "p_lse".to_string()]));
+        assert_eq!(list.prune(&partitions, &where_clause("exchange = 'LSE' AND exchange = 'NYSE'")), Some(vec![]));
+        assert_eq!(list.prune(&partitions, &where_clause("exchange <> 'LSE'")), None);
+    }
+
+    #[test]
+    fn test_tier_by_age() {
+        let policy = TierPolicy::default().with_threshold(StorageTier::L2, 30 * intervals::DAY);
+        let day = |start: i64| Partition { name: String::new(), range: Some((start, start + intervals::DAY)), value: None };
+        let now = ts("2024-03-01 12:00:00");
+        assert_eq!(policy.tier_of(&day(ts("2024-03-01 00:00:00")), now), StorageTier::L1);
+        assert_eq!(policy.tier_of(&day(ts("2024-02-20 00:00:00")), now), StorageTier::L2);
+        assert_eq!(policy.tier_of(&day(ts("2024-01-01 00:00:00")), now), StorageTier::L3);
+        assert_eq!(policy.tier_of(&day(ts("2023-06-01 00:00:00")), now), StorageTier::L4);
+        assert_eq!(policy.tier_of(&Partition { name: String::new(), range: None, value: None }, now), StorageTier::L1);
+    }
+
+    #[test]
+    fn test_parse_partition_commands() {
+        let command = PartitionCommand::parse(
+            "CREATE TABLE Trades (id BIGINT PRIMARY KEY, exchange VARCHAR(8), price DECIMAL(18, 4) NOT NULL, ts TIMESTAMP) \
+             PARTITION BY DAY(ts) WITH (l1 = '2 days', l2 = '14 days')",
+        ).unwrap().unwrap();
+        let PartitionCommand::CreateTable { definition, if_not_exists } = command else {
+            panic!("expected CREATE TABLE");
+        };
+        assert!(!if_not_exists);
+        assert_eq!(definition.name, "trades");
+        assert_eq!(definition.primary_key.as_deref(), Some("id"));
+        assert_eq!(definition.column_names(), vec!["id", "exchange", "price", "ts"]);
+        assert_eq!(definition.column("price").unwrap().column_type, ColumnType::Decimal);
+        assert!(!definition.column("ts").unwrap().nullable);
+        let spec = definition.partitioning.unwrap();
+        assert_eq!(spec.kind, PartitionKind::Time(TimeGranularity::Day));
+        assert_eq!(spec.tiering.thresholds, vec![(2 * intervals::DAY, StorageTier::L1), (14 * intervals::DAY, StorageTier::L2)]);
+
+        let command = PartitionCommand::parse(
+            "create table if not exists quotes (symbol SYMBOL, exchange TEXT, bid PRICE, PRIMARY KEY (symbol)) partition by list (exchange);",
+        ).unwrap().unwrap();
+        let PartitionCommand::CreateTable { definition, if_not_exists } = command else {
+            panic!("expected CREATE TABLE");
+        };
+        assert!(if_not_exists);
+        assert_eq!(definition.partitioning.unwrap().kind, PartitionKind::List);
+        assert_eq!(definition.column("bid").unwrap().column_type, ColumnType::Price);
+
+        assert_eq!(
+            PartitionCommand::parse("ALTER TABLE trades DROP PARTITION IF EXISTS P20240115").unwrap(),
+            Some(PartitionCommand::DropPartition { table: "trades".to_string(), partition: "p20240115".to_string(), if_exists: true }),
+        );
+        assert_eq!(
+            PartitionCommand::parse("ALTER TABLE trades DETACH PARTITION p20240115 INTO trades_archive").unwrap(),
+            Some(PartitionCommand::DetachPartition {
+                table: "trades".to_string(),
+                partition: "p20240115".to_string(),
+                target: Some("trades_archive".to_string()),
+            }),
+        );
+        assert!(PartitionCommand::parse("CREATE TABLE t (ts TIMESTAMP) PARTITION BY WEEK(ts)").is_err());
+        assert!(PartitionCommand::parse("CREATE TABLE t (ts TIMESTAMP) PARTITION BY DAY(ts) WITH (l2 = '1 day', l1 = '2 days')").is_err());
+        assert!(PartitionCommand::parse("CREATE TABLE t (id BIGINT)").unwrap().is_none());
+        assert!(PartitionCommand::parse("SELECT * FROM trades").unwrap().is_none());
+    }
+}
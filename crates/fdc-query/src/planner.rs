@@ -1,9 +1,23 @@
 //! Query planner for creating execution plans
 
-use crate::{parser::ParsedQuery, optimizer::OptimizedPlan};
+use crate::{
+    catalog::Catalog,
+    cost::{AccessMethod, ROWS_PER_MS},
+    executor::parse_statement,
+    grouping::collect_aggregates,
+    parser::{ParsedQuery, SqlParser},
+    optimizer::OptimizedPlan,
+    profile::OperatorMetrics,
+    sampling::extract_sample_by,
+    security::{Privilege, QuerySecurity},
+    statistics::DEFAULT_ROW_COUNT,
+};
 use fdc_core::error::{Error, Result};
 use serde::{Deserialize, Serialize};
+use sqlparser::ast::{GroupByExpr, Select, SelectItem, SetExpr, SetOperator, SetQuantifier, Statement};
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
 
 /// 计划节点类型
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -26,6 +40,20 @@ pub enum PlanNode {
     Join { join_type: JoinType, condition: String },
     /// 联合
     Union { all: bool },
+    /// 交集
+    Intersect { all: bool },
+    /// 差集
+    Except { all: bool },
+    /// 时间分桶降采样
+    SampleBy { interval: String, fill: Vec<String>, align: String },
+    /// 窗口函数
+    Window { functions: Vec<String> },
+    /// 把查询片段分发到各分片并行执行
+    Exchange { shards: usize, fragment: String },
+    /// 按连接键把行哈希重分区
+    Shuffle { keys: Vec<String>, partitions: usize },
+    /// 汇集各分片的结果（拼接、归并排序或最终聚合）
+    Gather { merge: String },
 }
 
 /// 连接类型
@@ -36,6 +64,10 @@ pub enum JoinType {
     Right,
     Full,
     Cross,
+    /// 左半连接（EXISTS / IN）
+    LeftSemi,
+    /// 左反连接（NOT EXISTS / NOT IN）
+    LeftAnti,
 }
 
 /// 执行计划
@@ -51,6 +83,9 @@ pub struct ExecutionPlan {
     pub estimated_rows: u64,
     /// 计划属性
     pub properties: HashMap<String, String>,
+    /// EXPLAIN ANALYZE收集的实际运行指标
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<OperatorMetrics>,
 }
 
 impl ExecutionPlan {
@@ -62,6 +97,7 @@ impl ExecutionPlan {
             estimated_cost: 100.0,
             estimated_rows: 1000,
             properties: HashMap::new(),
+            actual: None,
         }
     }
     
@@ -94,12 +130,120 @@ impl ExecutionPlan {
     pub fn total_cost(&self) -> f64 {
         self.estimated_cost + self.children.iter().map(|c| c.total_cost()).sum::<f64>()
     }
+    
+    /// 以缩进树的文本形式输出计划，每个节点一行
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        self.render_into(&mut output, 0);
+        output
+    }
+    
+    fn render_into(&self, output: &mut String, depth: usize) {
+        use std::fmt::Write;
+        
+        let prefix = if depth == 0 { String::new() } else { format!("{}-> ", "   ".repeat(depth - 1)) };
+        let _ = write!(output, "{}{}  (cost={:.2} rows={})", prefix, self.root, self.estimated_cost, self.estimated_rows);
+        if let Some(actual) = &self.actual {
+            let _ = write!(
+                output,
+                " (actual rows={} time={:.3}ms read={}B memory={}B spills={})",
+                actual.rows,
+                actual.elapsed_us as f64 / 1000.0,
+                actual.bytes_read,
+                actual.memory_peak,
+                actual.spills,
+            );
+        }
+        let mut properties: Vec<_> = self.properties.iter().collect();
+        properties.sort();
+        for (key, value) in properties {
+            let _ = write!(output, " [{}: {}]", key, value);
+        }
+        output.push('\n');
+        for child in &self.children {
+            child.render_into(output, depth + 1);
+        }
+    }
+}
+
+impl fmt::Display for ExecutionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render())
+    }
+}
+
+impl fmt::Display for PlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanNode::TableScan { table, filters } => {
+                write!(f, "TableScan on {}", table)?;
+                if !filters.is_empty() {
+                    write!(f, " filter: {}", filters.join(" AND "))?;
+                }
+                Ok(())
+            }
+            PlanNode::IndexScan { table, index, conditions } => {
+                write!(f, "IndexScan on {} using {}", table, index)?;
+                if !conditions.is_empty() {
+                    write!(f, " cond: {}", conditions.join(" AND "))?;
+                }
+                Ok(())
+            }
+            PlanNode::Filter { condition } => write!(f, "Filter: {}", condition),
+            PlanNode::Projection { columns } => write!(f, "Projection: {}", columns.join(", ")),
+            PlanNode::Sort { columns, ascending } => {
+                let keys: Vec<String> = columns.iter().enumerate()
+                    .map(|(i, column)| match ascending.get(i) {
+                        Some(false) => format!("{} DESC", column),
+                        _ => column.clone(),
+                    })
+                    .collect();
+                write!(f, "Sort: {}", keys.join(", "))
+            }
+            PlanNode::Limit { count, offset } => write!(f, "Limit: {} offset {}", count, offset),
+            PlanNode::Aggregate { group_by, aggregates } => {
+                write!(f, "Aggregate: [{}]", aggregates.join(", "))?;
+                if !group_by.is_empty() {
+                    write!(f, " group by {}", group_by.join(", "))?;
+                }
+                Ok(())
+            }
+            PlanNode::Join { join_type, condition } => {
+                write!(f, "{:?} Join", join_type)?;
+                if !condition.is_empty() {
+                    write!(f, " on {}", condition)?;
+                }
+                Ok(())
+            }
+            PlanNode::Union { all } => f.write_str(if *all { "Union All" } else { "Union" }),
+            PlanNode::Intersect { all } => f.write_str(if *all { "Intersect All" } else { "Intersect" }),
+            PlanNode::Except { all } => f.write_str(if *all { "Except All" } else { "Except" }),
+            PlanNode::SampleBy { interval, fill, align } => {
+                write!(f, "SampleBy {}", interval)?;
+                if !fill.is_empty() {
+                    write!(f, " fill({})", fill.join(", "))?;
+                }
+                if !align.is_empty() {
+                    write!(f, " align {}", align)?;
+                }
+                Ok(())
+            }
+            PlanNode::Window { functions } => write!(f, "Window: {}", functions.join(", ")),
+            PlanNode::Exchange { shards, fragment } => write!(f, "Exchange to {} shards: {}", shards, fragment),
+            PlanNode::Shuffle { keys, partitions } => {
+                write!(f, "Shuffle into {} partitions by {}", partitions, keys.join(", "))
+            }
+            PlanNode::Gather { merge } => write!(f, "Gather: {}", merge),
+        }
+    }
 }
 
 /// 查询计划器
 pub struct QueryPlanner {
     /// 统计信息
     table_stats: HashMap<String, TableStats>,
+    /// 表目录（提供ANALYZE收集的统计信息）
+    catalog: Option<Arc<Catalog>>,
 }
 
 /// 表统计信息
@@ -115,15 +259,22 @@ impl QueryPlanner {
     pub fn new() -> Self {
         Self {
             table_stats: HashMap::new(),
+            catalog: None,
         }
     }
     
+    /// 使用表目录中的统计信息估算行数
+    pub fn with_catalog(mut self, catalog: Arc<Catalog>) -> Self {
+        self.catalog = Some(catalog);
+        self
+    }
+    
     /// 创建执行计划
     pub fn create_plan(&self, optimized_plan: &OptimizedPlan) -> Result<ExecutionPlan> {
         let query = &optimized_plan.original_query;
         
         match query.query_type {
-            crate::parser::QueryType::Select => self.create_select_plan(query),
+            crate::parser::QueryType::Select => self.create_select_plan(optimized_plan),
             crate::parser::QueryType::Insert => self.create_insert_plan(query),
             crate::parser::QueryType::Update => self.create_update_plan(query),
             crate::parser::QueryType::Delete => self.create_delete_plan(query),
@@ -131,15 +282,104 @@ impl QueryPlanner {
         }
     }
     
+    /// 检查用户执行查询所需的权限，并解析需要注入扫描的行策略条件与列掩码
+    ///
+    /// 没有表启用访问控制、或查询只涉及开放的表时返回None。
+    pub fn secure(&self, query: &ParsedQuery, user: &str) -> Result<Option<Arc<QuerySecurity>>> {
+        let catalog = match &self.catalog {
+            Some(catalog) if catalog.security().has_access_control() => catalog,
+            _ => return Ok(None),
+        };
+        let (sql, _) = extract_sample_by(&query.sql)?;
+        let security = QuerySecurity::authorize(&parse_statement(&sql)?, catalog, user)?;
+        Ok((!security.is_empty()).then(|| Arc::new(security)))
+    }
+    
+    /// 在计划的扫描节点上标注注入的行策略条件与被掩码的列（EXPLAIN）
+    pub fn annotate_security(&self, plan: &mut ExecutionPlan, query: &ParsedQuery, security: &QuerySecurity) {
+        let privilege = match query.query_type {
+            crate::parser::QueryType::Insert => Privilege::Insert,
+            crate::parser::QueryType::Update => Privilege::Update,
+            crate::parser::QueryType::Delete => Privilege::Delete,
+            _ => Privilege::Select,
+        };
+        annotate_security(plan, privilege, security);
+    }
+    
     /// 创建SELECT执行计划
-    fn create_select_plan(&self, query: &ParsedQuery) -> Result<ExecutionPlan> {
-        let mut plan = if query.tables.len() == 1 {
+    fn create_select_plan(&self, optimized_plan: &OptimizedPlan) -> Result<ExecutionPlan> {
+        let query = &optimized_plan.original_query;
+        if query.features.has_set_operation {
+            if let Ok(Statement::Query(parsed)) = parse_statement(&query.sql) {
+                if matches!(parsed.body.as_ref(), SetExpr::SetOperation { .. }) {
+                    return self.create_set_expr_plan(&parsed.body);
+                }
+            }
+        }
+        let mut plan = if !optimized_plan.access_paths.is_empty() {
+            // 基于代价模型选定的访问路径与连接顺序
+            self.create_costed_plan(optimized_plan)
+        } else if query.tables.len() == 1 {
             // 单表查询
             self.create_single_table_plan(&query.tables[0], query)?
         } else {
             // 多表查询
             self.create_multi_table_plan(&query.tables, query)?
         };
+        let select = parse_select(&query.sql);
+        
+        // 添加过滤（下推到扫描的条件在连接后仍整体校验一次）
+        if let Some(selection) = select.as_ref().and_then(|s| s.selection.as_ref()) {
+            let rows = plan.estimated_rows;
+            let mut filter_plan = ExecutionPlan::new(PlanNode::Filter { condition: selection.to_string() });
+            filter_plan.set_estimates(rows as f64 / ROWS_PER_MS, rows);
+            filter_plan.add_child(plan);
+            plan = filter_plan;
+        }
+        
+        // 添加分组聚合（SAMPLE BY自带分组）
+        if query.features.has_aggregate && query.sample_by.is_none() {
+            if let Some(select) = &select {
+                let group_by = match &select.group_by {
+                    GroupByExpr::Expressions(exprs, _) => exprs.iter().map(|e| e.to_string()).collect(),
+                    GroupByExpr::All(_) => vec!["ALL".to_string()],
+                };
+                let projected = select.projection.iter().filter_map(|item| match item {
+                    SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => Some(expr),
+                    _ => None,
+                });
+                let aggregates = collect_aggregates(projected.chain(select.having.as_ref()))?
+                    .into_iter()
+                    .map(|call| call.key)
+                    .collect();
+                let mut aggregate_plan = ExecutionPlan::new(PlanNode::Aggregate { group_by, aggregates });
+                aggregate_plan.set_estimates(plan.estimated_rows as f64 / ROWS_PER_MS, plan.estimated_rows);
+                aggregate_plan.add_child(plan);
+                plan = aggregate_plan;
+            }
+        }
+        
+        // 添加降采样
+        if let Some(sample_by) = &query.sample_by {
+            let sample_node = PlanNode::SampleBy {
+                interval: sample_by.interval_text.clone(),
+                fill: sample_by.fill.iter().map(|f| f.to_string()).collect(),
+                align: sample_by.alignment.to_string(),
+            };
+            let mut sample_plan = ExecutionPlan::new(sample_node);
+            sample_plan.add_child(plan);
+            plan = sample_plan;
+        }
+        
+        // 添加窗口函数
+        if query.features.has_window_function {
+            let window_node = PlanNode::Window {
+                functions: query.features.window_functions.clone(),
+            };
+            let mut window_plan = ExecutionPlan::new(window_node);
+            window_plan.add_child(plan);
+            plan = window_plan;
+        }
         
         // 添加排序
         if query.sql.to_lowercase().contains("order by") {
@@ -166,6 +406,61 @@ impl QueryPlanner {
         Ok(plan)
     }
     
+    /// 按优化器选定的访问路径和连接顺序构建左深连接树
+    fn create_costed_plan(&self, optimized_plan: &OptimizedPlan) -> ExecutionPlan {
+        let mut scans = optimized_plan.access_paths.iter().map(|path| {
+            let node = match &path.method {
+                AccessMethod::PrimaryKeyLookup { column } => PlanNode::IndexScan {
+                    table: path.table.clone(),
+                    index: "primary".to_string(),
+                    conditions: path.filters.iter()
+                        .filter(|f| f.contains(column.as_str()))
+                        .cloned()
+                        .collect(),
+                },
+                AccessMethod::SeqScan => PlanNode::TableScan {
+                    table: path.table.clone(),
+                    filters: path.filters.clone(),
+                },
+            };
+            let mut plan = ExecutionPlan::new(node);
+            plan.set_estimates(path.cost, path.estimated_rows.round() as u64);
+            plan.add_property("table_rows".to_string(), path.table_rows.to_string());
+            if path.qualifier != path.table {
+                plan.add_property("alias".to_string(), path.qualifier.clone());
+            }
+            if let Some(range) = &path.time_range {
+                plan.add_property("time_range".to_string(), format!(
+                    "{} in [{}, {}], {:.1}% scanned",
+                    range.column,
+                    range.start.map(|ts| ts.to_string()).unwrap_or_else(|| "-inf".to_string()),
+                    range.end.map(|ts| ts.to_string()).unwrap_or_else(|| "+inf".to_string()),
+                    range.scanned_fraction * 100.0,
+                ));
+            }
+            if let Some(partitions) = &path.partitions {
+                plan.add_property("partitions".to_string(), format!(
+                    "{} of {} ({})", partitions.scanned.len(), partitions.total, partitions.scanned.join(", "),
+                ));
+            }
+            plan
+        });
+        
+        let mut plan = scans.next().unwrap_or_else(|| ExecutionPlan::new(PlanNode::Projection { columns: Vec::new() }));
+        for (right, step) in scans.zip(&optimized_plan.join_estimates) {
+            let join_type = if step.conditions.is_empty() { JoinType::Cross } else { JoinType::Inner };
+            let mut join = ExecutionPlan::new(PlanNode::Join {
+                join_type,
+                condition: step.conditions.join(" AND "),
+            });
+            join.set_estimates(step.cost, step.estimated_rows.round() as u64);
+            join.add_child(plan);
+            join.add_child(right);
+            plan = join;
+        }
+        plan
+    }
+    
     /// 创建单表计划
     fn create_single_table_plan(&self, table: &str, query: &ParsedQuery) -> Result<ExecutionPlan> {
         let scan_node = if query.sql.to_lowercase().contains("where") {
@@ -275,13 +570,28 @@ impl QueryPlanner {
             .unwrap_or(false)
     }
     
-    /// 获取表统计信息
+    /// 获取表统计信息：显式设置的优先，其次是目录中ANALYZE的结果
     fn get_table_stats(&self, table: &str) -> TableStats {
-        self.table_stats.get(table).cloned().unwrap_or(TableStats {
-            row_count: 1000,
-            avg_row_size: 100,
-            indexes: vec!["primary".to_string()],
-        })
+        if let Some(stats) = self.table_stats.get(table) {
+            return stats.clone();
+        }
+        let catalog = self.catalog.as_ref();
+        let indexes = match catalog.and_then(|c| c.get_table(table)) {
+            Some(definition) => definition.primary_key.iter().map(|_| "primary".to_string()).collect(),
+            None => vec!["primary".to_string()],
+        };
+        match catalog.and_then(|c| c.statistics(table)) {
+            Some(statistics) => TableStats {
+                row_count: statistics.row_count,
+                avg_row_size: statistics.avg_row_size as usize,
+                indexes,
+            },
+            None => TableStats {
+                row_count: DEFAULT_ROW_COUNT,
+                avg_row_size: 100,
+                indexes,
+            },
+        }
     }
     
     /// 设置表统计信息
@@ -294,6 +604,77 @@ impl QueryPlanner {
     }
 }
 
+/// 解析SELECT语句（去掉SAMPLE BY子句），其他语句返回None
+impl QueryPlanner {
+    /// 集合运算的计划：两侧分支分别规划后作为子计划
+    fn create_set_expr_plan(&self, body: &SetExpr) -> Result<ExecutionPlan> {
+        match body {
+            SetExpr::SetOperation { op, set_quantifier, left, right } => {
+                let all = matches!(set_quantifier, SetQuantifier::All);
+                let left = self.create_set_expr_plan(left)?;
+                let right = self.create_set_expr_plan(right)?;
+                let (root, rows) = match op {
+                    SetOperator::Union => (PlanNode::Union { all }, left.estimated_rows + right.estimated_rows),
+                    SetOperator::Intersect => (PlanNode::Intersect { all }, left.estimated_rows.min(right.estimated_rows)),
+                    SetOperator::Except => (PlanNode::Except { all }, left.estimated_rows),
+                };
+                let mut plan = ExecutionPlan::new(root);
+                plan.set_estimates((left.estimated_rows + right.estimated_rows) as f64 / ROWS_PER_MS, rows);
+                plan.add_child(left);
+                plan.add_child(right);
+                Ok(plan)
+            }
+            SetExpr::Query(query) => self.create_set_expr_plan(&query.body),
+            SetExpr::Select(_) => {
+                let parsed = SqlParser::new().parse(&body.to_string())?;
+                if parsed.tables.is_empty() {
+                    let mut plan = ExecutionPlan::new(PlanNode::Projection { columns: vec![body.to_string()] });
+                    plan.set_estimates(1.0, 1);
+                    return Ok(plan);
+                }
+                self.create_select_plan(&OptimizedPlan::new(parsed))
+            }
+            other => {
+                let mut plan = ExecutionPlan::new(PlanNode::Projection { columns: vec![other.to_string()] });
+                plan.set_estimates(1.0, 1);
+                Ok(plan)
+            }
+        }
+    }
+}
+
+/// 递归标注扫描节点上的行策略条件与掩码列
+fn annotate_security(plan: &mut ExecutionPlan, privilege: Privilege, security: &QuerySecurity) {
+    let (table, conditions) = match &mut plan.root {
+        PlanNode::TableScan { table, filters } => (table.clone(), Some(filters)),
+        PlanNode::IndexScan { table, conditions, .. } => (table.clone(), Some(conditions)),
+        _ => (String::new(), None),
+    };
+    if let (Some(conditions), Some(access)) = (conditions, security.table(&table)) {
+        if let Some(filter) = access.row_filter(privilege) {
+            conditions.push(format!("policy: {}", filter));
+        }
+        let masked: Vec<&str> = access.masked_columns().collect();
+        if !masked.is_empty() {
+            plan.properties.insert("masked".to_string(), masked.join(", "));
+        }
+    }
+    for child in &mut plan.children {
+        annotate_security(child, privilege, security);
+    }
+}
+
+fn parse_select(sql: &str) -> Option<Select> {
+    let (sql, _) = extract_sample_by(sql).ok()?;
+    match parse_statement(&sql).ok()? {
+        Statement::Query(query) => match *query.body {
+            SetExpr::Select(select) => Some(*select),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 impl Default for QueryPlanner {
     fn default() -> Self {
         Self::new()
@@ -320,6 +701,20 @@ mod tests {
         assert_eq!(plan.depth(), 1);
     }
 
+    #[test]
+    fn test_set_operation_plan() {
+        let planner = QueryPlanner::new();
+        let query = SqlParser::new()
+            .parse("SELECT id FROM users UNION ALL SELECT user_id FROM orders EXCEPT SELECT 1")
+            .unwrap();
+        let plan = planner.create_plan(&OptimizedPlan::new(query)).unwrap();
+        
+        assert_eq!(plan.root, PlanNode::Except { all: false });
+        assert_eq!(plan.children[0].root, PlanNode::Union { all: true });
+        assert_eq!(plan.children[0].children.len(), 2);
+        assert!(plan.render().contains("Union All"));
+    }
+
     #[test]
     fn test_planner_creation() {
         let planner = QueryPlanner::new();
@@ -0,0 +1,638 @@
+//! Prepared statements: placeholder discovery, parameter type inference and binding
+
+use crate::{
+    catalog::{Catalog, ColumnType, TableDefinition},
+    optimizer::OptimizedPlan,
+    sampling::SampleBy,
+};
+use dashmap::DashMap;
+use fdc_core::{error::{Error, Result}, types::Value};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlparser::{
+    ast::{
+        self, visit_expressions, visit_relations, AssignmentTarget, DataType, Expr, SetExpr, Statement,
+    },
+    dialect::GenericDialect,
+    parser::Parser,
+};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// 默认缓存的语句数量
+pub const DEFAULT_STATEMENT_CACHE_CAPACITY: usize = 1024;
+
+/// 预处理语句的参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterInfo {
+    /// 绑定名（`$1`为`1`，`:sym`为`sym`）
+    pub name: String,
+    /// SQL中的占位符原文
+    pub placeholder: String,
+    /// 从表结构推断或显式声明的类型
+    pub data_type: Option<ColumnType>,
+}
+
+/// 占位符的绑定名
+pub fn parameter_name(placeholder: &str) -> &str {
+    placeholder.trim_start_matches(['$', ':', '?', '@'])
+}
+
+/// 按出现顺序收集语句中的占位符（去重）
+pub fn collect_placeholders(statement: &Statement) -> Vec<String> {
+    let mut placeholders: Vec<String> = Vec::new();
+    let _ = visit_expressions(statement, |expr| {
+        if let Some(p) = placeholder(expr) {
+            if !placeholders.iter().any(|existing| existing == p) {
+                placeholders.push(p.to_string());
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    placeholders
+}
+
+fn placeholder(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Value(ast::Value::Placeholder(p)) => Some(p),
+        Expr::Nested(inner) => placeholder(inner),
+        _ => None,
+    }
+}
+
+/// 收集占位符并根据表结构推断参数类型
+///
+/// 识别`列 op 参数`、`列 BETWEEN 参数 AND 参数`、`列 IN (参数, ...)`、`列 LIKE 参数`、
+/// `CAST(参数 AS 类型)`、`LIMIT/OFFSET 参数`、INSERT的VALUES位置和UPDATE的SET赋值。
+/// 位置参数（`$1`、`$2`）按序号排列，命名参数按首次出现顺序排在其后。
+pub fn infer_parameters(statement: &Statement, catalog: &Catalog) -> Vec<ParameterInfo> {
+    let mut tables: Vec<TableDefinition> = Vec::new();
+    let _ = visit_relations(statement, |name| {
+        if let Some(table) = name.0.last().and_then(|t| catalog.get_table(&t.value)) {
+            if !tables.iter().any(|existing| existing.name == table.name) {
+                tables.push(table);
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    let column_type = |expr: &Expr| column_type_of(expr, &tables);
+
+    let mut hints: HashMap<String, ColumnType> = HashMap::new();
+    let mut hint = |expr: &Expr, data_type: Option<ColumnType>| {
+        if let (Some(p), Some(data_type)) = (placeholder(expr), data_type) {
+            hints.entry(p.to_string()).or_insert(data_type);
+        }
+    };
+
+    let _ = visit_expressions(statement, |expr| {
+        match expr {
+            Expr::BinaryOp { left, right, .. } => {
+                hint(right, column_type(left));
+                hint(left, column_type(right));
+            }
+            Expr::Between { expr, low, high, .. } => {
+                hint(low, column_type(expr));
+                hint(high, column_type(expr));
+            }
+            Expr::InList { expr, list, .. } => {
+                for item in list {
+                    hint(item, column_type(expr));
+                }
+            }
+            Expr::Like { pattern, .. } | Expr::ILike { pattern, .. } => hint(pattern, Some(ColumnType::String)),
+            Expr::Cast { expr, data_type, .. } => hint(expr, ColumnType::from_sql_type(data_type)),
+            _ => {}
+        }
+        ControlFlow::<()>::Continue(())
+    });
+
+    match statement {
+        Statement::Query(query) => {
+            if let Some(limit) = &query.limit {
+                hint(limit, Some(ColumnType::Int64));
+            }
+            if let Some(offset) = &query.offset {
+                hint(&offset.value, Some(ColumnType::Int64));
+            }
+        }
+        Statement::Insert(insert) => {
+            let columns: Vec<String> = match (insert.columns.is_empty(), tables.first()) {
+                (false, _) => insert.columns.iter().map(|c| c.value.clone()).collect(),
+                (true, Some(table)) => table.column_names(),
+                (true, None) => Vec::new(),
+            };
+            if let Some(SetExpr::Values(values)) = insert.source.as_ref().map(|q| q.body.as_ref()) {
+                for row in &values.rows {
+                    for (value, column) in row.iter().zip(&columns) {
+                        hint(value, tables.iter().find_map(|t| t.column(column)).map(|c| c.column_type));
+                    }
+                }
+            }
+        }
+        Statement::Update { assignments, .. } => {
+            for assignment in assignments {
+                if let AssignmentTarget::ColumnName(name) = &assignment.target {
+                    let column = name.0.last().map(|i| i.value.as_str()).unwrap_or_default();
+                    hint(&assignment.value, tables.iter().find_map(|t| t.column(column)).map(|c| c.column_type));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let mut parameters: Vec<ParameterInfo> = collect_placeholders(statement)
+        .into_iter()
+        .map(|p| ParameterInfo {
+            name: parameter_name(&p).to_string(),
+            data_type: hints.get(&p).copied(),
+            placeholder: p,
+        })
+        .collect();
+    // 位置参数按序号排在前面，命名参数保持出现顺序
+    parameters.sort_by_key(|p| p.name.parse::<usize>().unwrap_or(usize::MAX));
+    parameters
+}
+
+/// 列引用在涉及的表中的类型
+fn column_type_of(expr: &Expr, tables: &[TableDefinition]) -> Option<ColumnType> {
+    let name = match expr {
+        Expr::Identifier(ident) => &ident.value,
+        Expr::CompoundIdentifier(idents) => &idents.last()?.value,
+        Expr::Nested(inner) => return column_type_of(inner, tables),
+        _ => return None,
+    };
+    tables.iter().find_map(|t| t.column(name)).map(|c| c.column_type)
+}
+
+/// 查询结果的一列
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultColumn {
+    /// 输出列名（与结果行中的键一致）
+    pub name: String,
+    /// 从表结构或表达式推断的类型
+    pub data_type: Option<ColumnType>,
+}
+
+/// 推断SELECT结果的列（按投影顺序）
+///
+/// 通配符按FROM子句中各表的定义展开，`lookup`按表名返回表定义（含系统表）。
+/// 集合运算取最左侧分支的投影。无法确定列（非查询语句、通配符引用未知关系）时返回None，
+/// 调用方应退回到按结果行推断列。
+pub fn describe_columns(statement: &Statement, lookup: impl Fn(&str) -> Option<TableDefinition>) -> Option<Vec<ResultColumn>> {
+    let Statement::Query(query) = statement else {
+        return None;
+    };
+    let mut body = query.body.as_ref();
+    let select = loop {
+        match body {
+            SetExpr::Select(select) => break select,
+            SetExpr::SetOperation { left, .. } => body = left,
+            SetExpr::Query(inner) => body = inner.body.as_ref(),
+            _ => return None,
+        }
+    };
+
+    // FROM子句中的表：(限定名, 表定义)，派生表等未知关系为None
+    let mut relations: Vec<(String, Option<TableDefinition>)> = Vec::new();
+    for table in &select.from {
+        for factor in std::iter::once(&table.relation).chain(table.joins.iter().map(|join| &join.relation)) {
+            match factor {
+                ast::TableFactor::Table { name, alias, .. } => {
+                    let qualifier = alias.as_ref().map(|a| a.name.value.clone())
+                        .or_else(|| name.0.last().map(|i| i.value.clone()))
+                        .unwrap_or_default();
+                    relations.push((qualifier, lookup(&name.to_string())));
+                }
+                other => {
+                    let qualifier = match other {
+                        ast::TableFactor::Derived { alias: Some(alias), .. } => alias.name.value.clone(),
+                        _ => String::new(),
+                    };
+                    relations.push((qualifier, None));
+                }
+            }
+        }
+    }
+    let tables: Vec<TableDefinition> = relations.iter().filter_map(|(_, table)| table.clone()).collect();
+    let expand = |table: &Option<TableDefinition>| -> Option<Vec<ResultColumn>> {
+        Some(table.as_ref()?.columns.iter().map(|column| ResultColumn {
+            name: column.name.clone(),
+            data_type: Some(column.column_type),
+        }).collect())
+    };
+
+    let mut columns = Vec::new();
+    for item in &select.projection {
+        match item {
+            ast::SelectItem::Wildcard(_) => {
+                for (_, table) in &relations {
+                    columns.extend(expand(table)?);
+                }
+            }
+            ast::SelectItem::QualifiedWildcard(name, _) => {
+                let qualifier = name.0.last().map(|i| i.value.as_str()).unwrap_or_default();
+                let (_, table) = relations.iter().find(|(q, _)| q.eq_ignore_ascii_case(qualifier))?;
+                columns.extend(expand(table)?);
+            }
+            ast::SelectItem::UnnamedExpr(expr) => columns.push(ResultColumn {
+                name: crate::expressions::expr_output_name(expr),
+                data_type: expression_type(expr, &tables),
+            }),
+            ast::SelectItem::ExprWithAlias { expr, alias } => columns.push(ResultColumn {
+                name: alias.value.clone(),
+                data_type: expression_type(expr, &tables),
+            }),
+        }
+    }
+    Some(columns)
+}
+
+/// 投影表达式的类型（列引用、CAST、字面量与计数）
+fn expression_type(expr: &Expr, tables: &[TableDefinition]) -> Option<ColumnType> {
+    match expr {
+        Expr::Identifier(_) | Expr::CompoundIdentifier(_) => column_type_of(expr, tables),
+        Expr::Nested(inner) => expression_type(inner, tables),
+        Expr::Cast { data_type, .. } => ColumnType::from_sql_type(data_type),
+        Expr::Value(ast::Value::Number(number, _)) => Some(if number.contains(['.', 'e', 'E']) {
+            ColumnType::Float64
+        } else {
+            ColumnType::Int64
+        }),
+        Expr::Value(ast::Value::SingleQuotedString(_)) => Some(ColumnType::String),
+        Expr::Value(ast::Value::Boolean(_)) => Some(ColumnType::Boolean),
+        Expr::Function(function) if function.name.to_string().eq_ignore_ascii_case("count") => Some(ColumnType::Int64),
+        _ => None,
+    }
+}
+
+/// 预处理语句：解析与优化结果只计算一次，之后每次执行只绑定参数
+#[derive(Debug)]
+pub struct PreparedStatement {
+    /// 语句名（`PREPARE name AS ...`）
+    pub name: Option<String>,
+    /// 原始SQL
+    pub sql: String,
+    /// 参数列表
+    pub parameters: Vec<ParameterInfo>,
+    /// 缓存的优化计划
+    pub plan: OptimizedPlan,
+    /// 执行次数
+    executions: AtomicU64,
+}
+
+impl PreparedStatement {
+    /// 创建预处理语句
+    pub fn new(sql: impl Into<String>, plan: OptimizedPlan, parameters: Vec<ParameterInfo>) -> Self {
+        Self {
+            name: None,
+            sql: sql.into(),
+            parameters,
+            plan,
+            executions: AtomicU64::new(0),
+        }
+    }
+
+    /// 设置语句名
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// 按位置应用`PREPARE name (类型, ...)`中声明的参数类型
+    pub fn with_declared_types(mut self, data_types: &[DataType]) -> Result<Self> {
+        if data_types.len() > self.parameters.len() {
+            return Err(Error::validation(format!(
+                "PREPARE declares {} parameter types but the statement has {} parameters",
+                data_types.len(), self.parameters.len()
+            )));
+        }
+        for (parameter, data_type) in self.parameters.iter_mut().zip(data_types) {
+            parameter.data_type = Some(ColumnType::from_sql_type(data_type).ok_or_else(|| {
+                Error::unimplemented(format!("Parameter type {} is not supported", data_type))
+            })?);
+        }
+        Ok(self)
+    }
+
+    /// 按位置绑定参数值，返回执行上下文使用的参数表
+    pub fn bind(&self, values: Vec<Value>) -> Result<HashMap<String, Value>> {
+        if values.len() != self.parameters.len() {
+            return Err(Error::validation(format!(
+                "Statement expects {} parameters but {} were supplied",
+                self.parameters.len(), values.len()
+            )));
+        }
+        self.parameters.iter().zip(values).map(|(p, v)| Ok((p.name.clone(), coerce(p, v)?))).collect()
+    }
+
+    /// 按名称绑定参数值（键可以带或不带`$`/`:`前缀）
+    pub fn bind_named(&self, values: HashMap<String, Value>) -> Result<HashMap<String, Value>> {
+        let mut values: HashMap<String, Value> = values.into_iter()
+            .map(|(k, v)| (parameter_name(&k).to_string(), v))
+            .collect();
+        let bound = self.parameters.iter()
+            .map(|p| {
+                let value = values.remove(&p.name)
+                    .ok_or_else(|| Error::validation(format!("No value bound for parameter {}", p.placeholder)))?;
+                Ok((p.name.clone(), coerce(p, value)?))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        if let Some(extra) = values.keys().next() {
+            return Err(Error::validation(format!("Unknown parameter {}", extra)));
+        }
+        Ok(bound)
+    }
+
+    /// 记录一次执行
+    pub fn record_execution(&self) {
+        self.executions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 已执行次数
+    pub fn execution_count(&self) -> u64 {
+        self.executions.load(Ordering::Relaxed)
+    }
+}
+
+fn coerce(parameter: &ParameterInfo, value: Value) -> Result<Value> {
+    match parameter.data_type {
+        Some(data_type) => data_type.coerce(value).map_err(|e| {
+            Error::type_error(format!("Parameter {}: {}", parameter.placeholder, e))
+        }),
+        None => Ok(value),
+    }
+}
+
+/// 已注册的预处理语句（按名称）与按SQL文本缓存的语句
+pub struct PreparedStatementRegistry {
+    named: DashMap<String, Arc<PreparedStatement>>,
+    by_sql: DashMap<String, Arc<PreparedStatement>>,
+    capacity: usize,
+}
+
+impl PreparedStatementRegistry {
+    /// 创建注册表，`capacity`限制按SQL缓存的语句数
+    pub fn new(capacity: usize) -> Self {
+        Self { named: DashMap::new(), by_sql: DashMap::new(), capacity: capacity.max(1) }
+    }
+
+    /// 按SQL文本查找缓存的语句
+    pub fn get_by_sql(&self, sql: &str) -> Option<Arc<PreparedStatement>> {
+        self.by_sql.get(sql).map(|s| s.clone())
+    }
+
+    /// 按SQL文本缓存语句，超出容量时淘汰执行次数最少的语句
+    pub fn cache(&self, statement: Arc<PreparedStatement>) {
+        if self.by_sql.len() >= self.capacity {
+            let coldest = self.by_sql.iter()
+                .min_by_key(|entry| entry.value().execution_count())
+                .map(|entry| entry.key().clone());
+            if let Some(key) = coldest {
+                self.by_sql.remove(&key);
+            }
+        }
+        self.by_sql.insert(statement.sql.clone(), statement);
+    }
+
+    /// 注册命名语句
+    pub fn register(&self, statement: Arc<PreparedStatement>) -> Result<()> {
+        let name = statement.name.clone()
+            .ok_or_else(|| Error::validation("Prepared statement has no name"))?
+            .to_lowercase();
+        if self.named.contains_key(&name) {
+            return Err(Error::already_exists(format!("Prepared statement {} already exists", name)));
+        }
+        self.named.insert(name, statement);
+        Ok(())
+    }
+
+    /// 查找命名语句
+    pub fn get(&self, name: &str) -> Option<Arc<PreparedStatement>> {
+        self.named.get(&name.to_lowercase()).map(|s| s.clone())
+    }
+
+    /// 删除命名语句
+    pub fn deallocate(&self, name: &str) -> Result<()> {
+        self.named.remove(&name.to_lowercase())
+            .map(|_| ())
+            .ok_or_else(|| Error::not_found(format!("Prepared statement {} does not exist", name)))
+    }
+
+    /// 命名语句数量
+    pub fn len(&self) -> usize {
+        self.named.len()
+    }
+
+    /// 是否没有命名语句
+    pub fn is_empty(&self) -> bool {
+        self.named.is_empty()
+    }
+}
+
+impl Default for PreparedStatementRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATEMENT_CACHE_CAPACITY)
+    }
+}
+
+/// 解析后的语句（执行器按SQL文本缓存，避免重复解析）
+#[derive(Debug)]
+pub struct CachedStatement {
+    /// 语句AST
+    pub statement: Statement,
+    /// 从SQL中取出的SAMPLE BY子句
+    pub sample_by: Option<SampleBy>,
+}
+
+/// SQL文本到已解析语句的缓存
+pub struct StatementCache {
+    entries: DashMap<String, Arc<CachedStatement>>,
+    capacity: usize,
+}
+
+impl StatementCache {
+    /// 创建缓存
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: DashMap::new(), capacity: capacity.max(1) }
+    }
+
+    /// 获取已解析的语句，未命中时调用`parse`并缓存
+    pub fn get_or_parse(&self, sql: &str, parse: impl FnOnce(&str) -> Result<CachedStatement>) -> Result<Arc<CachedStatement>> {
+        if let Some(entry) = self.entries.get(sql) {
+            return Ok(entry.clone());
+        }
+        let parsed = Arc::new(parse(sql)?);
+        if self.entries.len() >= self.capacity {
+            // 容量满时淘汰任意一条
+            if let Some(key) = self.entries.iter().next().map(|e| e.key().clone()) {
+                self.entries.remove(&key);
+            }
+        }
+        self.entries.insert(sql.to_string(), parsed.clone());
+        Ok(parsed)
+    }
+
+    /// 缓存的语句数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 缓存是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for StatementCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATEMENT_CACHE_CAPACITY)
+    }
+}
+
+/// SQL级预处理命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreparedCommand {
+    /// `PREPARE name [(类型, ...)] AS <语句>`
+    Prepare { name: String, data_types: Vec<DataType>, sql: String },
+    /// `EXECUTE name [(参数, ...)]`
+    Execute { name: String, parameters: Vec<Expr> },
+    /// `DEALLOCATE [PREPARE] name`
+    Deallocate { name: String },
+}
+
+impl PreparedCommand {
+    /// 识别预处理命令；其他语句返回None
+    ///
+    /// PREPARE中的语句原样保留，因此可以包含SAMPLE BY、ASOF JOIN等扩展语法。
+    pub fn parse(sql: &str) -> Result<Option<Self>> {
+        static PREPARE: OnceLock<Regex> = OnceLock::new();
+        let prepare_re = PREPARE.get_or_init(|| {
+            Regex::new(r"(?is)^\s*PREPARE\s+([A-Za-z_][A-Za-z0-9_]*)\s*(?:\(([^)]*)\))?\s*AS\s+(.+?)\s*;?\s*$")
+                .expect("valid regex")
+        });
+
+        let keyword = sql.split_whitespace().next().unwrap_or_default().to_uppercase();
+        match keyword.as_str() {
+            "PREPARE" => {
+                let caps = prepare_re.captures(sql)
+                    .ok_or_else(|| Error::parse("Expected PREPARE name [(types)] AS statement"))?;
+                let data_types = match caps.get(2).map(|m| m.as_str().trim()).filter(|t| !t.is_empty()) {
+                    Some(types) => Parser::new(&GenericDialect {})
+                        .try_with_sql(types)
+                        .and_then(|mut p| p.parse_comma_separated(Parser::parse_data_type))
+                        .map_err(|e| Error::parse(format!("Invalid parameter types: {}", e)))?,
+                    None => Vec::new(),
+                };
+                Ok(Some(PreparedCommand::Prepare {
+                    name: caps[1].to_string(),
+                    data_types,
+                    sql: caps[3].to_string(),
+                }))
+            }
+            "EXECUTE" | "DEALLOCATE" => {
+                let statement = Parser::parse_sql(&GenericDialect {}, sql)
+                    .map_err(|e| Error::parse(format!("SQL parsing failed: {}", e)))?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| Error::parse("Empty SQL statement"))?;
+                match statement {
+                    Statement::Execute { name, parameters, .. } => {
+                        Ok(Some(PreparedCommand::Execute { name: name.value, parameters }))
+                    }
+                    Statement::Deallocate { name, .. } => Ok(Some(PreparedCommand::Deallocate { name: name.value })),
+                    _ => Ok(None),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ColumnDefinition;
+
+    fn statement(sql: &str) -> Statement {
+        Parser::parse_sql(&GenericDialect {}, sql).unwrap().remove(0)
+    }
+
+    fn catalog() -> Catalog {
+        let catalog = Catalog::new();
+        catalog.register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::Symbol),
+            ColumnDefinition::new("price", ColumnType::Float64),
+        ])).unwrap();
+        catalog
+    }
+
+    #[test]
+    fn test_infer_parameter_types() {
+        let parameters = infer_parameters(
+            &statement("SELECT * FROM trades t WHERE t.symbol = :sym AND price BETWEEN $2 AND $1 LIMIT $3"),
+            &catalog(),
+        );
+        let summary: Vec<_> = parameters.iter().map(|p| (p.name.as_str(), p.data_type)).collect();
+        assert_eq!(summary, vec![
+            ("1", Some(ColumnType::Float64)),
+            ("2", Some(ColumnType::Float64)),
+            ("3", Some(ColumnType::Int64)),
+            ("sym", Some(ColumnType::Symbol)),
+        ]);
+
+        let insert = infer_parameters(&statement("INSERT INTO trades (price, id) VALUES ($1, $2)"), &catalog());
+        assert_eq!(insert[1].data_type, Some(ColumnType::Int64));
+    }
+
+    #[test]
+    fn test_describe_columns() {
+        let catalog = catalog();
+        let lookup = |name: &str| catalog.get_table(name);
+        let describe = |sql: &str| describe_columns(&statement(sql), lookup).map(|columns| {
+            columns.into_iter().map(|c| (c.name, c.data_type)).collect::<Vec<_>>()
+        });
+
+        assert_eq!(describe("SELECT t.*, 1 AS one FROM trades t").unwrap(), vec![
+            ("id".to_string(), Some(ColumnType::Int64)),
+            ("symbol".to_string(), Some(ColumnType::Symbol)),
+            ("price".to_string(), Some(ColumnType::Float64)),
+            ("one".to_string(), Some(ColumnType::Int64)),
+        ]);
+        assert_eq!(describe("SELECT symbol, COUNT(*) AS n, price * 2 AS doubled FROM trades GROUP BY symbol").unwrap(), vec![
+            ("symbol".to_string(), Some(ColumnType::Symbol)),
+            ("n".to_string(), Some(ColumnType::Int64)),
+            ("doubled".to_string(), None),
+        ]);
+        assert!(describe("SELECT * FROM unknown_table").is_none());
+        assert!(describe("DELETE FROM trades").is_none());
+    }
+
+    #[test]
+    fn test_bind_parameters() {
+        let sql = "SELECT * FROM trades WHERE id = $1";
+        let parameters = infer_parameters(&statement(sql), &catalog());
+        let query = crate::parser::ParsedQuery::new(crate::parser::QueryType::Select, sql.to_string());
+        let prepared = PreparedStatement::new(sql, OptimizedPlan::new(query), parameters);
+
+        let bound = prepared.bind(vec![Value::String("7".to_string())]).unwrap();
+        assert_eq!(bound["1"], Value::Int64(7));
+        assert!(prepared.bind(vec![Value::String("7 OR 1=1".to_string())]).is_err());
+        assert!(prepared.bind(Vec::new()).is_err());
+        assert_eq!(prepared.bind_named(HashMap::from([("$1".to_string(), Value::Int32(3))])).unwrap()["1"], Value::Int64(3));
+    }
+
+    #[test]
+    fn test_parse_prepared_commands() {
+        let prepare = PreparedCommand::parse("PREPARE q (BIGINT) AS SELECT * FROM trades WHERE id = $1").unwrap();
+        assert!(matches!(prepare, Some(PreparedCommand::Prepare { ref name, ref data_types, ref sql })
+            if name == "q" && data_types.len() == 1 && sql == "SELECT * FROM trades WHERE id = $1"));
+
+        let execute = PreparedCommand::parse("EXECUTE q (42)").unwrap();
+        assert!(matches!(execute, Some(PreparedCommand::Execute { ref parameters, .. }) if parameters.len() == 1));
+        assert_eq!(PreparedCommand::parse("DEALLOCATE q").unwrap(), Some(PreparedCommand::Deallocate { name: "q".to_string() }));
+        assert_eq!(PreparedCommand::parse("SELECT 1").unwrap(), None);
+    }
+}
@@ -0,0 +1,325 @@
+//! Per-operator runtime profiling for EXPLAIN ANALYZE
+
+use crate::{
+    engine::ExecutionBackend,
+    joins::{estimate_row_size, Row},
+    optimizer::OptimizationStats,
+    planner::{ExecutionPlan, JoinType, PlanNode},
+};
+use fdc_core::error::{Error, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// 算子的实际运行指标
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OperatorMetrics {
+    /// 输出行数
+    pub rows: u64,
+    /// 耗时（微秒）
+    pub elapsed_us: u64,
+    /// 从存储读取的字节数
+    pub bytes_read: u64,
+    /// 峰值内存占用（字节，估算）
+    pub memory_peak: u64,
+    /// 落盘分区数
+    pub spills: u64,
+}
+
+/// 被剖析的算子类型，与执行计划节点一一对应
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OperatorKind {
+    Scan,
+    Join,
+    Filter,
+    Aggregate,
+    SampleBy,
+    Window,
+    Sort,
+    Limit,
+    Projection,
+}
+
+impl OperatorKind {
+    /// 计划节点对应的算子类型
+    pub fn of(node: &PlanNode) -> Option<Self> {
+        match node {
+            PlanNode::TableScan { .. } | PlanNode::IndexScan { .. } => Some(Self::Scan),
+            PlanNode::Join { .. } => Some(Self::Join),
+            PlanNode::Filter { .. } => Some(Self::Filter),
+            PlanNode::Aggregate { .. } => Some(Self::Aggregate),
+            PlanNode::SampleBy { .. } => Some(Self::SampleBy),
+            PlanNode::Window { .. } => Some(Self::Window),
+            PlanNode::Sort { .. } => Some(Self::Sort),
+            PlanNode::Limit { .. } => Some(Self::Limit),
+            PlanNode::Projection { .. } => Some(Self::Projection),
+            PlanNode::Union { .. } | PlanNode::Intersect { .. } | PlanNode::Except { .. } | PlanNode::Exchange { .. } | PlanNode::Shuffle { .. } | PlanNode::Gather { .. } => None,
+        }
+    }
+}
+
+/// 一个算子的剖析记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorProfile {
+    /// 算子类型
+    pub kind: OperatorKind,
+    /// 说明（扫描的表名、过滤条件、投影列等）
+    pub detail: String,
+    /// 实际运行指标
+    pub metrics: OperatorMetrics,
+}
+
+impl OperatorProfile {
+    /// 从算子开始时间与输出行创建剖析记录，峰值内存取输出行的估算大小
+    pub fn new(kind: OperatorKind, detail: impl Into<String>, started: Instant, output: &[Row]) -> Self {
+        Self {
+            kind,
+            detail: detail.into(),
+            metrics: OperatorMetrics {
+                rows: output.len() as u64,
+                elapsed_us: started.elapsed().as_micros() as u64,
+                bytes_read: 0,
+                memory_peak: output.iter().map(estimate_row_size).sum::<usize>() as u64,
+                spills: 0,
+            },
+        }
+    }
+
+    /// 设置读取的字节数
+    pub fn with_bytes_read(mut self, bytes: u64) -> Self {
+        self.metrics.bytes_read = bytes;
+        self
+    }
+
+    /// 峰值内存至少为`bytes`（例如哈希连接的构建侧）
+    pub fn with_memory_peak(mut self, bytes: u64) -> Self {
+        self.metrics.memory_peak = self.metrics.memory_peak.max(bytes);
+        self
+    }
+
+    /// 设置落盘分区数
+    pub fn with_spills(mut self, spills: u64) -> Self {
+        self.metrics.spills = spills;
+        self
+    }
+
+    /// 未在计划中出现的算子补成的计划节点
+    fn to_plan_node(&self) -> PlanNode {
+        let listed = || if self.detail.is_empty() {
+            Vec::new()
+        } else {
+            self.detail.split(", ").map(str::to_string).collect()
+        };
+        match self.kind {
+            OperatorKind::Scan => PlanNode::TableScan { table: self.detail.clone(), filters: Vec::new() },
+            OperatorKind::Join => PlanNode::Join { join_type: JoinType::Inner, condition: self.detail.clone() },
+            OperatorKind::Filter => PlanNode::Filter { condition: self.detail.clone() },
+            OperatorKind::Aggregate => PlanNode::Aggregate { group_by: listed(), aggregates: Vec::new() },
+            OperatorKind::SampleBy => PlanNode::SampleBy { interval: self.detail.clone(), fill: Vec::new(), align: String::new() },
+            OperatorKind::Window => PlanNode::Window { functions: listed() },
+            OperatorKind::Sort => PlanNode::Sort { columns: listed(), ascending: Vec::new() },
+            OperatorKind::Limit => PlanNode::Limit { count: self.metrics.rows as usize, offset: 0 },
+            OperatorKind::Projection => PlanNode::Projection { columns: listed() },
+        }
+    }
+
+    fn matches(&self, node: &PlanNode) -> bool {
+        if OperatorKind::of(node) != Some(self.kind) {
+            return false;
+        }
+        match node {
+            PlanNode::TableScan { table, .. } | PlanNode::IndexScan { table, .. } => table.eq_ignore_ascii_case(&self.detail),
+            _ => true,
+        }
+    }
+}
+
+/// 把执行器记录的算子指标标注到执行计划上
+///
+/// 节点按后序（即执行顺序）与同类型的第一条未使用记录配对，扫描还要求表名一致。
+/// 计划中没有的算子（例如投影）按执行顺序包在计划外层。
+pub fn annotate(plan: ExecutionPlan, operators: &[OperatorProfile]) -> ExecutionPlan {
+    let mut used = vec![false; operators.len()];
+    let mut plan = plan;
+    annotate_node(&mut plan, operators, &mut used);
+
+    let mut pending_scans = Vec::new();
+    for (operator, _) in operators.iter().zip(&used).filter(|(_, used)| !**used) {
+        let mut node = ExecutionPlan::new(operator.to_plan_node());
+        node.set_estimates(0.0, 0);
+        node.actual = Some(operator.metrics.clone());
+        match operator.kind {
+            OperatorKind::Scan => pending_scans.push(node),
+            OperatorKind::Join => {
+                node.add_child(plan);
+                node.children.extend(pending_scans.pop());
+                plan = node;
+            }
+            _ => {
+                node.add_child(plan);
+                plan = node;
+            }
+        }
+    }
+    plan.children.extend(pending_scans);
+    plan
+}
+
+fn annotate_node(plan: &mut ExecutionPlan, operators: &[OperatorProfile], used: &mut [bool]) {
+    for child in &mut plan.children {
+        annotate_node(child, operators, used);
+    }
+    let found = operators.iter().enumerate()
+        .position(|(i, operator)| !used[i] && operator.matches(&plan.root));
+    if let Some(index) = found {
+        used[index] = true;
+        plan.actual = Some(operators[index].metrics.clone());
+    }
+}
+
+/// EXPLAIN ANALYZE的结果：带实际指标的执行计划
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExplainAnalyze {
+    /// 标注了实际指标的执行计划
+    pub plan: ExecutionPlan,
+    /// 执行后端（分析型后端不提供算子级指标）
+    pub backend: ExecutionBackend,
+    /// 解析、优化与计划耗时（微秒）
+    pub planning_time_us: u64,
+    /// 执行耗时（微秒）
+    pub execution_time_us: u64,
+    /// 返回行数
+    pub rows: u64,
+    /// 优化统计
+    pub optimization: OptimizationStats,
+}
+
+impl ExplainAnalyze {
+    /// 文本格式
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}Backend: {:?}\nPlanning time: {:.3} ms\nExecution time: {:.3} ms\nRows: {}\n",
+            self.plan.render(),
+            self.backend,
+            self.planning_time_us as f64 / 1000.0,
+            self.execution_time_us as f64 / 1000.0,
+            self.rows,
+        )
+    }
+
+    /// JSON格式
+    pub fn to_json(&self) -> Result<serde_json::Value> {
+        serde_json::to_value(self)
+            .map_err(|e| Error::serialization(format!("Failed to encode EXPLAIN ANALYZE output: {}", e)))
+    }
+}
+
+/// EXPLAIN输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ExplainFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// SQL级的`EXPLAIN [ANALYZE] [FORMAT {TEXT|JSON}] <query>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplainCommand {
+    /// 是否实际执行并收集指标
+    pub analyze: bool,
+    /// 输出格式
+    pub format: ExplainFormat,
+    /// 被解释的查询
+    pub sql: String,
+}
+
+impl ExplainCommand {
+    /// 识别EXPLAIN命令，其他语句返回None
+    pub fn parse(sql: &str) -> Option<Self> {
+        static EXPLAIN: OnceLock<Regex> = OnceLock::new();
+        let pattern = EXPLAIN.get_or_init(|| {
+            Regex::new(r"(?is)^\s*EXPLAIN(\s+ANALYZE)?(?:\s+FORMAT\s+(TEXT|JSON))?\s+(.+?)\s*;?\s*$").expect("valid regex")
+        });
+        let captures = pattern.captures(sql)?;
+        let format = match captures.get(2).map(|m| m.as_str().to_uppercase()) {
+            Some(format) if format == "JSON" => ExplainFormat::Json,
+            _ => ExplainFormat::Text,
+        };
+        Some(Self {
+            analyze: captures.get(1).is_some(),
+            format,
+            sql: captures[3].to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_core::types::Value;
+    use std::collections::HashMap;
+
+    fn profile(kind: OperatorKind, detail: &str, rows: u64) -> OperatorProfile {
+        OperatorProfile {
+            kind,
+            detail: detail.to_string(),
+            metrics: OperatorMetrics { rows, ..Default::default() },
+        }
+    }
+
+    #[test]
+    fn test_annotate_matches_nodes_in_execution_order() {
+        let scan = |table: &str| ExecutionPlan::new(PlanNode::TableScan { table: table.to_string(), filters: Vec::new() });
+        let mut join = ExecutionPlan::new(PlanNode::Join { join_type: JoinType::Inner, condition: "f.a = a.a".to_string() });
+        join.add_child(scan("fills"));
+        join.add_child(scan("accounts"));
+        let mut filter = ExecutionPlan::new(PlanNode::Filter { condition: "a.desk = 'rates'".to_string() });
+        filter.add_child(join);
+
+        let operators = vec![
+            profile(OperatorKind::Scan, "FILLS", 200),
+            profile(OperatorKind::Scan, "accounts", 2),
+            profile(OperatorKind::Join, "", 20),
+            profile(OperatorKind::Filter, "", 20),
+            profile(OperatorKind::Projection, "f.id", 20),
+        ];
+        let plan = annotate(filter, &operators);
+
+        assert!(matches!(plan.root, PlanNode::Projection { .. }));
+        assert_eq!(plan.actual.as_ref().unwrap().rows, 20);
+        let join = &plan.children[0].children[0];
+        assert_eq!(join.actual.as_ref().unwrap().rows, 20);
+        assert_eq!(join.children[0].actual.as_ref().unwrap().rows, 200);
+        assert_eq!(join.children[1].actual.as_ref().unwrap().rows, 2);
+
+        let text = plan.render();
+        assert!(text.contains("TableScan on fills"));
+        assert!(text.contains("actual rows=200"));
+    }
+
+    #[test]
+    fn test_operator_profile_metrics() {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Int64(1));
+        let operator = OperatorProfile::new(OperatorKind::Join, "", Instant::now(), &[row])
+            .with_memory_peak(1 << 20)
+            .with_spills(4);
+        assert_eq!(operator.metrics.rows, 1);
+        assert_eq!(operator.metrics.memory_peak, 1 << 20);
+        assert_eq!(operator.metrics.spills, 4);
+    }
+
+    #[test]
+    fn test_explain_command_parse() {
+        let command = ExplainCommand::parse("EXPLAIN ANALYZE SELECT * FROM trades;").unwrap();
+        assert!(command.analyze);
+        assert_eq!(command.format, ExplainFormat::Text);
+        assert_eq!(command.sql, "SELECT * FROM trades");
+
+        let command = ExplainCommand::parse("explain format json select 1").unwrap();
+        assert!(!command.analyze);
+        assert_eq!(command.format, ExplainFormat::Json);
+        assert!(ExplainCommand::parse("SELECT 1").is_none());
+    }
+}
@@ -0,0 +1,442 @@
+//! Query history and slow query log
+//!
+//! Every statement executed by `QueryEngine` is recorded with the query, user
+//! and session ids of its `ExecutionContext`, a normalised fingerprint that
+//! groups statements differing only in literal values, a hash of the chosen
+//! plan shape, its duration, row counts and error. Entries are kept in a
+//! bounded in-memory ring exposed as the `system.query_log` system table and can
+//! additionally be appended to a regular table so the history survives restarts.
+//! Statements at or above the slow query threshold are flagged and reported with
+//! a `tracing` warning.
+
+use crate::{
+    catalog::{ColumnDefinition, ColumnType, TableDefinition},
+    executor::{ExecutionContext, ExecutionResult},
+    joins::Row,
+    planner::{ExecutionPlan, PlanNode},
+    system_tables::SystemTable,
+};
+use chrono::{DateTime, Utc};
+use fdc_core::{error::Result, types::{TimestampNs, Value}};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use sqlparser::{dialect::GenericDialect, keywords::Keyword, tokenizer::{Token, Tokenizer}};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// 查询日志系统表名
+pub const QUERY_LOG_TABLE: &str = "system.query_log";
+
+/// 查询日志配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryLogConfig {
+    /// 是否记录查询历史
+    pub enabled: bool,
+    /// 内存中保留的最近查询条数
+    pub capacity: usize,
+    /// 慢查询阈值，执行时间达到该值的查询被标记并输出警告；None不检测
+    pub slow_query_threshold: Option<Duration>,
+    /// 持久化日志表，设置后每条记录同时写入该表（表不存在时自动创建）
+    #[serde(default)]
+    pub persist_table: Option<String>,
+}
+
+impl Default for QueryLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            capacity: crate::DEFAULT_QUERY_LOG_CAPACITY,
+            slow_query_threshold: Some(Duration::from_millis(crate::DEFAULT_SLOW_QUERY_THRESHOLD_MS)),
+            persist_table: None,
+        }
+    }
+}
+
+impl QueryLogConfig {
+    /// 设置慢查询阈值
+    pub fn with_slow_query_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_query_threshold = Some(threshold);
+        self
+    }
+
+    /// 设置持久化日志表
+    pub fn with_persist_table(mut self, table: impl Into<String>) -> Self {
+        self.persist_table = Some(table.into());
+        self
+    }
+}
+
+/// 一条查询历史记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryLogEntry {
+    /// 查询ID
+    pub query_id: String,
+    /// 用户ID
+    pub user_id: Option<String>,
+    /// 会话ID
+    pub session_id: Option<String>,
+    /// SQL文本
+    pub sql: String,
+    /// 常量替换为`?`后的规范化SQL
+    pub normalized_sql: String,
+    /// 规范化SQL的哈希，只有常量不同的语句指纹相同
+    pub fingerprint: String,
+    /// 执行计划形状的哈希（未生成计划的语句为None）
+    pub plan_hash: Option<String>,
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    /// 执行时间
+    pub duration: Duration,
+    /// 返回的行数
+    pub rows: u64,
+    /// 影响的行数
+    pub affected_rows: u64,
+    /// 错误信息
+    pub error: Option<String>,
+    /// 是否为慢查询
+    pub slow: bool,
+}
+
+impl QueryLogEntry {
+    /// 日志表的结构（`system.query_log`与持久化日志表相同）
+    pub fn table_definition(name: &str) -> TableDefinition {
+        TableDefinition::new(name, vec![
+            ColumnDefinition::new("query_id", ColumnType::String).not_null(),
+            ColumnDefinition::new("user_id", ColumnType::String),
+            ColumnDefinition::new("session_id", ColumnType::String),
+            ColumnDefinition::new("query", ColumnType::String).not_null(),
+            ColumnDefinition::new("normalized_query", ColumnType::String).not_null(),
+            ColumnDefinition::new("fingerprint", ColumnType::String).not_null(),
+            ColumnDefinition::new("plan_hash", ColumnType::String),
+            ColumnDefinition::new("started_at", ColumnType::Timestamp).not_null(),
+            ColumnDefinition::new("duration_us", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("rows", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("affected_rows", ColumnType::Int64).not_null(),
+            ColumnDefinition::new("error", ColumnType::String),
+            ColumnDefinition::new("slow", ColumnType::Boolean).not_null(),
+        ]).with_primary_key("query_id")
+    }
+
+    /// 转换为日志表的一行
+    pub fn to_row(&self) -> Row {
+        let optional = |value: &Option<String>| value.clone().map(Value::String).unwrap_or(Value::Null);
+        let started_at = self.started_at.timestamp_nanos_opt().unwrap_or_default();
+        HashMap::from([
+            ("query_id".to_string(), Value::String(self.query_id.clone())),
+            ("user_id".to_string(), optional(&self.user_id)),
+            ("session_id".to_string(), optional(&self.session_id)),
+            ("query".to_string(), Value::String(self.sql.clone())),
+            ("normalized_query".to_string(), Value::String(self.normalized_sql.clone())),
+            ("fingerprint".to_string(), Value::String(self.fingerprint.clone())),
+            ("plan_hash".to_string(), optional(&self.plan_hash)),
+            ("started_at".to_string(), Value::Timestamp(TimestampNs::from_nanos(started_at))),
+            ("duration_us".to_string(), Value::Int64(self.duration.as_micros() as i64)),
+            ("rows".to_string(), Value::Int64(self.rows as i64)),
+            ("affected_rows".to_string(), Value::Int64(self.affected_rows as i64)),
+            ("error".to_string(), optional(&self.error)),
+            ("slow".to_string(), Value::Bool(self.slow)),
+        ])
+    }
+}
+
+/// 执行中的查询，结束时生成日志记录
+#[derive(Debug)]
+pub(crate) struct PendingQuery {
+    query_id: String,
+    user_id: Option<String>,
+    session_id: Option<String>,
+    sql: String,
+    started_at: DateTime<Utc>,
+    started: Instant,
+}
+
+impl PendingQuery {
+    /// 查询开始时根据执行上下文创建
+    pub(crate) fn new(sql: &str, context: &ExecutionContext) -> Self {
+        Self {
+            query_id: context.query_id.clone(),
+            user_id: context.user_id.clone(),
+            session_id: context.session_id.clone(),
+            sql: sql.to_string(),
+            started_at: Utc::now(),
+            started: Instant::now(),
+        }
+    }
+
+    /// 根据执行结果生成日志记录
+    pub(crate) fn finish(self, outcome: &Result<ExecutionResult>, plan_hash: Option<String>) -> QueryLogEntry {
+        let (rows, affected_rows, error) = match outcome {
+            Ok(result) => (result.rows.len() as u64, result.affected_rows, result.error.clone()),
+            Err(error) => (0, 0, Some(error.to_string())),
+        };
+        self.finish_with(rows, affected_rows, error, plan_hash)
+    }
+
+    /// 以给定的行数与错误生成日志记录（流式查询的行数在结束时才确定）
+    pub(crate) fn finish_with(self, rows: u64, affected_rows: u64, error: Option<String>, plan_hash: Option<String>) -> QueryLogEntry {
+        let normalized_sql = normalize_sql(&self.sql);
+        QueryLogEntry {
+            query_id: self.query_id,
+            user_id: self.user_id,
+            session_id: self.session_id,
+            fingerprint: format!("{:016x}", stable_hash(normalized_sql.as_bytes())),
+            normalized_sql,
+            sql: self.sql,
+            plan_hash,
+            started_at: self.started_at,
+            duration: self.started.elapsed(),
+            rows,
+            affected_rows,
+            error,
+            slow: false,
+        }
+    }
+}
+
+/// 查询日志：最近查询的有界环形缓冲
+#[derive(Debug, Default)]
+pub struct QueryLog {
+    config: RwLock<QueryLogConfig>,
+    entries: Mutex<VecDeque<QueryLogEntry>>,
+}
+
+impl QueryLog {
+    /// 创建查询日志
+    pub fn new(config: QueryLogConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// 获取配置
+    pub fn config(&self) -> QueryLogConfig {
+        self.config.read().clone()
+    }
+
+    /// 更新配置，容量变小时丢弃最旧的记录
+    pub fn set_config(&self, config: QueryLogConfig) {
+        let mut entries = self.entries.lock();
+        while entries.len() > config.capacity {
+            entries.pop_front();
+        }
+        *self.config.write() = config;
+    }
+
+    /// 记录一条查询，返回标记慢查询后的记录；未启用时返回None
+    pub fn record(&self, mut entry: QueryLogEntry) -> Option<QueryLogEntry> {
+        let config = self.config.read().clone();
+        if !config.enabled {
+            return None;
+        }
+        entry.slow = config.slow_query_threshold.is_some_and(|threshold| entry.duration >= threshold);
+        if entry.slow {
+            tracing::warn!(
+                query_id = %entry.query_id,
+                user_id = entry.user_id.as_deref().unwrap_or(""),
+                duration_ms = entry.duration.as_millis() as u64,
+                fingerprint = %entry.fingerprint,
+                "Slow query: {}", entry.sql,
+            );
+        }
+
+        let mut entries = self.entries.lock();
+        if config.capacity > 0 {
+            if entries.len() >= config.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(entry.clone());
+        }
+        Some(entry)
+    }
+
+    /// 全部记录（按开始时间从旧到新）
+    pub fn entries(&self) -> Vec<QueryLogEntry> {
+        self.entries.lock().iter().cloned().collect()
+    }
+
+    /// 最近的`limit`条记录（从新到旧）
+    pub fn recent(&self, limit: usize) -> Vec<QueryLogEntry> {
+        self.entries.lock().iter().rev().take(limit).cloned().collect()
+    }
+
+    /// 慢查询记录（按开始时间从旧到新）
+    pub fn slow_queries(&self) -> Vec<QueryLogEntry> {
+        self.entries.lock().iter().filter(|entry| entry.slow).cloned().collect()
+    }
+
+    /// 按查询ID查找记录
+    pub fn find(&self, query_id: &str) -> Option<QueryLogEntry> {
+        self.entries.lock().iter().rev().find(|entry| entry.query_id == query_id).cloned()
+    }
+
+    /// 清空内存中的记录
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+
+impl SystemTable for QueryLog {
+    fn definition(&self) -> TableDefinition {
+        QueryLogEntry::table_definition(QUERY_LOG_TABLE)
+    }
+
+    fn scan(&self) -> Result<Vec<Row>> {
+        Ok(self.entries.lock().iter().map(QueryLogEntry::to_row).collect())
+    }
+
+    fn owner_column(&self) -> Option<&str> {
+        Some("user_id")
+    }
+}
+
+/// 规范化SQL：常量与占位符替换为`?`，连续的常量列表折叠为一个，
+/// 关键字大写、未加引号的标识符小写，去掉注释并把空白压缩为一个空格
+pub fn normalize_sql(sql: &str) -> String {
+    let dialect = GenericDialect {};
+    let Ok(tokens) = Tokenizer::new(&dialect, sql).tokenize() else {
+        // 无法分词时只压缩空白
+        return sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    };
+
+    // (前面是否有空白, 记号文本)
+    let mut output: Vec<(bool, String)> = Vec::new();
+    let mut spaced = false;
+    for token in tokens {
+        let text = match token {
+            Token::Whitespace(_) => {
+                spaced = true;
+                continue;
+            }
+            Token::EOF => break,
+            Token::Number(..)
+            | Token::SingleQuotedString(_)
+            | Token::NationalStringLiteral(_)
+            | Token::EscapedStringLiteral(_)
+            | Token::HexStringLiteral(_)
+            | Token::DollarQuotedString(_)
+            | Token::SingleQuotedByteStringLiteral(_)
+            | Token::Placeholder(_) => "?".to_string(),
+            Token::Word(word) if word.quote_style.is_none() => match word.keyword {
+                Keyword::NoKeyword => word.value.to_lowercase(),
+                _ => word.value.to_uppercase(),
+            },
+            other => other.to_string(),
+        };
+        // `?, ?` → `?`
+        let len = output.len();
+        if text == "?" && len >= 2 && output[len - 1].1 == "," && output[len - 2].1 == "?" {
+            output.pop();
+        } else {
+            output.push((spaced && !output.is_empty(), text));
+        }
+        spaced = false;
+    }
+    output.into_iter().fold(String::new(), |mut normalized, (spaced, text)| {
+        if spaced {
+            normalized.push(' ');
+        }
+        normalized.push_str(&text);
+        normalized
+    })
+}
+
+/// 执行计划形状的哈希：算子、表、索引、连接类型与分组键参与，谓词中的常量不参与
+pub fn plan_hash(plan: &ExecutionPlan) -> String {
+    fn shape(plan: &ExecutionPlan, depth: usize, output: &mut String) {
+        let node = match &plan.root {
+            PlanNode::TableScan { table, filters } => format!("TableScan {} filters={}", table, filters.len()),
+            PlanNode::IndexScan { table, index, conditions } => {
+                format!("IndexScan {} {} conditions={}", table, index, conditions.len())
+            }
+            PlanNode::Filter { .. } => "Filter".to_string(),
+            PlanNode::Projection { columns } => format!("Projection columns={}", columns.len()),
+            PlanNode::Sort { columns, ascending } => format!("Sort {:?} {:?}", columns, ascending),
+            PlanNode::Limit { .. } => "Limit".to_string(),
+            PlanNode::Aggregate { group_by, aggregates } => {
+                format!("Aggregate {:?} aggregates={}", group_by, aggregates.len())
+            }
+            PlanNode::Join { join_type, .. } => format!("{:?} Join", join_type),
+            node @ (PlanNode::Union { .. } | PlanNode::Intersect { .. } | PlanNode::Except { .. }) => node.to_string(),
+            PlanNode::SampleBy { interval, .. } => format!("SampleBy {}", interval),
+            PlanNode::Window { functions } => format!("Window functions={}", functions.len()),
+            PlanNode::Exchange { shards, .. } => format!("Exchange {}", shards),
+            PlanNode::Shuffle { keys, partitions } => format!("Shuffle {:?} {}", keys, partitions),
+            PlanNode::Gather { merge } => format!("Gather {}", merge),
+        };
+        output.push_str(&format!("{}{}\n", " ".repeat(depth), node));
+        for child in &plan.children {
+            shape(child, depth + 1, output);
+        }
+    }
+
+    let mut output = String::new();
+    shape(plan, 0, &mut output);
+    format!("{:016x}", stable_hash(output.as_bytes()))
+}
+
+/// FNV-1a哈希：跨进程与版本稳定，持久化的指纹可以直接比较
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(sql: &str, duration: Duration) -> QueryLogEntry {
+        let context = ExecutionContext::new(format!("q-{}", sql.len())).with_user_id("alice".to_string());
+        let mut entry = PendingQuery::new(sql, &context).finish(&Ok(ExecutionResult::success(vec![HashMap::new()], 0)), None);
+        entry.duration = duration;
+        entry
+    }
+
+    #[test]
+    fn test_normalize_sql() {
+        assert_eq!(
+            normalize_sql("select  Price FROM trades -- latest\nWHERE symbol = 'AAPL' AND qty IN (1, 2,3) LIMIT 10"),
+            "SELECT price FROM trades WHERE symbol = ? AND qty IN (?) LIMIT ?",
+        );
+        let first = PendingQuery::new("SELECT * FROM t WHERE id = 1", &ExecutionContext::new("a".to_string()));
+        let second = PendingQuery::new("select *  from T where ID = $1", &ExecutionContext::new("b".to_string()));
+        let outcome = Ok(ExecutionResult::success(Vec::new(), 0));
+        assert_eq!(first.finish(&outcome, None).fingerprint, second.finish(&outcome, None).fingerprint);
+    }
+
+    #[test]
+    fn test_plan_hash_ignores_literals() {
+        let plan = |condition: &str| {
+            let mut plan = ExecutionPlan::new(PlanNode::Filter { condition: condition.to_string() });
+            plan.add_child(ExecutionPlan::new(PlanNode::TableScan { table: "trades".to_string(), filters: Vec::new() }));
+            plan
+        };
+        assert_eq!(plan_hash(&plan("price > 10")), plan_hash(&plan("price > 20")));
+        let mut other = plan("price > 10");
+        other.children[0] = ExecutionPlan::new(PlanNode::TableScan { table: "quotes".to_string(), filters: Vec::new() });
+        assert_ne!(plan_hash(&plan("price > 10")), plan_hash(&other));
+    }
+
+    #[test]
+    fn test_ring_and_slow_flag() {
+        let log = QueryLog::new(QueryLogConfig { capacity: 2, ..Default::default() }
+            .with_slow_query_threshold(Duration::from_millis(100)));
+        log.record(entry("SELECT 1", Duration::from_millis(5)));
+        log.record(entry("SELECT 22", Duration::from_millis(150)));
+        let recorded = log.record(entry("SELECT 333", Duration::from_millis(1))).unwrap();
+        assert!(!recorded.slow);
+
+        let entries = log.entries();
+        assert_eq!(entries.iter().map(|e| e.sql.as_str()).collect::<Vec<_>>(), vec!["SELECT 22", "SELECT 333"]);
+        assert_eq!(log.slow_queries().len(), 1);
+        assert_eq!(log.recent(1)[0].sql, "SELECT 333");
+        assert_eq!(log.find("q-9").unwrap().rows, 1);
+
+        let rows = log.scan().unwrap();
+        assert_eq!(rows[0]["slow"], Value::Bool(true));
+        assert_eq!(rows[0]["user_id"], Value::String("alice".to_string()));
+
+        log.set_config(QueryLogConfig { enabled: false, capacity: 1, ..Default::default() });
+        assert!(log.record(entry("SELECT 4", Duration::ZERO)).is_none());
+        assert_eq!(log.entries().len(), 1);
+    }
+}
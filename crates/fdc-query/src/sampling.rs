@@ -0,0 +1,558 @@
+//! Time-bucketed downsampling: `time_bucket()` and `SAMPLE BY ... FILL ... ALIGN TO`
+
+use crate::expressions::{display_value, parse_interval_nanos, value_as_f64, value_as_i64, value_as_str, value_as_timestamp};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use fdc_core::{
+    error::{Error, Result},
+    time::{intervals, TimeUtils},
+    types::{TimestampNs, Value},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// 分桶间隔
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BucketInterval {
+    /// 固定长度（纳秒）
+    Fixed(i64),
+    /// 日历月数（`1M`、`3 months`、`1y`）
+    Months(u32),
+}
+
+impl BucketInterval {
+    /// 解析间隔文本；大写`M`表示月，小写`m`表示分钟
+    pub fn parse(text: &str) -> Result<Self> {
+        static MONTHS: OnceLock<Regex> = OnceLock::new();
+        let months = MONTHS.get_or_init(|| {
+            Regex::new(r"^(\d+)\s*(M|(?i:mo|mon|month|months)|(?i:y|yr|year|years))$").expect("valid regex")
+        });
+
+        let text = text.trim();
+        if let Some(caps) = months.captures(text) {
+            let count: u32 = caps[1].parse().map_err(|_| Error::parse(format!("Invalid interval: {}", text)))?;
+            let unit = caps[2].to_lowercase();
+            let count = if unit.starts_with('y') { count * 12 } else { count };
+            if count == 0 {
+                return Err(Error::validation("Bucket interval must be positive"));
+            }
+            return Ok(BucketInterval::Months(count));
+        }
+
+        let nanos = parse_interval_nanos(text)?;
+        if nanos <= 0 {
+            return Err(Error::validation("Bucket interval must be positive"));
+        }
+        Ok(BucketInterval::Fixed(nanos))
+    }
+}
+
+/// 空桶填充策略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FillStrategy {
+    /// 填充NULL
+    Null,
+    /// 沿用上一个桶的值
+    Prev,
+    /// 在前后两个非空桶之间线性插值
+    Linear,
+    /// 填充常量
+    Value(Value),
+}
+
+impl FillStrategy {
+    fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        Ok(match text.to_uppercase().as_str() {
+            "NULL" => FillStrategy::Null,
+            "PREV" => FillStrategy::Prev,
+            "LINEAR" => FillStrategy::Linear,
+            _ if text.starts_with('\'') && text.ends_with('\'') && text.len() >= 2 => {
+                FillStrategy::Value(Value::String(text[1..text.len() - 1].to_string()))
+            }
+            _ => {
+                if let Ok(i) = text.parse::<i64>() {
+                    FillStrategy::Value(Value::Int64(i))
+                } else if let Ok(f) = text.parse::<f64>() {
+                    FillStrategy::Value(Value::Float64(f))
+                } else {
+                    return Err(Error::parse(format!("Invalid FILL option: {}", text)));
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for FillStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FillStrategy::Null => write!(f, "NULL"),
+            FillStrategy::Prev => write!(f, "PREV"),
+            FillStrategy::Linear => write!(f, "LINEAR"),
+            FillStrategy::Value(Value::String(s)) => write!(f, "'{}'", s),
+            FillStrategy::Value(v) => write!(f, "{}", display_value(v)),
+        }
+    }
+}
+
+/// 桶对齐方式
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Alignment {
+    /// 按（时区内的）日历边界对齐
+    Calendar {
+        /// 时区相对UTC的偏移（纳秒）
+        utc_offset: i64,
+        /// 额外的对齐偏移（纳秒），例如`WITH OFFSET '00:30'`
+        offset: i64,
+    },
+    /// 以第一条数据的时间为起点
+    FirstObservation,
+}
+
+impl Default for Alignment {
+    fn default() -> Self {
+        Alignment::Calendar { utc_offset: 0, offset: 0 }
+    }
+}
+
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alignment::FirstObservation => write!(f, "ALIGN TO FIRST OBSERVATION"),
+            Alignment::Calendar { utc_offset, offset } => {
+                write!(f, "ALIGN TO CALENDAR")?;
+                if *utc_offset != 0 {
+                    write!(f, " TIME ZONE '{}'", format_offset(*utc_offset))?;
+                }
+                if *offset != 0 {
+                    write!(f, " WITH OFFSET '{}'", format_offset(*offset))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn format_offset(nanos: i64) -> String {
+    let sign = if nanos < 0 { '-' } else { '+' };
+    let minutes = nanos.abs() / intervals::MINUTE;
+    format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// SAMPLE BY子句
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleBy {
+    /// 原始间隔文本
+    pub interval_text: String,
+    /// 分桶间隔
+    pub interval: BucketInterval,
+    /// 每个聚合列的填充策略（只有一个时作用于所有列，为空表示不填充）
+    pub fill: Vec<FillStrategy>,
+    /// 对齐方式
+    pub alignment: Alignment,
+}
+
+impl SampleBy {
+    /// 按间隔创建SAMPLE BY（日历对齐、UTC、不填充）
+    pub fn new(interval: &str) -> Result<Self> {
+        Ok(Self {
+            interval_text: interval.to_string(),
+            interval: BucketInterval::parse(interval)?,
+            fill: Vec::new(),
+            alignment: Alignment::default(),
+        })
+    }
+
+    /// 设置填充策略
+    pub fn with_fill(mut self, fill: Vec<FillStrategy>) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// 设置对齐方式
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// 计算时间戳所属桶的起点；`origin`为FIRST OBSERVATION对齐的起点
+    pub fn bucket(&self, ts: i64, origin: Option<i64>) -> Result<i64> {
+        match (&self.alignment, origin, self.interval) {
+            (Alignment::FirstObservation, Some(origin), BucketInterval::Fixed(interval)) => {
+                Ok(origin + floor_nanos(ts - origin, interval))
+            }
+            (Alignment::Calendar { utc_offset, offset }, _, interval) => {
+                bucket_start(ts, interval, *utc_offset - *offset)
+            }
+            (Alignment::FirstObservation, _, interval) => bucket_start(ts, interval, 0),
+        }
+    }
+
+    /// 下一个桶的起点
+    pub fn next_bucket(&self, bucket: i64) -> Result<i64> {
+        let shift = match self.alignment {
+            Alignment::Calendar { utc_offset, offset } => utc_offset - offset,
+            Alignment::FirstObservation => 0,
+        };
+        advance(bucket, self.interval, shift)
+    }
+
+    /// 列的填充策略
+    fn fill_for(&self, index: usize) -> Option<&FillStrategy> {
+        match self.fill.len() {
+            0 => None,
+            1 => self.fill.first(),
+            _ => self.fill.get(index),
+        }
+    }
+
+    /// 为缺失的桶补行
+    ///
+    /// `rows`为投影后的结果；每个分组键（`key_columns`）在全局最小到最大桶之间
+    /// 补齐空桶，聚合列（`value_columns`）按FILL策略取值。
+    pub fn fill_rows(
+        &self,
+        rows: Vec<HashMap<String, Value>>,
+        time_column: &str,
+        key_columns: &[String],
+        value_columns: &[String],
+    ) -> Result<Vec<HashMap<String, Value>>> {
+        if self.fill.is_empty() {
+            return Ok(rows);
+        }
+        if self.fill.len() > 1 && self.fill.len() != value_columns.len() {
+            return Err(Error::validation(format!(
+                "FILL has {} options but the query has {} aggregate columns",
+                self.fill.len(), value_columns.len()
+            )));
+        }
+
+        let bucket_of = |row: &HashMap<String, Value>| {
+            row.get(time_column).and_then(value_as_timestamp).map(|ts| ts.as_nanos())
+        };
+        let (Some(first), Some(last)) = (
+            rows.iter().filter_map(bucket_of).min(),
+            rows.iter().filter_map(bucket_of).max(),
+        ) else {
+            return Ok(rows);
+        };
+        let mut buckets = vec![first];
+        while let Some(&current) = buckets.last() {
+            let next = self.next_bucket(current)?;
+            if next > last {
+                break;
+            }
+            buckets.push(next);
+        }
+
+        // 按分组键归集已有的桶
+        let mut groups: Vec<(Vec<Value>, BucketRows)> = Vec::new();
+        for row in rows {
+            let key: Vec<Value> = key_columns.iter().map(|k| row.get(k).cloned().unwrap_or(Value::Null)).collect();
+            let Some(bucket) = bucket_of(&row) else { continue };
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, existing)) => {
+                    existing.insert(bucket, row);
+                }
+                None => groups.push((key, HashMap::from([(bucket, row)]))),
+            }
+        }
+
+        let mut output = Vec::with_capacity(groups.len() * buckets.len());
+        for (key, existing) in groups {
+            let mut filled: Vec<HashMap<String, Value>> = Vec::with_capacity(buckets.len());
+            for (position, bucket) in buckets.iter().enumerate() {
+                if let Some(row) = existing.get(bucket) {
+                    filled.push(row.clone());
+                    continue;
+                }
+                let mut row: HashMap<String, Value> = key_columns.iter().cloned().zip(key.iter().cloned()).collect();
+                row.insert(time_column.to_string(), Value::Timestamp(TimestampNs::from_nanos(*bucket)));
+                for (index, column) in value_columns.iter().enumerate() {
+                    let value = match self.fill_for(index) {
+                        Some(FillStrategy::Null) | None => Value::Null,
+                        Some(FillStrategy::Value(v)) => v.clone(),
+                        Some(FillStrategy::Prev) => filled.last()
+                            .and_then(|prev| prev.get(column).cloned())
+                            .unwrap_or(Value::Null),
+                        Some(FillStrategy::Linear) => interpolate(&buckets, &existing, position, column),
+                    };
+                    row.insert(column.clone(), value);
+                }
+                filled.push(row);
+            }
+            output.extend(filled);
+        }
+        Ok(output)
+    }
+}
+
+impl fmt::Display for SampleBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SAMPLE BY {}", self.interval_text)?;
+        if !self.fill.is_empty() {
+            let fill: Vec<String> = self.fill.iter().map(|s| s.to_string()).collect();
+            write!(f, " FILL({})", fill.join(", "))?;
+        }
+        write!(f, " {}", self.alignment)
+    }
+}
+
+/// 一个分组内按桶起点索引的行
+type BucketRows = HashMap<i64, HashMap<String, Value>>;
+
+/// 在前后最近的非空桶之间线性插值
+fn interpolate(
+    buckets: &[i64],
+    existing: &BucketRows,
+    position: usize,
+    column: &str,
+) -> Value {
+    let point = |bucket: &i64| existing.get(bucket)
+        .and_then(|row| row.get(column))
+        .and_then(value_as_f64)
+        .map(|v| (*bucket, v));
+    let before = buckets[..position].iter().rev().find_map(point);
+    let after = buckets[position + 1..].iter().find_map(point);
+    match (before, after) {
+        (Some((t0, v0)), Some((t1, v1))) if t1 != t0 => {
+            let t = buckets[position];
+            Value::Float64(v0 + (v1 - v0) * (t - t0) as f64 / (t1 - t0) as f64)
+        }
+        _ => Value::Null,
+    }
+}
+
+/// 向下取整（对负数同样向下）
+fn floor_nanos(nanos: i64, interval: i64) -> i64 {
+    let floored = TimeUtils::floor_to_interval(TimestampNs::from_nanos(nanos), interval).as_nanos();
+    if floored > nanos { floored - interval } else { floored }
+}
+
+/// 计算桶起点；`shift`为本地时间相对UTC的偏移
+fn bucket_start(ts: i64, interval: BucketInterval, shift: i64) -> Result<i64> {
+    let local = ts + shift;
+    match interval {
+        BucketInterval::Fixed(nanos) => Ok(floor_nanos(local, nanos) - shift),
+        BucketInterval::Months(months) => {
+            let date = DateTime::<Utc>::from_timestamp_nanos(local).date_naive();
+            let index = date.year() as i64 * 12 + date.month0() as i64;
+            let floored = index - index.rem_euclid(months as i64);
+            Ok(month_start_nanos(floored)? - shift)
+        }
+    }
+}
+
+fn advance(bucket: i64, interval: BucketInterval, shift: i64) -> Result<i64> {
+    match interval {
+        BucketInterval::Fixed(nanos) => Ok(bucket + nanos),
+        BucketInterval::Months(months) => {
+            let date = DateTime::<Utc>::from_timestamp_nanos(bucket + shift).date_naive();
+            let index = date.year() as i64 * 12 + date.month0() as i64 + months as i64;
+            Ok(month_start_nanos(index)? - shift)
+        }
+    }
+}
+
+pub(crate) fn month_start_nanos(month_index: i64) -> Result<i64> {
+    NaiveDate::from_ymd_opt(month_index.div_euclid(12) as i32, month_index.rem_euclid(12) as u32 + 1, 1)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .and_then(|dt| dt.and_utc().timestamp_nanos_opt())
+        .ok_or_else(|| Error::validation("Bucket is outside the supported timestamp range"))
+}
+
+/// 解析时区为相对UTC的固定偏移（纳秒）
+///
+/// 支持`UTC`、`+08:00`、`UTC-5`等固定偏移，以及没有夏令时的常用时区名。
+/// 有夏令时的时区需要时区数据库，目前返回错误。
+pub fn parse_utc_offset(zone: &str) -> Result<i64> {
+    static OFFSET: OnceLock<Regex> = OnceLock::new();
+    let offset_re = OFFSET.get_or_init(|| {
+        Regex::new(r"(?i)^(?:UTC|GMT)?\s*([+-])(\d{1,2})(?::?(\d{2}))?$").expect("valid regex")
+    });
+
+    let zone = zone.trim();
+    match zone.to_uppercase().as_str() {
+        "UTC" | "GMT" | "Z" | "ETC/UTC" => return Ok(0),
+        "ASIA/SHANGHAI" | "ASIA/HONG_KONG" | "ASIA/SINGAPORE" | "ASIA/TAIPEI" => return Ok(8 * intervals::HOUR),
+        "ASIA/TOKYO" | "ASIA/SEOUL" => return Ok(9 * intervals::HOUR),
+        "ASIA/KOLKATA" => return Ok(5 * intervals::HOUR + 30 * intervals::MINUTE),
+        "ASIA/DUBAI" => return Ok(4 * intervals::HOUR),
+        _ => {}
+    }
+
+    let caps = offset_re.captures(zone).ok_or_else(|| Error::validation(format!(
+        "Time zone {} is not supported; use a fixed UTC offset such as '+08:00'", zone
+    )))?;
+    let hours: i64 = caps[2].parse().unwrap_or(0);
+    let minutes: i64 = caps.get(3).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+    if hours > 14 || minutes >= 60 {
+        return Err(Error::validation(format!("Invalid UTC offset: {}", zone)));
+    }
+    let nanos = hours * intervals::HOUR + minutes * intervals::MINUTE;
+    Ok(if &caps[1] == "-" { -nanos } else { nanos })
+}
+
+/// 从SQL中取出`SAMPLE BY`子句，返回去掉该子句后的SQL
+///
+/// 语法：`SAMPLE BY <间隔> [FILL(<策略>[, ...])] [ALIGN TO FIRST OBSERVATION |
+/// ALIGN TO CALENDAR [TIME ZONE '<时区>'] [WITH OFFSET '<hh:mm>']]`
+pub fn extract_sample_by(sql: &str) -> Result<(String, Option<SampleBy>)> {
+    static SAMPLE: OnceLock<Regex> = OnceLock::new();
+    let sample_re = SAMPLE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)\s+SAMPLE\s+BY\s+('[^']*'|[0-9]+[A-Za-z]+)",
+            r"(?:\s+FILL\s*\(([^)]*)\))?",
+            r"(?:\s+ALIGN\s+TO\s+(FIRST\s+OBSERVATION|CALENDAR(?:\s+TIME\s+ZONE\s+'([^']*)')?(?:\s+WITH\s+OFFSET\s+'([^']*)')?))?",
+        ))
+        .expect("valid regex")
+    });
+
+    let Some(caps) = sample_re.captures(sql) else {
+        return Ok((sql.to_string(), None));
+    };
+
+    let interval = caps[1].trim_matches('\'');
+    let mut sample = SampleBy::new(interval)?;
+    if let Some(fill) = caps.get(2) {
+        sample.fill = split_fill_options(fill.as_str())
+            .into_iter()
+            .map(FillStrategy::parse)
+            .collect::<Result<_>>()?;
+    }
+    if let Some(align) = caps.get(3) {
+        sample.alignment = if align.as_str().to_uppercase().starts_with("FIRST") {
+            Alignment::FirstObservation
+        } else {
+            Alignment::Calendar {
+                utc_offset: caps.get(4).map(|z| parse_utc_offset(z.as_str())).transpose()?.unwrap_or(0),
+                offset: caps.get(5).map(|o| parse_utc_offset(o.as_str())).transpose()?.unwrap_or(0),
+            }
+        };
+    }
+
+    let whole = caps.get(0).expect("match");
+    let stripped = format!("{}{}", &sql[..whole.start()], &sql[whole.end()..]);
+    Ok((stripped, Some(sample)))
+}
+
+fn split_fill_options(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quote = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '\'' => in_quote = !in_quote,
+            ',' if !in_quote => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// SQL函数`time_bucket(interval, ts [, time_zone])`：返回时间戳所在桶的起点
+pub fn time_bucket(args: &[Value]) -> Result<Value> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(Error::validation("TIME_BUCKET requires 2 or 3 arguments"));
+    }
+    let interval = match &args[0] {
+        Value::String(text) => BucketInterval::parse(text)?,
+        other => match value_as_i64(other) {
+            Some(nanos) if nanos > 0 => BucketInterval::Fixed(nanos),
+            _ => return Err(Error::validation("TIME_BUCKET interval must be a positive interval")),
+        },
+    };
+    let Some(ts) = value_as_timestamp(&args[1]) else {
+        return match &args[1] {
+            Value::Null => Ok(Value::Null),
+            _ => Err(Error::type_error("TIME_BUCKET requires a timestamp argument")),
+        };
+    };
+    let shift = match args.get(2) {
+        Some(zone) => parse_utc_offset(&value_as_str(zone).ok_or_else(|| {
+            Error::type_error("TIME_BUCKET time zone must be a string")
+        })?)?,
+        None => 0,
+    };
+    Ok(Value::Timestamp(TimestampNs::from_nanos(bucket_start(ts.as_nanos(), interval, shift)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(text: &str) -> i64 {
+        DateTime::parse_from_rfc3339(text).unwrap().timestamp_nanos_opt().unwrap()
+    }
+
+    #[test]
+    fn test_bucket_intervals_and_alignment() {
+        assert_eq!(BucketInterval::parse("1m").unwrap(), BucketInterval::Fixed(intervals::MINUTE));
+        assert_eq!(BucketInterval::parse("1M").unwrap(), BucketInterval::Months(1));
+        assert_eq!(BucketInterval::parse("2y").unwrap(), BucketInterval::Months(24));
+
+        let daily = SampleBy::new("1d").unwrap();
+        assert_eq!(daily.bucket(ts("2024-03-05T22:30:00Z"), None).unwrap(), ts("2024-03-05T00:00:00Z"));
+
+        // 上海时间的日历日从UTC前一天16:00开始
+        let shanghai = daily.clone().with_alignment(Alignment::Calendar { utc_offset: parse_utc_offset("+08:00").unwrap(), offset: 0 });
+        assert_eq!(shanghai.bucket(ts("2024-03-05T22:30:00Z"), None).unwrap(), ts("2024-03-05T16:00:00Z"));
+
+        let monthly = SampleBy::new("1M").unwrap();
+        let bucket = monthly.bucket(ts("2024-02-29T12:00:00Z"), None).unwrap();
+        assert_eq!(bucket, ts("2024-02-01T00:00:00Z"));
+        assert_eq!(monthly.next_bucket(bucket).unwrap(), ts("2024-03-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_extract_sample_by() {
+        let sql = "SELECT ts, avg(price) FROM trades WHERE symbol = 'AAPL' SAMPLE BY 5m FILL(PREV) \
+                   ALIGN TO CALENDAR TIME ZONE 'Asia/Tokyo' ORDER BY ts";
+        let (stripped, sample) = extract_sample_by(sql).unwrap();
+        let sample = sample.unwrap();
+        assert_eq!(stripped, "SELECT ts, avg(price) FROM trades WHERE symbol = 'AAPL' ORDER BY ts");
+        assert_eq!(sample.interval, BucketInterval::Fixed(5 * intervals::MINUTE));
+        assert_eq!(sample.fill, vec![FillStrategy::Prev]);
+        assert_eq!(sample.alignment, Alignment::Calendar { utc_offset: 9 * intervals::HOUR, offset: 0 });
+
+        assert!(extract_sample_by("SELECT 1").unwrap().1.is_none());
+        assert!(parse_utc_offset("America/New_York").is_err());
+    }
+
+    #[test]
+    fn test_fill_strategies() {
+        let minute = intervals::MINUTE;
+        let row = |t: i64, v: f64| HashMap::from([
+            ("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(t * minute))),
+            ("v".to_string(), Value::Float64(v)),
+        ]);
+        let rows = vec![row(0, 1.0), row(3, 4.0)];
+        let values = vec!["v".to_string()];
+
+        let fill = |strategy: FillStrategy| {
+            SampleBy::new("1m").unwrap().with_fill(vec![strategy])
+                .fill_rows(rows.clone(), "ts", &[], &values).unwrap()
+                .into_iter().map(|r| r["v"].clone()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(fill(FillStrategy::Null), vec![Value::Float64(1.0), Value::Null, Value::Null, Value::Float64(4.0)]);
+        assert_eq!(fill(FillStrategy::Prev)[2], Value::Float64(1.0));
+        assert_eq!(fill(FillStrategy::Linear)[1], Value::Float64(2.0));
+        assert_eq!(fill(FillStrategy::Value(Value::Int64(0)))[1], Value::Int64(0));
+    }
+
+    #[test]
+    fn test_time_bucket_function() {
+        let value = time_bucket(&[
+            Value::String("15m".to_string()),
+            Value::Timestamp(TimestampNs::from_nanos(ts("2024-01-01T10:44:59Z"))),
+        ]).unwrap();
+        assert_eq!(value, Value::Timestamp(TimestampNs::from_nanos(ts("2024-01-01T10:30:00Z"))));
+    }
+}
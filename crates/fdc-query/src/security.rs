@@ -0,0 +1,1228 @@
+//! Table, column and row-level access control
+//!
+//! Access control is opt-in per table: a table is open to every user until
+//! the first `GRANT`, `REVOKE`, row policy or column mask is defined on it;
+//! from then on users need explicit privileges to read or write it.
+//!
+//! * `GRANT` / `REVOKE` SELECT, INSERT, UPDATE (optionally per column), DELETE
+//!   and UNMASK on a table to users or `PUBLIC`.
+//! * `CREATE POLICY name ON table [FOR command] [TO users] [USING (expr)]
+//!   [WITH CHECK (expr)]` adds a permissive row policy; the policies that apply
+//!   to a user are combined with OR. Once a table has policies, users read,
+//!   update and delete only rows admitted by `USING`, new and updated rows must
+//!   satisfy `WITH CHECK` (or `USING` when no check is given), and users no
+//!   policy applies to see no rows at all.
+//! * `ALTER TABLE t ALTER [COLUMN] c SET MASK USING (expr)` replaces the column
+//!   with a masking expression such as `mask_show_last(account_id, 4)` for
+//!   users without UNMASK on the table.
+//! * `ALTER USER name SET attribute = value` stores user attributes that row
+//!   policies read through `current_user_attribute('desk')` or the shorthand
+//!   `current_user_desk()`; `CURRENT_USER` returns the user name.
+//!
+//! The planner checks the privileges of the current user for every statement
+//! and resolves the policies and masks into predicates that the executor
+//! applies when it scans a table, before joins, filters and aggregation, so a
+//! query cannot observe rows or values the user may not see. Contexts without
+//! a user ID (embedded callers) and configured superusers bypass access
+//! control and are the only ones allowed to change it.
+
+use crate::{
+    catalog::{Catalog, TableDefinition},
+    expressions::{display_value, value_as_i64, value_as_str, ExpressionEvaluator},
+    joins::Row,
+};
+use fdc_core::{error::{Error, Result}, types::Value};
+use parking_lot::RwLock;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlparser::{
+    ast::{self, AssignmentTarget, BinaryOperator, Expr, Query, SelectItem, SetExpr, Statement, TableFactor, Visit, Visitor},
+    dialect::GenericDialect,
+    parser::Parser,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::ControlFlow;
+use std::sync::OnceLock;
+
+/// 授予所有用户时使用的授权对象名
+pub const PUBLIC: &str = "public";
+
+/// 可授予的权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Privilege {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    /// 读取掩码列的原值
+    Unmask,
+}
+
+impl Privilege {
+    /// 解析权限名（不区分大小写）
+    pub fn parse(text: &str) -> Result<Self> {
+        match text.trim().to_uppercase().as_str() {
+            "SELECT" => Ok(Privilege::Select),
+            "INSERT" => Ok(Privilege::Insert),
+            "UPDATE" => Ok(Privilege::Update),
+            "DELETE" => Ok(Privilege::Delete),
+            "UNMASK" => Ok(Privilege::Unmask),
+            other => Err(Error::validation(format!(
+                "Unknown privilege '{}', expected SELECT, INSERT, UPDATE, DELETE or UNMASK", other
+            ))),
+        }
+    }
+
+    /// SQL中的权限名
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Privilege::Select => "SELECT",
+            Privilege::Insert => "INSERT",
+            Privilege::Update => "UPDATE",
+            Privilege::Delete => "DELETE",
+            Privilege::Unmask => "UNMASK",
+        }
+    }
+
+    /// 是否可以只授予部分列
+    fn column_level(&self) -> bool {
+        matches!(self, Privilege::Select | Privilege::Insert | Privilege::Update)
+    }
+}
+
+/// 授予某个用户的一项权限
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivilegeGrant {
+    /// 授予了整张表
+    pub table: bool,
+    /// 单独授予的列（小写）
+    pub columns: BTreeSet<String>,
+}
+
+/// 行策略适用的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PolicyCommand {
+    #[default]
+    All,
+    Select,
+    Insert,
+    Update,
+    Delete,
+}
+
+impl PolicyCommand {
+    /// 解析`FOR`子句中的命令
+    pub fn parse(text: &str) -> Result<Self> {
+        match text.trim().to_uppercase().as_str() {
+            "ALL" => Ok(PolicyCommand::All),
+            "SELECT" => Ok(PolicyCommand::Select),
+            "INSERT" => Ok(PolicyCommand::Insert),
+            "UPDATE" => Ok(PolicyCommand::Update),
+            "DELETE" => Ok(PolicyCommand::Delete),
+            other => Err(Error::validation(format!("Unknown policy command '{}'", other))),
+        }
+    }
+
+    /// 策略是否约束该权限对应的操作
+    fn covers(&self, privilege: Privilege) -> bool {
+        match self {
+            PolicyCommand::All => true,
+            PolicyCommand::Select => privilege == Privilege::Select,
+            PolicyCommand::Insert => privilege == Privilege::Insert,
+            PolicyCommand::Update => privilege == Privilege::Update,
+            PolicyCommand::Delete => privilege == Privilege::Delete,
+        }
+    }
+}
+
+/// 行级安全策略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RowPolicy {
+    /// 策略名（同一张表内唯一）
+    pub name: String,
+    /// 适用的命令
+    pub command: PolicyCommand,
+    /// 适用的用户，为空时适用于所有用户
+    pub roles: Vec<String>,
+    /// 可见行条件，未指定时不限制
+    pub using: Option<String>,
+    /// 写入行需满足的条件，未指定时使用`using`
+    pub check: Option<String>,
+}
+
+impl RowPolicy {
+    /// 策略是否适用于该用户
+    fn applies_to(&self, user: &str) -> bool {
+        self.roles.is_empty() || self.roles.iter().any(|role| role == PUBLIC || role.eq_ignore_ascii_case(user))
+    }
+}
+
+/// 表的访问控制：授权、行策略与列掩码
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableSecurity {
+    /// 用户名（小写，`public`表示所有用户） -> 权限 -> 授权范围
+    pub grants: BTreeMap<String, BTreeMap<Privilege, PrivilegeGrant>>,
+    /// 行策略
+    pub policies: Vec<RowPolicy>,
+    /// 列名（小写） -> 掩码表达式
+    pub masks: BTreeMap<String, String>,
+}
+
+impl TableSecurity {
+    /// 授予权限；`columns`为None时授予整张表
+    fn grant(&mut self, grantee: &str, privilege: Privilege, columns: Option<&[String]>) {
+        let grant = self.grants.entry(grantee.to_lowercase()).or_default().entry(privilege).or_default();
+        match columns {
+            Some(columns) => grant.columns.extend(columns.iter().cloned()),
+            None => grant.table = true,
+        }
+    }
+
+    /// 收回权限；收回整张表的权限时同时收回单独授予的列
+    fn revoke(&mut self, grantee: &str, privilege: Privilege, columns: Option<&[String]>) {
+        let grantee = grantee.to_lowercase();
+        let Some(grants) = self.grants.get_mut(&grantee) else {
+            return;
+        };
+        match columns {
+            Some(columns) => {
+                let emptied = grants.get_mut(&privilege).is_some_and(|grant| {
+                    for column in columns {
+                        grant.columns.remove(column);
+                    }
+                    !grant.table && grant.columns.is_empty()
+                });
+                if emptied {
+                    grants.remove(&privilege);
+                }
+            }
+            None => {
+                grants.remove(&privilege);
+            }
+        }
+        if grants.is_empty() {
+            self.grants.remove(&grantee);
+        }
+    }
+
+    /// 用户自己与`PUBLIC`获得的该项权限
+    fn grants_of<'a>(&'a self, user: &'a str, privilege: Privilege) -> impl Iterator<Item = &'a PrivilegeGrant> + 'a {
+        [user, PUBLIC].into_iter()
+            .filter_map(move |grantee| self.grants.get(grantee).and_then(|grants| grants.get(&privilege)))
+    }
+
+    /// 用户是否拥有整张表的该项权限
+    pub fn has_privilege(&self, user: &str, privilege: Privilege) -> bool {
+        self.grants_of(&user.to_lowercase(), privilege).any(|grant| grant.table)
+    }
+
+    /// 检查用户对表的权限
+    ///
+    /// `columns`为None时需要所有列的权限（`SELECT *`、不带列名的INSERT）；为空集合时
+    /// 有任意一列的权限即可（例如`SELECT count(*)`）。
+    pub fn check(&self, definition: &TableDefinition, user: &str, privilege: Privilege, columns: Option<&BTreeSet<String>>) -> Result<()> {
+        let user = user.to_lowercase();
+        let denied = || Error::permission_denied(format!("{} on table {}", privilege.as_sql(), definition.name));
+        let mut granted = BTreeSet::new();
+        for grant in self.grants_of(&user, privilege) {
+            if grant.table {
+                return Ok(());
+            }
+            granted.extend(grant.columns.iter().cloned());
+        }
+        if !privilege.column_level() {
+            return Err(denied());
+        }
+        let required: BTreeSet<String> = match columns {
+            Some(columns) if columns.is_empty() => return if granted.is_empty() { Err(denied()) } else { Ok(()) },
+            Some(columns) => columns.clone(),
+            None => definition.columns.iter().map(|c| c.name.to_lowercase()).collect(),
+        };
+        match required.iter().find(|column| !granted.contains(*column)) {
+            Some(column) => Err(Error::permission_denied(format!(
+                "{} on column {}.{}", privilege.as_sql(), definition.name, column
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// 解析用户读写该表时需要施加的行条件与列掩码；都不需要时返回None
+    fn access(&self, user: &str) -> Result<Option<TableAccess>> {
+        let user = user.to_lowercase();
+        let mut access = TableAccess::default();
+        if !self.policies.is_empty() {
+            for privilege in [Privilege::Select, Privilege::Insert, Privilege::Update, Privilege::Delete] {
+                let policies: Vec<&RowPolicy> = self.policies.iter()
+                    .filter(|policy| policy.command.covers(privilege) && policy.applies_to(&user))
+                    .collect();
+                if privilege != Privilege::Insert {
+                    let using = policies.iter().map(|policy| policy.using.as_deref());
+                    access.using.insert(privilege, any_of(using)?);
+                }
+                if matches!(privilege, Privilege::Insert | Privilege::Update) {
+                    let checks = policies.iter().map(|policy| policy.check.as_deref().or(policy.using.as_deref()));
+                    access.checks.insert(privilege, any_of(checks)?);
+                }
+            }
+        }
+        if !self.has_privilege(&user, Privilege::Unmask) {
+            for (column, mask) in &self.masks {
+                access.masks.push((column.clone(), parse_expression(mask)?));
+            }
+        }
+        Ok((!access.is_empty()).then_some(access))
+    }
+}
+
+/// 合并多条策略的条件：任一满足即可；没有策略时不满足，策略未指定条件时总是满足
+fn any_of<'a>(conditions: impl Iterator<Item = Option<&'a str>>) -> Result<Expr> {
+    let mut combined: Option<Expr> = None;
+    for condition in conditions {
+        let expr = match condition {
+            Some(condition) => Expr::Nested(Box::new(parse_expression(condition)?)),
+            None => return Ok(Expr::Value(ast::Value::Boolean(true))),
+        };
+        combined = Some(match combined {
+            Some(left) => Expr::BinaryOp { left: Box::new(left), op: BinaryOperator::Or, right: Box::new(expr) },
+            None => expr,
+        });
+    }
+    Ok(combined.unwrap_or(Expr::Value(ast::Value::Boolean(false))))
+}
+
+/// 解析策略或掩码中的SQL表达式
+fn parse_expression(sql: &str) -> Result<Expr> {
+    Parser::new(&GenericDialect {})
+        .try_with_sql(sql)
+        .and_then(|mut parser| parser.parse_expr())
+        .map_err(|e| Error::parse(format!("Invalid expression '{}': {}", sql, e)))
+}
+
+/// 查询执行时的当前用户及其属性
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionUser {
+    /// 用户名
+    pub name: String,
+    /// `ALTER USER ... SET`设置的属性（属性名小写）
+    pub attributes: BTreeMap<String, Value>,
+}
+
+impl SessionUser {
+    /// 当前用户相关的函数；不是这类函数时返回None
+    ///
+    /// `CURRENT_USER`/`SESSION_USER`返回用户名，`CURRENT_USER_ATTRIBUTE('desk')`与
+    /// `CURRENT_USER_DESK()`返回属性值，未设置的属性为NULL。
+    pub fn call(user: Option<&SessionUser>, name: &str, args: &[Value]) -> Option<Result<Value>> {
+        let name = name.to_uppercase();
+        let attribute = |attribute: &str| {
+            user.and_then(|user| user.attributes.get(&attribute.to_lowercase())).cloned().unwrap_or(Value::Null)
+        };
+        match name.as_str() {
+            "CURRENT_USER" | "SESSION_USER" | "USER" if args.is_empty() => {
+                Some(Ok(user.map_or(Value::Null, |user| Value::String(user.name.clone()))))
+            }
+            "CURRENT_USER_ATTRIBUTE" => Some(match args {
+                [Value::String(name)] => Ok(attribute(name)),
+                _ => Err(Error::validation("CURRENT_USER_ATTRIBUTE requires exactly 1 string argument")),
+            }),
+            _ => match name.strip_prefix("CURRENT_USER_") {
+                Some(suffix) if args.is_empty() && !suffix.is_empty() => Some(Ok(attribute(suffix))),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// 目录中的访问控制：各表的授权、行策略与列掩码，以及用户属性
+#[derive(Debug, Default)]
+pub struct SecurityCatalog {
+    tables: RwLock<HashMap<String, TableSecurity>>,
+    users: RwLock<HashMap<String, BTreeMap<String, Value>>>,
+}
+
+impl SecurityCatalog {
+    /// 表的访问控制设置；未设置过的表返回None（对所有用户开放）
+    pub fn table(&self, name: &str) -> Option<TableSecurity> {
+        self.tables.read().get(&name.to_lowercase()).cloned()
+    }
+
+    /// 表是否启用了访问控制
+    pub fn is_secured(&self, name: &str) -> bool {
+        self.tables.read().contains_key(&name.to_lowercase())
+    }
+
+    /// 是否有任何表启用了访问控制
+    pub fn has_access_control(&self) -> bool {
+        !self.tables.read().is_empty()
+    }
+
+    /// 修改表的访问控制；表第一次被修改时启用访问控制，修改失败时保持原状
+    pub fn update(&self, table: &TableDefinition, update: impl FnOnce(&mut TableSecurity) -> Result<()>) -> Result<()> {
+        let mut tables = self.tables.write();
+        let name = table.name.to_lowercase();
+        let mut security = tables.get(&name).cloned().unwrap_or_default();
+        update(&mut security)?;
+        tables.insert(name, security);
+        Ok(())
+    }
+
+    /// 删除表时移除其访问控制
+    pub fn remove_table(&self, name: &str) {
+        self.tables.write().remove(&name.to_lowercase());
+    }
+
+    /// 设置用户属性；值为NULL时移除该属性
+    pub fn set_user_attribute(&self, user: &str, attribute: &str, value: Value) {
+        let mut users = self.users.write();
+        let attributes = users.entry(user.to_lowercase()).or_default();
+        match value {
+            Value::Null => attributes.remove(&attribute.to_lowercase()),
+            value => attributes.insert(attribute.to_lowercase(), value),
+        };
+        if attributes.is_empty() {
+            users.remove(&user.to_lowercase());
+        }
+    }
+
+    /// 移除用户的属性；`attribute`为None时移除全部属性
+    pub fn reset_user_attributes(&self, user: &str, attribute: Option<&str>) {
+        match attribute {
+            Some(attribute) => self.set_user_attribute(user, attribute, Value::Null),
+            None => {
+                self.users.write().remove(&user.to_lowercase());
+            }
+        }
+    }
+
+    /// 查询执行时的当前用户
+    pub fn session_user(&self, name: &str) -> SessionUser {
+        SessionUser {
+            name: name.to_string(),
+            attributes: self.users.read().get(&name.to_lowercase()).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// 当前用户读写某张表时施加的行条件与列掩码
+#[derive(Debug, Clone, Default)]
+pub struct TableAccess {
+    /// 各操作的可见行条件（USING），表没有行策略时为空
+    using: HashMap<Privilege, Expr>,
+    /// 写入行需满足的条件（WITH CHECK）
+    checks: HashMap<Privilege, Expr>,
+    /// 列名（小写） -> 掩码表达式
+    masks: Vec<(String, Expr)>,
+}
+
+impl TableAccess {
+    fn is_empty(&self) -> bool {
+        self.using.is_empty() && self.checks.is_empty() && self.masks.is_empty()
+    }
+
+    /// 该操作的可见行条件
+    pub fn row_filter(&self, privilege: Privilege) -> Option<&Expr> {
+        self.using.get(&privilege)
+    }
+
+    /// 列是否被掩码
+    pub fn masks_column(&self, column: &str) -> bool {
+        self.masks.iter().any(|(masked, _)| masked.eq_ignore_ascii_case(column))
+    }
+
+    /// 被掩码的列
+    pub fn masked_columns(&self) -> impl Iterator<Item = &str> {
+        self.masks.iter().map(|(column, _)| column.as_str())
+    }
+
+    /// 行是否对该操作可见
+    pub fn admits(&self, privilege: Privilege, row: &Row, evaluator: &ExpressionEvaluator) -> Result<bool> {
+        match self.using.get(&privilege) {
+            Some(condition) => evaluator.evaluate_predicate(condition, row),
+            None => Ok(true),
+        }
+    }
+
+    /// 按掩码替换列值后的行；掩码表达式在原始行上求值
+    pub fn masked(&self, row: &Row, evaluator: &ExpressionEvaluator) -> Result<Row> {
+        let mut masked = row.clone();
+        for (column, mask) in &self.masks {
+            let name = row.keys().find(|name| name.eq_ignore_ascii_case(column)).cloned();
+            if let Some(name) = name {
+                masked.insert(name, evaluator.evaluate(mask, row)?);
+            }
+        }
+        Ok(masked)
+    }
+
+    /// 扫描结果只保留可读的行并应用列掩码
+    pub fn secure_scan(&self, rows: Vec<Row>, evaluator: &ExpressionEvaluator) -> Result<Vec<Row>> {
+        let mut secured = Vec::with_capacity(rows.len());
+        for row in rows {
+            evaluator.checkpoint()?;
+            if !self.admits(Privilege::Select, &row, evaluator)? {
+                continue;
+            }
+            secured.push(if self.masks.is_empty() { row } else { self.masked(&row, evaluator)? });
+        }
+        Ok(secured)
+    }
+
+    /// 检查写入的行满足行策略
+    pub fn check(&self, table: &str, privilege: Privilege, row: &Row, evaluator: &ExpressionEvaluator) -> Result<()> {
+        match self.checks.get(&privilege) {
+            Some(check) if !evaluator.evaluate_predicate(check, row)? => Err(Error::permission_denied(format!(
+                "new row violates row-level security policy for table {}", table
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 一条语句中当前用户对各表的行条件与列掩码，由计划器解析后注入扫描
+#[derive(Debug, Clone, Default)]
+pub struct QuerySecurity {
+    tables: HashMap<String, TableAccess>,
+    /// 读取按用户归属的系统表（如查询日志）时只保留该用户的行
+    owner: Option<String>,
+}
+
+impl QuerySecurity {
+    /// 只能读取按用户归属的系统表中自己的行
+    pub(crate) fn with_owner(mut self, user: &str) -> Self {
+        self.owner = Some(user.to_string());
+        self
+    }
+
+    /// 按用户归属的系统表上限定的用户
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+
+    /// 表（名称不区分大小写）上的行条件与列掩码
+    pub fn table(&self, name: &str) -> Option<&TableAccess> {
+        self.tables.get(&name.to_lowercase())
+    }
+
+    /// 是否不需要施加任何限制
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty() && self.owner.is_none()
+    }
+
+    /// 检查语句所需的权限，并解析语句涉及的受控表上的行条件与列掩码
+    pub(crate) fn authorize(statement: &Statement, catalog: &Catalog, user: &str) -> Result<Self> {
+        let security = catalog.security();
+        let mut collector = ReferenceCollector::default();
+        let _ = statement.visit(&mut collector);
+        let target = write_target(statement);
+
+        let mut resolved = Self::default();
+        let mut tables: Vec<String> = collector.tables.clone();
+        if let Some((table, _, _)) = &target {
+            tables.push(table.clone());
+        }
+        for table in tables {
+            if resolved.tables.contains_key(&table) {
+                continue;
+            }
+            let (Some(definition), Some(table_security)) = (catalog.get_table(&table), security.table(&table)) else {
+                continue;
+            };
+            let columns = collector.columns_of(&table, &definition);
+            match &target {
+                // 写入目标只在读取了列（WHERE、赋值表达式）时需要SELECT权限
+                Some((name, privilege, written)) if *name == table => {
+                    table_security.check(&definition, user, *privilege, written.as_ref())?;
+                    if columns.as_ref().map_or(true, |columns| !columns.is_empty()) {
+                        table_security.check(&definition, user, Privilege::Select, columns.as_ref())?;
+                    }
+                }
+                _ => table_security.check(&definition, user, Privilege::Select, columns.as_ref())?,
+            }
+            if let Some(access) = table_security.access(user)? {
+                resolved.tables.insert(table, access);
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+/// 写入语句的目标表、所需权限与写入的列（None表示所有列）
+fn write_target(statement: &Statement) -> Option<(String, Privilege, Option<BTreeSet<String>>)> {
+    match statement {
+        Statement::Insert(insert) => {
+            let columns = (!insert.columns.is_empty())
+                .then(|| insert.columns.iter().map(|column| column.value.to_lowercase()).collect());
+            Some((insert.table_name.to_string().to_lowercase(), Privilege::Insert, columns))
+        }
+        Statement::Update { table, assignments, .. } => match &table.relation {
+            TableFactor::Table { name, .. } => {
+                let columns = assignments.iter()
+                    .flat_map(|assignment| match &assignment.target {
+                        AssignmentTarget::ColumnName(name) => vec![name],
+                        AssignmentTarget::Tuple(names) => names.iter().collect(),
+                    })
+                    .filter_map(|name| name.0.last().map(|ident| ident.value.to_lowercase()))
+                    .collect();
+                Some((name.to_string().to_lowercase(), Privilege::Update, Some(columns)))
+            }
+            _ => None,
+        },
+        Statement::Delete(delete) => {
+            let from = match &delete.from {
+                ast::FromTable::WithFromKeyword(from) | ast::FromTable::WithoutKeyword(from) => from,
+            };
+            match from.first().map(|item| &item.relation) {
+                Some(TableFactor::Table { name, .. }) => {
+                    Some((name.to_string().to_lowercase(), Privilege::Delete, Some(BTreeSet::new())))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// AST遍历器，收集语句读取的表与列
+#[derive(Default)]
+struct ReferenceCollector {
+    /// 读取的表（小写）
+    tables: Vec<String>,
+    /// 限定名（别名或表名，小写） -> 表名
+    qualifiers: HashMap<String, String>,
+    /// 未限定的列名（小写）
+    unqualified: BTreeSet<String>,
+    /// (限定名, 列名)，列名为`*`表示通配符
+    qualified: Vec<(String, String)>,
+}
+
+impl ReferenceCollector {
+    /// 语句读取的某张表的列；None表示读取了所有列
+    ///
+    /// 未限定的列名归属到含有该列的每张表。
+    fn columns_of(&self, table: &str, definition: &TableDefinition) -> Option<BTreeSet<String>> {
+        let mut columns: BTreeSet<String> = self.unqualified.iter()
+            .filter(|column| definition.column(column).is_some())
+            .cloned()
+            .collect();
+        for (qualifier, column) in &self.qualified {
+            if self.qualifiers.get(qualifier).map(String::as_str) != Some(table) {
+                continue;
+            }
+            if column == "*" {
+                return None;
+            }
+            columns.insert(column.clone());
+        }
+        Some(columns)
+    }
+
+    /// 收集SELECT列表中的通配符
+    fn collect_wildcards(&mut self, body: &SetExpr) {
+        match body {
+            SetExpr::Select(select) => {
+                for item in &select.projection {
+                    match item {
+                        SelectItem::Wildcard(_) => {
+                            let relations = select.from.iter()
+                                .flat_map(|from| std::iter::once(&from.relation).chain(from.joins.iter().map(|join| &join.relation)));
+                            for relation in relations {
+                                if let TableFactor::Table { name, alias, .. } = relation {
+                                    self.qualified.push((qualifier_of(name, alias.as_ref()), "*".to_string()));
+                                }
+                            }
+                        }
+                        SelectItem::QualifiedWildcard(name, _) => {
+                            let qualifier = name.0.last().map(|ident| ident.value.to_lowercase()).unwrap_or_default();
+                            self.qualified.push((qualifier, "*".to_string()));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            SetExpr::SetOperation { left, right, .. } => {
+                self.collect_wildcards(left);
+                self.collect_wildcards(right);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 表在查询中的限定名：别名或表名的最后一段
+fn qualifier_of(name: &ast::ObjectName, alias: Option<&ast::TableAlias>) -> String {
+    match alias {
+        Some(alias) => alias.name.value.to_lowercase(),
+        None => name.0.last().map(|ident| ident.value.to_lowercase()).unwrap_or_default(),
+    }
+}
+
+impl Visitor for ReferenceCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        self.collect_wildcards(&query.body);
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<()> {
+        if let TableFactor::Table { name, alias, .. } = table_factor {
+            let table = name.to_string().to_lowercase();
+            self.qualifiers.insert(qualifier_of(name, alias.as_ref()), table.clone());
+            self.qualifiers.insert(table.clone(), table.clone());
+            if !self.tables.contains(&table) {
+                self.tables.push(table);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        match expr {
+            Expr::Identifier(ident) => {
+                self.unqualified.insert(ident.value.to_lowercase());
+            }
+            Expr::CompoundIdentifier(idents) if idents.len() >= 2 => {
+                let column = idents[idents.len() - 1].value.to_lowercase();
+                let qualifier = idents[idents.len() - 2].value.to_lowercase();
+                self.qualified.push((qualifier, column));
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// 访问控制命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecurityCommand {
+    /// `GRANT privileges ON [TABLE] table TO users`
+    Grant {
+        privileges: Vec<(Privilege, Option<Vec<String>>)>,
+        table: String,
+        grantees: Vec<String>,
+    },
+    /// `REVOKE privileges ON [TABLE] table FROM users`
+    Revoke {
+        privileges: Vec<(Privilege, Option<Vec<String>>)>,
+        table: String,
+        grantees: Vec<String>,
+    },
+    /// `CREATE POLICY name ON table [FOR command] [TO users] [USING (expr)] [WITH CHECK (expr)]`
+    CreatePolicy {
+        table: String,
+        policy: RowPolicy,
+    },
+    /// `DROP POLICY [IF EXISTS] name ON table`
+    DropPolicy {
+        name: String,
+        table: String,
+        if_exists: bool,
+    },
+    /// `ALTER TABLE table ALTER [COLUMN] column SET MASK USING (expr)` / `... DROP MASK`
+    SetMask {
+        table: String,
+        column: String,
+        mask: Option<String>,
+    },
+    /// `ALTER USER name SET attribute = value` / `ALTER USER name RESET {attribute | ALL}`
+    SetUserAttribute {
+        user: String,
+        attribute: Option<String>,
+        value: Option<Value>,
+    },
+}
+
+impl SecurityCommand {
+    /// 识别访问控制命令；其他语句返回None
+    pub fn parse(sql: &str) -> Result<Option<Self>> {
+        static GRANT: OnceLock<Regex> = OnceLock::new();
+        static CREATE_POLICY: OnceLock<Regex> = OnceLock::new();
+        static DROP_POLICY: OnceLock<Regex> = OnceLock::new();
+        static MASK: OnceLock<Regex> = OnceLock::new();
+        static USER: OnceLock<Regex> = OnceLock::new();
+        let grant = GRANT.get_or_init(|| {
+            Regex::new(r"(?is)^\s*(GRANT|REVOKE)\s+(.+?)\s+ON\s+(?:TABLE\s+)?([A-Za-z_][A-Za-z0-9_.]*)\s+(TO|FROM)\s+(.+?)\s*;?\s*$")
+                .expect("valid regex")
+        });
+        let create_policy = CREATE_POLICY.get_or_init(|| {
+            Regex::new(concat!(
+                r"(?is)^\s*CREATE\s+POLICY\s+([A-Za-z_][A-Za-z0-9_]*)\s+ON\s+([A-Za-z_][A-Za-z0-9_.]*)\s*",
+                r"(?:AS\s+(PERMISSIVE|RESTRICTIVE)\s*)?",
+                r"(?:FOR\s+([A-Za-z]+)\s*)?",
+                r#"(?:TO\s+([A-Za-z0-9_,\s"]+?)\s*)?"#,
+                r"(?:USING\s*\((.*?)\)\s*)?",
+                r"(?:WITH\s+CHECK\s*\((.*)\)\s*)?;?\s*$",
+            ))
+            .expect("valid regex")
+        });
+        let drop_policy = DROP_POLICY.get_or_init(|| {
+            Regex::new(r"(?is)^\s*DROP\s+POLICY\s+(IF\s+EXISTS\s+)?([A-Za-z_][A-Za-z0-9_]*)\s+ON\s+([A-Za-z_][A-Za-z0-9_.]*)\s*;?\s*$")
+                .expect("valid regex")
+        });
+        let mask = MASK.get_or_init(|| {
+            Regex::new(concat!(
+                r"(?is)^\s*ALTER\s+TABLE\s+([A-Za-z_][A-Za-z0-9_.]*)\s+ALTER\s+(?:COLUMN\s+)?([A-Za-z_][A-Za-z0-9_]*)\s+",
+                r"(?:SET\s+MASK\s+USING\s*\((.*)\)|(DROP\s+MASK))\s*;?\s*$",
+            ))
+            .expect("valid regex")
+        });
+        let user = USER.get_or_init(|| {
+            Regex::new(concat!(
+                r#"(?is)^\s*ALTER\s+(?:USER|ROLE)\s+("[^"]+"|[A-Za-z_][A-Za-z0-9_]*)\s+"#,
+                r"(?:SET\s+([A-Za-z_][A-Za-z0-9_]*)\s*(?:=|\s+TO\s+)\s*(.+?)|RESET\s+([A-Za-z_][A-Za-z0-9_]*))\s*;?\s*$",
+            ))
+            .expect("valid regex")
+        });
+
+        if let Some(caps) = grant.captures(sql) {
+            let granting = caps[1].eq_ignore_ascii_case("GRANT");
+            if granting != caps[4].eq_ignore_ascii_case("TO") {
+                return Err(Error::parse(format!("{} ... {} is not valid", &caps[1].to_uppercase(), &caps[4].to_uppercase())));
+            }
+            let privileges = parse_privileges(&caps[2])?;
+            let table = caps[3].to_lowercase();
+            let grantees = parse_users(&caps[5])?;
+            return Ok(Some(if granting {
+                SecurityCommand::Grant { privileges, table, grantees }
+            } else {
+                SecurityCommand::Revoke { privileges, table, grantees }
+            }));
+        }
+        if let Some(caps) = create_policy.captures(sql) {
+            if caps.get(3).is_some_and(|m| m.as_str().eq_ignore_ascii_case("RESTRICTIVE")) {
+                return Err(Error::unimplemented("RESTRICTIVE row policies; policies are permissive"));
+            }
+            let command = caps.get(4).map(|m| PolicyCommand::parse(m.as_str())).transpose()?.unwrap_or_default();
+            let roles = match caps.get(5) {
+                Some(roles) => parse_users(roles.as_str())?,
+                None => Vec::new(),
+            };
+            let using = caps.get(6).map(|m| m.as_str().trim().to_string());
+            let check = caps.get(7).map(|m| m.as_str().trim().to_string());
+            for expr in using.iter().chain(&check) {
+                parse_expression(expr)?;
+            }
+            if command == PolicyCommand::Insert && using.is_some() {
+                return Err(Error::validation("Only WITH CHECK is allowed for INSERT policies"));
+            }
+            if matches!(command, PolicyCommand::Select | PolicyCommand::Delete) && check.is_some() {
+                return Err(Error::validation("WITH CHECK cannot be applied to SELECT or DELETE policies"));
+            }
+            let policy = RowPolicy { name: caps[1].to_lowercase(), command, roles, using, check };
+            return Ok(Some(SecurityCommand::CreatePolicy { table: caps[2].to_lowercase(), policy }));
+        }
+        if let Some(caps) = drop_policy.captures(sql) {
+            return Ok(Some(SecurityCommand::DropPolicy {
+                name: caps[2].to_lowercase(),
+                table: caps[3].to_lowercase(),
+                if_exists: caps.get(1).is_some(),
+            }));
+        }
+        if let Some(caps) = mask.captures(sql) {
+            let mask = caps.get(3).map(|m| m.as_str().trim().to_string());
+            if let Some(mask) = &mask {
+                parse_expression(mask)?;
+            }
+            return Ok(Some(SecurityCommand::SetMask {
+                table: caps[1].to_lowercase(),
+                column: caps[2].to_lowercase(),
+                mask,
+            }));
+        }
+        if let Some(caps) = user.captures(sql) {
+            let name = unquote(&caps[1]);
+            return Ok(Some(match (caps.get(2), caps.get(3), caps.get(4)) {
+                (Some(attribute), Some(value), _) => SecurityCommand::SetUserAttribute {
+                    user: name,
+                    attribute: Some(attribute.as_str().to_lowercase()),
+                    value: Some(parse_attribute_value(value.as_str())?),
+                },
+                (_, _, Some(attribute)) => SecurityCommand::SetUserAttribute {
+                    user: name,
+                    attribute: Some(attribute.as_str().to_lowercase()).filter(|a| a != "all"),
+                    value: None,
+                },
+                _ => return Ok(None),
+            }));
+        }
+        Ok(None)
+    }
+
+    /// 在目录上执行命令
+    pub fn apply(self, catalog: &Catalog) -> Result<()> {
+        let security = catalog.security();
+        let definition = |table: &str| catalog.get_table(table).ok_or_else(|| Error::not_found(format!("table {}", table)));
+        match self {
+            SecurityCommand::Grant { privileges, table, grantees } => {
+                let definition = definition(&table)?;
+                validate_privileges(&definition, &privileges)?;
+                security.update(&definition, |table| {
+                    for grantee in &grantees {
+                        for (privilege, columns) in &privileges {
+                            table.grant(grantee, *privilege, columns.as_deref());
+                        }
+                    }
+                    Ok(())
+                })
+            }
+            SecurityCommand::Revoke { privileges, table, grantees } => {
+                let definition = definition(&table)?;
+                validate_privileges(&definition, &privileges)?;
+                security.update(&definition, |table| {
+                    for grantee in &grantees {
+                        for (privilege, columns) in &privileges {
+                            table.revoke(grantee, *privilege, columns.as_deref());
+                        }
+                    }
+                    Ok(())
+                })
+            }
+            SecurityCommand::CreatePolicy { table, policy } => {
+                let definition = definition(&table)?;
+                security.update(&definition, |table| {
+                    if table.policies.iter().any(|p| p.name == policy.name) {
+                        return Err(Error::already_exists(format!("policy {} on table {}", policy.name, definition.name)));
+                    }
+                    table.policies.push(policy);
+                    Ok(())
+                })
+            }
+            SecurityCommand::DropPolicy { name, table, if_exists } => {
+                let definition = match (catalog.get_table(&table), if_exists) {
+                    (Some(definition), _) => definition,
+                    (None, true) => return Ok(()),
+                    (None, false) => return Err(Error::not_found(format!("table {}", table))),
+                };
+                if !security.is_secured(&table) && if_exists {
+                    return Ok(());
+                }
+                security.update(&definition, |table| {
+                    let before = table.policies.len();
+                    table.policies.retain(|p| p.name != name);
+                    if table.policies.len() == before && !if_exists {
+                        return Err(Error::not_found(format!("policy {} on table {}", name, definition.name)));
+                    }
+                    Ok(())
+                })
+            }
+            SecurityCommand::SetMask { table, column, mask } => {
+                let definition = definition(&table)?;
+                if definition.column(&column).is_none() {
+                    return Err(Error::not_found(format!("column {}.{}", definition.name, column)));
+                }
+                security.update(&definition, |table| {
+                    match mask {
+                        Some(mask) => table.masks.insert(column, mask),
+                        None => table.masks.remove(&column),
+                    };
+                    Ok(())
+                })
+            }
+            SecurityCommand::SetUserAttribute { user, attribute, value } => {
+                match (attribute, value) {
+                    (Some(attribute), Some(value)) => security.set_user_attribute(&user, &attribute, value),
+                    (attribute, _) => security.reset_user_attributes(&user, attribute.as_deref()),
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 检查授权的列存在，且只对支持列级授权的权限指定列
+fn validate_privileges(definition: &TableDefinition, privileges: &[(Privilege, Option<Vec<String>>)]) -> Result<()> {
+    for (privilege, columns) in privileges {
+        let Some(columns) = columns else {
+            continue;
+        };
+        if !privilege.column_level() {
+            return Err(Error::validation(format!("{} cannot be granted on columns", privilege.as_sql())));
+        }
+        if let Some(column) = columns.iter().find(|column| definition.column(column).is_none()) {
+            return Err(Error::not_found(format!("column {}.{}", definition.name, column)));
+        }
+    }
+    Ok(())
+}
+
+/// 解析权限列表：`ALL [PRIVILEGES]`或逗号分隔的`privilege [(column, ...)]`
+fn parse_privileges(text: &str) -> Result<Vec<(Privilege, Option<Vec<String>>)>> {
+    static ITEM: OnceLock<Regex> = OnceLock::new();
+    let item = ITEM.get_or_init(|| Regex::new(r"(?is)^\s*([A-Za-z]+)\s*(?:\(([^)]*)\))?\s*$").expect("valid regex"));
+    let words: Vec<String> = text.split_whitespace().map(str::to_uppercase).collect();
+    if matches!(words.iter().map(String::as_str).collect::<Vec<_>>().as_slice(), ["ALL"] | ["ALL", "PRIVILEGES"]) {
+        return Ok([Privilege::Select, Privilege::Insert, Privilege::Update, Privilege::Delete]
+            .into_iter()
+            .map(|privilege| (privilege, None))
+            .collect());
+    }
+    split_top_level(text).iter()
+        .map(|part| {
+            let caps = item.captures(part).ok_or_else(|| Error::parse(format!("Invalid privilege: {}", part.trim())))?;
+            let columns = caps.get(2).map(|columns| {
+                columns.as_str().split(',').map(|column| unquote(column.trim())).filter(|c| !c.is_empty()).collect()
+            });
+            Ok((Privilege::parse(&caps[1])?, columns))
+        })
+        .collect()
+}
+
+/// 解析逗号分隔的用户列表（`PUBLIC`表示所有用户）
+fn parse_users(text: &str) -> Result<Vec<String>> {
+    let users: Vec<String> = text.split(',').map(|user| unquote(user.trim())).collect();
+    if users.iter().any(|user| user.is_empty() || user.contains(char::is_whitespace)) {
+        return Err(Error::parse(format!("Invalid user list: {}", text.trim())));
+    }
+    Ok(users)
+}
+
+/// 按不在括号内的逗号切分
+pub(crate) fn split_top_level(text: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(part) = parts.last_mut() {
+            part.push(c);
+        }
+    }
+    parts
+}
+
+/// 标识符：带双引号时保留原样，否则转为小写
+fn unquote(identifier: &str) -> String {
+    match identifier.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        Some(quoted) => quoted.to_string(),
+        None => identifier.to_lowercase(),
+    }
+}
+
+/// 解析用户属性的取值（字面量）
+fn parse_attribute_value(text: &str) -> Result<Value> {
+    match parse_expression(text)? {
+        Expr::Value(literal) => crate::expressions::literal_to_value(&literal),
+        Expr::UnaryOp { op: ast::UnaryOperator::Minus, expr } => match *expr {
+            Expr::Value(literal) => crate::expressions::binary_op(
+                &Value::Int64(0), &BinaryOperator::Minus, &crate::expressions::literal_to_value(&literal)?,
+            ),
+            other => Err(Error::validation(format!("User attribute values must be literals: -{}", other))),
+        },
+        Expr::Identifier(ident) => Ok(Value::String(ident.value)),
+        other => Err(Error::validation(format!("User attribute values must be literals: {}", other))),
+    }
+}
+
+/// 完全掩码：字符串替换为`XXXX`（不暴露长度），数值为0，其他类型为NULL
+pub fn mask(args: &[Value]) -> Result<Value> {
+    let [value] = args else {
+        return Err(Error::validation("MASK requires exactly 1 argument"));
+    };
+    Ok(match value {
+        Value::Null => Value::Null,
+        Value::String(_) | Value::Symbol(_) => Value::String("XXXX".to_string()),
+        Value::Int8(_) | Value::Int16(_) | Value::Int32(_) | Value::Int64(_)
+        | Value::UInt8(_) | Value::UInt16(_) | Value::UInt32(_) | Value::UInt64(_) => Value::Int64(0),
+        Value::Float32(_) | Value::Float64(_) => Value::Float64(0.0),
+        Value::Decimal(_) | Value::Price(_) => Value::Decimal(Default::default()),
+        _ => Value::Null,
+    })
+}
+
+/// 只保留前n个字符，其余替换为`X`
+pub fn mask_show_first(args: &[Value]) -> Result<Value> {
+    partial_mask("MASK_SHOW_FIRST", args, |chars, keep| (0, keep.min(chars)))
+}
+
+/// 只保留后n个字符，其余替换为`X`（例如账号`XXXXXX1234`）
+pub fn mask_show_last(args: &[Value]) -> Result<Value> {
+    partial_mask("MASK_SHOW_LAST", args, |chars, keep| (chars.saturating_sub(keep), chars))
+}
+
+/// 按值的文本保留`[start, end)`范围内的字符
+fn partial_mask(name: &str, args: &[Value], visible: impl Fn(usize, usize) -> (usize, usize)) -> Result<Value> {
+    let [value, keep] = args else {
+        return Err(Error::validation(format!("{} requires exactly 2 arguments", name)));
+    };
+    let keep = value_as_i64(keep).filter(|n| *n >= 0)
+        .ok_or_else(|| Error::validation(format!("{} requires a non-negative character count", name)))? as usize;
+    let Some(text) = mask_text(value) else {
+        return Ok(Value::Null);
+    };
+    let chars = text.chars().count();
+    let (start, end) = visible(chars, keep);
+    Ok(Value::String(text.chars().enumerate().map(|(i, c)| if i >= start && i < end { c } else { 'X' }).collect()))
+}
+
+/// 被掩码值的文本；数值等非字符串值按显示格式处理，NULL返回None
+fn mask_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        value => Some(value_as_str(value).unwrap_or_else(|| display_value(value))),
+    }
+}
+
+/// 稳定的哈希掩码（十六进制），相同原值得到相同结果，仍可用于连接与分组
+pub fn mask_hash(args: &[Value]) -> Result<Value> {
+    let [value] = args else {
+        return Err(Error::validation("MASK_HASH requires exactly 1 argument"));
+    };
+    let Some(text) = mask_text(value) else {
+        return Ok(Value::Null);
+    };
+    // FNV-1a，跨进程与版本稳定
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    Ok(Value::String(format!("{:016x}", hash)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{ColumnDefinition, ColumnType};
+    use crate::executor::parse_statement;
+
+    fn catalog() -> Catalog {
+        let catalog = Catalog::new();
+        catalog.register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("desk", ColumnType::String),
+            ColumnDefinition::new("account_id", ColumnType::String),
+            ColumnDefinition::new("price", ColumnType::Float64),
+        ]).with_primary_key("id")).unwrap();
+        catalog
+    }
+
+    fn apply(catalog: &Catalog, sql: &str) {
+        SecurityCommand::parse(sql).unwrap().unwrap().apply(catalog).unwrap();
+    }
+
+    fn authorize(catalog: &Catalog, sql: &str, user: &str) -> Result<QuerySecurity> {
+        QuerySecurity::authorize(&parse_statement(sql).unwrap(), catalog, user)
+    }
+
+    #[test]
+    fn test_parse_security_commands() {
+        assert_eq!(SecurityCommand::parse("GRANT SELECT (id, price), UPDATE ON TABLE trades TO alice, \"Bob\"").unwrap(), Some(SecurityCommand::Grant {
+            privileges: vec![
+                (Privilege::Select, Some(vec!["id".to_string(), "price".to_string()])),
+                (Privilege::Update, None),
+            ],
+            table: "trades".to_string(),
+            grantees: vec!["alice".to_string(), "Bob".to_string()],
+        }));
+        assert!(matches!(
+            SecurityCommand::parse("revoke all privileges on trades from public").unwrap(),
+            Some(SecurityCommand::Revoke { privileges, .. }) if privileges.len() == 4
+        ));
+        assert!(SecurityCommand::parse("GRANT SELECT ON trades FROM alice").is_err());
+
+        let Some(SecurityCommand::CreatePolicy { table, policy }) = SecurityCommand::parse(
+            "CREATE POLICY desk_rows ON trades FOR UPDATE TO traders USING (desk = current_user_desk()) WITH CHECK (price > 0)",
+        ).unwrap() else {
+            panic!("expected CREATE POLICY");
+        };
+        assert_eq!(table, "trades");
+        assert_eq!(policy.command, PolicyCommand::Update);
+        assert_eq!(policy.roles, vec!["traders".to_string()]);
+        assert_eq!(policy.using.as_deref(), Some("desk = current_user_desk()"));
+        assert_eq!(policy.check.as_deref(), Some("price > 0"));
+        assert!(SecurityCommand::parse("CREATE POLICY p ON trades AS RESTRICTIVE USING (true)").is_err());
+
+        assert_eq!(SecurityCommand::parse("ALTER TABLE trades ALTER COLUMN account_id SET MASK USING (mask_show_last(account_id, 4))").unwrap(), Some(SecurityCommand::SetMask {
+            table: "trades".to_string(),
+            column: "account_id".to_string(),
+            mask: Some("mask_show_last(account_id, 4)".to_string()),
+        }));
+        assert_eq!(SecurityCommand::parse("ALTER USER alice SET desk = 'rates'").unwrap(), Some(SecurityCommand::SetUserAttribute {
+            user: "alice".to_string(),
+            attribute: Some("desk".to_string()),
+            value: Some(Value::String("rates".to_string())),
+        }));
+        assert_eq!(SecurityCommand::parse("ALTER TABLE trades ADD COLUMN qty INT").unwrap(), None);
+        assert_eq!(SecurityCommand::parse("SELECT 1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_privileges_and_column_grants() {
+        let catalog = catalog();
+        // 未设置访问控制的表对所有用户开放
+        assert!(authorize(&catalog, "SELECT * FROM trades", "alice").unwrap().is_empty());
+
+        apply(&catalog, "GRANT SELECT (id, desk, price) ON trades TO alice");
+        apply(&catalog, "GRANT UPDATE (price) ON trades TO alice");
+        assert!(authorize(&catalog, "SELECT t.id, price FROM trades t WHERE desk = 'rates'", "alice").is_ok());
+        assert!(authorize(&catalog, "SELECT count(*) FROM trades", "alice").is_ok());
+        let denied = authorize(&catalog, "SELECT id FROM trades WHERE account_id = 'A1'", "alice").unwrap_err();
+        assert!(denied.to_string().contains("SELECT on column trades.account_id"));
+        assert!(authorize(&catalog, "SELECT * FROM trades", "alice").is_err());
+        assert!(authorize(&catalog, "SELECT id FROM trades", "bob").is_err());
+        assert!(authorize(&catalog, "UPDATE trades SET price = price * 2 WHERE id = 1", "alice").is_ok());
+        assert!(authorize(&catalog, "UPDATE trades SET desk = 'fx'", "alice").is_err());
+        assert!(authorize(&catalog, "DELETE FROM trades WHERE id = 1", "alice").is_err());
+
+        apply(&catalog, "GRANT ALL ON trades TO PUBLIC");
+        assert!(authorize(&catalog, "SELECT * FROM trades", "bob").is_ok());
+        apply(&catalog, "REVOKE ALL ON trades FROM public");
+        apply(&catalog, "REVOKE SELECT (desk) ON trades FROM alice");
+        assert!(authorize(&catalog, "SELECT desk FROM trades", "alice").is_err());
+        assert!(authorize(&catalog, "SELECT id FROM trades", "alice").is_ok());
+    }
+
+    #[test]
+    fn test_policies_and_masks() {
+        let catalog = catalog();
+        apply(&catalog, "GRANT ALL ON trades TO alice, bob, carol");
+        apply(&catalog, "GRANT UNMASK ON trades TO carol");
+        apply(&catalog, "CREATE POLICY desk_rows ON trades TO alice, carol USING (desk = current_user_desk())");
+        apply(&catalog, "ALTER TABLE trades ALTER account_id SET MASK USING (mask_show_last(account_id, 2))");
+        apply(&catalog, "ALTER USER alice SET desk = 'rates'");
+
+        let row: Row = HashMap::from([
+            ("id".to_string(), Value::Int64(1)),
+            ("desk".to_string(), Value::String("rates".to_string())),
+            ("account_id".to_string(), Value::String("AC1234".to_string())),
+        ]);
+        let security = authorize(&catalog, "SELECT * FROM trades", "alice").unwrap();
+        let access = security.table("TRADES").unwrap();
+        let alice = ExpressionEvaluator::new().with_user(Some(std::sync::Arc::new(catalog.security().session_user("alice"))));
+        let rows = access.secure_scan(vec![row.clone()], &alice).unwrap();
+        assert_eq!(rows[0]["account_id"], Value::String("XXXX34".to_string()));
+        assert!(access.check("trades", Privilege::Insert, &row, &alice).is_ok());
+        let mut other_desk = row.clone();
+        other_desk.insert("desk".to_string(), Value::String("fx".to_string()));
+        assert!(access.check("trades", Privilege::Insert, &other_desk, &alice).is_err());
+
+        // 没有适用策略的用户看不到任何行
+        let security = authorize(&catalog, "SELECT id FROM trades", "bob").unwrap();
+        let bob = ExpressionEvaluator::new();
+        assert!(security.table("trades").unwrap().secure_scan(vec![row.clone()], &bob).unwrap().is_empty());
+
+        // 有UNMASK权限的用户看到原值
+        let security = authorize(&catalog, "SELECT id FROM trades", "carol").unwrap();
+        assert!(!security.table("trades").unwrap().masks_column("account_id"));
+
+        apply(&catalog, "DROP POLICY desk_rows ON trades");
+        apply(&catalog, "DROP POLICY IF EXISTS desk_rows ON trades");
+        apply(&catalog, "ALTER TABLE trades ALTER COLUMN account_id DROP MASK");
+        assert!(authorize(&catalog, "SELECT id FROM trades", "carol").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mask_functions() {
+        let account = Value::String("AC123456".to_string());
+        assert_eq!(mask(&[account.clone()]).unwrap(), Value::String("XXXX".to_string()));
+        assert_eq!(mask(&[Value::Int64(42)]).unwrap(), Value::Int64(0));
+        assert_eq!(mask_show_last(&[account.clone(), Value::Int64(4)]).unwrap(), Value::String("XXXX3456".to_string()));
+        assert_eq!(mask_show_first(&[account.clone(), Value::Int64(2)]).unwrap(), Value::String("ACXXXXXX".to_string()));
+        assert_eq!(mask_show_last(&[Value::Null, Value::Int64(4)]).unwrap(), Value::Null);
+        assert_eq!(mask_hash(&[account.clone()]).unwrap(), mask_hash(&[account]).unwrap());
+        assert_ne!(mask_hash(&[Value::String("a".to_string())]).unwrap(), mask_hash(&[Value::String("b".to_string())]).unwrap());
+    }
+}
@@ -0,0 +1,337 @@
+//! Mergeable sketches for approximate aggregates: t-digest, HyperLogLog and streaming histograms
+
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// t-digest默认压缩参数（约100个质心，分位数误差通常小于1%）
+pub const DEFAULT_TDIGEST_COMPRESSION: f64 = 100.0;
+
+/// HyperLogLog寄存器索引位数（4096个寄存器，标准误差约1.6%）
+pub const HLL_PRECISION: u32 = 12;
+
+/// 流式直方图默认桶数
+pub const DEFAULT_HISTOGRAM_BINS: usize = 16;
+
+/// t-digest质心
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// 合并式t-digest，用于近似分位数
+///
+/// 新值先进入缓冲区，缓冲区满时与已有质心一起按均值排序并合并；
+/// 靠近两端的质心容量小，使尾部分位数更精确。
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self::new(DEFAULT_TDIGEST_COMPRESSION)
+    }
+}
+
+impl TDigest {
+    /// 创建空的t-digest
+    pub fn new(compression: f64) -> Self {
+        Self {
+            compression: compression.max(10.0),
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// 加入一个值（NaN被忽略）
+    pub fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(Centroid { mean: value, weight: 1.0 });
+        if self.buffer.len() >= self.compression as usize * 5 {
+            self.compress();
+        }
+    }
+
+    /// 合并另一个t-digest
+    pub fn merge(&mut self, other: &TDigest) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.buffer.extend(other.centroids.iter().chain(&other.buffer).copied());
+        self.compress();
+    }
+
+    /// 已加入的值个数
+    pub fn count(&self) -> f64 {
+        self.centroids.iter().chain(&self.buffer).map(|c| c.weight).sum()
+    }
+
+    /// 近似分位数，`q`取0到1；为空时返回None
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let mut digest = self.clone();
+        digest.compress();
+        let centroids = &digest.centroids;
+        let total: f64 = centroids.iter().map(|c| c.weight).sum();
+        if centroids.is_empty() {
+            return None;
+        }
+        if q <= 0.0 {
+            return Some(digest.min);
+        }
+        if q >= 1.0 {
+            return Some(digest.max);
+        }
+        if centroids.len() == 1 {
+            return Some(centroids[0].mean);
+        }
+
+        // 每个质心的权重集中在其中心位置，中心之间线性插值
+        let target = q * total;
+        let mut cumulative = 0.0;
+        let mut previous = (0.0, digest.min);
+        for centroid in centroids {
+            let center = cumulative + centroid.weight / 2.0;
+            if target < center {
+                let (prev_position, prev_mean) = previous;
+                let span = center - prev_position;
+                let fraction = if span > 0.0 { (target - prev_position) / span } else { 0.0 };
+                return Some(prev_mean + fraction * (centroid.mean - prev_mean));
+            }
+            previous = (center, centroid.mean);
+            cumulative += centroid.weight;
+        }
+        let (last_position, last_mean) = previous;
+        let span = total - last_position;
+        let fraction = if span > 0.0 { (target - last_position) / span } else { 1.0 };
+        Some(last_mean + fraction * (digest.max - last_mean))
+    }
+
+    /// 与`q`分位最接近的质心均值（离散分位数）
+    pub fn quantile_disc(&self, q: f64) -> Option<f64> {
+        let mut digest = self.clone();
+        digest.compress();
+        let total: f64 = digest.centroids.iter().map(|c| c.weight).sum();
+        let target = (q.clamp(0.0, 1.0) * total).max(f64::MIN_POSITIVE);
+        let mut cumulative = 0.0;
+        for centroid in &digest.centroids {
+            cumulative += centroid.weight;
+            if cumulative >= target {
+                return Some(centroid.mean);
+            }
+        }
+        digest.centroids.last().map(|c| c.mean)
+    }
+
+    /// 把缓冲区与质心合并，质心容量上限为`4·n·q·(1-q)/δ`
+    fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut all: Vec<Centroid> = self.centroids.drain(..).chain(self.buffer.drain(..)).collect();
+        all.sort_by(|a, b| a.mean.partial_cmp(&b.mean).unwrap_or(Ordering::Equal));
+        let total: f64 = all.iter().map(|c| c.weight).sum();
+
+        let mut merged: Vec<Centroid> = Vec::with_capacity(self.compression as usize * 2);
+        let mut cumulative = 0.0;
+        for centroid in all {
+            if let Some(last) = merged.last_mut() {
+                // 合并后质心中心所在的分位
+                let q = (cumulative - last.weight + (last.weight + centroid.weight) / 2.0) / total;
+                let limit = (4.0 * total * q * (1.0 - q) / self.compression).max(1.0);
+                if last.weight + centroid.weight <= limit {
+                    let weight = last.weight + centroid.weight;
+                    last.mean += (centroid.mean - last.mean) * centroid.weight / weight;
+                    last.weight = weight;
+                    cumulative += centroid.weight;
+                    continue;
+                }
+            }
+            cumulative += centroid.weight;
+            merged.push(centroid);
+        }
+        self.centroids = merged;
+    }
+}
+
+/// HyperLogLog基数估计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self { registers: vec![0; 1 << HLL_PRECISION] }
+    }
+}
+
+impl HyperLogLog {
+    /// 创建空的HyperLogLog
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 加入一个可哈希的值；哈希与进程无关，不同节点上的草图可以合并
+    pub fn add(&mut self, item: impl Hash) {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        self.add_hash(hasher.finish());
+    }
+
+    /// 加入一个64位哈希值
+    pub fn add_hash(&mut self, hash: u64) {
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        // 补一个哨兵位，保证剩余位全为0时rank有上界
+        let rest = (hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// 合并另一个草图（逐寄存器取最大值）
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    /// 估计的不同值个数
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // 小基数时线性计数更准确
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// 流式直方图（Ben-Haim & Tom-Tov）：最多保留`max_bins`个(中心, 计数)桶，
+/// 超出时合并中心最接近的两个桶
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamingHistogram {
+    max_bins: usize,
+    bins: Vec<(f64, u64)>,
+}
+
+impl StreamingHistogram {
+    /// 创建空直方图
+    pub fn new(max_bins: usize) -> Self {
+        Self { max_bins: max_bins.max(1), bins: Vec::new() }
+    }
+
+    /// 桶数上限
+    pub fn max_bins(&self) -> usize {
+        self.max_bins
+    }
+
+    /// 加入一个值（NaN被忽略）
+    pub fn add(&mut self, value: f64) {
+        if !value.is_nan() {
+            self.insert(value, 1);
+        }
+    }
+
+    /// 合并另一个直方图
+    pub fn merge(&mut self, other: &StreamingHistogram) {
+        for &(center, count) in &other.bins {
+            self.insert(center, count);
+        }
+    }
+
+    /// 按中心升序排列的(中心, 计数)
+    pub fn bins(&self) -> &[(f64, u64)] {
+        &self.bins
+    }
+
+    fn insert(&mut self, center: f64, count: u64) {
+        let position = self.bins.partition_point(|(c, _)| *c < center);
+        match self.bins.get_mut(position) {
+            Some(bin) if bin.0 == center => bin.1 += count,
+            _ => self.bins.insert(position, (center, count)),
+        }
+        while self.bins.len() > self.max_bins {
+            let closest = (0..self.bins.len() - 1)
+                .min_by(|&a, &b| {
+                    let gap = |i: usize| self.bins[i + 1].0 - self.bins[i].0;
+                    gap(a).partial_cmp(&gap(b)).unwrap_or(Ordering::Equal)
+                })
+                .unwrap_or(0);
+            let (left, right) = (self.bins[closest], self.bins.remove(closest + 1));
+            let total = left.1 + right.1;
+            self.bins[closest] = ((left.0 * left.1 as f64 + right.0 * right.1 as f64) / total as f64, total);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tdigest_quantiles() {
+        let mut left = TDigest::default();
+        let mut right = TDigest::default();
+        for i in 0..10_000 {
+            if i % 2 == 0 { left.add(i as f64) } else { right.add(i as f64) }
+        }
+        left.merge(&right);
+        assert_eq!(left.count(), 10_000.0);
+        let median = left.quantile(0.5).unwrap();
+        assert!((median - 5_000.0).abs() < 100.0, "median {}", median);
+        let p99 = left.quantile(0.99).unwrap();
+        assert!((p99 - 9_900.0).abs() < 30.0, "p99 {}", p99);
+        assert_eq!(left.quantile(0.0), Some(0.0));
+        assert_eq!(left.quantile(1.0), Some(9_999.0));
+        assert_eq!(TDigest::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_hyperloglog_merge() {
+        let mut left = HyperLogLog::new();
+        let mut right = HyperLogLog::new();
+        for i in 0..20_000u64 {
+            left.add(i);
+            right.add(i + 10_000);
+        }
+        left.merge(&right);
+        let estimate = left.estimate() as f64;
+        assert!((estimate - 30_000.0).abs() / 30_000.0 < 0.05, "estimate {}", estimate);
+
+        let mut small = HyperLogLog::new();
+        for symbol in ["AAPL", "MSFT", "AAPL", "TSLA"] {
+            small.add(symbol);
+        }
+        assert_eq!(small.estimate(), 3);
+    }
+
+    #[test]
+    fn test_streaming_histogram() {
+        let mut histogram = StreamingHistogram::new(2);
+        for value in [1.0, 2.0, 3.0, 100.0, 101.0] {
+            histogram.add(value);
+        }
+        assert_eq!(histogram.bins(), &[(2.0, 3), (100.5, 2)]);
+        let mut other = StreamingHistogram::new(2);
+        other.add(2.0);
+        histogram.merge(&other);
+        assert_eq!(histogram.bins(), &[(2.0, 4), (100.5, 2)]);
+    }
+}
@@ -1,54 +1,300 @@
 //! Sort operations
 
-use fdc_core::{error::Result, types::Value};
-use std::collections::HashMap;
-use std::cmp::Ordering;
+use crate::{
+    executor::select_item_expr,
+    expressions::{self, ExpressionEvaluator},
+    joins::{estimate_row_size, Row},
+    spill::SpillFiles,
+};
+use fdc_core::{error::{Error, Result}, types::Value};
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{Expr, OrderByExpr, SelectItem};
+use std::collections::BinaryHeap;
+use std::cmp::{Ordering, Reverse};
+use std::path::PathBuf;
+
+/// 带已求值排序键的行
+type KeyedRow = (Vec<Value>, Row);
+
+/// 排序执行配置
+#[derive(Debug, Clone)]
+pub struct SortConfig {
+    /// 排序在内存中保留的字节数上限，超出后把已排序的顺串落盘再归并
+    pub memory_budget: usize,
+    /// 落盘目录
+    pub spill_dir: PathBuf,
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        Self {
+            memory_budget: 64 * 1024 * 1024,
+            spill_dir: std::env::temp_dir(),
+        }
+    }
+}
+
+impl SortConfig {
+    /// 设置内存预算
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    /// 设置落盘目录
+    pub fn with_spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = dir.into();
+        self
+    }
+}
+
+/// 单个排序键的方向与NULL位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortOrder {
+    /// 是否升序
+    pub ascending: bool,
+    /// NULL是否排在最前
+    pub nulls_first: bool,
+}
+
+impl SortOrder {
+    /// 从`ORDER BY`项创建（默认升序；NULL默认视为最大值）
+    pub fn from_order_by(order_by: &OrderByExpr) -> Self {
+        let ascending = order_by.asc.unwrap_or(true);
+        Self {
+            ascending,
+            nulls_first: order_by.nulls_first.unwrap_or(!ascending),
+        }
+    }
+}
+
+/// 把`ORDER BY`中的投影别名和位置编号（`ORDER BY 2`）替换为对应的投影表达式，
+/// 使排序键可以在投影之前的行上求值
+pub fn resolve_order_by(order_by: &[OrderByExpr], projection: &[SelectItem]) -> Vec<Expr> {
+    order_by.iter().map(|item| {
+        let resolved = match &item.expr {
+            Expr::Identifier(ident) => projection.iter().find_map(|p| match p {
+                SelectItem::ExprWithAlias { expr, alias } if alias.value.eq_ignore_ascii_case(&ident.value) => Some(expr),
+                _ => None,
+            }),
+            Expr::Value(sqlparser::ast::Value::Number(n, _)) => n.parse::<usize>().ok()
+                .and_then(|position| position.checked_sub(1))
+                .and_then(|index| projection.get(index))
+                .and_then(select_item_expr),
+            _ => None,
+        };
+        resolved.unwrap_or(&item.expr).clone()
+    }).collect()
+}
+
+/// 按排序键对行做稳定排序，每行的键只求值一次
+pub fn sort_rows(rows: Vec<Row>, keys: &[Expr], orders: &[SortOrder], evaluator: &ExpressionEvaluator) -> Result<Vec<Row>> {
+    let mut keyed = evaluate_keys(rows, keys, evaluator)?;
+    keyed.sort_by(|(a, _), (b, _)| SortOperations::compare_keys(a, b, orders));
+    Ok(keyed.into_iter().map(|(_, row)| row).collect())
+}
+
+/// 多路归并若干已按同一排序键有序的行序列，`limit`限制输出行数（Top-N归并）
+///
+/// 键相等时序号较小的序列优先，结果与拼接后稳定排序一致。
+pub fn merge_sorted(
+    runs: Vec<Vec<Row>>,
+    keys: &[Expr],
+    orders: &[SortOrder],
+    evaluator: &ExpressionEvaluator,
+    limit: Option<usize>,
+) -> Result<Vec<Row>> {
+    let runs = runs.into_iter()
+        .map(|run| Ok(evaluate_keys(run, keys, evaluator)?.into_iter().map(Ok::<_, Error>)))
+        .collect::<Result<Vec<_>>>()?;
+    merge_runs(runs, orders, limit)
+}
+
+/// 按排序键取前`n`行：用容量为`n`的最大堆淘汰较大的行，结果与完整稳定排序后截断一致
+pub fn top_n_rows(
+    rows: Vec<Row>,
+    keys: &[Expr],
+    orders: &[SortOrder],
+    evaluator: &ExpressionEvaluator,
+    n: usize,
+) -> Result<Vec<Row>> {
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+    let mut heap = BinaryHeap::with_capacity(n.min(rows.len()));
+    for (sequence, row) in rows.into_iter().enumerate() {
+        evaluator.checkpoint()?;
+        let key = keys.iter().map(|expr| evaluator.evaluate(expr, &row)).collect::<Result<Vec<_>>>()?;
+        let entry = HeapEntry { key, sequence, row, orders };
+        if heap.len() < n {
+            heap.push(entry);
+        } else if heap.peek().is_some_and(|largest| entry < *largest) {
+            heap.pop();
+            heap.push(entry);
+        }
+    }
+    Ok(heap.into_sorted_vec().into_iter().map(|entry| entry.row).collect())
+}
+
+/// 外部归并排序：行在内存预算内分块排序，超出预算时把每块作为有序顺串落盘，最后多路归并
+///
+/// `limit`限制输出行数。返回的第二项是落盘的顺串个数（全部放得进内存时为0）。结果与`sort_rows`一致。
+pub fn external_sort(
+    rows: Vec<Row>,
+    keys: &[Expr],
+    orders: &[SortOrder],
+    evaluator: &ExpressionEvaluator,
+    config: &SortConfig,
+    limit: Option<usize>,
+) -> Result<(Vec<Row>, usize)> {
+    let total: usize = rows.iter().map(estimate_row_size).sum();
+    if total <= config.memory_budget {
+        let mut sorted = sort_rows(rows, keys, orders, evaluator)?;
+        if let Some(limit) = limit {
+            sorted.truncate(limit);
+        }
+        return Ok((sorted, 0));
+    }
+
+    tracing::debug!("Sort input ({} bytes) exceeds budget ({} bytes), spilling sorted runs", total, config.memory_budget);
+    let mut spill = SpillFiles::new(&config.spill_dir, "sort", 0);
+    let mut chunk: Vec<KeyedRow> = Vec::new();
+    let mut chunk_bytes = 0;
+    for row in rows {
+        evaluator.checkpoint()?;
+        let size = estimate_row_size(&row);
+        // 每块至少一行，预算为0时每行一个顺串
+        if !chunk.is_empty() && chunk_bytes + size > config.memory_budget {
+            chunk.sort_by(|(a, _), (b, _)| SortOperations::compare_keys(a, b, orders));
+            spill.push(&chunk)?;
+            chunk.clear();
+            chunk_bytes = 0;
+        }
+        chunk_bytes += size;
+        let key = keys.iter().map(|expr| evaluator.evaluate(expr, &row)).collect::<Result<Vec<_>>>()?;
+        chunk.push((key, row));
+    }
+    // 最后一块留在内存中作为序号最大的顺串
+    chunk.sort_by(|(a, _), (b, _)| SortOperations::compare_keys(a, b, orders));
+
+    let mut runs = (0..spill.len())
+        .map(|index| Ok(Box::new(spill.reader::<KeyedRow>(index)?) as Box<dyn Iterator<Item = Result<KeyedRow>>>))
+        .collect::<Result<Vec<_>>>()?;
+    runs.push(Box::new(chunk.into_iter().map(Ok::<_, Error>)));
+    Ok((merge_runs(runs, orders, limit)?, spill.len()))
+}
+
+/// 多路归并已有序的顺串，每个顺串逐行读取；键相等时序号较小的顺串优先
+///
+/// 各顺串的当前行放在以顺串序号为次序的小顶堆中，每输出一行为O(log k)。
+fn merge_runs<I>(mut runs: Vec<I>, orders: &[SortOrder], limit: Option<usize>) -> Result<Vec<Row>>
+where
+    I: Iterator<Item = Result<KeyedRow>>,
+{
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut heads = BinaryHeap::with_capacity(runs.len());
+    for (sequence, run) in runs.iter_mut().enumerate() {
+        if let Some((key, row)) = run.next().transpose()? {
+            heads.push(Reverse(HeapEntry { key, sequence, row, orders }));
+        }
+    }
+    let mut merged = Vec::new();
+    while merged.len() < limit {
+        let Some(Reverse(head)) = heads.pop() else { break };
+        if let Some((key, row)) = runs[head.sequence].next().transpose()? {
+            heads.push(Reverse(HeapEntry { key, sequence: head.sequence, row, orders }));
+        }
+        merged.push(head.row);
+    }
+    Ok(merged)
+}
+
+/// 排序堆中的一行：按排序键比较，键相等时序号较小的行较小（Top-N中为行序号，多路归并中为顺串序号）
+struct HeapEntry<'a> {
+    key: Vec<Value>,
+    sequence: usize,
+    row: Row,
+    orders: &'a [SortOrder],
+}
+
+impl Ord for HeapEntry<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        SortOperations::compare_keys(&self.key, &other.key, self.orders)
+            .then(self.sequence.cmp(&other.sequence))
+    }
+}
+
+impl PartialOrd for HeapEntry<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapEntry<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry<'_> {}
+
+fn evaluate_keys(rows: Vec<Row>, keys: &[Expr], evaluator: &ExpressionEvaluator) -> Result<Vec<KeyedRow>> {
+    rows.into_iter()
+        .map(|row| {
+            evaluator.checkpoint()?;
+            let key = keys.iter().map(|expr| evaluator.evaluate(expr, &row)).collect::<Result<Vec<_>>>()?;
+            Ok((key, row))
+        })
+        .collect()
+}
 
 /// 排序操作
 pub struct SortOperations;
 
 impl SortOperations {
-    pub fn sort(
-        mut rows: Vec<HashMap<String, Value>>,
-        column: &str,
-        ascending: bool,
-    ) -> Result<Vec<HashMap<String, Value>>> {
-        rows.sort_by(|a, b| {
-            let a_val = a.get(column);
-            let b_val = b.get(column);
-            
-            let ordering = match (a_val, b_val) {
-                (Some(a), Some(b)) => Self::compare_values(a, b),
-                (Some(_), None) => Ordering::Less,
-                (None, Some(_)) => Ordering::Greater,
-                (None, None) => Ordering::Equal,
+    /// 按多个排序键比较两组已求值的键
+    pub fn compare_keys(a: &[Value], b: &[Value], orders: &[SortOrder]) -> Ordering {
+        for ((a, b), order) in a.iter().zip(b).zip(orders) {
+            let ordering = match (matches!(a, Value::Null), matches!(b, Value::Null)) {
+                (true, true) => Ordering::Equal,
+                (true, false) => if order.nulls_first { Ordering::Less } else { Ordering::Greater },
+                (false, true) => if order.nulls_first { Ordering::Greater } else { Ordering::Less },
+                (false, false) => {
+                    let ordering = expressions::compare_values(a, b).unwrap_or(Ordering::Equal);
+                    if order.ascending { ordering } else { ordering.reverse() }
+                }
             };
-            
-            if ascending {
-                ordering
-            } else {
-                ordering.reverse()
+            if ordering != Ordering::Equal {
+                return ordering;
             }
-        });
-        
-        Ok(rows)
+        }
+        Ordering::Equal
     }
     
-    fn compare_values(a: &Value, b: &Value) -> Ordering {
-        match (a, b) {
-            (Value::Int32(a), Value::Int32(b)) => a.cmp(b),
-            (Value::Int64(a), Value::Int64(b)) => a.cmp(b),
-            (Value::Float32(a), Value::Float32(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
-            (Value::Float64(a), Value::Float64(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
-            (Value::String(a), Value::String(b)) => a.cmp(b),
-            _ => Ordering::Equal,
-        }
+    /// 按单列排序（缺失列视为NULL，升序时排在最后）
+    pub fn sort(rows: Vec<Row>, column: &str, ascending: bool) -> Result<Vec<Row>> {
+        let order = SortOrder { ascending, nulls_first: !ascending };
+        Self::sort_by_columns(rows, &[(column, order)])
+    }
+
+    /// 按多列排序，各列有各自的方向与NULL位置
+    pub fn sort_by_columns(rows: Vec<Row>, columns: &[(&str, SortOrder)]) -> Result<Vec<Row>> {
+        let orders: Vec<SortOrder> = columns.iter().map(|(_, order)| *order).collect();
+        let mut keyed: Vec<KeyedRow> = rows.into_iter()
+            .map(|row| {
+                let key = columns.iter().map(|(column, _)| row.get(*column).cloned().unwrap_or(Value::Null)).collect();
+                (key, row)
+            })
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| Self::compare_keys(a, b, &orders));
+        Ok(keyed.into_iter().map(|(_, row)| row).collect())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn test_sort_operations() {
@@ -66,4 +312,61 @@ mod tests {
         assert_eq!(result[0].get("id"), Some(&Value::Int32(1)));
         assert_eq!(result[1].get("id"), Some(&Value::Int32(2)));
     }
+
+    #[test]
+    fn test_compare_keys() {
+        let asc = SortOrder { ascending: true, nulls_first: false };
+        let desc = SortOrder { ascending: false, nulls_first: true };
+        let a = [Value::Int64(1), Value::Null];
+        let b = [Value::Int32(1), Value::Float64(2.0)];
+        assert_eq!(SortOperations::compare_keys(&a, &b, &[asc, asc]), Ordering::Greater);
+        assert_eq!(SortOperations::compare_keys(&a, &b, &[asc, desc]), Ordering::Less);
+    }
+
+    #[test]
+    fn test_sort_and_merge_rows() {
+        let row = |id: i64, price: f64| -> Row {
+            HashMap::from([("id".to_string(), Value::Int64(id)), ("price".to_string(), Value::Float64(price))])
+        };
+        let keys = vec![Expr::Identifier(sqlparser::ast::Ident::new("price"))];
+        let desc = [SortOrder { ascending: false, nulls_first: true }];
+        let evaluator = ExpressionEvaluator::new();
+
+        let left = sort_rows(vec![row(1, 3.0), row(2, 9.0), row(3, 3.0)], &keys, &desc, &evaluator).unwrap();
+        let ids: Vec<_> = left.iter().map(|r| r["id"].clone()).collect();
+        assert_eq!(ids, vec![Value::Int64(2), Value::Int64(1), Value::Int64(3)]);
+
+        let right = vec![row(4, 7.0), row(5, 3.0)];
+        let merged = merge_sorted(vec![left, right], &keys, &desc, &evaluator, Some(4)).unwrap();
+        let ids: Vec<_> = merged.iter().map(|r| r["id"].clone()).collect();
+        assert_eq!(ids, vec![Value::Int64(2), Value::Int64(4), Value::Int64(1), Value::Int64(3)]);
+    }
+
+    #[test]
+    fn test_top_n_and_external_sort() {
+        let row = |id: i64, price: Option<f64>| -> Row {
+            HashMap::from([("id".to_string(), Value::Int64(id)), ("price".to_string(), price.map_or(Value::Null, Value::Float64))])
+        };
+        let rows: Vec<Row> = (0..40).map(|i| row(i, (i % 7 != 3).then_some(((i * 13) % 11) as f64))).collect();
+        let keys = vec![Expr::Identifier(sqlparser::ast::Ident::new("price"))];
+        let orders = [SortOrder { ascending: false, nulls_first: true }];
+        let evaluator = ExpressionEvaluator::new();
+        let ids = |rows: &[Row]| rows.iter().map(|r| r["id"].clone()).collect::<Vec<_>>();
+
+        let expected = sort_rows(rows.clone(), &keys, &orders, &evaluator).unwrap();
+        let top = top_n_rows(rows.clone(), &keys, &orders, &evaluator, 10).unwrap();
+        assert_eq!(ids(&top), ids(&expected[..10]));
+        assert!(top_n_rows(rows.clone(), &keys, &orders, &evaluator, 0).unwrap().is_empty());
+
+        let (in_memory, spilled) = external_sort(rows.clone(), &keys, &orders, &evaluator, &SortConfig::default(), None).unwrap();
+        assert_eq!(spilled, 0);
+        assert_eq!(ids(&in_memory), ids(&expected));
+
+        let config = SortConfig::default().with_memory_budget(estimate_row_size(&rows[0]) * 4);
+        let (external, spilled) = external_sort(rows.clone(), &keys, &orders, &evaluator, &config, None).unwrap();
+        assert_eq!(spilled, 9);
+        assert_eq!(ids(&external), ids(&expected));
+        let (limited, _) = external_sort(rows, &keys, &orders, &evaluator, &config.with_memory_budget(0), Some(5)).unwrap();
+        assert_eq!(ids(&limited), ids(&expected[..5]));
+    }
 }
@@ -0,0 +1,151 @@
+//! Temporary spill files shared by the spilling operators (hash join, external sort, hash aggregation)
+
+use fdc_core::error::{Error, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// 一组落盘文件（每行一条JSON记录），析构时删除
+pub struct SpillFiles {
+    dir: PathBuf,
+    stem: String,
+    paths: Vec<PathBuf>,
+}
+
+impl SpillFiles {
+    /// 在`dir`下预留`count`个文件，文件名形如`fdc-{prefix}-{uuid}-{序号}.jsonl`
+    pub fn new(dir: &Path, prefix: &str, count: usize) -> Self {
+        let mut files = Self {
+            dir: dir.to_path_buf(),
+            stem: format!("fdc-{}-{}", prefix, uuid::Uuid::new_v4()),
+            paths: Vec::new(),
+        };
+        for _ in 0..count {
+            files.add_path();
+        }
+        files
+    }
+
+    /// 文件个数
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// 是否没有文件
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// 追加一个文件并写入`items`，返回其序号
+    pub fn push<'a, T: Serialize + 'a>(&mut self, items: impl IntoIterator<Item = &'a T>) -> Result<usize> {
+        let index = self.add_path();
+        let mut writer = self.writer(index)?;
+        for item in items {
+            write_item(&mut writer, item)?;
+        }
+        writer.flush().map_err(spill_error)?;
+        Ok(index)
+    }
+
+    /// 按`partition_of`把`items`分到各文件
+    pub fn write_partitioned<T: Serialize>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        mut partition_of: impl FnMut(&T) -> Result<usize>,
+    ) -> Result<()> {
+        let mut writers = (0..self.paths.len()).map(|index| self.writer(index)).collect::<Result<Vec<_>>>()?;
+        for item in items {
+            let partition = partition_of(&item)?;
+            write_item(&mut writers[partition], &item)?;
+        }
+        for writer in &mut writers {
+            writer.flush().map_err(spill_error)?;
+        }
+        Ok(())
+    }
+
+    /// 打开所有文件的写入器，供逐条追加分区记录
+    pub fn partitioned_writer(&self) -> Result<PartitionedWriter> {
+        let writers = (0..self.paths.len()).map(|index| self.writer(index)).collect::<Result<Vec<_>>>()?;
+        Ok(PartitionedWriter { writers })
+    }
+
+    /// 读出第`index`个文件的全部记录
+    pub fn read<T: DeserializeOwned>(&self, index: usize) -> Result<Vec<T>> {
+        self.reader(index)?.collect()
+    }
+
+    /// 逐条读取第`index`个文件
+    pub fn reader<T: DeserializeOwned>(&self, index: usize) -> Result<SpillReader<T>> {
+        let file = File::open(&self.paths[index]).map_err(spill_error)?;
+        Ok(SpillReader { lines: BufReader::new(file).lines(), _item: PhantomData })
+    }
+
+    fn add_path(&mut self) -> usize {
+        let index = self.paths.len();
+        self.paths.push(self.dir.join(format!("{}-{}.jsonl", self.stem, index)));
+        index
+    }
+
+    fn writer(&self, index: usize) -> Result<BufWriter<File>> {
+        File::create(&self.paths[index]).map(BufWriter::new).map_err(spill_error)
+    }
+}
+
+impl Drop for SpillFiles {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// 按分区逐条追加记录的写入器，`finish`后文件内容才完整
+pub struct PartitionedWriter {
+    writers: Vec<BufWriter<File>>,
+}
+
+impl PartitionedWriter {
+    /// 把`item`追加到第`partition`个文件
+    pub fn write<T: Serialize>(&mut self, partition: usize, item: &T) -> Result<()> {
+        write_item(&mut self.writers[partition], item)
+    }
+
+    /// 刷新并关闭所有文件
+    pub fn finish(self) -> Result<()> {
+        for mut writer in self.writers {
+            writer.flush().map_err(spill_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// 落盘文件的逐条读取器
+pub struct SpillReader<T> {
+    lines: Lines<BufReader<File>>,
+    _item: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Iterator for SpillReader<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        Some(line.map_err(spill_error).and_then(|line| {
+            serde_json::from_str(&line)
+                .map_err(|e| Error::serialization(format!("Failed to read spilled record: {}", e)))
+        }))
+    }
+}
+
+fn write_item<T: Serialize>(writer: &mut BufWriter<File>, item: &T) -> Result<()> {
+    serde_json::to_writer(&mut *writer, item)
+        .map_err(|e| Error::serialization(format!("Failed to spill record: {}", e)))?;
+    writer.write_all(b"\n").map_err(spill_error)
+}
+
+fn spill_error(e: std::io::Error) -> Error {
+    Error::internal(format!("Spill I/O failed: {}", e))
+}
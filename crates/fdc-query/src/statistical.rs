@@ -0,0 +1,421 @@
+//! Statistical and approximate aggregates: dispersion, correlation, percentiles, distinct counts and histograms
+//!
+//! 所有状态都可以合并：方差与协方差按Chan的并行算法合并矩，精确分位数合并值列表，
+//! 近似聚合合并t-digest、HyperLogLog与流式直方图草图。
+
+use crate::{
+    expressions::{compare_values, value_as_f64, value_as_i64},
+    joins::normalize_key_value,
+    sketches::{HyperLogLog, StreamingHistogram, TDigest},
+};
+use fdc_core::{error::{Error, Result}, types::Value};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// `histogram(value, buckets)`允许的最大桶数
+pub const MAX_HISTOGRAM_BUCKETS: usize = 1000;
+
+/// 统计聚合的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatisticalKind {
+    /// `stddev(x)` / `stddev_samp(x)`：样本标准差
+    StddevSamp,
+    /// `stddev_pop(x)`：总体标准差
+    StddevPop,
+    /// `variance(x)` / `var_samp(x)`：样本方差
+    VarSamp,
+    /// `var_pop(x)`：总体方差
+    VarPop,
+    /// `corr(y, x)`：皮尔逊相关系数
+    Corr,
+    /// `covar(y, x)` / `covar_samp(y, x)`：样本协方差
+    CovarSamp,
+    /// `covar_pop(y, x)`：总体协方差
+    CovarPop,
+    /// `percentile_cont(x, p)`：精确连续分位数（线性插值）
+    PercentileCont,
+    /// `percentile_disc(x, p)`：精确离散分位数
+    PercentileDisc,
+    /// `approx_percentile_cont(x, p)`：基于t-digest的近似连续分位数
+    ApproxPercentileCont,
+    /// `approx_percentile_disc(x, p)`：基于t-digest的近似离散分位数
+    ApproxPercentileDisc,
+    /// `approx_count_distinct(x)`：基于HyperLogLog的近似不同值个数
+    ApproxCountDistinct,
+    /// `histogram(x, buckets)`：流式直方图
+    Histogram,
+}
+
+impl StatisticalKind {
+    /// 按SQL函数名（大写）查找
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "STDDEV" | "STDDEV_SAMP" => StatisticalKind::StddevSamp,
+            "STDDEV_POP" => StatisticalKind::StddevPop,
+            "VARIANCE" | "VAR_SAMP" => StatisticalKind::VarSamp,
+            "VAR_POP" => StatisticalKind::VarPop,
+            "CORR" => StatisticalKind::Corr,
+            "COVAR" | "COVAR_SAMP" => StatisticalKind::CovarSamp,
+            "COVAR_POP" => StatisticalKind::CovarPop,
+            "PERCENTILE_CONT" => StatisticalKind::PercentileCont,
+            "PERCENTILE_DISC" => StatisticalKind::PercentileDisc,
+            "APPROX_PERCENTILE_CONT" | "APPROX_PERCENTILE" => StatisticalKind::ApproxPercentileCont,
+            "APPROX_PERCENTILE_DISC" => StatisticalKind::ApproxPercentileDisc,
+            "APPROX_COUNT_DISTINCT" => StatisticalKind::ApproxCountDistinct,
+            "HISTOGRAM" => StatisticalKind::Histogram,
+            _ => return None,
+        })
+    }
+
+    /// SQL函数名
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatisticalKind::StddevSamp => "STDDEV_SAMP",
+            StatisticalKind::StddevPop => "STDDEV_POP",
+            StatisticalKind::VarSamp => "VAR_SAMP",
+            StatisticalKind::VarPop => "VAR_POP",
+            StatisticalKind::Corr => "CORR",
+            StatisticalKind::CovarSamp => "COVAR_SAMP",
+            StatisticalKind::CovarPop => "COVAR_POP",
+            StatisticalKind::PercentileCont => "PERCENTILE_CONT",
+            StatisticalKind::PercentileDisc => "PERCENTILE_DISC",
+            StatisticalKind::ApproxPercentileCont => "APPROX_PERCENTILE_CONT",
+            StatisticalKind::ApproxPercentileDisc => "APPROX_PERCENTILE_DISC",
+            StatisticalKind::ApproxCountDistinct => "APPROX_COUNT_DISTINCT",
+            StatisticalKind::Histogram => "HISTOGRAM",
+        }
+    }
+
+    /// 参数个数
+    pub fn arity(&self) -> usize {
+        match self {
+            StatisticalKind::StddevSamp | StatisticalKind::StddevPop | StatisticalKind::VarSamp
+            | StatisticalKind::VarPop | StatisticalKind::ApproxCountDistinct => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// 统计聚合的可合并状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatisticalState {
+    /// 方差与标准差：个数、均值与离差平方和
+    Moments { kind: StatisticalKind, count: u64, mean: f64, m2: f64 },
+    /// 相关与协方差：个数、两列均值、协离差和与各自的离差平方和
+    Comoments { kind: StatisticalKind, count: u64, mean_x: f64, mean_y: f64, c: f64, m2_x: f64, m2_y: f64 },
+    /// 精确分位数：分位点与全部输入值
+    Percentile { kind: StatisticalKind, fraction: Option<f64>, values: Vec<Value> },
+    /// 近似分位数：分位点与t-digest
+    Digest { kind: StatisticalKind, fraction: Option<f64>, digest: TDigest },
+    /// 近似不同值个数
+    Distinct(HyperLogLog),
+    /// 直方图（首个输入确定桶数）
+    Histogram(Option<StreamingHistogram>),
+}
+
+impl StatisticalState {
+    /// 空状态
+    pub fn new(kind: StatisticalKind) -> Self {
+        match kind {
+            StatisticalKind::StddevSamp | StatisticalKind::StddevPop | StatisticalKind::VarSamp | StatisticalKind::VarPop => {
+                StatisticalState::Moments { kind, count: 0, mean: 0.0, m2: 0.0 }
+            }
+            StatisticalKind::Corr | StatisticalKind::CovarSamp | StatisticalKind::CovarPop => StatisticalState::Comoments {
+                kind, count: 0, mean_x: 0.0, mean_y: 0.0, c: 0.0, m2_x: 0.0, m2_y: 0.0,
+            },
+            StatisticalKind::PercentileCont | StatisticalKind::PercentileDisc => {
+                StatisticalState::Percentile { kind, fraction: None, values: Vec::new() }
+            }
+            StatisticalKind::ApproxPercentileCont | StatisticalKind::ApproxPercentileDisc => {
+                StatisticalState::Digest { kind, fraction: None, digest: TDigest::default() }
+            }
+            StatisticalKind::ApproxCountDistinct => StatisticalState::Distinct(HyperLogLog::new()),
+            StatisticalKind::Histogram => StatisticalState::Histogram(None),
+        }
+    }
+
+    /// 在一组非NULL输入上计算聚合
+    pub fn evaluate(kind: StatisticalKind, inputs: &[Value]) -> Result<Value> {
+        let mut state = Self::new(kind);
+        for input in inputs {
+            state.update(input)?;
+        }
+        Ok(state.finish())
+    }
+
+    /// 累加一个输入（双参数聚合的输入为参数数组），NULL被忽略
+    pub fn update(&mut self, input: &Value) -> Result<()> {
+        if matches!(input, Value::Null) {
+            return Ok(());
+        }
+        match self {
+            StatisticalState::Moments { kind, count, mean, m2 } => {
+                let x = number(input, *kind)?;
+                *count += 1;
+                let delta = x - *mean;
+                *mean += delta / *count as f64;
+                *m2 += delta * (x - *mean);
+            }
+            StatisticalState::Comoments { kind, count, mean_x, mean_y, c, m2_x, m2_y } => {
+                let [y, x] = pair(input, *kind)?;
+                let (y, x) = (number(y, *kind)?, number(x, *kind)?);
+                *count += 1;
+                let n = *count as f64;
+                let dx = x - *mean_x;
+                let dy = y - *mean_y;
+                *mean_x += dx / n;
+                *mean_y += dy / n;
+                *c += dx * (y - *mean_y);
+                *m2_x += dx * (x - *mean_x);
+                *m2_y += dy * (y - *mean_y);
+            }
+            StatisticalState::Percentile { kind, fraction, values } => {
+                let [value, p] = pair(input, *kind)?;
+                set_fraction(fraction, p, *kind)?;
+                number(value, *kind)?;
+                values.push(value.clone());
+            }
+            StatisticalState::Digest { kind, fraction, digest } => {
+                let [value, p] = pair(input, *kind)?;
+                set_fraction(fraction, p, *kind)?;
+                digest.add(number(value, *kind)?);
+            }
+            StatisticalState::Distinct(sketch) => sketch.add(normalize_key_value(input)),
+            StatisticalState::Histogram(histogram) => {
+                let [value, buckets] = pair(input, StatisticalKind::Histogram)?;
+                let value = number(value, StatisticalKind::Histogram)?;
+                match histogram {
+                    Some(histogram) => histogram.add(value),
+                    None => {
+                        let buckets = value_as_i64(buckets)
+                            .filter(|b| (1..=MAX_HISTOGRAM_BUCKETS as i64).contains(b))
+                            .ok_or_else(|| Error::validation(format!(
+                                "HISTOGRAM buckets must be an integer between 1 and {}", MAX_HISTOGRAM_BUCKETS
+                            )))?;
+                        let mut created = StreamingHistogram::new(buckets as usize);
+                        created.add(value);
+                        *histogram = Some(created);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 合并另一个同类状态
+    pub fn merge(&mut self, other: &StatisticalState) -> Result<()> {
+        match (self, other) {
+            (
+                StatisticalState::Moments { kind, count, mean, m2 },
+                StatisticalState::Moments { kind: other_kind, count: other_count, mean: other_mean, m2: other_m2 },
+            ) if kind == other_kind => {
+                if *other_count == 0 {
+                    return Ok(());
+                }
+                let (na, nb) = (*count as f64, *other_count as f64);
+                let n = na + nb;
+                let delta = other_mean - *mean;
+                *mean += delta * nb / n;
+                *m2 += other_m2 + delta * delta * na * nb / n;
+                *count += other_count;
+            }
+            (
+                StatisticalState::Comoments { kind, count, mean_x, mean_y, c, m2_x, m2_y },
+                StatisticalState::Comoments {
+                    kind: other_kind, count: other_count, mean_x: other_mean_x, mean_y: other_mean_y,
+                    c: other_c, m2_x: other_m2_x, m2_y: other_m2_y,
+                },
+            ) if kind == other_kind => {
+                if *other_count == 0 {
+                    return Ok(());
+                }
+                let (na, nb) = (*count as f64, *other_count as f64);
+                let n = na + nb;
+                let dx = other_mean_x - *mean_x;
+                let dy = other_mean_y - *mean_y;
+                *c += other_c + dx * dy * na * nb / n;
+                *m2_x += other_m2_x + dx * dx * na * nb / n;
+                *m2_y += other_m2_y + dy * dy * na * nb / n;
+                *mean_x += dx * nb / n;
+                *mean_y += dy * nb / n;
+                *count += other_count;
+            }
+            (
+                StatisticalState::Percentile { kind, fraction, values },
+                StatisticalState::Percentile { kind: other_kind, fraction: other_fraction, values: other_values },
+            ) if kind == other_kind => {
+                *fraction = fraction.or(*other_fraction);
+                values.extend(other_values.iter().cloned());
+            }
+            (
+                StatisticalState::Digest { kind, fraction, digest },
+                StatisticalState::Digest { kind: other_kind, fraction: other_fraction, digest: other_digest },
+            ) if kind == other_kind => {
+                *fraction = fraction.or(*other_fraction);
+                digest.merge(other_digest);
+            }
+            (StatisticalState::Distinct(sketch), StatisticalState::Distinct(other)) => sketch.merge(other),
+            (StatisticalState::Histogram(histogram), StatisticalState::Histogram(other)) => match (histogram.as_mut(), other) {
+                (Some(histogram), Some(other)) => histogram.merge(other),
+                (None, Some(other)) => *histogram = Some(other.clone()),
+                (_, None) => {}
+            },
+            (current, other) => {
+                return Err(Error::validation(format!("Cannot merge aggregate states {:?} and {:?}", current, other)));
+            }
+        }
+        Ok(())
+    }
+
+    /// 当前的聚合结果，输入不足时为NULL（近似不同值个数为0）
+    pub fn finish(&self) -> Value {
+        match self {
+            StatisticalState::Moments { kind, count, m2, .. } => {
+                let divisor = match kind {
+                    StatisticalKind::VarSamp | StatisticalKind::StddevSamp => count.saturating_sub(1),
+                    _ => *count,
+                };
+                if divisor == 0 {
+                    return Value::Null;
+                }
+                let variance = (m2 / divisor as f64).max(0.0);
+                Value::Float64(match kind {
+                    StatisticalKind::StddevSamp | StatisticalKind::StddevPop => variance.sqrt(),
+                    _ => variance,
+                })
+            }
+            StatisticalState::Comoments { kind, count, c, m2_x, m2_y, .. } => match kind {
+                StatisticalKind::Corr if *count < 2 || *m2_x == 0.0 || *m2_y == 0.0 => Value::Null,
+                StatisticalKind::Corr => Value::Float64(c / (m2_x * m2_y).sqrt()),
+                StatisticalKind::CovarSamp if *count < 2 => Value::Null,
+                StatisticalKind::CovarSamp => Value::Float64(c / (*count - 1) as f64),
+                _ if *count == 0 => Value::Null,
+                _ => Value::Float64(c / *count as f64),
+            },
+            StatisticalState::Percentile { kind, fraction, values } => {
+                let Some(p) = fraction.filter(|_| !values.is_empty()) else {
+                    return Value::Null;
+                };
+                let mut sorted: Vec<&Value> = values.iter().collect();
+                sorted.sort_by(|a, b| compare_values(a, b).unwrap_or(Ordering::Equal));
+                if *kind == StatisticalKind::PercentileDisc {
+                    // 累计分布首次不小于p的值
+                    let index = ((p * sorted.len() as f64).ceil() as usize).saturating_sub(1);
+                    return sorted[index.min(sorted.len() - 1)].clone();
+                }
+                let position = p * (sorted.len() - 1) as f64;
+                let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+                let (low, high) = (value_as_f64(sorted[lower]), value_as_f64(sorted[upper]));
+                match (low, high) {
+                    (Some(low), Some(high)) => Value::Float64(low + (high - low) * (position - lower as f64)),
+                    _ => Value::Null,
+                }
+            }
+            StatisticalState::Digest { kind, fraction, digest } => {
+                let quantile = fraction.and_then(|p| match kind {
+                    StatisticalKind::ApproxPercentileDisc => digest.quantile_disc(p),
+                    _ => digest.quantile(p),
+                });
+                quantile.map(Value::Float64).unwrap_or(Value::Null)
+            }
+            StatisticalState::Distinct(sketch) => Value::Int64(sketch.estimate() as i64),
+            StatisticalState::Histogram(None) => Value::Null,
+            StatisticalState::Histogram(Some(histogram)) => Value::Array(
+                histogram.bins().iter()
+                    .map(|&(center, count)| Value::Struct(HashMap::from([
+                        ("value".to_string(), Value::Float64(center)),
+                        ("count".to_string(), Value::Int64(count as i64)),
+                    ])))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// 取出双参数聚合的两个参数
+fn pair(input: &Value, kind: StatisticalKind) -> Result<[&Value; 2]> {
+    match input {
+        Value::Array(values) if values.len() == 2 => Ok([&values[0], &values[1]]),
+        _ => Err(Error::validation(format!("{} takes exactly 2 arguments", kind.name()))),
+    }
+}
+
+fn number(value: &Value, kind: StatisticalKind) -> Result<f64> {
+    value_as_f64(value).ok_or_else(|| Error::validation(format!("{} requires numeric values", kind.name())))
+}
+
+/// 记录分位点（取首个输入的值，必须在0到1之间）
+fn set_fraction(fraction: &mut Option<f64>, value: &Value, kind: StatisticalKind) -> Result<()> {
+    if fraction.is_none() {
+        let p = value_as_f64(value)
+            .filter(|p| (0.0..=1.0).contains(p))
+            .ok_or_else(|| Error::validation(format!("{} requires a fraction between 0 and 1", kind.name())))?;
+        *fraction = Some(p);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(kind: StatisticalKind, inputs: &[Value]) -> Value {
+        StatisticalState::evaluate(kind, inputs).unwrap()
+    }
+
+    fn with(values: &[f64], second: f64) -> Vec<Value> {
+        values.iter().map(|&v| Value::Array(vec![Value::Float64(v), Value::Float64(second)])).collect()
+    }
+
+    #[test]
+    fn test_dispersion_and_correlation() {
+        let values: Vec<Value> = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].into_iter().map(Value::Float64).collect();
+        assert_eq!(evaluate(StatisticalKind::VarPop, &values), Value::Float64(4.0));
+        assert_eq!(evaluate(StatisticalKind::StddevPop, &values), Value::Float64(2.0));
+        assert_eq!(evaluate(StatisticalKind::VarSamp, &values[..1]), Value::Null);
+
+        let pairs: Vec<Value> = [(2.0, 1.0), (4.0, 2.0), (6.0, 3.0)].into_iter()
+            .map(|(y, x)| Value::Array(vec![Value::Float64(y), Value::Float64(x)]))
+            .collect();
+        assert_eq!(evaluate(StatisticalKind::Corr, &pairs), Value::Float64(1.0));
+        assert_eq!(evaluate(StatisticalKind::CovarSamp, &pairs), Value::Float64(2.0));
+    }
+
+    #[test]
+    fn test_exact_percentiles() {
+        let inputs = with(&[4.0, 1.0, 3.0, 2.0], 0.5);
+        assert_eq!(evaluate(StatisticalKind::PercentileCont, &inputs), Value::Float64(2.5));
+        assert_eq!(evaluate(StatisticalKind::PercentileDisc, &inputs), Value::Float64(2.0));
+        assert!(StatisticalState::evaluate(StatisticalKind::PercentileCont, &with(&[1.0], 1.5)).is_err());
+    }
+
+    #[test]
+    fn test_merge_statistical_states() {
+        let values: Vec<f64> = (1..=100).map(|i| (i * i % 37) as f64).collect();
+        for kind in [StatisticalKind::VarSamp, StatisticalKind::StddevPop] {
+            let inputs: Vec<Value> = values.iter().copied().map(Value::Float64).collect();
+            let (mut left, mut right) = (StatisticalState::new(kind), StatisticalState::new(kind));
+            inputs[..30].iter().for_each(|v| left.update(v).unwrap());
+            inputs[30..].iter().for_each(|v| right.update(v).unwrap());
+            left.merge(&right).unwrap();
+            let (Some(merged), Some(whole)) = (value_as_f64(&left.finish()), value_as_f64(&evaluate(kind, &inputs))) else {
+                panic!("{:?} should be numeric", kind)
+            };
+            assert!((merged - whole).abs() < 1e-9, "{:?}: {} != {}", kind, merged, whole);
+        }
+
+        let inputs = with(&values, 0.9);
+        let (mut left, mut right) = (StatisticalState::new(StatisticalKind::ApproxPercentileCont), StatisticalState::new(StatisticalKind::ApproxPercentileCont));
+        inputs[..50].iter().for_each(|v| left.update(v).unwrap());
+        inputs[50..].iter().for_each(|v| right.update(v).unwrap());
+        left.merge(&right).unwrap();
+        let approx = value_as_f64(&left.finish()).unwrap();
+        let exact = value_as_f64(&evaluate(StatisticalKind::PercentileCont, &inputs)).unwrap();
+        assert!((approx - exact).abs() <= 1.0, "{} vs {}", approx, exact);
+
+        let symbols: Vec<Value> = ["AAPL", "MSFT", "AAPL", "TSLA"].into_iter().map(|s| Value::String(s.to_string())).collect();
+        assert_eq!(evaluate(StatisticalKind::ApproxCountDistinct, &symbols), Value::Int64(3));
+        assert!(StatisticalState::new(StatisticalKind::VarPop).merge(&StatisticalState::new(StatisticalKind::VarSamp)).is_err());
+    }
+}
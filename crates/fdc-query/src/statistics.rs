@@ -0,0 +1,325 @@
+//! Table and column statistics collected by ANALYZE
+
+use crate::{
+    catalog::TableDefinition,
+    expressions::{compare_values, value_as_f64},
+    joins::estimate_row_size,
+};
+use fdc_core::types::{TimestampNs, Value};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 默认直方图桶数
+pub const DEFAULT_HISTOGRAM_BUCKETS: usize = 32;
+
+/// 未收集统计信息时假定的表行数
+pub const DEFAULT_ROW_COUNT: u64 = 1000;
+
+/// 无法从统计信息推断时的等值选择率
+pub const DEFAULT_EQUALITY_SELECTIVITY: f64 = 0.1;
+
+/// 无法从统计信息推断时的范围及其他谓词选择率
+pub const DEFAULT_RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+
+/// 等深直方图桶：每个桶包含数量大致相同的非空值，相同的值不会跨桶
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    /// 桶内最小值
+    pub lower: Value,
+    /// 桶内最大值
+    pub upper: Value,
+    /// 桶内值数量
+    pub count: u64,
+    /// 桶内不同值数量
+    pub distinct: u64,
+}
+
+/// 列统计信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnStatistics {
+    /// 不同值数量（NDV）
+    pub distinct_count: u64,
+    /// 空值数量
+    pub null_count: u64,
+    /// 最小值
+    pub min: Option<Value>,
+    /// 最大值
+    pub max: Option<Value>,
+    /// 等深直方图
+    pub histogram: Vec<HistogramBucket>,
+}
+
+impl ColumnStatistics {
+    /// 从列值计算统计信息
+    pub fn compute<'a>(values: impl IntoIterator<Item = &'a Value>, buckets: usize) -> Self {
+        let mut null_count = 0;
+        let mut sorted = Vec::new();
+        for value in values {
+            match value {
+                Value::Null => null_count += 1,
+                other => sorted.push(other),
+            }
+        }
+        sorted.sort_by(|a, b| compare_values(a, b).unwrap_or(Ordering::Equal));
+
+        let distinct_count = count_distinct(&sorted);
+        let histogram = build_histogram(&sorted, buckets.max(1));
+        Self {
+            distinct_count,
+            null_count,
+            min: sorted.first().map(|v| (*v).clone()),
+            max: sorted.last().map(|v| (*v).clone()),
+            histogram,
+        }
+    }
+
+    /// 非空值数量
+    pub fn non_null_count(&self) -> u64 {
+        self.histogram.iter().map(|b| b.count).sum()
+    }
+
+    /// `列 = 值`的选择率（相对于表的全部行）
+    pub fn equality_selectivity(&self, value: &Value, row_count: u64) -> f64 {
+        if row_count == 0 {
+            return 0.0;
+        }
+        if matches!(value, Value::Null) {
+            return 0.0;
+        }
+        let matched = match self.histogram.iter().find(|b| in_bucket(value, b)) {
+            Some(bucket) => bucket.count as f64 / bucket.distinct.max(1) as f64,
+            None if self.histogram.is_empty() => return DEFAULT_EQUALITY_SELECTIVITY,
+            // 不在任何桶内的值不存在
+            None => 0.0,
+        };
+        (matched / row_count as f64).min(1.0)
+    }
+
+    /// 范围谓词的选择率，`lower`/`upper`为`(值, 是否包含)`
+    pub fn range_selectivity(
+        &self,
+        lower: Option<(&Value, bool)>,
+        upper: Option<(&Value, bool)>,
+        row_count: u64,
+    ) -> f64 {
+        if row_count == 0 {
+            return 0.0;
+        }
+        if self.histogram.is_empty() {
+            return DEFAULT_RANGE_SELECTIVITY;
+        }
+        let matched: f64 = self.histogram.iter()
+            .map(|bucket| bucket.count as f64 * bucket_overlap(bucket, lower, upper))
+            .sum();
+        (matched / row_count as f64).clamp(0.0, 1.0)
+    }
+
+    /// 与范围相交的桶所含值的比例（用于按时间范围剪枝）
+    pub fn overlapping_fraction(&self, lower: Option<(&Value, bool)>, upper: Option<(&Value, bool)>) -> f64 {
+        let total = self.non_null_count();
+        if total == 0 {
+            return 1.0;
+        }
+        let overlapping: u64 = self.histogram.iter()
+            .filter(|bucket| bucket_overlap(bucket, lower, upper) > 0.0)
+            .map(|bucket| bucket.count)
+            .sum();
+        overlapping as f64 / total as f64
+    }
+}
+
+/// 表统计信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableStatistics {
+    /// 表名
+    pub table: String,
+    /// 行数
+    pub row_count: u64,
+    /// 平均行大小（字节）
+    pub avg_row_size: u64,
+    /// 各列统计信息
+    pub columns: HashMap<String, ColumnStatistics>,
+    /// 收集时间
+    pub analyzed_at: TimestampNs,
+}
+
+impl TableStatistics {
+    /// 从表的全部行计算统计信息
+    pub fn compute(definition: &TableDefinition, rows: &[HashMap<String, Value>], buckets: usize) -> Self {
+        let columns = definition.columns.iter()
+            .map(|column| {
+                let values = rows.iter().map(|row| row.get(&column.name).unwrap_or(&Value::Null));
+                (column.name.to_lowercase(), ColumnStatistics::compute(values, buckets))
+            })
+            .collect();
+        let avg_row_size = if rows.is_empty() {
+            0
+        } else {
+            (rows.iter().map(estimate_row_size).sum::<usize>() / rows.len()) as u64
+        };
+        Self {
+            table: definition.name.clone(),
+            row_count: rows.len() as u64,
+            avg_row_size,
+            columns,
+            analyzed_at: TimestampNs::now(),
+        }
+    }
+
+    /// 获取列统计信息
+    pub fn column(&self, name: &str) -> Option<&ColumnStatistics> {
+        self.columns.get(&name.to_lowercase())
+    }
+
+    /// 列的不同值数量，未知时按唯一列处理
+    pub fn distinct_count(&self, column: &str) -> u64 {
+        self.column(column).map(|c| c.distinct_count).unwrap_or(self.row_count).max(1)
+    }
+}
+
+/// `ANALYZE [TABLE] [name]`命令，未指定表时分析目录中的所有表
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalyzeCommand {
+    /// 要分析的表
+    pub table: Option<String>,
+}
+
+impl AnalyzeCommand {
+    /// 识别ANALYZE命令，其他语句返回None
+    pub fn parse(sql: &str) -> Option<Self> {
+        static ANALYZE: OnceLock<Regex> = OnceLock::new();
+        let pattern = ANALYZE.get_or_init(|| {
+            Regex::new(r"(?i)^\s*ANALYZE(?:\s+TABLE)?(?:\s+([A-Za-z_][A-Za-z0-9_.]*))?\s*;?\s*$").expect("valid regex")
+        });
+        let captures = pattern.captures(sql)?;
+        Some(Self {
+            table: captures.get(1).map(|m| m.as_str().to_string()),
+        })
+    }
+}
+
+fn count_distinct(sorted: &[&Value]) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    1 + sorted.windows(2)
+        .filter(|pair| compare_values(pair[0], pair[1]) != Some(Ordering::Equal))
+        .count() as u64
+}
+
+/// 构建等深直方图
+fn build_histogram(sorted: &[&Value], buckets: usize) -> Vec<HistogramBucket> {
+    let depth = sorted.len().div_ceil(buckets).max(1);
+    let mut histogram = Vec::new();
+    let mut start = 0;
+    while start < sorted.len() {
+        let mut end = (start + depth).min(sorted.len());
+        // 相同的值留在同一个桶里
+        while end < sorted.len() && compare_values(sorted[end - 1], sorted[end]) == Some(Ordering::Equal) {
+            end += 1;
+        }
+        let slice = &sorted[start..end];
+        histogram.push(HistogramBucket {
+            lower: slice[0].clone(),
+            upper: slice[slice.len() - 1].clone(),
+            count: slice.len() as u64,
+            distinct: count_distinct(slice),
+        });
+        start = end;
+    }
+    histogram
+}
+
+fn in_bucket(value: &Value, bucket: &HistogramBucket) -> bool {
+    matches!(compare_values(value, &bucket.lower), Some(Ordering::Greater | Ordering::Equal))
+        && matches!(compare_values(value, &bucket.upper), Some(Ordering::Less | Ordering::Equal))
+}
+
+/// 桶落在范围内的比例，数值与时间类型在桶内线性插值
+fn bucket_overlap(bucket: &HistogramBucket, lower: Option<(&Value, bool)>, upper: Option<(&Value, bool)>) -> f64 {
+    if let Some((low, inclusive)) = lower {
+        match compare_values(&bucket.upper, low) {
+            Some(Ordering::Less) => return 0.0,
+            Some(Ordering::Equal) if !inclusive => return 0.0,
+            _ => {}
+        }
+    }
+    if let Some((high, inclusive)) = upper {
+        match compare_values(&bucket.lower, high) {
+            Some(Ordering::Greater) => return 0.0,
+            Some(Ordering::Equal) if !inclusive => return 0.0,
+            _ => {}
+        }
+    }
+
+    let (Some(start), Some(end)) = (position(&bucket.lower), position(&bucket.upper)) else {
+        // 不可插值的类型：部分相交的桶按一半计算
+        let covers_lower = lower.map_or(true, |(low, _)| compare_values(&bucket.lower, low) != Some(Ordering::Less));
+        let covers_upper = upper.map_or(true, |(high, _)| compare_values(&bucket.upper, high) != Some(Ordering::Greater));
+        return if covers_lower && covers_upper { 1.0 } else { 0.5 };
+    };
+    if end <= start {
+        return 1.0;
+    }
+    let low = lower.and_then(|(v, _)| position(v)).map_or(start, |v| v.max(start));
+    let high = upper.and_then(|(v, _)| position(v)).map_or(end, |v| v.min(end));
+    ((high - low) / (end - start)).clamp(0.0, 1.0)
+}
+
+/// 值在数轴上的位置
+fn position(value: &Value) -> Option<f64> {
+    match value {
+        Value::Timestamp(ts) => Some(ts.as_nanos() as f64),
+        Value::String(_) | Value::Symbol(_) | Value::Binary(_) | Value::Bool(_) => None,
+        other => value_as_f64(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equi_depth_histogram() {
+        let values: Vec<Value> = (0..100).map(|i| Value::Int64(i / 2)).chain([Value::Null]).collect();
+        let stats = ColumnStatistics::compute(&values, 10);
+
+        assert_eq!(stats.distinct_count, 50);
+        assert_eq!(stats.null_count, 1);
+        assert_eq!(stats.min, Some(Value::Int64(0)));
+        assert_eq!(stats.max, Some(Value::Int64(49)));
+        assert_eq!(stats.histogram.len(), 10);
+        assert!(stats.histogram.iter().all(|b| b.count == 10 && b.distinct == 5));
+
+        // 每个值出现两次
+        assert!((stats.equality_selectivity(&Value::Int64(7), 101) - 2.0 / 101.0).abs() < 1e-9);
+        assert_eq!(stats.equality_selectivity(&Value::Int64(500), 101), 0.0);
+
+        let below_ten = stats.range_selectivity(None, Some((&Value::Int64(10), false)), 100);
+        assert!((below_ten - 0.2).abs() < 0.05, "{}", below_ten);
+        assert!((stats.overlapping_fraction(Some((&Value::Int64(45), true)), None) - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_skewed_values_stay_in_one_bucket() {
+        let values: Vec<Value> = std::iter::repeat(Value::Int64(1)).take(90)
+            .chain((2..12).map(Value::Int64))
+            .collect();
+        let stats = ColumnStatistics::compute(&values, 4);
+
+        assert_eq!(stats.histogram[0].count, 90);
+        assert!(stats.equality_selectivity(&Value::Int64(1), 100) > 0.8);
+        assert!(stats.equality_selectivity(&Value::Int64(5), 100) < 0.05);
+    }
+
+    #[test]
+    fn test_analyze_command() {
+        assert_eq!(AnalyzeCommand::parse("ANALYZE").unwrap().table, None);
+        assert_eq!(AnalyzeCommand::parse("analyze table trades;").unwrap().table.as_deref(), Some("trades"));
+        assert_eq!(AnalyzeCommand::parse("ANALYZE trades").unwrap().table.as_deref(), Some("trades"));
+        assert!(AnalyzeCommand::parse("SELECT * FROM trades").is_none());
+    }
+}
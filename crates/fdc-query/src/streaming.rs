@@ -0,0 +1,503 @@
+//! Streaming result batches with backpressure and named server-side cursors
+
+use crate::cancellation::CancellationToken;
+use dashmap::DashMap;
+use fdc_core::{error::{Error, Result}, types::Value};
+use futures::Stream;
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// 默认每批行数
+pub const DEFAULT_STREAM_BATCH_SIZE: usize = 1024;
+
+/// 生产者最多领先消费者的批次数
+pub const STREAM_CHANNEL_CAPACITY: usize = 4;
+
+/// 默认游标空闲超时（5分钟）
+pub const DEFAULT_CURSOR_IDLE_TIMEOUT_SECS: u64 = 300;
+
+/// 结果行
+pub type Row = HashMap<String, Value>;
+
+/// 一批结果行
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowBatch {
+    /// 批次序号，从0开始
+    pub sequence: u64,
+    /// 本批的行
+    pub rows: Vec<Row>,
+}
+
+/// 行批次流
+///
+/// 由有界通道支撑：消费者不再拉取时通道写满，生产者在`send`处挂起，扫描随之暂停，
+/// 内存中至多缓存`STREAM_CHANNEL_CAPACITY`批。流被丢弃时取消底层查询。
+pub struct RowStream {
+    query_id: String,
+    receiver: mpsc::Receiver<Result<RowBatch>>,
+    cancellation: CancellationToken,
+}
+
+impl RowStream {
+    /// 创建一对批次发送端与行批次流
+    pub fn channel(query_id: impl Into<String>, cancellation: CancellationToken, capacity: usize) -> (BatchSender, Self) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let stream = Self {
+            query_id: query_id.into(),
+            receiver,
+            cancellation,
+        };
+        (BatchSender { sender, sequence: 0, rows_sent: 0 }, stream)
+    }
+
+    /// 产出结果的查询ID
+    pub fn query_id(&self) -> &str {
+        &self.query_id
+    }
+
+    /// 取下一批；流结束返回None
+    pub async fn next_batch(&mut self) -> Option<Result<RowBatch>> {
+        self.receiver.recv().await
+    }
+
+    /// 读完剩余的所有批次并拼接成行
+    pub async fn collect_rows(mut self) -> Result<Vec<Row>> {
+        let mut rows = Vec::new();
+        while let Some(batch) = self.next_batch().await {
+            rows.extend(batch?.rows);
+        }
+        Ok(rows)
+    }
+
+    /// 取消底层查询
+    pub fn cancel(&self) {
+        self.cancellation.cancel("result stream closed");
+    }
+}
+
+impl Stream for RowStream {
+    type Item = Result<RowBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for RowStream {
+    fn drop(&mut self) {
+        // 消费者提前放弃时让生产者尽快停止扫描
+        self.cancel();
+    }
+}
+
+impl std::fmt::Debug for RowStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RowStream").field("query_id", &self.query_id).finish()
+    }
+}
+
+/// 行批次发送端
+#[derive(Debug)]
+pub struct BatchSender {
+    sender: mpsc::Sender<Result<RowBatch>>,
+    sequence: u64,
+    rows_sent: u64,
+}
+
+impl BatchSender {
+    /// 发送一批行，通道满时等待消费者；消费者已关闭返回false
+    pub async fn send(&mut self, rows: Vec<Row>) -> bool {
+        if rows.is_empty() {
+            return !self.sender.is_closed();
+        }
+        self.rows_sent += rows.len() as u64;
+        let batch = RowBatch { sequence: self.sequence, rows };
+        self.sequence += 1;
+        self.sender.send(Ok(batch)).await.is_ok()
+    }
+
+    /// 把已物化的结果按`batch_size`切分后逐批发送；消费者关闭返回false
+    pub async fn send_all(&mut self, rows: Vec<Row>, batch_size: usize) -> bool {
+        let mut rows = rows.into_iter().peekable();
+        while rows.peek().is_some() {
+            let batch: Vec<Row> = rows.by_ref().take(batch_size.max(1)).collect();
+            if !self.send(batch).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 把错误作为流的最后一项交给消费者
+    pub async fn fail(&self, error: Error) {
+        let _ = self.sender.send(Err(error)).await;
+    }
+
+    /// 消费者是否已关闭
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// 已交给通道的行数
+    pub fn rows_sent(&self) -> u64 {
+        self.rows_sent
+    }
+}
+
+/// FETCH的行数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchCount {
+    /// `FETCH NEXT` / 省略行数
+    Next,
+    /// `FETCH n` / `FETCH FORWARD n`
+    Count(usize),
+    /// `FETCH ALL` / `FETCH FORWARD ALL`
+    All,
+}
+
+impl FetchCount {
+    /// 本次最多读取的行数，None表示读到结束
+    pub fn limit(&self) -> Option<usize> {
+        match self {
+            Self::Next => Some(1),
+            Self::Count(count) => Some(*count),
+            Self::All => None,
+        }
+    }
+}
+
+/// SQL级的游标命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CursorCommand {
+    /// `DECLARE name CURSOR FOR <select>`
+    Declare {
+        /// 游标名（小写）
+        name: String,
+        /// 游标对应的查询
+        sql: String,
+    },
+    /// `FETCH [NEXT | n | FORWARD n | ALL] FROM name`
+    Fetch {
+        /// 游标名（小写）
+        name: String,
+        /// 读取行数
+        count: FetchCount,
+    },
+    /// `CLOSE name` / `CLOSE ALL`，None表示关闭会话的所有游标
+    Close {
+        /// 游标名（小写）
+        name: Option<String>,
+    },
+}
+
+impl CursorCommand {
+    /// 识别DECLARE / FETCH / CLOSE，其他语句返回None
+    pub fn parse(sql: &str) -> Result<Option<Self>> {
+        static DECLARE: OnceLock<Regex> = OnceLock::new();
+        static FETCH: OnceLock<Regex> = OnceLock::new();
+        static CLOSE: OnceLock<Regex> = OnceLock::new();
+        let declare = DECLARE.get_or_init(|| {
+            Regex::new(
+                r"(?is)^\s*DECLARE\s+([A-Za-z_][A-Za-z0-9_]*)\s+(?:NO\s+SCROLL\s+)?CURSOR\s+(?:WITH(?:OUT)?\s+HOLD\s+)?FOR\s+(.+?)\s*;?\s*$",
+            ).expect("valid regex")
+        });
+        let fetch = FETCH.get_or_init(|| {
+            Regex::new(
+                r"(?i)^\s*FETCH\s+(?:(NEXT|ALL|FORWARD\s+ALL|FORWARD\s+\d+|FORWARD|\d+)\s+)?(?:FROM|IN)\s+([A-Za-z_][A-Za-z0-9_]*)\s*;?\s*$",
+            ).expect("valid regex")
+        });
+        let close = CLOSE.get_or_init(|| {
+            Regex::new(r"(?i)^\s*CLOSE\s+([A-Za-z_][A-Za-z0-9_]*)\s*;?\s*$").expect("valid regex")
+        });
+
+        if let Some(captures) = declare.captures(sql) {
+            let sql = captures[2].to_string();
+            if !sql.trim_start().to_uppercase().starts_with("SELECT") && !sql.trim_start().to_uppercase().starts_with("WITH") {
+                return Err(Error::validation("DECLARE CURSOR requires a SELECT query"));
+            }
+            return Ok(Some(Self::Declare { name: captures[1].to_lowercase(), sql }));
+        }
+        if let Some(captures) = fetch.captures(sql) {
+            let direction = captures.get(1).map(|m| m.as_str().to_uppercase()).unwrap_or_default();
+            let words: Vec<&str> = direction.split_whitespace().collect();
+            let count = match words.as_slice() {
+                [] | ["NEXT"] | ["FORWARD"] => FetchCount::Next,
+                ["ALL"] | ["FORWARD", "ALL"] => FetchCount::All,
+                [count] | ["FORWARD", count] => FetchCount::Count(
+                    count.parse().map_err(|_| Error::validation(format!("Invalid FETCH count: {}", count)))?,
+                ),
+                _ => return Err(Error::validation(format!("Invalid FETCH direction: {}", direction))),
+            };
+            return Ok(Some(Self::Fetch { name: captures[2].to_lowercase(), count }));
+        }
+        if let Some(captures) = close.captures(sql) {
+            let name = captures[1].to_lowercase();
+            return Ok(Some(Self::Close { name: (name != "all").then_some(name) }));
+        }
+        Ok(None)
+    }
+}
+
+/// 游标信息
+#[derive(Debug, Clone)]
+pub struct CursorInfo {
+    /// 游标名
+    pub name: String,
+    /// 所属会话
+    pub session_id: Option<String>,
+    /// 游标对应的查询
+    pub sql: String,
+    /// 已读取的行数
+    pub fetched: u64,
+    /// 是否已读完
+    pub exhausted: bool,
+    /// 距上次访问的时长
+    pub idle: Duration,
+}
+
+/// 服务端游标：按需从行批次流拉取，多余的行暂存到下次FETCH
+#[derive(Debug)]
+pub struct Cursor {
+    sql: String,
+    stream: RowStream,
+    buffer: VecDeque<Row>,
+    fetched: u64,
+    exhausted: bool,
+}
+
+impl Cursor {
+    /// 在行批次流上创建游标
+    pub fn new(sql: impl Into<String>, stream: RowStream) -> Self {
+        Self {
+            sql: sql.into(),
+            stream,
+            buffer: VecDeque::new(),
+            fetched: 0,
+            exhausted: false,
+        }
+    }
+
+    /// 读取至多`limit`行（None读到结束），游标读完后返回空
+    pub async fn fetch(&mut self, limit: Option<usize>) -> Result<Vec<Row>> {
+        let wanted = limit.unwrap_or(usize::MAX);
+        while self.buffer.len() < wanted && !self.exhausted {
+            match self.stream.next_batch().await {
+                Some(batch) => self.buffer.extend(batch?.rows),
+                None => self.exhausted = true,
+            }
+        }
+        let take = wanted.min(self.buffer.len());
+        let rows: Vec<Row> = self.buffer.drain(..take).collect();
+        self.fetched += rows.len() as u64;
+        Ok(rows)
+    }
+
+    /// 是否已无更多行
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted && self.buffer.is_empty()
+    }
+}
+
+struct CursorEntry {
+    name: String,
+    session_id: Option<String>,
+    last_access: parking_lot::Mutex<Instant>,
+    cursor: tokio::sync::Mutex<Cursor>,
+}
+
+/// 游标注册表
+///
+/// 游标按会话隔离，不同会话可以使用同名游标。超过空闲时间未访问的游标在下次
+/// DECLARE/FETCH时被回收，底层查询随之取消。
+pub struct CursorRegistry {
+    cursors: DashMap<String, Arc<CursorEntry>>,
+    idle_timeout: Duration,
+}
+
+impl CursorRegistry {
+    /// 创建游标注册表
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            cursors: DashMap::new(),
+            idle_timeout,
+        }
+    }
+
+    fn key(session_id: Option<&str>, name: &str) -> String {
+        format!("{}\u{1f}{}", session_id.unwrap_or_default(), name.to_lowercase())
+    }
+
+    /// 登记游标，会话内同名游标已存在时报错
+    pub fn declare(&self, session_id: Option<&str>, name: &str, cursor: Cursor) -> Result<()> {
+        self.purge_idle();
+        let entry = Arc::new(CursorEntry {
+            name: name.to_lowercase(),
+            session_id: session_id.map(str::to_string),
+            last_access: parking_lot::Mutex::new(Instant::now()),
+            cursor: tokio::sync::Mutex::new(cursor),
+        });
+        match self.cursors.entry(Self::key(session_id, name)) {
+            dashmap::mapref::entry::Entry::Occupied(_) => Err(Error::already_exists(format!("cursor {}", name))),
+            dashmap::mapref::entry::Entry::Vacant(slot) => {
+                slot.insert(entry);
+                Ok(())
+            }
+        }
+    }
+
+    /// 从游标读取行，返回读到的行以及游标是否已读完
+    pub async fn fetch(&self, session_id: Option<&str>, name: &str, count: FetchCount) -> Result<(Vec<Row>, bool)> {
+        self.purge_idle();
+        let entry = self.cursors.get(&Self::key(session_id, name))
+            .map(|entry| entry.clone())
+            .ok_or_else(|| Error::not_found(format!("cursor {}", name)))?;
+        *entry.last_access.lock() = Instant::now();
+        let mut cursor = entry.cursor.lock().await;
+        let rows = cursor.fetch(count.limit()).await?;
+        *entry.last_access.lock() = Instant::now();
+        Ok((rows, cursor.is_exhausted()))
+    }
+
+    /// 关闭游标并取消其查询
+    pub fn close(&self, session_id: Option<&str>, name: &str) -> Result<()> {
+        self.cursors.remove(&Self::key(session_id, name))
+            .map(|_| ())
+            .ok_or_else(|| Error::not_found(format!("cursor {}", name)))
+    }
+
+    /// 关闭会话的所有游标，返回关闭的数量
+    pub fn close_all(&self, session_id: Option<&str>) -> usize {
+        let before = self.cursors.len();
+        self.cursors.retain(|_, entry| entry.session_id.as_deref() != session_id);
+        before - self.cursors.len()
+    }
+
+    /// 回收空闲超时的游标，返回回收的数量
+    pub fn purge_idle(&self) -> usize {
+        let before = self.cursors.len();
+        let idle_timeout = self.idle_timeout;
+        self.cursors.retain(|_, entry| entry.last_access.lock().elapsed() < idle_timeout);
+        before - self.cursors.len()
+    }
+
+    /// 列出所有游标
+    pub fn list(&self) -> Vec<CursorInfo> {
+        self.cursors.iter().map(|entry| {
+            // 正在FETCH的游标不阻塞列表，统计值以上次可见的为准
+            let (sql, fetched, exhausted) = match entry.cursor.try_lock() {
+                Ok(cursor) => (cursor.sql.clone(), cursor.fetched, cursor.is_exhausted()),
+                Err(_) => (String::new(), 0, false),
+            };
+            CursorInfo {
+                name: entry.name.clone(),
+                session_id: entry.session_id.clone(),
+                sql,
+                fetched,
+                exhausted,
+                idle: entry.last_access.lock().elapsed(),
+            }
+        }).collect()
+    }
+
+    /// 游标数量
+    pub fn len(&self) -> usize {
+        self.cursors.len()
+    }
+
+    /// 是否没有游标
+    pub fn is_empty(&self) -> bool {
+        self.cursors.is_empty()
+    }
+}
+
+impl Default for CursorRegistry {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(DEFAULT_CURSOR_IDLE_TIMEOUT_SECS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(i: i64) -> Row {
+        HashMap::from([("id".to_string(), Value::Int64(i))])
+    }
+
+    #[test]
+    fn test_parse_cursor_commands() {
+        assert_eq!(
+            CursorCommand::parse("DECLARE c1 CURSOR FOR SELECT * FROM trades;").unwrap(),
+            Some(CursorCommand::Declare { name: "c1".to_string(), sql: "SELECT * FROM trades".to_string() })
+        );
+        assert_eq!(
+            CursorCommand::parse("fetch 100 from C1").unwrap(),
+            Some(CursorCommand::Fetch { name: "c1".to_string(), count: FetchCount::Count(100) })
+        );
+        assert_eq!(
+            CursorCommand::parse("FETCH FORWARD ALL IN c1").unwrap(),
+            Some(CursorCommand::Fetch { name: "c1".to_string(), count: FetchCount::All })
+        );
+        assert_eq!(
+            CursorCommand::parse("FETCH NEXT FROM c1").unwrap(),
+            Some(CursorCommand::Fetch { name: "c1".to_string(), count: FetchCount::Next })
+        );
+        assert_eq!(CursorCommand::parse("CLOSE ALL").unwrap(), Some(CursorCommand::Close { name: None }));
+        assert!(CursorCommand::parse("DECLARE c CURSOR FOR DELETE FROM trades").is_err());
+        assert_eq!(CursorCommand::parse("SELECT 1").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stream_applies_backpressure() {
+        let token = CancellationToken::new();
+        let (mut sender, mut stream) = RowStream::channel("q1", token.clone(), 2);
+        let producer = tokio::spawn(async move {
+            let mut sent = 0;
+            for i in 0..10 {
+                if !sender.send(vec![row(i)]).await {
+                    break;
+                }
+                sent += 1;
+            }
+            sent
+        });
+        // 消费者只读两批后放弃，生产者在通道满时挂起，之后因消费者关闭而停止
+        let first = stream.next_batch().await.unwrap().unwrap();
+        assert_eq!(first.sequence, 0);
+        stream.next_batch().await.unwrap().unwrap();
+        drop(stream);
+        let sent = producer.await.unwrap();
+        assert!(sent < 10);
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_cursor_registry_pages() {
+        let registry = CursorRegistry::default();
+        let (mut sender, stream) = RowStream::channel("q1", CancellationToken::new(), STREAM_CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            for chunk in (0..25).collect::<Vec<i64>>().chunks(10) {
+                sender.send(chunk.iter().map(|i| row(*i)).collect()).await;
+            }
+        });
+        registry.declare(Some("s1"), "c1", Cursor::new("SELECT * FROM t", stream)).unwrap();
+        assert!(registry.declare(Some("s1"), "C1", Cursor::new("", RowStream::channel("q2", CancellationToken::new(), 1).1)).is_err());
+
+        let (page, done) = registry.fetch(Some("s1"), "c1", FetchCount::Count(15)).await.unwrap();
+        assert_eq!(page.len(), 15);
+        assert!(!done);
+        assert!(registry.fetch(Some("s2"), "c1", FetchCount::Next).await.is_err());
+        let (rest, done) = registry.fetch(Some("s1"), "c1", FetchCount::All).await.unwrap();
+        assert_eq!(rest.len(), 10);
+        assert_eq!(rest[0].get("id"), Some(&Value::Int64(15)));
+        assert!(done);
+
+        registry.close(Some("s1"), "c1").unwrap();
+        assert!(registry.is_empty());
+    }
+}
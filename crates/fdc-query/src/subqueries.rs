@@ -0,0 +1,964 @@
+//! CTEs, subqueries and set operations for the native executor
+
+use crate::{
+    executor::{apply_offset_limit, select_item_name, split_conjunction, DefaultQueryExecutor, ExecutionContext, SelectInput},
+    expressions::{expr_output_name, ExpressionEvaluator},
+    joins::{normalize_key_value, Row},
+    parser::QueryFeatures,
+    sorts::{sort_rows, SortOrder},
+};
+use fdc_core::{error::{Error, Result}, types::Value};
+use futures::future::BoxFuture;
+use sqlparser::ast::{
+    self, BinaryOperator, Expr, GroupByExpr, Ident, JoinConstraint, JoinOperator, Query, Select, SelectItem,
+    SetExpr, SetOperator, SetQuantifier, TableAlias, TableFactor, Visit, VisitMut, Visitor, VisitorMut, With,
+};
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+/// 递归CTE的最大迭代次数
+pub const MAX_RECURSIVE_ITERATIONS: usize = 10_000;
+
+/// 已物化的关系：CTE或FROM中的派生表
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Relation {
+    /// 按输出顺序排列的列名
+    pub columns: Vec<String>,
+    /// 以列名为键的行
+    pub rows: Vec<Row>,
+}
+
+impl Relation {
+    /// 按别名列表（`t(a, b)`）依次重命名各列
+    fn renamed(self, alias: &TableAlias) -> Result<Self> {
+        if alias.columns.is_empty() {
+            return Ok(self);
+        }
+        if alias.columns.len() != self.columns.len() {
+            return Err(Error::validation(format!(
+                "{} has {} columns but {} column aliases were given",
+                alias.name, self.columns.len(), alias.columns.len()
+            )));
+        }
+        let columns: Vec<String> = alias.columns.iter().map(|c| c.value.clone()).collect();
+        let rows = align_columns(self.rows, &self.columns, &columns)?;
+        Ok(Self { columns, rows })
+    }
+}
+
+/// 查询可见的物化关系
+///
+/// CTE对定义它的查询及其所有子查询可见；派生表只对所在SELECT的FROM可见。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct QueryScope {
+    ctes: HashMap<String, Arc<Relation>>,
+    derived: HashMap<String, Arc<Relation>>,
+}
+
+impl QueryScope {
+    /// 按名称（不区分大小写）查找物化关系，派生表优先于CTE
+    pub fn relation(&self, name: &str) -> Option<&Relation> {
+        let key = name.to_lowercase();
+        self.derived.get(&key).or_else(|| self.ctes.get(&key)).map(Arc::as_ref)
+    }
+
+    /// 子查询可见的关系（不含外层FROM中的派生表）
+    fn for_subquery(&self) -> Self {
+        Self { ctes: self.ctes.clone(), derived: HashMap::new() }
+    }
+
+    fn bind_cte(&mut self, name: &str, relation: Relation) {
+        self.ctes.insert(name.to_lowercase(), Arc::new(relation));
+    }
+
+    fn bind_derived(&mut self, name: &str, relation: Relation) {
+        self.derived.insert(name.to_lowercase(), Arc::new(relation));
+    }
+}
+
+/// 子查询表达式在SELECT管线中的求值时机
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SubqueryStage {
+    /// WHERE之前，在扫描出的行上求值
+    Where,
+    /// HAVING之前，在分组后的行上求值
+    Having,
+    /// 排序与投影之前
+    Projection,
+}
+
+/// 已从SELECT中取出、以合成列代替的子查询表达式
+#[derive(Debug, Clone)]
+pub(crate) struct SubqueryBinding {
+    /// 保存结果的合成列名
+    pub column: String,
+    /// 求值时机
+    pub stage: SubqueryStage,
+    /// 原始表达式（标量子查询、EXISTS或IN子查询）
+    pub expr: Expr,
+}
+
+impl SubqueryBinding {
+    /// 在外层行上求值的操作数（`x IN (...)`中的`x`），其中的聚合由外层查询计算
+    pub fn operand(&self) -> Option<&Expr> {
+        match &self.expr {
+            Expr::InSubquery { expr, .. } => Some(&**expr),
+            _ => None,
+        }
+    }
+}
+
+/// 子查询的种类
+enum SubqueryKind<'a> {
+    Scalar,
+    Exists { negated: bool },
+    In { operand: &'a Expr, negated: bool },
+}
+
+/// 子查询对一组外层绑定的结果
+#[derive(Debug, Clone)]
+enum SubqueryResult {
+    Scalar(Value),
+    Exists(bool),
+    Set { values: HashSet<String>, has_null: bool },
+}
+
+impl SubqueryResult {
+    /// 由子查询的输出行构建结果
+    fn build(kind: &SubqueryKind<'_>, columns: &[String], rows: &[Row]) -> Result<Self> {
+        match kind {
+            SubqueryKind::Exists { .. } => Ok(Self::Exists(!rows.is_empty())),
+            SubqueryKind::Scalar => {
+                if columns.len() != 1 && !rows.is_empty() {
+                    return Err(Error::validation("Scalar subquery must return exactly one column"));
+                }
+                if rows.len() > 1 {
+                    return Err(Error::validation("Scalar subquery returned more than one row"));
+                }
+                let value = rows.first()
+                    .and_then(|row| columns.first().and_then(|column| row.get(column)))
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                Ok(Self::Scalar(value))
+            }
+            SubqueryKind::In { .. } => {
+                if columns.len() != 1 && !rows.is_empty() {
+                    return Err(Error::validation("Subquery in IN must return exactly one column"));
+                }
+                let mut result = Self::empty(kind);
+                for row in rows {
+                    result.insert(columns.first().and_then(|column| row.get(column)).unwrap_or(&Value::Null));
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    /// 没有任何输出行时的结果
+    fn empty(kind: &SubqueryKind<'_>) -> Self {
+        match kind {
+            SubqueryKind::Scalar => Self::Scalar(Value::Null),
+            SubqueryKind::Exists { .. } => Self::Exists(false),
+            SubqueryKind::In { .. } => Self::Set { values: HashSet::new(), has_null: false },
+        }
+    }
+
+    /// 向IN子查询的结果集加入一个值
+    fn insert(&mut self, value: &Value) {
+        match self {
+            Self::Set { values, has_null } => match normalize_key_value(value) {
+                Some(key) => {
+                    values.insert(key);
+                }
+                None => *has_null = true,
+            },
+            Self::Exists(exists) => *exists = true,
+            Self::Scalar(_) => {}
+        }
+    }
+
+    /// 在外层行上求子查询表达式的值（IN遵循SQL的三值逻辑）
+    fn apply(&self, kind: &SubqueryKind<'_>, row: &Row, evaluator: &ExpressionEvaluator) -> Result<Value> {
+        Ok(match (self, kind) {
+            (Self::Scalar(value), _) => value.clone(),
+            (Self::Exists(exists), SubqueryKind::Exists { negated }) => Value::Bool(exists != negated),
+            (Self::Set { values, has_null }, SubqueryKind::In { operand, negated }) => {
+                if values.is_empty() && !has_null {
+                    return Ok(Value::Bool(*negated));
+                }
+                match normalize_key_value(&evaluator.evaluate(operand, row)?) {
+                    None => Value::Null,
+                    Some(key) if values.contains(&key) => Value::Bool(!negated),
+                    Some(_) if *has_null => Value::Null,
+                    Some(_) => Value::Bool(*negated),
+                }
+            }
+            _ => return Err(Error::internal("Subquery result does not match its kind")),
+        })
+    }
+}
+
+/// 子查询中引用外层查询列的限定名（如`u.id`）
+#[derive(Debug, Clone)]
+struct OuterReference {
+    /// 引用表达式
+    expr: Expr,
+    /// 规范化名称，同时用作绑定参数名
+    key: String,
+}
+
+impl OuterReference {
+    fn parameter(&self) -> String {
+        format!("__outer_{}", self.key)
+    }
+}
+
+/// 收集子查询中的外层引用
+///
+/// 限定名的限定符不是子查询内任何FROM项（表名或别名）时视为外层引用；
+/// 未限定的列名总是解析到子查询自身的关系，关联子查询中的外层列需要写限定名。
+fn outer_references(query: &Query) -> Vec<OuterReference> {
+    #[derive(Default)]
+    struct Collector {
+        bound: HashSet<String>,
+        candidates: Vec<Vec<Ident>>,
+    }
+
+    impl Visitor for Collector {
+        type Break = ();
+
+        fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<()> {
+            match table_factor {
+                TableFactor::Table { alias: Some(alias), .. } | TableFactor::Derived { alias: Some(alias), .. } => {
+                    self.bound.insert(alias.name.value.to_lowercase());
+                }
+                TableFactor::Table { name, .. } => {
+                    if let Some(table) = name.0.last() {
+                        self.bound.insert(table.value.to_lowercase());
+                    }
+                }
+                _ => {}
+            }
+            ControlFlow::Continue(())
+        }
+
+        fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+            if let Expr::CompoundIdentifier(idents) = expr {
+                if idents.len() >= 2 {
+                    self.candidates.push(idents.clone());
+                }
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    let mut collector = Collector::default();
+    let _ = query.visit(&mut collector);
+    let mut references: Vec<OuterReference> = Vec::new();
+    for idents in collector.candidates {
+        let qualifier = idents[idents.len() - 2].value.to_lowercase();
+        if collector.bound.contains(&qualifier) {
+            continue;
+        }
+        let expr = Expr::CompoundIdentifier(idents);
+        if !references.iter().any(|r| r.expr == expr) {
+            let key = expr.to_string().to_lowercase();
+            references.push(OuterReference { expr, key });
+        }
+    }
+    references
+}
+
+/// 表达式是否引用了外层列
+fn references_outer(expr: &Expr, references: &[OuterReference]) -> bool {
+    let mut found = false;
+    let _ = ast::visit_expressions(expr, |e| {
+        if references.iter().any(|r| r.expr == *e) {
+            found = true;
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    });
+    found
+}
+
+/// 表达式中的列是否全部是外层引用（且至少有一个）
+fn only_outer(expr: &Expr, references: &[OuterReference]) -> bool {
+    let mut outer = false;
+    let mut other = false;
+    let _ = ast::visit_expressions(expr, |e| {
+        match e {
+            Expr::CompoundIdentifier(_) if references.iter().any(|r| r.expr == *e) => outer = true,
+            Expr::Identifier(_) | Expr::CompoundIdentifier(_) | Expr::Subquery(_) | Expr::Exists { .. } | Expr::InSubquery { .. } => {
+                other = true;
+            }
+            _ => {}
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    outer && !other
+}
+
+/// 把外层引用替换为绑定参数（`$__outer_u.id`），每组外层值绑定一次后执行
+fn bind_outer_references(query: &Query, references: &[OuterReference]) -> Query {
+    let mut bound = query.clone();
+    let _ = ast::visit_expressions_mut(&mut bound, |e| {
+        if let Some(reference) = references.iter().find(|r| r.expr == *e) {
+            *e = Expr::Value(ast::Value::Placeholder(format!("${}", reference.parameter())));
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    bound
+}
+
+/// 去关联后的EXISTS/IN子查询：只执行一次，按关联键构建哈希表后逐行探测（半连接/反连接）
+struct Decorrelated {
+    /// 投影出关联键（`__key_0`…）与IN的比较值（`__value`）的非关联查询
+    query: Query,
+    /// 在外层行上求值的关联键
+    outer_keys: Vec<Expr>,
+}
+
+/// 尝试把`WHERE inner = outer AND ...`形式的关联EXISTS/IN子查询去关联
+///
+/// 外层引用只出现在WHERE顶层的等值条件中、且子查询不含聚合、LIMIT和集合运算时才能去关联。
+fn decorrelate(subquery: &Query, kind: &SubqueryKind<'_>, references: &[OuterReference]) -> Option<Decorrelated> {
+    if matches!(kind, SubqueryKind::Scalar)
+        || subquery.with.is_some()
+        || subquery.limit.is_some()
+        || subquery.offset.is_some()
+        || subquery.fetch.is_some()
+    {
+        return None;
+    }
+    let SetExpr::Select(select) = subquery.body.as_ref() else {
+        return None;
+    };
+    let features = QueryFeatures::from_query(subquery);
+    if features.has_aggregate || features.has_window_function || select.from.is_empty() {
+        return None;
+    }
+    let in_value = match (kind, select.projection.as_slice()) {
+        (SubqueryKind::In { .. }, [SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. }]) => Some(expr.clone()),
+        (SubqueryKind::In { .. }, _) => return None,
+        _ => None,
+    };
+    if in_value.as_ref().is_some_and(|expr| references_outer(expr, references)) {
+        return None;
+    }
+    let joins_correlated = select.from.iter().flat_map(|t| &t.joins).any(|join| match &join.join_operator {
+        JoinOperator::Inner(JoinConstraint::On(on)) | JoinOperator::LeftOuter(JoinConstraint::On(on))
+        | JoinOperator::RightOuter(JoinConstraint::On(on)) | JoinOperator::FullOuter(JoinConstraint::On(on)) => {
+            references_outer(on, references)
+        }
+        _ => false,
+    });
+    if joins_correlated {
+        return None;
+    }
+
+    let mut inner_keys = Vec::new();
+    let mut outer_keys = Vec::new();
+    let mut remaining = Vec::new();
+    for conjunct in select.selection.as_ref().map(split_conjunction).unwrap_or_default() {
+        if !references_outer(conjunct, references) {
+            remaining.push(conjunct.clone());
+            continue;
+        }
+        let Expr::BinaryOp { left, op: BinaryOperator::Eq, right } = conjunct else {
+            return None;
+        };
+        let (inner, outer) = if only_outer(right, references) && !references_outer(left, references) {
+            (left, right)
+        } else if only_outer(left, references) && !references_outer(right, references) {
+            (right, left)
+        } else {
+            return None;
+        };
+        inner_keys.push((**inner).clone());
+        outer_keys.push((**outer).clone());
+    }
+    if outer_keys.is_empty() {
+        return None;
+    }
+
+    let mut select = (**select).clone();
+    select.distinct = None;
+    select.projection = inner_keys.into_iter().enumerate()
+        .map(|(index, expr)| SelectItem::ExprWithAlias { expr, alias: Ident::new(format!("__key_{}", index)) })
+        .chain(in_value.map(|expr| SelectItem::ExprWithAlias { expr, alias: Ident::new("__value") }))
+        .collect();
+    select.selection = remaining.into_iter().reduce(|a, b| Expr::BinaryOp {
+        left: Box::new(a),
+        op: BinaryOperator::And,
+        right: Box::new(b),
+    });
+    let mut query = subquery.clone();
+    query.body = Box::new(SetExpr::Select(Box::new(select)));
+    query.order_by = None;
+    Some(Decorrelated { query, outer_keys })
+}
+
+/// 把SELECT中的子查询表达式替换为合成列，返回改写后的查询与各子查询的求值时机
+///
+/// 投影项中被改写的表达式保留原来的输出列名。
+pub(crate) fn extract_subqueries(query: &Query, select: &Select) -> Result<(Query, Vec<SubqueryBinding>)> {
+    struct Extractor<'a> {
+        bindings: &'a mut Vec<SubqueryBinding>,
+        stage: SubqueryStage,
+    }
+
+    impl VisitorMut for Extractor<'_> {
+        type Break = ();
+
+        fn pre_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<()> {
+            if matches!(expr, Expr::Subquery(_) | Expr::Exists { .. } | Expr::InSubquery { .. }) {
+                let column = format!("__subquery_{}", self.bindings.len());
+                let original = std::mem::replace(expr, Expr::Identifier(Ident::new(column.clone())));
+                self.bindings.push(SubqueryBinding { column, stage: self.stage, expr: original });
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    let unsupported = |what: &str| Error::unimplemented(format!("Subqueries in {}", what));
+    if let GroupByExpr::Expressions(exprs, _) = &select.group_by {
+        if exprs.iter().any(contains_subquery) {
+            return Err(unsupported("GROUP BY"));
+        }
+    }
+    for join in select.from.iter().flat_map(|t| &t.joins) {
+        if let JoinOperator::Inner(JoinConstraint::On(on)) | JoinOperator::LeftOuter(JoinConstraint::On(on))
+        | JoinOperator::RightOuter(JoinConstraint::On(on)) | JoinOperator::FullOuter(JoinConstraint::On(on)) = &join.join_operator
+        {
+            if contains_subquery(on) {
+                return Err(unsupported("JOIN conditions"));
+            }
+        }
+    }
+
+    let mut bindings = Vec::new();
+    let mut select = select.clone();
+    if let Some(selection) = &mut select.selection {
+        let _ = VisitMut::visit(selection, &mut Extractor { bindings: &mut bindings, stage: SubqueryStage::Where });
+    }
+    if let Some(having) = &mut select.having {
+        let _ = VisitMut::visit(having, &mut Extractor { bindings: &mut bindings, stage: SubqueryStage::Having });
+    }
+    for item in &mut select.projection {
+        if let SelectItem::UnnamedExpr(expr) = item {
+            if contains_subquery(expr) {
+                let alias = Ident::new(expr_output_name(expr));
+                *item = SelectItem::ExprWithAlias { expr: expr.clone(), alias };
+            }
+        }
+        if let SelectItem::ExprWithAlias { expr, .. } = item {
+            let _ = VisitMut::visit(expr, &mut Extractor { bindings: &mut bindings, stage: SubqueryStage::Projection });
+        }
+    }
+    let mut query = query.clone();
+    if let Some(order_by) = &mut query.order_by {
+        for item in &mut order_by.exprs {
+            let _ = VisitMut::visit(&mut item.expr, &mut Extractor { bindings: &mut bindings, stage: SubqueryStage::Projection });
+        }
+    }
+    query.with = None;
+    query.body = Box::new(SetExpr::Select(Box::new(select)));
+    Ok((query, bindings))
+}
+
+/// 表达式中是否包含子查询
+pub(crate) fn contains_subquery(expr: &Expr) -> bool {
+    let mut found = false;
+    let _ = ast::visit_expressions(expr, |e| {
+        if matches!(e, Expr::Subquery(_) | Expr::Exists { .. } | Expr::InSubquery { .. }) {
+            found = true;
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    });
+    found
+}
+
+impl DefaultQueryExecutor {
+    /// 求值一个查询（含WITH、集合运算和子查询），返回输出列名（按输出顺序）与结果行
+    pub(crate) fn run_query<'a>(
+        &'a self,
+        query: &'a Query,
+        scope: &'a QueryScope,
+        context: &'a ExecutionContext,
+    ) -> BoxFuture<'a, Result<(Vec<String>, Vec<Row>)>> {
+        Box::pin(async move {
+            // 行数上限只作用于最外层结果，集合运算的分支和子查询必须完整求值
+            let mut context = context.clone();
+            context.max_rows = None;
+            context.profile = false;
+            let scope = match &query.with {
+                Some(with) => self.materialize_ctes(with, scope, &context).await?,
+                None => scope.clone(),
+            };
+            match query.body.as_ref() {
+                SetExpr::Select(select) => self.run_subquery_select(query, select, &scope, &context).await,
+                body => {
+                    let (columns, rows) = self.run_set_expr(body, query, &scope, &context).await?;
+                    let rows = order_and_limit(rows, &columns, query, &self.evaluator(&context))?;
+                    Ok((columns, rows))
+                }
+            }
+        })
+    }
+
+    /// 在外层行上求值指定时机的子查询，结果写入各自的合成列，返回写入的列名
+    pub(crate) async fn bind_subqueries(
+        &self,
+        rows: &mut [Row],
+        bindings: &[SubqueryBinding],
+        stage: SubqueryStage,
+        scope: &QueryScope,
+        context: &ExecutionContext,
+    ) -> Result<Vec<String>> {
+        let mut columns = Vec::new();
+        for binding in bindings.iter().filter(|b| b.stage == stage) {
+            let values = self.evaluate_subquery(&binding.expr, rows, &scope.for_subquery(), context).await?;
+            for (row, value) in rows.iter_mut().zip(values) {
+                row.insert(binding.column.clone(), value);
+            }
+            columns.push(binding.column.clone());
+        }
+        Ok(columns)
+    }
+
+    /// 对每个外层行求子查询表达式的值
+    ///
+    /// 非关联子查询只执行一次；可去关联的EXISTS/IN执行一次后按关联键哈希探测；
+    /// 其余关联子查询按外层引用的取值分别执行，相同取值只执行一次。
+    async fn evaluate_subquery(
+        &self,
+        expr: &Expr,
+        rows: &[Row],
+        scope: &QueryScope,
+        context: &ExecutionContext,
+    ) -> Result<Vec<Value>> {
+        let (subquery, kind) = match expr {
+            Expr::Subquery(subquery) => (subquery, SubqueryKind::Scalar),
+            Expr::Exists { subquery, negated } => (subquery, SubqueryKind::Exists { negated: *negated }),
+            Expr::InSubquery { expr, subquery, negated } => (subquery, SubqueryKind::In { operand: expr, negated: *negated }),
+            other => return Err(Error::internal(format!("Not a subquery expression: {}", other))),
+        };
+        let evaluator = self.evaluator(context);
+        let references = outer_references(subquery);
+
+        if references.is_empty() {
+            let (columns, result) = self.run_query(subquery, scope, context).await?;
+            let result = SubqueryResult::build(&kind, &columns, &result)?;
+            return rows.iter().map(|row| result.apply(&kind, row, &evaluator)).collect();
+        }
+
+        if let Some(decorrelated) = decorrelate(subquery, &kind, &references) {
+            let (_, inner) = self.run_query(&decorrelated.query, scope, context).await?;
+            let mut groups: HashMap<Vec<String>, SubqueryResult> = HashMap::new();
+            for row in &inner {
+                let key: Option<Vec<String>> = (0..decorrelated.outer_keys.len())
+                    .map(|index| row.get(&format!("__key_{}", index)).and_then(normalize_key_value))
+                    .collect();
+                // 关联键为NULL的行与任何外层行都不相等
+                let Some(key) = key else { continue };
+                groups.entry(key)
+                    .or_insert_with(|| SubqueryResult::empty(&kind))
+                    .insert(row.get("__value").unwrap_or(&Value::Bool(true)));
+            }
+            let empty = SubqueryResult::empty(&kind);
+            return rows.iter()
+                .map(|row| {
+                    evaluator.checkpoint()?;
+                    let key = decorrelated.outer_keys.iter()
+                        .map(|expr| Ok(normalize_key_value(&evaluator.evaluate(expr, row)?)))
+                        .collect::<Result<Option<Vec<String>>>>()?;
+                    let result = key.as_ref().and_then(|key| groups.get(key)).unwrap_or(&empty);
+                    result.apply(&kind, row, &evaluator)
+                })
+                .collect();
+        }
+
+        let bound = bind_outer_references(subquery, &references);
+        let mut memo: HashMap<Vec<Option<String>>, SubqueryResult> = HashMap::new();
+        let mut values = Vec::with_capacity(rows.len());
+        for row in rows {
+            evaluator.checkpoint()?;
+            let outer = references.iter()
+                .map(|reference| evaluator.evaluate(&reference.expr, row))
+                .collect::<Result<Vec<_>>>()?;
+            let key: Vec<Option<String>> = outer.iter().map(normalize_key_value).collect();
+            if !memo.contains_key(&key) {
+                let mut child = context.clone();
+                for (reference, value) in references.iter().zip(outer) {
+                    child.parameters.insert(reference.parameter(), value);
+                }
+                let (columns, result) = self.run_query(&bound, scope, &child).await?;
+                memo.insert(key.clone(), SubqueryResult::build(&kind, &columns, &result)?);
+            }
+            values.push(memo[&key].apply(&kind, row, &evaluator)?);
+        }
+        Ok(values)
+    }
+
+    /// 依次物化WITH中的各个CTE（后定义的CTE可以引用先定义的）
+    async fn materialize_ctes(&self, with: &With, scope: &QueryScope, context: &ExecutionContext) -> Result<QueryScope> {
+        let mut scope = scope.clone();
+        for cte in &with.cte_tables {
+            let name = &cte.alias.name.value;
+            let relation = if with.recursive && references_relation(cte.query.as_ref(), name) {
+                self.run_recursive_cte(name, &cte.alias, &cte.query, &scope, context).await?
+            } else {
+                let (columns, rows) = self.run_query(&cte.query, &scope, context).await?;
+                Relation { columns, rows }.renamed(&cte.alias)?
+            };
+            scope.bind_cte(name, relation);
+        }
+        Ok(scope)
+    }
+
+    /// 求值递归CTE：`锚点 UNION [ALL] 递归项`
+    ///
+    /// 先求锚点，再反复以上一轮新产生的行作为CTE自身求值递归项，直到不再产生新行。
+    /// UNION去重时已出现过的行不再参与下一轮。
+    async fn run_recursive_cte(
+        &self,
+        name: &str,
+        alias: &TableAlias,
+        query: &Query,
+        scope: &QueryScope,
+        context: &ExecutionContext,
+    ) -> Result<Relation> {
+        let SetExpr::SetOperation { op: SetOperator::Union, set_quantifier, left, right } = query.body.as_ref() else {
+            return Err(Error::validation(format!("Recursive CTE {} must be of the form <anchor> UNION [ALL] <recursive term>", name)));
+        };
+        if references_relation(left.as_ref(), name) {
+            return Err(Error::validation(format!("The anchor of recursive CTE {} must not reference itself", name)));
+        }
+        let distinct = !matches!(set_quantifier, SetQuantifier::All);
+
+        let (columns, anchor) = self.run_set_expr(left, query, scope, context).await?;
+        let Relation { columns, rows: anchor } = Relation { columns, rows: anchor }.renamed(alias)?;
+        let mut seen = HashSet::new();
+        let mut result: Vec<Row> = anchor.into_iter()
+            .filter(|row| !distinct || seen.insert(row_key(row, &columns)))
+            .collect();
+        let mut working = result.clone();
+        let mut iterations = 0;
+        while !working.is_empty() {
+            iterations += 1;
+            if iterations > MAX_RECURSIVE_ITERATIONS {
+                return Err(Error::resource_exhausted(format!(
+                    "Recursive CTE {} did not terminate within {} iterations", name, MAX_RECURSIVE_ITERATIONS
+                )));
+            }
+            context.cancellation.check()?;
+            let mut step_scope = scope.clone();
+            step_scope.bind_cte(name, Relation { columns: columns.clone(), rows: std::mem::take(&mut working) });
+            let (step_columns, step) = self.run_set_expr(right, query, &step_scope, context).await?;
+            working = align_columns(step, &step_columns, &columns)?.into_iter()
+                .filter(|row| !distinct || seen.insert(row_key(row, &columns)))
+                .collect();
+            result.extend(working.iter().cloned());
+        }
+        let rows = order_and_limit(result, &columns, query, &self.evaluator(context))?;
+        Ok(Relation { columns, rows })
+    }
+
+    /// 求值查询体（SELECT、VALUES、括号内的查询或集合运算），`template`提供分支SELECT所在的查询
+    fn run_set_expr<'a>(
+        &'a self,
+        body: &'a SetExpr,
+        template: &'a Query,
+        scope: &'a QueryScope,
+        context: &'a ExecutionContext,
+    ) -> BoxFuture<'a, Result<(Vec<String>, Vec<Row>)>> {
+        Box::pin(async move {
+            match body {
+                SetExpr::Select(select) => {
+                    let mut query = template.clone();
+                    query.with = None;
+                    query.body = Box::new(SetExpr::Select(select.clone()));
+                    query.order_by = None;
+                    query.limit = None;
+                    query.limit_by.clear();
+                    query.offset = None;
+                    query.fetch = None;
+                    self.run_subquery_select(&query, select, scope, context).await
+                }
+                SetExpr::Query(query) => self.run_query(query, scope, context).await,
+                SetExpr::Values(values) => {
+                    let evaluator = self.evaluator(context);
+                    let width = values.rows.first().map_or(0, Vec::len);
+                    let columns: Vec<String> = (1..=width).map(|i| format!("column{}", i)).collect();
+                    let empty = Row::new();
+                    let rows = values.rows.iter()
+                        .map(|exprs| {
+                            if exprs.len() != width {
+                                return Err(Error::validation("VALUES rows must all have the same number of columns"));
+                            }
+                            columns.iter().zip(exprs)
+                                .map(|(column, expr)| Ok((column.clone(), evaluator.evaluate(expr, &empty)?)))
+                                .collect()
+                        })
+                        .collect::<Result<Vec<Row>>>()?;
+                    Ok((columns, rows))
+                }
+                SetExpr::SetOperation { op, set_quantifier, left, right } => {
+                    let all = match set_quantifier {
+                        SetQuantifier::All => true,
+                        SetQuantifier::Distinct | SetQuantifier::None => false,
+                        other => return Err(Error::unimplemented(format!("{} {}", op, other))),
+                    };
+                    let (columns, left) = self.run_set_expr(left, template, scope, context).await?;
+                    let (right_columns, right) = self.run_set_expr(right, template, scope, context).await?;
+                    let right = align_columns(right, &right_columns, &columns)?;
+                    context.cancellation.check()?;
+                    Ok((columns.clone(), combine(op, all, left, right, &columns)))
+                }
+                other => Err(Error::unimplemented(format!("Query body not supported by the native backend: {}", other))),
+            }
+        })
+    }
+
+    /// 执行单个SELECT：先物化FROM中的派生表并取出子查询表达式，再交给原生管线
+    async fn run_subquery_select(
+        &self,
+        query: &Query,
+        select: &Select,
+        scope: &QueryScope,
+        context: &ExecutionContext,
+    ) -> Result<(Vec<String>, Vec<Row>)> {
+        let mut local = scope.clone();
+        let factors = select.from.iter().flat_map(|t| std::iter::once(&t.relation).chain(t.joins.iter().map(|j| &j.relation)));
+        for factor in factors {
+            if let TableFactor::Derived { lateral, subquery, alias } = factor {
+                if *lateral {
+                    return Err(Error::unimplemented("LATERAL subqueries"));
+                }
+                let alias = alias.as_ref().ok_or_else(|| Error::validation("Subquery in FROM must have an alias"))?;
+                let (columns, rows) = self.run_query(subquery, &scope.for_subquery(), context).await?;
+                local.bind_derived(&alias.name.value, Relation { columns, rows }.renamed(alias)?);
+            }
+        }
+        let (rewritten, bindings) = extract_subqueries(query, select)?;
+        let (rows, _) = self.run_select_body(&rewritten, None, &[], context, SelectInput::Scan, &local, &bindings).await?;
+        Ok((output_columns(&select.projection, &rows), rows))
+    }
+}
+
+/// 查询（或查询体）是否引用了名为`name`的关系
+fn references_relation<V: Visit>(node: &V, name: &str) -> bool {
+    let mut found = false;
+    let _ = ast::visit_relations(node, |relation| {
+        if relation.0.last().is_some_and(|ident| ident.value.eq_ignore_ascii_case(name)) {
+            found = true;
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    });
+    found
+}
+
+/// 投影的输出列名（按输出顺序）；通配符展开的列没有确定顺序，按列名排序
+fn output_columns(projection: &[SelectItem], rows: &[Row]) -> Vec<String> {
+    let explicit: Vec<String> = projection.iter()
+        .filter(|item| matches!(item, SelectItem::UnnamedExpr(_) | SelectItem::ExprWithAlias { .. }))
+        .map(select_item_name)
+        .collect();
+    let mut columns = Vec::new();
+    for item in projection {
+        match item {
+            SelectItem::UnnamedExpr(_) | SelectItem::ExprWithAlias { .. } => columns.push(select_item_name(item)),
+            _ => {
+                let mut expanded: Vec<String> = rows.first()
+                    .map(|row| row.keys().filter(|k| !explicit.contains(k) && !columns.contains(k)).cloned().collect())
+                    .unwrap_or_default();
+                expanded.sort();
+                columns.extend(expanded);
+            }
+        }
+    }
+    columns
+}
+
+/// 按位置把`from`列重命名为`to`列（集合运算的右侧分支、带列别名的CTE）
+fn align_columns(rows: Vec<Row>, from: &[String], to: &[String]) -> Result<Vec<Row>> {
+    if from == to || rows.is_empty() {
+        return Ok(rows);
+    }
+    if from.len() != to.len() {
+        return Err(Error::validation(format!(
+            "Each query in a set operation must have the same number of columns ({} vs {})",
+            to.len(), from.len()
+        )));
+    }
+    Ok(rows.into_iter()
+        .map(|mut row| {
+            from.iter().zip(to)
+                .map(|(from, to)| (to.clone(), row.remove(from).unwrap_or(Value::Null)))
+                .collect()
+        })
+        .collect())
+}
+
+/// 行在各输出列上的规范化值（NULL彼此相等），用于集合运算的去重与匹配
+fn row_key(row: &Row, columns: &[String]) -> Vec<Option<String>> {
+    columns.iter().map(|column| row.get(column).and_then(normalize_key_value)).collect()
+}
+
+/// 合并集合运算的两个分支；`all`为false时结果去重
+fn combine(op: &SetOperator, all: bool, left: Vec<Row>, right: Vec<Row>, columns: &[String]) -> Vec<Row> {
+    let mut emitted = HashSet::new();
+    match op {
+        SetOperator::Union if all => left.into_iter().chain(right).collect(),
+        SetOperator::Union => left.into_iter().chain(right)
+            .filter(|row| emitted.insert(row_key(row, columns)))
+            .collect(),
+        SetOperator::Intersect | SetOperator::Except => {
+            let mut counts: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+            for row in &right {
+                *counts.entry(row_key(row, columns)).or_default() += 1;
+            }
+            let intersect = matches!(op, SetOperator::Intersect);
+            left.into_iter()
+                .filter(|row| {
+                    let key = row_key(row, columns);
+                    if all {
+                        // 多重集语义：右侧每一行只抵消左侧的一行
+                        let matched = counts.get_mut(&key).filter(|count| **count > 0).map(|count| *count -= 1).is_some();
+                        matched == intersect
+                    } else {
+                        counts.contains_key(&key) == intersect && emitted.insert(key)
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+/// 集合运算结果的ORDER BY与OFFSET/LIMIT：排序键按输出列名解析，位置编号对应输出列
+fn order_and_limit(rows: Vec<Row>, columns: &[String], query: &Query, evaluator: &ExpressionEvaluator) -> Result<Vec<Row>> {
+    let mut rows = rows;
+    if let Some(order_by) = &query.order_by {
+        let keys: Vec<Expr> = order_by.exprs.iter().map(|item| match &item.expr {
+            Expr::Value(ast::Value::Number(n, _)) => n.parse::<usize>().ok()
+                .and_then(|position| position.checked_sub(1))
+                .and_then(|index| columns.get(index))
+                .map(|column| Expr::Identifier(Ident::new(column.clone())))
+                .unwrap_or_else(|| item.expr.clone()),
+            other => other.clone(),
+        }).collect();
+        let orders: Vec<SortOrder> = order_by.exprs.iter().map(SortOrder::from_order_by).collect();
+        rows = sort_rows(rows, &keys, &orders, evaluator)?;
+    }
+    apply_offset_limit(rows, query, evaluator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::QueryExecutor;
+    use crate::optimizer::OptimizedPlan;
+    use crate::parser::{ParsedQuery, QueryType};
+    use fdc_storage::engine::StorageEngine;
+    use fdc_storage::engines::memory::MemoryEngine;
+
+    async fn run(sql: &str) -> Result<Vec<Row>> {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let executor = DefaultQueryExecutor::new(storage);
+        let query = ParsedQuery::new(QueryType::Select, sql.to_string());
+        let result = executor.execute(OptimizedPlan::new(query), ExecutionContext::new("q".to_string())).await?;
+        Ok(result.rows)
+    }
+
+    async fn ints(sql: &str, column: &str) -> Vec<i64> {
+        run(sql).await.unwrap().iter()
+            .map(|row| match row.get(column) {
+                Some(Value::Int64(v)) => *v,
+                other => panic!("{}: unexpected {} value {:?}", sql, column, other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_ctes() {
+        let sql = "WITH big AS (SELECT id, user_id FROM orders WHERE amount > 1500), \
+                   counts(uid, n) AS (SELECT user_id, COUNT(*) FROM big GROUP BY user_id) \
+                   SELECT uid FROM counts ORDER BY uid";
+        assert_eq!(ints(sql, "uid").await, vec![1, 7, 8, 9, 10]);
+
+        let sql = "WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 5) \
+                   SELECT n FROM seq ORDER BY n";
+        assert_eq!(ints(sql, "n").await, vec![1, 2, 3, 4, 5]);
+
+        // UNION去重使循环的递归在不再产生新行时终止
+        let sql = "WITH RECURSIVE r(n) AS (SELECT 1 UNION SELECT (n % 3) + 1 FROM r) SELECT n FROM r ORDER BY n";
+        assert_eq!(ints(sql, "n").await, vec![1, 2, 3]);
+
+        let error = run("WITH RECURSIVE r(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM r) SELECT n FROM r").await.unwrap_err();
+        assert!(error.to_string().contains("did not terminate"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_subqueries() {
+        let sql = "SELECT id FROM users WHERE id IN (SELECT user_id FROM orders WHERE amount > 1500) ORDER BY id";
+        assert_eq!(ints(sql, "id").await, vec![1, 7, 8, 9, 10]);
+
+        // NOT IN遇到NULL时结果为NULL，没有行满足条件
+        assert!(run("SELECT id FROM users WHERE id NOT IN (SELECT NULL)").await.unwrap().is_empty());
+
+        // 关联EXISTS按关联键去关联为半连接/反连接
+        let sql = "SELECT u.id AS id FROM users u \
+                   WHERE EXISTS (SELECT 1 FROM orders o WHERE o.user_id = u.id AND o.amount > 1500) ORDER BY u.id";
+        assert_eq!(ints(sql, "id").await, vec![1, 7, 8, 9, 10]);
+        let sql = "SELECT u.id AS id FROM users u \
+                   WHERE NOT EXISTS (SELECT 1 FROM orders o WHERE o.user_id = u.id AND o.amount > 1500) ORDER BY u.id";
+        assert_eq!(ints(sql, "id").await, vec![2, 3, 4, 5, 6]);
+
+        // 含聚合的关联标量子查询按外层取值逐一执行
+        let sql = "SELECT u.id AS id, (SELECT COUNT(*) FROM orders o WHERE o.user_id = u.id AND o.amount > 500) AS n \
+                   FROM users u ORDER BY u.id";
+        assert_eq!(ints(sql, "n").await, vec![2, 1, 1, 1, 1, 1, 2, 2, 2, 2]);
+
+        let sql = "SELECT id FROM orders WHERE amount > (SELECT AVG(amount) FROM orders) ORDER BY id";
+        assert_eq!(ints(sql, "id").await, (11..=20).collect::<Vec<_>>());
+
+        let error = run("SELECT (SELECT id FROM users) AS x").await.unwrap_err();
+        assert!(error.to_string().contains("more than one row"), "{}", error);
+
+        // FROM中的派生表
+        let sql = "SELECT d.user_id AS user_id FROM \
+                   (SELECT user_id, SUM(amount) AS total FROM orders GROUP BY user_id) d \
+                   WHERE d.total > 2500 ORDER BY d.user_id";
+        assert_eq!(ints(sql, "user_id").await, vec![1, 9, 10]);
+        let rows = run("SELECT u.name AS name FROM users u JOIN (SELECT user_id FROM orders WHERE amount > 1900) big \
+                        ON big.user_id = u.id").await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get("name"), Some(&Value::String("User1".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_set_operations() {
+        let branches = "SELECT id FROM users WHERE id <= 3 {} SELECT user_id FROM orders WHERE id <= 4 ORDER BY 1";
+        let sql = |op: &str| branches.replace("{}", op);
+        assert_eq!(ints(&sql("UNION"), "id").await, vec![1, 2, 3, 4, 5]);
+        assert_eq!(ints(&sql("UNION ALL"), "id").await, vec![1, 2, 2, 3, 3, 4, 5]);
+        assert_eq!(ints(&sql("INTERSECT"), "id").await, vec![2, 3]);
+        assert_eq!(ints(&sql("EXCEPT"), "id").await, vec![1]);
+
+        // ALL按多重集计数：右侧每一行只匹配或抵消左侧的一行
+        let sql = "SELECT user_id FROM orders INTERSECT ALL SELECT user_id FROM orders WHERE id <= 12 ORDER BY 1";
+        assert_eq!(ints(sql, "user_id").await, vec![1, 2, 2, 3, 3, 4, 5, 6, 7, 8, 9, 10]);
+        let sql = "SELECT user_id FROM orders EXCEPT ALL SELECT user_id FROM orders WHERE id <= 12 ORDER BY 1";
+        assert_eq!(ints(sql, "user_id").await, vec![1, 4, 5, 6, 7, 8, 9, 10]);
+
+        let sql = "SELECT id FROM users UNION ALL SELECT id FROM orders ORDER BY id DESC LIMIT 2";
+        assert_eq!(ints(sql, "id").await, vec![20, 19]);
+
+        let error = run("SELECT id, name FROM users UNION SELECT id FROM orders").await.unwrap_err();
+        assert!(error.to_string().contains("same number of columns"), "{}", error);
+    }
+}
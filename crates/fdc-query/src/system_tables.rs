@@ -0,0 +1,122 @@
+//! Virtual system tables
+//!
+//! System tables are read-only relations whose rows are produced on demand
+//! (catalog introspection, engine state) instead of being read from storage.
+//! The native executor resolves them before the table catalog, and queries
+//! over them always run on the native backend and bypass the result cache.
+
+use crate::{catalog::TableDefinition, joins::Row};
+use dashmap::DashMap;
+use fdc_core::error::{Error, Result};
+use std::sync::Arc;
+
+/// 虚拟系统表：每次扫描时即时生成全部行
+pub trait SystemTable: Send + Sync {
+    /// 表结构（列顺序即`SELECT *`的输出顺序）
+    fn definition(&self) -> TableDefinition;
+
+    /// 生成当前全部行
+    fn scan(&self) -> Result<Vec<Row>>;
+
+    /// 行所属用户所在的列；受访问控制约束的用户只能读到该列等于自己的行
+    fn owner_column(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// 由Rust闭包生成行的系统表
+pub struct RustSystemTable {
+    definition: TableDefinition,
+    rows: Box<dyn Fn() -> Result<Vec<Row>> + Send + Sync>,
+}
+
+impl RustSystemTable {
+    /// 创建系统表
+    pub fn new(definition: TableDefinition, rows: impl Fn() -> Result<Vec<Row>> + Send + Sync + 'static) -> Self {
+        Self {
+            definition,
+            rows: Box::new(rows),
+        }
+    }
+}
+
+impl SystemTable for RustSystemTable {
+    fn definition(&self) -> TableDefinition {
+        self.definition.clone()
+    }
+
+    fn scan(&self) -> Result<Vec<Row>> {
+        (self.rows)()
+    }
+}
+
+/// 系统表注册表（表名不区分大小写，可带模式前缀如`pg_catalog.pg_type`）
+#[derive(Default)]
+pub struct SystemTables {
+    tables: DashMap<String, Arc<dyn SystemTable>>,
+}
+
+impl SystemTables {
+    /// 创建空注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册系统表，同名表已存在时报错
+    pub fn register(&self, name: &str, table: Arc<dyn SystemTable>) -> Result<()> {
+        let name = name.to_lowercase();
+        if self.tables.contains_key(&name) {
+            return Err(Error::already_exists(format!("system table {}", name)));
+        }
+        self.tables.insert(name, table);
+        Ok(())
+    }
+
+    /// 查找系统表
+    pub fn get(&self, name: &str) -> Option<Arc<dyn SystemTable>> {
+        self.tables.get(&name.to_lowercase()).map(|table| table.clone())
+    }
+
+    /// 是否存在该系统表
+    pub fn contains(&self, name: &str) -> bool {
+        self.tables.contains_key(&name.to_lowercase())
+    }
+
+    /// 引用的表中是否有系统表
+    pub fn references_any<'a>(&self, tables: impl IntoIterator<Item = &'a String>) -> bool {
+        !self.tables.is_empty() && tables.into_iter().any(|table| self.contains(table))
+    }
+
+    /// 已注册的系统表名（排序）
+    pub fn list(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.iter().map(|table| table.key().clone()).collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{ColumnDefinition, ColumnType};
+    use fdc_core::types::Value;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_system_table_registry() {
+        let tables = SystemTables::new();
+        let definition = TableDefinition::new("pg_database", vec![ColumnDefinition::new("datname", ColumnType::String)]);
+        let table: Arc<dyn SystemTable> = Arc::new(RustSystemTable::new(definition, || {
+            Ok(vec![HashMap::from([("datname".to_string(), Value::String("fdc".to_string()))])])
+        }));
+        tables.register("pg_catalog.pg_database", table.clone()).unwrap();
+        tables.register("pg_database", table).unwrap();
+
+        assert!(tables.contains("PG_CATALOG.PG_DATABASE"));
+        assert!(tables.register("pg_database", Arc::new(RustSystemTable::new(TableDefinition::new("x", vec![]), || Ok(vec![])))).is_err());
+        assert!(tables.references_any(&["orders".to_string(), "pg_database".to_string()]));
+        assert!(!tables.references_any(&["orders".to_string()]));
+        assert_eq!(tables.get("pg_database").unwrap().scan().unwrap().len(), 1);
+        assert_eq!(tables.list(), vec!["pg_catalog.pg_database", "pg_database"]);
+    }
+}
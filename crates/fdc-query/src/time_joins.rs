@@ -0,0 +1,667 @@
+//! Time-series joins: ASOF joins and window joins as streaming merge operators
+
+use crate::{
+    aggregates::AggregateFunction,
+    expressions::value_as_timestamp,
+    joins::{JoinPredicate, JoinType, Row},
+};
+use fdc_core::{error::{Error, Result}, types::Value};
+use regex::Regex;
+use sqlparser::{ast::{BinaryOperator, Expr}, dialect::GenericDialect, parser::Parser};
+use std::collections::{HashMap, VecDeque};
+use std::iter::Peekable;
+use std::sync::OnceLock;
+
+/// ASOF匹配方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsofDirection {
+    /// 匹配左侧时间点及之前最近的右侧行（`t.ts >= q.ts`）
+    Backward,
+    /// 匹配左侧时间点及之后最近的右侧行（`t.ts <= q.ts`）
+    Forward,
+}
+
+/// ASOF连接定义
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsofSpec {
+    /// 左侧时间列
+    pub left_time: String,
+    /// 右侧时间列
+    pub right_time: String,
+    /// 匹配方向
+    pub direction: AsofDirection,
+    /// 是否允许时间相等
+    pub inclusive: bool,
+    /// 左侧分组键（BY）
+    pub left_by: Vec<String>,
+    /// 右侧分组键（BY）
+    pub right_by: Vec<String>,
+    /// 左右时间差上限（纳秒）
+    pub tolerance: Option<i64>,
+    /// 连接类型（Inner或Left，默认Left）
+    pub join_type: JoinType,
+}
+
+impl AsofSpec {
+    /// 创建向后匹配（`left_time >= right_time`）的ASOF连接
+    pub fn new(left_time: impl Into<String>, right_time: impl Into<String>) -> Self {
+        Self {
+            left_time: left_time.into(),
+            right_time: right_time.into(),
+            direction: AsofDirection::Backward,
+            inclusive: true,
+            left_by: Vec::new(),
+            right_by: Vec::new(),
+            tolerance: None,
+            join_type: JoinType::Left,
+        }
+    }
+
+    /// 设置匹配方向与是否包含相等时间
+    pub fn with_direction(mut self, direction: AsofDirection, inclusive: bool) -> Self {
+        self.direction = direction;
+        self.inclusive = inclusive;
+        self
+    }
+
+    /// 添加分组键
+    pub fn with_by(mut self, left: impl Into<String>, right: impl Into<String>) -> Self {
+        self.left_by.push(left.into());
+        self.right_by.push(right.into());
+        self
+    }
+
+    /// 设置时间差上限
+    pub fn with_tolerance(mut self, nanos: i64) -> Self {
+        self.tolerance = Some(nanos);
+        self
+    }
+
+    /// 设置连接类型
+    pub fn with_join_type(mut self, join_type: JoinType) -> Self {
+        self.join_type = join_type;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.left_by.len() != self.right_by.len() {
+            return Err(Error::validation("ASOF join requires the same number of BY keys on both sides"));
+        }
+        if !matches!(self.join_type, JoinType::Inner | JoinType::Left) {
+            return Err(Error::validation("ASOF join supports only INNER and LEFT semantics"));
+        }
+        Ok(())
+    }
+}
+
+/// ASOF连接流：按时间有序的左右输入做单次归并
+///
+/// 两侧输入都必须按各自的时间列升序。向后匹配时每个分组只保留最近一行，
+/// 向前匹配时只缓存尚未过期的右侧行。
+pub struct AsofJoinStream<'a, L, R>
+where
+    L: Iterator<Item = Row>,
+    R: Iterator<Item = Row>,
+{
+    left: L,
+    right: Peekable<R>,
+    spec: AsofSpec,
+    right_columns: Vec<String>,
+    residual: Option<&'a JoinPredicate<'a>>,
+    state: HashMap<Vec<String>, VecDeque<Row>>,
+}
+
+impl<'a, L, R> AsofJoinStream<'a, L, R>
+where
+    L: Iterator<Item = Row>,
+    R: Iterator<Item = Row>,
+{
+    /// 创建ASOF连接流
+    pub fn new(left: L, right: R, spec: AsofSpec) -> Result<Self> {
+        spec.validate()?;
+        Ok(Self {
+            left,
+            right: right.peekable(),
+            spec,
+            right_columns: Vec::new(),
+            residual: None,
+            state: HashMap::new(),
+        })
+    }
+
+    /// 设置右侧列（左连接未匹配时补NULL）
+    pub fn with_right_columns(mut self, columns: Vec<String>) -> Self {
+        self.right_columns = columns;
+        self
+    }
+
+    /// 设置附加匹配条件，在选出的最近行上判断
+    pub fn with_residual(mut self, residual: &'a JoinPredicate<'a>) -> Self {
+        self.residual = Some(residual);
+        self
+    }
+
+    /// 为左侧行找到匹配的右侧行
+    fn find_match(&mut self, left_ts: i64, key: &[String]) -> Option<Row> {
+        match self.spec.direction {
+            AsofDirection::Backward => {
+                // 吸收所有不晚于左侧时间的右侧行，每个分组保留最新一行
+                while let Some(right_ts) = self.right.peek().map(|r| time_of(r, &self.spec.right_time)) {
+                    match right_ts {
+                        Some(ts) if right_consumed(&self.spec, ts, left_ts) => {}
+                        Some(_) => break,
+                        None => {
+                            self.right.next();
+                            continue;
+                        }
+                    }
+                    let row = self.right.next().expect("peeked");
+                    if let Some(right_key) = group_key(&row, &self.spec.right_by) {
+                        let slot = self.state.entry(right_key).or_default();
+                        slot.clear();
+                        slot.push_back(row);
+                    }
+                }
+                let candidate = self.state.get(key)?.back()?;
+                let gap = left_ts - time_of(candidate, &self.spec.right_time)?;
+                within_tolerance(gap, self.spec.tolerance).then(|| candidate.clone())
+            }
+            AsofDirection::Forward => {
+                // 丢弃早于左侧时间的右侧行；左侧有序，它们不会再被需要
+                while let Some(right_ts) = self.right.peek().map(|r| time_of(r, &self.spec.right_time)) {
+                    match right_ts {
+                        Some(ts) if right_consumed(&self.spec, ts, left_ts) => {
+                            self.right.next();
+                        }
+                        Some(_) => break,
+                        None => {
+                            self.right.next();
+                        }
+                    }
+                }
+                if let Some(queue) = self.state.get_mut(key) {
+                    while queue.front()
+                        .and_then(|r| time_of(r, &self.spec.right_time))
+                        .is_some_and(|ts| right_consumed(&self.spec, ts, left_ts))
+                    {
+                        queue.pop_front();
+                    }
+                }
+                // 读取右侧直到该分组出现候选行
+                while self.state.get(key).map(|q| q.is_empty()).unwrap_or(true) {
+                    let Some(row) = self.right.next() else { break };
+                    if let Some(right_key) = group_key(&row, &self.spec.right_by) {
+                        self.state.entry(right_key).or_default().push_back(row);
+                    }
+                }
+                let candidate = self.state.get(key)?.front()?;
+                let gap = time_of(candidate, &self.spec.right_time)? - left_ts;
+                within_tolerance(gap, self.spec.tolerance).then(|| candidate.clone())
+            }
+        }
+    }
+}
+
+impl<'a, L, R> Iterator for AsofJoinStream<'a, L, R>
+where
+    L: Iterator<Item = Row>,
+    R: Iterator<Item = Row>,
+{
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let left_row = self.left.next()?;
+            let candidate = match (time_of(&left_row, &self.spec.left_time), group_key(&left_row, &self.spec.left_by)) {
+                (Some(ts), Some(key)) => self.find_match(ts, &key),
+                _ => None,
+            };
+
+            let joined = match candidate {
+                Some(right_row) => {
+                    let mut joined = left_row.clone();
+                    joined.extend(right_row);
+                    match self.residual.map(|p| p(&joined)).unwrap_or(Ok(true)) {
+                        Ok(true) => Some(joined),
+                        Ok(false) => None,
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                None => None,
+            };
+
+            match joined {
+                Some(row) => return Some(Ok(row)),
+                None if self.spec.join_type == JoinType::Left => {
+                    let mut row = left_row;
+                    for column in &self.right_columns {
+                        row.entry(column.clone()).or_insert(Value::Null);
+                    }
+                    return Some(Ok(row));
+                }
+                None => continue,
+            }
+        }
+    }
+}
+
+/// 窗口连接中的聚合
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowAggregate {
+    /// 聚合函数
+    pub function: AggregateFunction,
+    /// 右侧被聚合的列（COUNT可用`*`）
+    pub column: String,
+    /// 输出列名
+    pub output: String,
+}
+
+impl WindowAggregate {
+    /// 创建窗口聚合
+    pub fn new(function: AggregateFunction, column: impl Into<String>, output: impl Into<String>) -> Self {
+        Self { function, column: column.into(), output: output.into() }
+    }
+}
+
+/// 窗口连接定义：聚合右侧时间落在`[t - before, t + after]`内的行
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowJoinSpec {
+    /// 左侧时间列
+    pub left_time: String,
+    /// 右侧时间列
+    pub right_time: String,
+    /// 左侧分组键
+    pub left_by: Vec<String>,
+    /// 右侧分组键
+    pub right_by: Vec<String>,
+    /// 窗口向前延伸（纳秒）
+    pub before: i64,
+    /// 窗口向后延伸（纳秒）
+    pub after: i64,
+    /// 聚合列表
+    pub aggregates: Vec<WindowAggregate>,
+}
+
+impl WindowJoinSpec {
+    /// 创建对称的±Δ窗口连接
+    pub fn new(left_time: impl Into<String>, right_time: impl Into<String>, delta: i64) -> Self {
+        Self {
+            left_time: left_time.into(),
+            right_time: right_time.into(),
+            left_by: Vec::new(),
+            right_by: Vec::new(),
+            before: delta,
+            after: delta,
+            aggregates: Vec::new(),
+        }
+    }
+
+    /// 添加分组键
+    pub fn with_by(mut self, left: impl Into<String>, right: impl Into<String>) -> Self {
+        self.left_by.push(left.into());
+        self.right_by.push(right.into());
+        self
+    }
+
+    /// 添加聚合
+    pub fn with_aggregate(mut self, aggregate: WindowAggregate) -> Self {
+        self.aggregates.push(aggregate);
+        self
+    }
+}
+
+/// 窗口连接流：左右输入按时间升序，每个分组维护一个滑动窗口
+///
+/// 缓存的右侧行另按到达（即时间）顺序记录在`arrivals`中，每个左侧行的窗口下界作为水位线，
+/// 在所有分组上移出过期行，长时间没有左侧行的分组也不会无限增长。
+pub struct WindowJoinStream<L, R>
+where
+    L: Iterator<Item = Row>,
+    R: Iterator<Item = Row>,
+{
+    left: L,
+    right: Peekable<R>,
+    spec: WindowJoinSpec,
+    windows: HashMap<Vec<String>, VecDeque<Row>>,
+    /// 缓存的右侧行的(时间, 分组键)，按时间升序
+    arrivals: VecDeque<(i64, Vec<String>)>,
+}
+
+impl<L, R> WindowJoinStream<L, R>
+where
+    L: Iterator<Item = Row>,
+    R: Iterator<Item = Row>,
+{
+    /// 创建窗口连接流
+    pub fn new(left: L, right: R, spec: WindowJoinSpec) -> Result<Self> {
+        if spec.left_by.len() != spec.right_by.len() {
+            return Err(Error::validation("Window join requires the same number of BY keys on both sides"));
+        }
+        if spec.before < 0 || spec.after < 0 {
+            return Err(Error::validation("Window join bounds must be non-negative"));
+        }
+        Ok(Self { left, right: right.peekable(), spec, windows: HashMap::new(), arrivals: VecDeque::new() })
+    }
+
+    fn aggregate(&self, window: Option<&VecDeque<Row>>) -> Result<Row> {
+        let mut output = HashMap::new();
+        for aggregate in &self.spec.aggregates {
+            let rows = window.map(|w| w.iter()).into_iter().flatten();
+            let value = if aggregate.function == AggregateFunction::Count && aggregate.column == "*" {
+                Value::Int64(window.map(|w| w.len()).unwrap_or(0) as i64)
+            } else {
+                let values: Vec<Value> = rows
+                    .filter_map(|r| r.get(&aggregate.column))
+                    .filter(|v| !matches!(v, Value::Null))
+                    .cloned()
+                    .collect();
+                if values.is_empty() && aggregate.function != AggregateFunction::Count {
+                    Value::Null
+                } else {
+                    aggregate.function.apply(&values)?
+                }
+            };
+            output.insert(aggregate.output.clone(), value);
+        }
+        Ok(output)
+    }
+}
+
+impl<L, R> Iterator for WindowJoinStream<L, R>
+where
+    L: Iterator<Item = Row>,
+    R: Iterator<Item = Row>,
+{
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut left_row = self.left.next()?;
+        let (Some(ts), Some(key)) = (time_of(&left_row, &self.spec.left_time), group_key(&left_row, &self.spec.left_by)) else {
+            return Some(self.aggregate(None).map(|aggs| {
+                left_row.extend(aggs);
+                left_row
+            }));
+        };
+
+        // 读入窗口上界之内的右侧行
+        let upper = ts.saturating_add(self.spec.after);
+        while let Some(right_ts) = self.right.peek().map(|r| time_of(r, &self.spec.right_time)) {
+            match right_ts {
+                Some(right_ts) if right_ts > upper => break,
+                _ => {}
+            }
+            let row = self.right.next().expect("peeked");
+            if let (Some(right_ts), Some(right_key)) = (right_ts, group_key(&row, &self.spec.right_by)) {
+                self.arrivals.push_back((right_ts, right_key.clone()));
+                self.windows.entry(right_key).or_default().push_back(row);
+            }
+        }
+
+        // 以窗口下界为水位线，移出所有分组中更早的行并释放空窗口
+        let lower = ts.saturating_sub(self.spec.before);
+        while self.arrivals.front().is_some_and(|(t, _)| *t < lower) {
+            let (_, right_key) = self.arrivals.pop_front().expect("checked front");
+            if let Some(window) = self.windows.get_mut(&right_key) {
+                window.pop_front();
+                if window.is_empty() {
+                    self.windows.remove(&right_key);
+                }
+            }
+        }
+
+        Some(self.aggregate(self.windows.get(&key)).map(|aggs| {
+            left_row.extend(aggs);
+            left_row
+        }))
+    }
+}
+
+/// 右侧行相对左侧时间是否已可消费：向后匹配时表示可成为候选，
+/// 向前匹配时表示已过期
+fn right_consumed(spec: &AsofSpec, right_ts: i64, left_ts: i64) -> bool {
+    match (spec.direction, spec.inclusive) {
+        (AsofDirection::Backward, true) => right_ts <= left_ts,
+        (AsofDirection::Backward, false) => right_ts < left_ts,
+        (AsofDirection::Forward, true) => right_ts < left_ts,
+        (AsofDirection::Forward, false) => right_ts <= left_ts,
+    }
+}
+
+fn within_tolerance(gap: i64, tolerance: Option<i64>) -> bool {
+    tolerance.map(|t| gap <= t).unwrap_or(true)
+}
+
+/// 读取行的时间列（纳秒）
+pub fn time_of(row: &Row, column: &str) -> Option<i64> {
+    row.get(column).and_then(value_as_timestamp).map(|ts| ts.as_nanos())
+}
+
+/// 分组键；含NULL时返回None（不参与匹配）
+fn group_key(row: &Row, columns: &[String]) -> Option<Vec<String>> {
+    columns.iter()
+        .map(|c| match row.get(c) {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(crate::expressions::display_value(value)),
+        })
+        .collect()
+}
+
+/// 把ASOF JOIN的扩展子句改写为sqlparser可解析的形式
+///
+/// - `ASOF JOIN quotes MATCH_CONDITION(...)`：补全别名，避免`MATCH_CONDITION`被当成别名
+/// - `BY a, b`：改写为`USING (a, b)`
+/// - `TOLERANCE '5s'`：改写为匹配条件中的`左时间 - 右时间 <= 纳秒数`
+pub fn rewrite_asof_sql(sql: &str) -> Result<String> {
+    static ALIAS: OnceLock<Regex> = OnceLock::new();
+    static MATCH: OnceLock<Regex> = OnceLock::new();
+    static BY: OnceLock<Regex> = OnceLock::new();
+    static TOLERANCE: OnceLock<Regex> = OnceLock::new();
+
+    let match_re = MATCH.get_or_init(|| Regex::new(r"(?i)\bMATCH_CONDITION\s*\(").expect("valid regex"));
+    if !match_re.is_match(sql) {
+        return Ok(sql.to_string());
+    }
+
+    let alias_re = ALIAS.get_or_init(|| {
+        Regex::new(r"(?i)\bASOF\s+JOIN\s+(?:[A-Za-z_][\w]*\.)*([A-Za-z_][\w]*)\s+MATCH_CONDITION\b").expect("valid regex")
+    });
+    let sql = alias_re.replace_all(sql, |caps: &regex::Captures<'_>| {
+        let whole = &caps[0];
+        let table_end = whole.to_uppercase().rfind("MATCH_CONDITION").unwrap_or(whole.len());
+        format!("{} AS {} MATCH_CONDITION", whole[..table_end].trim_end(), &caps[1])
+    }).into_owned();
+
+    let by_re = BY.get_or_init(|| {
+        Regex::new(r"(?i)^\s+BY\s+(\([^)]*\)|[A-Za-z_][\w.]*(?:\s*,\s*[A-Za-z_][\w.]*)*)").expect("valid regex")
+    });
+    let tolerance_re = TOLERANCE.get_or_init(|| {
+        Regex::new(r"(?i)^\s+TOLERANCE\s+(?:INTERVAL\s+)?(?:'([^']*)'|([0-9][\w]*))").expect("valid regex")
+    });
+
+    let mut output = String::with_capacity(sql.len());
+    let mut rest = sql.as_str();
+    while let Some(found) = match_re.find(rest) {
+        output.push_str(&rest[..found.start()]);
+        let open = found.end() - 1;
+        let close = matching_paren(rest, open)
+            .ok_or_else(|| Error::parse("Unbalanced parentheses in MATCH_CONDITION"))?;
+        let mut condition = rest[open + 1..close].to_string();
+        let mut tail = &rest[close + 1..];
+
+        let mut by_columns = None;
+        loop {
+            if let Some(caps) = by_re.captures(tail) {
+                let list = caps[1].trim_matches(|c| c == '(' || c == ')');
+                let columns: Vec<&str> = list.split(',')
+                    .map(|c| c.trim().rsplit('.').next().unwrap_or(""))
+                    .collect();
+                by_columns = Some(columns.join(", "));
+                tail = &tail[caps[0].len()..];
+            } else if let Some(caps) = tolerance_re.captures(tail) {
+                let text = caps.get(1).or_else(|| caps.get(2)).map(|m| m.as_str()).unwrap_or_default();
+                let nanos = crate::expressions::parse_interval_nanos(text)?;
+                condition = add_tolerance(&condition, nanos)?;
+                tail = &tail[caps[0].len()..];
+            } else {
+                break;
+            }
+        }
+
+        output.push_str(&format!("MATCH_CONDITION ({})", condition));
+        if let Some(columns) = by_columns {
+            output.push_str(&format!(" USING ({})", columns));
+        }
+        rest = tail;
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn matching_paren(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_quote = false;
+    for (i, c) in text.char_indices().skip_while(|(i, _)| *i < open) {
+        match c {
+            '\'' => in_quote = !in_quote,
+            '(' if !in_quote => depth += 1,
+            ')' if !in_quote => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn add_tolerance(condition: &str, nanos: i64) -> Result<String> {
+    let expr = Parser::new(&GenericDialect {})
+        .try_with_sql(condition)
+        .and_then(|mut p| p.parse_expr())
+        .map_err(|e| Error::parse(format!("Invalid MATCH_CONDITION: {}", e)))?;
+    let Expr::BinaryOp { left, op, right } = &expr else {
+        return Err(Error::parse("TOLERANCE requires MATCH_CONDITION to be a single time comparison"));
+    };
+    let (newer, older) = match op {
+        BinaryOperator::Gt | BinaryOperator::GtEq => (left, right),
+        BinaryOperator::Lt | BinaryOperator::LtEq => (right, left),
+        _ => return Err(Error::parse("MATCH_CONDITION must compare timestamps with >=, >, <= or <")),
+    };
+    Ok(format!("{} AND {} - {} <= {}", expr, newer, older, nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_core::types::TimestampNs;
+
+    fn quote(ts: i64, symbol: &str, bid: f64) -> Row {
+        let mut row = HashMap::new();
+        row.insert("q.ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(ts)));
+        row.insert("q.symbol".to_string(), Value::String(symbol.to_string()));
+        row.insert("q.bid".to_string(), Value::Float64(bid));
+        row
+    }
+
+    fn trade(ts: i64, symbol: &str) -> Row {
+        let mut row = HashMap::new();
+        row.insert("t.ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(ts)));
+        row.insert("t.symbol".to_string(), Value::String(symbol.to_string()));
+        row
+    }
+
+    fn quotes() -> Vec<Row> {
+        vec![quote(10, "AAPL", 1.0), quote(15, "MSFT", 2.0), quote(20, "AAPL", 3.0), quote(40, "AAPL", 4.0)]
+    }
+
+    #[test]
+    fn test_asof_backward_with_by_and_tolerance() {
+        let trades = vec![trade(5, "AAPL"), trade(20, "AAPL"), trade(25, "MSFT"), trade(35, "AAPL")];
+        let spec = AsofSpec::new("t.ts", "q.ts").with_by("t.symbol", "q.symbol");
+        let rows: Vec<Row> = AsofJoinStream::new(trades.clone().into_iter(), quotes().into_iter(), spec.clone())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].get("q.bid"), None);
+        assert_eq!(rows[1].get("q.bid"), Some(&Value::Float64(3.0)));
+        assert_eq!(rows[2].get("q.bid"), Some(&Value::Float64(2.0)));
+        assert_eq!(rows[3].get("q.bid"), Some(&Value::Float64(3.0)));
+
+        let rows: Vec<Row> = AsofJoinStream::new(trades.into_iter(), quotes().into_iter(), spec.with_tolerance(10))
+            .unwrap()
+            .with_right_columns(vec!["q.bid".to_string()])
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(rows[1].get("q.bid"), Some(&Value::Float64(3.0)));
+        assert_eq!(rows[2].get("q.bid"), Some(&Value::Float64(2.0)));
+        assert_eq!(rows[3].get("q.bid"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn test_asof_forward() {
+        let trades = vec![trade(12, "AAPL"), trade(20, "AAPL"), trade(41, "AAPL")];
+        let spec = AsofSpec::new("t.ts", "q.ts")
+            .with_by("t.symbol", "q.symbol")
+            .with_direction(AsofDirection::Forward, false)
+            .with_join_type(JoinType::Inner);
+        let rows: Vec<Row> = AsofJoinStream::new(trades.into_iter(), quotes().into_iter(), spec)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get("q.bid"), Some(&Value::Float64(3.0)));
+        assert_eq!(rows[1].get("q.bid"), Some(&Value::Float64(4.0)));
+    }
+
+    #[test]
+    fn test_window_join() {
+        let trades = vec![trade(15, "AAPL"), trade(30, "AAPL")];
+        let spec = WindowJoinSpec::new("t.ts", "q.ts", 10)
+            .with_by("t.symbol", "q.symbol")
+            .with_aggregate(WindowAggregate::new(AggregateFunction::Count, "*", "quote_count"))
+            .with_aggregate(WindowAggregate::new(AggregateFunction::Avg, "q.bid", "avg_bid"));
+        let rows: Vec<Row> = WindowJoinStream::new(trades.into_iter(), quotes().into_iter(), spec)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(rows[0].get("quote_count"), Some(&Value::Int64(2)));
+        assert_eq!(rows[0].get("avg_bid"), Some(&Value::Float64(2.0)));
+        assert_eq!(rows[1].get("quote_count"), Some(&Value::Int64(2)));
+        assert_eq!(rows[1].get("avg_bid"), Some(&Value::Float64(3.5)));
+    }
+
+    #[test]
+    fn test_window_join_evicts_idle_keys() {
+        // 每个代码只有一笔报价，之后再没有对应的成交
+        let quotes: Vec<Row> = (0..100).map(|i| quote(i, &format!("S{}", i), 1.0)).collect();
+        let trades = vec![trade(50, "S45"), trade(500, "AAPL")];
+        let spec = WindowJoinSpec::new("t.ts", "q.ts", 10)
+            .with_by("t.symbol", "q.symbol")
+            .with_aggregate(WindowAggregate::new(AggregateFunction::Count, "*", "quote_count"));
+        let mut stream = WindowJoinStream::new(trades.into_iter(), quotes.into_iter(), spec).unwrap();
+
+        assert_eq!(stream.next().unwrap().unwrap().get("quote_count"), Some(&Value::Int64(1)));
+        // 只保留[40, 60]内的报价
+        assert_eq!((stream.windows.len(), stream.arrivals.len()), (21, 21));
+        assert_eq!(stream.next().unwrap().unwrap().get("quote_count"), Some(&Value::Int64(0)));
+        assert!(stream.windows.is_empty() && stream.arrivals.is_empty());
+    }
+
+    #[test]
+    fn test_rewrite_asof_sql() {
+        let sql = "SELECT * FROM trades t ASOF JOIN quotes MATCH_CONDITION(t.ts >= quotes.ts) BY symbol TOLERANCE '1s'";
+        let rewritten = rewrite_asof_sql(sql).unwrap();
+        assert!(rewritten.contains("quotes AS quotes MATCH_CONDITION"));
+        assert!(rewritten.contains("t.ts - quotes.ts <= 1000000000"));
+        assert!(rewritten.ends_with("USING (symbol)"));
+        assert!(Parser::parse_sql(&GenericDialect {}, &rewritten).is_ok());
+
+        assert_eq!(rewrite_asof_sql("SELECT 1").unwrap(), "SELECT 1");
+    }
+}
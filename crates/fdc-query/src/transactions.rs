@@ -0,0 +1,669 @@
+//! SQL transactions over catalog tables
+//!
+//! `BEGIN` / `COMMIT` / `ROLLBACK` / `SAVEPOINT` group statements of one
+//! session into a transaction. Writes are buffered per table and row key in
+//! the transaction and applied to the storage engine as a single batch at
+//! `COMMIT`, so engines that report `supports_transactions` commit all tables
+//! of the transaction atomically. Reads inside the transaction see its own
+//! uncommitted writes on top of the committed data.
+//!
+//! # Isolation levels
+//!
+//! * `SNAPSHOT` (default; `REPEATABLE READ` is an alias): every read of the
+//!   transaction sees the data committed when it began. The catalog keeps an
+//!   undo log of before-images for rows written while snapshots are open and
+//!   rolls newer writes back when a snapshot reads. Concurrent writers follow
+//!   first-committer-wins: a transaction whose write set overlaps a row
+//!   committed after it began fails at `COMMIT` with a serialization error and
+//!   is rolled back.
+//! * `READ COMMITTED` (`READ UNCOMMITTED` is an alias): every statement sees
+//!   the latest committed data and commits never conflict.
+//!
+//! `SERIALIZABLE` is not supported. Only writes that go through the catalog are
+//! versioned; data written to the storage engine directly (for example by
+//! ingestion pipelines) is visible to snapshots as soon as it lands. DDL and
+//! other commands inside a transaction take effect immediately.
+
+use crate::{catalog::decode_row, joins::Row};
+use dashmap::DashMap;
+use fdc_core::error::{Error, Result};
+use fdc_storage::engine::{BatchOperation, StorageEngine};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+/// 提交时检测到写冲突的错误信息（PostgreSQL客户端据此重试，SQLSTATE 40001）
+pub const SERIALIZATION_FAILURE: &str = "could not serialize access due to concurrent update";
+
+/// 事务已失败时执行语句的错误信息（SQLSTATE 25P02）
+pub const TRANSACTION_ABORTED: &str = "current transaction is aborted, commands ignored until end of transaction block";
+
+/// 事务隔离级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IsolationLevel {
+    /// 快照隔离：读取事务开始时已提交的数据，写冲突时先提交者胜出
+    #[default]
+    Snapshot,
+    /// 读已提交：每条语句读取最新已提交的数据
+    ReadCommitted,
+}
+
+impl IsolationLevel {
+    /// 解析SQL中的隔离级别名（不区分大小写，单词间可有多个空格）
+    pub fn parse(text: &str) -> Result<Self> {
+        let words: Vec<String> = text.split_whitespace().map(str::to_uppercase).collect();
+        match words.join(" ").as_str() {
+            "SNAPSHOT" | "REPEATABLE READ" => Ok(Self::Snapshot),
+            "READ COMMITTED" | "READ UNCOMMITTED" => Ok(Self::ReadCommitted),
+            "SERIALIZABLE" => Err(Error::unimplemented(
+                "SERIALIZABLE isolation; supported levels are SNAPSHOT (REPEATABLE READ) and READ COMMITTED",
+            )),
+            other => Err(Error::validation(format!("Unknown isolation level: {}", other))),
+        }
+    }
+
+    /// 解析事务模式列表（如`ISOLATION LEVEL READ COMMITTED, READ WRITE`）中的隔离级别，未指定时返回None
+    pub fn parse_modes(text: &str) -> Result<Option<Self>> {
+        let modes: Vec<String> = text.split_whitespace().map(str::to_uppercase).collect();
+        parse_modes(&modes)
+    }
+
+    /// PostgreSQL中的等价名称
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Snapshot => "repeatable read",
+            Self::ReadCommitted => "read committed",
+        }
+    }
+}
+
+/// 事务控制语句
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionCommand {
+    /// `BEGIN [WORK | TRANSACTION]` / `START TRANSACTION`，可带`ISOLATION LEVEL`
+    Begin { isolation: Option<IsolationLevel> },
+    /// `COMMIT` / `END`
+    Commit,
+    /// `ROLLBACK` / `ABORT`，带保存点时只回滚到该保存点
+    Rollback { savepoint: Option<String> },
+    /// `SAVEPOINT name`
+    Savepoint { name: String },
+    /// `RELEASE [SAVEPOINT] name`
+    Release { name: String },
+}
+
+impl TransactionCommand {
+    /// 解析事务控制语句，其他语句返回None
+    pub fn parse(sql: &str) -> Result<Option<Self>> {
+        let sql = skip_comments(sql).trim_end().trim_end_matches(';');
+        let words: Vec<&str> = sql.split_whitespace().collect();
+        let Some(first) = words.first() else {
+            return Ok(None);
+        };
+        let upper: Vec<String> = words.iter().map(|w| w.to_uppercase()).collect();
+        let rest = &upper[1..];
+        // 可省略的WORK / TRANSACTION
+        let optional_noise = |rest: &[String]| -> usize {
+            usize::from(matches!(rest.first().map(String::as_str), Some("WORK" | "TRANSACTION")))
+        };
+        let command = match first.to_uppercase().as_str() {
+            "BEGIN" => Self::Begin { isolation: parse_modes(&rest[optional_noise(rest)..])? },
+            "START" if rest.first().map(String::as_str) == Some("TRANSACTION") => {
+                Self::Begin { isolation: parse_modes(&rest[1..])? }
+            }
+            "COMMIT" | "END" => {
+                expect_end(&rest[optional_noise(rest)..], first)?;
+                Self::Commit
+            }
+            "ROLLBACK" | "ABORT" => {
+                let skip = optional_noise(rest);
+                let tail = &words[1 + skip..];
+                match rest.get(skip).map(String::as_str) {
+                    None => Self::Rollback { savepoint: None },
+                    Some("TO") => {
+                        let name = match rest.get(skip + 1).map(String::as_str) {
+                            Some("SAVEPOINT") => tail.get(2),
+                            _ => tail.get(1),
+                        };
+                        let name = name.ok_or_else(|| Error::validation("ROLLBACK TO requires a savepoint name"))?;
+                        Self::Rollback { savepoint: Some(savepoint_name(name)) }
+                    }
+                    Some(other) => return Err(Error::validation(format!("Unexpected {} after {}", other, first))),
+                }
+            }
+            "SAVEPOINT" => match words.as_slice() {
+                [_, name] => Self::Savepoint { name: savepoint_name(name) },
+                _ => return Err(Error::validation("SAVEPOINT requires exactly one savepoint name")),
+            },
+            "RELEASE" => {
+                let name = match rest.first().map(String::as_str) {
+                    Some("SAVEPOINT") => words.get(2),
+                    _ => words.get(1),
+                };
+                let name = name.ok_or_else(|| Error::validation("RELEASE requires a savepoint name"))?;
+                Self::Release { name: savepoint_name(name) }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// 解析BEGIN之后的事务模式，只关心ISOLATION LEVEL；READ WRITE等其余模式忽略
+fn parse_modes(modes: &[String]) -> Result<Option<IsolationLevel>> {
+    let modes: Vec<&str> = modes.iter().map(|m| m.trim_end_matches(',')).collect();
+    match modes.iter().position(|m| *m == "ISOLATION") {
+        Some(at) if modes.get(at + 1) == Some(&"LEVEL") => {
+            let level: Vec<&str> = modes[at + 2..].iter()
+                .take_while(|word| matches!(**word, "SNAPSHOT" | "REPEATABLE" | "READ" | "COMMITTED" | "UNCOMMITTED" | "SERIALIZABLE"))
+                .copied()
+                .collect();
+            // READ COMMITTED后紧跟的READ WRITE / READ ONLY不属于隔离级别
+            let level = match level.as_slice() {
+                ["READ", second, ..] => vec!["READ", *second],
+                [first, second, ..] if *first == "REPEATABLE" => vec![*first, *second],
+                [first, ..] => vec![*first],
+                [] => return Err(Error::validation("ISOLATION LEVEL requires a level")),
+            };
+            IsolationLevel::parse(&level.join(" ")).map(Some)
+        }
+        Some(_) => Err(Error::validation("Expected LEVEL after ISOLATION")),
+        None => Ok(None),
+    }
+}
+
+/// 跳过语句开头的注释
+fn skip_comments(mut sql: &str) -> &str {
+    loop {
+        sql = sql.trim_start();
+        if let Some(rest) = sql.strip_prefix("--") {
+            sql = rest.split_once('\n').map_or("", |(_, after)| after);
+        } else if let Some(rest) = sql.strip_prefix("/*") {
+            sql = rest.split_once("*/").map_or("", |(_, after)| after);
+        } else {
+            return sql;
+        }
+    }
+}
+
+fn expect_end(rest: &[String], keyword: &str) -> Result<()> {
+    match rest.first() {
+        None => Ok(()),
+        Some(word) if word == "AND" => Err(Error::unimplemented(format!("{} AND CHAIN", keyword.to_uppercase()))),
+        Some(word) => Err(Error::validation(format!("Unexpected {} after {}", word, keyword.to_uppercase()))),
+    }
+}
+
+/// 保存点名：带引号时保留原样，否则转为小写
+fn savepoint_name(name: &str) -> String {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) => quoted.to_string(),
+        None => name.to_lowercase(),
+    }
+}
+
+/// 会话的事务状态（对应PostgreSQL ReadyForQuery中的I/T/E）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionStatus {
+    /// 不在事务中
+    Idle,
+    /// 事务进行中
+    Active,
+    /// 事务中有语句失败，只接受COMMIT/ROLLBACK
+    Failed,
+}
+
+/// 目录写入的撤销日志，为活跃快照保留被覆盖行的前像
+///
+/// 目录写入按提交顺序串行化并依次编号；有活跃快照时，写入前先读取被覆盖键的旧值。
+/// 快照读取时把编号大于快照的写入逐键回滚到快照时刻的值。没有快照引用的记录随即清除。
+#[derive(Debug, Default)]
+pub(crate) struct VersionStore {
+    /// 提交锁：写入、快照登记和快照读取撤销日志时持有
+    commit_lock: tokio::sync::Mutex<()>,
+    state: Mutex<VersionState>,
+}
+
+#[derive(Debug, Default)]
+struct VersionState {
+    /// 最近一次提交的序号
+    sequence: u64,
+    /// 活跃快照的序号 -> 引用数
+    snapshots: BTreeMap<u64, usize>,
+    /// 按序号递增排列的前像
+    undo: Vec<UndoRecord>,
+}
+
+#[derive(Debug)]
+struct UndoRecord {
+    sequence: u64,
+    key: Vec<u8>,
+    before: Option<Vec<u8>>,
+}
+
+impl VersionStore {
+    /// 登记当前已提交状态的快照
+    pub(crate) async fn snapshot(self: &Arc<Self>) -> Snapshot {
+        let _commit = self.commit_lock.lock().await;
+        let mut state = self.state.lock();
+        let sequence = state.sequence;
+        *state.snapshots.entry(sequence).or_default() += 1;
+        Snapshot { store: self.clone(), sequence }
+    }
+
+    /// 写入一批操作
+    pub(crate) async fn apply(&self, storage: &dyn StorageEngine, operations: Vec<BatchOperation>) -> Result<()> {
+        let _commit = self.commit_lock.lock().await;
+        self.apply_locked(storage, operations).await
+    }
+
+    /// 提交事务的写入
+    ///
+    /// 带快照时先检查快照之后是否已有其他事务提交了同一行；不带快照（读已提交与隐式事务）的读取
+    /// 发生在提交锁之外，逐键核对写入前读到的行`expected`仍是当前已提交的值，避免丢失更新。
+    pub(crate) async fn commit(
+        &self,
+        storage: &dyn StorageEngine,
+        operations: Vec<BatchOperation>,
+        snapshot: Option<&Snapshot>,
+        expected: &[(Vec<u8>, Row)],
+    ) -> Result<()> {
+        let _commit = self.commit_lock.lock().await;
+        match snapshot {
+            Some(snapshot) => {
+                let keys: HashSet<&[u8]> = operations.iter().map(operation_key).collect();
+                let state = self.state.lock();
+                let conflict = state.undo.iter()
+                    .any(|record| record.sequence > snapshot.sequence && keys.contains(record.key.as_slice()));
+                if conflict {
+                    return Err(Error::query(SERIALIZATION_FAILURE));
+                }
+            }
+            None => {
+                for (key, before) in expected {
+                    let current = storage.get(key).await?.map(|bytes| decode_row(&bytes)).transpose()?;
+                    if current.as_ref() != Some(before) {
+                        return Err(Error::query(SERIALIZATION_FAILURE));
+                    }
+                }
+            }
+        }
+        self.apply_locked(storage, operations).await
+    }
+
+    async fn apply_locked(&self, storage: &dyn StorageEngine, operations: Vec<BatchOperation>) -> Result<()> {
+        let capture = !self.state.lock().snapshots.is_empty();
+        let mut before = Vec::new();
+        if capture {
+            for operation in &operations {
+                let key = operation_key(operation);
+                before.push((key.to_vec(), storage.get(key).await?));
+            }
+        }
+        storage.batch(operations).await?;
+        let mut state = self.state.lock();
+        state.sequence += 1;
+        let sequence = state.sequence;
+        state.undo.extend(before.into_iter().map(|(key, before)| UndoRecord { sequence, key, before }));
+        Ok(())
+    }
+
+    fn release(&self, sequence: u64) {
+        let mut state = self.state.lock();
+        if let Some(count) = state.snapshots.get_mut(&sequence) {
+            *count -= 1;
+            if *count == 0 {
+                state.snapshots.remove(&sequence);
+            }
+        }
+        match state.snapshots.keys().next().copied() {
+            Some(oldest) => state.undo.retain(|record| record.sequence > oldest),
+            None => state.undo.clear(),
+        }
+    }
+}
+
+fn operation_key(operation: &BatchOperation) -> &[u8] {
+    match operation {
+        BatchOperation::Put { key, .. } | BatchOperation::Delete { key } => key,
+    }
+}
+
+/// 已提交状态的快照，释放时清理不再需要的撤销记录
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+    store: Arc<VersionStore>,
+    sequence: u64,
+}
+
+impl Snapshot {
+    /// 把`[start, end)`内从当前存储读到的键值还原为快照时刻的版本
+    ///
+    /// 须在读取存储之后调用：持有提交锁读取撤销日志，读取期间完成的写入都已记录前像。
+    pub(crate) async fn restore(&self, entries: &mut BTreeMap<Vec<u8>, Vec<u8>>, start: &[u8], end: &[u8]) {
+        let _commit = self.store.commit_lock.lock().await;
+        let state = self.store.state.lock();
+        // 快照之后每个键的第一条前像即快照时刻的值
+        let mut restored: HashMap<&[u8], &Option<Vec<u8>>> = HashMap::new();
+        for record in state.undo.iter().filter(|record| record.sequence > self.sequence) {
+            if record.key.as_slice() >= start && record.key.as_slice() < end {
+                restored.entry(record.key.as_slice()).or_insert(&record.before);
+            }
+        }
+        for (key, before) in restored {
+            match before {
+                Some(value) => entries.insert(key.to_vec(), value.clone()),
+                None => entries.remove(key),
+            };
+        }
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.store.release(self.sequence);
+    }
+}
+
+/// 事务中缓冲的一行写入
+#[derive(Debug, Clone)]
+pub(crate) struct PendingWrite {
+    /// 写入后的行，None表示删除
+    pub(crate) row: Option<Row>,
+    /// 是否由INSERT写入（提交时通知插入观察者）
+    pub(crate) inserted: bool,
+    /// 本事务第一次写该键前已提交的行（新插入的键为None），提交时据此失效旧行所在的时间范围
+    pub(crate) before: Option<Row>,
+}
+
+/// 按表（小写）和行键缓冲的写入
+pub(crate) type WriteSet = BTreeMap<String, BTreeMap<Vec<u8>, PendingWrite>>;
+
+#[derive(Debug, Default)]
+struct TransactionState {
+    writes: WriteSet,
+    /// 保存点及其建立时的写入集合
+    savepoints: Vec<(String, WriteSet)>,
+    failed: bool,
+}
+
+/// 一个SQL事务：隔离级别、快照与尚未提交的写入
+#[derive(Debug)]
+pub struct Transaction {
+    id: String,
+    isolation: IsolationLevel,
+    snapshot: Option<Snapshot>,
+    state: Mutex<TransactionState>,
+}
+
+impl Transaction {
+    pub(crate) fn new(isolation: IsolationLevel, snapshot: Option<Snapshot>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            isolation,
+            snapshot,
+            state: Mutex::new(TransactionState::default()),
+        }
+    }
+
+    /// 单条写语句的隐式事务：读取最新数据，语句结束时立即提交
+    pub(crate) fn autocommit() -> Self {
+        Self::new(IsolationLevel::ReadCommitted, None)
+    }
+
+    /// 事务ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 隔离级别
+    pub fn isolation(&self) -> IsolationLevel {
+        self.isolation
+    }
+
+    /// 是否有语句失败
+    pub fn is_failed(&self) -> bool {
+        self.state.lock().failed
+    }
+
+    /// 缓冲的行写入数
+    pub fn pending_writes(&self) -> usize {
+        self.state.lock().writes.values().map(BTreeMap::len).sum()
+    }
+
+    pub(crate) fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// 标记为失败，此后只接受COMMIT（按回滚处理）与ROLLBACK
+    pub(crate) fn fail(&self) {
+        self.state.lock().failed = true;
+    }
+
+    /// 缓冲一行写入，`before`为写入前读到的行；同一行先插入后更新时仍视为插入
+    pub(crate) fn put(&self, table: &str, key: Vec<u8>, row: Row, inserted: bool, before: Option<Row>) {
+        self.buffer(table, key, Some(row), inserted, before);
+    }
+
+    /// 缓冲一行删除，`before`为被删除的行
+    pub(crate) fn delete(&self, table: &str, key: Vec<u8>, before: Row) {
+        self.buffer(table, key, None, false, Some(before));
+    }
+
+    /// 同一键只保留第一次写入时的前像：之后读到的是本事务自己的写入，而非已提交的行
+    fn buffer(&self, table: &str, key: Vec<u8>, row: Option<Row>, inserted: bool, before: Option<Row>) {
+        let mut state = self.state.lock();
+        let rows = state.writes.entry(table.to_lowercase()).or_default();
+        let (inserted, before) = match rows.get(&key) {
+            Some(previous) => (row.is_some() && (inserted || (previous.inserted && previous.row.is_some())), previous.before.clone()),
+            None => (row.is_some() && inserted, before),
+        };
+        rows.insert(key, PendingWrite { row, inserted, before });
+    }
+
+    /// 把本事务对`table`的写入叠加到已提交的行上
+    pub(crate) fn overlay(&self, table: &str, entries: &mut BTreeMap<Vec<u8>, Row>) {
+        let state = self.state.lock();
+        if let Some(rows) = state.writes.get(&table.to_lowercase()) {
+            for (key, write) in rows {
+                match &write.row {
+                    Some(row) => entries.insert(key.clone(), row.clone()),
+                    None => entries.remove(key),
+                };
+            }
+        }
+    }
+
+    /// 本事务对某行的写入：外层None表示未写过，内层None表示已删除
+    pub(crate) fn pending(&self, table: &str, key: &[u8]) -> Option<Option<Row>> {
+        let state = self.state.lock();
+        state.writes.get(&table.to_lowercase())?.get(key).map(|write| write.row.clone())
+    }
+
+    /// 全部缓冲写入
+    pub(crate) fn writes(&self) -> WriteSet {
+        self.state.lock().writes.clone()
+    }
+
+    /// 建立保存点（同名保存点可重复建立，回滚时取最近的一个）
+    pub(crate) fn savepoint(&self, name: &str) {
+        let mut state = self.state.lock();
+        let writes = state.writes.clone();
+        state.savepoints.push((name.to_string(), writes));
+    }
+
+    /// 回滚到保存点：撤销其后的写入和保存点，保存点本身保留，失败状态清除
+    pub(crate) fn rollback_to(&self, name: &str) -> Result<()> {
+        let mut state = self.state.lock();
+        let at = state.savepoints.iter().rposition(|(savepoint, _)| savepoint == name)
+            .ok_or_else(|| Error::not_found(format!("savepoint {}", name)))?;
+        state.savepoints.truncate(at + 1);
+        state.writes = state.savepoints[at].1.clone();
+        state.failed = false;
+        Ok(())
+    }
+
+    /// 释放保存点及其后建立的保存点，写入保留
+    pub(crate) fn release(&self, name: &str) -> Result<()> {
+        let mut state = self.state.lock();
+        let at = state.savepoints.iter().rposition(|(savepoint, _)| savepoint == name)
+            .ok_or_else(|| Error::not_found(format!("savepoint {}", name)))?;
+        state.savepoints.truncate(at);
+        Ok(())
+    }
+}
+
+/// 按会话登记的进行中事务
+#[derive(Debug, Default)]
+pub struct TransactionManager {
+    sessions: DashMap<String, Arc<Transaction>>,
+}
+
+impl TransactionManager {
+    /// 创建空的事务表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 会话的进行中事务
+    pub fn get(&self, session_id: &str) -> Option<Arc<Transaction>> {
+        self.sessions.get(session_id).map(|transaction| transaction.clone())
+    }
+
+    /// 会话的事务状态
+    pub fn status(&self, session_id: &str) -> TransactionStatus {
+        match self.sessions.get(session_id) {
+            Some(transaction) if transaction.is_failed() => TransactionStatus::Failed,
+            Some(_) => TransactionStatus::Active,
+            None => TransactionStatus::Idle,
+        }
+    }
+
+    /// 登记会话的新事务，会话已在事务中时报错
+    pub(crate) fn begin(&self, session_id: &str, transaction: Transaction) -> Result<Arc<Transaction>> {
+        match self.sessions.entry(session_id.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => {
+                Err(Error::validation("There is already a transaction in progress"))
+            }
+            dashmap::mapref::entry::Entry::Vacant(slot) => Ok(slot.insert(Arc::new(transaction)).clone()),
+        }
+    }
+
+    /// 取出会话的事务（提交或回滚时）
+    pub(crate) fn take(&self, session_id: &str) -> Option<Arc<Transaction>> {
+        self.sessions.remove(session_id).map(|(_, transaction)| transaction)
+    }
+
+    /// 会话结束：回滚其未提交的事务，返回是否有事务被回滚
+    pub fn end_session(&self, session_id: &str) -> bool {
+        self.take(session_id).is_some()
+    }
+
+    /// 进行中的事务数
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// 是否没有进行中的事务
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_storage::engines::memory::MemoryEngine;
+
+    #[test]
+    fn test_parse_transaction_commands() {
+        let parse = |sql: &str| TransactionCommand::parse(sql).unwrap();
+        assert_eq!(parse("BEGIN"), Some(TransactionCommand::Begin { isolation: None }));
+        assert_eq!(parse("begin work;"), Some(TransactionCommand::Begin { isolation: None }));
+        assert_eq!(
+            parse("START TRANSACTION ISOLATION LEVEL READ COMMITTED, READ WRITE"),
+            Some(TransactionCommand::Begin { isolation: Some(IsolationLevel::ReadCommitted) }),
+        );
+        assert_eq!(
+            parse("BEGIN TRANSACTION ISOLATION LEVEL REPEATABLE READ"),
+            Some(TransactionCommand::Begin { isolation: Some(IsolationLevel::Snapshot) }),
+        );
+        assert_eq!(parse("END"), Some(TransactionCommand::Commit));
+        assert_eq!(parse("-- settle\n/* fees */ COMMIT;"), Some(TransactionCommand::Commit));
+        assert_eq!(parse("COMMIT TRANSACTION"), Some(TransactionCommand::Commit));
+        assert_eq!(parse("ABORT"), Some(TransactionCommand::Rollback { savepoint: None }));
+        assert_eq!(parse("ROLLBACK TO SAVEPOINT Before_Fees"), Some(TransactionCommand::Rollback { savepoint: Some("before_fees".to_string()) }));
+        assert_eq!(parse("ROLLBACK WORK TO \"Sp\""), Some(TransactionCommand::Rollback { savepoint: Some("Sp".to_string()) }));
+        assert_eq!(parse("SAVEPOINT sp1"), Some(TransactionCommand::Savepoint { name: "sp1".to_string() }));
+        assert_eq!(parse("RELEASE SAVEPOINT sp1"), Some(TransactionCommand::Release { name: "sp1".to_string() }));
+        assert_eq!(parse("RELEASE sp1"), Some(TransactionCommand::Release { name: "sp1".to_string() }));
+        assert_eq!(parse("SELECT 1"), None);
+        assert_eq!(parse("START something"), None);
+
+        assert!(TransactionCommand::parse("BEGIN ISOLATION LEVEL SERIALIZABLE").is_err());
+        assert!(TransactionCommand::parse("COMMIT AND CHAIN").is_err());
+        assert!(TransactionCommand::parse("SAVEPOINT").is_err());
+
+        assert_eq!(IsolationLevel::parse_modes("isolation level read committed read only").unwrap(), Some(IsolationLevel::ReadCommitted));
+        assert_eq!(IsolationLevel::parse_modes("READ WRITE").unwrap(), None);
+        assert!(matches!(IsolationLevel::parse_modes("ISOLATION LEVEL SERIALIZABLE"), Err(Error::Unimplemented { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_restores_and_detects_conflicts() {
+        let storage = MemoryEngine::new(HashMap::new()).await.unwrap();
+        let versions = Arc::new(VersionStore::default());
+        let put = |key: &str, value: &str| BatchOperation::Put { key: key.as_bytes().to_vec(), value: value.as_bytes().to_vec() };
+        versions.apply(&storage, vec![put("k1", "a"), put("k2", "b")]).await.unwrap();
+
+        let snapshot = versions.snapshot().await;
+        versions.apply(&storage, vec![put("k1", "a2"), put("k3", "c"), BatchOperation::Delete { key: b"k2".to_vec() }]).await.unwrap();
+        versions.apply(&storage, vec![put("k1", "a3")]).await.unwrap();
+
+        let mut entries: BTreeMap<Vec<u8>, Vec<u8>> = storage.scan(None, None, None).await.unwrap().into_iter().collect();
+        snapshot.restore(&mut entries, b"k", b"l").await;
+        let restored: Vec<(String, String)> = entries.into_iter()
+            .map(|(k, v)| (String::from_utf8(k).unwrap(), String::from_utf8(v).unwrap()))
+            .collect();
+        assert_eq!(restored, vec![("k1".to_string(), "a".to_string()), ("k2".to_string(), "b".to_string())]);
+
+        // 快照之后k1已被提交，先提交者胜出
+        let error = versions.commit(&storage, vec![put("k1", "mine")], Some(&snapshot), &[]).await.unwrap_err();
+        assert!(error.to_string().contains(SERIALIZATION_FAILURE));
+        versions.commit(&storage, vec![put("k4", "d")], Some(&snapshot), &[]).await.unwrap();
+
+        // 最后一个快照释放后撤销日志清空
+        drop(snapshot);
+        assert!(versions.state.lock().undo.is_empty());
+        versions.apply(&storage, vec![put("k1", "a4")]).await.unwrap();
+        assert!(versions.state.lock().undo.is_empty());
+    }
+
+    #[test]
+    fn test_savepoints() {
+        let transaction = Transaction::autocommit();
+        let row = |id: i64| Row::from([("id".to_string(), fdc_core::types::Value::Int64(id))]);
+        transaction.put("Accounts", b"1".to_vec(), row(1), true, None);
+        transaction.savepoint("sp");
+        transaction.put("accounts", b"2".to_vec(), row(2), true, None);
+        transaction.delete("accounts", b"1".to_vec(), row(1));
+        transaction.fail();
+        assert_eq!(transaction.pending_writes(), 2);
+
+        transaction.rollback_to("sp").unwrap();
+        assert!(!transaction.is_failed());
+        assert_eq!(transaction.pending("accounts", b"1"), Some(Some(row(1))));
+        assert_eq!(transaction.pending("accounts", b"2"), None);
+        transaction.release("sp").unwrap();
+        assert!(transaction.rollback_to("sp").is_err());
+
+        transaction.put("accounts", b"1".to_vec(), row(10), false, Some(row(1)));
+        assert!(transaction.writes()["accounts"][b"1".as_slice()].inserted);
+        // 新插入的键没有已提交的前像；已提交的键保留第一次写入前的行
+        assert_eq!(transaction.writes()["accounts"][b"1".as_slice()].before, None);
+        transaction.put("accounts", b"3".to_vec(), row(30), false, Some(row(3)));
+        transaction.delete("accounts", b"3".to_vec(), row(30));
+        assert_eq!(transaction.writes()["accounts"][b"3".as_slice()].before, Some(row(3)));
+    }
+}
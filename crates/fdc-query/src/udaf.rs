@@ -0,0 +1,282 @@
+//! User-defined aggregate functions backed by Rust implementations or WASM Aggregator plugins
+
+use crate::udf::{UdfSignature, UDF_BATCH_SIZE};
+use fdc_core::{error::{Error, Result}, types::Value};
+use fdc_wasm::{types::{WasmTypeConverter, WasmValue}, PluginRegistry};
+use std::fmt;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// WASM聚合插件的状态函数后缀：`<prefix>_init`、`<prefix>_accumulate`、`<prefix>_merge`、`<prefix>_finalize`
+pub const AGGREGATE_EXPORT_SUFFIXES: [&str; 4] = ["init", "accumulate", "merge", "finalize"];
+
+/// 用户自定义聚合函数
+///
+/// 聚合以状态值表示部分结果：`init`创建空状态，`accumulate`把一批输入并入状态，
+/// `merge`合并两个部分状态（跨分片、分区汇总），`finalize`由状态得到结果。
+/// 状态本身是普通的`Value`，可以序列化后在节点间传递。
+/// 多参数聚合的每个输入是各参数组成的`Value::Array`。
+pub trait AggregateUdf: Send + Sync {
+    /// 函数签名
+    fn signature(&self) -> &UdfSignature;
+
+    /// 空状态
+    fn init(&self) -> Result<Value>;
+
+    /// 把一批非NULL输入累加到状态上
+    fn accumulate(&self, state: Value, inputs: &[Value]) -> Result<Value>;
+
+    /// 合并两个部分状态
+    fn merge(&self, state: Value, other: Value) -> Result<Value>;
+
+    /// 由状态计算最终结果
+    fn finalize(&self, state: Value) -> Result<Value>;
+}
+
+type AccumulateFn = Box<dyn Fn(Value, &Value) -> Result<Value> + Send + Sync>;
+type MergeFn = Box<dyn Fn(Value, Value) -> Result<Value> + Send + Sync>;
+type FinalizeFn = Box<dyn Fn(Value) -> Result<Value> + Send + Sync>;
+
+/// 由Rust闭包实现的聚合函数（逐个输入累加）
+pub struct RustAggregateFunction {
+    signature: UdfSignature,
+    initial: Value,
+    accumulate: AccumulateFn,
+    merge: MergeFn,
+    finalize: FinalizeFn,
+}
+
+impl RustAggregateFunction {
+    /// 创建Rust聚合函数，`initial`为空状态
+    pub fn new(
+        signature: UdfSignature,
+        initial: Value,
+        accumulate: impl Fn(Value, &Value) -> Result<Value> + Send + Sync + 'static,
+        merge: impl Fn(Value, Value) -> Result<Value> + Send + Sync + 'static,
+        finalize: impl Fn(Value) -> Result<Value> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            signature,
+            initial,
+            accumulate: Box::new(accumulate),
+            merge: Box::new(merge),
+            finalize: Box::new(finalize),
+        }
+    }
+}
+
+impl AggregateUdf for RustAggregateFunction {
+    fn signature(&self) -> &UdfSignature {
+        &self.signature
+    }
+
+    fn init(&self) -> Result<Value> {
+        Ok(self.initial.clone())
+    }
+
+    fn accumulate(&self, state: Value, inputs: &[Value]) -> Result<Value> {
+        inputs.iter().try_fold(state, |state, input| (self.accumulate)(state, input))
+    }
+
+    fn merge(&self, state: Value, other: Value) -> Result<Value> {
+        (self.merge)(state, other)
+    }
+
+    fn finalize(&self, state: Value) -> Result<Value> {
+        (self.finalize)(state)
+    }
+}
+
+/// 由WASM Aggregator插件导出的四个状态函数实现的聚合函数
+///
+/// `<prefix>_init()`返回空状态；`<prefix>_accumulate(state, inputs)`中`inputs`是一批输入组成的数组；
+/// `<prefix>_merge(state, other)`与`<prefix>_finalize(state)`分别合并状态与计算结果。
+pub struct WasmAggregateFunction {
+    signature: UdfSignature,
+    plugins: Arc<PluginRegistry>,
+    plugin_id: Uuid,
+    prefix: String,
+}
+
+impl WasmAggregateFunction {
+    /// 绑定到插件中以`prefix`命名的状态函数
+    pub fn new(signature: UdfSignature, plugins: Arc<PluginRegistry>, plugin_id: Uuid, prefix: impl Into<String>) -> Self {
+        Self {
+            signature,
+            plugins,
+            plugin_id,
+            prefix: prefix.into(),
+        }
+    }
+
+    fn call(&self, suffix: &str, args: &[Value]) -> Result<Value> {
+        let args = WasmTypeConverter::from_core_values(args)?;
+        let output = self.plugins.call_plugin_function(self.plugin_id, &format!("{}_{}", self.prefix, suffix), &args)?;
+        WasmTypeConverter::to_core_value(&output)
+    }
+}
+
+impl AggregateUdf for WasmAggregateFunction {
+    fn signature(&self) -> &UdfSignature {
+        &self.signature
+    }
+
+    fn init(&self) -> Result<Value> {
+        self.call("init", &[])
+    }
+
+    fn accumulate(&self, state: Value, inputs: &[Value]) -> Result<Value> {
+        let inputs = WasmTypeConverter::from_core_values(inputs)?;
+        let args = [WasmTypeConverter::from_core_value(&state)?, WasmValue::Array(inputs)];
+        let output = self.plugins.call_plugin_function(self.plugin_id, &format!("{}_accumulate", self.prefix), &args)?;
+        WasmTypeConverter::to_core_value(&output)
+    }
+
+    fn merge(&self, state: Value, other: Value) -> Result<Value> {
+        self.call("merge", &[state, other])
+    }
+
+    fn finalize(&self, state: Value) -> Result<Value> {
+        self.call("finalize", &[state])
+    }
+}
+
+/// 查询中引用的用户自定义聚合
+///
+/// 在调用实现前校验并转换输入、按批次累加，并把结果转换为声明的返回类型。
+/// 按函数名比较相等。
+#[derive(Clone)]
+pub struct UserAggregate(Arc<dyn AggregateUdf>);
+
+impl UserAggregate {
+    /// 包装聚合函数实现
+    pub fn new(function: Arc<dyn AggregateUdf>) -> Self {
+        Self(function)
+    }
+
+    /// 函数名
+    pub fn name(&self) -> &str {
+        &self.0.signature().name
+    }
+
+    /// 函数签名
+    pub fn signature(&self) -> &UdfSignature {
+        self.0.signature()
+    }
+
+    /// 空状态
+    pub fn init(&self) -> Result<Value> {
+        self.0.init()
+    }
+
+    /// 把输入累加到状态上，每批最多`UDF_BATCH_SIZE`个输入
+    pub fn accumulate(&self, mut state: Value, inputs: &[Value]) -> Result<Value> {
+        let inputs = inputs.iter()
+            .map(|input| self.coerce_input(input))
+            .collect::<Result<Vec<_>>>()?;
+        for chunk in inputs.chunks(UDF_BATCH_SIZE) {
+            state = self.0.accumulate(state, chunk)?;
+        }
+        Ok(state)
+    }
+
+    /// 合并两个部分状态
+    pub fn merge(&self, state: Value, other: Value) -> Result<Value> {
+        self.0.merge(state, other)
+    }
+
+    /// 由状态计算最终结果
+    pub fn finalize(&self, state: Value) -> Result<Value> {
+        let signature = self.0.signature();
+        signature.return_type.coerce(self.0.finalize(state)?)
+    }
+
+    /// 在一组输入上完整计算一次聚合
+    pub fn evaluate(&self, inputs: &[Value]) -> Result<Value> {
+        let state = self.accumulate(self.init()?, inputs)?;
+        self.finalize(state)
+    }
+
+    /// 把一个输入转换为声明的参数类型（多参数时输入为参数数组）
+    fn coerce_input(&self, input: &Value) -> Result<Value> {
+        let signature = self.0.signature();
+        match (signature.arg_types.as_slice(), input) {
+            // 无参数聚合的输入恒为true
+            ([], input) => Ok(input.clone()),
+            ([column_type], input) => column_type.coerce(input.clone()),
+            (types, Value::Array(values)) if values.len() == types.len() => values.iter().zip(types)
+                .map(|(value, column_type)| column_type.coerce(value.clone()))
+                .collect::<Result<Vec<_>>>()
+                .map(Value::Array),
+            (types, _) => Err(Error::validation(format!(
+                "Aggregate {} expects {} arguments", signature.name, types.len()
+            ))),
+        }
+    }
+}
+
+impl fmt::Debug for UserAggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("UserAggregate").field(&self.name()).finish()
+    }
+}
+
+impl PartialEq for UserAggregate {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for UserAggregate {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ColumnType;
+    use crate::expressions::value_as_f64;
+
+    /// 几何平均：状态为[对数和, 个数]
+    fn geomean() -> Arc<dyn AggregateUdf> {
+        let state = |sum: f64, count: i64| Value::Array(vec![Value::Float64(sum), Value::Int64(count)]);
+        let parts = |state: &Value| match state {
+            Value::Array(parts) => Ok((value_as_f64(&parts[0]).unwrap_or(0.0), value_as_f64(&parts[1]).unwrap_or(0.0) as i64)),
+            other => Err(Error::validation(format!("Invalid state {:?}", other))),
+        };
+        Arc::new(RustAggregateFunction::new(
+            UdfSignature::new("geomean", vec![ColumnType::Float64], ColumnType::Float64),
+            state(0.0, 0),
+            move |acc, input| {
+                let (sum, count) = parts(&acc)?;
+                Ok(state(sum + value_as_f64(input).unwrap_or(0.0).ln(), count + 1))
+            },
+            move |a, b| {
+                let ((sa, ca), (sb, cb)) = (parts(&a)?, parts(&b)?);
+                Ok(state(sa + sb, ca + cb))
+            },
+            move |acc| {
+                let (sum, count) = parts(&acc)?;
+                Ok(if count == 0 { Value::Null } else { Value::Float64((sum / count as f64).exp()) })
+            },
+        ))
+    }
+
+    #[test]
+    fn test_merge_partial_states() {
+        let geomean = UserAggregate::new(geomean());
+        let inputs: Vec<Value> = [1.0, 2.0, 4.0, 8.0].into_iter().map(Value::Float64).collect();
+        let whole = geomean.evaluate(&inputs).unwrap();
+
+        // 两个分片各自累加，再合并部分状态
+        let left = geomean.accumulate(geomean.init().unwrap(), &inputs[..1]).unwrap();
+        let right = geomean.accumulate(geomean.init().unwrap(), &inputs[1..]).unwrap();
+        let merged = geomean.finalize(geomean.merge(left, right).unwrap()).unwrap();
+        let (Some(whole), Some(merged)) = (value_as_f64(&whole), value_as_f64(&merged)) else { panic!() };
+        assert!((whole - 8f64.sqrt()).abs() < 1e-9);
+        assert!((whole - merged).abs() < 1e-9);
+
+        // 整数输入按声明的参数类型转换
+        assert!(geomean.evaluate(&[Value::Int64(4)]).is_ok());
+        assert!(geomean.evaluate(&[Value::Array(vec![Value::Int64(1), Value::Int64(2)])]).is_err());
+        assert_eq!(geomean, UserAggregate::new(self::geomean()));
+    }
+}
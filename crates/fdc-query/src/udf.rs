@@ -0,0 +1,545 @@
+//! User-defined scalar functions backed by Rust closures or WASM plugins
+
+use crate::aggregates::AggregateFunction;
+use crate::catalog::ColumnType;
+use crate::expressions::{function_args, ExpressionEvaluator};
+use crate::udaf::{AggregateUdf, WasmAggregateFunction, AGGREGATE_EXPORT_SUFFIXES};
+use dashmap::DashMap;
+use fdc_core::{error::{Error, Result}, types::Value};
+use fdc_wasm::{plugin::{PluginInfo, PluginType}, types::{WasmTypeConverter, WasmValue}, PluginRegistry};
+use parking_lot::RwLock;
+use regex::Regex;
+use sqlparser::ast::{visit_expressions, Expr};
+use sqlparser::{dialect::GenericDialect, parser::Parser};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+/// 每次跨越WASM边界时传入的最大行数
+pub const UDF_BATCH_SIZE: usize = 1024;
+
+/// 标量用户自定义函数
+///
+/// 以列式批量调用：`args[i]`是第i个参数在批内各行的取值，返回值与行一一对应。
+pub trait ScalarUdf: Send + Sync {
+    /// 函数签名
+    fn signature(&self) -> &UdfSignature;
+
+    /// 对一批行求值
+    fn invoke_batch(&self, args: &[Vec<Value>], rows: usize) -> Result<Vec<Value>>;
+}
+
+/// 用户自定义函数的签名
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdfSignature {
+    /// 函数名（小写）
+    pub name: String,
+    /// 参数类型
+    pub arg_types: Vec<ColumnType>,
+    /// 返回类型
+    pub return_type: ColumnType,
+}
+
+impl UdfSignature {
+    /// 创建函数签名
+    pub fn new(name: impl Into<String>, arg_types: Vec<ColumnType>, return_type: ColumnType) -> Self {
+        Self {
+            name: name.into().to_lowercase(),
+            arg_types,
+            return_type,
+        }
+    }
+
+    /// 校验参数个数并把参数列转换为声明的类型
+    fn coerce_args(&self, args: &[Vec<Value>]) -> Result<Vec<Vec<Value>>> {
+        if args.len() != self.arg_types.len() {
+            return Err(Error::validation(format!(
+                "Function {} expects {} arguments, got {}",
+                self.name, self.arg_types.len(), args.len()
+            )));
+        }
+        args.iter().zip(&self.arg_types)
+            .map(|(column, column_type)| column.iter().map(|value| column_type.coerce(value.clone())).collect())
+            .collect()
+    }
+}
+
+/// 由Rust闭包实现的标量函数（逐行调用）
+pub struct RustScalarFunction {
+    signature: UdfSignature,
+    function: Box<dyn Fn(&[Value]) -> Result<Value> + Send + Sync>,
+}
+
+impl RustScalarFunction {
+    /// 创建Rust标量函数
+    pub fn new(signature: UdfSignature, function: impl Fn(&[Value]) -> Result<Value> + Send + Sync + 'static) -> Self {
+        Self {
+            signature,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl ScalarUdf for RustScalarFunction {
+    fn signature(&self) -> &UdfSignature {
+        &self.signature
+    }
+
+    fn invoke_batch(&self, args: &[Vec<Value>], rows: usize) -> Result<Vec<Value>> {
+        (0..rows).map(|row| {
+            let values: Vec<Value> = args.iter().map(|column| column[row].clone()).collect();
+            (self.function)(&values)
+        }).collect()
+    }
+}
+
+/// 由WASM CustomFunction插件导出函数实现的标量函数
+///
+/// 每批调用一次导出函数：每个SQL参数作为一个`WasmValue::Array`传入（列式），
+/// 导出函数返回同样长度的数组。
+pub struct WasmScalarFunction {
+    signature: UdfSignature,
+    plugins: Arc<PluginRegistry>,
+    plugin_id: Uuid,
+    export: String,
+}
+
+impl WasmScalarFunction {
+    /// 绑定到插件导出函数
+    pub fn new(signature: UdfSignature, plugins: Arc<PluginRegistry>, plugin_id: Uuid, export: impl Into<String>) -> Self {
+        Self {
+            signature,
+            plugins,
+            plugin_id,
+            export: export.into(),
+        }
+    }
+}
+
+impl ScalarUdf for WasmScalarFunction {
+    fn signature(&self) -> &UdfSignature {
+        &self.signature
+    }
+
+    fn invoke_batch(&self, args: &[Vec<Value>], rows: usize) -> Result<Vec<Value>> {
+        let columns = args.iter()
+            .map(|column| WasmTypeConverter::from_core_values(column).map(WasmValue::Array))
+            .collect::<Result<Vec<_>>>()?;
+        let output = self.plugins.call_plugin_function(self.plugin_id, &self.export, &columns)?;
+        let values = match output {
+            WasmValue::Array(values) => WasmTypeConverter::to_core_values(&values)?,
+            // 单行调用允许直接返回标量
+            value if rows == 1 => vec![WasmTypeConverter::to_core_value(&value)?],
+            other => return Err(Error::plugin(format!(
+                "Function {} must return an array, got {}", self.signature.name, other.value_type()
+            ))),
+        };
+        if values.len() != rows {
+            return Err(Error::plugin(format!(
+                "Function {} returned {} values for {} rows", self.signature.name, values.len(), rows
+            )));
+        }
+        Ok(values)
+    }
+}
+
+/// 用户自定义函数注册表（标量函数与聚合函数共用一个命名空间）
+#[derive(Default)]
+pub struct FunctionRegistry {
+    functions: DashMap<String, Arc<dyn ScalarUdf>>,
+    aggregates: DashMap<String, Arc<dyn AggregateUdf>>,
+    plugins: RwLock<Option<Arc<PluginRegistry>>>,
+}
+
+impl FunctionRegistry {
+    /// 创建空注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置解析`LANGUAGE wasm`函数所用的插件注册表
+    pub fn set_plugin_registry(&self, plugins: Arc<PluginRegistry>) {
+        *self.plugins.write() = Some(plugins);
+    }
+
+    /// 注册函数，`replace`为false时同名函数已存在报错
+    pub fn register(&self, function: Arc<dyn ScalarUdf>, replace: bool) -> Result<()> {
+        let name = function.signature().name.clone();
+        self.check_name(&name)?;
+        if self.aggregates.contains_key(&name) {
+            return Err(Error::already_exists(format!("aggregate {}", name)));
+        }
+        if !replace && self.functions.contains_key(&name) {
+            return Err(Error::already_exists(format!("function {}", name)));
+        }
+        self.functions.insert(name, function);
+        Ok(())
+    }
+
+    /// 注册聚合函数，`replace`为false时同名聚合已存在报错
+    pub fn register_aggregate(&self, function: Arc<dyn AggregateUdf>, replace: bool) -> Result<()> {
+        let name = function.signature().name.clone();
+        self.check_name(&name)?;
+        if self.functions.contains_key(&name) {
+            return Err(Error::already_exists(format!("function {}", name)));
+        }
+        if !replace && self.aggregates.contains_key(&name) {
+            return Err(Error::already_exists(format!("aggregate {}", name)));
+        }
+        self.aggregates.insert(name, function);
+        Ok(())
+    }
+
+    /// 注册绑定到WASM插件导出函数的标量函数
+    pub fn register_wasm(&self, signature: UdfSignature, plugin: &str, export: &str, replace: bool) -> Result<()> {
+        let (plugins, info) = self.plugin(plugin, PluginType::CustomFunction)?;
+        if !info.exported_functions.is_empty() && !info.exported_functions.iter().any(|f| f == export) {
+            return Err(Error::not_found(format!("export {} in plugin {}", export, plugin)));
+        }
+        self.register(Arc::new(WasmScalarFunction::new(signature, plugins, info.id, export)), replace)
+    }
+
+    /// 注册绑定到WASM Aggregator插件的聚合函数，插件需导出`<prefix>_init`等四个状态函数
+    pub fn register_wasm_aggregate(&self, signature: UdfSignature, plugin: &str, prefix: &str, replace: bool) -> Result<()> {
+        let (plugins, info) = self.plugin(plugin, PluginType::Aggregator)?;
+        if !info.exported_functions.is_empty() {
+            for suffix in AGGREGATE_EXPORT_SUFFIXES {
+                let export = format!("{}_{}", prefix, suffix);
+                if !info.exported_functions.iter().any(|f| *f == export) {
+                    return Err(Error::not_found(format!("export {} in plugin {}", export, plugin)));
+                }
+            }
+        }
+        self.register_aggregate(Arc::new(WasmAggregateFunction::new(signature, plugins, info.id, prefix)), replace)
+    }
+
+    /// 内置函数与内置聚合的名字不能被覆盖
+    fn check_name(&self, name: &str) -> Result<()> {
+        if crate::functions::BuiltinFunctions::new().contains(name) || AggregateFunction::from_name(name).is_some() {
+            return Err(Error::already_exists(format!("built-in function {}", name)));
+        }
+        Ok(())
+    }
+
+    /// 按名字查找指定类型的已加载插件
+    fn plugin(&self, name: &str, plugin_type: PluginType) -> Result<(Arc<PluginRegistry>, PluginInfo)> {
+        let plugins = self.plugins.read().clone()
+            .ok_or_else(|| Error::plugin("No WASM plugin registry is configured"))?;
+        let info = plugins.get_plugin_by_name(name)
+            .ok_or_else(|| Error::not_found(format!("plugin {}", name)))?
+            .info()
+            .clone();
+        if info.plugin_type != plugin_type {
+            return Err(Error::plugin(format!("Plugin {} is a {} plugin, not a {} plugin", name, info.plugin_type, plugin_type)));
+        }
+        Ok((plugins, info))
+    }
+
+    /// 删除函数
+    pub fn drop_function(&self, name: &str, if_exists: bool) -> Result<()> {
+        match self.functions.remove(&name.to_lowercase()) {
+            Some(_) => Ok(()),
+            None if if_exists => Ok(()),
+            None => Err(Error::not_found(format!("function {}", name))),
+        }
+    }
+
+    /// 删除聚合函数
+    pub fn drop_aggregate(&self, name: &str, if_exists: bool) -> Result<()> {
+        match self.aggregates.remove(&name.to_lowercase()) {
+            Some(_) => Ok(()),
+            None if if_exists => Ok(()),
+            None => Err(Error::not_found(format!("aggregate {}", name))),
+        }
+    }
+
+    /// 查找函数
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ScalarUdf>> {
+        self.functions.get(&name.to_lowercase()).map(|f| f.clone())
+    }
+
+    /// 查找聚合函数
+    pub fn get_aggregate(&self, name: &str) -> Option<Arc<dyn AggregateUdf>> {
+        self.aggregates.get(&name.to_lowercase()).map(|f| f.clone())
+    }
+
+    /// 所有函数的签名
+    pub fn list(&self) -> Vec<UdfSignature> {
+        self.functions.iter().map(|f| f.signature().clone()).collect()
+    }
+
+    /// 所有聚合函数的签名
+    pub fn list_aggregates(&self) -> Vec<UdfSignature> {
+        self.aggregates.iter().map(|f| f.signature().clone()).collect()
+    }
+
+    /// 是否没有注册函数
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.aggregates.is_empty()
+    }
+
+    /// SQL文本是否调用了已注册的函数或聚合
+    pub fn is_referenced(&self, sql: &str) -> bool {
+        let lowered = sql.to_lowercase();
+        let mut names = self.functions.iter().map(|f| f.key().clone())
+            .chain(self.aggregates.iter().map(|f| f.key().clone()));
+        names.any(|name| {
+            lowered.match_indices(name.as_str()).any(|(at, _)| {
+                let before = lowered[..at].chars().next_back();
+                let after = lowered[at + name.len()..].trim_start().chars().next();
+                !before.is_some_and(|c| c.is_alphanumeric() || c == '_') && after == Some('(')
+            })
+        })
+    }
+
+    /// 用一行参数调用函数（未能批量预计算的位置使用）
+    pub fn call(&self, name: &str, args: &[Value]) -> Option<Result<Value>> {
+        let function = self.get(name)?;
+        let columns: Vec<Vec<Value>> = args.iter().map(|arg| vec![arg.clone()]).collect();
+        Some(invoke(function.as_ref(), &columns, 1).map(|mut values| values.remove(0)))
+    }
+
+    /// 批量预计算表达式中的函数调用，结果以调用文本为列名写入行中
+    ///
+    /// 内层调用先于外层计算，外层求参数时直接取用内层结果。返回写入的列名。
+    pub fn precompute<'a>(
+        &self,
+        rows: &mut [HashMap<String, Value>],
+        exprs: impl IntoIterator<Item = &'a Expr>,
+        evaluator: &ExpressionEvaluator,
+    ) -> Result<Vec<String>> {
+        if self.functions.is_empty() || rows.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut calls: Vec<(String, Expr)> = Vec::new();
+        for expr in exprs {
+            let _ = visit_expressions(expr, |e: &Expr| {
+                if let Expr::Function(function) = e {
+                    let key = e.to_string();
+                    if self.get(&function.name.to_string()).is_some() && !calls.iter().any(|(k, _)| *k == key) {
+                        calls.push((key, e.clone()));
+                    }
+                }
+                ControlFlow::<()>::Continue(())
+            });
+        }
+
+        // 先序遍历得到的是外层在前，倒序即可保证内层先计算
+        let mut computed = Vec::with_capacity(calls.len());
+        for (key, call) in calls.into_iter().rev() {
+            let Expr::Function(function) = &call else { continue };
+            let udf = match self.get(&function.name.to_string()) {
+                Some(udf) => udf,
+                None => continue,
+            };
+            let arg_exprs = function_args(function)?;
+            for chunk in rows.chunks_mut(UDF_BATCH_SIZE) {
+                evaluator.cancellation().check()?;
+                let mut columns = vec![Vec::with_capacity(chunk.len()); arg_exprs.len()];
+                for row in chunk.iter() {
+                    for (column, arg) in columns.iter_mut().zip(&arg_exprs) {
+                        column.push(evaluator.evaluate(arg, row)?);
+                    }
+                }
+                let values = invoke(udf.as_ref(), &columns, chunk.len())?;
+                for (row, value) in chunk.iter_mut().zip(values) {
+                    row.insert(key.clone(), value);
+                }
+            }
+            computed.push(key);
+        }
+        Ok(computed)
+    }
+}
+
+/// 校验参数、调用函数并把结果转换为声明的返回类型
+fn invoke(function: &dyn ScalarUdf, args: &[Vec<Value>], rows: usize) -> Result<Vec<Value>> {
+    let signature = function.signature();
+    let args = signature.coerce_args(args)?;
+    function.invoke_batch(&args, rows)?
+        .into_iter()
+        .map(|value| signature.return_type.coerce(value))
+        .collect()
+}
+
+/// SQL级的函数定义命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunctionCommand {
+    /// `CREATE [OR REPLACE] FUNCTION name(args) RETURNS type LANGUAGE wasm AS 'plugin.export'`
+    Create {
+        /// 函数签名
+        signature: UdfSignature,
+        /// 插件名
+        plugin: String,
+        /// 插件导出的函数名
+        export: String,
+        /// 是否替换同名函数
+        or_replace: bool,
+    },
+    /// `DROP FUNCTION [IF EXISTS] name`
+    Drop {
+        /// 函数名
+        name: String,
+        /// 不存在时不报错
+        if_exists: bool,
+    },
+    /// `CREATE [OR REPLACE] AGGREGATE name(args) RETURNS type LANGUAGE wasm AS 'plugin.prefix'`
+    CreateAggregate {
+        /// 聚合签名
+        signature: UdfSignature,
+        /// 插件名
+        plugin: String,
+        /// 插件状态函数名前缀
+        prefix: String,
+        /// 是否替换同名聚合
+        or_replace: bool,
+    },
+    /// `DROP AGGREGATE [IF EXISTS] name`
+    DropAggregate {
+        /// 聚合名
+        name: String,
+        /// 不存在时不报错
+        if_exists: bool,
+    },
+}
+
+impl FunctionCommand {
+    /// 识别CREATE / DROP FUNCTION与CREATE / DROP AGGREGATE，其他语句返回None
+    pub fn parse(sql: &str) -> Result<Option<Self>> {
+        static CREATE: OnceLock<Regex> = OnceLock::new();
+        static DROP: OnceLock<Regex> = OnceLock::new();
+        let create = CREATE.get_or_init(|| {
+            Regex::new(
+                r"(?is)^\s*CREATE\s+(OR\s+REPLACE\s+)?(FUNCTION|AGGREGATE)\s+([A-Za-z_][A-Za-z0-9_]*)\s*\((.*?)\)\s*RETURNS\s+(.+?)\s+LANGUAGE\s+([A-Za-z_]+)\s+AS\s+'([^'.]+)\.([^']+)'\s*;?\s*$",
+            ).expect("valid regex")
+        });
+        let drop = DROP.get_or_init(|| {
+            Regex::new(r"(?i)^\s*DROP\s+(FUNCTION|AGGREGATE)\s+(IF\s+EXISTS\s+)?([A-Za-z_][A-Za-z0-9_]*)\s*;?\s*$").expect("valid regex")
+        });
+
+        if let Some(captures) = create.captures(sql) {
+            let language = &captures[6];
+            if !language.eq_ignore_ascii_case("wasm") {
+                return Err(Error::unimplemented(format!("Function language {}", language)));
+            }
+            let arg_types = captures[4].split(',')
+                .map(str::trim)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| {
+                    // 参数可写作`name TYPE`或只写`TYPE`，类型可能包含空格（DOUBLE PRECISION）
+                    parse_type(arg).or_else(|_| match arg.split_once(char::is_whitespace) {
+                        Some((_, data_type)) => parse_type(data_type.trim()),
+                        None => Err(Error::validation(format!("Invalid function argument: {}", arg))),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let signature = UdfSignature::new(&captures[3], arg_types, parse_type(captures[5].trim())?);
+            let plugin = captures[7].to_string();
+            let or_replace = captures.get(1).is_some();
+            return Ok(Some(if captures[2].eq_ignore_ascii_case("aggregate") {
+                Self::CreateAggregate { signature, plugin, prefix: captures[8].to_string(), or_replace }
+            } else {
+                Self::Create { signature, plugin, export: captures[8].to_string(), or_replace }
+            }));
+        }
+        if let Some(captures) = drop.captures(sql) {
+            let name = captures[3].to_lowercase();
+            let if_exists = captures.get(2).is_some();
+            return Ok(Some(if captures[1].eq_ignore_ascii_case("aggregate") {
+                Self::DropAggregate { name, if_exists }
+            } else {
+                Self::Drop { name, if_exists }
+            }));
+        }
+        Ok(None)
+    }
+}
+
+/// 解析SQL类型名
+fn parse_type(text: &str) -> Result<ColumnType> {
+    let data_type = Parser::new(&GenericDialect {})
+        .try_with_sql(text)
+        .and_then(|mut parser| parser.parse_data_type())
+        .map_err(|e| Error::validation(format!("Invalid type {}: {}", text, e)))?;
+    ColumnType::from_sql_type(&data_type)
+        .ok_or_else(|| Error::unimplemented(format!("Function type {}", data_type)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expressions::value_as_f64;
+
+    fn notional() -> Arc<dyn ScalarUdf> {
+        let signature = UdfSignature::new("notional", vec![ColumnType::Float64, ColumnType::Int64], ColumnType::Float64);
+        Arc::new(RustScalarFunction::new(signature, |args| {
+            match (value_as_f64(&args[0]), value_as_f64(&args[1])) {
+                (Some(price), Some(qty)) => Ok(Value::Float64(price * qty)),
+                _ => Ok(Value::Null),
+            }
+        }))
+    }
+
+    #[test]
+    fn test_parse_function_commands() {
+        let command = FunctionCommand::parse(
+            "CREATE OR REPLACE FUNCTION bs_price(spot DOUBLE PRECISION, strike DOUBLE, days INT) RETURNS DOUBLE LANGUAGE wasm AS 'pricing.black_scholes';",
+        ).unwrap().unwrap();
+        assert_eq!(command, FunctionCommand::Create {
+            signature: UdfSignature::new(
+                "bs_price",
+                vec![ColumnType::Float64, ColumnType::Float64, ColumnType::Int64],
+                ColumnType::Float64,
+            ),
+            plugin: "pricing".to_string(),
+            export: "black_scholes".to_string(),
+            or_replace: true,
+        });
+        assert!(FunctionCommand::parse("CREATE FUNCTION f(INT) RETURNS INT LANGUAGE python AS 'a.b'").is_err());
+        assert_eq!(
+            FunctionCommand::parse("DROP FUNCTION IF EXISTS BS_PRICE").unwrap(),
+            Some(FunctionCommand::Drop { name: "bs_price".to_string(), if_exists: true })
+        );
+        assert_eq!(FunctionCommand::parse("SELECT 1").unwrap(), None);
+
+        let command = FunctionCommand::parse(
+            "CREATE AGGREGATE realized_vol(price DOUBLE, ts TIMESTAMP) RETURNS DOUBLE LANGUAGE wasm AS 'risk.rvol'",
+        ).unwrap().unwrap();
+        assert_eq!(command, FunctionCommand::CreateAggregate {
+            signature: UdfSignature::new("realized_vol", vec![ColumnType::Float64, ColumnType::Timestamp], ColumnType::Float64),
+            plugin: "risk".to_string(),
+            prefix: "rvol".to_string(),
+            or_replace: false,
+        });
+        assert_eq!(
+            FunctionCommand::parse("drop aggregate realized_vol;").unwrap(),
+            Some(FunctionCommand::DropAggregate { name: "realized_vol".to_string(), if_exists: false })
+        );
+    }
+
+    #[test]
+    fn test_precompute_in_batches() {
+        let registry = FunctionRegistry::new();
+        registry.register(notional(), false).unwrap();
+        assert!(registry.register(notional(), false).is_err());
+        assert!(registry.is_referenced("SELECT NOTIONAL (price, qty) FROM trades"));
+        assert!(!registry.is_referenced("SELECT my_notional(price, qty) FROM trades"));
+
+        let mut rows: Vec<_> = (0..3).map(|i| HashMap::from([
+            ("price".to_string(), Value::Float64(10.0)),
+            ("qty".to_string(), Value::Int64(i)),
+        ])).collect();
+        let expr = Parser::new(&GenericDialect {})
+            .try_with_sql("notional(notional(price, qty), 2)").unwrap()
+            .parse_expr().unwrap();
+        let evaluator = ExpressionEvaluator::new();
+        let keys = registry.precompute(&mut rows, [&expr], &evaluator).unwrap();
+        assert_eq!(keys, vec!["notional(price, qty)".to_string(), "notional(notional(price, qty), 2)".to_string()]);
+        assert_eq!(rows[2].get(&expr.to_string()), Some(&Value::Float64(40.0)));
+
+        assert_eq!(registry.call("notional", &[Value::Int64(3), Value::Int64(2)]).unwrap().unwrap(), Value::Float64(6.0));
+        assert!(registry.call("notional", &[Value::Int64(3)]).unwrap().is_err());
+        assert!(registry.register_wasm(UdfSignature::new("f", vec![], ColumnType::Int64), "pricing", "f", false).is_err());
+    }
+}
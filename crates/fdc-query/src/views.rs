@@ -0,0 +1,779 @@
+//! Incrementally maintained materialized views and continuous queries
+
+use crate::{
+    aggregates::Accumulator,
+    catalog::{encode_key_value, Catalog, ColumnDefinition, ColumnType, InsertObserver, TableDefinition},
+    executor::{parse_statement, project_row, select_item_expr, select_item_name, DefaultQueryExecutor, ExecutionContext, BUCKET_COLUMN},
+    expressions::{value_as_timestamp, ExpressionEvaluator},
+    grouping::{collect_aggregates_with, AggregateCall},
+    joins::Row,
+    parser::{QueryFeatures, SqlParser},
+    sampling::{extract_sample_by, Alignment, SampleBy},
+    udf::FunctionRegistry,
+};
+use fdc_core::{error::{Error, Result}, types::{TimestampNs, Value}};
+use fdc_storage::engine::StorageEngine;
+use parking_lot::RwLock;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{Expr, GroupByExpr, Ident, SelectItem, SetExpr, Statement, TableFactor};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{broadcast, Mutex};
+
+/// 每个视图的增量推送缓冲（慢订阅者落后超过该数量时丢失最早的增量）
+pub const DELTA_CHANNEL_CAPACITY: usize = 1024;
+
+/// 视图刷新方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RefreshMode {
+    /// 按新写入的行增量维护（单表、可分解聚合）
+    #[default]
+    Incremental,
+    /// 源表每次写入后重新执行查询
+    Full,
+}
+
+impl RefreshMode {
+    /// 解析`refresh = '...'`选项的取值
+    pub fn parse(text: &str) -> Result<Self> {
+        match text.to_lowercase().as_str() {
+            "incremental" => Ok(RefreshMode::Incremental),
+            "full" => Ok(RefreshMode::Full),
+            other => Err(Error::validation(format!("Unknown refresh mode '{}', expected 'incremental' or 'full'", other))),
+        }
+    }
+}
+
+/// 视图类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViewKind {
+    /// 结果持久化到存储引擎，可以像表一样查询
+    MaterializedView,
+    /// 不持久化，只把结果增量推送给订阅者
+    ContinuousQuery,
+}
+
+/// 一次写入引起的视图结果变化
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewDelta {
+    /// 视图名
+    pub view: String,
+    /// 新增或更新后的结果行（全量刷新时为完整结果）
+    pub upserted: Vec<Row>,
+    /// 不再满足HAVING而移除的结果行（移除前的值）
+    pub removed: Vec<Row>,
+}
+
+/// 视图信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewInfo {
+    /// 视图名
+    pub name: String,
+    /// 视图类型
+    pub kind: ViewKind,
+    /// 刷新方式
+    pub refresh: RefreshMode,
+    /// 定义视图的查询
+    pub sql: String,
+    /// 源表
+    pub sources: Vec<String>,
+}
+
+/// 视图管理命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViewCommand {
+    /// `CREATE MATERIALIZED VIEW name [WITH (refresh = '...')] AS query`或`CREATE CONTINUOUS QUERY name AS query`
+    Create {
+        kind: ViewKind,
+        name: String,
+        refresh: RefreshMode,
+        if_not_exists: bool,
+        sql: String,
+    },
+    /// `DROP MATERIALIZED VIEW name` / `DROP CONTINUOUS QUERY name`
+    Drop {
+        kind: ViewKind,
+        name: String,
+        if_exists: bool,
+    },
+    /// `REFRESH MATERIALIZED VIEW name`：按源表全量重建
+    Refresh {
+        name: String,
+    },
+}
+
+impl ViewCommand {
+    /// 识别视图命令；其他语句返回None
+    ///
+    /// 视图查询原样保留，因此可以包含SAMPLE BY等扩展语法。
+    pub fn parse(sql: &str) -> Result<Option<Self>> {
+        static CREATE: OnceLock<Regex> = OnceLock::new();
+        static DROP: OnceLock<Regex> = OnceLock::new();
+        static REFRESH: OnceLock<Regex> = OnceLock::new();
+        static OPTION: OnceLock<Regex> = OnceLock::new();
+        let create = CREATE.get_or_init(|| {
+            Regex::new(r"(?is)^\s*CREATE\s+(MATERIALIZED\s+VIEW|CONTINUOUS\s+QUERY)\s+(IF\s+NOT\s+EXISTS\s+)?([A-Za-z_][A-Za-z0-9_]*)\s*(?:WITH\s*\(([^)]*)\)\s*)?AS\s+(.+?)\s*;?\s*$")
+                .expect("valid regex")
+        });
+        let drop = DROP.get_or_init(|| {
+            Regex::new(r"(?is)^\s*DROP\s+(MATERIALIZED\s+VIEW|CONTINUOUS\s+QUERY)\s+(IF\s+EXISTS\s+)?([A-Za-z_][A-Za-z0-9_]*)\s*;?\s*$")
+                .expect("valid regex")
+        });
+        let refresh = REFRESH.get_or_init(|| {
+            Regex::new(r"(?is)^\s*REFRESH\s+MATERIALIZED\s+VIEW\s+([A-Za-z_][A-Za-z0-9_]*)\s*;?\s*$").expect("valid regex")
+        });
+        let option = OPTION.get_or_init(|| {
+            Regex::new(r#"(?is)^\s*([A-Za-z_]+)\s*=\s*(?:'([^']*)'|"([^"]*)"|([A-Za-z_]+))\s*$"#).expect("valid regex")
+        });
+        let kind_of = |text: &str| {
+            if text.to_uppercase().starts_with("MATERIALIZED") {
+                ViewKind::MaterializedView
+            } else {
+                ViewKind::ContinuousQuery
+            }
+        };
+
+        if let Some(caps) = create.captures(sql) {
+            let mut refresh_mode = RefreshMode::default();
+            for item in caps.get(4).map(|m| m.as_str()).unwrap_or_default().split(',').filter(|i| !i.trim().is_empty()) {
+                let option = option.captures(item)
+                    .ok_or_else(|| Error::parse(format!("Invalid view option: {}", item.trim())))?;
+                let value = (2..=4).find_map(|i| option.get(i)).map(|m| m.as_str()).unwrap_or_default();
+                match option[1].to_lowercase().as_str() {
+                    "refresh" => refresh_mode = RefreshMode::parse(value)?,
+                    other => return Err(Error::parse(format!("Unknown view option: {}", other))),
+                }
+            }
+            return Ok(Some(ViewCommand::Create {
+                kind: kind_of(&caps[1]),
+                name: caps[3].to_string(),
+                refresh: refresh_mode,
+                if_not_exists: caps.get(2).is_some(),
+                sql: caps[5].to_string(),
+            }));
+        }
+        if let Some(caps) = drop.captures(sql) {
+            return Ok(Some(ViewCommand::Drop {
+                kind: kind_of(&caps[1]),
+                name: caps[3].to_string(),
+                if_exists: caps.get(2).is_some(),
+            }));
+        }
+        Ok(refresh.captures(sql).map(|caps| ViewCommand::Refresh { name: caps[1].to_string() }))
+    }
+}
+
+/// 可增量维护的查询：单表、无排序与LIMIT、聚合均可逐行累加
+#[derive(Debug, Clone)]
+struct IncrementalPlan {
+    source: String,
+    selection: Option<Expr>,
+    projection: Vec<SelectItem>,
+    group_by: Vec<Expr>,
+    aggregates: Vec<AggregateCall>,
+    having: Option<Expr>,
+    /// SAMPLE BY子句与时间列
+    sample: Option<(SampleBy, String)>,
+}
+
+impl IncrementalPlan {
+    fn compile(sql: &str, catalog: &Catalog, functions: &FunctionRegistry) -> Result<Self> {
+        let unsupported = |what: &str| {
+            Error::validation(format!("{} cannot be maintained incrementally, use WITH (refresh = 'full')", what))
+        };
+        let (body, sample) = extract_sample_by(sql)?;
+        let statement = parse_statement(&body)?;
+        let features = QueryFeatures::from_statement(&statement);
+        if features.has_cte || features.has_subquery || features.has_set_operation {
+            return Err(unsupported("Subqueries, CTEs and set operations"));
+        }
+        if features.has_window_function {
+            return Err(unsupported("Window functions"));
+        }
+        let Statement::Query(query) = &statement else {
+            return Err(Error::validation("Expected a SELECT statement"));
+        };
+        if query.order_by.is_some() || query.limit.is_some() || query.offset.is_some() || query.fetch.is_some() {
+            return Err(unsupported("ORDER BY, LIMIT and OFFSET"));
+        }
+        let SetExpr::Select(select) = query.body.as_ref() else {
+            return Err(Error::validation("Expected a SELECT statement"));
+        };
+        if select.distinct.is_some() {
+            return Err(unsupported("SELECT DISTINCT"));
+        }
+        let source = match select.from.as_slice() {
+            [from] if from.joins.is_empty() => match &from.relation {
+                TableFactor::Table { name, .. } => name.0.last().map(|i| i.value.to_lowercase()).unwrap_or_default(),
+                _ => return Err(unsupported("Derived tables")),
+            },
+            _ => return Err(unsupported("Joins")),
+        };
+        let definition = catalog.get_table(&source)
+            .ok_or_else(|| Error::not_found(format!("table {}", source)))?;
+
+        let mut group_by = match &select.group_by {
+            GroupByExpr::Expressions(exprs, _) => exprs.clone(),
+            GroupByExpr::All(_) => return Err(Error::unimplemented("GROUP BY ALL")),
+        };
+        let aggregates = collect_aggregates_with(
+            select.projection.iter().filter_map(select_item_expr).chain(select.having.as_ref()),
+            Some(functions),
+        )?;
+        if features.has_aggregate && aggregates.is_empty() {
+            return Err(unsupported("Aggregate functions other than COUNT/SUM/AVG/MIN/MAX/FIRST/LAST"));
+        }
+        if aggregates.iter().any(|call| call.distinct) {
+            return Err(unsupported("DISTINCT aggregates"));
+        }
+
+        let sample = match sample {
+            None => None,
+            Some(sample) => {
+                if !sample.fill.is_empty() {
+                    return Err(unsupported("SAMPLE BY ... FILL"));
+                }
+                if sample.alignment == Alignment::FirstObservation {
+                    return Err(unsupported("ALIGN TO FIRST OBSERVATION"));
+                }
+                let time_column_of = |expr: &Expr| match expr {
+                    Expr::Identifier(ident) => Some(ident.value.clone()),
+                    Expr::CompoundIdentifier(idents) => idents.last().map(|i| i.value.clone()),
+                    _ => None,
+                }
+                .and_then(|name| definition.column(&name))
+                .filter(|column| column.column_type == ColumnType::Timestamp)
+                .map(|column| column.name.clone());
+                let time_item = select.projection.iter()
+                    .position(|item| select_item_expr(item).and_then(time_column_of).is_some());
+                let time_column = match time_item {
+                    Some(index) => select_item_expr(&select.projection[index]).and_then(time_column_of),
+                    None => ["ts", "timestamp", "time"].iter()
+                        .filter_map(|name| definition.column(name))
+                        .find(|column| column.column_type == ColumnType::Timestamp)
+                        .map(|column| column.name.clone()),
+                }
+                .ok_or_else(|| Error::validation("SAMPLE BY requires a timestamp column in the select list"))?;
+
+                // 分组键：时间桶 + 其余不含聚合的投影列
+                group_by = vec![Expr::Identifier(Ident::new(BUCKET_COLUMN))];
+                for (index, item) in select.projection.iter().enumerate() {
+                    let expr = select_item_expr(item)
+                        .ok_or_else(|| Error::validation("SAMPLE BY does not support wildcard projections"))?;
+                    if Some(index) != time_item && collect_aggregates_with([expr], Some(functions))?.is_empty() {
+                        group_by.push(expr.clone());
+                    }
+                }
+                Some((sample, time_column))
+            }
+        };
+
+        Ok(Self {
+            source,
+            selection: select.selection.clone(),
+            projection: select.projection.clone(),
+            group_by,
+            aggregates,
+            having: select.having.clone(),
+            sample,
+        })
+    }
+
+    fn is_grouped(&self) -> bool {
+        !self.group_by.is_empty() || !self.aggregates.is_empty()
+    }
+
+    /// 没有GROUP BY的全局聚合在没有输入时也输出一行
+    fn initialize(&self, state: &mut ViewState, evaluator: &ExpressionEvaluator) -> Result<Changes> {
+        let mut changes = Changes::default();
+        if self.group_by.is_empty() && !self.aggregates.is_empty() {
+            state.groups.entry(String::new()).or_insert_with(|| self.new_group(Row::new()));
+            self.emit(state, String::new(), evaluator, &mut changes)?;
+        }
+        Ok(changes)
+    }
+
+    fn new_group(&self, base: Row) -> GroupState {
+        GroupState {
+            base,
+            accumulators: self.aggregates.iter().map(|call| call.function.accumulator()).collect(),
+            output: None,
+        }
+    }
+
+    /// 把新写入的行累加到分组状态，返回受影响分组的新结果
+    fn apply(&self, state: &mut ViewState, rows: &[Row], evaluator: &ExpressionEvaluator) -> Result<Changes> {
+        let mut changes = Changes::default();
+        let mut touched: Vec<String> = Vec::new();
+        let mut seen: HashSet<String> = HashSet::new();
+
+        for row in rows {
+            evaluator.checkpoint()?;
+            if let Some(selection) = &self.selection {
+                if !evaluator.evaluate_predicate(selection, row)? {
+                    continue;
+                }
+            }
+            if !self.is_grouped() {
+                // 无聚合的视图只追加
+                let output = project_row(&self.projection, row, evaluator)?;
+                changes.upserted.push((uuid::Uuid::new_v4().to_string(), output));
+                continue;
+            }
+
+            let mut row = row.clone();
+            if let Some((sample, time_column)) = &self.sample {
+                let Some(ts) = row.get(time_column).and_then(value_as_timestamp) else { continue };
+                let bucket = sample.bucket(ts.as_nanos(), None)?;
+                row.insert(BUCKET_COLUMN.to_string(), Value::Timestamp(TimestampNs::from_nanos(bucket)));
+            }
+            let keys = self.group_by.iter()
+                .map(|expr| evaluator.evaluate(expr, &row))
+                .collect::<Result<Vec<_>>>()?;
+            let key = group_key(&keys);
+
+            let group = state.groups.entry(key.clone()).or_insert_with(|| {
+                let mut base = row.clone();
+                for (expr, value) in self.group_by.iter().zip(&keys) {
+                    base.insert(expr.to_string(), value.clone());
+                }
+                // 投影中的时间列取桶起点
+                if let (Some((_, time_column)), Some(bucket)) = (&self.sample, row.get(BUCKET_COLUMN)) {
+                    base.insert(time_column.clone(), bucket.clone());
+                }
+                self.new_group(base)
+            });
+            for (call, accumulator) in self.aggregates.iter().zip(group.accumulators.iter_mut()) {
+                accumulator.update(&call.input(&row, evaluator)?)?;
+            }
+            if seen.insert(key.clone()) {
+                touched.push(key);
+            }
+        }
+
+        for key in touched {
+            self.emit(state, key, evaluator, &mut changes)?;
+        }
+        Ok(changes)
+    }
+
+    fn emit(&self, state: &mut ViewState, key: String, evaluator: &ExpressionEvaluator, changes: &mut Changes) -> Result<()> {
+        let Some(group) = state.groups.get_mut(&key) else {
+            return Ok(());
+        };
+        let mut row = group.base.clone();
+        for (call, accumulator) in self.aggregates.iter().zip(&group.accumulators) {
+            row.insert(call.key.clone(), accumulator.finish()?);
+        }
+        let visible = match &self.having {
+            Some(having) => evaluator.evaluate_predicate(having, &row)?,
+            None => true,
+        };
+        if visible {
+            let output = project_row(&self.projection, &row, evaluator)?;
+            group.output = Some(output.clone());
+            changes.upserted.push((key, output));
+        } else if let Some(previous) = group.output.take() {
+            changes.removed.push((key, previous));
+        }
+        Ok(())
+    }
+}
+
+/// 分组键：各键值的保序编码，使视图行在存储中按分组键（例如时间桶）排序
+fn group_key(values: &[Value]) -> String {
+    values.iter()
+        .map(|value| String::from_utf8_lossy(&encode_key_value(value)).into_owned())
+        .collect::<Vec<_>>()
+        .join("\u{1f}")
+}
+
+#[derive(Debug)]
+struct GroupState {
+    /// 组内第一行，加入分组表达式的值
+    base: Row,
+    accumulators: Vec<Accumulator>,
+    /// 最近一次输出的结果行（不满足HAVING时为None）
+    output: Option<Row>,
+}
+
+#[derive(Debug, Default)]
+struct ViewState {
+    groups: HashMap<String, GroupState>,
+}
+
+/// 以存储键后缀标识的结果变化
+#[derive(Debug, Default)]
+struct Changes {
+    upserted: Vec<(String, Row)>,
+    removed: Vec<(String, Row)>,
+}
+
+impl Changes {
+    fn is_empty(&self) -> bool {
+        self.upserted.is_empty() && self.removed.is_empty()
+    }
+}
+
+struct View {
+    info: ViewInfo,
+    plan: Option<IncrementalPlan>,
+    /// 串行化同一视图的维护
+    state: Mutex<ViewState>,
+    sender: broadcast::Sender<ViewDelta>,
+}
+
+impl View {
+    fn delta(&self, changes: &Changes) -> ViewDelta {
+        ViewDelta {
+            view: self.info.name.clone(),
+            upserted: changes.upserted.iter().map(|(_, row)| row.clone()).collect(),
+            removed: changes.removed.iter().map(|(_, row)| row.clone()).collect(),
+        }
+    }
+}
+
+/// 物化视图与连续查询管理器
+///
+/// 作为目录的写入观察者，在`Catalog::insert_rows`提交后维护视图；数据接入路径提交批次后
+/// 通过`QueryEngine::apply_ingested_rows`调用`apply`。物化视图的结果写入名为视图名的表，
+/// 可以通过`QueryEngine::execute_sql`直接查询。
+pub struct ViewManager {
+    catalog: Arc<Catalog>,
+    storage: Arc<dyn StorageEngine>,
+    executor: Arc<DefaultQueryExecutor>,
+    views: RwLock<HashMap<String, Arc<View>>>,
+}
+
+impl ViewManager {
+    /// 创建视图管理器
+    pub fn new(catalog: Arc<Catalog>, storage: Arc<dyn StorageEngine>, executor: Arc<DefaultQueryExecutor>) -> Self {
+        Self {
+            catalog,
+            storage,
+            executor,
+            views: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 创建视图并用源表的现有数据初始化，返回初始结果行数
+    ///
+    /// 创建期间并发写入的行可能未计入，可用`REFRESH MATERIALIZED VIEW`重建。
+    pub async fn create(&self, kind: ViewKind, name: &str, refresh: RefreshMode, sql: &str, if_not_exists: bool) -> Result<u64> {
+        let name = name.to_lowercase();
+        if self.views.read().contains_key(&name) {
+            return if if_not_exists { Ok(0) } else { Err(Error::already_exists(format!("view {}", name))) };
+        }
+        if kind == ViewKind::MaterializedView && self.catalog.get_table(&name).is_some() {
+            return Err(Error::already_exists(format!("table {}", name)));
+        }
+        if kind == ViewKind::ContinuousQuery && refresh == RefreshMode::Full {
+            return Err(Error::validation("Continuous queries only support incremental refresh"));
+        }
+
+        let plan = match refresh {
+            RefreshMode::Incremental => Some(IncrementalPlan::compile(sql, &self.catalog, self.executor.functions())?),
+            RefreshMode::Full => None,
+        };
+        let mut sources = match &plan {
+            Some(plan) => vec![plan.source.clone()],
+            None => SqlParser::new().referenced_tables(sql)?,
+        };
+        sources.sort();
+        sources.dedup();
+        if let Some(source) = sources.iter().find(|s| self.views.read().contains_key(*s)) {
+            return Err(Error::validation(format!("Views cannot be defined over another view ({})", source)));
+        }
+
+        let (sender, _) = broadcast::channel(DELTA_CHANNEL_CAPACITY);
+        let view = Arc::new(View {
+            info: ViewInfo { name: name.clone(), kind, refresh, sql: sql.to_string(), sources },
+            plan,
+            state: Mutex::new(ViewState::default()),
+            sender,
+        });
+
+        let mut state = view.state.lock().await;
+        let changes = self.rebuild(&view, &mut state).await?;
+        if kind == ViewKind::MaterializedView {
+            self.catalog.register_table(TableDefinition::new(name.clone(), self.output_columns(&view, &changes)))?;
+            self.catalog.write_keyed_rows(self.storage.as_ref(), &name, &changes.upserted, &[]).await?;
+        }
+        drop(state);
+
+        let mut views = self.views.write();
+        if views.contains_key(&name) {
+            return Err(Error::already_exists(format!("view {}", name)));
+        }
+        views.insert(name, view);
+        Ok(changes.upserted.len() as u64)
+    }
+
+    /// 删除视图，物化视图的结果行一并删除
+    pub async fn drop_view(&self, kind: ViewKind, name: &str, if_exists: bool) -> Result<()> {
+        let name = name.to_lowercase();
+        let view = {
+            let mut views = self.views.write();
+            match views.get(&name) {
+                Some(view) if view.info.kind == kind => views.remove(&name),
+                _ => None,
+            }
+        };
+        let Some(view) = view else {
+            return if if_exists { Ok(()) } else { Err(Error::not_found(format!("view {}", name))) };
+        };
+        if view.info.kind == ViewKind::MaterializedView {
+            let _state = view.state.lock().await;
+            self.catalog.replace_rows(self.storage.as_ref(), &name, &[]).await?;
+            self.catalog.drop_table(&name)?;
+        }
+        Ok(())
+    }
+
+    /// 按源表当前数据全量重建视图，返回结果行数
+    pub async fn refresh(&self, name: &str) -> Result<u64> {
+        let view = self.get(name)?;
+        let mut state = view.state.lock().await;
+        let changes = self.rebuild(&view, &mut state).await?;
+        if view.info.kind == ViewKind::MaterializedView {
+            self.catalog.replace_rows(self.storage.as_ref(), &view.info.name, &changes.upserted).await?;
+        }
+        let _ = view.sender.send(view.delta(&changes));
+        Ok(changes.upserted.len() as u64)
+    }
+
+    /// 维护以`table`为源表的视图
+    pub async fn apply(&self, table: &str, rows: &[Row]) -> Result<()> {
+        let table = table.to_lowercase();
+        let views: Vec<Arc<View>> = self.views.read().values()
+            .filter(|view| view.info.sources.contains(&table))
+            .cloned()
+            .collect();
+
+        let mut first_error = None;
+        for view in views {
+            if let Err(e) = self.apply_view(&view, rows).await {
+                tracing::warn!("Failed to maintain view {}: {}", view.info.name, e);
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    async fn apply_view(&self, view: &View, rows: &[Row]) -> Result<()> {
+        let mut state = view.state.lock().await;
+        let changes = match &view.plan {
+            Some(plan) => plan.apply(&mut state, rows, &ExpressionEvaluator::new())?,
+            None => self.rebuild(view, &mut state).await?,
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+        if view.info.kind == ViewKind::MaterializedView {
+            match &view.plan {
+                Some(_) => {
+                    let removed: Vec<String> = changes.removed.iter().map(|(key, _)| key.clone()).collect();
+                    self.catalog.write_keyed_rows(self.storage.as_ref(), &view.info.name, &changes.upserted, &removed).await?;
+                }
+                None => self.catalog.replace_rows(self.storage.as_ref(), &view.info.name, &changes.upserted).await?,
+            }
+        }
+        // 没有订阅者时发送失败，忽略
+        let _ = view.sender.send(view.delta(&changes));
+        Ok(())
+    }
+
+    /// 从头计算视图结果：增量视图重建分组状态，全量视图重新执行查询
+    async fn rebuild(&self, view: &View, state: &mut ViewState) -> Result<Changes> {
+        match &view.plan {
+            Some(plan) => {
+                *state = ViewState::default();
+                let evaluator = ExpressionEvaluator::new();
+                let rows = self.catalog.scan_rows(self.storage.as_ref(), &plan.source).await?;
+                let mut changes = plan.initialize(state, &evaluator)?;
+                let applied = plan.apply(state, &rows, &evaluator)?;
+                // 全局聚合的初始空行会被第一批数据覆盖
+                changes.upserted.retain(|(key, _)| !applied.upserted.iter().any(|(k, _)| k == key));
+                changes.upserted.extend(applied.upserted);
+                changes.removed.extend(applied.removed);
+                Ok(changes)
+            }
+            None => {
+                let context = ExecutionContext::new(uuid::Uuid::new_v4().to_string());
+                let rows = self.executor.select_rows(&view.info.sql, &context).await?;
+                // 键按结果顺序编号，保持查询的ORDER BY
+                Ok(Changes {
+                    upserted: rows.into_iter().enumerate().map(|(i, row)| (format!("{:016x}", i), row)).collect(),
+                    removed: Vec::new(),
+                })
+            }
+        }
+    }
+
+    /// 视图表的列：按投影顺序，类型取自结果值
+    fn output_columns(&self, view: &View, changes: &Changes) -> Vec<ColumnDefinition> {
+        let mut names: Vec<String> = Vec::new();
+        if let Some(plan) = &view.plan {
+            for item in &plan.projection {
+                match item {
+                    SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => {
+                        if let Some(source) = self.catalog.get_table(&plan.source) {
+                            names.extend(source.column_names());
+                        }
+                    }
+                    item => names.push(select_item_name(item)),
+                }
+            }
+        }
+        for (_, row) in &changes.upserted {
+            let mut extra: Vec<&String> = row.keys().filter(|k| !names.contains(k)).collect();
+            extra.sort();
+            names.extend(extra.into_iter().cloned());
+        }
+        names.into_iter()
+            .map(|name| {
+                let column_type = changes.upserted.iter()
+                    .find_map(|(_, row)| row.get(&name).and_then(ColumnType::of_value))
+                    .unwrap_or(ColumnType::Float64);
+                ColumnDefinition::new(name, column_type)
+            })
+            .collect()
+    }
+
+    fn get(&self, name: &str) -> Result<Arc<View>> {
+        self.views.read().get(&name.to_lowercase()).cloned()
+            .ok_or_else(|| Error::not_found(format!("view {}", name)))
+    }
+
+    /// 订阅视图的结果增量
+    pub fn subscribe(&self, name: &str) -> Result<broadcast::Receiver<ViewDelta>> {
+        Ok(self.get(name)?.sender.subscribe())
+    }
+
+    /// 所有视图
+    pub fn list(&self) -> Vec<ViewInfo> {
+        let mut views: Vec<ViewInfo> = self.views.read().values().map(|view| view.info.clone()).collect();
+        views.sort_by(|a, b| a.name.cmp(&b.name));
+        views
+    }
+
+    /// 是否存在视图
+    pub fn contains(&self, name: &str) -> bool {
+        self.views.read().contains_key(&name.to_lowercase())
+    }
+}
+
+#[async_trait::async_trait]
+impl InsertObserver for ViewManager {
+    async fn rows_inserted(&self, table: &str, rows: &[Row]) -> Result<()> {
+        self.apply(table, rows).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_storage::engines::memory::MemoryEngine;
+
+    fn trade(id: i64, symbol: &str, price: f64, qty: i64, minute: i64) -> Row {
+        HashMap::from([
+            ("id".to_string(), Value::Int64(id)),
+            ("symbol".to_string(), Value::String(symbol.to_string())),
+            ("price".to_string(), Value::Float64(price)),
+            ("qty".to_string(), Value::Int64(qty)),
+            ("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(minute * 60_000_000_000 + 1_000_000_000))),
+        ])
+    }
+
+    async fn manager() -> (Arc<Catalog>, Arc<dyn StorageEngine>, Arc<ViewManager>) {
+        let storage: Arc<dyn StorageEngine> = Arc::new(MemoryEngine::new(HashMap::new()).await.unwrap());
+        let catalog = Arc::new(Catalog::new());
+        catalog.register_table(TableDefinition::new("trades", vec![
+            ColumnDefinition::new("id", ColumnType::Int64),
+            ColumnDefinition::new("symbol", ColumnType::String),
+            ColumnDefinition::new("price", ColumnType::Float64),
+            ColumnDefinition::new("qty", ColumnType::Int64),
+            ColumnDefinition::new("ts", ColumnType::Timestamp),
+        ]).with_primary_key("id")).unwrap();
+        let executor = Arc::new(DefaultQueryExecutor::with_catalog(storage.clone(), catalog.clone()));
+        let views = Arc::new(ViewManager::new(catalog.clone(), storage.clone(), executor));
+        let observer: std::sync::Weak<dyn InsertObserver> = Arc::downgrade(&views);
+        catalog.observe_inserts(observer);
+        (catalog, storage, views)
+    }
+
+    #[test]
+    fn test_parse_view_commands() {
+        let command = ViewCommand::parse(
+            "CREATE MATERIALIZED VIEW vwap_1m WITH (refresh = 'incremental') AS SELECT symbol, SUM(price) FROM trades GROUP BY symbol"
+        ).unwrap();
+        assert!(matches!(command, Some(ViewCommand::Create { kind: ViewKind::MaterializedView, refresh: RefreshMode::Incremental, ref name, .. }) if name == "vwap_1m"));
+        assert!(matches!(
+            ViewCommand::parse("create continuous query big_trades as select * from trades where qty > 100").unwrap(),
+            Some(ViewCommand::Create { kind: ViewKind::ContinuousQuery, if_not_exists: false, .. })
+        ));
+        assert_eq!(
+            ViewCommand::parse("DROP MATERIALIZED VIEW IF EXISTS vwap_1m").unwrap(),
+            Some(ViewCommand::Drop { kind: ViewKind::MaterializedView, name: "vwap_1m".to_string(), if_exists: true })
+        );
+        assert!(ViewCommand::parse("CREATE MATERIALIZED VIEW v WITH (refresh = 'hourly') AS SELECT 1").is_err());
+        assert_eq!(ViewCommand::parse("SELECT * FROM trades").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_incremental_sample_by_view() {
+        let (catalog, storage, views) = manager().await;
+        catalog.insert_rows(storage.as_ref(), "trades", &[trade(1, "AAPL", 10.0, 100, 0)]).await.unwrap();
+
+        let sql = "SELECT ts, symbol, SUM(price * qty) / SUM(qty) AS vwap, COUNT(*) AS trades FROM trades SAMPLE BY 1m";
+        assert_eq!(views.create(ViewKind::MaterializedView, "vwap_1m", RefreshMode::Incremental, sql, false).await.unwrap(), 1);
+        let mut deltas = views.subscribe("vwap_1m").unwrap();
+
+        catalog.insert_rows(storage.as_ref(), "trades", &[
+            trade(2, "AAPL", 20.0, 300, 0),
+            trade(3, "AAPL", 30.0, 100, 1),
+        ]).await.unwrap();
+
+        let rows = catalog.scan_rows(storage.as_ref(), "vwap_1m").await.unwrap();
+        assert_eq!(rows.len(), 2);
+        // 存储键按时间桶排序
+        assert_eq!(rows[0]["vwap"], Value::Float64(17.5));
+        assert_eq!(rows[0]["trades"], Value::Int64(2));
+        assert_eq!(rows[0]["ts"], Value::Timestamp(TimestampNs::from_nanos(0)));
+        assert_eq!(rows[1]["vwap"], Value::Float64(30.0));
+
+        let delta = deltas.try_recv().unwrap();
+        assert_eq!(delta.upserted.len(), 2);
+        assert!(delta.removed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_having_removes_groups_and_full_refresh() {
+        let (catalog, storage, views) = manager().await;
+        let sql = "SELECT symbol, MIN(price) AS low FROM trades GROUP BY symbol HAVING MIN(price) > 5";
+        views.create(ViewKind::ContinuousQuery, "lows", RefreshMode::Incremental, sql, false).await.unwrap();
+        let mut deltas = views.subscribe("lows").unwrap();
+
+        catalog.insert_rows(storage.as_ref(), "trades", &[trade(1, "MSFT", 9.0, 1, 0)]).await.unwrap();
+        assert_eq!(deltas.try_recv().unwrap().upserted[0]["low"], Value::Float64(9.0));
+        catalog.insert_rows(storage.as_ref(), "trades", &[trade(2, "MSFT", 4.0, 1, 0)]).await.unwrap();
+        let delta = deltas.try_recv().unwrap();
+        assert!(delta.upserted.is_empty());
+        assert_eq!(delta.removed[0]["low"], Value::Float64(9.0));
+        // 连续查询不持久化
+        assert!(catalog.get_table("lows").is_none());
+
+        // LIMIT只能全量刷新
+        let sorted = "SELECT symbol, price FROM trades LIMIT 10";
+        assert!(views.create(ViewKind::MaterializedView, "sorted", RefreshMode::Incremental, sorted, false).await.is_err());
+        assert_eq!(views.create(ViewKind::MaterializedView, "sorted", RefreshMode::Full, sorted, false).await.unwrap(), 2);
+        catalog.insert_rows(storage.as_ref(), "trades", &[trade(3, "IBM", 1.0, 1, 0)]).await.unwrap();
+        let rows = catalog.scan_rows(storage.as_ref(), "sorted").await.unwrap();
+        assert_eq!(rows.len(), 3);
+
+        views.drop_view(ViewKind::MaterializedView, "sorted", false).await.unwrap();
+        assert!(catalog.get_table("sorted").is_none());
+        assert!(views.drop_view(ViewKind::MaterializedView, "sorted", false).await.is_err());
+    }
+}
@@ -0,0 +1,787 @@
+//! Window functions: `OVER (PARTITION BY ... ORDER BY ... ROWS | RANGE ...)`
+
+use crate::{
+    aggregates::{aggregate_input, AggregateFunction},
+    expressions::{compare_values, value_as_decimal, value_as_f64, value_as_i64, value_as_timestamp, ExpressionEvaluator},
+    joins::{normalize_key_value, Row},
+    sorts::{SortOperations, SortOrder},
+    udf::FunctionRegistry,
+};
+use fdc_core::{error::{Error, Result}, types::{Price, Value}};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use sqlparser::ast::{
+    visit_expressions, DuplicateTreatment, Expr, Function, FunctionArg, FunctionArgExpr, FunctionArguments,
+    NamedWindowDefinition, NamedWindowExpr, WindowFrame, WindowFrameBound, WindowFrameUnits, WindowSpec,
+    WindowType,
+};
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::ops::ControlFlow;
+
+/// 窗口函数
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowFunction {
+    RowNumber,
+    Rank,
+    DenseRank,
+    PercentRank,
+    CumeDist,
+    Ntile,
+    Lag,
+    Lead,
+    FirstValue,
+    LastValue,
+    NthValue,
+    /// 在窗口帧上计算的普通聚合（滚动/累计聚合）
+    Aggregate(AggregateFunction),
+}
+
+impl WindowFunction {
+    /// 按SQL函数名查找窗口函数
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_uppercase().as_str() {
+            "ROW_NUMBER" => WindowFunction::RowNumber,
+            "RANK" => WindowFunction::Rank,
+            "DENSE_RANK" => WindowFunction::DenseRank,
+            "PERCENT_RANK" => WindowFunction::PercentRank,
+            "CUME_DIST" => WindowFunction::CumeDist,
+            "NTILE" => WindowFunction::Ntile,
+            "LAG" => WindowFunction::Lag,
+            "LEAD" => WindowFunction::Lead,
+            "FIRST_VALUE" => WindowFunction::FirstValue,
+            "LAST_VALUE" => WindowFunction::LastValue,
+            "NTH_VALUE" => WindowFunction::NthValue,
+            other => WindowFunction::Aggregate(AggregateFunction::from_name(other)?),
+        })
+    }
+
+    /// 按SQL函数名查找窗口函数，`functions`中的用户自定义聚合可作为窗口聚合使用
+    pub fn resolve(name: &str, functions: Option<&FunctionRegistry>) -> Option<Self> {
+        Self::from_name(name).or_else(|| AggregateFunction::resolve(name, functions).map(WindowFunction::Aggregate))
+    }
+}
+
+/// 查询中出现的一次窗口函数调用
+#[derive(Debug, Clone)]
+pub struct WindowCall {
+    /// 结果在行中的列名（即调用表达式文本）
+    pub key: String,
+    /// 窗口函数
+    pub function: WindowFunction,
+    /// 参数（`COUNT(*)`为空）
+    pub args: Vec<Expr>,
+    /// 解析命名窗口后的窗口定义
+    pub spec: WindowSpec,
+}
+
+/// 收集表达式中的窗口函数调用，按出现顺序去重；`named`为`WINDOW`子句中的命名窗口
+pub fn collect_window_calls<'a>(
+    exprs: impl IntoIterator<Item = &'a Expr>,
+    named: &[NamedWindowDefinition],
+) -> Result<Vec<WindowCall>> {
+    collect_window_calls_with(exprs, named, None)
+}
+
+/// 收集表达式中的窗口函数调用，`functions`中注册的用户自定义聚合也会被识别
+pub fn collect_window_calls_with<'a>(
+    exprs: impl IntoIterator<Item = &'a Expr>,
+    named: &[NamedWindowDefinition],
+    functions: Option<&FunctionRegistry>,
+) -> Result<Vec<WindowCall>> {
+    let mut calls: Vec<WindowCall> = Vec::new();
+    let mut error = None;
+    for expr in exprs {
+        let _ = visit_expressions(expr, |e| {
+            let Expr::Function(function) = e else {
+                return ControlFlow::Continue(());
+            };
+            let Some(over) = &function.over else {
+                return ControlFlow::Continue(());
+            };
+            let key = e.to_string();
+            if calls.iter().any(|c| c.key == key) {
+                return ControlFlow::Continue(());
+            }
+            match window_call(key, function, over, named, functions) {
+                Ok(call) => {
+                    calls.push(call);
+                    ControlFlow::Continue(())
+                }
+                Err(e) => {
+                    error = Some(e);
+                    ControlFlow::Break(())
+                }
+            }
+        });
+        if let Some(error) = error.take() {
+            return Err(error);
+        }
+    }
+    Ok(calls)
+}
+
+fn window_call(
+    key: String,
+    function: &Function,
+    over: &WindowType,
+    named: &[NamedWindowDefinition],
+    functions: Option<&FunctionRegistry>,
+) -> Result<WindowCall> {
+    let name = function.name.to_string();
+    let window_function = WindowFunction::resolve(&name, functions)
+        .ok_or_else(|| Error::validation(format!("{} is not a window function", name)))?;
+    let args = match &function.args {
+        FunctionArguments::None => Vec::new(),
+        FunctionArguments::List(list) => {
+            if list.duplicate_treatment == Some(DuplicateTreatment::Distinct) {
+                return Err(Error::unimplemented("DISTINCT in window aggregates"));
+            }
+            list.args.iter().filter_map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Wildcard) => None,
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(e)) => Some(Ok(e.clone())),
+                other => Some(Err(Error::unimplemented(format!("Window function argument not supported: {}", other)))),
+            }).collect::<Result<Vec<_>>>()?
+        }
+        FunctionArguments::Subquery(_) => return Err(Error::unimplemented("Subquery window function arguments")),
+    };
+    Ok(WindowCall { key, function: window_function, args, spec: resolve_window(over, named, 0)? })
+}
+
+/// 解析命名窗口引用（`OVER w`、`OVER (w ORDER BY ...)`）
+fn resolve_window(over: &WindowType, named: &[NamedWindowDefinition], depth: usize) -> Result<WindowSpec> {
+    if depth > named.len() {
+        return Err(Error::validation("Circular named window definition"));
+    }
+    let lookup = |name: &sqlparser::ast::Ident| {
+        named.iter().find(|d| d.0.value.eq_ignore_ascii_case(&name.value))
+            .ok_or_else(|| Error::validation(format!("Window {} is not defined", name)))
+    };
+    match over {
+        WindowType::WindowSpec(spec) => match &spec.window_name {
+            None => Ok(spec.clone()),
+            Some(name) => {
+                // 在基础窗口上追加ORDER BY与帧
+                let base = match &lookup(name)?.1 {
+                    NamedWindowExpr::WindowSpec(base) => resolve_window(&WindowType::WindowSpec(base.clone()), named, depth + 1)?,
+                    NamedWindowExpr::NamedWindow(other) => resolve_window(&WindowType::NamedWindow(other.clone()), named, depth + 1)?,
+                };
+                Ok(WindowSpec {
+                    window_name: None,
+                    partition_by: base.partition_by,
+                    order_by: if spec.order_by.is_empty() { base.order_by } else { spec.order_by.clone() },
+                    window_frame: spec.window_frame.clone().or(base.window_frame),
+                })
+            }
+        },
+        WindowType::NamedWindow(name) => match &lookup(name)?.1 {
+            NamedWindowExpr::WindowSpec(spec) => resolve_window(&WindowType::WindowSpec(spec.clone()), named, depth + 1),
+            NamedWindowExpr::NamedWindow(other) => resolve_window(&WindowType::NamedWindow(other.clone()), named, depth + 1),
+        },
+    }
+}
+
+/// 计算窗口函数，结果以调用表达式文本为列名写入每一行
+///
+/// 行的顺序保持不变；每个调用独立地按PARTITION BY分区、按窗口ORDER BY排序。
+pub fn apply_window_functions(rows: &mut [Row], calls: &[WindowCall], evaluator: &ExpressionEvaluator) -> Result<()> {
+    for call in calls {
+        let results = evaluate_call(rows, call, evaluator)?;
+        for (row, value) in rows.iter_mut().zip(results) {
+            row.insert(call.key.clone(), value);
+        }
+    }
+    Ok(())
+}
+
+fn evaluate_call(rows: &[Row], call: &WindowCall, evaluator: &ExpressionEvaluator) -> Result<Vec<Value>> {
+    let spec = &call.spec;
+    let orders: Vec<SortOrder> = spec.order_by.iter().map(SortOrder::from_order_by).collect();
+
+    // 分区（按首次出现顺序）
+    let mut partitions: Vec<Vec<usize>> = Vec::new();
+    let mut index: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        evaluator.checkpoint()?;
+        let key = spec.partition_by.iter()
+            .map(|e| evaluator.evaluate(e, row).map(|v| normalize_key_value(&v)))
+            .collect::<Result<Vec<_>>>()?;
+        let position = *index.entry(key).or_insert_with(|| {
+            partitions.push(Vec::new());
+            partitions.len() - 1
+        });
+        partitions[position].push(i);
+    }
+
+    let order_keys = rows.iter()
+        .map(|row| spec.order_by.iter().map(|o| evaluator.evaluate(&o.expr, row)).collect::<Result<Vec<_>>>())
+        .collect::<Result<Vec<_>>>()?;
+    let arguments = rows.iter()
+        .map(|row| call.args.iter().map(|a| evaluator.evaluate(a, row)).collect::<Result<Vec<_>>>())
+        .collect::<Result<Vec<_>>>()?;
+
+    let frame = FrameSpec::new(spec.window_frame.as_ref(), !spec.order_by.is_empty(), evaluator)?;
+    let mut results = vec![Value::Null; rows.len()];
+    for mut partition in partitions {
+        evaluator.checkpoint()?;
+        partition.sort_by(|&a, &b| SortOperations::compare_keys(&order_keys[a], &order_keys[b], &orders));
+        let keys: Vec<&[Value]> = partition.iter().map(|&i| order_keys[i].as_slice()).collect();
+        let args: Vec<&[Value]> = partition.iter().map(|&i| arguments[i].as_slice()).collect();
+        let values = evaluate_partition(&call.function, &keys, &args, &orders, &frame)?;
+        for (i, value) in partition.into_iter().zip(values) {
+            results[i] = value;
+        }
+    }
+    Ok(results)
+}
+
+/// 帧边界
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    UnboundedPreceding,
+    Preceding(f64),
+    CurrentRow,
+    Following(f64),
+    UnboundedFollowing,
+}
+
+/// 解析后的窗口帧
+#[derive(Debug, Clone, Copy)]
+struct FrameSpec {
+    range: bool,
+    start: Bound,
+    end: Bound,
+}
+
+impl FrameSpec {
+    /// 没有显式帧时：有ORDER BY为`RANGE UNBOUNDED PRECEDING AND CURRENT ROW`，否则为整个分区
+    fn new(frame: Option<&WindowFrame>, ordered: bool, evaluator: &ExpressionEvaluator) -> Result<Self> {
+        let Some(frame) = frame else {
+            return Ok(Self {
+                range: true,
+                start: Bound::UnboundedPreceding,
+                end: if ordered { Bound::CurrentRow } else { Bound::UnboundedFollowing },
+            });
+        };
+        let range = match frame.units {
+            WindowFrameUnits::Rows => false,
+            WindowFrameUnits::Range => true,
+            WindowFrameUnits::Groups => return Err(Error::unimplemented("GROUPS window frames")),
+        };
+        let bound = |bound: &WindowFrameBound| -> Result<Bound> {
+            let offset = |expr: &Expr| -> Result<f64> {
+                let value = evaluator.evaluate(expr, &HashMap::new())?;
+                let offset = if range { value_as_f64(&value) } else { value_as_i64(&value).map(|v| v as f64) };
+                match offset {
+                    Some(offset) if offset >= 0.0 => Ok(offset),
+                    _ => Err(Error::validation(format!("Invalid window frame offset: {}", expr))),
+                }
+            };
+            Ok(match bound {
+                WindowFrameBound::CurrentRow => Bound::CurrentRow,
+                WindowFrameBound::Preceding(None) => Bound::UnboundedPreceding,
+                WindowFrameBound::Preceding(Some(e)) => Bound::Preceding(offset(e)?),
+                WindowFrameBound::Following(None) => Bound::UnboundedFollowing,
+                WindowFrameBound::Following(Some(e)) => Bound::Following(offset(e)?),
+            })
+        };
+        let start = bound(&frame.start_bound)?;
+        let end = frame.end_bound.as_ref().map(bound).transpose()?.unwrap_or(Bound::CurrentRow);
+        if matches!(start, Bound::UnboundedFollowing) || matches!(end, Bound::UnboundedPreceding) {
+            return Err(Error::validation("Invalid window frame bounds"));
+        }
+        Ok(Self { range, start, end })
+    }
+
+    /// 第`i`行的帧`[start, end)`
+    fn bounds(&self, i: usize, keys: &[&[Value]], orders: &[SortOrder], peers: &[(usize, usize)]) -> Result<(usize, usize)> {
+        let len = keys.len();
+        if !self.range {
+            let start = match self.start {
+                Bound::UnboundedPreceding => 0,
+                Bound::Preceding(n) => i.saturating_sub(n as usize),
+                Bound::CurrentRow => i,
+                Bound::Following(n) => (i + n as usize).min(len),
+                Bound::UnboundedFollowing => len,
+            };
+            let end = match self.end {
+                Bound::UnboundedPreceding => 0,
+                Bound::Preceding(n) => (i + 1).saturating_sub(n as usize),
+                Bound::CurrentRow => i + 1,
+                Bound::Following(n) => (i + 1 + n as usize).min(len),
+                Bound::UnboundedFollowing => len,
+            };
+            return Ok((start, end.max(start)));
+        }
+
+        let has_offset = matches!(self.start, Bound::Preceding(_) | Bound::Following(_))
+            || matches!(self.end, Bound::Preceding(_) | Bound::Following(_));
+        if has_offset && orders.len() != 1 {
+            return Err(Error::validation("RANGE frames with an offset require exactly one ORDER BY column"));
+        }
+        let (peer_start, peer_end) = peers[i];
+        let current = keys[i].first();
+        // NULL排序键只与NULL同组
+        let null_current = !matches!(current, Some(v) if !matches!(v, Value::Null));
+
+        // 相对当前行的有向距离（升序为正方向），随分区位置单调不减
+        let distance = |j: usize| -> f64 {
+            let order = orders[0];
+            match (current, keys[j].first()) {
+                (Some(c), Some(v)) if !matches!(v, Value::Null) => {
+                    let diff = key_difference(v, c).unwrap_or(0.0);
+                    if order.ascending { diff } else { -diff }
+                }
+                _ => if order.nulls_first { f64::NEG_INFINITY } else { f64::INFINITY },
+            }
+        };
+        // 二分查找第一个距离达到边界的位置
+        let first_at_least = |bound: f64, inclusive: bool| {
+            let (mut lo, mut hi) = (0, len);
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                let d = distance(mid);
+                if (inclusive && d < bound) || (!inclusive && d <= bound) {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            lo
+        };
+
+        let start = match self.start {
+            Bound::UnboundedPreceding => 0,
+            Bound::CurrentRow => peer_start,
+            Bound::UnboundedFollowing => len,
+            _ if null_current => peer_start,
+            Bound::Preceding(n) => first_at_least(-n, true),
+            Bound::Following(n) => first_at_least(n, true),
+        };
+        let end = match self.end {
+            Bound::UnboundedFollowing => len,
+            Bound::CurrentRow => peer_end,
+            Bound::UnboundedPreceding => 0,
+            _ if null_current => peer_end,
+            Bound::Preceding(n) => first_at_least(-n, false),
+            Bound::Following(n) => first_at_least(n, false),
+        };
+        Ok((start, end.max(start)))
+    }
+}
+
+/// 排序键之差（时间戳按纳秒）
+fn key_difference(a: &Value, b: &Value) -> Option<f64> {
+    match (a, b) {
+        (Value::Timestamp(_), _) | (_, Value::Timestamp(_)) => {
+            Some((value_as_timestamp(a)?.as_nanos() as i128 - value_as_timestamp(b)?.as_nanos() as i128) as f64)
+        }
+        _ => match (value_as_i64(a), value_as_i64(b), a, b) {
+            (Some(x), Some(y), Value::Int8(_) | Value::Int16(_) | Value::Int32(_) | Value::Int64(_),
+                Value::Int8(_) | Value::Int16(_) | Value::Int32(_) | Value::Int64(_)) => Some((x as i128 - y as i128) as f64),
+            _ => Some(value_as_f64(a)? - value_as_f64(b)?),
+        },
+    }
+}
+
+/// 对一个已排序分区计算窗口函数
+fn evaluate_partition(
+    function: &WindowFunction,
+    keys: &[&[Value]],
+    args: &[&[Value]],
+    orders: &[SortOrder],
+    frame: &FrameSpec,
+) -> Result<Vec<Value>> {
+    let len = keys.len();
+
+    // 对等行（排序键相同）的范围
+    let mut peers = vec![(0, 0); len];
+    let mut start = 0;
+    for i in 1..=len {
+        if i == len || SortOperations::compare_keys(keys[i], keys[start], orders) != Ordering::Equal {
+            for peer in &mut peers[start..i] {
+                *peer = (start, i);
+            }
+            start = i;
+        }
+    }
+
+    let arg = |i: usize, n: usize| args[i].get(n).cloned().unwrap_or(Value::Null);
+    let constant = |n: usize, name: &str, default: i64| -> Result<i64> {
+        match args.first().and_then(|a| a.get(n)) {
+            None => Ok(default),
+            Some(v) => value_as_i64(v).ok_or_else(|| Error::validation(format!("{} requires an integer argument", name))),
+        }
+    };
+
+    let mut results = Vec::with_capacity(len);
+    match function {
+        WindowFunction::RowNumber => results.extend((1..=len).map(|n| Value::Int64(n as i64))),
+        WindowFunction::Rank => results.extend(peers.iter().map(|(s, _)| Value::Int64(*s as i64 + 1))),
+        WindowFunction::DenseRank => {
+            let mut rank = 0;
+            for (i, (s, _)) in peers.iter().enumerate() {
+                if *s == i {
+                    rank += 1;
+                }
+                results.push(Value::Int64(rank));
+            }
+        }
+        WindowFunction::PercentRank => results.extend(peers.iter().map(|(s, _)| {
+            Value::Float64(if len > 1 { *s as f64 / (len - 1) as f64 } else { 0.0 })
+        })),
+        WindowFunction::CumeDist => results.extend(peers.iter().map(|(_, e)| Value::Float64(*e as f64 / len as f64))),
+        WindowFunction::Ntile => {
+            let buckets = constant(0, "NTILE", 0)?;
+            if buckets <= 0 {
+                return Err(Error::validation("NTILE requires a positive bucket count"));
+            }
+            let buckets = buckets as usize;
+            // 前`len % buckets`个桶多分一行
+            let (size, remainder) = (len / buckets, len % buckets);
+            let large = remainder * (size + 1);
+            results.extend((0..len).map(|i| {
+                let bucket = if i < large { i / (size + 1) } else { remainder + (i - large) / size.max(1) };
+                Value::Int64(bucket as i64 + 1)
+            }));
+        }
+        WindowFunction::Lag | WindowFunction::Lead => {
+            let offset = constant(1, "LAG/LEAD offset", 1)?;
+            let offset = if *function == WindowFunction::Lag { -offset } else { offset };
+            for i in 0..len {
+                let target = i as i64 + offset;
+                results.push(if (0..len as i64).contains(&target) {
+                    arg(target as usize, 0)
+                } else {
+                    arg(i, 2)
+                });
+            }
+        }
+        WindowFunction::FirstValue | WindowFunction::LastValue | WindowFunction::NthValue => {
+            let nth = if *function == WindowFunction::NthValue { constant(1, "NTH_VALUE", 1)? } else { 1 };
+            if nth <= 0 {
+                return Err(Error::validation("NTH_VALUE requires a positive position"));
+            }
+            for i in 0..len {
+                let (start, end) = frame.bounds(i, keys, orders, &peers)?;
+                let position = match function {
+                    WindowFunction::LastValue => end.checked_sub(1).filter(|p| *p >= start),
+                    _ => Some(start + nth as usize - 1).filter(|p| *p < end),
+                };
+                results.push(position.map(|p| arg(p, 0)).unwrap_or(Value::Null));
+            }
+        }
+        WindowFunction::Aggregate(aggregate) => {
+            // COUNT(*)统计所有行，其余聚合只统计非NULL值；多参数聚合的输入为参数数组
+            let inputs: Vec<Value> = args.iter().map(|a| aggregate_input(a.to_vec())).collect();
+            let mut prefix_count = vec![0usize; len + 1];
+            for i in 0..len {
+                prefix_count[i + 1] = prefix_count[i] + !matches!(inputs[i], Value::Null) as usize;
+            }
+            let mut sliding = SlidingFrame::new(aggregate, &inputs);
+            for i in 0..len {
+                let (start, end) = frame.bounds(i, keys, orders, &peers)?;
+                let count = prefix_count[end] - prefix_count[start];
+                let value = match aggregate {
+                    AggregateFunction::Count => Value::Int64(count as i64),
+                    _ if count == 0 => Value::Null,
+                    AggregateFunction::Sum | AggregateFunction::Avg | AggregateFunction::Min | AggregateFunction::Max => {
+                        sliding.slide(start, end)?;
+                        sliding.value(count)
+                    }
+                    other => {
+                        let values: Vec<Value> = inputs[start..end].iter().filter(|v| !matches!(v, Value::Null)).cloned().collect();
+                        other.apply(&values)?
+                    }
+                };
+                results.push(value);
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// 在窗口帧上增量计算SUM/AVG/MIN/MAX
+///
+/// 帧边界随行单调前移时，SUM/AVG加上进入帧的值、减去离开帧的值，MIN/MAX维护单调队列，
+/// 每行摊还O(1)；帧边界后退时从新的起点重建。
+struct SlidingFrame<'a> {
+    function: &'a AggregateFunction,
+    inputs: &'a [Value],
+    /// 当前已累加的位置范围[start, end)
+    range: (usize, usize),
+    sum: RunningSum,
+    /// MIN/MAX的候选位置，对应的值单调（MIN递增、MAX递减）
+    candidates: VecDeque<usize>,
+}
+
+impl<'a> SlidingFrame<'a> {
+    fn new(function: &'a AggregateFunction, inputs: &'a [Value]) -> Self {
+        Self { function, inputs, range: (0, 0), sum: RunningSum::default(), candidates: VecDeque::new() }
+    }
+
+    /// 把帧移动到[start, end)
+    fn slide(&mut self, start: usize, end: usize) -> Result<()> {
+        let (current_start, current_end) = self.range;
+        if start < current_start || end < current_end || start > current_end {
+            self.sum = RunningSum::default();
+            self.candidates.clear();
+            self.range = (start, start);
+        }
+        while self.range.1 < end {
+            self.enter(self.range.1)?;
+            self.range.1 += 1;
+        }
+        while self.range.0 < start {
+            self.leave(self.range.0)?;
+            self.range.0 += 1;
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, index: usize) -> Result<()> {
+        let value = &self.inputs[index];
+        if matches!(value, Value::Null) {
+            return Ok(());
+        }
+        match self.function {
+            AggregateFunction::Min | AggregateFunction::Max => {
+                // 队尾比新值更差的候选不会再成为帧内极值
+                let worse = if *self.function == AggregateFunction::Min { Ordering::Greater } else { Ordering::Less };
+                while let Some(&back) = self.candidates.back() {
+                    if compare_values(&self.inputs[back], value) != Some(worse) {
+                        break;
+                    }
+                    self.candidates.pop_back();
+                }
+                self.candidates.push_back(index);
+                Ok(())
+            }
+            function => self.sum.add(value, function),
+        }
+    }
+
+    fn leave(&mut self, index: usize) -> Result<()> {
+        let value = &self.inputs[index];
+        if matches!(value, Value::Null) {
+            return Ok(());
+        }
+        match self.function {
+            AggregateFunction::Min | AggregateFunction::Max => {
+                if self.candidates.front() == Some(&index) {
+                    self.candidates.pop_front();
+                }
+                Ok(())
+            }
+            function => self.sum.subtract(value, function),
+        }
+    }
+
+    /// 帧内`count`个非NULL值的聚合结果
+    fn value(&self, count: usize) -> Value {
+        match self.function {
+            AggregateFunction::Sum => self.sum.total(),
+            AggregateFunction::Avg => self.sum.mean(count),
+            _ => self.candidates.front().map(|&index| self.inputs[index].clone()).unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// 可增减的累加和
+///
+/// 整数按i128、`Decimal`与`Price`按`Decimal`分别精确累加，结果保持输入的数值类型
+/// （全部为`Price`时返回`Price`）；帧内有浮点值时按f64返回。
+#[derive(Debug, Default)]
+struct RunningSum {
+    integer: i128,
+    decimal: Decimal,
+    float: f64,
+    /// 帧内浮点值、Decimal/Price值、Price值与全部值的个数
+    floats: usize,
+    decimals: usize,
+    prices: usize,
+    values: usize,
+}
+
+impl RunningSum {
+    fn add(&mut self, value: &Value, function: &AggregateFunction) -> Result<()> {
+        self.apply(value, function, 1)
+    }
+
+    fn subtract(&mut self, value: &Value, function: &AggregateFunction) -> Result<()> {
+        self.apply(value, function, -1)
+    }
+
+    fn apply(&mut self, value: &Value, function: &AggregateFunction, sign: i8) -> Result<()> {
+        let invalid = || Error::validation(format!("{:?} requires numeric values", function));
+        let step = |count: &mut usize| {
+            if sign > 0 { *count += 1 } else { *count -= 1 }
+        };
+        match value {
+            Value::Float32(_) | Value::Float64(_) => {
+                self.float += f64::from(sign) * value_as_f64(value).ok_or_else(invalid)?;
+                step(&mut self.floats);
+            }
+            Value::Decimal(_) | Value::Price(_) => {
+                let decimal = value_as_decimal(value).ok_or_else(invalid)?;
+                let decimal = if sign > 0 { self.decimal.checked_add(decimal) } else { self.decimal.checked_sub(decimal) };
+                self.decimal = decimal.ok_or_else(|| Error::validation(format!("{:?} overflowed the decimal range", function)))?;
+                step(&mut self.decimals);
+                if matches!(value, Value::Price(_)) {
+                    step(&mut self.prices);
+                }
+            }
+            other => {
+                let integer = value_as_i128(other).ok_or_else(invalid)?;
+                self.integer += i128::from(sign) * integer;
+            }
+        }
+        step(&mut self.values);
+        Ok(())
+    }
+
+    /// 精确部分之和，没有浮点值且不溢出时可用
+    fn exact(&self) -> Option<Decimal> {
+        if self.floats > 0 {
+            return None;
+        }
+        Decimal::try_from_i128_with_scale(self.integer, 0).ok()?.checked_add(self.decimal)
+    }
+
+    fn approx(&self) -> f64 {
+        self.float + self.integer as f64 + self.decimal.to_f64().unwrap_or(f64::NAN)
+    }
+
+    fn total(&self) -> Value {
+        if self.floats == 0 && self.decimals == 0 {
+            if let Ok(integer) = i64::try_from(self.integer) {
+                return Value::Int64(integer);
+            }
+        }
+        match self.exact() {
+            Some(sum) => self.exact_value(sum),
+            None => Value::Float64(self.approx()),
+        }
+    }
+
+    fn mean(&self, count: usize) -> Value {
+        // 整数的平均值与普通AVG一致按f64返回
+        if self.decimals > 0 {
+            if let Some(mean) = self.exact().and_then(|sum| sum.checked_div(Decimal::from(count))) {
+                return self.exact_value(mean.normalize());
+            }
+        }
+        Value::Float64(self.approx() / count as f64)
+    }
+
+    fn exact_value(&self, decimal: Decimal) -> Value {
+        if self.prices == self.values {
+            Value::Price(Price::new(decimal))
+        } else {
+            Value::Decimal(decimal)
+        }
+    }
+}
+
+/// 整数类值转换为i128
+fn value_as_i128(value: &Value) -> Option<i128> {
+    match value {
+        Value::Int128(v) => Some(*v),
+        Value::UInt64(v) => Some(*v as i128),
+        Value::Volume(v) => Some(v.as_u64() as i128),
+        other => value_as_i64(other).map(i128::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdc_core::types::TimestampNs;
+    use sqlparser::{dialect::GenericDialect, parser::Parser};
+
+    fn calls(sql: &str) -> Vec<WindowCall> {
+        let exprs = Parser::new(&GenericDialect {})
+            .try_with_sql(sql).unwrap()
+            .parse_comma_separated(Parser::parse_expr).unwrap();
+        collect_window_calls(&exprs, &[]).unwrap()
+    }
+
+    fn rows() -> Vec<Row> {
+        [("A", 0, 10.0), ("A", 1, 11.0), ("A", 2, 11.0), ("A", 10, 14.0), ("B", 0, 20.0), ("B", 1, 18.0)]
+            .iter()
+            .map(|(symbol, minute, px)| HashMap::from([
+                ("symbol".to_string(), Value::String(symbol.to_string())),
+                ("ts".to_string(), Value::Timestamp(TimestampNs::from_nanos(minute * 60_000_000_000))),
+                ("px".to_string(), Value::Float64(*px)),
+            ]))
+            .collect()
+    }
+
+    fn column(rows: &[Row], key: &str) -> Vec<Value> {
+        rows.iter().map(|r| r[key].clone()).collect()
+    }
+
+    #[test]
+    fn test_ranking_and_offsets() {
+        let calls = calls(
+            "ROW_NUMBER() OVER (PARTITION BY symbol ORDER BY ts), \
+             RANK() OVER (ORDER BY px), \
+             LAG(px) OVER (PARTITION BY symbol ORDER BY ts), \
+             LEAD(px, 1, 0) OVER (PARTITION BY symbol ORDER BY ts)",
+        );
+        let mut rows = rows();
+        apply_window_functions(&mut rows, &calls, &ExpressionEvaluator::new()).unwrap();
+
+        let ints = |v: &[i64]| v.iter().map(|i| Value::Int64(*i)).collect::<Vec<_>>();
+        assert_eq!(column(&rows, &calls[0].key), ints(&[1, 2, 3, 4, 1, 2]));
+        assert_eq!(column(&rows, &calls[1].key), ints(&[1, 2, 2, 4, 6, 5]));
+        assert_eq!(column(&rows, &calls[2].key)[..2], [Value::Null, Value::Float64(10.0)]);
+        assert_eq!(column(&rows, &calls[3].key)[3], Value::Int64(0));
+    }
+
+    #[test]
+    fn test_rows_and_time_range_frames() {
+        let calls = calls(
+            "AVG(px) OVER (PARTITION BY symbol ORDER BY ts ROWS BETWEEN 1 PRECEDING AND CURRENT ROW), \
+             SUM(px) OVER (PARTITION BY symbol ORDER BY ts), \
+             COUNT(*) OVER (PARTITION BY symbol ORDER BY ts RANGE BETWEEN INTERVAL '5 minutes' PRECEDING AND CURRENT ROW), \
+             LAST_VALUE(px) OVER (PARTITION BY symbol ORDER BY ts ROWS BETWEEN CURRENT ROW AND UNBOUNDED FOLLOWING)",
+        );
+        let mut rows = rows();
+        apply_window_functions(&mut rows, &calls, &ExpressionEvaluator::new()).unwrap();
+
+        assert_eq!(column(&rows, &calls[0].key)[..4], [10.0, 10.5, 11.0, 12.5].map(Value::Float64));
+        assert_eq!(column(&rows, &calls[1].key)[..4], [10.0, 21.0, 32.0, 46.0].map(Value::Float64));
+        assert_eq!(column(&rows, &calls[2].key)[..4], [1, 2, 3, 1].map(Value::Int64));
+        assert_eq!(column(&rows, &calls[3].key)[0], Value::Float64(14.0));
+    }
+
+    #[test]
+    fn test_sliding_aggregates_keep_native_types() {
+        let price = |text: &str| Value::Price(Price::new(text.parse().unwrap()));
+        let mut rows: Vec<Row> = [("0.1", 5), ("0.2", 3), ("0.3", 8), ("0.7", 1), ("0.1", 4)]
+            .iter()
+            .enumerate()
+            .map(|(i, (px, qty))| HashMap::from([
+                ("seq".to_string(), Value::Int64(i as i64)),
+                ("px".to_string(), price(px)),
+                ("qty".to_string(), Value::Int64(*qty)),
+            ]))
+            .collect();
+        let calls = calls(
+            "SUM(px) OVER (ORDER BY seq ROWS BETWEEN 2 PRECEDING AND CURRENT ROW), \
+             AVG(px) OVER (ORDER BY seq ROWS BETWEEN 1 PRECEDING AND CURRENT ROW), \
+             SUM(qty) OVER (ORDER BY seq ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING), \
+             MIN(qty) OVER (ORDER BY seq ROWS BETWEEN 2 PRECEDING AND CURRENT ROW), \
+             MAX(qty) OVER (ORDER BY seq ROWS BETWEEN CURRENT ROW AND 1 FOLLOWING)",
+        );
+        apply_window_functions(&mut rows, &calls, &ExpressionEvaluator::new()).unwrap();
+
+        // 价格按Decimal精确增减，不经过f64
+        assert_eq!(column(&rows, &calls[0].key), ["0.1", "0.3", "0.6", "1.2", "1.1"].map(price));
+        assert_eq!(column(&rows, &calls[1].key), ["0.1", "0.15", "0.25", "0.5", "0.4"].map(price));
+        let ints = |v: [i64; 5]| v.map(Value::Int64);
+        assert_eq!(column(&rows, &calls[2].key), ints([8, 16, 12, 13, 5]));
+        assert_eq!(column(&rows, &calls[3].key), ints([5, 3, 3, 1, 1]));
+        assert_eq!(column(&rows, &calls[4].key), ints([5, 8, 8, 4, 4]));
+    }
+}
@@ -4,14 +4,15 @@ use crate::engine::{StorageEngine, StorageEngineType, EngineCapabilities, Storag
 use fdc_core::{error::{Error, Result}, types::Value};
 use async_trait::async_trait;
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
 use std::sync::Arc;
 use std::time::Instant;
 
 /// 内存存储引擎
 pub struct MemoryEngine {
-    /// 数据存储
-    data: Arc<RwLock<HashMap<Vec<u8>, Vec<u8>>>>,
+    /// 数据存储（按键有序，范围扫描只访问范围内的键）
+    data: Arc<RwLock<BTreeMap<Vec<u8>, Vec<u8>>>>,
     /// 统计信息
     stats: Arc<RwLock<StorageStats>>,
     /// 最大大小
@@ -27,7 +28,7 @@ impl MemoryEngine {
             .and_then(|s| s.parse().ok());
         
         Ok(Self {
-            data: Arc::new(RwLock::new(HashMap::new())),
+            data: Arc::new(RwLock::new(BTreeMap::new())),
             stats: Arc::new(RwLock::new(StorageStats::default())),
             max_size,
             current_size: Arc::new(RwLock::new(0)),
@@ -187,33 +188,18 @@ impl StorageEngine for MemoryEngine {
         let start = Instant::now();
         
         let data = self.data.read();
-        let mut results = Vec::new();
-        
-        for (key, value) in data.iter() {
-            // 检查范围
-            if let Some(start_key) = start_key {
-                if key.as_slice() < start_key {
-                    continue;
-                }
-            }
-            if let Some(end_key) = end_key {
-                if key.as_slice() >= end_key {
-                    continue;
-                }
+        let results: Vec<_> = match (start_key, end_key) {
+            (Some(start_key), Some(end_key)) if start_key >= end_key => Vec::new(),
+            _ => {
+                let lower = start_key.map_or(Bound::Unbounded, Bound::Included);
+                let upper = end_key.map_or(Bound::Unbounded, Bound::Excluded);
+                // 按键顺序返回范围内最小的`limit`个键（分页扫描依赖这一点），只复制保留的键值
+                data.range::<[u8], _>((lower, upper))
+                    .take(limit.unwrap_or(usize::MAX))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect()
             }
-            
-            results.push((key.clone(), value.clone()));
-            
-            // 检查限制
-            if let Some(limit) = limit {
-                if results.len() >= limit {
-                    break;
-                }
-            }
-        }
-        
-        // 排序结果
-        results.sort_by(|a, b| a.0.cmp(&b.0));
+        };
         
         // 更新统计
         let latency_us = start.elapsed().as_micros() as u64;
@@ -315,6 +301,22 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_memory_engine_scan_range() {
+        let engine = MemoryEngine::new(HashMap::new()).await.unwrap();
+        for i in (0..20u8).rev() {
+            engine.put(&[b'k', i], &[i]).await.unwrap();
+        }
+        
+        let page = engine.scan(Some(&[b'k', 5]), Some(&[b'k', 15]), Some(3)).await.unwrap();
+        let keys: Vec<_> = page.iter().map(|(key, _)| key[1]).collect();
+        assert_eq!(keys, vec![5, 6, 7]);
+        
+        let rest = engine.scan(Some(&[b'k', 18]), None, None).await.unwrap();
+        assert_eq!(rest.len(), 2);
+        assert!(engine.scan(Some(&[b'k', 9]), Some(&[b'k', 3]), None).await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_memory_engine_stats() {
         let mut engine = MemoryEngine::new(HashMap::new()).await.unwrap();